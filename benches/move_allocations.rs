@@ -0,0 +1,105 @@
+//! Counts heap allocations of the 2-opt move operators, the old version collected
+//! the reversed segment into a temporary vector, now segments are reversed in place.
+//!
+//! usage: cargo bench --bench move_allocations
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use teeline::tsp::route::{reverse_segment, Route};
+
+const N_MOVES: usize = 100_000;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// the reversal used before, kept here for the comparison
+fn reverse_with_temporary_vec(route: &mut [usize], from: usize, to: usize) {
+    let reversed_seq: Vec<usize> = route[from..=to].iter().rev().cloned().collect();
+
+    for (i, swapped_val) in reversed_seq.iter().enumerate() {
+        route[from + i] = *swapped_val;
+    }
+}
+
+fn random_moves(n: usize) -> Vec<(usize, usize)> {
+    let mut rng = StdRng::seed_from_u64(2020);
+
+    (0..N_MOVES)
+        .map(|_| {
+            let a = rng.gen_range(0, n);
+            let b = rng.gen_range(0, n);
+            (a.min(b), a.max(b))
+        })
+        .collect()
+}
+
+// returns number of allocations and milliseconds spent
+fn measure(f: impl FnOnce()) -> (usize, f64) {
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let started = Instant::now();
+
+    f();
+
+    let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+    (
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations_before,
+        elapsed,
+    )
+}
+
+fn main() {
+    println!(
+        "{:>8} | {:>24} | {:>24} | {:>24}",
+        "n", "temporary vec", "in place", "route try & undo"
+    );
+
+    for &n in [100, 1_000, 10_000].iter() {
+        let moves = random_moves(n);
+        let mut path: Vec<usize> = (0..n).collect();
+        let mut route = Route::new(&path);
+
+        let old = measure(|| {
+            for &(from, to) in moves.iter() {
+                reverse_with_temporary_vec(&mut path, from, to);
+            }
+        });
+
+        let in_place = measure(|| {
+            for &(from, to) in moves.iter() {
+                reverse_segment(&mut path, from, to);
+            }
+        });
+
+        // what simulated annealing does for the rejected moves
+        let try_undo = measure(|| {
+            for &(from, to) in moves.iter() {
+                route.reverse(from, to).unwrap();
+                route.reverse(from, to).unwrap();
+            }
+        });
+
+        println!(
+            "{:>8} | {:>9} allocs {:>7.2}ms | {:>9} allocs {:>7.2}ms | {:>9} allocs {:>7.2}ms",
+            n, old.0, old.1, in_place.0, in_place.1, try_undo.0, try_undo.1
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Compares 2-opt reversals on the vector based tour against the two-level list.
+//!
+//! usage: cargo bench --bench tour_reversal
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use teeline::tsp::tour::{ArrayTour, Tour, TwoLevelTour, TWO_LEVEL_THRESHOLD};
+
+const N_MOVES: usize = 20_000;
+
+fn random_moves(n: usize) -> Vec<(usize, usize)> {
+    let mut rng = StdRng::seed_from_u64(2020);
+
+    (0..N_MOVES)
+        .map(|_| (rng.gen_range(0, n), rng.gen_range(0, n)))
+        .collect()
+}
+
+fn run_moves(tour: &mut dyn Tour, moves: &[(usize, usize)]) -> Duration {
+    let started = Instant::now();
+
+    for &(from, to) in moves.iter() {
+        tour.reverse(from, to);
+    }
+
+    started.elapsed()
+}
+
+fn main() {
+    println!("switch threshold: {} nodes", TWO_LEVEL_THRESHOLD);
+    println!("{:>10} | {:>14} | {:>14}", "n", "vec swaps", "two-level");
+
+    for &n in [100, 500, 1_000, 5_000, 10_000, 100_000].iter() {
+        let order: Vec<usize> = (0..n).collect();
+        let moves = random_moves(n);
+
+        let array_time = run_moves(&mut ArrayTour::new(&order), &moves);
+        let two_level_time = run_moves(&mut TwoLevelTour::new(&order), &moves);
+
+        println!(
+            "{:>10} | {:>12.2}ms | {:>12.2}ms",
+            n,
+            array_time.as_secs_f64() * 1000.0,
+            two_level_time.as_secs_f64() * 1000.0
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! Example of a dynamic solver plugin, which keeps the cities in the input order.
+//!
+//! build: cargo build --example input_order_plugin
+//! usage: teeline input_order --plugin ./target/debug/examples/libinput_order_plugin.so -i data.tsp
+//!
+//! the binary must be compiled with the `plugins` feature
+use teeline::tsp::kdtree::KDPoint;
+use teeline::tsp::plugin::PluginRegistrar;
+use teeline::tsp::progress::{ProgressMessage, PublisherFn};
+use teeline::tsp::registry::Category;
+use teeline::tsp::route::Route;
+use teeline::tsp::{validate_cities, Error, Solution, SolverInfo, SolverOptions, TspSolver};
+
+struct InputOrderSolver;
+
+impl TspSolver for InputOrderSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "input_order",
+            &[],
+            "keeps the cities in the input order",
+            false,
+        )
+        .with_category(Category::Constructive)
+        .with_complexity("O(n)")
+    }
+
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        _options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 1)?;
+        let route = Route::from_cities(cities);
+
+        progress(ProgressMessage::PathUpdate(route.clone(), 0.0));
+        progress(ProgressMessage::Done);
+        Ok(Solution::new(route.route(), cities))
+    }
+}
+
+fn register(registrar: &mut PluginRegistrar) {
+    registrar.register(Box::new(InputOrderSolver));
+}
+
+teeline::declare_plugin!(register);
@@ -1,2 +1,110 @@
+//! Teeline, solvers for the traveling salesman problem
+//!
+//! The library has no dependency on the command-line or on the progress window, the `gui`
+//! feature only adds `tsp::progress::ProgressPlot`. The binary is a thin layer over it.
+//!
+//! ```no_run
+//! use teeline::{tsp::tsplib, SolverOptions};
+//!
+//! let problem = tsplib::read_from_file(std::path::Path::new("berlin52.tsp")).unwrap();
+//! let mut options = SolverOptions::default();
+//! options.max_seconds = Some(5.0);
+//!
+//! let tour = teeline::solve(&problem, "nn+2opt", &options).unwrap();
+//! println!("{:.2}: {:?}", tour.total, tour.route());
+//! ```
+//!
+//! The options of a single solver can be given as a typed struct instead, so the compiler checks them:
+//!
+//! ```no_run
+//! use teeline::tsp::{simulated_annealing::SaOptions, tsplib};
+//! use teeline::{AlgorithmOptions, SolverOptions};
+//!
+//! let problem = tsplib::read_from_file(std::path::Path::new("berlin52.tsp")).unwrap();
+//! let annealing = AlgorithmOptions::SimulatedAnnealing(SaOptions {
+//!     cooling_rate: 0.001,
+//!     ..SaOptions::default()
+//! });
+//!
+//! let tour = teeline::solve_with(&problem, &annealing, &SolverOptions::default()).unwrap();
+//! ```
+//!
+//! The solvers are listed by `tsp::registry::catalog`, `tsp::progress` reports the progress of
+//! a running solver and the rest of the `tsp` modules read and write the problems and tours.
+#[cfg(test)]
 mod test;
 pub mod tsp;
+
+// the progress window needs a display, the browser build has only the callbacks of `tsp::wasm`
+#[cfg(all(target_arch = "wasm32", feature = "gui"))]
+compile_error!("the gui feature doesn't build for wasm32, build it with --no-default-features");
+
+pub use tsp::kdtree::KDPoint;
+pub use tsp::progress::PublisherFn;
+pub use tsp::tsplib::TspLibData;
+pub use tsp::{
+    AlgorithmOptions, Error, Solution, SolverOptions, TourDiff, TspSolver, ValidationError,
+};
+
+/// solves the problem with the solver of the name, alias or pipeline, e.g `2opt` or `nn+2opt`
+pub fn solve(
+    instance: &TspLibData,
+    solver: &str,
+    options: &SolverOptions,
+) -> Result<Solution, Error> {
+    solve_with_progress(instance, solver, options, tsp::progress::silent_publisher())
+}
+
+/// solves the problem like `solve` and sends the progress of the solver to the publisher,
+/// see `tsp::progress::publisher_for`
+pub fn solve_with_progress(
+    instance: &TspLibData,
+    solver: &str,
+    options: &SolverOptions,
+    progress: PublisherFn,
+) -> Result<Solution, Error> {
+    let solver = tsp::registry::resolve(solver)?;
+    let options = instance_options(instance, options);
+    options.validate(&solver.metadata())?;
+
+    solver
+        .solve(instance.cities(), &options.to_tour_options(), progress)?
+        .with_start_city(&options)
+}
+
+/// solves the problem with the solver of the typed options, they override the same options
+/// of the `options`
+pub fn solve_with(
+    instance: &TspLibData,
+    algorithm: &AlgorithmOptions,
+    options: &SolverOptions,
+) -> Result<Solution, Error> {
+    let solver = algorithm
+        .solver()
+        .solver()
+        .ok_or_else(|| Error::Runtime(format!("no solver for {:?}", algorithm)))?;
+    let options = instance_options(instance, options).with_algorithm(algorithm);
+    options.validate(&solver.metadata())?;
+
+    solver
+        .solve(
+            instance.cities(),
+            &options.to_tour_options(),
+            tsp::progress::silent_publisher(),
+        )?
+        .with_start_city(&options)
+}
+
+// the options with the fixed edges and the precedences of the instance, unless the options
+// have their own
+fn instance_options(instance: &TspLibData, options: &SolverOptions) -> SolverOptions {
+    let mut options = options.clone();
+    if options.fixed_edges.is_empty() {
+        options.fixed_edges = instance.fixed_edges().to_vec();
+    }
+    if options.precedences.is_empty() {
+        options.precedences = instance.precedences().to_vec();
+    }
+
+    options
+}
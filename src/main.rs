@@ -1,33 +1,51 @@
 extern crate clap;
 extern crate lazy_static;
-extern crate piston;
-extern crate piston_window;
 extern crate rand;
 extern crate regex;
 
-use clap::{App, Arg, ArgMatches};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
-use std::path::Path;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 use std::thread;
+use std::time::Instant;
 
-use teeline::tsp::{self, kdtree, progress, tsplib, Solution, SolverOptions, Solvers};
+use teeline::tsp::{
+    self, analysis, batch, bellman_karp, benchmark, city_filter, concorde, config, cvrp, generator,
+    grid, gtsp, improve, kdtree, lkh, manifest, neighbors, optima, output, pipeline, plot_script,
+    plugin, precedence, prize_collecting, progress,
+    progress::ProgressMode,
+    registry, remote, restart, route, selftest, serve, simulated_annealing, stochastic_hill, trace,
+    tsplib,
+    units::{Length, Units},
+    validate, CancelFlag, Solution, SolverInfo, SolverOptions,
+};
 
 fn main() {
+    let solver_names = registry::names();
+    let solver_help = format!(
+        "specify an algorithm to use: {}; or chain them with +, e.g nn+2opt",
+        solver_names.join(", ")
+    );
+
     //process command-line params
-    let args = App::new("Teeline")
+    let app = App::new("Teeline")
         .version(tsp::VERSION)
         .author(tsp::AUTHOR)
         .about("Solver for Traveling Salesman problem")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .arg(solver_arg(&solver_help, &solver_names))
         .arg(
-            Arg::with_name("solver")
-                .index(1)
-                .help("specify an algorithm to use")
-                .possible_values(&Solvers::variants())
-                .required(true)
-                .value_name("SOLVER_NAME")
-                .case_insensitive(true)
-                .takes_value(true),
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE_PATH")
+                .help("reads the solver, the input, the output and the solver options from the TOML file, the flags override its values")
+                .takes_value(true)
+                .required(false),
         )
         .arg(
             Arg::with_name("epochs")
@@ -43,10 +61,88 @@ fn main() {
                 .takes_value(true)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("hill_strategy")
+                .long("hill_strategy")
+                .alias("hill-strategy")
+                .value_name("STRATEGY")
+                .help("stochastic hill takes the first sampled move which shortens the tour or the best one of n_samples moves per epoch")
+                .possible_values(&stochastic_hill::HillStrategy::variants())
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("n_samples")
+                .long("n_samples")
+                .alias("n-samples")
+                .value_name("N")
+                .help("how many random moves the best strategy of stochastic hill compares per epoch, the number of cities by default")
+                .takes_value(true)
+                .required(false),
+        )
         .arg(
             Arg::with_name("n_nearest")
                 .long("n_nearest")
-                .help("specify how many nearest neighbors to look for")
+                .help("specify how many nearest neighbors to look for, 2-opt checks every pair of edges with 0")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("candidates")
+                .long("candidates")
+                .value_name("KIND")
+                .help("how 2-opt picks the cities to connect: the n_nearest cities, the Delaunay neighbors or the nearest cities in every quadrant")
+                .possible_values(&neighbors::Candidates::variants())
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("start_city")
+                .long("start_city")
+                .alias("start-city")
+                .help("the city id the tour starts from, the first city by default")
+                .value_name("CITY_ID")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("end_city")
+                .long("end_city")
+                .alias("end-city")
+                .help("the city id the path from --start_city ends at, the tour doesn't return to the start")
+                .value_name("CITY_ID")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("all_starts")
+                .long("all_starts")
+                .alias("all-starts")
+                .help("nearest neighbor builds the tour from every city and keeps the shortest one")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("open_tour")
+                .long("open_tour")
+                .alias("open-tour")
+                .help("the tour ends at its last city, its length has no edge back to the start")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("precedences")
+                .long("precedences")
+                .value_name("FILE_PATH")
+                .help("the file of the city id pairs, a pair per line; the first city of the pair is visited before the second one")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("spatial_index")
+                .long("spatial_index")
+                .alias("spatial-index")
+                .value_name("INDEX")
+                .help("the index nearest neighbor searches the cities with, the grid is faster to build for millions of cities")
+                .possible_values(&grid::SpatialIndex::variants())
                 .takes_value(true)
                 .required(false),
         )
@@ -57,6 +153,42 @@ fn main() {
                 .takes_value(true)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("population_size")
+                .long("population_size")
+                .alias("population-size")
+                .value_name("N")
+                .help("specify how many individuals each generation of the genetic algorithm has, default 100")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("n_offspring")
+                .long("n_offspring")
+                .alias("n-offspring")
+                .value_name("N")
+                .help("specify how many children each generation breeds, the fittest of them survive; by default they refill the population")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("n_islands")
+                .long("n_islands")
+                .alias("n-islands")
+                .value_name("N")
+                .help("the genetic algorithm evolves N populations on their own threads, which exchange their elites, default 1")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("migration_interval")
+                .long("migration_interval")
+                .alias("migration-interval")
+                .value_name("EPOCHS")
+                .help("how many epochs the islands evolve apart before the elites migrate to the next island, default 50")
+                .takes_value(true)
+                .required(false),
+        )
         .arg(
             Arg::with_name("mutation_probability")
                 .long("mutation_probability")
@@ -86,11 +218,79 @@ fn main() {
                 .required(false),
         )
         .arg(
-            Arg::with_name("input")
-                .long("input")
-                .short("i")
+            Arg::with_name("cooling_schedule")
+                .long("cooling_schedule")
+                .alias("cooling-schedule")
+                .value_name("SCHEDULE")
+                .help("specify how the temperature of the annealing decreases, the adaptive schedule follows the share of the accepted moves, the timed one spreads the cooling over --max_seconds")
+                .possible_values(&simulated_annealing::CoolingSchedule::variants())
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("neighborhood")
+                .long("neighborhood")
+                .value_name("MOVE")
+                .help("the moves simulated annealing and stochastic hill try: reverse the cities between 2 positions, swap 2 cities, insert a city elsewhere or a random one of them")
+                .possible_values(&route::Neighborhood::variants())
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("reheat")
+                .long("reheat")
+                .value_name("FRACTION")
+                .help("raises the temperature of the annealing back to the fraction of max_temperature after platoo_epochs without an accepted move")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("restart_from_best")
+                .long("restart_from_best")
+                .alias("restart-from-best")
+                .value_name("EPOCHS")
+                .help("the annealing continues from the best tour after EPOCHS epochs without finding a better one")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("no_aspiration")
+                .long("no_aspiration")
+                .alias("no-aspiration")
+                .help("tabu search rejects the tabu moves even when they find a new best tour")
+                .required(false),
+        )
+        .arg(
+            input_arg()
+                .help("filepath or http(s) URL of input file, must be in TSPLIB format; repeat it or use a glob pattern, e.g 'data/*.tsp', to solve several files")
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(stdin_arg())
+        .arg(
+            Arg::with_name("only_cities")
+                .long("only_cities")
+                .alias("only-cities")
+                .value_name("CITY_IDS")
+                .help("solves only the cities of the ids and the ranges of them, e.g 1,5,9-40")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("exclude_cities")
+                .long("exclude_cities")
+                .alias("exclude-cities")
+                .value_name("CITY_IDS")
+                .help("leaves out the cities of the ids and the ranges of them, e.g 1,5,9-40")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .short("o")
                 .value_name("FILE_PATH")
-                .help("filepath to input file, must be in TSPLIB format")
+                .help("writes the tour into the file instead of STDOUT, the format is inferred from the extension: .tour, .json, .csv or .svg")
                 .takes_value(true)
                 .required(false),
         )
@@ -107,147 +307,2131 @@ fn main() {
                 .help("Doesnt show any progress or visualization, default false")
                 .required(false),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("progress")
+                .long("progress")
+                .help("how to show the progress: window, status line in the terminal, JSON lines on STDERR or off")
+                .possible_values(&progress::ProgressMode::variants())
+                .value_name("MODE")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .help("seeds the random generator of the stochastic solvers, makes runs reproducible")
+                .value_name("N")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("target_cost")
+                .long("target_cost")
+                .alias("target-cost")
+                .help("stops the solver as soon as it finds a tour at or below the cost")
+                .value_name("COST")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("max_seconds")
+                .long("max_seconds")
+                .alias("max-seconds")
+                .help("stops the solver after the time budget and returns the best tour found so far")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("max_stale_epochs")
+                .long("max_stale_epochs")
+                .alias("max-stale-epochs")
+                .help("stops simulated annealing, tabu search, the genetic algorithm and 2-opt after N epochs without a new best tour; 2-opt counts the cities it checks")
+                .value_name("N")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("max_nodes")
+                .long("max_nodes")
+                .alias("max-nodes")
+                .help("stops branch and bound after expanding N nodes and returns the best tour found so far")
+                .value_name("N")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("max_memory")
+                .long("max_memory")
+                .alias("max-memory")
+                .help("the exact solvers refuse the problems which need more memory, default 2048")
+                .value_name("MB")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry_run")
+                .alias("dry-run")
+                .help("prints the memory and the time estimate of bellman_karp instead of solving the problem")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("restarts")
+                .long("restarts")
+                .help("re-runs the stochastic solver N times and keeps the best solution")
+                .value_name("N")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("repeats")
+                .long("repeats")
+                .help("runs the stochastic solver N times with different seeds, prints the spread of the cost and the time and keeps the best solution")
+                .value_name("N")
+                .takes_value(true)
+                .conflicts_with("restarts")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .help("runs independent starts of the stochastic solver on N threads, or solves N input files at once")
+                .value_name("N")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("analyze")
+                .long("analyze")
+                .help("prints how many edges of the tour cross each other")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("units")
+                .long("units")
+                .help("reports the tour length of geographic problems in the units")
+                .possible_values(&Units::variants())
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("export_plot_script")
+                .long("export_plot_script")
+                .alias("export-plot-script")
+                .help("writes the tour data and the script that plots it into the working directory")
+                .possible_values(&plot_script::PlotFormat::variants())
+                .value_name("FORMAT")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .value_name("FILE_PATH")
+                .help("logs every accepted move into the file")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("history_output")
+                .long("history_output")
+                .alias("history-output")
+                .value_name("FILE_PATH")
+                .help("writes the new best costs of the solver as CSV: epoch,best_cost,elapsed_s")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("record_gif")
+                .long("record_gif")
+                .alias("record-gif")
+                .value_name("FILE_PATH")
+                .help("renders the routes found by the solver into an animated GIF")
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("record_events")
+                .long("record_events")
+                .alias("record-events")
+                .value_name("FILE_PATH")
+                .help("writes every progress message of the solver into the file, see the replay command")
+                .takes_value(true)
+                .required(false),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("shows the progress recorded with --record_events again")
+                .arg(
+                    Arg::with_name("events_file")
+                        .index(1)
+                        .help("filepath to the recorded events")
+                        .required(true)
+                        .value_name("FILE_PATH")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("speed")
+                        .long("speed")
+                        .value_name("FACTOR")
+                        .help("how many times faster than recorded, 0.5 replays it at half speed")
+                        .default_value("1.0")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("progress")
+                        .long("progress")
+                        .help("how to show the progress: window, status line in the terminal or JSON lines on STDERR")
+                        .possible_values(&progress::ProgressMode::variants())
+                        .value_name("MODE")
+                        .takes_value(true)
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("generate")
+                .about("writes a random problem in TSPLIB format, e.g for the scaling experiments")
+                .arg(
+                    Arg::with_name("n")
+                        .long("n")
+                        .value_name("N")
+                        .help("number of cities")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("distribution")
+                        .long("distribution")
+                        .value_name("NAME")
+                        .help("how the cities are spread over the plane")
+                        .possible_values(&["uniform", "clustered", "grid"])
+                        .default_value("uniform")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("clusters")
+                        .long("clusters")
+                        .value_name("N")
+                        .help("number of clusters for the clustered distribution")
+                        .default_value("10")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("jitter")
+                        .long("jitter")
+                        .value_name("DISTANCE")
+                        .help("how far the cities of the grid distribution are moved randomly, the grid spacing is 1")
+                        .default_value("0")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .value_name("N")
+                        .help("seed of the random generator, it is random by default")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("FILE_PATH")
+                        .help("filepath for the generated problem")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("solvers")
+                .about("lists the solvers with their category, complexity and options"),
+        )
+        .subcommand(
+            SubCommand::with_name("benchmark")
+                .about("runs every solver on every instance and summarizes the cost, time and gap of the tours")
+                .arg(
+                    Arg::with_name("solvers")
+                        .long("solvers")
+                        .value_name("SOLVER_NAMES")
+                        .help("comma separated list of the solvers")
+                        .use_delimiter(true)
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("input")
+                        .long("input")
+                        .short("i")
+                        .value_name("FILE_PATH")
+                        .help("filepaths to the TSPLIB files, the gap is measured to the optimal tour in <name>.opt.tour if it exists")
+                        .multiple(true)
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("repeats")
+                        .long("repeats")
+                        .value_name("N")
+                        .help("how many times each solver runs on each instance")
+                        .default_value("1")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("csv")
+                        .long("csv")
+                        .value_name("FILE_PATH")
+                        .help("writes every run into the CSV file")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("epochs")
+                        .long("epochs")
+                        .help("specify how many maximum iterations before stopping, 0 is forever")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("max_seconds")
+                        .long("max_seconds")
+                        .alias("max-seconds")
+                        .help("the time budget of every run")
+                        .value_name("SECONDS")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .help("seeds the random generator of the stochastic solvers, makes the benchmark reproducible")
+                        .value_name("N")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("trace-stats")
+                .about("summarizes move types of the trace file")
+                .arg(
+                    Arg::with_name("trace_file")
+                        .index(1)
+                        .help("filepath to the trace file")
+                        .required(true)
+                        .value_name("FILE_PATH")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("improve")
+                .about("continues from the tour with a local search or a metaheuristic solver")
+                .arg(solver_arg(&solver_help, &solver_names).required(true))
+                .arg(input_arg().required(true))
+                .arg(
+                    Arg::with_name("tour")
+                        .long("tour")
+                        .value_name("FILE_PATH")
+                        .help("filepath to the starting tour in TSPLIB format")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("FILE_PATH")
+                        .help("writes the tour into the file instead of STDOUT, the format is inferred from the extension: .tour, .json, .csv or .svg")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("epochs")
+                        .long("epochs")
+                        .help("specify how many maximum iterations before stopping, 0 is forever")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("max_seconds")
+                        .long("max_seconds")
+                        .alias("max-seconds")
+                        .help("stops the solver after the time budget and keeps the best tour found so far")
+                        .value_name("SECONDS")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .help("seeds the random generator of the stochastic solvers")
+                        .value_name("N")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("progress")
+                        .long("progress")
+                        .help("how to show the progress: window, status line in the terminal, JSON lines on STDERR or off")
+                        .possible_values(&progress::ProgressMode::variants())
+                        .value_name("MODE")
+                        .takes_value(true)
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("validate")
+                .about("checks that the tour visits every city of the problem once and recomputes its length")
+                .arg(input_arg().required(true))
+                .arg(
+                    Arg::with_name("tour")
+                        .long("tour")
+                        .value_name("FILE_PATH")
+                        .help("filepath to the tour file in TSPLIB format")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("cvrp")
+                .about("routes the vehicles of the capacitated vehicle routing problem, the TSPLIB file must have CAPACITY and DEMAND_SECTION")
+                .arg(input_arg().required(true))
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("FILE_PATH")
+                        .help("writes the routes into the file instead of STDOUT")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("max_seconds")
+                        .long("max_seconds")
+                        .alias("max-seconds")
+                        .help("stops the local search after the time budget and keeps the best routes found so far")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("verbose")
+                        .long("verbose")
+                        .short("v")
+                        .help("prints the length of the routes after every pass of the local search"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("gtsp")
+                .about("builds the tour which visits one city of every cluster, the TSPLIB file must have GTSP_SET_SECTION")
+                .arg(input_arg().required(true))
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("FILE_PATH")
+                        .help("writes the tour into the file instead of STDOUT, the format is inferred from the extension")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("max_seconds")
+                        .long("max_seconds")
+                        .alias("max-seconds")
+                        .help("stops the local search after the time budget and keeps the best tour found so far")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("verbose")
+                        .long("verbose")
+                        .short("v")
+                        .help("prints the length of the tour after every pass of the local search"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pctsp")
+                .about("builds the prize-collecting tour from the depot, the TSPLIB file must have PRIZE_SECTION")
+                .arg(input_arg().required(true))
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("FILE_PATH")
+                        .help("writes the tour into the file instead of STDOUT")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("quota")
+                        .long("quota")
+                        .value_name("PRIZE")
+                        .help("builds the shortest tour which collects at least the prize, by default the tour collects the most prizes minus its length")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("max_seconds")
+                        .long("max_seconds")
+                        .alias("max-seconds")
+                        .help("stops the local search after the time budget and keeps the best tour found so far")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("verbose")
+                        .long("verbose")
+                        .short("v")
+                        .help("prints the prize and the length of the tour after every pass of the local search"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("selftest")
+                .about("cross-checks the implementations on random instances and reports discrepancies")
+                .arg(
+                    Arg::with_name("instances")
+                        .long("instances")
+                        .value_name("N")
+                        .help("number of random instances")
+                        .default_value("100")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .value_name("N")
+                        .help("seed of the random instances, it is random by default")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("answers the solve requests over HTTP: POST /solve and GET /solvers")
+                .arg(
+                    Arg::with_name("host")
+                        .long("host")
+                        .value_name("ADDRESS")
+                        .help("address to listen on, use 0.0.0.0 to accept the other machines")
+                        .default_value("127.0.0.1")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .value_name("PORT")
+                        .default_value("8080")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("max_seconds")
+                        .long("max_seconds")
+                        .alias("max-seconds")
+                        .help("the time budget of every request, the requests may ask for less")
+                        .value_name("SECONDS")
+                        .default_value("60")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("exports the problem for an external solver")
+                .arg(
+                    Arg::with_name("format")
+                        .index(1)
+                        .help("specify the external solver")
+                        .possible_values(&["concorde", "lkh"])
+                        .required(true)
+                        .value_name("FORMAT")
+                        .takes_value(true),
+                )
+                .arg(input_arg())
+                .arg(stdin_arg())
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("FILE_PATH")
+                        .help("filepath for the exported problem")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("run")
+                        .long("run")
+                        .help("solves the exported problem with the external solver and prints the solution")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("concorde_bin")
+                        .long("concorde_bin")
+                        .value_name("BINARY_PATH")
+                        .help("path to the concorde binary")
+                        .default_value(concorde::DEFAULT_BINARY)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("lkh_bin")
+                        .long("lkh_bin")
+                        .value_name("BINARY_PATH")
+                        .help("path to the LKH binary")
+                        .default_value(lkh::DEFAULT_BINARY)
+                        .takes_value(true),
+                ),
+        );
 
-    let solver_type = Solvers::from_str(args.value_of("solver").unwrap_or("unspecified"))
-        .expect("Unknown solver");
+    #[cfg(feature = "plugins")]
+    let app = app.arg(
+        Arg::with_name("plugin")
+            .long("plugin")
+            .value_name("LIBRARY_PATH")
+            .help("loads solvers from the dynamic library")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true),
+    );
 
-    let options = solver_options_from_args(&args);
-    if options.verbose {
-        println!("Selected solver: {:?}", solver_type);
+    let args = app.get_matches();
+
+    if let Some(export_args) = args.subcommand_matches("export") {
+        export_problem(export_args);
+        return;
     }
 
-    let tsp_data = if let Some(input_file_path) = args.value_of("input") {
-        let file_path = Path::new(input_file_path);
-        read_tsp_data_from_file(&file_path)
-    } else {
-        read_tsp_data_from_stdin()
-    };
+    if let Some(generate_args) = args.subcommand_matches("generate") {
+        generate_problem(generate_args);
+        return;
+    }
 
-    if options.verbose {
-        println!(
-            "Problem details:\n\tname:{:?}\n\tcomment:{:?}\n\tcities:{:?}",
-            tsp_data.name,
-            tsp_data.comment,
-            tsp_data.len()
-        );
+    if args.subcommand_matches("solvers").is_some() {
+        list_solvers();
+        return;
     }
 
-    let cities = tsp_data.cities().to_vec();
-    // start progress listener
-    let handler1 = thread::spawn(move || {
-        let mut progress_display = progress::ProgressPlot::new(&cities, 1024.0, 1024.0, 50.0);
+    if let Some(benchmark_args) = args.subcommand_matches("benchmark") {
+        run_benchmark(benchmark_args);
+        return;
+    }
 
-        progress_display.run();
-    });
+    if let Some(replay_args) = args.subcommand_matches("replay") {
+        replay_events(replay_args);
+        return;
+    }
 
-    // execute solver
-    let handler2 = thread::spawn(move || {
-        let tour = solve(solver_type, tsp_data.cities(), &options.clone());
-        print_solution(&tour, false);
-    });
+    if let Some(stats_args) = args.subcommand_matches("trace-stats") {
+        show_trace_stats(stats_args);
+        return;
+    }
 
-    // run threads
-    handler1.join().expect("Progress Thread Failed");
-    handler2.join().expect("Solver thread failed");
-}
+    if let Some(improve_args) = args.subcommand_matches("improve") {
+        improve_tour(improve_args);
+        return;
+    }
 
-/// solves tsp for given cities by using solver
-fn solve(algorithm: Solvers, cities: &[kdtree::KDPoint], options: &SolverOptions) -> Solution {
-    match algorithm {
-        Solvers::BellmanKarp => tsp::bellman_karp::solve(cities, options),
-        Solvers::BranchBound => tsp::branch_bound::solve(cities, options),
-        Solvers::NearestNeighbor => tsp::nearest_neighbor::solve(cities, options),
-        Solvers::TwoOpt => tsp::two_opt::solve(cities, options),
-        Solvers::StochasticHill => tsp::stochastic_hill::solve(cities, options),
-        Solvers::SimulatedAnnealing => tsp::simulated_annealing::solve(cities, options),
-        Solvers::TabuSearch => tsp::tabu_search::solve(cities, options),
-        Solvers::GeneticAlgorithm => tsp::genetic_algorithm::solve(cities, options),
-        _ => panic!("Unspecified solver"),
+    if let Some(validate_args) = args.subcommand_matches("validate") {
+        validate_tour(validate_args);
+        return;
     }
-}
 
-/// prints output to stdin
-fn print_solution(tour: &Solution, is_optimized: bool) {
-    let optimization_flag = if is_optimized { 1 } else { 0 };
+    if let Some(cvrp_args) = args.subcommand_matches("cvrp") {
+        solve_cvrp(cvrp_args);
+        return;
+    }
 
-    println!("{:.5} {}", tour.total, optimization_flag);
-    for city_id in tour.route().iter() {
-        print!("{} ", city_id);
+    if let Some(gtsp_args) = args.subcommand_matches("gtsp") {
+        solve_gtsp(gtsp_args);
+        return;
     }
 
-    print!("\n");
-}
+    if let Some(pctsp_args) = args.subcommand_matches("pctsp") {
+        solve_pctsp(pctsp_args);
+        return;
+    }
 
-fn read_tsp_data_from_file(file_path: &Path) -> tsplib::TspLibData {
-    if !file_path.exists() {
-        eprintln!("File doesnt exists: {:?}", file_path);
-        std::process::exit(1);
+    if let Some(selftest_args) = args.subcommand_matches("selftest") {
+        run_selftest(selftest_args);
+        return;
     }
 
-    match tsplib::read_from_file(file_path) {
-        Err(err_msg) => {
-            eprintln!("Error in TSPLIB file: {:?}", err_msg);
-            std::process::exit(1);
-        }
-        Ok(tsp_data_) => {
-            return tsp_data_;
-        }
+    if let Some(serve_args) = args.subcommand_matches("serve") {
+        #[cfg(feature = "plugins")]
+        load_plugins(&args);
+
+        run_server(serve_args);
+        return;
     }
-}
 
-fn read_tsp_data_from_stdin() -> tsplib::TspLibData {
-    match tsplib::read_from_stdin() {
-        Err(err_msg) => {
-            eprintln!("Failed to read TSPLIB file from STDIN: {:?}", err_msg);
+    #[cfg(feature = "plugins")]
+    load_plugins(&args);
+
+    let config = match args.value_of("config") {
+        Some(config_path) => read_config(Path::new(config_path)),
+        None => config::Config::default(),
+    };
+
+    let solver_name = match args.value_of("solver").or(config.solver.as_deref()) {
+        Some(solver_name) => solver_name.to_lowercase(),
+        None => {
+            eprintln!("No solver given, specify SOLVER_NAME or set `solver` in the config file");
             std::process::exit(1);
         }
-        Ok(tsp_data_) => return tsp_data_,
-    }
-}
-
-fn solver_options_from_args(args: &ArgMatches) -> SolverOptions {
-    let mut options = SolverOptions::default();
+    };
+    let is_plugin = plugin::find(&solver_name).is_some();
+    let solver = find_solver(&solver_name);
 
-    if args.is_present("verbose") {
-        options.verbose = true;
+    reject_unused_flags(&args, &[solver.metadata()]);
+    let options = solver_options_from_args(&args, config.options.clone());
+    validate_options(&options, &solver.metadata());
+    if options.verbose {
+        let plugin_flag = if is_plugin { " (plugin)" } else { "" };
+        println!("Selected solver: {}{}", solver.metadata().name, plugin_flag);
     }
 
-    if args.is_present("disable_progress") {
-        options.show_progress = false;
-    }
+    let input_patterns: Vec<&str> = args.values_of("input").into_iter().flatten().collect();
+    let input_paths = match batch::expand_inputs(&input_patterns) {
+        Ok(paths) => paths,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if input_paths.len() > 1 {
+        if args.is_present("output") {
+            eprintln!("--output takes a single input file, the batch prints only the summary");
+            std::process::exit(1);
+        }
+        if args.is_present("only_cities") || args.is_present("exclude_cities") {
+            eprintln!("the city filters take a single input file, the city ids differ by the file");
+            std::process::exit(1);
+        }
 
-    if let Some(n_epochs_str) = args.value_of("epochs") {
-        options.epochs = usize::from_str(n_epochs_str).unwrap_or(0);
-    }
+        if args.is_present("dry_run") {
+            for input_path in input_paths.iter() {
+                dry_run(
+                    &solver.metadata(),
+                    &read_tsp_data_from_file(input_path),
+                    &options,
+                );
+            }
+            return;
+        }
 
-    if let Some(n_platoo_str) = args.value_of("platoo_epochs") {
-        options.platoo_epochs = usize::from_str(n_platoo_str).unwrap_or(0);
+        solve_batch(&args, solver, &input_paths, &options);
+        return;
     }
 
-    if let Some(n_nearest_str) = args.value_of("n_nearest") {
-        options.n_nearest = usize::from_str(n_nearest_str).unwrap_or(0);
+    let input_path = match input_paths.first() {
+        Some(path) => Some(path.clone()),
+        None if args.is_present("stdin") => None,
+        None => config.input.clone(),
+    };
+    let tsp_data = filter_cities(&args, read_tsp_data(&args, input_path.as_deref()));
+    let is_subset = args.is_present("only_cities") || args.is_present("exclude_cities");
+    let output_path = args
+        .value_of("output")
+        .map(PathBuf::from)
+        .or_else(|| config.output.clone());
+    // the unknown format is reported before the solver runs
+    if let Some(Err(err)) = output_path.as_deref().map(output::OutputFormat::from_path) {
+        eprintln!("{}", err);
+        std::process::exit(1);
     }
-
-    if let Some(n_elite_str) = args.value_of("n_elite") {
-        options.n_elite = usize::from_str(n_elite_str).unwrap_or(0);
+    // the manifest of the output records the seed, so the random run can be repeated;
+    // it is kept small enough for the JSON readers, which keep the numbers in f64 or i64
+    let mut options = options;
+    if output_path.is_some() && options.seed.is_none() && solver.metadata().is_stochastic {
+        options.seed = Some(rand::random::<u32>() as u64);
     }
-
-    if let Some(mutation_prob_str) = args.value_of("mutation_probability") {
-        options.mutation_probability = f32::from_str(mutation_prob_str).unwrap_or(0.0);
+    if !tsp_data.fixed_edges().is_empty() {
+        options.fixed_edges = tsp_data.fixed_edges().to_vec();
+        validate_options(&options, &solver.metadata());
     }
-
-    if let Some(cooling_rate_str) = args.value_of("cooling_rate") {
-        options.cooling_rate = f32::from_str(cooling_rate_str).unwrap_or(0.0);
+    // the file of --precedences overrides the PRECEDENCE_SECTION
+    if options.precedences.is_empty() && !tsp_data.precedences().is_empty() {
+        options.precedences = tsp_data.precedences().to_vec();
+        validate_options(&options, &solver.metadata());
     }
 
-    if let Some(min_temperature_str) = args.value_of("min_temperature") {
-        options.min_temperature = f32::from_str(min_temperature_str).unwrap_or(0.0);
+    if args.is_present("dry_run") {
+        dry_run(&solver.metadata(), &tsp_data, &options);
+        return;
+    }
+
+    if options.verbose {
+        println!(
+            "Problem details:\n\tname:{:?}\n\tcomment:{:?}\n\tcities:{:?}",
+            tsp_data.name,
+            tsp_data.comment,
+            tsp_data.len()
+        );
+    }
+
+    let units = report_units(&args, &tsp_data);
+    let plot_format = args
+        .value_of("export_plot_script")
+        .and_then(|val| plot_script::PlotFormat::from_str(val).ok());
+    let (n_starts, n_threads) = multistart_from_args(&args, &solver.metadata());
+    let show_repeats = n_starts > 1 && args.is_present("repeats");
+    let analyze = args.is_present("analyze");
+    let history_path = args.value_of("history_output").map(PathBuf::from);
+
+    if let Some(trace_path) = args.value_of("trace") {
+        if let Err(err_msg) = trace::init(Path::new(trace_path)) {
+            eprintln!("Failed to start the trace: {}", err_msg);
+            std::process::exit(1);
+        }
+    }
+
+    install_interrupt_handler(&options.cancel);
+
+    // start progress listener
+    let progress_mode = progress_mode_from_args(&args, options.show_progress);
+    let handler1 = if progress_mode == ProgressMode::Window {
+        spawn_progress_window(tsp_data.cities().to_vec(), options.cancel.clone())
+    } else {
+        None
+    };
+    let mut publisher = publisher_for_mode(progress_mode, &solver.metadata().name);
+
+    let gif_path = args.value_of("record_gif").map(PathBuf::from);
+    let gif_recorder = gif_path
+        .as_ref()
+        .map(|_| Arc::new(progress::GifRecorder::new(tsp_data.cities())));
+    if let Some(recorder) = &gif_recorder {
+        publisher = progress::broadcast(vec![publisher, progress::publisher_for(recorder.clone())]);
+    }
+
+    let event_recorder = args.value_of("record_events").map(|events_path| {
+        match progress::EventRecorder::create(Path::new(events_path), tsp_data.cities()) {
+            Ok(recorder) => Arc::new(recorder),
+            Err(err) => {
+                eprintln!("Failed to start the recording: {}", err);
+                std::process::exit(1);
+            }
+        }
+    });
+    if let Some(recorder) = &event_recorder {
+        publisher = progress::broadcast(vec![publisher, progress::publisher_for(recorder.clone())]);
+    }
+
+    // execute solver
+    let handler2 = thread::spawn(move || {
+        let solve_fn = |cities: &[kdtree::KDPoint], options: &SolverOptions| {
+            solver
+                .solve(cities, &options.to_tour_options(), publisher.clone())?
+                .with_start_city(options)
+        };
+
+        let keep_best = |res: restart::RestartResult| {
+            if show_repeats {
+                eprint!("{}", res);
+            }
+            res.best
+        };
+
+        let started = Instant::now();
+        let res = if n_threads > 1 {
+            restart::run_parallel(solve_fn, tsp_data.cities(), &options, n_starts, n_threads)
+                .map(keep_best)
+        } else if n_starts > 1 {
+            restart::run(solve_fn, tsp_data.cities(), &options, n_starts).map(keep_best)
+        } else {
+            solve_fn(tsp_data.cities(), &options)
+        };
+        let tour = match res {
+            Ok(tour) => tour,
+            Err(err) => {
+                eprintln!("Failed to solve the problem: {}", err);
+                std::process::exit(1);
+            }
+        };
+        trace::finish();
+        if let Some(recorder) = event_recorder {
+            if let Err(err) = recorder.finish() {
+                eprintln!("Failed to save the recorded events: {}", err);
+            }
+        }
+        if options.cancel.is_cancelled() {
+            eprintln!("Interrupted, the best tour found so far:");
+        }
+        if let Some(target_cost) = options.target_cost {
+            let status = if tour.target_reached {
+                "reached"
+            } else {
+                "not reached"
+            };
+            eprintln!("Target cost {} {}", target_cost, status);
+        }
+        // the known optima are of the closed tours through all the cities of the instance
+        if !tour.is_open_tour() && options.end_city.is_none() && !is_subset {
+            if let Some(gap) = optima::gap(&tsp_data, tour.route()) {
+                eprintln!("{}", gap);
+            }
+        }
+        if analyze {
+            let n_crossings = analysis::count_crossings(tour.route(), tsp_data.cities());
+            eprintln!("Crossing edges: {}", n_crossings);
+        }
+        let length = units.and_then(|units| units.length(&tsp_data, &tour));
+        match &output_path {
+            Some(output_path) => {
+                if let Err(err) =
+                    output::write_to_file(output_path, &tsp_data.name, &tour, length.as_ref())
+                {
+                    eprintln!("Failed to save the tour: {}", err);
+                    std::process::exit(1);
+                }
+                eprintln!("Saved the tour into {:?}", output_path);
+
+                let seconds = started.elapsed().as_secs_f64();
+                let manifest =
+                    manifest::Manifest::new(&solver_name, &options, &tsp_data, &tour, seconds);
+                if let Err(err) = manifest.write_to_file(&manifest::manifest_path(output_path)) {
+                    eprintln!("Failed to save the run manifest: {}", err);
+                }
+            }
+            None => print_solution(&tour, false, length.as_ref()),
+        }
+        if let Some(history_path) = &history_path {
+            match tour.history().write_to_file(history_path) {
+                Ok(()) => eprintln!("Saved the history into {:?}", history_path),
+                Err(err) => eprintln!("Failed to save the history: {}", err),
+            }
+        }
+
+        if let Some(format) = plot_format {
+            export_plot_script(format, &tour, &tsp_data.name);
+        }
+
+        if let (Some(recorder), Some(path)) = (gif_recorder, gif_path) {
+            match recorder.save(&path) {
+                Ok(()) => eprintln!("Saved {} frames into {:?}", recorder.n_frames(), path),
+                Err(err) => eprintln!("Failed to record the GIF: {}", err),
+            }
+        }
+    });
+
+    // run threads
+    if let Some(handler1) = handler1 {
+        handler1.join().expect("Progress Thread Failed");
+    }
+    handler2.join().expect("Solver thread failed");
+}
+
+#[cfg(feature = "gui")]
+fn spawn_progress_window(
+    cities: Vec<kdtree::KDPoint>,
+    cancel: CancelFlag,
+) -> Option<thread::JoinHandle<()>> {
+    let handler = thread::spawn(move || {
+        let mut progress_display =
+            progress::ProgressPlot::new(&cities, 1024.0, 1024.0, 50.0).close_on_cancel(&cancel);
+
+        progress_display.run();
+    });
+
+    Some(handler)
+}
+
+// the binary is built without the window, the progress is not shown
+#[cfg(not(feature = "gui"))]
+fn spawn_progress_window(
+    _cities: Vec<kdtree::KDPoint>,
+    _cancel: CancelFlag,
+) -> Option<thread::JoinHandle<()>> {
+    eprintln!("teeline is built without the gui feature, use --progress term instead");
+    None
+}
+
+fn publisher_for_mode(mode: ProgressMode, solver_name: &str) -> progress::PublisherFn {
+    match mode {
+        ProgressMode::Window => progress::default_publisher(),
+        ProgressMode::Term => progress::publisher_for(Arc::new(progress::TermSink::new())),
+        ProgressMode::Jsonl => {
+            progress::publisher_for(Arc::new(progress::JsonlSink::new(solver_name)))
+        }
+        ProgressMode::Off => progress::silent_publisher(),
+    }
+}
+
+fn progress_mode_from_args(args: &ArgMatches, show_progress: bool) -> ProgressMode {
+    if args.is_present("disable_progress") {
+        return ProgressMode::Off;
+    }
+
+    // `show_progress = false` of the config file is overridden by the explicit mode
+    match args
+        .value_of("progress")
+        .and_then(|val| ProgressMode::from_str(val).ok())
+    {
+        Some(mode) => mode,
+        None if !show_progress => ProgressMode::Off,
+        None => ProgressMode::default(),
+    }
+}
+
+#[cfg(feature = "plugins")]
+fn load_plugins(args: &ArgMatches) {
+    for library_path in args.values_of("plugin").into_iter().flatten() {
+        match plugin::load_library(Path::new(library_path)) {
+            Ok(n_solvers) if args.is_present("verbose") => {
+                println!("Loaded {} solvers from {}", n_solvers, library_path)
+            }
+            Ok(_) => (),
+            Err(err_msg) => {
+                eprintln!("{}", err_msg);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn solver_arg<'a, 'b>(help: &'a str, names: &'a [String]) -> Arg<'a, 'b> {
+    let arg = Arg::with_name("solver")
+        .index(1)
+        .help(help)
+        .required_unless("config")
+        .value_name("SOLVER_NAME")
+        .takes_value(true);
+
+    // the plugins are loaded after the arguments are parsed, so their names are checked later
+    if cfg!(feature = "plugins") {
+        return arg;
+    }
+
+    // the pipelines, e.g `nn+2opt`, are checked stage by stage
+    let names = names.to_vec();
+    arg.validator(move |value| {
+        let value = value.to_lowercase();
+        match value
+            .split(pipeline::STAGE_SEPARATOR)
+            .find(|stage| !names.iter().any(|name| name == stage.trim()))
+        {
+            Some(unknown) => Err(format!(
+                "unknown solver `{}`, available solvers: {}",
+                unknown,
+                names.join(", ")
+            )),
+            None => Ok(()),
+        }
+    })
+}
+
+fn read_config(path: &Path) -> config::Config {
+    match config::read_from_file(path) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Failed to read the config file: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// finds the solver from the registry or exits with the list of available solvers
+fn find_solver(name: &str) -> plugin::SharedSolver {
+    match registry::resolve(name) {
+        Ok(solver) => solver,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn input_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("input")
+        .long("input")
+        .short("i")
+        .value_name("FILE_PATH")
+        .help("filepath or http(s) URL of the input file, must be in TSPLIB format")
+        .takes_value(true)
+        .required(false)
+}
+
+/// the path of the input file, the URL is downloaded into the cache first
+fn local_path(input: &str) -> PathBuf {
+    if !remote::is_url(input) {
+        return PathBuf::from(input);
+    }
+
+    match remote::fetch(input) {
+        Ok(path) => path,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// solves every input file with the same settings and prints the summary of the results;
+/// the threads solve the files in parallel instead of the starts of one problem
+fn solve_batch(
+    args: &ArgMatches,
+    solver: plugin::SharedSolver,
+    paths: &[PathBuf],
+    options: &SolverOptions,
+) {
+    let n_restarts = if solver.metadata().is_stochastic {
+        count_arg(args, "restarts").max(count_arg(args, "repeats"))
+    } else {
+        1
+    };
+    let n_threads = count_arg(args, "threads");
+
+    install_interrupt_handler(&options.cancel);
+
+    let publisher = progress::silent_publisher();
+    let solve_fn = |cities: &[kdtree::KDPoint], options: &SolverOptions| {
+        // the files with the fixed edges need a solver, which keeps them
+        options.check_constraints(&solver.metadata())?;
+        let run_fn = |cities: &[kdtree::KDPoint], options: &SolverOptions| {
+            solver
+                .solve(cities, &options.to_tour_options(), publisher.clone())?
+                .with_start_city(options)
+        };
+
+        if n_restarts > 1 {
+            restart::run(run_fn, cities, options, n_restarts).map(|res| res.best)
+        } else {
+            run_fn(cities, options)
+        }
+    };
+
+    match batch::run(paths, solve_fn, options, n_threads) {
+        Ok(report) => {
+            print!("{}", report);
+            if report.n_failed() > 0 {
+                std::process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("Failed to solve the batch: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_selftest(args: &ArgMatches) {
+    let n_instances = count_arg(args, "instances");
+    let seed = match args.value_of("seed").map(u64::from_str) {
+        None => rand::random(),
+        Some(Ok(seed)) => seed,
+        Some(Err(_)) => {
+            eprintln!("--seed must be a non-negative number");
+            std::process::exit(1);
+        }
+    };
+
+    let report = selftest::run(n_instances, seed);
+    print!("{}", report);
+
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+}
+
+fn generate_problem(args: &ArgMatches) {
+    let n_cities = count_arg(args, "n");
+    let n_clusters = count_arg(args, "clusters");
+    let distribution = args.value_of("distribution").unwrap();
+    let jitter = match args.value_of("jitter").map(f64::from_str) {
+        Some(Ok(jitter)) if jitter >= 0.0 => jitter,
+        _ => {
+            eprintln!("--jitter must be a non-negative number");
+            std::process::exit(1);
+        }
+    };
+    let seed = match args.value_of("seed").map(u64::from_str) {
+        None => rand::random(),
+        Some(Ok(seed)) => seed,
+        Some(Err(_)) => {
+            eprintln!("--seed must be a non-negative number");
+            std::process::exit(1);
+        }
+    };
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let cities = match distribution {
+        "clustered" => {
+            let spread = generator::AREA_SIZE / (4.0 * (n_clusters as f64).sqrt());
+            generator::clustered(n_cities, n_clusters, spread, &mut rng)
+        }
+        "grid" => {
+            // the last row is left incomplete, if the number isn't a square
+            let n_cols = (n_cities as f32).sqrt().ceil() as usize;
+            let n_rows = n_cities.div_ceil(n_cols);
+            let mut cities = generator::grid(n_rows, n_cols, jitter, &mut rng);
+            cities.truncate(n_cities);
+            cities
+        }
+        _ => generator::uniform(
+            n_cities,
+            &generator::BoundingBox::square(generator::AREA_SIZE),
+            &mut rng,
+        ),
+    };
+
+    let output_path = Path::new(args.value_of("output").unwrap());
+    let name = output_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "generated".to_string());
+    let comment = format!(
+        "{} cities, {} distribution, seed {}",
+        n_cities, distribution, seed
+    );
+    let tsp_data = tsplib::TspLibData::new(name, comment, cities).renumbered();
+
+    if let Err(err_msg) = tsplib::write_to_file(output_path, &tsp_data) {
+        eprintln!("Failed to write the problem: {}", err_msg);
+        std::process::exit(1);
+    }
+}
+
+fn list_solvers() {
+    for info in registry::catalog() {
+        let aliases = if info.aliases.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", info.aliases.join(", "))
+        };
+        let options = if info.options.is_empty() {
+            "-".to_string()
+        } else {
+            info.options.join(", ")
+        };
+
+        println!(
+            "{:<28}{:<16}{}",
+            format!("{}{}", info.name, aliases),
+            info.category,
+            info.complexity
+        );
+        println!("    {}", info.description);
+        println!("    options: {}", options);
+    }
+}
+
+fn run_benchmark(args: &ArgMatches) {
+    let mut solvers = vec![];
+    for name in args.values_of("solvers").unwrap() {
+        let name = name.trim().to_lowercase();
+        solvers.push(find_solver(&name));
+    }
+
+    let mut instances = vec![];
+    for input_path in args.values_of("input").unwrap() {
+        match benchmark::Instance::from_file(&local_path(input_path)) {
+            Ok(instance) => instances.push(instance),
+            Err(err_msg) => {
+                eprintln!("Failed to read {}: {}", input_path, err_msg);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let n_repeats = count_arg(args, "repeats");
+    let infos: Vec<SolverInfo> = solvers.iter().map(|solver| solver.metadata()).collect();
+    reject_unused_flags(args, &infos);
+    let options = solver_options_from_args(args, SolverOptions::default());
+    for solver in solvers.iter() {
+        validate_options(&options, &solver.metadata());
+    }
+    install_interrupt_handler(&options.cancel);
+
+    let report = benchmark::run(&solvers, &instances, &options, n_repeats);
+    print!("{}", report);
+
+    if let Some(csv_path) = args.value_of("csv") {
+        let res = std::fs::File::create(csv_path).and_then(|mut f| report.write_csv(&mut f));
+        if let Err(err) = res {
+            eprintln!("Failed to write {}: {}", csv_path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn replay_events(args: &ArgMatches) {
+    let events_path = Path::new(args.value_of("events_file").unwrap());
+    let recording = match progress::events::read_from_file(events_path) {
+        Ok(recording) => recording,
+        Err(err_msg) => {
+            eprintln!("Failed to read the recorded events: {}", err_msg);
+            std::process::exit(1);
+        }
+    };
+
+    let speed = match args.value_of("speed").map(f32::from_str) {
+        Some(Ok(speed)) if speed > 0.0 => speed,
+        _ => {
+            eprintln!("--speed must be a positive number");
+            std::process::exit(1);
+        }
+    };
+
+    let cancel = CancelFlag::new();
+    install_interrupt_handler(&cancel);
+
+    let progress_mode = progress_mode_from_args(args, true);
+    let handler = if progress_mode == ProgressMode::Window {
+        spawn_progress_window(recording.cities.clone(), cancel.clone())
+    } else {
+        None
+    };
+    let publisher = publisher_for_mode(progress_mode, "replay");
+
+    progress::events::replay(&recording.messages, &publisher, speed, &cancel);
+
+    if let Some(handler) = handler {
+        handler.join().expect("Progress Thread Failed");
+    }
+}
+
+fn show_trace_stats(args: &ArgMatches) {
+    let trace_path = Path::new(args.value_of("trace_file").unwrap());
+
+    match trace::read_from_file(trace_path) {
+        Ok(events) => print!("{}", trace::summarize(&events)),
+        Err(err_msg) => {
+            eprintln!("Failed to read the trace: {}", err_msg);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// continues from the tour of the file with the local search or the metaheuristic
+fn improve_tour(args: &ArgMatches) {
+    let solver = find_solver(&args.value_of("solver").unwrap().to_lowercase());
+    if !improve::can_improve(&solver.metadata()) {
+        eprintln!(
+            "{} builds the tour from scratch, use a local search or a metaheuristic",
+            solver.metadata().name
+        );
+        std::process::exit(1);
+    }
+
+    let tsp_data = read_tsp_data_from_file(&local_path(args.value_of("input").unwrap()));
+    let tour = tsplib::read_tour_from_file(Path::new(args.value_of("tour").unwrap()))
+        .and_then(|tour| tsp::validate_route(tsp_data.cities(), &tour).map(|_| tour));
+    let tour = match tour {
+        Ok(tour) => tour,
+        Err(err_msg) => {
+            eprintln!("Failed to read the tour: {}", err_msg);
+            std::process::exit(1);
+        }
+    };
+
+    let output_path = args.value_of("output").map(PathBuf::from);
+    if let Some(Err(err)) = output_path.as_deref().map(output::OutputFormat::from_path) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+
+    reject_unused_flags(args, &[solver.metadata()]);
+    let options = solver_options_from_args(args, SolverOptions::default());
+    validate_options(&options, &solver.metadata());
+    install_interrupt_handler(&options.cancel);
+
+    let progress_mode = progress_mode_from_args(args, true);
+    let handler = if progress_mode == ProgressMode::Window {
+        spawn_progress_window(tsp_data.cities().to_vec(), options.cancel.clone())
+    } else {
+        None
+    };
+    let publisher = publisher_for_mode(progress_mode, &solver.metadata().name);
+
+    let start_total = Solution::new(&tour, tsp_data.cities()).total;
+    let res = improve::run(
+        solver.as_ref(),
+        tsp_data.cities(),
+        &tour,
+        &options,
+        publisher,
+    );
+    if let Some(handler) = handler {
+        handler.join().expect("Progress Thread Failed");
+    }
+
+    let tour = match res {
+        Ok(tour) => tour,
+        Err(err) => {
+            eprintln!("Failed to improve the tour: {}", err);
+            std::process::exit(1);
+        }
+    };
+    eprintln!(
+        "Improved the tour from {:.2} to {:.2}",
+        start_total, tour.total
+    );
+
+    match output_path {
+        Some(output_path) => {
+            if let Err(err) = output::write_to_file(&output_path, &tsp_data.name, &tour, None) {
+                eprintln!("Failed to save the tour: {}", err);
+                std::process::exit(1);
+            }
+            eprintln!("Saved the tour into {:?}", output_path);
+        }
+        None => print_solution(&tour, false, None),
+    }
+}
+
+/// prints the estimate of the exact solver instead of solving the problem
+fn dry_run(solver_info: &SolverInfo, tsp_data: &tsplib::TspLibData, options: &SolverOptions) {
+    if solver_info.name != "bellman_karp" {
+        println!(
+            "{}: {} has no estimate, only bellman_karp has",
+            tsp_data.name, solver_info.name
+        );
+        return;
+    }
+
+    let estimate = bellman_karp::estimate(tsp_data.len());
+    let verdict = if estimate.memory_mb > options.max_memory_mb as f64 {
+        format!("over the limit of {} MB", options.max_memory_mb)
+    } else {
+        "within the memory limit".to_string()
+    };
+    println!("{}: {}, {}", tsp_data.name, estimate, verdict);
+}
+
+fn validate_tour(args: &ArgMatches) {
+    let tsp_data = read_tsp_data_from_file(&local_path(args.value_of("input").unwrap()));
+    let tour_path = Path::new(args.value_of("tour").unwrap());
+
+    let entries = match tsplib::read_tour_entries_from_file(tour_path) {
+        Ok(entries) => entries,
+        Err(err_msg) => {
+            eprintln!("Failed to read the tour: {}", err_msg);
+            std::process::exit(1);
+        }
+    };
+
+    let report = validate::validate(&tsp_data, &entries);
+    print!("{}", report);
+    if !report.is_valid() {
+        std::process::exit(1);
+    }
+
+    let route: Vec<usize> = entries.iter().map(|entry| entry.city_id).collect();
+    if let Some(gap) = optima::gap(&tsp_data, &route) {
+        println!("{}", gap);
+    }
+}
+
+fn solve_cvrp(args: &ArgMatches) {
+    let tsp_data = read_tsp_data_from_file(&local_path(args.value_of("input").unwrap()));
+    let problem = match cvrp::CvrpProblem::from_tsplib(&tsp_data) {
+        Ok(problem) => problem,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut options = SolverOptions::default();
+    options.verbose = args.is_present("verbose");
+    options.max_seconds = match args.value_of("max_seconds").map(f32::from_str) {
+        None => None,
+        Some(Ok(secs)) if secs >= 0.0 => Some(secs),
+        Some(_) => {
+            eprintln!("--max_seconds must be a non-negative number");
+            std::process::exit(1);
+        }
+    };
+    install_interrupt_handler(&options.cancel);
+
+    let solution = match cvrp::solve(&problem, &options) {
+        Ok(solution) => solution,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    match args.value_of("output") {
+        Some(output_path) => {
+            if let Err(err) = std::fs::write(output_path, solution.to_string()) {
+                eprintln!("Failed to write {}: {}", output_path, err);
+                std::process::exit(1);
+            }
+        }
+        None => print!("{}", solution),
+    }
+}
+
+fn solve_gtsp(args: &ArgMatches) {
+    let tsp_data = read_tsp_data_from_file(&local_path(args.value_of("input").unwrap()));
+    let problem = match gtsp::GtspProblem::from_tsplib(&tsp_data) {
+        Ok(problem) => problem,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut options = SolverOptions::default();
+    options.verbose = args.is_present("verbose");
+    options.max_seconds = match args.value_of("max_seconds").map(f32::from_str) {
+        None => None,
+        Some(Ok(secs)) if secs >= 0.0 => Some(secs),
+        Some(_) => {
+            eprintln!("--max_seconds must be a non-negative number");
+            std::process::exit(1);
+        }
+    };
+    install_interrupt_handler(&options.cancel);
+
+    let solution = match gtsp::solve(&problem, &options) {
+        Ok(solution) => solution,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    match args.value_of("output") {
+        Some(output_path) => {
+            let res =
+                output::write_to_file(Path::new(output_path), &tsp_data.name, &solution, None);
+            if let Err(err) = res {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        None => print_solution(&solution, false, None),
+    }
+}
+
+fn solve_pctsp(args: &ArgMatches) {
+    let tsp_data = read_tsp_data_from_file(&local_path(args.value_of("input").unwrap()));
+    let problem = match prize_collecting::PrizeProblem::from_tsplib(&tsp_data) {
+        Ok(problem) => problem,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let objective = match args.value_of("quota").map(usize::from_str) {
+        None => prize_collecting::Objective::Profit,
+        Some(Ok(quota)) => prize_collecting::Objective::Quota(quota),
+        Some(Err(_)) => {
+            eprintln!("--quota must be a non-negative integer");
+            std::process::exit(1);
+        }
+    };
+
+    let mut options = SolverOptions::default();
+    options.verbose = args.is_present("verbose");
+    options.max_seconds = match args.value_of("max_seconds").map(f32::from_str) {
+        None => None,
+        Some(Ok(secs)) if secs >= 0.0 => Some(secs),
+        Some(_) => {
+            eprintln!("--max_seconds must be a non-negative number");
+            std::process::exit(1);
+        }
+    };
+    install_interrupt_handler(&options.cancel);
+
+    let solution = match prize_collecting::solve(&problem, objective, &options) {
+        Ok(solution) => solution,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    match args.value_of("output") {
+        Some(output_path) => {
+            if let Err(err) = std::fs::write(output_path, solution.to_string()) {
+                eprintln!("Failed to write {}: {}", output_path, err);
+                std::process::exit(1);
+            }
+        }
+        None => print!("{}", solution),
+    }
+}
+
+fn run_server(args: &ArgMatches) {
+    let port = match args.value_of("port").map(u16::from_str) {
+        Some(Ok(port)) => port,
+        _ => {
+            eprintln!("--port must be a number from 0 to 65535");
+            std::process::exit(1);
+        }
+    };
+    let budget = match args.value_of("max_seconds").map(f32::from_str) {
+        Some(Ok(budget)) if budget > 0.0 => budget,
+        _ => {
+            eprintln!("--max_seconds must be a positive number");
+            std::process::exit(1);
+        }
+    };
+
+    let addr = format!("{}:{}", args.value_of("host").unwrap(), port);
+    println!("Listening on http://{}", addr);
+    if let Err(err) = serve::serve(&addr, budget) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn export_problem(args: &ArgMatches) {
+    let input_path = args.value_of("input").map(local_path);
+    let tsp_data = read_tsp_data(args, input_path.as_deref());
+    let output_path = PathBuf::from(args.value_of("output").unwrap());
+
+    let res = match args.value_of("format").unwrap() {
+        "lkh" => export_lkh(args, &tsp_data, &output_path),
+        _ => export_concorde(args, &tsp_data, &output_path),
+    };
+
+    match res {
+        Ok(Some(tour)) => print_solution(&tour, true, None),
+        Ok(None) => (),
+        Err(err_msg) => {
+            eprintln!("Failed to export the problem: {}", err_msg);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn export_concorde(
+    args: &ArgMatches,
+    tsp_data: &tsplib::TspLibData,
+    output_path: &Path,
+) -> Result<Option<Solution>, tsp::Error> {
+    concorde::write_problem(output_path, tsp_data)?;
+
+    if !args.is_present("run") {
+        return Ok(None);
+    }
+
+    let binary = PathBuf::from(args.value_of("concorde_bin").unwrap());
+    let solution_path = output_path.with_extension("sol");
+    concorde::run(&binary, output_path, &solution_path)?;
+
+    concorde::read_solution(&solution_path, tsp_data.cities()).map(Some)
+}
+
+/// LKH needs the parameter file too, it is kept next to the problem file
+fn export_lkh(
+    args: &ArgMatches,
+    tsp_data: &tsplib::TspLibData,
+    output_path: &Path,
+) -> Result<Option<Solution>, tsp::Error> {
+    let par_path = output_path.with_extension("par");
+    let tour_path = output_path.with_extension("tour");
+
+    lkh::write_problem(output_path, tsp_data)?;
+    lkh::write_parameters(
+        &par_path,
+        output_path,
+        &tour_path,
+        &lkh::LkhParameters::default(),
+    )?;
+
+    if !args.is_present("run") {
+        return Ok(None);
+    }
+
+    let binary = PathBuf::from(args.value_of("lkh_bin").unwrap());
+    lkh::run(&binary, &par_path)?;
+
+    lkh::read_tour(&tour_path, tsp_data.cities()).map(Some)
+}
+
+/// prints output to stdin
+fn print_solution(tour: &Solution, is_optimized: bool, length: Option<&Length>) {
+    let optimization_flag = if is_optimized { 1 } else { 0 };
+    let total = length.map_or(tour.total as f64, |length| length.value);
+
+    println!("{:.5} {}", total, optimization_flag);
+    for city_id in tour.route().iter() {
+        print!("{} ", city_id);
+    }
+
+    print!("\n");
+}
+
+fn stdin_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("stdin")
+        .long("stdin")
+        .help("reads TSPLIB data from STDIN even if it is a terminal")
+        .conflicts_with("input")
+        .required(false)
+}
+
+fn export_plot_script(format: plot_script::PlotFormat, tour: &Solution, name: &str) {
+    match plot_script::export(format, tour, name, Path::new(".")) {
+        Ok(script_path) => eprintln!("Plot script written to {}", script_path.display()),
+        Err(err_msg) => eprintln!("Failed to export the plot script: {}", err_msg),
+    }
+}
+
+/// restarts and threads are used only for the stochastic solvers,
+/// the others would find the same solution on every run
+fn multistart_from_args(args: &ArgMatches, solver_info: &SolverInfo) -> (usize, usize) {
+    // --repeats runs like --restarts, it only prints the summary of the runs too
+    let n_restarts = count_arg(args, "restarts").max(count_arg(args, "repeats"));
+    let n_threads = count_arg(args, "threads");
+
+    if (n_restarts > 1 || n_threads > 1) && !solver_info.is_stochastic {
+        eprintln!(
+            "Ignoring --restarts, --repeats and --threads, {} finds the same solution on every run",
+            solver_info.name
+        );
+        return (1, 1);
+    }
+
+    // every thread runs at least one start
+    (n_restarts.max(n_threads), n_threads)
+}
+
+fn count_arg(args: &ArgMatches, name: &str) -> usize {
+    match args.value_of(name).map(usize::from_str) {
+        None => 1,
+        Some(Ok(n)) if n > 0 => n,
+        Some(_) => {
+            eprintln!("--{} must be a positive number", name);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// units are used only for the GEO distances, which are kilometers
+fn report_units(args: &ArgMatches, tsp_data: &tsplib::TspLibData) -> Option<Units> {
+    let units = args
+        .value_of("units")
+        .and_then(|val| Units::from_str(val).ok())?;
+
+    if !Units::applies_to(tsp_data) {
+        eprintln!(
+            "Ignoring --units {}, it applies only to the GEO distances, not to {}",
+            units, tsp_data.edge_weight_type
+        );
+        return None;
+    }
+
+    Some(units)
+}
+
+/// reads the input file or, without it, STDIN
+fn read_tsp_data(args: &ArgMatches, input_path: Option<&Path>) -> tsplib::TspLibData {
+    if let Some(file_path) = input_path {
+        return read_tsp_data_from_file(file_path);
+    }
+
+    // without a pipe the reader would wait silently for the input
+    if !args.is_present("stdin") && std::io::stdin().is_terminal() {
+        eprintln!("No input file given, use `-i FILE_PATH` or pipe TSPLIB data into teeline.");
+        eprintln!("Reading TSPLIB data from the terminal, finish it with an EOF line or Ctrl-D:");
+    }
+
+    read_tsp_data_from_stdin()
+}
+
+/// keeps the cities of --only_cities without the cities of --exclude_cities
+fn filter_cities(args: &ArgMatches, tsp_data: tsplib::TspLibData) -> tsplib::TspLibData {
+    let filter = city_filter::CityFilter::new(
+        args.value_of("only_cities"),
+        args.value_of("exclude_cities"),
+    );
+
+    match filter.and_then(|filter| filter.apply(&tsp_data)) {
+        Ok(filtered) => filtered,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn read_tsp_data_from_file(file_path: &Path) -> tsplib::TspLibData {
+    if !file_path.exists() {
+        eprintln!("File doesnt exists: {:?}", file_path);
+        std::process::exit(1);
+    }
+
+    let is_xml = file_path
+        .to_str()
+        .is_some_and(|path| path.ends_with(".xml") || path.ends_with(".xml.gz"));
+    let res = if is_xml {
+        read_xml_file(file_path)
+    } else {
+        tsplib::read_from_file(file_path)
+    };
+
+    match res {
+        Err(err_msg) => {
+            eprintln!("Error in TSPLIB file: {}", err_msg);
+            std::process::exit(1);
+        }
+        Ok(tsp_data_) => {
+            return tsp_data_;
+        }
+    }
+}
+
+// the TSPLIB instances encoded in XML, see `tsp::tsplib_xml`
+#[cfg(feature = "xml")]
+fn read_xml_file(file_path: &Path) -> Result<tsplib::TspLibData, tsp::Error> {
+    tsp::tsplib_xml::read_from_file(file_path)
+}
+
+#[cfg(not(feature = "xml"))]
+fn read_xml_file(file_path: &Path) -> Result<tsplib::TspLibData, tsp::Error> {
+    Err(tsp::Error::InvalidInput(format!(
+        "teeline is built without the `xml` feature, it can't read {:?}",
+        file_path
+    )))
+}
+
+fn read_tsp_data_from_stdin() -> tsplib::TspLibData {
+    match tsplib::read_from_stdin() {
+        Err(err_msg) => {
+            eprintln!("Failed to read TSPLIB file from STDIN: {}", err_msg);
+            std::process::exit(1);
+        }
+        Ok(tsp_data_) => return tsp_data_,
+    }
+}
+
+/// the first Ctrl-C stops the solver and it prints the best tour found so far,
+/// the second one exits right away
+fn install_interrupt_handler(cancel: &CancelFlag) {
+    let cancel = cancel.clone();
+    let res = ctrlc::set_handler(move || {
+        if cancel.is_cancelled() {
+            std::process::exit(130);
+        }
+
+        eprintln!("\nStopping the solver, press Ctrl-C again to exit immediately");
+        cancel.cancel();
+    });
+
+    if let Err(err) = res {
+        eprintln!("Failed to set the Ctrl-C handler: {}", err);
+    }
+}
+
+/// overrides the `base` options with the flags given on the command-line
+fn solver_options_from_args(args: &ArgMatches, base: SolverOptions) -> SolverOptions {
+    let mut options = base;
+
+    if args.is_present("verbose") {
+        options.verbose = true;
+    }
+
+    if args.is_present("disable_progress") {
+        options.show_progress = false;
+    }
+
+    if let Some(n_epochs_str) = args.value_of("epochs") {
+        match usize::from_str(n_epochs_str) {
+            Ok(value) => options.epochs = value,
+            _ => {
+                eprintln!("--epochs must be a whole number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(n_platoo_str) = args.value_of("platoo_epochs") {
+        match usize::from_str(n_platoo_str) {
+            Ok(value) => options.platoo_epochs = value,
+            _ => {
+                eprintln!("--platoo_epochs must be a whole number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(strategy) = args
+        .value_of("hill_strategy")
+        .and_then(|val| stochastic_hill::HillStrategy::from_str(val).ok())
+    {
+        options.hill_strategy = strategy;
+    }
+
+    if let Some(n_samples_str) = args.value_of("n_samples") {
+        match usize::from_str(n_samples_str) {
+            Ok(n_samples) if n_samples > 0 => options.n_samples = Some(n_samples),
+            _ => {
+                eprintln!("--n_samples must be a positive number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(n_nearest_str) = args.value_of("n_nearest") {
+        match usize::from_str(n_nearest_str) {
+            Ok(value) => options.n_nearest = value,
+            _ => {
+                eprintln!("--n_nearest must be a whole number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(start_city_str) = args.value_of("start_city") {
+        match usize::from_str(start_city_str) {
+            Ok(start_city) => options.start_city = Some(start_city),
+            Err(_) => {
+                eprintln!("--start_city must be a city id");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(end_city_str) = args.value_of("end_city") {
+        match usize::from_str(end_city_str) {
+            Ok(end_city) => options.end_city = Some(end_city),
+            Err(_) => {
+                eprintln!("--end_city must be a city id");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(candidates) = args
+        .value_of("candidates")
+        .and_then(|val| neighbors::Candidates::from_str(val).ok())
+    {
+        options.candidates = candidates;
+    }
+
+    if args.is_present("all_starts") {
+        options.all_starts = true;
+    }
+
+    if args.is_present("open_tour") {
+        options.open_tour = true;
+    }
+
+    if let Some(precedences_path) = args.value_of("precedences") {
+        match precedence::read_from_file(Path::new(precedences_path)) {
+            Ok(precedences) => options.precedences = precedences,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(spatial_index) = args
+        .value_of("spatial_index")
+        .and_then(|val| grid::SpatialIndex::from_str(val).ok())
+    {
+        options.spatial_index = spatial_index;
+    }
+
+    if let Some(n_elite_str) = args.value_of("n_elite") {
+        match usize::from_str(n_elite_str) {
+            Ok(value) => options.n_elite = value,
+            _ => {
+                eprintln!("--n_elite must be a whole number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(population_size_str) = args.value_of("population_size") {
+        match usize::from_str(population_size_str) {
+            Ok(population_size) if population_size >= 2 => {
+                options.population_size = population_size
+            }
+            _ => {
+                eprintln!("--population_size must be at least 2");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(n_offspring_str) = args.value_of("n_offspring") {
+        match usize::from_str(n_offspring_str) {
+            Ok(n_offspring) if n_offspring > 0 => options.n_offspring = Some(n_offspring),
+            _ => {
+                eprintln!("--n_offspring must be a positive number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(n_islands_str) = args.value_of("n_islands") {
+        match usize::from_str(n_islands_str) {
+            Ok(n_islands) if n_islands > 0 => options.n_islands = n_islands,
+            _ => {
+                eprintln!("--n_islands must be a positive number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(interval_str) = args.value_of("migration_interval") {
+        match usize::from_str(interval_str) {
+            Ok(interval) if interval > 0 => options.migration_interval = interval,
+            _ => {
+                eprintln!("--migration_interval must be a positive number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(mutation_prob_str) = args.value_of("mutation_probability") {
+        match f32::from_str(mutation_prob_str) {
+            Ok(value) => options.mutation_probability = value,
+            _ => {
+                eprintln!("--mutation_probability must be a number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(cooling_rate_str) = args.value_of("cooling_rate") {
+        match f32::from_str(cooling_rate_str) {
+            Ok(value) => options.cooling_rate = value,
+            _ => {
+                eprintln!("--cooling_rate must be a number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(min_temperature_str) = args.value_of("min_temperature") {
+        match f32::from_str(min_temperature_str) {
+            Ok(value) => options.min_temperature = value,
+            _ => {
+                eprintln!("--min_temperature must be a number");
+                std::process::exit(1);
+            }
+        }
     }
 
     if let Some(max_temperature_str) = args.value_of("max_temperature") {
-        options.max_temperature = f32::from_str(max_temperature_str).unwrap_or(0.0);
+        match f32::from_str(max_temperature_str) {
+            Ok(value) => options.max_temperature = value,
+            _ => {
+                eprintln!("--max_temperature must be a number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(neighborhood) = args
+        .value_of("neighborhood")
+        .and_then(|val| route::Neighborhood::from_str(val).ok())
+    {
+        options.neighborhood = neighborhood;
+    }
+
+    if let Some(schedule) = args
+        .value_of("cooling_schedule")
+        .and_then(|val| simulated_annealing::CoolingSchedule::from_str(val).ok())
+    {
+        options.cooling_schedule = schedule;
+    }
+
+    if let Some(reheat_str) = args.value_of("reheat") {
+        match f32::from_str(reheat_str) {
+            Ok(reheat) if reheat > 0.0 && reheat <= 1.0 => options.reheat = Some(reheat),
+            _ => {
+                eprintln!("--reheat must be a fraction in (0, 1]");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(n_epochs_str) = args.value_of("restart_from_best") {
+        match usize::from_str(n_epochs_str) {
+            Ok(n_epochs) if n_epochs > 0 => options.restart_from_best = Some(n_epochs),
+            _ => {
+                eprintln!("--restart_from_best must be a positive number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if args.is_present("no_aspiration") {
+        options.aspiration = false;
+    }
+
+    if let Some(max_seconds_str) = args.value_of("max_seconds") {
+        match f32::from_str(max_seconds_str) {
+            Ok(max_seconds) if max_seconds > 0.0 => options.max_seconds = Some(max_seconds),
+            _ => {
+                eprintln!("--max_seconds must be a positive number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(target_cost_str) = args.value_of("target_cost") {
+        match f32::from_str(target_cost_str) {
+            Ok(target_cost) => options.target_cost = Some(target_cost),
+            Err(_) => {
+                eprintln!("--target_cost must be a number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(n_epochs_str) = args.value_of("max_stale_epochs") {
+        match usize::from_str(n_epochs_str) {
+            Ok(n_epochs) if n_epochs > 0 => options.max_stale_epochs = Some(n_epochs),
+            _ => {
+                eprintln!("--max_stale_epochs must be a positive number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(max_nodes_str) = args.value_of("max_nodes") {
+        match usize::from_str(max_nodes_str) {
+            Ok(max_nodes) => options.max_nodes = Some(max_nodes),
+            Err(_) => {
+                eprintln!("--max_nodes must be a non-negative number");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(max_memory_str) = args.value_of("max_memory") {
+        match usize::from_str(max_memory_str) {
+            Ok(max_memory) => options.max_memory_mb = max_memory,
+            Err(_) => {
+                eprintln!("--max_memory must be a non-negative number of megabytes");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(seed_str) = args.value_of("seed") {
+        match u64::from_str(seed_str) {
+            Ok(seed) => options.seed = Some(seed),
+            Err(_) => {
+                eprintln!("--seed must be a non-negative number");
+                std::process::exit(1);
+            }
+        }
     }
 
     options
 }
+
+/// stops when a flag of another solver is given, the solvers would silently ignore it
+fn reject_unused_flags(args: &ArgMatches, solvers: &[SolverInfo]) {
+    for option in registry::catalog()
+        .iter()
+        .flat_map(|info| info.options.iter())
+        .filter(|option| !tsp::COMMON_OPTIONS.contains(&option.as_str()))
+    {
+        let flag = option_flag(option);
+        if args.occurrences_of(flag) > 0 && !solvers.iter().any(|s| s.uses_option(option)) {
+            let names: Vec<&str> = solvers.iter().map(|s| s.name.as_str()).collect();
+            eprintln!(
+                "--{} doesn't apply to {}, `teeline solvers` lists the options of the solvers",
+                flag,
+                names.join(", ")
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+// the flag of the option, when it's named differently on the command-line
+fn option_flag(option: &str) -> &str {
+    match option {
+        "aspiration" => "no_aspiration",
+        option => option,
+    }
+}
+
+/// stops with the message when the options are out of the range the solver accepts
+fn validate_options(options: &SolverOptions, solver: &SolverInfo) {
+    if let Err(err) = options.validate(solver) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,851 @@
+/// Adaptive Large Neighborhood Search
+///
+/// Every epoch removes a part of the cities from the tour with one of the destroy operators and
+/// inserts them back with one of the repair operators. The operators are picked by the roulette
+/// over their weights, which follow how often the operators found new tours: after every segment
+/// of epochs the weight moves towards the average score of the operator in the segment.
+///
+/// The new tour is accepted like in the simulated annealing: the temperature starts so that
+/// a tour 5% longer is accepted with the probability of 1/2 and it cools down over the epochs.
+///
+/// The operators work on the tour as the linked list of the cities, so the removal and
+/// the insertion take O(1) and the insertion looks for the place only next to the `n_nearest`
+/// cities; the new operators implement `DestroyOperator` or `RepairOperator`.
+///
+/// source: Ropke & Pisinger, "An Adaptive Large Neighborhood Search Heuristic for the Pickup
+/// and Delivery Problem with Time Windows", https://doi.org/10.1287/trsc.1050.0135
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::distance_matrix::{DistanceSource, LazyDistances};
+use super::history::History;
+use super::kdtree::KDPoint;
+use super::neighbors;
+use super::progress::{self, ProgressMessage, PublisherFn};
+use super::registry::Category;
+use super::route::Route;
+use super::{validate_cities, Error, Solution, SolverInfo, SolverOptions, TspSolver};
+
+// the share of the cities removed at most, and the upper limit of the removed cities
+const MAX_REMOVED_SHARE: f32 = 0.3;
+const MAX_REMOVED: usize = 50;
+// how strongly the worst and the Shaw removal prefer the first cities of their order
+const WORST_RANDOMNESS: i32 = 3;
+const SHAW_RANDOMNESS: i32 = 6;
+// the scores of the operators for the new best tour, a better tour and an accepted worse tour
+const SCORE_BEST: f32 = 33.0;
+const SCORE_BETTER: f32 = 9.0;
+const SCORE_ACCEPTED: f32 = 13.0;
+// the weights are updated after so many epochs, by this share of the scores
+const SEGMENT_EPOCHS: usize = 100;
+const REACTION: f32 = 0.1;
+// a tour 5% longer is accepted with the probability of 1/2 at the start
+const START_WORSENING: f32 = 0.05;
+const END_TEMPERATURE_SHARE: f32 = 0.001;
+
+/// the neighbor lists the insertions of ALNS look at
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlnsOptions {
+    pub n_nearest: usize, // size of the neighbor lists of the insertions, 0 turns them off
+}
+
+impl AlnsOptions {
+    /// copies the options into the options the solver runs with
+    pub fn apply(&self, options: &mut SolverOptions) {
+        options.n_nearest = self.n_nearest;
+    }
+}
+
+impl Default for AlnsOptions {
+    fn default() -> Self {
+        AlnsOptions::from(&SolverOptions::default())
+    }
+}
+
+impl From<&SolverOptions> for AlnsOptions {
+    fn from(options: &SolverOptions) -> Self {
+        AlnsOptions {
+            n_nearest: options.n_nearest,
+        }
+    }
+}
+
+pub struct AlnsSolver;
+
+impl TspSolver for AlnsSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "alns",
+            &["adaptive_large_neighborhood_search"],
+            "removes and reinserts parts of the tour with adaptively weighted operators",
+            true,
+        )
+        .with_category(Category::Metaheuristic)
+        .with_complexity("O(epochs * k^2)")
+        .with_options(&["epochs", "n_nearest", "max_seconds", "target_cost", "seed"])
+    }
+
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 3)?;
+
+        let mut rng = options.rng();
+        let deadline = options.deadline();
+        let problem = Problem::new(cities, options.n_nearest)?;
+
+        let index_of: HashMap<usize, usize> =
+            cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+        let start: Vec<usize> = options
+            .start_route(cities)?
+            .route()
+            .iter()
+            .map(|id| index_of[id])
+            .collect();
+
+        let mut current = Tour::new(&start, &problem)?;
+        let mut best = current.clone();
+        let mut history = History::new();
+        history.record(0, best.length);
+        progress(ProgressMessage::PathUpdate(
+            Route::new(&problem.city_ids(&best.positions())),
+            best.length,
+        ));
+
+        let destroy_operators: Vec<Box<dyn DestroyOperator>> = vec![
+            Box::new(RandomSegmentRemoval),
+            Box::new(WorstRemoval),
+            Box::new(ShawRemoval),
+        ];
+        let repair_operators: Vec<Box<dyn RepairOperator>> =
+            vec![Box::new(GreedyInsertion), Box::new(RegretInsertion)];
+        let mut destroy_weights = OperatorWeights::new(destroy_operators.len());
+        let mut repair_weights = OperatorWeights::new(repair_operators.len());
+
+        let max_removed = ((cities.len() as f32 * MAX_REMOVED_SHARE) as usize)
+            .min(MAX_REMOVED)
+            .clamp(1, cities.len() - 2);
+        let mut temperature = START_WORSENING * current.length / 2.0_f32.ln();
+        let cooling_rate = END_TEMPERATURE_SHARE.powf(1.0 / options.epochs.max(1) as f32);
+
+        let mut epoch = 0;
+        while epoch < options.epochs
+            && !deadline.is_over()
+            && !options.is_target_reached(best.length)
+        {
+            let destroy = destroy_weights.select(&mut rng);
+            let repair = repair_weights.select(&mut rng);
+            let n_removed = rng.gen_range(1, max_removed + 1);
+
+            let mut candidate = current.clone();
+            let removed = destroy_operators[destroy].destroy(
+                &mut candidate,
+                &problem,
+                n_removed,
+                &mut rng,
+            )?;
+            repair_operators[repair].repair(&mut candidate, &problem, removed, &mut rng)?;
+
+            let score = if candidate.length < best.length {
+                // the deltas accumulate rounding errors, the best one gets the exact length
+                candidate.length = problem.tour_length(&candidate.positions())?;
+                if candidate.length < best.length {
+                    best = candidate.clone();
+                    history.record(epoch, best.length);
+                    progress(ProgressMessage::PathUpdate(
+                        Route::new(&problem.city_ids(&best.positions())),
+                        best.length,
+                    ));
+
+                    if options.verbose {
+                        println!(
+                            "ALNS: epoch.{:?} new best distance: {:?}",
+                            epoch, best.length
+                        );
+                    }
+                }
+
+                Some(SCORE_BEST)
+            } else if candidate.length < current.length {
+                Some(SCORE_BETTER)
+            } else if is_acceptable(temperature, current.length, candidate.length, &mut rng) {
+                Some(SCORE_ACCEPTED)
+            } else {
+                None
+            };
+
+            destroy_weights.reward(destroy, score.unwrap_or(0.0));
+            repair_weights.reward(repair, score.unwrap_or(0.0));
+            if score.is_some() {
+                current = candidate;
+            }
+
+            epoch += 1;
+            temperature *= cooling_rate;
+            if epoch.is_multiple_of(SEGMENT_EPOCHS) {
+                destroy_weights.update();
+                repair_weights.update();
+            }
+            progress::report_epoch(&progress, epoch);
+        }
+
+        if options.verbose {
+            for (operator, weight) in destroy_operators.iter().zip(destroy_weights.weights.iter()) {
+                println!("ALNS: destroy {} weight {:.2}", operator.name(), weight);
+            }
+            for (operator, weight) in repair_operators.iter().zip(repair_weights.weights.iter()) {
+                println!("ALNS: repair {} weight {:.2}", operator.name(), weight);
+            }
+        }
+
+        progress(ProgressMessage::Done);
+        let route = problem.city_ids(&best.positions());
+        Ok(Solution::new(&route, cities)
+            .with_target(options)
+            .with_history(history))
+    }
+}
+
+/// solves the problem and sends the progress into the global progress channel
+pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Result<Solution, Error> {
+    AlnsSolver.solve(cities, options, progress::default_publisher())
+}
+
+fn is_acceptable(temperature: f32, old_distance: f32, new_distance: f32, rng: &mut StdRng) -> bool {
+    if temperature <= 0.0 {
+        return false;
+    }
+
+    let p: f32 = rng.gen();
+    p < (-(new_distance - old_distance) / temperature).exp()
+}
+
+/// the distances and the nearest cities, by the positions of the cities
+pub struct Problem {
+    dm: Arc<dyn DistanceSource>,
+    city_ids: Vec<usize>,
+    neighbors: Option<Vec<Vec<usize>>>, // without them every place of the tour is tried
+}
+
+impl Problem {
+    pub fn new(cities: &[KDPoint], n_nearest: usize) -> Result<Self, Error> {
+        let neighbors = if n_nearest > 0 {
+            Some(neighbors::build_lists(cities, n_nearest)?)
+        } else {
+            None
+        };
+
+        Ok(Problem {
+            // the lookups jump around the large matrix, computing the distances is faster
+            dm: Arc::new(LazyDistances::from_cities(cities)?),
+            city_ids: cities.iter().map(|c| c.id).collect(),
+            neighbors,
+        })
+    }
+
+    pub fn n_cities(&self) -> usize {
+        self.city_ids.len()
+    }
+
+    pub fn distance(&self, pos1: usize, pos2: usize) -> Result<f32, Error> {
+        self.dm.distance_by_pos(pos1, pos2)
+    }
+
+    pub fn neighbors(&self, pos: usize) -> &[usize] {
+        match &self.neighbors {
+            Some(lists) => &lists[pos],
+            None => &[],
+        }
+    }
+
+    pub fn has_neighbors(&self) -> bool {
+        self.neighbors.is_some()
+    }
+
+    fn city_ids(&self, positions: &[usize]) -> Vec<usize> {
+        positions.iter().map(|pos| self.city_ids[*pos]).collect()
+    }
+
+    fn tour_length(&self, positions: &[usize]) -> Result<f32, Error> {
+        self.dm.tour_length(&self.city_ids(positions))
+    }
+}
+
+/// the tour as the doubly linked list of the city positions
+#[derive(Debug, Clone)]
+pub struct Tour {
+    next: Vec<usize>,
+    prev: Vec<usize>,
+    in_tour: Vec<bool>,
+    head: usize, // one of the cities in the tour
+    len: usize,
+    length: f32,
+}
+
+impl Tour {
+    pub fn new(positions: &[usize], problem: &Problem) -> Result<Self, Error> {
+        let n = problem.n_cities();
+        let mut tour = Tour {
+            next: vec![0; n],
+            prev: vec![0; n],
+            in_tour: vec![false; n],
+            head: positions.first().copied().unwrap_or(0),
+            len: positions.len(),
+            length: problem.tour_length(positions)?,
+        };
+
+        for (i, pos) in positions.iter().enumerate() {
+            let next = positions[(i + 1) % positions.len()];
+            tour.next[*pos] = next;
+            tour.prev[next] = *pos;
+            tour.in_tour[*pos] = true;
+        }
+
+        Ok(tour)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    pub fn contains(&self, pos: usize) -> bool {
+        self.in_tour[pos]
+    }
+
+    pub fn next(&self, pos: usize) -> usize {
+        self.next[pos]
+    }
+
+    pub fn prev(&self, pos: usize) -> usize {
+        self.prev[pos]
+    }
+
+    /// the positions of the cities in the order of the tour
+    pub fn positions(&self) -> Vec<usize> {
+        let mut positions = Vec::with_capacity(self.len);
+        let mut pos = self.head;
+        for _ in 0..self.len {
+            positions.push(pos);
+            pos = self.next[pos];
+        }
+
+        positions
+    }
+
+    /// how much shorter the tour gets without the city
+    pub fn removal_gain(&self, pos: usize, problem: &Problem) -> Result<f32, Error> {
+        let (prev, next) = (self.prev[pos], self.next[pos]);
+
+        Ok(problem.distance(prev, pos)? + problem.distance(pos, next)?
+            - problem.distance(prev, next)?)
+    }
+
+    /// how much longer the tour gets with the city inserted after the city `after`
+    pub fn insertion_cost(
+        &self,
+        after: usize,
+        pos: usize,
+        problem: &Problem,
+    ) -> Result<f32, Error> {
+        let next = self.next[after];
+
+        Ok(problem.distance(after, pos)? + problem.distance(pos, next)?
+            - problem.distance(after, next)?)
+    }
+
+    pub fn remove(&mut self, pos: usize, problem: &Problem) -> Result<(), Error> {
+        if !self.in_tour[pos] {
+            return Ok(());
+        }
+
+        self.length -= self.removal_gain(pos, problem)?;
+        let (prev, next) = (self.prev[pos], self.next[pos]);
+        self.next[prev] = next;
+        self.prev[next] = prev;
+        self.in_tour[pos] = false;
+        self.len -= 1;
+        if self.head == pos {
+            self.head = next;
+        }
+
+        Ok(())
+    }
+
+    pub fn insert_after(
+        &mut self,
+        after: usize,
+        pos: usize,
+        problem: &Problem,
+    ) -> Result<(), Error> {
+        self.length += self.insertion_cost(after, pos, problem)?;
+        let next = self.next[after];
+        self.next[after] = pos;
+        self.prev[pos] = after;
+        self.next[pos] = next;
+        self.prev[next] = pos;
+        self.in_tour[pos] = true;
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// the two cheapest places of the city as (cost, city to insert after), cheapest first;
+    /// the places are next to the nearest cities in the tour, none if they are all removed;
+    /// without the lists of the nearest cities it tries every place
+    pub fn best_insertions(
+        &self,
+        pos: usize,
+        problem: &Problem,
+    ) -> Result<[(f32, Option<usize>); 2], Error> {
+        if !problem.has_neighbors() {
+            return self.best_insertions_anywhere(pos, problem);
+        }
+
+        let places = problem
+            .neighbors(pos)
+            .iter()
+            .filter(|neighbor| self.in_tour[**neighbor])
+            .flat_map(|neighbor| [self.prev[*neighbor], *neighbor]);
+
+        self.cheapest_places(pos, problem, places)
+    }
+
+    /// the two cheapest places of the city anywhere in the tour, it takes O(n)
+    pub fn best_insertions_anywhere(
+        &self,
+        pos: usize,
+        problem: &Problem,
+    ) -> Result<[(f32, Option<usize>); 2], Error> {
+        self.cheapest_places(pos, problem, self.positions().into_iter())
+    }
+
+    fn cheapest_places(
+        &self,
+        pos: usize,
+        problem: &Problem,
+        places: impl Iterator<Item = usize>,
+    ) -> Result<[(f32, Option<usize>); 2], Error> {
+        let mut best = [(f32::INFINITY, None), (f32::INFINITY, None)];
+        for after in places {
+            if best.iter().any(|(_, place)| *place == Some(after)) {
+                continue;
+            }
+
+            let cost = self.insertion_cost(after, pos, problem)?;
+            if cost < best[0].0 {
+                best[1] = best[0];
+                best[0] = (cost, Some(after));
+            } else if cost < best[1].0 {
+                best[1] = (cost, Some(after));
+            }
+        }
+
+        Ok(best)
+    }
+
+    // a random city of the tour
+    fn random_city(&self, rng: &mut StdRng) -> usize {
+        loop {
+            let pos = rng.gen_range(0, self.in_tour.len());
+            if self.in_tour[pos] {
+                return pos;
+            }
+        }
+    }
+}
+
+/// removes the cities from the tour and returns them
+pub trait DestroyOperator {
+    fn name(&self) -> &'static str;
+
+    fn destroy(
+        &self,
+        tour: &mut Tour,
+        problem: &Problem,
+        n_removed: usize,
+        rng: &mut StdRng,
+    ) -> Result<Vec<usize>, Error>;
+}
+
+/// inserts the removed cities back into the tour
+pub trait RepairOperator {
+    fn name(&self) -> &'static str;
+
+    fn repair(
+        &self,
+        tour: &mut Tour,
+        problem: &Problem,
+        removed: Vec<usize>,
+        rng: &mut StdRng,
+    ) -> Result<(), Error>;
+}
+
+/// removes the consecutive cities of the tour from a random city
+pub struct RandomSegmentRemoval;
+
+impl DestroyOperator for RandomSegmentRemoval {
+    fn name(&self) -> &'static str {
+        "random_segment"
+    }
+
+    fn destroy(
+        &self,
+        tour: &mut Tour,
+        problem: &Problem,
+        n_removed: usize,
+        rng: &mut StdRng,
+    ) -> Result<Vec<usize>, Error> {
+        let mut removed = Vec::with_capacity(n_removed);
+        let mut pos = tour.random_city(rng);
+        for _ in 0..n_removed {
+            let next = tour.next(pos);
+            tour.remove(pos, problem)?;
+            removed.push(pos);
+            pos = next;
+        }
+
+        Ok(removed)
+    }
+}
+
+/// removes the cities, which cost the most: the ones between the long edges
+pub struct WorstRemoval;
+
+impl DestroyOperator for WorstRemoval {
+    fn name(&self) -> &'static str {
+        "worst"
+    }
+
+    fn destroy(
+        &self,
+        tour: &mut Tour,
+        problem: &Problem,
+        n_removed: usize,
+        rng: &mut StdRng,
+    ) -> Result<Vec<usize>, Error> {
+        let mut gains = tour
+            .positions()
+            .into_iter()
+            .map(|pos| Ok((tour.removal_gain(pos, problem)?, pos)))
+            .collect::<Result<Vec<(f32, usize)>, Error>>()?;
+        gains.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        // the gains are not updated after the removals, the randomness covers the difference
+        let mut removed = Vec::with_capacity(n_removed);
+        for _ in 0..n_removed {
+            let (_, pos) = gains.remove(randomized_index(gains.len(), WORST_RANDOMNESS, rng));
+            tour.remove(pos, problem)?;
+            removed.push(pos);
+        }
+
+        Ok(removed)
+    }
+}
+
+/// removes the cities related to each other, i.e. close to each other,
+/// so the repair can put them into a different order
+pub struct ShawRemoval;
+
+impl DestroyOperator for ShawRemoval {
+    fn name(&self) -> &'static str {
+        "shaw"
+    }
+
+    fn destroy(
+        &self,
+        tour: &mut Tour,
+        problem: &Problem,
+        n_removed: usize,
+        rng: &mut StdRng,
+    ) -> Result<Vec<usize>, Error> {
+        let first = tour.random_city(rng);
+        tour.remove(first, problem)?;
+        let mut removed = vec![first];
+
+        while removed.len() < n_removed {
+            let related_to = removed[rng.gen_range(0, removed.len())];
+            let related: Vec<usize> = problem
+                .neighbors(related_to)
+                .iter()
+                .copied()
+                .filter(|pos| tour.contains(*pos))
+                .collect();
+
+            let pos = if related.is_empty() {
+                tour.random_city(rng)
+            } else {
+                related[randomized_index(related.len(), SHAW_RANDOMNESS, rng)]
+            };
+            tour.remove(pos, problem)?;
+            removed.push(pos);
+        }
+
+        Ok(removed)
+    }
+}
+
+/// inserts the city with the cheapest place first
+pub struct GreedyInsertion;
+
+impl RepairOperator for GreedyInsertion {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn repair(
+        &self,
+        tour: &mut Tour,
+        problem: &Problem,
+        removed: Vec<usize>,
+        _rng: &mut StdRng,
+    ) -> Result<(), Error> {
+        insert_all(tour, problem, removed, |[best, _]| -best.0)
+    }
+}
+
+/// inserts the city first, which would lose the most if it didn't get its cheapest place:
+/// the difference between its two cheapest places is the largest
+pub struct RegretInsertion;
+
+impl RepairOperator for RegretInsertion {
+    fn name(&self) -> &'static str {
+        "regret"
+    }
+
+    fn repair(
+        &self,
+        tour: &mut Tour,
+        problem: &Problem,
+        removed: Vec<usize>,
+        _rng: &mut StdRng,
+    ) -> Result<(), Error> {
+        insert_all(tour, problem, removed, |[best, second]| second.0 - best.0)
+    }
+}
+
+// inserts the cities one by one, always the city with the highest priority to its cheapest place
+fn insert_all(
+    tour: &mut Tour,
+    problem: &Problem,
+    mut removed: Vec<usize>,
+    priority: impl Fn([(f32, Option<usize>); 2]) -> f32,
+) -> Result<(), Error> {
+    let mut places = removed
+        .iter()
+        .map(|pos| tour.best_insertions(*pos, problem))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    while !removed.is_empty() {
+        let mut selected: Option<(f32, usize, usize)> = None;
+        for (i, city_places) in places.iter().enumerate() {
+            let after = match city_places[0].1 {
+                Some(after) => after,
+                None => continue,
+            };
+
+            let value = priority(*city_places);
+            if selected.is_none_or(|(best_value, _, _)| value > best_value) {
+                selected = Some((value, i, after));
+            }
+        }
+
+        // the nearest cities of every removed city are removed too, one of them goes anywhere
+        let (i, after) = match selected {
+            Some((_, i, after)) => (i, after),
+            None => {
+                places[0] = tour.best_insertions_anywhere(removed[0], problem)?;
+                let after = places[0][0].1.ok_or_else(|| {
+                    Error::InvalidInput("alns: no place in the tour to insert the city".to_string())
+                })?;
+
+                (0, after)
+            }
+        };
+        let pos = removed.swap_remove(i);
+        places.swap_remove(i);
+        let before = tour.next(after);
+        tour.insert_after(after, pos, problem)?;
+
+        // only the places next to the cities around the new one have changed
+        for (other, other_places) in removed.iter().zip(places.iter_mut()) {
+            let is_affected = !problem.has_neighbors()
+                || problem
+                    .neighbors(*other)
+                    .iter()
+                    .any(|n| *n == after || *n == before || *n == pos);
+
+            if is_affected {
+                *other_places = tour.best_insertions(*other, problem)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// picks the index of the ordered list, the higher the randomness the more it prefers the first
+fn randomized_index(len: usize, randomness: i32, rng: &mut StdRng) -> usize {
+    let y: f32 = rng.gen();
+
+    ((y.powi(randomness) * len as f32) as usize).min(len - 1)
+}
+
+// the roulette over the weights of the operators, which follow their scores
+struct OperatorWeights {
+    weights: Vec<f32>,
+    scores: Vec<f32>,
+    n_used: Vec<usize>,
+}
+
+impl OperatorWeights {
+    fn new(n_operators: usize) -> Self {
+        OperatorWeights {
+            weights: vec![1.0; n_operators],
+            scores: vec![0.0; n_operators],
+            n_used: vec![0; n_operators],
+        }
+    }
+
+    fn select(&self, rng: &mut StdRng) -> usize {
+        let total: f32 = self.weights.iter().sum();
+        let mut ball = rng.gen::<f32>() * total;
+        for (i, weight) in self.weights.iter().enumerate() {
+            if ball < *weight {
+                return i;
+            }
+            ball -= weight;
+        }
+
+        self.weights.len() - 1
+    }
+
+    fn reward(&mut self, operator: usize, score: f32) {
+        self.scores[operator] += score;
+        self.n_used[operator] += 1;
+    }
+
+    // the end of the segment, the operators which were not used keep their weights
+    fn update(&mut self) {
+        for i in 0..self.weights.len() {
+            if self.n_used[i] > 0 {
+                self.weights[i] = self.weights[i] * (1.0 - REACTION)
+                    + REACTION * self.scores[i] / self.n_used[i] as f32;
+            }
+            self.scores[i] = 0.0;
+            self.n_used[i] = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::{generator, kdtree, total_distance};
+    use rand::SeedableRng;
+
+    fn random_problem(n: usize) -> (Vec<KDPoint>, Problem) {
+        let bbox = generator::BoundingBox::square(100.0);
+        let cities = generator::uniform(n, &bbox, &mut StdRng::seed_from_u64(11));
+        let problem = Problem::new(&cities, 8).unwrap();
+
+        (cities, problem)
+    }
+
+    #[test]
+    fn test_operators_keep_the_tour_length() {
+        let (cities, problem) = random_problem(60);
+        let positions: Vec<usize> = (0..cities.len()).collect();
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let destroy_operators: Vec<Box<dyn DestroyOperator>> = vec![
+            Box::new(RandomSegmentRemoval),
+            Box::new(WorstRemoval),
+            Box::new(ShawRemoval),
+        ];
+        let repair_operators: Vec<Box<dyn RepairOperator>> =
+            vec![Box::new(GreedyInsertion), Box::new(RegretInsertion)];
+
+        for destroy in destroy_operators.iter() {
+            for repair in repair_operators.iter() {
+                let mut tour = Tour::new(&positions, &problem).unwrap();
+                let removed = destroy.destroy(&mut tour, &problem, 15, &mut rng).unwrap();
+
+                assert_eq!(15, removed.len(), "{}", destroy.name());
+                assert_eq!(45, tour.len());
+                assert!(removed.iter().all(|pos| !tour.contains(*pos)));
+                let length = problem.tour_length(&tour.positions()).unwrap();
+                assert!((length - tour.length()).abs() < 0.01);
+
+                repair
+                    .repair(&mut tour, &problem, removed, &mut rng)
+                    .unwrap();
+
+                let mut visited = tour.positions();
+                let length = problem.tour_length(&visited).unwrap();
+                assert!((length - tour.length()).abs() < 0.01, "{}", repair.name());
+                visited.sort();
+                assert_eq!(positions, visited);
+            }
+        }
+    }
+
+    #[test]
+    fn test_regret_insertion_looks_at_every_place_without_neighbors() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 10.0],
+            vec![10.0, 10.0],
+            vec![10.0, 0.0],
+            vec![5.0, 11.0],
+        ]);
+        let problem = Problem::new(&cities, 0).unwrap();
+        let mut tour = Tour::new(&[0, 1, 2, 3], &problem).unwrap();
+
+        RegretInsertion
+            .repair(&mut tour, &problem, vec![4], &mut StdRng::seed_from_u64(1))
+            .unwrap();
+        assert_eq!(vec![0, 1, 4, 2, 3], tour.positions());
+    }
+
+    #[test]
+    fn test_solve_improves_the_start_route() {
+        let (cities, _) = random_problem(100);
+        let start_distance = total_distance(&cities, Route::from_cities(&cities).route());
+
+        let mut options = SolverOptions::default();
+        options.epochs = 1_000;
+        options.seed = Some(42);
+
+        let tour = solve(&cities, &options).unwrap();
+        assert_eq!(Ok(()), tour.validate());
+        assert!(tour.total < start_distance / 3.0);
+
+        // the same seed finds the same tour
+        assert_eq!(tour.route(), solve(&cities, &options).unwrap().route());
+    }
+
+    #[test]
+    fn test_solve_with_three_cities() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![0.0, 1.0], vec![1.0, 0.0]]);
+        let mut options = SolverOptions::default();
+        options.epochs = 10;
+        options.seed = Some(1);
+
+        let tour = solve(&cities, &options).unwrap();
+        assert_eq!(Ok(()), tour.validate());
+        assert_eq!(3, tour.route().len());
+    }
+}
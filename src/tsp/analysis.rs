@@ -0,0 +1,154 @@
+/// Analysis of the tours
+///
+/// The edges of the tour, which cross each other, show how far the tour is from the 2-opt
+/// optimum: every crossing can be removed by reversing the route between the edges, so the
+/// 2-opt tour of the euclidean problem has none of them.
+///
+/// The crossings are found with a sweep over the x axis: the edges are sorted by their left
+/// end and every edge is tested only against the edges, which still overlap it on the x axis.
+use std::cmp::Ordering;
+
+use super::city_table_from_vec;
+use super::kdtree::KDPoint;
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    from: (f64, f64),
+    to: (f64, f64),
+    position: usize, // position of the edge in the route
+}
+
+impl Segment {
+    fn min_x(&self) -> f64 {
+        self.from.0.min(self.to.0)
+    }
+
+    fn max_x(&self) -> f64 {
+        self.from.0.max(self.to.0)
+    }
+}
+
+/// the number of the pairs of the tour edges, which cross each other;
+/// the edges sharing a city and the edges only touching each other are not counted
+pub fn count_crossings(route: &[usize], cities: &[KDPoint]) -> usize {
+    let n_edges = route.len();
+    if n_edges < 4 {
+        return 0;
+    }
+
+    let cities_table = city_table_from_vec(cities);
+    let point = |id: &usize| {
+        let city = &cities_table[id];
+        (city.x(), city.y())
+    };
+
+    let mut segments: Vec<Segment> = (0..n_edges)
+        .map(|i| Segment {
+            from: point(&route[i]),
+            to: point(&route[(i + 1) % n_edges]),
+            position: i,
+        })
+        .collect();
+    segments.sort_by(|a, b| a.min_x().partial_cmp(&b.min_x()).unwrap_or(Ordering::Equal));
+
+    let mut n_crossings = 0;
+    let mut active: Vec<Segment> = vec![];
+    for segment in segments {
+        let sweep_x = segment.min_x();
+        active.retain(|other| other.max_x() >= sweep_x);
+
+        n_crossings += active
+            .iter()
+            .filter(|other| !are_adjacent(segment.position, other.position, n_edges))
+            .filter(|other| segments_cross(&segment, other))
+            .count();
+
+        active.push(segment);
+    }
+
+    n_crossings
+}
+
+fn are_adjacent(a: usize, b: usize, n_edges: usize) -> bool {
+    (a + 1) % n_edges == b || (b + 1) % n_edges == a
+}
+
+// the sign of the turn from a over b to c
+fn orientation(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+// true when the segments cross in a point, which is inside both of them
+fn segments_cross(a: &Segment, b: &Segment) -> bool {
+    let d1 = orientation(a.from, a.to, b.from);
+    let d2 = orientation(a.from, a.to, b.to);
+    let d3 = orientation(b.from, b.to, a.from);
+    let d4 = orientation(b.from, b.to, a.to);
+
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    fn square() -> Vec<KDPoint> {
+        kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.0],
+        ])
+    }
+
+    #[test]
+    fn test_count_crossings_of_square() {
+        let cities = square();
+
+        assert_eq!(0, count_crossings(&[0, 1, 2, 3], &cities));
+        // the diagonals cross in the middle of the square
+        assert_eq!(1, count_crossings(&[0, 2, 1, 3], &cities));
+        assert_eq!(0, count_crossings(&[0, 1, 2], &cities));
+        assert_eq!(0, count_crossings(&[], &cities));
+    }
+
+    #[test]
+    fn test_count_crossings_matches_all_pairs() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![3.0, 4.0],
+            vec![6.0, 1.0],
+            vec![1.0, 5.0],
+            vec![5.0, 5.0],
+            vec![2.0, 1.0],
+            vec![4.0, 0.0],
+            vec![6.0, 3.0],
+        ]);
+        let route = vec![0, 4, 5, 7, 3, 6, 1, 2];
+
+        let points: Vec<(f64, f64)> = route
+            .iter()
+            .map(|id| (cities[*id].x(), cities[*id].y()))
+            .collect();
+        let edges: Vec<Segment> = (0..points.len())
+            .map(|i| Segment {
+                from: points[i],
+                to: points[(i + 1) % points.len()],
+                position: i,
+            })
+            .collect();
+
+        let mut expected = 0;
+        for i in 0..edges.len() {
+            for j in (i + 1)..edges.len() {
+                if !are_adjacent(i, j, edges.len()) && segments_cross(&edges[i], &edges[j]) {
+                    expected += 1;
+                }
+            }
+        }
+
+        assert!(expected > 0);
+        assert_eq!(expected, count_crossings(&route, &cities));
+    }
+}
@@ -0,0 +1,247 @@
+/// Batch solving
+///
+/// It solves every input file with the same solver and options, one after another
+/// or on a pool of threads, and summarizes the results per file. A file, which can't be read
+/// or solved, is reported in the summary and the rest of the batch continues.
+use rayon::prelude::*;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use super::kdtree::KDPoint;
+use super::{remote, tsplib, Error, Solution, SolverOptions};
+
+const GLOB_CHARS: &[char] = &['*', '?', '['];
+
+/// expands the glob patterns, e.g `data/tsplib/*.tsp`, into the sorted lists of files;
+/// the paths without the pattern characters are kept as they are and the URLs are replaced
+/// with the paths of their cached copies
+pub fn expand_inputs(patterns: &[&str]) -> Result<Vec<PathBuf>, Error> {
+    let mut paths = vec![];
+
+    for pattern in patterns.iter() {
+        if remote::is_url(pattern) {
+            paths.push(remote::fetch(pattern)?);
+            continue;
+        }
+
+        if !pattern.contains(GLOB_CHARS) {
+            paths.push(PathBuf::from(pattern));
+            continue;
+        }
+
+        let entries = glob::glob(pattern).map_err(|err| {
+            Error::InvalidInput(format!("batch: invalid pattern {}: {}", pattern, err))
+        })?;
+        let mut matches: Vec<PathBuf> = entries.filter_map(Result::ok).collect();
+        if matches.is_empty() {
+            return Err(Error::InvalidInput(format!(
+                "batch: no files match {}",
+                pattern
+            )));
+        }
+
+        matches.sort();
+        paths.extend(matches);
+    }
+
+    Ok(paths)
+}
+
+pub struct BatchResult {
+    pub path: PathBuf,
+    pub n_cities: usize,
+    pub outcome: Result<Solution, Error>,
+    pub seconds: f64,
+}
+
+impl BatchResult {
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.path.to_string_lossy().to_string())
+    }
+}
+
+#[derive(Default)]
+pub struct BatchReport {
+    pub results: Vec<BatchResult>, // in the order of the input files
+}
+
+impl BatchReport {
+    pub fn n_solved(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|res| res.outcome.is_ok())
+            .count()
+    }
+
+    pub fn n_failed(&self) -> usize {
+        self.results.len() - self.n_solved()
+    }
+}
+
+/// solves the files with `solve_fn`, on `n_threads` threads if it is more than 1;
+/// after the cancellation the files, which have not started yet, are reported as skipped
+pub fn run<F>(
+    paths: &[PathBuf],
+    solve_fn: F,
+    options: &SolverOptions,
+    n_threads: usize,
+) -> Result<BatchReport, Error>
+where
+    F: Fn(&[KDPoint], &SolverOptions) -> Result<Solution, Error> + Sync,
+{
+    let solve = |path: &PathBuf| solve_file(path, &solve_fn, options);
+
+    let results = if n_threads > 1 {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(n_threads)
+            .build()
+            .map_err(|e| Error::Runtime(format!("batch: failed to start threads: {}", e)))?;
+
+        pool.install(|| paths.par_iter().map(solve).collect())
+    } else {
+        paths.iter().map(solve).collect()
+    };
+
+    Ok(BatchReport { results })
+}
+
+fn solve_file<F>(path: &Path, solve_fn: &F, options: &SolverOptions) -> BatchResult
+where
+    F: Fn(&[KDPoint], &SolverOptions) -> Result<Solution, Error>,
+{
+    let started_at = Instant::now();
+    let mut n_cities = 0;
+
+    let outcome = if options.cancel.is_cancelled() {
+        Err(Error::Runtime(
+            "skipped, the batch was interrupted".to_string(),
+        ))
+    } else {
+        tsplib::read_from_file(path).and_then(|data| {
+            n_cities = data.len();
+            if data.fixed_edges().is_empty() && data.precedences().is_empty() {
+                return solve_fn(data.cities(), options);
+            }
+
+            let mut options = options.clone();
+            if !data.fixed_edges().is_empty() {
+                options.fixed_edges = data.fixed_edges().to_vec();
+            }
+            if !data.precedences().is_empty() {
+                options.precedences = data.precedences().to_vec();
+            }
+            solve_fn(data.cities(), &options)
+        })
+    };
+
+    BatchResult {
+        path: path.to_path_buf(),
+        n_cities,
+        outcome,
+        seconds: started_at.elapsed().as_secs_f64(),
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<24}{:>8}{:>14}{:>10}",
+            "file", "cities", "cost", "time_s"
+        )?;
+
+        for res in self.results.iter() {
+            match &res.outcome {
+                Ok(tour) => writeln!(
+                    f,
+                    "{:<24}{:>8}{:>14.2}{:>10.3}",
+                    res.file_name(),
+                    res.n_cities,
+                    tour.total,
+                    res.seconds
+                )?,
+                Err(_) => writeln!(f, "{:<24}{:>8}{:>14}{:>10}", res.file_name(), "-", "-", "-")?,
+            }
+        }
+
+        for res in self.results.iter() {
+            if let Err(err) = &res.outcome {
+                writeln!(f, "failed: {}: {}", res.file_name(), err)?;
+            }
+        }
+
+        writeln!(
+            f,
+            "solved {} of {} files",
+            self.n_solved(),
+            self.results.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    #[test]
+    fn test_expand_inputs() {
+        let paths = expand_inputs(&["problem.tsp", "Cargo.tom?"]).unwrap();
+
+        assert_eq!(
+            vec![PathBuf::from("problem.tsp"), PathBuf::from("Cargo.toml")],
+            paths
+        );
+        assert!(expand_inputs(&["no_such_dir/*.tsp"]).is_err());
+    }
+
+    #[test]
+    fn test_run_reports_unreadable_file() {
+        let paths = vec![PathBuf::from("no_such_dir/missing.tsp")];
+        let solve_fn = |cities: &[KDPoint], _: &SolverOptions| -> Result<Solution, Error> {
+            let route: Vec<usize> = cities.iter().map(|c| c.id).collect();
+            Ok(Solution::new(&route, cities))
+        };
+
+        let report = run(&paths, solve_fn, &SolverOptions::default(), 2).unwrap();
+
+        assert_eq!(1, report.n_failed());
+        let summary = report.to_string();
+        assert!(summary.contains("failed: missing.tsp: "), "{}", summary);
+        assert!(summary.ends_with("solved 0 of 1 files\n"));
+    }
+
+    #[test]
+    fn test_report_lists_files_in_order() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![3.0, 4.0]]);
+        let report = BatchReport {
+            results: vec![
+                BatchResult {
+                    path: PathBuf::from("data/b.tsp"),
+                    n_cities: 2,
+                    outcome: Ok(Solution::new(&[0, 1], &cities)),
+                    seconds: 0.5,
+                },
+                BatchResult {
+                    path: PathBuf::from("data/a.tsp"),
+                    n_cities: 0,
+                    outcome: Err(Error::InvalidInput("empty".to_string())),
+                    seconds: 0.0,
+                },
+            ],
+        };
+
+        let summary = report.to_string();
+        let lines: Vec<&str> = summary.lines().collect();
+
+        assert!(lines[1].starts_with("b.tsp"));
+        assert!(lines[1].contains("10.00"));
+        assert!(lines[2].starts_with("a.tsp"));
+        assert_eq!("failed: a.tsp: invalid input: empty", lines[3]);
+        assert_eq!("solved 1 of 2 files", lines[4]);
+    }
+}
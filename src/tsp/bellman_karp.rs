@@ -9,151 +9,297 @@
 ///
 ///
 ///
-use super::distance_matrix::DistanceMatrix;
+use rayon::prelude::*;
+use std::fmt;
+
+use super::distance_matrix::{distance_source, DistanceSource};
+use super::history::History;
 use super::kdtree::KDPoint;
-use super::progress::{send_progress, ProgressMessage};
+use super::progress::{self, ProgressMessage, PublisherFn};
+use super::registry::Category;
 use super::route::Route;
-use super::{Solution, SolverOptions};
+use super::{
+    total_distance, validate_cities, Error, Solution, SolverInfo, SolverOptions, TspSolver,
+};
 
 // 0-1 Set, where 1 means that city N is collected
-type FlagSet = u64;
-type DPTable = Vec<Vec<f32>>;
+type FlagSet = usize;
 
 const UNKNOWN_DISTANCE: f32 = f32::MAX;
+const NO_PARENT: u8 = u8::MAX;
+// the sets of the other cities are the bits of the index of the table
+const MAX_CITIES: usize = 32;
+// every cell of the table keeps the cost and the parent city
+const CELL_BYTES: f64 = 5.0;
+// the inner steps of the table per second, measured with the release build on a laptop
+const STEPS_PER_SECOND: f64 = 1e8;
+const SETS_PER_CHUNK: usize = 1 << 14;
 
-/// TODO: fix bug with duplicated city.1, and missing city.4
-/// replication: use ./data/discopt/tsp_5_1.tsp;
-pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Solution {
-    let n_cities = cities.len();
-    let n_others = n_cities - 1; // we start from last city
-    let n_powersets = 1 << n_others;
-
-    let dists = DistanceMatrix::from_cities(cities).unwrap();
-    let mut opt = vec![vec![UNKNOWN_DISTANCE; n_powersets]; n_others];
+pub struct BellmanKarpSolver;
 
-    if options.verbose == true {
-        println!("BHK: initializing the table with subresults");
+impl TspSolver for BellmanKarpSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "bellman_karp",
+            &["bhk"],
+            "exact dynamic programming, feasible only for small problems",
+            false,
+        )
+        .with_category(Category::Exact)
+        .with_complexity("O(n^2 2^n)")
     }
-    // inialize tables first row with distance from first cities to other cities
-    let last_pos = n_others;
-    for i in 0..n_others {
-        opt[i][1 << i] = dists
-            .distance_by_pos(i, last_pos)
-            .unwrap_or(UNKNOWN_DISTANCE);
-
-        if let Some(city_id) = dists.pos2city_id(&i) {
-            send_progress(ProgressMessage::CityChange(city_id));
+
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 2)?;
+
+        // the process would be killed for the lack of memory, it is refused before the allocation
+        let estimate = estimate(cities.len());
+        if estimate.memory_mb > options.max_memory_mb as f64 {
+            return Err(Error::InvalidInput(format!(
+                "{}, over the limit of {} MB; raise it with --max_memory or use branch_bound or a heuristic solver",
+                estimate, options.max_memory_mb
+            )));
+        }
+
+        if cities.len() > MAX_CITIES {
+            return Err(Error::InvalidInput(format!(
+                "bellman_karp supports up to {} cities, got {}",
+                MAX_CITIES,
+                cities.len()
+            )));
+        }
+
+        let mut history = History::new();
+        let dists = distance_source(cities)?;
+
+        if options.verbose {
+            println!("BHK: filling the table from the smallest subsets");
         }
+        let table = DPTable::build(dists.as_ref(), cities.len(), &progress)?;
+
+        if options.verbose {
+            println!("BHK: done with calculations, preparing the result");
+            show_table(&table);
+        }
+
+        let route_vec: Vec<usize> = table
+            .optimal_route(dists.as_ref())?
+            .iter()
+            .map(|pos| {
+                dists
+                    .pos2city_id(pos)
+                    .ok_or_else(|| Error::Runtime(format!("BHK: no city at position {}", pos)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        // send final route to the visualizer
+        let route = Route::new(route_vec.as_ref());
+        progress(ProgressMessage::PathUpdate(
+            route,
+            total_distance(cities, &route_vec),
+        ));
+        progress(ProgressMessage::Done);
+
+        let tour = Solution::new(&route_vec, cities).with_target(options);
+        history.record(0, tour.total);
+
+        Ok(tour.with_history(history))
     }
+}
+
+/// solves the problem and sends the progress into the global progress channel
+pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Result<Solution, Error> {
+    BellmanKarpSolver.solve(cities, options, progress::default_publisher())
+}
+
+/// the memory of the table and the expected running time of the problem
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub n_cities: usize,
+    pub memory_mb: f64,
+    pub seconds: f64,
+}
 
-    let selected_set = (1 << n_others) - 1;
-    for city_pos in 0..n_others {
-        solve_bhk(&mut opt, &dists, selected_set, city_pos);
+/// estimates the problem before the table is allocated: it has `(n - 1) * 2^(n - 1)` cells
+/// and every cell of a set checks all the other cities of the set, on all the threads
+pub fn estimate(n_cities: usize) -> Estimate {
+    let n_others = n_cities.saturating_sub(1) as f64;
+    let n_sets = 2f64.powf(n_others);
+    // the average set has the half of the cities
+    let n_steps = n_others * n_others * n_sets / 4.0;
+
+    Estimate {
+        n_cities,
+        memory_mb: n_others * n_sets * CELL_BYTES / 1e6,
+        seconds: n_steps / STEPS_PER_SECOND / rayon::current_num_threads() as f64,
     }
+}
 
-    if options.verbose == true {
-        println!("BHK: done with calculations, preparing the result");
-        show_table(&opt);
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "bellman_karp: {} cities need {:.1} MB of memory and about {:.1} seconds",
+            self.n_cities, self.memory_mb, self.seconds
+        )
     }
+}
 
-    let route_vec = read_optimal_route(&opt, &dists, n_cities, f32::MAX);
+// the tours start from the last city, the sets are over the other `n - 1` cities;
+// cost[set][t] is the shortest path from the start through all the cities of the set,
+// which ends in the city t of the set, and parent[set][t] is the city before t on it
+struct DPTable {
+    n_others: usize,
+    cost: Vec<f32>,
+    parent: Vec<u8>,
+}
 
-    // send final route to the visualizer
-    let route = Route::new(route_vec.as_ref());
-    send_progress(ProgressMessage::PathUpdate(route, 0.0));
-    send_progress(ProgressMessage::Done);
+impl DPTable {
+    // iterates over the subsets by their size, so the subsets without the city t
+    // are ready before the sets of the next size need them
+    fn build(
+        dm: &dyn DistanceSource,
+        n_cities: usize,
+        progress: &PublisherFn,
+    ) -> Result<Self, Error> {
+        let n_others = n_cities - 1;
+        let start = n_others;
+        let n_sets = 1 << n_others;
 
-    let tour = Solution::new(&route_vec, cities);
+        let mut dist = vec![0.0; n_cities * n_cities];
+        for i in 0..n_cities {
+            for j in 0..n_cities {
+                dist[i * n_cities + j] = dm.distance_by_pos(i, j)?;
+            }
+        }
+        let dist = |i: usize, j: usize| dist[i * n_cities + j];
 
-    tour
-}
+        let mut table = DPTable {
+            n_others,
+            cost: vec![UNKNOWN_DISTANCE; n_sets * n_others],
+            parent: vec![NO_PARENT; n_sets * n_others],
+        };
+
+        for t in 0..n_others {
+            let cell = table.cell(1 << t, t);
+            table.cost[cell] = dist(start, t);
+        }
+
+        // the sets of the same size read only the smaller sets, so they are computed in parallel;
+        // the chunks keep the memory of the results small
+        for size in 2..=n_others {
+            let sets: Vec<FlagSet> = subsets_of_size(n_others, size).collect();
+
+            for chunk in sets.chunks(SETS_PER_CHUNK) {
+                let cells: Vec<(f32, u8)> = chunk
+                    .par_iter()
+                    .flat_map_iter(|&set| (0..n_others).map(move |t| (set, t)))
+                    .map(|(set, t)| table.best_parent(set, t, dist))
+                    .collect();
+
+                for (&set, row) in chunk.iter().zip(cells.chunks(n_others)) {
+                    for (t, &(cost, parent)) in row.iter().enumerate() {
+                        let cell = table.cell(set, t);
+                        table.cost[cell] = cost;
+                        table.parent[cell] = parent;
+                    }
+                }
+            }
+
+            progress(ProgressMessage::EpochUpdate(size));
+        }
 
-fn solve_bhk(
-    opt: &mut DPTable,
-    dm: &DistanceMatrix,
-    selected_set: FlagSet,
-    city_pos: usize,
-) -> f32 {
-    let mut best_val = f32::MAX;
-
-    // distance is already calculated
-    let selected_pos = selected_set as usize;
-    if opt[city_pos][selected_pos] < UNKNOWN_DISTANCE {
-        return opt[city_pos][selected_pos];
+        Ok(table)
     }
 
-    // rest_selected R = S \ t , all other than city_id
-    let rest_selected = selected_set & !(1 << city_pos);
-    let n_other = opt.len() - 1; // opt has n_city rows
-    for i in 0..n_other {
-        // if city i is not in rest_selected
-        if (rest_selected & (1 << i)) == 0 {
-            continue;
+    // the cheapest path through the set, which ends in the city t, and the city before t;
+    // UNKNOWN_DISTANCE for the city outside of the set
+    fn best_parent(&self, set: FlagSet, t: usize, dist: impl Fn(usize, usize) -> f32) -> (f32, u8) {
+        let mut best = (UNKNOWN_DISTANCE, NO_PARENT);
+        if set & (1 << t) == 0 {
+            return best;
         }
 
-        // solve sub problem
-        let step_dist = dm
-            .distance_by_pos(i, city_pos)
-            .expect("solve_bhk tried to access non-existent cities");
-        let sub_val = solve_bhk(opt, dm, rest_selected, i) + step_dist;
-        if sub_val < best_val {
-            best_val = sub_val;
+        let rest = set & !(1 << t);
+        for i in (0..self.n_others).filter(|i| rest & (1 << i) != 0) {
+            let sub_cost = self.cost[self.cell(rest, i)] + dist(i, t);
+            if sub_cost < best.0 {
+                best = (sub_cost, i as u8);
+            }
         }
+
+        best
     }
 
-    opt[city_pos][selected_pos] = best_val;
-    opt[city_pos][selected_pos]
-}
+    fn cell(&self, set: FlagSet, t: usize) -> usize {
+        set * self.n_others + t
+    }
 
-fn read_optimal_route(opt: &DPTable, dm: &DistanceMatrix, n: usize, best_val: f32) -> Vec<usize> {
-    let mut route_ids = vec![0; n];
-    route_ids[0] = n - 1;
+    // closes the cheapest path through all the cities and follows the parents back to the
+    // start, returns the positions of the cities in the distance matrix
+    fn optimal_route(&self, dm: &dyn DistanceSource) -> Result<Vec<usize>, Error> {
+        let start = self.n_others;
+        let full_set: FlagSet = (1 << self.n_others) - 1;
 
-    let mut unread_set = (1 << (n - 1)) - 1;
-    let mut left_dist = best_val;
-    for i in 1..n {
-        if left_dist <= 0.0 {
-            break;
+        let mut last = 0;
+        let mut best_val = UNKNOWN_DISTANCE;
+        for t in 0..self.n_others {
+            let tour_val = self.cost[self.cell(full_set, t)] + dm.distance_by_pos(t, start)?;
+            if tour_val < best_val {
+                best_val = tour_val;
+                last = t;
+            }
         }
 
-        for j in 0..(n - 1) {
-            let step_dist = dm
-                .distance_by_pos(j, route_ids[i - 1])
-                .expect("step_dist points are out range");
-
-            let cur_dist = opt[j][unread_set] + step_dist;
-            let is_unprocessed = (unread_set & (1 << j)) > 0;
-            if is_unprocessed && approx(left_dist, cur_dist) {
-                left_dist -= step_dist;
-                route_ids[i] = j;
+        let mut route = vec![start];
+        let mut set = full_set;
+        let mut city = last;
+        loop {
+            route.push(city);
 
-                unread_set &= !(1 << j);
+            let parent = self.parent[self.cell(set, city)];
+            set &= !(1 << city);
+            if parent == NO_PARENT {
                 break;
             }
+            city = parent as usize;
         }
-    }
 
-    let route: Vec<usize> = route_ids
-        .iter()
-        .map(|pos| dm.pos2city_id(pos).unwrap_or(0))
-        .collect();
+        // the parents lead from the end of the tour back to its start
+        route[1..].reverse();
 
-    route
+        Ok(route)
+    }
 }
 
-fn approx(x1: f32, x2: f32) -> bool {
-    (x1 - x2).abs() < f32::EPSILON
+// Gosper's hack: the next bigger number with the same count of 1 bits
+fn subsets_of_size(n: usize, size: usize) -> impl Iterator<Item = FlagSet> {
+    let first: FlagSet = (1 << size) - 1;
+
+    std::iter::successors(Some(first), |&set| {
+        let lowest = set & set.wrapping_neg();
+        let ripple = set + lowest;
+
+        Some((((ripple ^ set) >> 2) / lowest) | ripple)
+    })
+    .take_while(move |&set| set < (1 << n))
 }
 
-fn show_table(opt: &DPTable) {
+fn show_table(table: &DPTable) {
     println!("=============================================");
     println!("Dynamic programming table");
 
-    for row in opt.iter() {
+    for t in 0..table.n_others {
         print!("| ");
 
-        for val in row.iter() {
-            let fval = if approx(*val, UNKNOWN_DISTANCE) {
+        for set in 0..(1 << table.n_others) {
+            let val = table.cost[table.cell(set, t)];
+            let fval = if val == UNKNOWN_DISTANCE {
                 " - ".to_string()
             } else {
                 format!("{:.2}", val)
@@ -165,3 +311,91 @@ fn show_table(opt: &DPTable) {
         println!(" |");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::helpers::assert_approx;
+    use crate::tsp::{generator, kdtree};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_subsets_of_size() {
+        let subsets: Vec<FlagSet> = subsets_of_size(4, 2).collect();
+
+        assert_eq!(
+            vec![0b0011, 0b0101, 0b0110, 0b1001, 0b1010, 0b1100],
+            subsets
+        );
+        assert_eq!(vec![0b1111], subsets_of_size(4, 4).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_solve_with_tsp5_example() {
+        // the tour of tsp_5_1 used to visit the city 1 twice and miss the city 4
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 0.5],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+
+        let tour = solve(&cities, &SolverOptions::default()).unwrap();
+
+        let mut visited = tour.route().to_vec();
+        visited.sort();
+        assert_eq!(vec![0, 1, 2, 3, 4], visited);
+        assert_approx(4.0, tour.total);
+    }
+
+    #[test]
+    fn test_solve_matches_branch_bound() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let bbox = generator::BoundingBox::square(100.0);
+        let cities = generator::uniform(11, &bbox, &mut rng);
+        let options = SolverOptions::default();
+
+        let tour = solve(&cities, &options).unwrap();
+        let exact = crate::tsp::branch_bound::solve(&cities, &options).unwrap();
+
+        assert_eq!(cities.len(), tour.len());
+        assert!((exact.total - tour.total).abs() < 1e-3, "{}", tour.total);
+    }
+
+    #[test]
+    fn test_estimate() {
+        let n_threads = rayon::current_num_threads() as f64;
+
+        let small = estimate(20);
+        assert!((small.memory_mb - 49.8).abs() < 0.1, "{}", small.memory_mb);
+        assert!((small.seconds * n_threads - 0.47).abs() < 0.01);
+
+        let big = estimate(26);
+        assert!(big
+            .to_string()
+            .starts_with("bellman_karp: 26 cities need 4194.3 MB of memory and about"));
+        assert!((big.seconds * n_threads - 52.4).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_solve_refuses_problem_over_memory_limit() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let cities = generator::uniform(12, &generator::BoundingBox::square(10.0), &mut rng);
+        let mut options = SolverOptions::default();
+        options.max_memory_mb = 0;
+
+        let err = solve(&cities, &options).err().unwrap();
+        assert!(err.to_string().contains("12 cities need 0.1 MB"));
+        assert!(err.to_string().contains("use branch_bound"));
+    }
+
+    #[test]
+    fn test_solve_with_two_cities() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![3.0, 4.0]]);
+
+        let tour = solve(&cities, &SolverOptions::default()).unwrap();
+        assert_approx(10.0, tour.total);
+    }
+}
@@ -0,0 +1,385 @@
+/// Benchmark of the solvers
+///
+/// It runs every solver on every instance several times and summarizes the cost, the time
+/// and the gap of the tours. The gap is measured to the optimal tour, if the instance has
+/// the TSPLIB `.opt.tour` file next to it, otherwise to the best tour found by the benchmark.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::kdtree::KDPoint;
+use super::progress;
+use super::{restart, tsplib, validate_route, Error, Solution, SolverOptions, TspSolver};
+
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub name: String,
+    pub cities: Vec<KDPoint>,
+    pub optimum: Option<f64>, // the length of the optimal tour, if it is known
+}
+
+impl Instance {
+    pub fn new(name: &str, cities: Vec<KDPoint>) -> Self {
+        Instance {
+            name: name.to_string(),
+            cities,
+            optimum: None,
+        }
+    }
+
+    /// reads the TSPLIB file and the optimal tour from the `<name>.opt.tour` file next to it,
+    /// both of them may be gzipped as they are in the TSPLIB archive
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let data = tsplib::read_from_file(path)?;
+        let path = without_gz_extension(path);
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| data.name.clone());
+
+        let tour_path = path.with_extension("opt.tour");
+        let gz_tour_path = path.with_extension("opt.tour.gz");
+        let optimum = match [tour_path, gz_tour_path].iter().find(|p| p.exists()) {
+            Some(tour_path) => {
+                let tour = tsplib::read_tour_from_file(tour_path)?;
+                // the tour file may be of another instance, it names the file then
+                validate_route(data.cities(), &tour).map_err(|err| {
+                    Error::InvalidInput(format!("{}: {}", tour_path.display(), err))
+                })?;
+                Some(Solution::new(&tour, data.cities()).length)
+            }
+            None => None,
+        };
+
+        Ok(Instance {
+            name,
+            cities: data.cities().to_vec(),
+            optimum,
+        })
+    }
+}
+
+// `berlin52.tsp.gz` names the same instance as `berlin52.tsp`
+fn without_gz_extension(path: &Path) -> PathBuf {
+    match path.extension() {
+        Some(ext) if ext == "gz" => path.with_extension(""),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkRun {
+    pub instance: String,
+    pub solver: String,
+    pub repeat: usize,
+    pub cost: f64,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BenchmarkReport {
+    pub runs: Vec<BenchmarkRun>,
+    pub failures: Vec<String>,
+    // the cost the gaps are measured to, and whether it is the optimum
+    references: BTreeMap<String, (f64, bool)>,
+}
+
+/// runs every solver `n_repeats` times on every instance, the seeded runs get their own seeds;
+/// a solver which fails on the instance is skipped there and the failure is reported
+pub fn run(
+    solvers: &[Arc<dyn TspSolver>],
+    instances: &[Instance],
+    options: &SolverOptions,
+    n_repeats: usize,
+) -> BenchmarkReport {
+    let mut report = BenchmarkReport::default();
+    let publisher = progress::silent_publisher();
+
+    'instances: for instance in instances.iter() {
+        for solver in solvers.iter() {
+            let solver_name = solver.metadata().name;
+
+            for repeat in 0..n_repeats {
+                if options.cancel.is_cancelled() {
+                    break 'instances;
+                }
+
+                let run_options = restart::run_options(options, repeat);
+                let started_at = Instant::now();
+                match solver.solve(&instance.cities, &run_options, publisher.clone()) {
+                    Ok(tour) => report.runs.push(BenchmarkRun {
+                        instance: instance.name.clone(),
+                        solver: solver_name.to_string(),
+                        repeat,
+                        cost: tour.length,
+                        seconds: started_at.elapsed().as_secs_f64(),
+                    }),
+                    Err(err) => {
+                        report
+                            .failures
+                            .push(format!("{} on {}: {}", solver_name, instance.name, err));
+                        break;
+                    }
+                }
+            }
+        }
+
+        let best_found = report
+            .runs
+            .iter()
+            .filter(|run| run.instance == instance.name)
+            .map(|run| run.cost)
+            .fold(f64::INFINITY, f64::min);
+        let reference = match instance.optimum {
+            Some(optimum) => (optimum, true),
+            None => (best_found, false),
+        };
+        report.references.insert(instance.name.clone(), reference);
+    }
+
+    report
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryRow {
+    pub instance: String,
+    pub solver: String,
+    pub n_runs: usize,
+    pub best: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub mean_seconds: f64,
+    pub gap: f64, // of the mean cost, in percents
+    pub is_optimum_gap: bool,
+}
+
+impl BenchmarkReport {
+    /// the gap of the cost to the reference cost of the instance, in percents
+    pub fn gap(&self, instance: &str, cost: f64) -> f64 {
+        match self.references.get(instance) {
+            Some((reference, _)) if *reference > 0.0 => (cost - reference) / reference * 100.0,
+            _ => 0.0,
+        }
+    }
+
+    /// summarizes the runs of each solver on each instance, in the order of runs
+    pub fn summary(&self) -> Vec<SummaryRow> {
+        let mut rows: Vec<SummaryRow> = vec![];
+
+        for run in self.runs.iter() {
+            let exists = rows
+                .iter()
+                .any(|row| row.instance == run.instance && row.solver == run.solver);
+            if exists {
+                continue;
+            }
+
+            let runs: Vec<&BenchmarkRun> = self
+                .runs
+                .iter()
+                .filter(|other| other.instance == run.instance && other.solver == run.solver)
+                .collect();
+            let n_runs = runs.len();
+            let mean = runs.iter().map(|r| r.cost).sum::<f64>() / n_runs as f64;
+            let variance =
+                runs.iter().map(|r| (r.cost - mean).powi(2)).sum::<f64>() / n_runs as f64;
+
+            rows.push(SummaryRow {
+                instance: run.instance.clone(),
+                solver: run.solver.clone(),
+                n_runs,
+                best: runs.iter().map(|r| r.cost).fold(f64::INFINITY, f64::min),
+                mean,
+                std_dev: variance.sqrt(),
+                mean_seconds: runs.iter().map(|r| r.seconds).sum::<f64>() / n_runs as f64,
+                gap: self.gap(&run.instance, mean),
+                is_optimum_gap: self
+                    .references
+                    .get(&run.instance)
+                    .map(|(_, is_optimum)| *is_optimum)
+                    .unwrap_or(false),
+            });
+        }
+
+        rows
+    }
+
+    /// writes every run as a CSV row
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "instance,solver,repeat,cost,seconds,gap")?;
+
+        for run in self.runs.iter() {
+            writeln!(
+                writer,
+                "{},{},{},{},{:.6},{:.4}",
+                run.instance,
+                run.solver,
+                run.repeat + 1,
+                run.cost,
+                run.seconds,
+                self.gap(&run.instance, run.cost)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16}{:<22}{:>6}{:>14}{:>14}{:>12}{:>12}{:>10}",
+            "instance", "solver", "runs", "best", "mean", "std_dev", "time_s", "gap_%"
+        )?;
+
+        for row in self.summary() {
+            // the gap to the best found tour is marked, as it is not the real gap
+            let marker = if row.is_optimum_gap { "" } else { "*" };
+            writeln!(
+                f,
+                "{:<16}{:<22}{:>6}{:>14.2}{:>14.2}{:>12.2}{:>12.3}{:>9.2}{:1}",
+                row.instance,
+                row.solver,
+                row.n_runs,
+                row.best,
+                row.mean,
+                row.std_dev,
+                row.mean_seconds,
+                row.gap,
+                marker
+            )?;
+        }
+
+        if self.references.values().any(|(_, is_optimum)| !is_optimum) {
+            writeln!(
+                f,
+                "* the gap to the best tour found, the optimal tour is not known"
+            )?;
+        }
+
+        for failure in self.failures.iter() {
+            writeln!(f, "failed: {}", failure)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+    use crate::tsp::Solvers;
+    use std::str::FromStr;
+
+    fn square_instance() -> Instance {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 0.5],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+
+        Instance::new("square", cities)
+    }
+
+    fn solvers(names: &[&str]) -> Vec<Arc<dyn TspSolver>> {
+        names
+            .iter()
+            .map(|name| Solvers::from_str(name).unwrap().solver().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_run_repeats_every_solver_on_every_instance() {
+        let mut options = SolverOptions::default();
+        options.epochs = 10;
+        options.seed = Some(7);
+
+        let report = run(&solvers(&["nn", "2opt"]), &[square_instance()], &options, 3);
+
+        assert_eq!(6, report.runs.len());
+        assert!(report.failures.is_empty());
+
+        let summary = report.summary();
+        assert_eq!(2, summary.len());
+        assert_eq!("nearest_neighbor", summary[0].solver);
+        assert_eq!(3, summary[1].n_runs);
+        assert_eq!(4.0, summary[1].best);
+        assert!(!summary[1].is_optimum_gap);
+    }
+
+    #[test]
+    fn test_gap_is_measured_to_the_optimum() {
+        let mut instance = square_instance();
+        instance.optimum = Some(3.0);
+
+        let report = run(
+            &solvers(&["2opt"]),
+            &[instance],
+            &SolverOptions::default(),
+            1,
+        );
+        let summary = report.summary();
+
+        assert!((summary[0].gap - 33.333).abs() < 0.01);
+        assert!(summary[0].is_optimum_gap);
+    }
+
+    #[test]
+    fn test_from_file_rejects_optimal_tour_of_unknown_cities() {
+        let dir = std::env::temp_dir().join(format!("teeline_benchmark_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tri.tsp");
+        std::fs::write(
+            &path,
+            "NAME: tri\nNODE_COORD_SECTION\n1 0 0\n2 0 1\n3 1 0\nEOF\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("tri.opt.tour"), "TOUR_SECTION\n1\n2\n7\n-1\nEOF\n").unwrap();
+
+        let res = Instance::from_file(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let err = res.err().unwrap().to_string();
+        assert!(err.contains("tri.opt.tour"), "{}", err);
+        assert!(err.contains("7"), "{}", err);
+    }
+
+    #[test]
+    fn test_run_reports_failing_solver() {
+        let instance = Instance::new("single", kdtree::build_points(&[vec![0.0, 0.0]]));
+
+        let report = run(
+            &solvers(&["2opt"]),
+            &[instance],
+            &SolverOptions::default(),
+            5,
+        );
+
+        assert!(report.runs.is_empty());
+        assert_eq!(1, report.failures.len());
+        assert!(report.to_string().contains("failed: two_opt on single"));
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut options = SolverOptions::default();
+        options.epochs = 10;
+
+        let report = run(&solvers(&["2opt"]), &[square_instance()], &options, 1);
+        let mut buf = vec![];
+        report.write_csv(&mut buf).unwrap();
+        let csv = String::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!("instance,solver,repeat,cost,seconds,gap", lines[0]);
+        assert!(lines[1].starts_with("square,two_opt,1,4,"));
+        assert!(lines[1].ends_with(",0.0000"));
+    }
+}
@@ -1,166 +1,499 @@
-use std::collections::HashSet;
-use std::rc::Rc;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 
-use super::distance_matrix::DistanceMatrix;
+use super::distance_matrix::{distance_source, DistanceSource};
+use super::history::History;
 use super::kdtree::KDPoint;
-use super::progress::{send_progress, ProgressMessage};
+use super::progress::{self, ProgressMessage, PublisherFn};
+use super::registry::Category;
 use super::route::Route;
-use super::{Solution, SolverOptions};
+use super::{
+    total_distance, validate_cities, Error, Solution, SolverInfo, SolverOptions, TspSolver,
+};
 
-const UNVISITED_NODE: usize = 0;
-
-type UniqSet = HashSet<usize>;
 type Path = Vec<usize>;
-type PathEvaluator = Rc<dyn Fn(&Path) -> f32>;
+type Candidate = (usize, f32); // city id and the lower bound of the branch
 
-// TODO: add better strategy or constraints for Bounding step
-pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Solution {
-    let mut route = Route::from_cities(cities);
-    let n_cities = route.len();
+/// the node budget of branch and bound
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchBoundOptions {
+    pub max_nodes: Option<usize>, // how many nodes it expands at most
+}
 
-    // we will start from city with smallest ID
-    route.sort();
-    send_progress(ProgressMessage::PathUpdate(route.clone(), 0.0));
+impl BranchBoundOptions {
+    /// copies the options into the options the solver runs with
+    pub fn apply(&self, options: &mut SolverOptions) {
+        options.max_nodes = self.max_nodes;
+    }
+}
 
-    let mut open_path: Path = vec![0; n_cities];
-    open_path[0] = route.get(0).unwrap();
+impl Default for BranchBoundOptions {
+    fn default() -> Self {
+        BranchBoundOptions::from(&SolverOptions::default())
+    }
+}
 
-    // at the beginning all cities the except the first city are unvisited
-    let unvisited_cities: UniqSet = route.route().iter().skip(1).map(|x| x.clone()).collect();
+impl From<&SolverOptions> for BranchBoundOptions {
+    fn from(options: &SolverOptions) -> Self {
+        BranchBoundOptions {
+            max_nodes: options.max_nodes,
+        }
+    }
+}
+
+pub struct BranchBoundSolver;
+
+impl TspSolver for BranchBoundSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "branch_bound",
+            &[],
+            "exact best-first branch and bound search with a spanning tree bound",
+            false,
+        )
+        .with_category(Category::Exact)
+        .with_complexity("O(n!) in the worst case")
+        .with_options(&["max_nodes", "max_seconds", "target_cost"])
+    }
 
-    let fitness_fn = build_evaluator(cities);
-    let (best_path, _best_distance) = backtrack(
-        &fitness_fn,
-        &mut open_path,
-        &unvisited_cities,
-        1, // we start backtracking from second city
-        0.0,
-        f32::MAX,
-        options,
-    );
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 2)?;
+
+        let mut route = Route::from_cities(cities);
+
+        // we will start from city with smallest ID
+        route.sort();
+        progress(ProgressMessage::PathUpdate(
+            route.clone(),
+            total_distance(cities, route.route()),
+        ));
 
-    send_progress(ProgressMessage::Done);
-    Solution::new(&best_path, cities)
+        let dm = distance_source(cities)?;
+
+        // greedy tour gives the first upper bound, so the pruning works from the very first branch
+        let (greedy_path, greedy_distance) = greedy_tour(dm.as_ref(), route.route())?;
+        if options.verbose {
+            println!("B&B: initial upper bound {:?}", greedy_distance);
+        }
+        progress(ProgressMessage::PathUpdate(
+            Route::new(&greedy_path),
+            greedy_distance,
+        ));
+
+        let mut history = History::new();
+        history.record(0, greedy_distance);
+        let (best_path, _) = best_first_search(
+            dm.as_ref(),
+            route.route(),
+            (greedy_path, greedy_distance),
+            options,
+            &progress,
+            &mut history,
+        )?;
+
+        progress(ProgressMessage::Done);
+        Ok(Solution::new(&best_path, cities)
+            .with_target(options)
+            .with_history(history))
+    }
 }
 
-fn build_evaluator(cities: &[KDPoint]) -> PathEvaluator {
-    let dm = Rc::new(DistanceMatrix::from_cities(cities).expect("Failed to build distance matrix"));
+/// solves the problem and sends the progress into the global progress channel
+pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Result<Solution, Error> {
+    BranchBoundSolver.solve(cities, options, progress::default_publisher())
+}
 
-    return Rc::new(move |path: &Path| dm.tour_length(path));
+// the partial tour from the first city, its cost and the lower bound of every tour,
+// which continues from it
+#[derive(Debug, Clone)]
+struct Node {
+    path: Path,
+    unvisited: Vec<usize>,
+    cost: f32,
+    bound: f32,
 }
 
-fn backtrack(
-    evaluate_fn: &PathEvaluator,
-    path: &mut Path,
-    unvisited_cities: &UniqSet,
-    k: usize,
-    running_cost: f32,
-    upper_bound: f32,
+// the binary heap pops the greatest node first: the one with the lowest bound, then the
+// deepest one, which completes the tours sooner; the path breaks the ties deterministically
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .bound
+            .total_cmp(&self.bound)
+            .then(self.path.len().cmp(&other.path.len()))
+            .then(other.path.cmp(&self.path))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Node {}
+
+/// expands the open node with the lowest bound until no node can beat the incumbent tour,
+/// or until the time or the node budget runs out; returns the best tour found so far
+fn best_first_search(
+    dm: &dyn DistanceSource,
+    city_ids: &[usize],
+    incumbent: (Path, f32),
     options: &SolverOptions,
-) -> (Path, f32) {
-    let mut best_path = path.clone();
-    let mut best_distance = upper_bound;
+    progress: &PublisherFn,
+    history: &mut History,
+) -> Result<(Path, f32), Error> {
+    let (mut best_path, mut best_distance) = incumbent;
+    let deadline = options.deadline();
+
+    let start = city_ids[0];
+    let rest = city_ids[1..].to_vec();
+    let mut open_nodes = BinaryHeap::new();
+    open_nodes.push(Node {
+        bound: lower_bound(dm, start, &rest, start)?,
+        path: vec![start],
+        unvisited: rest,
+        cost: 0.0,
+    });
+
+    let mut n_expanded = 0;
+    while let Some(node) = open_nodes.pop() {
+        // the rest of the nodes have the same or higher bounds, the incumbent is optimal
+        if node.bound >= best_distance {
+            break;
+        }
+
+        // out of the budget or good enough, the caller keeps the best tour found so far
+        if deadline.is_over()
+            || options.is_target_reached(best_distance)
+            || options.max_nodes.is_some_and(|max| n_expanded >= max)
+        {
+            break;
+        }
+
+        n_expanded += 1;
+        progress::report_epoch(progress, n_expanded);
 
-    let n_cities = k + unvisited_cities.len();
-    if is_solution(path.as_ref(), k, n_cities) {
-        let new_distance = evaluate_fn(path);
+        let last = node.path[node.path.len() - 1];
+        let candidates =
+            construct_candidates(dm, &node.path, &node.unvisited, node.cost, best_distance)?;
+        for (city_id, bound) in candidates.into_iter() {
+            let mut path = node.path.clone();
+            path.push(city_id);
+            let cost = node.cost + distance(dm, last, city_id)?;
 
-        if new_distance < upper_bound {
-            best_path = path.clone();
-            best_distance = new_distance;
+            if path.len() == city_ids.len() {
+                // the bound of the complete tour is its length with the closing edge
+                if bound < best_distance {
+                    best_path = path;
+                    best_distance = bound;
+                    // the expanded nodes are the epochs of the search
+                    history.record(n_expanded, best_distance);
 
-            if options.verbose {
-                println!("B&B: epoch.{:?}, new best distance {:?}", k, best_distance);
+                    if options.verbose {
+                        println!(
+                            "B&B: node.{:?}, new best distance {:?}",
+                            n_expanded, best_distance
+                        );
+                    }
+                    progress(ProgressMessage::PathUpdate(
+                        Route::new(&best_path),
+                        best_distance,
+                    ));
+                }
+
+                continue;
             }
+
+            let unvisited = node
+                .unvisited
+                .iter()
+                .filter(|&&c| c != city_id)
+                .copied()
+                .collect();
+            open_nodes.push(Node {
+                path,
+                unvisited,
+                cost,
+                bound,
+            });
         }
-    };
-
-    let candidates: Vec<usize> = construct_candidates(
-        &path,
-        k,
-        &unvisited_cities,
-        running_cost,
-        best_distance,
-        evaluate_fn,
-    );
-
-    for candidate in candidates.iter() {
-        make_move(path, k, candidate.clone());
-
-        let visited_path: Vec<usize> = path
-            .to_vec()
-            .iter()
-            .filter(|&&x| x != UNVISITED_NODE)
-            .map(|x| x.clone())
-            .collect();
-        send_progress(ProgressMessage::PathUpdate(
-            Route::new(&visited_path),
-            best_distance,
-        ));
+    }
 
-        let prev_city = path[k - 1];
-        let next_distance = evaluate_fn(&vec![prev_city, candidate.clone()]);
+    Ok((best_path, best_distance))
+}
+
+/// returns unvisited cities whose lower bound is still below the best known distance
+fn construct_candidates(
+    dm: &dyn DistanceSource,
+    path: &[usize],
+    unvisited_cities: &[usize],
+    running_cost: f32,
+    best_distance: f32,
+) -> Result<Vec<Candidate>, Error> {
+    let mut candidates: Vec<Candidate> = vec![];
+    let last = path[path.len() - 1];
 
-        let mut next_cities = unvisited_cities.clone();
-        next_cities.remove(candidate);
+    for city_id in unvisited_cities.iter() {
+        let next_cost = running_cost + distance(dm, last, *city_id)?;
+        if next_cost >= best_distance {
+            continue;
+        }
 
-        let (sub_res, sub_dist) = backtrack(
-            evaluate_fn,
-            path,
-            &next_cities,
-            k + 1,
-            running_cost + next_distance,
-            best_distance,
-            options,
-        );
+        let rest: Vec<usize> = unvisited_cities
+            .iter()
+            .filter(|&c| c != city_id)
+            .copied()
+            .collect();
 
-        if sub_dist < best_distance {
-            best_path = sub_res;
-            best_distance = sub_dist;
+        let bound = next_cost + lower_bound(dm, *city_id, &rest, path[0])?;
+        if bound < best_distance {
+            candidates.push((*city_id, bound));
         }
+    }
+
+    Ok(candidates)
+}
 
-        undo_move(path, k);
+/// lower bound for the cost of finishing the tour: any path from `from_city` through
+/// all the `rest` cities back to `start_city` is a spanning tree of these cities,
+/// so it can not be cheaper than the minimum spanning tree over them
+fn lower_bound(
+    dm: &dyn DistanceSource,
+    from_city: usize,
+    rest: &[usize],
+    start_city: usize,
+) -> Result<f32, Error> {
+    let mut nodes = Vec::with_capacity(rest.len() + 2);
+    nodes.push(from_city);
+    nodes.extend_from_slice(rest);
+    if start_city != from_city {
+        nodes.push(start_city);
     }
 
-    (best_path, best_distance)
+    spanning_tree_weight(dm, &nodes)
 }
 
-fn is_solution(path: &Path, k: usize, n_cities: usize) -> bool {
-    let uniq_ids: UniqSet = path[0..k].iter().map(|c| c.clone()).collect();
+/// Prim's algorithm over the dense distance matrix, O(n^2)
+fn spanning_tree_weight(dm: &dyn DistanceSource, nodes: &[usize]) -> Result<f32, Error> {
+    let n = nodes.len();
+    if n < 2 {
+        return Ok(0.0);
+    }
+
+    let mut in_tree = vec![false; n];
+    let mut min_edge = vec![f32::MAX; n];
+    min_edge[0] = 0.0;
+
+    let mut total = 0.0;
+    for _ in 0..n {
+        let mut next = n;
+        for i in 0..n {
+            if !in_tree[i] && (next == n || min_edge[i] < min_edge[next]) {
+                next = i;
+            }
+        }
+
+        in_tree[next] = true;
+        total += min_edge[next];
 
-    k == n_cities && k > 1 && uniq_ids.len() == n_cities
+        for i in 0..n {
+            if !in_tree[i] {
+                let d = distance(dm, nodes[next], nodes[i])?;
+                if d < min_edge[i] {
+                    min_edge[i] = d;
+                }
+            }
+        }
+    }
+
+    Ok(total)
 }
 
-fn construct_candidates(
-    path: &Path,
-    k: usize,
-    unvisited_cities: &UniqSet,
-    running_cost: f32,
-    best_distance: f32,
-    evaluate_fn: &PathEvaluator,
-) -> Path {
-    let mut candidates: Path = vec![]; // always start from city.id 0
+/// builds a tour by always moving to the closest unvisited city
+fn greedy_tour(dm: &dyn DistanceSource, city_ids: &[usize]) -> Result<(Path, f32), Error> {
+    let mut path: Path = Vec::with_capacity(city_ids.len());
+    let mut unvisited: Vec<usize> = city_ids.to_vec();
+    let mut total = 0.0;
 
-    for city_id in unvisited_cities.iter() {
-        let next_distance = evaluate_fn(&vec![path[k - 1], city_id.clone()]);
-        // simple pruning
-        if best_distance > running_cost + next_distance {
-            candidates.push(city_id.clone());
+    let mut current = unvisited.remove(0);
+    path.push(current);
+
+    while !unvisited.is_empty() {
+        let mut closest = (0, f32::MAX);
+        for (i, &c) in unvisited.iter().enumerate() {
+            let step = distance(dm, current, c)?;
+            if step.total_cmp(&closest.1) == Ordering::Less {
+                closest = (i, step);
+            }
         }
+        let (pos, step) = closest;
+
+        total += step;
+        current = unvisited.remove(pos);
+        path.push(current);
     }
 
-    candidates.sort(); // try to keep lexikographic order
-    return candidates;
+    total += distance(dm, current, path[0])?;
+
+    Ok((path, total))
 }
 
-fn make_move(path: &mut Path, k: usize, candidate: usize) {
-    path[k] = candidate;
-    send_progress(ProgressMessage::CityChange(candidate));
+fn distance(dm: &dyn DistanceSource, city_id1: usize, city_id2: usize) -> Result<f32, Error> {
+    dm.distance_between(city_id1, city_id2)
 }
 
-fn undo_move(path: &mut Path, k: usize) {
-    // we wouldnt change the first city
-    if k > 0 {
-        path[k] = UNVISITED_NODE;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::helpers::assert_approx;
+    use crate::tsp::distance_matrix::{DistanceMatrix, LazyDistances};
+    use crate::tsp::generator;
+    use crate::tsp::kdtree;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_spanning_tree_weight_on_line() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![0.0, 1.0], vec![0.0, 3.0]]);
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+
+        assert_approx(3.0, spanning_tree_weight(&dm, &[0, 1, 2]).unwrap());
+        assert_approx(0.0, spanning_tree_weight(&dm, &[1]).unwrap());
+    }
+
+    #[test]
+    fn test_lower_bound_with_closing_edge_only() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![0.0, 1.0], vec![2.0, 1.0]]);
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+
+        // no cities left, the bound must be the exact closing edge
+        assert_approx(1.0, lower_bound(&dm, 1, &[], 0).unwrap());
+    }
+
+    #[test]
+    fn test_solve_with_tsp5_example() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 0.5],
+            vec![1.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+        ]);
+
+        let tour = solve(&cities, &SolverOptions::default()).unwrap();
+        assert_approx(4.0, tour.total);
+        assert_eq!(5, tour.len());
+    }
+
+    #[test]
+    fn test_solve_finds_optimal_tour_of_grid() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let cities = generator::grid(3, 4, 0.0, &mut rng);
+
+        let tour = solve(&cities, &SolverOptions::default()).unwrap();
+        assert_approx(12.0, tour.total);
+    }
+
+    #[test]
+    fn test_solve_without_time_returns_greedy_tour() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 0.5],
+            vec![1.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+        ]);
+
+        let mut options = SolverOptions::default();
+        options.max_seconds = Some(0.0);
+
+        let tour = solve(&cities, &options).unwrap();
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+        let (greedy_path, greedy_distance) = greedy_tour(&dm, &[0, 1, 2, 3, 4]).unwrap();
+
+        assert_eq!(greedy_path, tour.route());
+        assert_approx(greedy_distance, tour.total);
+    }
+
+    #[test]
+    fn test_lazy_distances_give_same_bounds() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let cities = generator::grid(3, 3, 0.3, &mut rng);
+        let ids: Vec<usize> = cities.iter().map(|c| c.id).collect();
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+        let lazy = LazyDistances::from_cities(&cities).unwrap();
+
+        assert_eq!(
+            greedy_tour(&dm, &ids).unwrap(),
+            greedy_tour(&lazy, &ids).unwrap()
+        );
+        assert_approx(
+            lower_bound(&dm, ids[1], &ids[2..], ids[0]).unwrap(),
+            lower_bound(&lazy, ids[1], &ids[2..], ids[0]).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_open_nodes_pop_lowest_bound_first() {
+        let node = |path: &[usize], bound: f32| Node {
+            path: path.to_vec(),
+            unvisited: vec![],
+            cost: 0.0,
+            bound,
+        };
+
+        let mut open_nodes = BinaryHeap::new();
+        open_nodes.push(node(&[0, 1], 5.0));
+        open_nodes.push(node(&[0, 2], 3.0));
+        open_nodes.push(node(&[0, 3, 1], 3.0));
+        open_nodes.push(node(&[0, 4], 7.0));
+
+        let order: Vec<Path> = std::iter::from_fn(|| open_nodes.pop())
+            .map(|node| node.path)
+            .collect();
+        assert_eq!(
+            vec![vec![0, 3, 1], vec![0, 2], vec![0, 1], vec![0, 4]],
+            order
+        );
+    }
+
+    #[test]
+    fn test_node_budget_keeps_best_tour_so_far() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let cities = generator::grid(3, 4, 0.2, &mut rng);
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+        let ids: Vec<usize> = cities.iter().map(|c| c.id).collect();
+        let (_, greedy_distance) = greedy_tour(&dm, &ids).unwrap();
+
+        let mut options = SolverOptions::default();
+        let optimal = solve(&cities, &options).unwrap();
+
+        options.max_nodes = Some(3);
+        let tour = solve(&cities, &options).unwrap();
+
+        assert_eq!(cities.len(), tour.len());
+        assert!(tour.total <= greedy_distance + 1e-4);
+        assert!(tour.total >= optimal.total - 1e-4);
+    }
+
+    #[test]
+    fn test_solve_finds_optimal_tour_of_larger_grid() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let cities = generator::grid(4, 5, 0.0, &mut rng);
+
+        let tour = solve(&cities, &SolverOptions::default()).unwrap();
+        assert_approx(20.0, tour.total);
     }
 }
@@ -0,0 +1,121 @@
+/// City filters
+///
+/// `--only_cities` and `--exclude_cities` pick the cities of the problem to solve, so a part of
+/// the big instance can be solved without editing its TSPLIB file. They take the list of the city
+/// ids and the ranges of them, e.g `1,5,9-40`; the excluded cities are dropped from the cities
+/// the other filter keeps. The sections of the file, which refer to the dropped cities, e.g their
+/// fixed edges, are dropped with them.
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use super::tsplib::TspLibData;
+use super::Error;
+
+/// the cities the problem keeps, by their ids
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CityFilter {
+    only: Option<Vec<RangeInclusive<usize>>>,
+    excluded: Vec<RangeInclusive<usize>>,
+}
+
+impl CityFilter {
+    /// the filter of the lists of the city ids, None keeps all the cities
+    pub fn new(only: Option<&str>, excluded: Option<&str>) -> Result<Self, Error> {
+        Ok(CityFilter {
+            only: only.map(parse_ids).transpose()?,
+            excluded: excluded.map(parse_ids).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    /// true if the filter keeps all the cities
+    pub fn is_empty(&self) -> bool {
+        self.only.is_none() && self.excluded.is_empty()
+    }
+
+    pub fn keeps(&self, city_id: usize) -> bool {
+        let is_in = |ranges: &[RangeInclusive<usize>]| ranges.iter().any(|r| r.contains(&city_id));
+
+        self.only.as_deref().is_none_or(is_in) && !is_in(&self.excluded)
+    }
+
+    /// the problem with the cities the filter keeps, it fails if none of them is left
+    pub fn apply(&self, data: &TspLibData) -> Result<TspLibData, Error> {
+        if self.is_empty() {
+            return Ok(data.clone());
+        }
+
+        let subset = data.subset(|city_id| self.keeps(city_id));
+        if subset.len() == 0 {
+            return Err(Error::InvalidInput(format!(
+                "the city filters leave no city of {}",
+                data.name.trim()
+            )));
+        }
+
+        Ok(subset)
+    }
+}
+
+/// parses the comma separated city ids and the ranges of them, e.g `1,5,9-40`
+pub fn parse_ids(text: &str) -> Result<Vec<RangeInclusive<usize>>, Error> {
+    let invalid = |item: &str| {
+        Error::InvalidInput(format!(
+            "city ids: `{}` is not a city id or a range of them, e.g 9-40",
+            item
+        ))
+    };
+
+    text.split(',')
+        .map(str::trim)
+        .map(|item| {
+            let (first, last) = item.split_once('-').unwrap_or((item, item));
+            let first = usize::from_str(first.trim()).map_err(|_| invalid(item))?;
+            let last = usize::from_str(last.trim()).map_err(|_| invalid(item))?;
+            if first > last {
+                return Err(invalid(item));
+            }
+
+            Ok(first..=last)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    #[test]
+    fn test_parse_ids() {
+        assert_eq!(vec![1..=1, 5..=5, 9..=40], parse_ids("1,5, 9-40").unwrap());
+        assert!(parse_ids("").is_err());
+        assert!(parse_ids("1,,2").is_err());
+        assert!(parse_ids("40-9").is_err());
+        assert!(parse_ids("a-b").is_err());
+    }
+
+    #[test]
+    fn test_apply_keeps_only_cities_without_excluded() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![2.0, 0.0],
+            vec![3.0, 0.0],
+            vec![4.0, 0.0],
+        ]);
+        let data = TspLibData::new("line5".to_string(), "".to_string(), cities);
+
+        let filter = CityFilter::new(Some("0,2-4"), Some("3")).unwrap();
+        let ids: Vec<usize> = filter
+            .apply(&data)
+            .unwrap()
+            .cities()
+            .iter()
+            .map(|c| c.id)
+            .collect();
+        assert_eq!(vec![0, 2, 4], ids);
+
+        let filter = CityFilter::new(None, Some("0-4")).unwrap();
+        assert!(filter.apply(&data).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+/// Clock of the solvers
+///
+/// `std::time::Instant` panics in the browser, so the WebAssembly build of the `wasm` feature
+/// measures the time with `Date.now()` of JavaScript; the other builds use the `Instant` of the
+/// standard library as it is.
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+pub use std::time::Instant;
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+pub use self::browser::Instant;
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+mod browser {
+    use std::ops::Add;
+    use std::time::Duration;
+
+    /// the milliseconds since the epoch, as `Date.now()` tells them
+    #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+    pub struct Instant(f64);
+
+    impl Instant {
+        pub fn now() -> Self {
+            Instant(js_sys::Date::now())
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Instant::now().saturating_duration_since(*self)
+        }
+
+        // the clock of the browser may be turned back, then no time has passed
+        pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+            Duration::from_secs_f64((self.0 - earlier.0).max(0.0) / 1000.0)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+
+        fn add(self, duration: Duration) -> Instant {
+            Instant(self.0 + duration.as_secs_f64() * 1000.0)
+        }
+    }
+}
@@ -0,0 +1,409 @@
+/// Clustering of cities
+///
+/// Splitting cities into compact groups is the first step for decomposition approaches,
+/// multiple salesmen (mTSP) and territory design. The module offers 2 methods:
+///
+/// * `cluster_kmeans` - Lloyd's k-means with k-means++ seeding, it is fast, but the result
+///   depends on the seed and it prefers clusters of similar size;
+///
+/// * `cluster_nn_chain` - agglomerative clustering with Ward's linkage, built with
+///   the nearest-neighbor chain algorithm in O(n^2) time and O(n) memory; it is deterministic.
+///
+/// sources:
+/// * k-means++: http://ilpubs.stanford.edu:8090/778/1/2006-13.pdf
+/// * nearest-neighbor chain: https://en.wikipedia.org/wiki/Nearest-neighbor_chain_algorithm
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::kdtree::KDPoint;
+
+const MAX_KMEANS_ITERATIONS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct Cluster {
+    pub centroid: KDPoint, // mean of the cities, its id is the index of the cluster
+    pub cities: Vec<KDPoint>,
+}
+
+impl Cluster {
+    pub fn len(&self) -> usize {
+        self.cities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cities.is_empty()
+    }
+
+    pub fn city_ids(&self) -> Vec<usize> {
+        self.cities.iter().map(|c| c.id).collect()
+    }
+}
+
+/// splits cities into `k` clusters with k-means, same seed gives same clusters
+pub fn cluster_kmeans(cities: &[KDPoint], k: usize, seed: u64) -> Vec<Cluster> {
+    let k = k.min(cities.len());
+    if k == 0 {
+        return vec![];
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut centroids = kmeans_plus_plus(cities, k, &mut rng);
+    let mut labels = vec![usize::MAX; cities.len()];
+
+    for _ in 0..MAX_KMEANS_ITERATIONS {
+        let mut changed = false;
+        for (i, city) in cities.iter().enumerate() {
+            let nearest = nearest_centroid(city.coords(), &centroids);
+            if labels[i] != nearest {
+                labels[i] = nearest;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+
+        centroids = mean_coords(cities, &labels, k);
+
+        // empty cluster gets the city, which is farthest from its centroid
+        for cluster_id in 0..k {
+            if labels.contains(&cluster_id) {
+                continue;
+            }
+
+            let farthest = (0..cities.len())
+                .max_by(|&a, &b| {
+                    let dist_a = squared_distance(cities[a].coords(), &centroids[labels[a]]);
+                    let dist_b = squared_distance(cities[b].coords(), &centroids[labels[b]]);
+                    dist_a.partial_cmp(&dist_b).unwrap()
+                })
+                .unwrap();
+
+            labels[farthest] = cluster_id;
+            centroids = mean_coords(cities, &labels, k);
+        }
+    }
+
+    build_clusters(cities, &labels, k)
+}
+
+/// splits cities into `k` clusters by merging the closest clusters by Ward's linkage
+pub fn cluster_nn_chain(cities: &[KDPoint], k: usize) -> Vec<Cluster> {
+    let k = k.min(cities.len());
+    if k == 0 {
+        return vec![];
+    }
+
+    // the chain finds merges out of order, so we collect the full dendrogram
+    // and replay the n - k cheapest merges, Ward's linkage keeps the dendrogram monotone
+    let mut merges = nn_chain_merges(cities);
+    merges.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+
+    let mut parents: Vec<usize> = (0..cities.len()).collect();
+    for &(a, b, _) in merges.iter().take(cities.len() - k) {
+        let root_a = find_root(&mut parents, a);
+        let root_b = find_root(&mut parents, b);
+        parents[root_b] = root_a;
+    }
+
+    // label clusters in the order of their first city
+    let mut labels = vec![usize::MAX; cities.len()];
+    let mut root_labels: Vec<Option<usize>> = vec![None; cities.len()];
+    let mut n_labels = 0;
+    for (i, label) in labels.iter_mut().enumerate() {
+        let root = find_root(&mut parents, i);
+        *label = *root_labels[root].get_or_insert_with(|| {
+            n_labels += 1;
+            n_labels - 1
+        });
+    }
+
+    build_clusters(cities, &labels, k)
+}
+
+struct WardCluster {
+    centroid: Vec<f64>,
+    size: usize,
+    representative: usize, // position of any city in the cluster
+}
+
+// returns merges as (city position, city position, Ward's distance)
+fn nn_chain_merges(cities: &[KDPoint]) -> Vec<(usize, usize, f64)> {
+    let mut clusters: Vec<Option<WardCluster>> = cities
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            Some(WardCluster {
+                centroid: c.coords().to_vec(),
+                size: 1,
+                representative: i,
+            })
+        })
+        .collect();
+
+    let mut merges = Vec::with_capacity(cities.len());
+    let mut chain: Vec<usize> = vec![];
+    let mut n_active = cities.len();
+
+    while n_active > 1 {
+        if chain.is_empty() {
+            chain.push(clusters.iter().position(|c| c.is_some()).unwrap());
+        }
+
+        let top = *chain.last().unwrap();
+        let previous = if chain.len() > 1 {
+            Some(chain[chain.len() - 2])
+        } else {
+            None
+        };
+
+        let top_cluster = clusters[top].as_ref().unwrap();
+        // prefer the previous cluster on ties, otherwise the chain may cycle
+        let mut nearest = previous;
+        let mut nearest_distance = previous.map_or(f64::MAX, |p| {
+            ward_distance(top_cluster, clusters[p].as_ref().unwrap())
+        });
+
+        for (i, other) in clusters.iter().enumerate() {
+            if let Some(other) = other {
+                if i == top {
+                    continue;
+                }
+
+                let distance = ward_distance(top_cluster, other);
+                if distance < nearest_distance {
+                    nearest = Some(i);
+                    nearest_distance = distance;
+                }
+            }
+        }
+
+        let nearest = nearest.unwrap();
+        if Some(nearest) != previous {
+            chain.push(nearest);
+            continue;
+        }
+
+        // reciprocal nearest neighbors, merge them
+        chain.truncate(chain.len() - 2);
+        let a = clusters[top].take().unwrap();
+        let b = clusters[nearest].take().unwrap();
+        merges.push((a.representative, b.representative, nearest_distance));
+
+        clusters[top] = Some(merge_clusters(&a, &b));
+        n_active -= 1;
+    }
+
+    merges
+}
+
+fn ward_distance(a: &WardCluster, b: &WardCluster) -> f64 {
+    let size_a = a.size as f64;
+    let size_b = b.size as f64;
+
+    size_a * size_b / (size_a + size_b) * squared_distance(&a.centroid, &b.centroid)
+}
+
+fn merge_clusters(a: &WardCluster, b: &WardCluster) -> WardCluster {
+    let size = a.size + b.size;
+    let centroid = a
+        .centroid
+        .iter()
+        .zip(b.centroid.iter())
+        .map(|(x, y)| (x * a.size as f64 + y * b.size as f64) / size as f64)
+        .collect();
+
+    WardCluster {
+        centroid,
+        size,
+        representative: a.representative,
+    }
+}
+
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+
+    // compress the path for the next lookups
+    let mut node = i;
+    while parents[node] != root {
+        let next = parents[node];
+        parents[node] = root;
+        node = next;
+    }
+
+    root
+}
+
+// picks initial centroids far from each other, with probability proportional to D^2
+fn kmeans_plus_plus(cities: &[KDPoint], k: usize, rng: &mut StdRng) -> Vec<Vec<f64>> {
+    let mut centroids = vec![cities[rng.gen_range(0, cities.len())].coords().to_vec()];
+    let mut distances: Vec<f64> = cities
+        .iter()
+        .map(|c| squared_distance(c.coords(), &centroids[0]))
+        .collect();
+
+    while centroids.len() < k {
+        let total: f64 = distances.iter().sum();
+        let next_pos = if total > 0.0 {
+            let mut target = rng.gen::<f64>() * total;
+            let mut pos = distances.len() - 1;
+            for (i, d) in distances.iter().enumerate() {
+                if target < *d {
+                    pos = i;
+                    break;
+                }
+                target -= d;
+            }
+
+            pos
+        } else {
+            rng.gen_range(0, cities.len()) // all the cities are on the centroids
+        };
+
+        let next_centroid = cities[next_pos].coords().to_vec();
+        for (d, city) in distances.iter_mut().zip(cities.iter()) {
+            *d = d.min(squared_distance(city.coords(), &next_centroid));
+        }
+
+        centroids.push(next_centroid);
+    }
+
+    centroids
+}
+
+fn nearest_centroid(coords: &[f64], centroids: &[Vec<f64>]) -> usize {
+    let mut nearest = 0;
+    let mut nearest_distance = f64::MAX;
+
+    for (i, centroid) in centroids.iter().enumerate() {
+        let distance = squared_distance(coords, centroid);
+        if distance < nearest_distance {
+            nearest = i;
+            nearest_distance = distance;
+        }
+    }
+
+    nearest
+}
+
+fn mean_coords(cities: &[KDPoint], labels: &[usize], k: usize) -> Vec<Vec<f64>> {
+    let dim = cities[0].dim();
+    let mut sums = vec![vec![0.0; dim]; k];
+    let mut counts = vec![0; k];
+
+    for (city, &label) in cities.iter().zip(labels.iter()) {
+        counts[label] += 1;
+        for (sum, coord) in sums[label].iter_mut().zip(city.coords().iter()) {
+            *sum += coord;
+        }
+    }
+
+    for (sum, &count) in sums.iter_mut().zip(counts.iter()) {
+        if count > 0 {
+            sum.iter_mut().for_each(|x| *x /= count as f64);
+        }
+    }
+
+    sums
+}
+
+fn squared_distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y).powi(2)).sum()
+}
+
+fn build_clusters(cities: &[KDPoint], labels: &[usize], k: usize) -> Vec<Cluster> {
+    let centroids = mean_coords(cities, labels, k);
+    let mut clusters: Vec<Cluster> = centroids
+        .iter()
+        .enumerate()
+        .map(|(i, centroid)| Cluster {
+            centroid: KDPoint::new_with_id(i, centroid),
+            cities: vec![],
+        })
+        .collect();
+
+    for (city, &label) in cities.iter().zip(labels.iter()) {
+        clusters[label].cities.push(city.clone());
+    }
+
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    fn two_groups() -> Vec<KDPoint> {
+        kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![100.0, 100.0],
+            vec![1.0, 0.0],
+            vec![101.0, 100.0],
+            vec![0.0, 1.0],
+            vec![100.0, 101.0],
+        ])
+    }
+
+    fn sorted_ids(clusters: &[Cluster]) -> Vec<Vec<usize>> {
+        let mut ids: Vec<Vec<usize>> = clusters.iter().map(|c| c.city_ids()).collect();
+        ids.sort();
+
+        ids
+    }
+
+    #[test]
+    fn test_cluster_kmeans_separates_groups() {
+        let clusters = cluster_kmeans(&two_groups(), 2, 42);
+
+        assert_eq!(2, clusters.len());
+        assert_eq!(vec![vec![0, 2, 4], vec![1, 3, 5]], sorted_ids(&clusters));
+
+        let first_group = clusters.iter().find(|c| c.city_ids()[0] == 0).unwrap();
+        assert!((first_group.centroid.get(0).unwrap() - 1.0 / 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_cluster_kmeans_with_same_seed_gives_same_clusters() {
+        let cities = kdtree::build_points(
+            &(0..50)
+                .map(|i| vec![(i * 37 % 50) as f64, (i * 11 % 23) as f64])
+                .collect::<Vec<_>>(),
+        );
+
+        let clusters1 = cluster_kmeans(&cities, 4, 7);
+        let clusters2 = cluster_kmeans(&cities, 4, 7);
+
+        assert_eq!(4, clusters1.len());
+        assert!(clusters1.iter().all(|c| !c.is_empty()));
+        assert_eq!(sorted_ids(&clusters1), sorted_ids(&clusters2));
+        assert_eq!(50, clusters1.iter().map(|c| c.len()).sum::<usize>());
+    }
+
+    #[test]
+    fn test_cluster_nn_chain_separates_groups() {
+        let clusters = cluster_nn_chain(&two_groups(), 2);
+
+        assert_eq!(vec![vec![0, 2, 4], vec![1, 3, 5]], sorted_ids(&clusters));
+        assert_eq!(0, clusters[0].centroid.id);
+        assert_eq!(vec![0, 2, 4], clusters[0].city_ids());
+    }
+
+    #[test]
+    fn test_cluster_nn_chain_with_k_as_number_of_cities() {
+        let clusters = cluster_nn_chain(&two_groups(), 10);
+
+        assert_eq!(6, clusters.len());
+        assert!(clusters.iter().all(|c| c.len() == 1));
+    }
+
+    #[test]
+    fn test_clustering_without_cities_or_clusters() {
+        assert!(cluster_kmeans(&[], 3, 1).is_empty());
+        assert!(cluster_nn_chain(&two_groups(), 0).is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+/// Concorde interoperability
+///
+/// Concorde is the state-of-art exact solver for the symmetric TSP,
+/// teeline can export problems for it and read its solutions back.
+///
+/// Concorde reads TSPLIB files, but it expects that node ids are 1..n in the order of
+/// the coordinates, and the solution file lists the zero based positions of the nodes:
+///
+/// ```text
+/// 5
+/// 0 2 1
+/// 4 3
+/// ```
+///
+/// NB! Concorde uses EUC_2D metric, which rounds distances to the nearest integer,
+/// so scale up the coordinates of small instances before exporting them.
+///
+/// source: http://www.math.uwaterloo.ca/tsp/concorde.html
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::external::{self, absolute_path, file_stem, parent_dir};
+use super::kdtree::KDPoint;
+use super::tsplib::{self, TspLibData};
+use super::{Error, Solution};
+
+pub const DEFAULT_BINARY: &str = "concorde";
+
+/// writes the problem into TSPLIB file that Concorde accepts
+pub fn write_problem(path: &Path, data: &TspLibData) -> Result<(), Error> {
+    tsplib::write_to_file(path, &data.renumbered())
+}
+
+/// reads Concorde's .sol file and maps node positions back to the city ids
+pub fn read_solution(path: &Path, cities: &[KDPoint]) -> Result<Solution, Error> {
+    let f = File::open(path).map_err(Error::io("concorde: failed to open solution"))?;
+
+    parse_solution(BufReader::new(f), cities)
+}
+
+/// solves the problem with locally installed concorde binary;
+/// the problem and the solution files are kept in the `work_dir`
+pub fn solve(binary: &Path, data: &TspLibData, work_dir: &Path) -> Result<Solution, Error> {
+    let problem_path = work_dir.join(format!("{}.tsp", file_stem(data)));
+    let solution_path = work_dir.join(format!("{}.sol", file_stem(data)));
+
+    write_problem(&problem_path, data)?;
+    run(binary, &problem_path, &solution_path)?;
+
+    read_solution(&solution_path, data.cities())
+}
+
+/// executes concorde for the problem file, concorde writes its temporary files
+/// into the working directory, so we run it in the directory of the problem file
+pub fn run(binary: &Path, problem_path: &Path, solution_path: &Path) -> Result<(), Error> {
+    let args = vec![
+        PathBuf::from("-o"),
+        absolute_path(solution_path),
+        absolute_path(problem_path),
+    ];
+
+    external::run("concorde", binary, &args, &parent_dir(problem_path))
+}
+
+fn parse_solution<R: BufRead>(reader: R, cities: &[KDPoint]) -> Result<Solution, Error> {
+    let mut tokens: Vec<usize> = vec![];
+
+    for (line_no, line_res) in reader.lines().enumerate() {
+        let line =
+            line_res.map_err(|_| Error::parse("concorde", line_no + 1, "failed to read line"))?;
+
+        for token in line.split_whitespace() {
+            let val = usize::from_str(token)
+                .map_err(|_| Error::parse("concorde", line_no + 1, "invalid number"))?;
+            tokens.push(val);
+        }
+    }
+
+    if tokens.is_empty() {
+        return Err(Error::external(
+            "concorde",
+            "solution file is empty".to_string(),
+        ));
+    }
+
+    let n = tokens[0];
+    let positions = &tokens[1..];
+    if n != cities.len() || positions.len() != n {
+        return Err(Error::external(
+            "concorde",
+            format!(
+                "solution has {} nodes, but problem has {} cities",
+                positions.len(),
+                cities.len()
+            ),
+        ));
+    }
+
+    let mut route = Vec::with_capacity(n);
+    for &pos in positions.iter() {
+        match cities.get(pos) {
+            Some(city) => route.push(city.id),
+            None => {
+                return Err(Error::external(
+                    "concorde",
+                    format!("unknown node {} in solution", pos),
+                ))
+            }
+        }
+    }
+
+    Ok(Solution::new(&route, cities))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    #[test]
+    fn test_parse_solution_maps_positions_to_city_ids() {
+        let cities = vec![
+            KDPoint::new_with_id(10, &[0.0, 0.0]),
+            KDPoint::new_with_id(20, &[0.0, 1.0]),
+            KDPoint::new_with_id(30, &[1.0, 1.0]),
+            KDPoint::new_with_id(40, &[1.0, 0.0]),
+        ];
+
+        let res = parse_solution("4\n0 1\n3 2\n".as_bytes(), &cities);
+        assert!(res.is_ok());
+
+        let tour = res.unwrap();
+        assert_eq!(&[10, 20, 40, 30], tour.route());
+        assert!((tour.total - 4.828_427).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_parse_solution_with_wrong_number_of_nodes() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 1.0]]);
+
+        assert!(parse_solution("3\n0 1 2\n".as_bytes(), &cities).is_err());
+        assert!(parse_solution("".as_bytes(), &cities).is_err());
+    }
+
+    #[test]
+    fn test_parse_solution_with_unknown_node() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 1.0]]);
+
+        assert!(parse_solution("2\n0 5\n".as_bytes(), &cities).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+/// Configuration file
+///
+/// The TOML file sets the solver, the input and the output paths, and the solver options
+/// under the same names as the command-line flags:
+///
+/// ```toml
+/// solver = "sa"
+/// input = "berlin52.tsp"
+/// output = "berlin52.tour"
+///
+/// epochs = 100000
+/// cooling_rate = 0.0005
+/// max_seconds = 30
+/// seed = 42
+/// ```
+///
+/// The relative paths are resolved from the directory of the configuration file;
+/// the flags given on the command-line override the values of the file.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml::Value;
+
+use super::grid::SpatialIndex;
+use super::neighbors::Candidates;
+use super::route::Neighborhood;
+use super::simulated_annealing::CoolingSchedule;
+use super::stochastic_hill::HillStrategy;
+use super::{Error, SolverOptions};
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub solver: Option<String>,
+    pub input: Option<PathBuf>,
+    pub output: Option<PathBuf>, // where to save the tour, same as `--output`
+    pub options: SolverOptions,  // the defaults with the values of the file
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            solver: None,
+            input: None,
+            output: None,
+            options: SolverOptions::default(),
+        }
+    }
+}
+
+impl Config {
+    /// resolves the relative input and output paths from the directory
+    pub fn relative_to(mut self, dir: &Path) -> Self {
+        self.input = self.input.map(|input| dir.join(input));
+        self.output = self.output.map(|output| dir.join(output));
+
+        self
+    }
+}
+
+pub fn read_from_file(path: &Path) -> Result<Config, Error> {
+    let text = fs::read_to_string(path).map_err(Error::io("config: failed to read file"))?;
+    let config = parse(&text)?;
+
+    match path.parent() {
+        Some(config_dir) => Ok(config.relative_to(config_dir)),
+        None => Ok(config),
+    }
+}
+
+pub fn parse(text: &str) -> Result<Config, Error> {
+    let table = match text.parse::<Value>() {
+        Ok(Value::Table(table)) => table,
+        Ok(_) => return Err(invalid_config("the file must be a table of keys")),
+        Err(err) => return Err(invalid_config(&err.to_string())),
+    };
+
+    let mut config = Config::default();
+    for (key, value) in table.iter() {
+        match key.replace('-', "_").as_str() {
+            "solver" => config.solver = Some(string_value(key, value)?.to_lowercase()),
+            "input" => config.input = Some(PathBuf::from(string_value(key, value)?)),
+            "output" => config.output = Some(PathBuf::from(string_value(key, value)?)),
+            _ => set_option(&mut config.options, key, value)?,
+        }
+    }
+
+    Ok(config)
+}
+
+/// sets the solver option of the key, e.g the options of the `serve` requests
+pub fn set_option(options: &mut SolverOptions, key: &str, value: &Value) -> Result<(), Error> {
+    // the kebab-case names are accepted in the same way as the command-line aliases
+    match key.replace('-', "_").as_str() {
+        "epochs" => options.epochs = usize_value(key, value)?,
+        "platoo_epochs" => options.platoo_epochs = usize_value(key, value)?,
+        "hill_strategy" => {
+            options.hill_strategy =
+                string_value(key, value)?
+                    .to_lowercase()
+                    .parse()
+                    .map_err(|_| {
+                        invalid_value(
+                            key,
+                            &format!("one of {}", HillStrategy::variants().join(", ")),
+                        )
+                    })?
+        }
+        "n_samples" => options.n_samples = Some(usize_value(key, value)?),
+        "verbose" => options.verbose = bool_value(key, value)?,
+        "show_progress" => options.show_progress = bool_value(key, value)?,
+        "n_nearest" => options.n_nearest = usize_value(key, value)?,
+        "candidates" => {
+            options.candidates = string_value(key, value)?
+                .to_lowercase()
+                .parse()
+                .map_err(|_| {
+                    invalid_value(
+                        key,
+                        &format!("one of {}", Candidates::variants().join(", ")),
+                    )
+                })?
+        }
+        "start_city" => options.start_city = Some(usize_value(key, value)?),
+        "end_city" => options.end_city = Some(usize_value(key, value)?),
+        "all_starts" => options.all_starts = bool_value(key, value)?,
+        "open_tour" => options.open_tour = bool_value(key, value)?,
+        "spatial_index" => {
+            options.spatial_index =
+                string_value(key, value)?
+                    .to_lowercase()
+                    .parse()
+                    .map_err(|_| {
+                        invalid_value(
+                            key,
+                            &format!("one of {}", SpatialIndex::variants().join(", ")),
+                        )
+                    })?
+        }
+        "n_elite" => options.n_elite = usize_value(key, value)?,
+        "population_size" => options.population_size = usize_value(key, value)?,
+        "n_offspring" => options.n_offspring = Some(usize_value(key, value)?),
+        "n_islands" => options.n_islands = usize_value(key, value)?,
+        "migration_interval" => options.migration_interval = usize_value(key, value)?,
+        "mutation_probability" => options.mutation_probability = f32_value(key, value)?,
+        "cooling_rate" => options.cooling_rate = f32_value(key, value)?,
+        "cooling_schedule" => {
+            options.cooling_schedule =
+                string_value(key, value)?
+                    .to_lowercase()
+                    .parse()
+                    .map_err(|_| {
+                        invalid_value(
+                            key,
+                            &format!("one of {}", CoolingSchedule::variants().join(", ")),
+                        )
+                    })?
+        }
+        "neighborhood" => {
+            options.neighborhood =
+                string_value(key, value)?
+                    .to_lowercase()
+                    .parse()
+                    .map_err(|_| {
+                        invalid_value(
+                            key,
+                            &format!("one of {}", Neighborhood::variants().join(", ")),
+                        )
+                    })?
+        }
+        "min_temperature" => options.min_temperature = f32_value(key, value)?,
+        "max_temperature" => options.max_temperature = f32_value(key, value)?,
+        "reheat" => match f32_value(key, value)? {
+            reheat if reheat > 0.0 && reheat <= 1.0 => options.reheat = Some(reheat),
+            _ => return Err(invalid_value(key, "a fraction in (0, 1]")),
+        },
+        "restart_from_best" => match usize_value(key, value)? {
+            0 => return Err(invalid_value(key, "a positive integer")),
+            n_epochs => options.restart_from_best = Some(n_epochs),
+        },
+        "aspiration" => options.aspiration = bool_value(key, value)?,
+        "seed" => options.seed = Some(usize_value(key, value)? as u64),
+        "target_cost" => options.target_cost = Some(f32_value(key, value)?),
+        "max_stale_epochs" => match usize_value(key, value)? {
+            0 => return Err(invalid_value(key, "a positive integer")),
+            n_epochs => options.max_stale_epochs = Some(n_epochs),
+        },
+        "max_nodes" => options.max_nodes = Some(usize_value(key, value)?),
+        "max_memory" => options.max_memory_mb = usize_value(key, value)?,
+        "max_seconds" => match f32_value(key, value)? {
+            max_seconds if max_seconds > 0.0 => options.max_seconds = Some(max_seconds),
+            _ => return Err(invalid_value(key, "a positive number")),
+        },
+        _ => return Err(invalid_config(&format!("unknown key `{}`", key))),
+    }
+
+    Ok(())
+}
+
+fn invalid_config(message: &str) -> Error {
+    Error::InvalidInput(format!("config: {}", message))
+}
+
+fn invalid_value(key: &str, expected: &str) -> Error {
+    invalid_config(&format!("`{}` must be {}", key, expected))
+}
+
+fn string_value<'a>(key: &str, value: &'a Value) -> Result<&'a str, Error> {
+    value.as_str().ok_or_else(|| invalid_value(key, "a string"))
+}
+
+fn bool_value(key: &str, value: &Value) -> Result<bool, Error> {
+    value
+        .as_bool()
+        .ok_or_else(|| invalid_value(key, "true or false"))
+}
+
+fn usize_value(key: &str, value: &Value) -> Result<usize, Error> {
+    value
+        .as_integer()
+        .filter(|n| *n >= 0)
+        .map(|n| n as usize)
+        .ok_or_else(|| invalid_value(key, "a non-negative integer"))
+}
+
+// the integers are accepted too, e.g `max_seconds = 30`
+fn f32_value(key: &str, value: &Value) -> Result<f32, Error> {
+    value
+        .as_float()
+        .or_else(|| value.as_integer().map(|n| n as f64))
+        .map(|x| x as f32)
+        .ok_or_else(|| invalid_value(key, "a number"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sets_options() {
+        let text = r#"
+            solver = "SA"
+            input = "berlin52.tsp"
+            epochs = 500
+            cooling-rate = 0.5
+            max_seconds = 2
+            reheat = 0.25
+            seed = 42
+            verbose = true
+            aspiration = false
+            start_city = 3
+            all-starts = true
+            candidates = "Delaunay"
+            neighborhood = "mixed"
+            hill-strategy = "best"
+        "#;
+
+        let config = parse(text).unwrap();
+
+        assert_eq!(Some("sa".to_string()), config.solver);
+        assert_eq!(Some(PathBuf::from("berlin52.tsp")), config.input);
+        assert_eq!(None, config.output);
+        assert_eq!(500, config.options.epochs);
+        assert_eq!(0.5, config.options.cooling_rate);
+        assert_eq!(Some(2.0), config.options.max_seconds);
+        assert_eq!(Some(0.25), config.options.reheat);
+        assert_eq!(Some(42), config.options.seed);
+        assert!(config.options.verbose);
+        assert!(!config.options.aspiration);
+        assert_eq!(Some(3), config.options.start_city);
+        assert!(config.options.all_starts);
+        assert_eq!(Candidates::Delaunay, config.options.candidates);
+        assert_eq!(Neighborhood::Mixed, config.options.neighborhood);
+        assert_eq!(HillStrategy::Best, config.options.hill_strategy);
+        // the rest keep the defaults
+        assert_eq!(SolverOptions::default().n_elite, config.options.n_elite);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_key() {
+        let err = parse("epohcs = 10").unwrap_err();
+
+        assert_eq!(
+            "invalid input: config: unknown key `epohcs`",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_wrong_type() {
+        let err = parse("epochs = -1").unwrap_err();
+        assert_eq!(
+            "invalid input: config: `epochs` must be a non-negative integer",
+            err.to_string()
+        );
+
+        assert!(parse("max_seconds = 0").is_err());
+        assert!(parse("reheat = 1.5").is_err());
+        assert!(parse("restart_from_best = 0").is_err());
+        assert!(parse("max_stale_epochs = 0").is_err());
+        assert!(parse("candidates = \"nearest\"").is_err());
+        assert!(parse("neighborhood = \"2opt\"").is_err());
+        assert!(parse("verbose = \"yes\"").is_err());
+        assert!(parse("epochs = ").is_err());
+    }
+
+    #[test]
+    fn test_relative_to_resolves_relative_paths() {
+        let config = parse("input = \"problem.tsp\"\noutput = \"/tmp/problem.tour\"").unwrap();
+
+        let config = config.relative_to(Path::new("configs"));
+
+        assert_eq!(Some(PathBuf::from("configs/problem.tsp")), config.input);
+        assert_eq!(Some(PathBuf::from("/tmp/problem.tour")), config.output);
+    }
+}
@@ -0,0 +1,485 @@
+/// Capacitated vehicle routing
+///
+/// The CVRP problem has a depot and the cities with their demands; the vehicles of the same
+/// capacity start from the depot, serve the cities of their route and return to the depot.
+/// The TSPLIB files of the problems have `CAPACITY`, `DEMAND_SECTION` and `DEPOT_SECTION`:
+///
+/// ```text
+/// teeline cvrp -i ./data/cvrp/A-n32-k5.vrp
+/// ```
+///
+/// The routes are built with the savings of Clarke and Wright, every route starts as a return
+/// trip to a single city and the routes, whose ends save the most when they are joined, are
+/// merged while the vehicle has room for them. Then the local search improves every route with
+/// 2-opt and moves or swaps the cities between the routes, until no move shortens them.
+///
+/// The solution has several routes, so it is not a `Solution` of the TSP solvers.
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::kdtree::KDPoint;
+use super::tsplib::TspLibData;
+use super::{validate_cities, Deadline, Error, SolverOptions};
+
+// the smallest gain of the move, the rounding errors must not undo the earlier moves
+const MIN_GAIN: f32 = 1e-5;
+
+/// the cities with their demands, the depot is one of the cities
+#[derive(Debug, Clone)]
+pub struct CvrpProblem {
+    cities: Vec<KDPoint>,
+    demands: Vec<usize>, // by the positions of the cities
+    depot: usize,        // the position of the depot
+    capacity: usize,
+}
+
+impl CvrpProblem {
+    /// checks the demands: every city has one, the depot has none and no demand is bigger than
+    /// the capacity
+    pub fn new(
+        cities: &[KDPoint],
+        capacity: usize,
+        demands: &[(usize, usize)],
+        depot_id: usize,
+    ) -> Result<Self, Error> {
+        validate_cities(cities, 2)?;
+        let invalid = |message: String| Err(Error::InvalidInput(format!("cvrp: {}", message)));
+
+        let pos_of: HashMap<usize, usize> =
+            cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+        let depot = match pos_of.get(&depot_id) {
+            Some(pos) => *pos,
+            None => return invalid(format!("the depot {} is not a city", depot_id)),
+        };
+
+        let mut city_demands = vec![None; cities.len()];
+        for (city_id, demand) in demands.iter() {
+            match pos_of.get(city_id) {
+                Some(pos) => city_demands[*pos] = Some(*demand),
+                None => return invalid(format!("the demand of the unknown city {}", city_id)),
+            }
+        }
+
+        let mut checked_demands = Vec::with_capacity(cities.len());
+        for (pos, demand) in city_demands.into_iter().enumerate() {
+            let demand = match demand {
+                Some(demand) => demand,
+                None if pos == depot => 0,
+                None => return invalid(format!("the city {} has no demand", cities[pos].id)),
+            };
+            if pos == depot && demand > 0 {
+                return invalid(format!("the depot {} has the demand {}", depot_id, demand));
+            }
+            if demand > capacity {
+                return invalid(format!(
+                    "the demand {} of the city {} is bigger than the capacity {}",
+                    demand, cities[pos].id, capacity
+                ));
+            }
+
+            checked_demands.push(demand);
+        }
+
+        Ok(CvrpProblem {
+            cities: cities.to_vec(),
+            demands: checked_demands,
+            depot,
+            capacity,
+        })
+    }
+
+    /// the problem of the TSPLIB file, the first depot or, without the DEPOT_SECTION,
+    /// the first city is the depot
+    pub fn from_tsplib(data: &TspLibData) -> Result<Self, Error> {
+        let capacity = data
+            .capacity()
+            .ok_or_else(|| Error::InvalidInput(format!("cvrp: {} has no CAPACITY", data.name)))?;
+        if data.depots().len() > 1 {
+            return Err(Error::InvalidInput(
+                "cvrp: only the problems with a single depot are supported".to_string(),
+            ));
+        }
+
+        let depot_id = match data.depots().first() {
+            Some(depot_id) => *depot_id,
+            None => data.cities().first().map_or(0, |city| city.id),
+        };
+
+        CvrpProblem::new(data.cities(), capacity, data.demands(), depot_id)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn depot_id(&self) -> usize {
+        self.cities[self.depot].id
+    }
+
+    fn distance(&self, a: usize, b: usize) -> f32 {
+        self.cities[a].distance(&self.cities[b])
+    }
+
+    // the length of the route from the depot through the cities back to the depot
+    fn route_length(&self, route: &[usize]) -> f32 {
+        let mut length = 0.0;
+        let mut previous = self.depot;
+        for city in route.iter().chain(std::iter::once(&self.depot)) {
+            length += self.distance(previous, *city);
+            previous = *city;
+        }
+
+        length
+    }
+
+    fn load(&self, route: &[usize]) -> usize {
+        route.iter().map(|city| self.demands[*city]).sum()
+    }
+}
+
+/// the routes of the vehicles by the city ids, without the depot at their ends
+#[derive(Debug, Clone, PartialEq)]
+pub struct CvrpSolution {
+    pub routes: Vec<Vec<usize>>,
+    pub loads: Vec<usize>,
+    pub total: f32, // the length of all the routes
+}
+
+impl fmt::Display for CvrpSolution {
+    /// the format of the CVRPLIB solutions
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, route) in self.routes.iter().enumerate() {
+            let ids: Vec<String> = route.iter().map(|id| id.to_string()).collect();
+            writeln!(f, "Route #{}: {}", i + 1, ids.join(" "))?;
+        }
+
+        writeln!(f, "Cost {}", self.total)
+    }
+}
+
+/// builds the routes with the savings and improves them with the local search,
+/// it stops early after `max_seconds`
+pub fn solve(problem: &CvrpProblem, options: &SolverOptions) -> Result<CvrpSolution, Error> {
+    let deadline = options.deadline();
+
+    let mut routes = savings_routes(problem);
+    if options.verbose {
+        println!(
+            "CVRP: the savings built {} routes of {:.2}",
+            routes.len(),
+            total_length(problem, &routes)
+        );
+    }
+
+    let mut n_passes = 0;
+    while !deadline.is_over() && improve_routes(problem, &mut routes, &deadline) {
+        n_passes += 1;
+        routes.retain(|route| !route.is_empty());
+
+        if options.verbose {
+            println!(
+                "CVRP: pass {} shortened the routes to {:.2}",
+                n_passes,
+                total_length(problem, &routes)
+            );
+        }
+    }
+    routes.retain(|route| !route.is_empty());
+
+    Ok(CvrpSolution {
+        total: total_length(problem, &routes),
+        loads: routes.iter().map(|route| problem.load(route)).collect(),
+        routes: routes
+            .iter()
+            .map(|route| route.iter().map(|pos| problem.cities[*pos].id).collect())
+            .collect(),
+    })
+}
+
+fn total_length(problem: &CvrpProblem, routes: &[Vec<usize>]) -> f32 {
+    routes.iter().map(|route| problem.route_length(route)).sum()
+}
+
+// the routes by the positions of the cities; the pairs of the cities are joined in the order
+// of their savings, when both of them are at the end of their routes and the vehicle has room
+fn savings_routes(problem: &CvrpProblem) -> Vec<Vec<usize>> {
+    let depot = problem.depot;
+    let customers: Vec<usize> = (0..problem.cities.len()).filter(|c| *c != depot).collect();
+
+    let mut savings: Vec<(f32, usize, usize)> = vec![];
+    for (k, a) in customers.iter().copied().enumerate() {
+        for b in customers[(k + 1)..].iter().copied() {
+            let saving =
+                problem.distance(depot, a) + problem.distance(depot, b) - problem.distance(a, b);
+            savings.push((saving, a, b));
+        }
+    }
+    savings.sort_by(|x, y| y.0.partial_cmp(&x.0).unwrap_or(Ordering::Equal));
+
+    let mut routes: Vec<Vec<usize>> = customers.iter().map(|c| vec![*c]).collect();
+    let mut route_of = vec![usize::MAX; problem.cities.len()];
+    for (r, c) in customers.iter().enumerate() {
+        route_of[*c] = r;
+    }
+    let mut loads: Vec<usize> = customers.iter().map(|c| problem.demands[*c]).collect();
+
+    for (saving, a, b) in savings.into_iter() {
+        let (ra, rb) = (route_of[a], route_of[b]);
+        if saving <= 0.0 {
+            break;
+        }
+        if ra == rb || loads[ra] + loads[rb] > problem.capacity {
+            continue;
+        }
+
+        let is_end = |route: &[usize], city: usize| {
+            route.first() == Some(&city) || route.last() == Some(&city)
+        };
+        if !is_end(&routes[ra], a) || !is_end(&routes[rb], b) {
+            continue;
+        }
+
+        // the route of `a` ends with it and the route of `b` starts with it
+        let mut merged = std::mem::take(&mut routes[ra]);
+        if merged.last() != Some(&a) {
+            merged.reverse();
+        }
+        let mut tail = std::mem::take(&mut routes[rb]);
+        if tail.first() != Some(&b) {
+            tail.reverse();
+        }
+
+        for city in tail.iter() {
+            route_of[*city] = ra;
+        }
+        merged.extend(tail);
+        routes[ra] = merged;
+        loads[ra] += loads[rb];
+        loads[rb] = 0;
+    }
+
+    routes.retain(|route| !route.is_empty());
+    routes
+}
+
+// one pass of the moves over all the cities, true if any of them shortened the routes
+fn improve_routes(problem: &CvrpProblem, routes: &mut [Vec<usize>], deadline: &Deadline) -> bool {
+    let mut improved = false;
+
+    for route in routes.iter_mut() {
+        improved |= two_opt_route(problem, route);
+    }
+
+    for r in 0..routes.len() {
+        if deadline.is_over() {
+            break;
+        }
+
+        improved |= relocate_from(problem, routes, r);
+        improved |= swap_from(problem, routes, r);
+    }
+
+    improved
+}
+
+// the city before the position `i` of the route, the depot before the first one
+fn before(problem: &CvrpProblem, route: &[usize], i: usize) -> usize {
+    if i == 0 {
+        problem.depot
+    } else {
+        route[i - 1]
+    }
+}
+
+// the city after the position `i` of the route, the depot after the last one
+fn after(problem: &CvrpProblem, route: &[usize], i: usize) -> usize {
+    route.get(i + 1).copied().unwrap_or(problem.depot)
+}
+
+// reverses the segments of the route while it shortens it
+fn two_opt_route(problem: &CvrpProblem, route: &mut [usize]) -> bool {
+    let mut improved = false;
+    let mut found = true;
+
+    while found {
+        found = false;
+        // the segment i..=j is reversed, the edges before i and after j are replaced
+        for i in 0..route.len() {
+            for j in (i + 1)..route.len() {
+                let (p, s) = (before(problem, route, i), after(problem, route, j));
+                let delta = problem.distance(p, route[j]) + problem.distance(route[i], s)
+                    - problem.distance(p, route[i])
+                    - problem.distance(route[j], s);
+
+                if delta < -MIN_GAIN {
+                    route[i..=j].reverse();
+                    found = true;
+                    improved = true;
+                }
+            }
+        }
+    }
+
+    improved
+}
+
+// moves the cities of the route to the cheapest place in another route, which has room for them
+fn relocate_from(problem: &CvrpProblem, routes: &mut [Vec<usize>], r: usize) -> bool {
+    let mut improved = false;
+    let mut i = 0;
+
+    while i < routes[r].len() {
+        let city = routes[r][i];
+        let (p, s) = (
+            before(problem, &routes[r], i),
+            after(problem, &routes[r], i),
+        );
+        let removed =
+            problem.distance(p, city) + problem.distance(city, s) - problem.distance(p, s);
+
+        let mut best: Option<(f32, usize, usize)> = None;
+        for (other, route) in routes.iter().enumerate() {
+            if other == r || problem.load(route) + problem.demands[city] > problem.capacity {
+                continue;
+            }
+
+            // the city goes before the position k, the last k is after the last city
+            for k in 0..=route.len() {
+                let (a, b) = (
+                    before(problem, route, k),
+                    route.get(k).copied().unwrap_or(problem.depot),
+                );
+                let delta = problem.distance(a, city) + problem.distance(city, b)
+                    - problem.distance(a, b)
+                    - removed;
+
+                if delta < -MIN_GAIN && best.is_none_or(|(best_delta, _, _)| delta < best_delta) {
+                    best = Some((delta, other, k));
+                }
+            }
+        }
+
+        match best {
+            Some((_, other, k)) => {
+                routes[r].remove(i);
+                routes[other].insert(k, city);
+                improved = true;
+            }
+            None => i += 1,
+        }
+    }
+
+    improved
+}
+
+// swaps the cities of the route with the cities of the other routes, when both vehicles have room
+fn swap_from(problem: &CvrpProblem, routes: &mut [Vec<usize>], r: usize) -> bool {
+    let mut improved = false;
+
+    for i in 0..routes[r].len() {
+        let a = routes[r][i];
+        let (pa, sa) = (
+            before(problem, &routes[r], i),
+            after(problem, &routes[r], i),
+        );
+        let load_r = problem.load(&routes[r]);
+
+        'routes: for other in 0..routes.len() {
+            if other == r {
+                continue;
+            }
+
+            let load_other = problem.load(&routes[other]);
+            for k in 0..routes[other].len() {
+                let b = routes[other][k];
+                if load_r - problem.demands[a] + problem.demands[b] > problem.capacity
+                    || load_other - problem.demands[b] + problem.demands[a] > problem.capacity
+                {
+                    continue;
+                }
+
+                let (pb, sb) = (
+                    before(problem, &routes[other], k),
+                    after(problem, &routes[other], k),
+                );
+                let delta = problem.distance(pa, b)
+                    + problem.distance(b, sa)
+                    + problem.distance(pb, a)
+                    + problem.distance(a, sb)
+                    - problem.distance(pa, a)
+                    - problem.distance(a, sa)
+                    - problem.distance(pb, b)
+                    - problem.distance(b, sb);
+
+                if delta < -MIN_GAIN {
+                    routes[r][i] = b;
+                    routes[other][k] = a;
+                    improved = true;
+                    break 'routes;
+                }
+            }
+        }
+    }
+
+    improved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    // the depot in the middle, 2 clusters of the cities on its sides
+    fn problem(capacity: usize) -> CvrpProblem {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![10.0, 0.0],
+            vec![10.0, 1.0],
+            vec![11.0, 0.0],
+            vec![-10.0, 0.0],
+            vec![-10.0, 1.0],
+            vec![-11.0, 0.0],
+        ]);
+        let demands = vec![(1, 2), (2, 2), (3, 2), (4, 2), (5, 2), (6, 2)];
+
+        CvrpProblem::new(&cities, capacity, &demands, 0).unwrap()
+    }
+
+    #[test]
+    fn test_solve_serves_every_city_within_capacity() {
+        let problem = problem(6);
+        let solution = solve(&problem, &SolverOptions::default()).unwrap();
+
+        let mut visited: Vec<usize> = solution.routes.iter().flatten().copied().collect();
+        visited.sort();
+        assert_eq!(vec![1, 2, 3, 4, 5, 6], visited);
+        assert!(solution
+            .loads
+            .iter()
+            .all(|load| *load <= problem.capacity()));
+
+        // a vehicle serves a cluster
+        assert_eq!(2, solution.routes.len());
+        assert!(solution.total < 46.0, "{}", solution.total);
+    }
+
+    #[test]
+    fn test_small_capacity_needs_more_vehicles() {
+        let solution = solve(&problem(4), &SolverOptions::default()).unwrap();
+
+        // the demands 12 need at least 3 vehicles
+        assert!(solution.routes.len() >= 3);
+        assert!(solution.loads.iter().all(|load| *load <= 4));
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_demands() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 0.0], vec![2.0, 0.0]]);
+
+        assert!(CvrpProblem::new(&cities, 5, &[(1, 2)], 0).is_err());
+        assert!(CvrpProblem::new(&cities, 5, &[(1, 2), (2, 6)], 0).is_err());
+        assert!(CvrpProblem::new(&cities, 5, &[(1, 2), (2, 3)], 7).is_err());
+        assert!(CvrpProblem::new(&cities, 5, &[(1, 2), (2, 3)], 0).is_ok());
+    }
+}
@@ -0,0 +1,410 @@
+/// Delaunay triangulation
+///
+/// The Delaunay triangulation connects the cities, which have a circle through them with no
+/// other city inside. Its edges contain most of the edges of the optimal tour, also the edges
+/// between the clusters, where all the nearest cities of a city are in its own cluster.
+///
+/// The cities are inserted one by one with the Bowyer-Watson algorithm: the triangles, whose
+/// circumcircle contains the new city, are removed and the hole is connected to the city.
+/// The triangle of the city is found by walking from the last triangle, which is close by as
+/// the cities are inserted in the order of the Hilbert curve, so it takes about O(n log n).
+///
+/// The first triangle, which contains all the cities, has its vertices at the infinity: their
+/// coordinates are the polynomials of the scale `L` and the tests take the sign of the polynomial
+/// at the infinity. A large finite triangle would cut off the edges on the convex hull.
+///
+/// * Bowyer-Watson: https://en.wikipedia.org/wiki/Bowyer%E2%80%93Watson_algorithm
+use std::collections::HashMap;
+use std::ops::{Add, Mul, Sub};
+
+use super::hilbert;
+use super::kdtree::KDPoint;
+
+// the directions of the vertices of the super triangle, counter-clockwise
+const SUPER_DIRECTIONS: [(f64, f64); 3] = [(-1.0, -1.0), (1.0, -1.0), (0.0, 1.0)];
+
+type Point = (f64, f64);
+
+/// polynomial of the scale of the super triangle, the coefficients from the constant up
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Poly([f64; 5]);
+
+impl Poly {
+    fn linear(constant: f64, slope: f64) -> Self {
+        Poly([constant, slope, 0.0, 0.0, 0.0])
+    }
+
+    // the sign of the polynomial at the infinity is the sign of its highest coefficient
+    fn sign(&self) -> f64 {
+        self.0
+            .iter()
+            .rev()
+            .find(|c| **c != 0.0)
+            .map_or(0.0, |c| c.signum())
+    }
+}
+
+impl Add for Poly {
+    type Output = Poly;
+
+    fn add(self, other: Poly) -> Poly {
+        let mut sum = self.0;
+        for (c, o) in sum.iter_mut().zip(other.0.iter()) {
+            *c += o;
+        }
+
+        Poly(sum)
+    }
+}
+
+impl Sub for Poly {
+    type Output = Poly;
+
+    fn sub(self, other: Poly) -> Poly {
+        let mut difference = self.0;
+        for (c, o) in difference.iter_mut().zip(other.0.iter()) {
+            *c -= o;
+        }
+
+        Poly(difference)
+    }
+}
+
+// the tests multiply at most 4 coordinates, the higher powers never appear
+impl Mul for Poly {
+    type Output = Poly;
+
+    fn mul(self, other: Poly) -> Poly {
+        let mut product = [0.0; 5];
+        for (i, a) in self.0.iter().enumerate() {
+            for (j, b) in other.0.iter().enumerate() {
+                if *a != 0.0 && *b != 0.0 {
+                    product[i + j] += a * b;
+                }
+            }
+        }
+
+        Poly(product)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Triangle {
+    vertices: [usize; 3],         // counter-clockwise
+    adjacent: [Option<usize>; 3], // the triangle across the edge opposite to the vertex
+    is_alive: bool,
+}
+
+struct Triangulation {
+    points: Vec<Point>,
+    triangles: Vec<Triangle>,
+    last: usize, // the walks start from the last new triangle
+}
+
+/// the edges of the Delaunay triangulation by the positions of the cities in the `cities`,
+/// each edge once with the smaller position first; the cities at the same place are connected
+/// to the first of them
+pub fn edges(cities: &[KDPoint]) -> Vec<(usize, usize)> {
+    let position_of: HashMap<usize, usize> =
+        cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+    let mut first_at: HashMap<(u64, u64), usize> = HashMap::new();
+    let mut edges = vec![];
+
+    // the positions of the cities, which are not at the same place as an earlier one
+    let mut order = vec![];
+    for id in hilbert::curve_order(cities) {
+        let i = position_of[&id];
+        match first_at.get(&place(&cities[i])) {
+            Some(first) => edges.push(((*first).min(i), (*first).max(i))),
+            None => {
+                first_at.insert(place(&cities[i]), i);
+                order.push(i);
+            }
+        }
+    }
+
+    if order.len() > 1 {
+        let points = order.iter().map(|i| coords(&cities[*i])).collect();
+        let triangulation = Triangulation::new(points);
+
+        for (a, b) in triangulation.edges() {
+            edges.push((order[a].min(order[b]), order[a].max(order[b])));
+        }
+    }
+
+    edges.sort_unstable();
+    edges.dedup();
+    edges
+}
+
+fn coords(city: &KDPoint) -> Point {
+    (city.get(0).unwrap_or(0.0), city.get(1).unwrap_or(0.0))
+}
+
+// adding 0.0 turns -0.0 into 0.0
+fn place(city: &KDPoint) -> (u64, u64) {
+    (
+        (city.get(0).unwrap_or(0.0) + 0.0).to_bits(),
+        (city.get(1).unwrap_or(0.0) + 0.0).to_bits(),
+    )
+}
+
+// positive when c is on the left of the line from a to b
+fn orientation<T>(a: (T, T), b: (T, T), c: (T, T)) -> T
+where
+    T: Copy + Sub<Output = T> + Mul<Output = T>,
+{
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+// positive when d is inside the circumcircle of the counter-clockwise triangle a, b, c
+fn in_circle<T>(a: (T, T), b: (T, T), c: (T, T), d: (T, T)) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+{
+    let (adx, ady) = (a.0 - d.0, a.1 - d.1);
+    let (bdx, bdy) = (b.0 - d.0, b.1 - d.1);
+    let (cdx, cdy) = (c.0 - d.0, c.1 - d.1);
+
+    (adx * adx + ady * ady) * (bdx * cdy - cdx * bdy)
+        - (bdx * bdx + bdy * bdy) * (adx * cdy - cdx * ady)
+        + (cdx * cdx + cdy * cdy) * (adx * bdy - bdx * ady)
+}
+
+impl Triangulation {
+    fn new(points: Vec<Point>) -> Self {
+        let n_points = points.len();
+        let mut triangulation = Triangulation {
+            points,
+            triangles: vec![Triangle {
+                vertices: [n_points, n_points + 1, n_points + 2],
+                adjacent: [None, None, None],
+                is_alive: true,
+            }],
+            last: 0,
+        };
+
+        for p in 0..n_points {
+            triangulation.insert(p);
+        }
+
+        triangulation
+    }
+
+    // the vertices after the points are the vertices of the super triangle
+    fn vertex(&self, v: usize) -> (Poly, Poly) {
+        match self.points.get(v) {
+            Some((x, y)) => (Poly::linear(*x, 0.0), Poly::linear(*y, 0.0)),
+            None => {
+                let (dx, dy) = SUPER_DIRECTIONS[v - self.points.len()];
+                (Poly::linear(0.0, dx), Poly::linear(0.0, dy))
+            }
+        }
+    }
+
+    /// the edges between the points, without the edges of the super triangle
+    fn edges(&self) -> Vec<(usize, usize)> {
+        let n_points = self.points.len();
+        let mut edges = vec![];
+
+        for triangle in self.triangles.iter().filter(|t| t.is_alive) {
+            for i in 0..3 {
+                let a = triangle.vertices[i];
+                let b = triangle.vertices[(i + 1) % 3];
+                if a < n_points && b < n_points {
+                    edges.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+    }
+
+    fn insert(&mut self, p: usize) {
+        let start = self.locate(p);
+
+        // the triangles, whose circumcircle contains the point, leave the hole around it
+        let mut hole = vec![start];
+        let mut stack = vec![start];
+        self.triangles[start].is_alive = false;
+        while let Some(t) = stack.pop() {
+            for i in 0..3 {
+                if let Some(next) = self.triangles[t].adjacent[i] {
+                    if self.triangles[next].is_alive && self.contains_in_circle(next, p) {
+                        self.triangles[next].is_alive = false;
+                        hole.push(next);
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+
+        // every edge on the border of the hole makes a new triangle with the point
+        let mut starts_at: HashMap<usize, usize> = HashMap::new();
+        let mut ends_at: HashMap<usize, usize> = HashMap::new();
+        for t in hole {
+            for i in 0..3 {
+                let outside = self.triangles[t].adjacent[i];
+                if outside.is_some_and(|o| !self.triangles[o].is_alive) {
+                    continue;
+                }
+
+                let a = self.triangles[t].vertices[(i + 1) % 3];
+                let b = self.triangles[t].vertices[(i + 2) % 3];
+                let new_triangle = self.triangles.len();
+                self.triangles.push(Triangle {
+                    vertices: [a, b, p],
+                    adjacent: [None, None, outside],
+                    is_alive: true,
+                });
+
+                if let Some(o) = outside {
+                    for adjacent in self.triangles[o].adjacent.iter_mut() {
+                        if *adjacent == Some(t) {
+                            *adjacent = Some(new_triangle);
+                        }
+                    }
+                }
+                starts_at.insert(a, new_triangle);
+                ends_at.insert(b, new_triangle);
+                self.last = new_triangle;
+            }
+        }
+
+        // the new triangles share the edges to the point with each other
+        for new_triangle in starts_at.values() {
+            let [a, b, _] = self.triangles[*new_triangle].vertices;
+            self.triangles[*new_triangle].adjacent[0] = starts_at.get(&b).copied();
+            self.triangles[*new_triangle].adjacent[1] = ends_at.get(&a).copied();
+        }
+    }
+
+    // walks from the last triangle towards the point, over the edges it is behind of
+    fn locate(&self, p: usize) -> usize {
+        let mut t = self.last;
+
+        for _ in 0..self.triangles.len() {
+            let triangle = &self.triangles[t];
+            let behind = (0..3).find(|i| self.is_behind_edge(triangle, *i, p));
+
+            match behind.and_then(|i| triangle.adjacent[i]) {
+                Some(next) => t = next,
+                None => return t,
+            }
+        }
+
+        // the rounding errors may send the walk into a loop
+        self.triangles
+            .iter()
+            .position(|triangle| {
+                triangle.is_alive && (0..3).all(|i| !self.is_behind_edge(triangle, i, p))
+            })
+            .unwrap_or(self.last)
+    }
+
+    // true when the point is on the right of the edge opposite to the vertex i
+    fn is_behind_edge(&self, triangle: &Triangle, i: usize, p: usize) -> bool {
+        let a = self.vertex(triangle.vertices[(i + 1) % 3]);
+        let b = self.vertex(triangle.vertices[(i + 2) % 3]);
+
+        orientation(a, b, self.vertex(p)).sign() < 0.0
+    }
+
+    fn contains_in_circle(&self, t: usize, p: usize) -> bool {
+        let [a, b, c] = self.triangles[t].vertices;
+
+        in_circle(
+            self.vertex(a),
+            self.vertex(b),
+            self.vertex(c),
+            self.vertex(p),
+        )
+        .sign()
+            > 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::{generator, kdtree};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // the edges of the triangles with the empty circumcircle, O(n^4)
+    fn brute_force_edges(cities: &[KDPoint]) -> Vec<(usize, usize)> {
+        let points: Vec<Point> = cities.iter().map(coords).collect();
+        let n = points.len();
+        let mut edges = vec![];
+
+        for a in 0..n {
+            for b in (a + 1)..n {
+                for c in (b + 1)..n {
+                    let (a2, b2) = match orientation(points[a], points[b], points[c]) {
+                        o if o > 0.0 => (a, b),
+                        o if o < 0.0 => (b, a),
+                        _ => continue,
+                    };
+                    let is_empty = (0..n)
+                        .all(|d| in_circle(points[a2], points[b2], points[c], points[d]) <= 0.0);
+
+                    if is_empty {
+                        edges.extend_from_slice(&[(a, b), (a, c), (b, c)]);
+                    }
+                }
+            }
+        }
+
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+    }
+
+    #[test]
+    fn test_edges_of_square_with_center() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 2.0],
+            vec![2.0, 2.0],
+            vec![2.0, 0.0],
+            vec![1.0, 1.0],
+        ]);
+
+        // the sides and the spokes to the center, the diagonals cross the center
+        assert_eq!(
+            vec![
+                (0, 1),
+                (0, 3),
+                (0, 4),
+                (1, 2),
+                (1, 4),
+                (2, 3),
+                (2, 4),
+                (3, 4)
+            ],
+            edges(&cities)
+        );
+    }
+
+    #[test]
+    fn test_edges_match_empty_circles() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let cities = generator::uniform(40, &generator::BoundingBox::square(100.0), &mut rng);
+
+        assert_eq!(brute_force_edges(&cities), edges(&cities));
+    }
+
+    #[test]
+    fn test_edges_of_degenerate_cities() {
+        let same_place = kdtree::build_points(&[vec![1.0, 1.0], vec![1.0, 1.0], vec![5.0, 1.0]]);
+        assert_eq!(vec![(0, 1), (0, 2)], edges(&same_place));
+
+        let on_line = kdtree::build_points(&[vec![0.0, 0.0], vec![2.0, 0.0], vec![1.0, 0.0]]);
+        assert_eq!(vec![(0, 2), (1, 2)], edges(&on_line));
+
+        assert!(edges(&kdtree::build_points(&[vec![1.0, 1.0]])).is_empty());
+        assert!(edges(&[]).is_empty());
+    }
+}
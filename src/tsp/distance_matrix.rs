@@ -52,13 +52,123 @@
 */
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use super::kdtree::KDPoint;
-use super::{CityTable, NearestResult};
+use super::{validate_cities, CityTable, Error, NearestResult};
+
+// the matrix of 10k cities takes 200 MB, the bigger problems compute the distances on demand
+pub const MAX_MATRIX_CITIES: usize = 10_000;
 
 // to have similar builder as kdtree
-pub fn from_cities(cities: &[KDPoint]) -> DistanceMatrix {
-    DistanceMatrix::from_cities(cities).unwrap()
+pub fn from_cities(cities: &[KDPoint]) -> Result<DistanceMatrix, Error> {
+    DistanceMatrix::from_cities(cities)
+}
+
+/// the distances of the cities: the precomputed matrix for the problems, where it fits into
+/// memory, otherwise the distances computed on every access
+pub fn distance_source(cities: &[KDPoint]) -> Result<Arc<dyn DistanceSource>, Error> {
+    if cities.len() <= MAX_MATRIX_CITIES {
+        Ok(Arc::new(DistanceMatrix::from_cities(cities)?))
+    } else {
+        Ok(Arc::new(LazyDistances::from_cities(cities)?))
+    }
+}
+
+/// the distances between the cities, by their ids or by their positions in the list of cities,
+/// so the solvers work with the precomputed matrix and with the distances computed on demand
+pub trait DistanceSource: Send + Sync {
+    fn n_cities(&self) -> usize;
+
+    fn distance_by_pos(&self, pos1: usize, pos2: usize) -> Result<f32, Error>;
+
+    fn distance_between(&self, city_id1: usize, city_id2: usize) -> Result<f32, Error>;
+
+    fn pos2city_id(&self, pos: &usize) -> Option<usize>;
+
+    fn tour_length(&self, path: &[usize]) -> Result<f32, Error> {
+        let tour_length = path.len();
+        if tour_length < 2 {
+            return Ok(0.0);
+        }
+
+        // the sum of the long tour drifts in f32
+        let mut total = self.distance_between(path[tour_length - 1], path[0])? as f64;
+
+        for i in 1..tour_length {
+            total += self.distance_between(path[i], path[i - 1])? as f64;
+        }
+
+        Ok(total as f32)
+    }
+
+    /// the length of the open tour, without the edge back to the first city
+    fn path_length(&self, path: &[usize]) -> Result<f32, Error> {
+        let mut total = 0.0;
+        for i in 1..path.len() {
+            total += self.distance_between(path[i], path[i - 1])? as f64;
+        }
+
+        Ok(total as f32)
+    }
+
+    /// the length the solvers minimize, without the closing edge if the tour is open
+    fn route_length(&self, path: &[usize], open_tour: bool) -> Result<f32, Error> {
+        if open_tour {
+            self.path_length(path)
+        } else {
+            self.tour_length(path)
+        }
+    }
+}
+
+/// computes the distance on every access, it keeps only the cities
+#[derive(Debug, Clone)]
+pub struct LazyDistances {
+    cities: Vec<KDPoint>,
+    city_idx: HashMap<usize, usize>, // translates city_id to the position
+}
+
+impl LazyDistances {
+    pub fn from_cities(cities: &[KDPoint]) -> Result<Self, Error> {
+        validate_cities(cities, 2)?;
+
+        Ok(LazyDistances {
+            cities: cities.to_vec(),
+            city_idx: cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect(),
+        })
+    }
+
+    fn city(&self, pos: usize) -> Result<&KDPoint, Error> {
+        self.cities.get(pos).ok_or_else(|| {
+            Error::InvalidInput(format!("position {} is not in the distance matrix", pos))
+        })
+    }
+}
+
+impl DistanceSource for LazyDistances {
+    fn n_cities(&self) -> usize {
+        self.cities.len()
+    }
+
+    fn distance_by_pos(&self, pos1: usize, pos2: usize) -> Result<f32, Error> {
+        Ok(self.city(pos1)?.distance(self.city(pos2)?))
+    }
+
+    fn distance_between(&self, city_id1: usize, city_id2: usize) -> Result<f32, Error> {
+        let pos = |city_id: usize| {
+            self.city_idx
+                .get(&city_id)
+                .copied()
+                .ok_or(Error::UnknownCity(city_id))
+        };
+
+        self.distance_by_pos(pos(city_id1)?, pos(city_id2)?)
+    }
+
+    fn pos2city_id(&self, pos: &usize) -> Option<usize> {
+        self.cities.get(*pos).map(|c| c.id)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -87,12 +197,12 @@ impl DistanceMatrix {
         }
     }
 
-    // assumes that cities are already sorted by id and ids are incrementally crowing
-    pub fn from_cities(cities: &[KDPoint]) -> Result<Self, &'static str> {
+    // the matrix is ordered by the positions of the cities, `city_idx` maps the ids to them,
+    // so the ids may have gaps, e.g after the city filters
+    pub fn from_cities(cities: &[KDPoint]) -> Result<Self, Error> {
+        validate_cities(cities, 2)?;
+
         let n = cities.len();
-        if n < 2 {
-            return Err("distance matrix requires at least 2 points");
-        }
 
         let size = n * (n - 1) / 2; // how many items on distance vec
 
@@ -124,7 +234,7 @@ impl DistanceMatrix {
 
     // It returns distance by raw vector ids for backward support for some solutions
     // preferred solution: distance_between as it checks if city exists on table
-    pub fn distance_by_pos(&self, pos1: usize, pos2: usize) -> Result<f32, &'static str> {
+    pub fn distance_by_pos(&self, pos1: usize, pos2: usize) -> Result<f32, Error> {
         if pos1 == pos2 {
             return Ok(0.0);
         }
@@ -133,11 +243,13 @@ impl DistanceMatrix {
         let to_city = std::cmp::min(pos1, pos2);
 
         let n_items_before = (from_city - 1) * from_city / 2;
-        if n_items_before > self.size {
-            return Err("city with biggest id is not in distance matrix");
-        }
-
         let distance_idx = n_items_before + to_city;
+        if distance_idx >= self.size {
+            return Err(Error::InvalidInput(format!(
+                "position {} is not in the distance matrix",
+                from_city
+            )));
+        }
 
         Ok(self.items[distance_idx])
     }
@@ -146,50 +258,30 @@ impl DistanceMatrix {
     /// array is packed version of bottom triangle with given structure
     /// ||d2,1|d3,1|d3,2|d4,1|d4,2|d4,3||
     /// here bigger cityId works like padding, then smaller id acts as index from padding
-    pub fn distance_between(&self, city_id1: usize, city_id2: usize) -> Result<f32, &'static str> {
+    pub fn distance_between(&self, city_id1: usize, city_id2: usize) -> Result<f32, Error> {
         if city_id1 == city_id2 {
             return Ok(0.0); // elements on the diagonal
         }
 
         // translate city ids to matrix id
-        let pos1 = self
-            .city_idx
-            .get(&city_id1)
-            .expect("city_id1 doesnt exists in index");
+        let pos1 = self.city_pos(city_id1)?;
+        let pos2 = self.city_pos(city_id2)?;
 
-        let pos2 = self
-            .city_idx
-            .get(&city_id2)
-            .expect("city_id2 doesnt exists in index");
-
-        self.distance_by_pos(*pos1, *pos2)
+        self.distance_by_pos(pos1, pos2)
     }
 
     /// returns list of distances from city N, where 0 distance from the city;
-    pub fn distances_from(&self, city_id: usize) -> Vec<f32> {
-        let pos: usize = self
-            .city_idx
-            .get(&city_id)
-            .expect("Unknown city id")
-            .clone();
+    pub fn distances_from(&self, city_id: usize) -> Result<Vec<f32>, Error> {
+        let pos = self.city_pos(city_id)?;
 
-        self.distances_from_index(pos)
+        Ok(self.distances_from_index(pos))
     }
 
-    pub fn tour_length(&self, path: &[usize]) -> f32 {
-        let tour_length = path.len();
-        if tour_length < 2 {
-            return 0.0;
-        }
-
-        let last_city_id = path.last().unwrap().clone();
-        let mut total = self.distance_between(last_city_id, path[0]).unwrap();
-
-        for i in 1..tour_length {
-            total += self.distance_between(path[i], path[i - 1]).unwrap();
-        }
-
-        total
+    fn city_pos(&self, city_id: usize) -> Result<usize, Error> {
+        self.city_idx
+            .get(&city_id)
+            .copied()
+            .ok_or(Error::UnknownCity(city_id))
     }
 
     pub fn city_index(&self) -> &HashMap<usize, usize> {
@@ -204,24 +296,19 @@ impl DistanceMatrix {
         self.city_idx.get(city_id).map(|i| i.clone())
     }
 
-    pub fn nearest(&self, target: &KDPoint, n: usize) -> NearestResult {
+    pub fn nearest(&self, target: &KDPoint, n: usize) -> Result<NearestResult, Error> {
         let mut search_result = NearestResult::new(target.clone(), f32::INFINITY, n);
 
-        if let Some(city_pos) = self.city_id2pos(&target.id) {
-            let distances_from_target = self.distances_from_index(city_pos);
-            for (pos, distance) in distances_from_target.iter().enumerate() {
-                let city_id = self.pos2city_id(&pos).unwrap();
-
-                // the NearestResult takes care of ordering the results
-                if let Some(pt) = self.cities.get(&city_id) {
-                    search_result.add(pt.clone(), distance.clone());
-                }
+        let city_pos = self.city_pos(target.id)?;
+        let distances_from_target = self.distances_from_index(city_pos);
+        for (pos, distance) in distances_from_target.iter().enumerate() {
+            // the NearestResult takes care of ordering the results
+            if let Some(pt) = self.cities.get(&pos) {
+                search_result.add(pt.clone(), *distance);
             }
-        } else {
-            panic!("DistanceMatrix.nearest with unknow_id");
         }
 
-        search_result
+        Ok(search_result)
     }
 
     fn distances_from_index(&self, pos: usize) -> Vec<f32> {
@@ -229,7 +316,7 @@ impl DistanceMatrix {
 
         // all the values from the city row aka with smalled_ids
         for i in 0..pos {
-            let d = self.distance_between(pos, i).unwrap_or(-1.0); //-1 would mean error
+            let d = self.distance_by_pos(pos, i).unwrap_or(-1.0); //-1 would mean error
             distances.push(d);
         }
 
@@ -237,7 +324,7 @@ impl DistanceMatrix {
         // it starts from city , which would return distance 0, which we need for place holder
         let n_cities = self.n;
         for i in pos..n_cities {
-            let d = self.distance_between(i, pos).unwrap_or(-1.0);
+            let d = self.distance_by_pos(i, pos).unwrap_or(-1.0);
             distances.push(d);
         }
 
@@ -245,12 +332,43 @@ impl DistanceMatrix {
     }
 }
 
+impl DistanceSource for DistanceMatrix {
+    fn n_cities(&self) -> usize {
+        self.n
+    }
+
+    fn distance_by_pos(&self, pos1: usize, pos2: usize) -> Result<f32, Error> {
+        DistanceMatrix::distance_by_pos(self, pos1, pos2)
+    }
+
+    fn distance_between(&self, city_id1: usize, city_id2: usize) -> Result<f32, Error> {
+        DistanceMatrix::distance_between(self, city_id1, city_id2)
+    }
+
+    fn pos2city_id(&self, pos: &usize) -> Option<usize> {
+        DistanceMatrix::pos2city_id(self, pos)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::test::helpers::assert_approx;
     use crate::tsp::kdtree;
 
+    #[test]
+    fn test_distance_matrix_is_shareable_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<DistanceMatrix>();
+
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![3.0, 4.0]]);
+        let dm = std::sync::Arc::new(from_cities(&cities).unwrap());
+        let shared = std::sync::Arc::clone(&dm);
+
+        let distance = std::thread::spawn(move || shared.distance_by_pos(0, 1).unwrap());
+        assert_eq!(5.0, distance.join().unwrap());
+    }
+
     #[test]
     fn test_build_distance_matrix_from_empty_list() {
         let cities = kdtree::build_points(&vec![]);
@@ -350,7 +468,7 @@ mod tests {
         ]);
         let dm = DistanceMatrix::from_cities(&cities).unwrap();
 
-        let res = dm.distances_from(0);
+        let res = dm.distances_from(0).unwrap();
         assert_eq!(4, res.len());
         assert_approx(0.0, res[0]);
         assert_approx(1.0, res[1]);
@@ -371,7 +489,7 @@ mod tests {
         let route = vec![0, 1, 2, 3, 4];
         let dm = DistanceMatrix::from_cities(&cities).unwrap();
 
-        assert_approx(4.0, dm.tour_length(&route));
+        assert_approx(4.0, dm.tour_length(&route).unwrap());
     }
 
     #[test]
@@ -384,21 +502,91 @@ mod tests {
             vec![1.0, 0.0],
         ]);
 
-        let dm = from_cities(&cities);
+        let dm = from_cities(&cities).unwrap();
 
-        let res = dm.nearest(&cities[0], 3);
+        let res = dm.nearest(&cities[0], 3).unwrap();
         assert_eq!(cities[1].id, res.point.id);
 
-        let res2 = dm.nearest(&cities[1], 3);
+        let res2 = dm.nearest(&cities[1], 3).unwrap();
         assert_eq!(cities[0].id, res2.point.id);
 
-        let res3 = dm.nearest(&cities[2], 3);
+        let res3 = dm.nearest(&cities[2], 3).unwrap();
         assert_eq!(cities[1].id, res3.point.id);
 
-        let res4 = dm.nearest(&cities[3], 3);
+        let res4 = dm.nearest(&cities[3], 3).unwrap();
         assert_eq!(cities[2].id, res4.point.id);
 
-        let res5 = dm.nearest(&cities[4], 2);
+        let res5 = dm.nearest(&cities[4], 2).unwrap();
         assert_eq!(cities[0].id, res5.point.id);
     }
+
+    #[test]
+    fn test_distance_matrix_with_unknown_city_returns_error() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 0.0]]);
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+
+        assert!(dm.distance_between(0, 5).is_err());
+        assert!(dm.tour_length(&[0, 5]).is_err());
+        assert!(dm
+            .nearest(&KDPoint::new_with_id(5, &[0.0, 0.0]), 1)
+            .is_err());
+    }
+
+    #[test]
+    fn test_nearest_with_ids_not_matching_positions() {
+        let cities = vec![
+            KDPoint::new_with_id(10, &[0.0, 0.0]),
+            KDPoint::new_with_id(20, &[5.0, 0.0]),
+            KDPoint::new_with_id(30, &[1.0, 0.0]),
+        ];
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+
+        let res = dm.nearest(&cities[0], 2).unwrap();
+        assert_eq!(30, res.point.id);
+        assert_eq!(2, res.nearest().len());
+    }
+
+    #[test]
+    fn test_lazy_distances_match_the_matrix() {
+        let cities = vec![
+            KDPoint::new_with_id(10, &[0.0, 0.0]),
+            KDPoint::new_with_id(20, &[3.0, 4.0]),
+            KDPoint::new_with_id(30, &[3.0, 0.0]),
+        ];
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+        let lazy = LazyDistances::from_cities(&cities).unwrap();
+
+        assert_eq!(3, lazy.n_cities());
+        assert_eq!(dm.n_cities(), lazy.n_cities());
+        assert_eq!(Some(30), lazy.pos2city_id(&2));
+        for pos1 in 0..3 {
+            for pos2 in 0..3 {
+                assert_eq!(
+                    dm.distance_by_pos(pos1, pos2).unwrap(),
+                    lazy.distance_by_pos(pos1, pos2).unwrap()
+                );
+            }
+        }
+        assert_approx(5.0, lazy.distance_between(10, 20).unwrap());
+        assert_approx(12.0, lazy.tour_length(&[10, 20, 30]).unwrap());
+
+        assert!(lazy.distance_between(10, 5).is_err());
+        assert!(lazy.distance_by_pos(0, 3).is_err());
+    }
+
+    #[test]
+    fn test_distance_source_of_big_problem_is_lazy() {
+        let rows: Vec<Vec<f64>> = (0..MAX_MATRIX_CITIES + 1)
+            .map(|i| vec![i as f64, 0.0])
+            .collect();
+        let cities = kdtree::build_points(&rows);
+
+        // the matrix would take 200 MB, the lazy source only keeps the cities
+        let source = distance_source(&cities).unwrap();
+        assert_eq!(MAX_MATRIX_CITIES + 1, source.n_cities());
+        assert_approx(
+            MAX_MATRIX_CITIES as f32,
+            source.distance_by_pos(0, MAX_MATRIX_CITIES).unwrap(),
+        );
+    }
 }
@@ -0,0 +1,102 @@
+/// Error type of the library
+///
+/// Public functions return it instead of panicking, so applications embedding teeline
+/// can reject malformed input without aborting the process.
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{format}: {message} on line.{line}")]
+    Parse {
+        format: &'static str, // what kind of file it was reading
+        line: usize,
+        message: String,
+    },
+
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("unknown city id {0}")]
+    UnknownCity(usize),
+
+    #[error("{solver}: {message}")]
+    External { solver: String, message: String },
+
+    #[error("plugin: {0}")]
+    Plugin(String),
+
+    #[error("{0}")]
+    Runtime(String),
+}
+
+/// why the route is not a tour of the cities, see `Solution::validate`
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ValidationError {
+    #[error("the route visits the unknown city {0}")]
+    UnknownCity(usize),
+
+    #[error("the route visits the city {0} twice")]
+    DuplicateCity(usize),
+
+    #[error("the route doesn't visit the city {0}")]
+    MissingCity(usize),
+}
+
+impl From<ValidationError> for Error {
+    fn from(err: ValidationError) -> Self {
+        match err {
+            ValidationError::UnknownCity(city_id) => Error::UnknownCity(city_id),
+            err => Error::InvalidInput(err.to_string()),
+        }
+    }
+}
+
+impl Error {
+    /// wraps io errors with the context, usage: `.map_err(Error::io("tsplib: failed to open file"))`
+    pub fn io(context: &str) -> impl FnOnce(io::Error) -> Error + '_ {
+        move |source| Error::Io {
+            context: context.to_string(),
+            source,
+        }
+    }
+
+    pub fn parse(format: &'static str, line: usize, message: &str) -> Error {
+        Error::Parse {
+            format,
+            line,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn external(solver: &str, message: String) -> Error {
+        Error::External {
+            solver: solver.to_string(),
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_messages() {
+        let err = Error::parse("tsplib", 3, "invalid number");
+        assert_eq!("tsplib: invalid number on line.3", err.to_string());
+
+        let err =
+            Error::io("tsplib: failed to open file")(io::Error::from(io::ErrorKind::NotFound));
+        assert!(err.to_string().starts_with("tsplib: failed to open file: "));
+
+        assert_eq!("unknown city id 7", Error::UnknownCity(7).to_string());
+    }
+}
@@ -0,0 +1,74 @@
+/// helpers for running external solvers, like Concorde and LKH
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::tsplib::TspLibData;
+use super::Error;
+
+/// executes the binary in the working directory and fails if it exits with an error;
+/// plain binary names are looked up from PATH, relative paths are resolved before the
+/// directory change
+pub fn run(name: &str, binary: &Path, args: &[PathBuf], work_dir: &Path) -> Result<(), Error> {
+    let binary = if binary.components().count() > 1 {
+        absolute_path(binary)
+    } else {
+        binary.to_path_buf()
+    };
+
+    let output = Command::new(&binary)
+        .current_dir(work_dir)
+        .args(args)
+        .output()
+        .map_err(|e| Error::external(name, format!("failed to execute {:?}: {}", binary, e)))?;
+
+    if !output.status.success() {
+        return Err(Error::external(
+            name,
+            format!(
+                "exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// returns the directory of the file, or the current directory
+pub fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// builds a file name from the name of the problem
+pub fn file_stem(data: &TspLibData) -> String {
+    name_stem(&data.name)
+}
+
+/// keeps only the letters, the digits, `_` and `-` of the name, so it can't point into
+/// another directory
+pub fn name_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
+        .collect();
+
+    if stem.is_empty() {
+        "teeline".to_string()
+    } else {
+        stem
+    }
+}
+
+pub fn absolute_path(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    std::env::current_dir()
+        .map(|dir| dir.join(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,211 @@
+/// Fixed edges
+///
+/// The edges the tour must keep, e.g the legs of the route which are decided already. They come
+/// from the `FIXED_EDGES_SECTION` of the TSPLIB file and are passed to the solvers in
+/// `SolverOptions::fixed_edges`:
+///
+/// ```text
+/// FIXED_EDGES_SECTION
+/// 1 7
+/// 7 3
+/// -1
+/// ```
+///
+/// The fixed edges make chains of cities, the nearest neighbor construction travels every chain
+/// from one end to the other and 2-opt never removes a fixed edge. The other solvers can't keep
+/// them, so they refuse the options with the fixed edges.
+use std::collections::{HashMap, HashSet};
+
+use super::kdtree::KDPoint;
+use super::{Error, SolverInfo, SolverOptions};
+
+/// the fixed edges of the cities, by the city ids
+#[derive(Debug, Clone, Default)]
+pub struct FixedEdges {
+    partners: HashMap<usize, Vec<usize>>,
+}
+
+impl FixedEdges {
+    /// checks that the edges connect the known cities and that a tour can keep all of them:
+    /// a city has at most 2 fixed edges and the edges close no cycle shorter than the tour
+    pub fn new(cities: &[KDPoint], edges: &[(usize, usize)]) -> Result<Self, Error> {
+        let invalid =
+            |message: String| Err(Error::InvalidInput(format!("fixed_edges: {}", message)));
+
+        let known_ids: HashSet<usize> = cities.iter().map(|c| c.id).collect();
+        let mut partners: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (a, b) in edges.iter().copied() {
+            if let Some(city_id) = [a, b].iter().find(|id| !known_ids.contains(id)) {
+                return invalid(format!(
+                    "the edge {}-{} has the unknown city {}",
+                    a, b, city_id
+                ));
+            }
+
+            if a == b {
+                return invalid(format!("the edge {}-{} connects the city to itself", a, b));
+            }
+
+            if partners.get(&a).is_some_and(|p| p.contains(&b)) {
+                continue;
+            }
+
+            for city_id in [a, b] {
+                if partners.get(&city_id).is_some_and(|p| p.len() == 2) {
+                    return invalid(format!("the city {} has more than 2 fixed edges", city_id));
+                }
+            }
+
+            partners.entry(a).or_default().push(b);
+            partners.entry(b).or_default().push(a);
+        }
+
+        let fixed = FixedEdges { partners };
+        if let Some(city_id) = fixed.short_cycle(cities.len()) {
+            return invalid(format!(
+                "the fixed edges of the city {} close a cycle shorter than the tour",
+                city_id
+            ));
+        }
+
+        Ok(fixed)
+    }
+
+    /// the fixed edges of the options
+    pub fn from_options(cities: &[KDPoint], options: &SolverOptions) -> Result<Self, Error> {
+        FixedEdges::new(cities, &options.fixed_edges)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.partners.is_empty()
+    }
+
+    pub fn is_fixed(&self, a: usize, b: usize) -> bool {
+        !self.partners.is_empty() && self.partners(a).contains(&b)
+    }
+
+    /// the cities the city has the fixed edges to
+    pub fn partners(&self, city_id: usize) -> &[usize] {
+        self.partners.get(&city_id).map_or(&[], |p| p.as_slice())
+    }
+
+    /// the city is inside the chain of the fixed edges, the tour can't reach it from elsewhere
+    pub fn is_inner(&self, city_id: usize) -> bool {
+        self.partners(city_id).len() == 2
+    }
+
+    /// the end of the chain the city is on, the city itself when the chain closes the whole tour
+    pub fn chain_end(&self, city_id: usize) -> usize {
+        let (mut previous, mut current) = (city_id, city_id);
+
+        while self.is_inner(current) {
+            let next = match self.partners(current).iter().find(|p| **p != previous) {
+                Some(next) => *next,
+                None => break,
+            };
+            if next == city_id {
+                return city_id;
+            }
+
+            previous = current;
+            current = next;
+        }
+
+        current
+    }
+
+    /// the first fixed edge the route doesn't keep, in the order of the route
+    pub fn broken_edge(&self, route: &[usize]) -> Option<(usize, usize)> {
+        let n = route.len();
+
+        route.iter().enumerate().find_map(|(i, a)| {
+            let (prev, next) = (route[(i + n - 1) % n], route[(i + 1) % n]);
+            self.partners(*a)
+                .iter()
+                .find(|b| **b != prev && **b != next)
+                .map(|b| (*a, *b))
+        })
+    }
+
+    // a city on the cycle of the fixed edges, which doesn't visit all the cities
+    fn short_cycle(&self, n_cities: usize) -> Option<usize> {
+        let mut visited: HashSet<usize> = HashSet::with_capacity(self.partners.len());
+
+        for city_id in self.partners.keys().copied() {
+            if !visited.insert(city_id) {
+                continue;
+            }
+
+            // the cities connected to the city, the chain is a cycle if none of them is its end
+            let (mut stack, mut n, mut is_cycle) = (vec![city_id], 0, true);
+            while let Some(current) = stack.pop() {
+                n += 1;
+                is_cycle &= self.is_inner(current);
+                for partner in self.partners(current).iter().copied() {
+                    if visited.insert(partner) {
+                        stack.push(partner);
+                    }
+                }
+            }
+
+            if is_cycle && n < n_cities {
+                return Some(city_id);
+            }
+        }
+
+        None
+    }
+}
+
+/// fails when the options have the fixed edges and the solver can't keep them
+pub fn check_solver(solver: &SolverInfo, options: &SolverOptions) -> Result<(), Error> {
+    if options.fixed_edges.is_empty() || solver.uses_option("fixed_edges") {
+        return Ok(());
+    }
+
+    Err(Error::InvalidInput(format!(
+        "{}: it can't keep the fixed edges, use nearest_neighbor or two_opt",
+        solver.name
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    fn cities() -> Vec<KDPoint> {
+        kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![2.0, 0.0],
+            vec![3.0, 0.0],
+        ])
+    }
+
+    #[test]
+    fn test_chain_of_fixed_edges() {
+        let fixed = FixedEdges::new(&cities(), &[(0, 1), (2, 1)]).unwrap();
+
+        assert!(fixed.is_fixed(1, 0));
+        assert!(fixed.is_inner(1));
+        assert!(!fixed.is_fixed(2, 3));
+        assert!([0, 2].contains(&fixed.chain_end(1)));
+        assert_eq!(3, fixed.chain_end(3));
+        assert_eq!(None, fixed.broken_edge(&[3, 0, 1, 2]));
+        assert!(fixed.broken_edge(&[0, 2, 1, 3]).is_some());
+    }
+
+    #[test]
+    fn test_new_rejects_edges_no_tour_keeps() {
+        let cities = cities();
+
+        assert!(FixedEdges::new(&cities, &[(0, 7)]).is_err());
+        assert!(FixedEdges::new(&cities, &[(2, 2)]).is_err());
+        assert!(FixedEdges::new(&cities, &[(0, 1), (0, 2), (0, 3)]).is_err());
+        assert!(FixedEdges::new(&cities, &[(0, 1), (1, 2), (2, 0)]).is_err());
+
+        let tour = FixedEdges::new(&cities, &[(0, 1), (1, 2), (2, 3), (3, 0)]).unwrap();
+        assert_eq!(0, tour.chain_end(0));
+    }
+}
@@ -0,0 +1,167 @@
+/// Random problem instances
+///
+/// The generators return cities with ids from 0, in the same way as `kdtree::build_points`,
+/// so they can replace the hand-written fixtures in tests and benches; the `generate` command
+/// writes them into TSPLIB files.
+use rand::Rng;
+
+use super::kdtree::{self, KDPoint};
+
+// side of the square the clustered cities are spread over
+pub const AREA_SIZE: f64 = 1_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl BoundingBox {
+    pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        BoundingBox {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    /// the square from the origin
+    pub fn square(size: f64) -> Self {
+        BoundingBox::new(0.0, 0.0, size, size)
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+}
+
+/// cities spread uniformly over the bounding box
+pub fn uniform<R: Rng + ?Sized>(n: usize, bbox: &BoundingBox, rng: &mut R) -> Vec<KDPoint> {
+    kdtree::build_points(&uniform_coords(n, bbox, rng))
+}
+
+/// cities normally distributed around `k` random centers in the `AREA_SIZE` square,
+/// `spread` is the standard deviation of the distance from the center
+pub fn clustered<R: Rng + ?Sized>(n: usize, k: usize, spread: f64, rng: &mut R) -> Vec<KDPoint> {
+    let centers = uniform_coords(k.max(1), &BoundingBox::square(AREA_SIZE), rng);
+
+    let rows: Vec<Vec<f64>> = (0..n)
+        .map(|i| {
+            let center = &centers[i % centers.len()];
+            // Box-Muller transform
+            let u1: f64 = rng.gen_range(f64::EPSILON, 1.0);
+            let u2: f64 = rng.gen();
+            let radius = spread * (-2.0 * u1.ln()).sqrt();
+            let angle = 2.0 * std::f64::consts::PI * u2;
+
+            vec![
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]
+        })
+        .collect();
+
+    kdtree::build_points(&rows)
+}
+
+/// cities on the grid with the unit spacing, row by row; every city is moved randomly
+/// by up to `jitter` along both axes, without the jitter the optimal tour is known
+pub fn grid<R: Rng + ?Sized>(rows: usize, cols: usize, jitter: f64, rng: &mut R) -> Vec<KDPoint> {
+    let coords: Vec<Vec<f64>> = (0..rows * cols)
+        .map(|i| {
+            let (dx, dy) = if jitter > 0.0 {
+                (
+                    rng.gen_range(-jitter, jitter),
+                    rng.gen_range(-jitter, jitter),
+                )
+            } else {
+                (0.0, 0.0)
+            };
+
+            vec![(i % cols) as f64 + dx, (i / cols) as f64 + dy]
+        })
+        .collect();
+
+    kdtree::build_points(&coords)
+}
+
+fn uniform_coords<R: Rng + ?Sized>(n: usize, bbox: &BoundingBox, rng: &mut R) -> Vec<Vec<f64>> {
+    (0..n)
+        .map(|_| {
+            vec![
+                rng.gen_range(bbox.min_x, bbox.max_x),
+                rng.gen_range(bbox.min_y, bbox.max_y),
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_uniform_stays_in_bounding_box() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let bbox = BoundingBox::new(-5.0, 10.0, 5.0, 20.0);
+
+        let cities = uniform(100, &bbox, &mut rng);
+
+        assert_eq!(100, cities.len());
+        assert_eq!(99, cities[99].id);
+        assert!(cities.iter().all(|c| bbox.contains(c.x(), c.y())));
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_cities() {
+        let cities1 = clustered(50, 3, 10.0, &mut StdRng::seed_from_u64(42));
+        let cities2 = clustered(50, 3, 10.0, &mut StdRng::seed_from_u64(42));
+
+        let coords1: Vec<&[f64]> = cities1.iter().map(|c| c.coords()).collect();
+        let coords2: Vec<&[f64]> = cities2.iter().map(|c| c.coords()).collect();
+        assert_eq!(coords1, coords2);
+    }
+
+    #[test]
+    fn test_clustered_cities_are_close_to_centers() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let cities = clustered(200, 1, 1.0, &mut rng);
+        let mean_x = cities.iter().map(|c| c.x()).sum::<f64>() / 200.0;
+        let mean_y = cities.iter().map(|c| c.y()).sum::<f64>() / 200.0;
+        let max_distance = cities
+            .iter()
+            .map(|c| ((c.x() - mean_x).powi(2) + (c.y() - mean_y).powi(2)).sqrt())
+            .fold(0.0, f64::max);
+
+        assert!(max_distance < 6.0, "max distance {}", max_distance);
+    }
+
+    #[test]
+    fn test_grid_without_jitter() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let cities = grid(2, 3, 0.0, &mut rng);
+
+        assert_eq!(6, cities.len());
+        assert_eq!(&[2.0, 0.0], cities[2].coords());
+        assert_eq!(&[0.0, 1.0], cities[3].coords());
+    }
+
+    #[test]
+    fn test_grid_jitter_is_bounded() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let cities = grid(5, 5, 0.25, &mut rng);
+
+        for (i, city) in cities.iter().enumerate() {
+            assert!((city.x() - (i % 5) as f64).abs() <= 0.25);
+            assert!((city.y() - (i / 5) as f64).abs() <= 0.25);
+        }
+    }
+}
@@ -1,76 +1,218 @@
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
 use std::cmp::Ordering;
 use std::collections::HashSet;
-use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
 
-use super::distance_matrix::DistanceMatrix;
+use super::distance_matrix::distance_source;
+use super::history::History;
 use super::kdtree::KDPoint;
-use super::progress::{send_progress, ProgressMessage};
-use super::route::{random_position_pair, Route};
-use super::{Solution, SolverOptions};
-
-type FitnessFn = Rc<dyn Fn(&[usize]) -> f32>;
-
-pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Solution {
-    let evaluator = build_evaluator(cities);
-
-    let population_size = cities.len();
-    let population = TspPopulation::from_cities(cities, population_size, &evaluator);
-    let best_candidate = solve_ga(&population, evaluator, options);
-
-    let best_route = Route::new(best_candidate.genotype());
-    send_progress(ProgressMessage::PathUpdate(
-        best_route,
-        best_candidate.fitness(),
-    ));
-    send_progress(ProgressMessage::Done);
-    Solution::new(best_candidate.genotype(), cities)
+use super::progress::{self, ProgressMessage, PublisherFn};
+use super::registry::Category;
+use super::route::{random_position_pair, reverse_segment, Route};
+use super::{validate_cities, Error, Solution, SolverInfo, SolverOptions, TspSolver};
+
+// it is shared by the threads, which evaluate the new individuals
+type FitnessFn = Arc<dyn Fn(&[usize]) -> f32 + Send + Sync>;
+
+/// the population and the islands of the genetic algorithm
+#[derive(Debug, Clone, PartialEq)]
+pub struct GaOptions {
+    pub mutation_probability: f32,
+    pub n_elite: usize, // the fittest tours kept for the next generation
+    pub population_size: usize,
+    pub n_offspring: Option<usize>, // children bred per generation, by default they refill the population
+    pub n_islands: usize,           // populations evolving on their own threads
+    pub migration_interval: usize, // epochs between the migrations of the elites to the next island
 }
 
-fn solve_ga(
-    population: &TspPopulation,
-    fitness_fn: FitnessFn,
-    options: &SolverOptions,
-) -> TspGenotype {
-    let population_size = population.len();
-    let mutation_prob = options.mutation_probability;
-    let elite_size = options.n_elite;
-
-    let mut epoch = 0;
-    let mut current_population = population.clone();
+impl GaOptions {
+    /// copies the options into the options the solver runs with
+    pub fn apply(&self, options: &mut SolverOptions) {
+        options.mutation_probability = self.mutation_probability;
+        options.n_elite = self.n_elite;
+        options.population_size = self.population_size;
+        options.n_offspring = self.n_offspring;
+        options.n_islands = self.n_islands;
+        options.migration_interval = self.migration_interval;
+    }
+}
 
-    while epoch < options.epochs {
-        let mut new_population = TspPopulation::with_capacity(population_size);
+impl Default for GaOptions {
+    fn default() -> Self {
+        GaOptions::from(&SolverOptions::default())
+    }
+}
 
-        // pass n-fittest directly into new population;
-        current_population.sort();
-        for elite in current_population.individuals().iter().take(elite_size) {
-            new_population.add(elite.clone());
+impl From<&SolverOptions> for GaOptions {
+    fn from(options: &SolverOptions) -> Self {
+        GaOptions {
+            mutation_probability: options.mutation_probability,
+            n_elite: options.n_elite,
+            population_size: options.population_size,
+            n_offspring: options.n_offspring,
+            n_islands: options.n_islands,
+            migration_interval: options.migration_interval,
         }
+    }
+}
 
-        for _ in elite_size..(population_size / 2) {
-            let parent1 = current_population.random_selection();
-            let parent2 = current_population.random_selection();
+pub struct GeneticAlgorithmSolver;
+
+impl TspSolver for GeneticAlgorithmSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "genetic_algorithm",
+            &["ga"],
+            "evolves a population of tours with crossover and mutation",
+            true,
+        )
+        .with_category(Category::Metaheuristic)
+        .with_complexity("O(epochs * population * n)")
+        .with_options(&[
+            "epochs",
+            "max_seconds",
+            "target_cost",
+            "mutation_probability",
+            "n_elite",
+            "population_size",
+            "n_offspring",
+            "n_islands",
+            "migration_interval",
+            "max_stale_epochs",
+            "seed",
+        ])
+    }
 
-            let (mut child1, mut child2) = ordered_crossover(&parent1, &parent2, &fitness_fn);
-            if probability(mutation_prob) {
-                child1.mutate()
-            };
-            if probability(mutation_prob) {
-                child2.mutate()
-            };
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 3)?;
+        validate_population(options)?;
+
+        let evaluator = build_evaluator(cities)?;
+        let mut rng = options.rng();
+
+        let population_size = options.population_size;
+        let initial_route = options.start_route(cities)?;
+        let mut history = History::new();
+        let best_candidate = if options.n_islands > 1 {
+            solve_islands(
+                &initial_route,
+                evaluator,
+                options,
+                &progress,
+                &mut history,
+                &mut rng,
+            )?
+        } else {
+            let population =
+                TspPopulation::from_route(&initial_route, population_size, &evaluator, &mut rng)?;
+            solve_ga(
+                &population,
+                evaluator,
+                options,
+                &progress,
+                &mut history,
+                &mut rng,
+            )?
+        };
 
-            new_population.add(child1);
-            new_population.add(child2);
-        }
+        let best_route = Route::new(best_candidate.genotype());
+        progress(ProgressMessage::PathUpdate(
+            best_route,
+            1.0 / best_candidate.fitness(),
+        ));
+        progress(ProgressMessage::Done);
+        Ok(Solution::new(best_candidate.genotype(), cities)
+            .with_target(options)
+            .with_history(history))
+    }
+}
+
+/// solves the problem and sends the progress into the global progress channel
+pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Result<Solution, Error> {
+    GeneticAlgorithmSolver.solve(cities, options, progress::default_publisher())
+}
+
+/// checks that the elites and the fittest children fill every generation
+fn validate_population(options: &SolverOptions) -> Result<(), Error> {
+    if options.population_size < 2 {
+        return Err(Error::InvalidInput(format!(
+            "genetic_algorithm: population_size must be at least 2, got {}",
+            options.population_size
+        )));
+    }
+
+    if options.n_elite >= options.population_size {
+        return Err(Error::InvalidInput(format!(
+            "genetic_algorithm: n_elite {} must be smaller than population_size {}",
+            options.n_elite, options.population_size
+        )));
+    }
+
+    if options.n_islands == 0 || options.migration_interval == 0 {
+        return Err(Error::InvalidInput(
+            "genetic_algorithm: n_islands and migration_interval must be at least 1".to_string(),
+        ));
+    }
+
+    // the islands evolve on their own threads, the browser can't start them
+    if cfg!(target_arch = "wasm32") && options.n_islands > 1 {
+        return Err(Error::InvalidInput(
+            "genetic_algorithm: n_islands must be 1 in WebAssembly, it has no threads".to_string(),
+        ));
+    }
 
-        current_population = new_population;
+    let n_survivors = options.population_size - options.n_elite;
+    match options.n_offspring {
+        Some(n_offspring) if n_offspring < n_survivors => Err(Error::InvalidInput(format!(
+            "genetic_algorithm: n_offspring {} can't refill the population, it needs at least {}",
+            n_offspring, n_survivors
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn solve_ga(
+    population: &TspPopulation,
+    fitness_fn: FitnessFn,
+    options: &SolverOptions,
+    progress: &PublisherFn,
+    history: &mut History,
+    rng: &mut StdRng,
+) -> Result<TspGenotype, Error> {
+    let deadline = options.deadline();
+    let mut epoch = 0;
+    let mut current_population = population.clone();
+    let mut best_fitness = current_population.best().fitness();
+    let mut n_stale = 0;
+    // fitness is the inverse of the tour length
+    history.record(epoch, 1.0 / best_fitness);
+
+    while epoch < options.epochs && !deadline.is_over() && !options.is_stale(n_stale) {
+        current_population = next_generation(&mut current_population, &fitness_fn, options, rng)?;
 
         let best_candidate = current_population.best().clone();
+        if best_candidate.fitness() > best_fitness {
+            best_fitness = best_candidate.fitness();
+            n_stale = 0;
+        } else {
+            n_stale += 1;
+        }
         let best_route = Route::new(best_candidate.genotype());
-        send_progress(ProgressMessage::PathUpdate(
+        // fitness is the inverse of the tour length
+        history.record(epoch, 1.0 / best_candidate.fitness());
+        progress(ProgressMessage::PathUpdate(
             best_route,
-            best_candidate.fitness(),
+            1.0 / best_candidate.fitness(),
         ));
 
         if options.verbose {
@@ -82,39 +224,264 @@ fn solve_ga(
         }
 
         epoch += 1;
+
+        progress::report_epoch(progress, epoch);
+
+        // fitness is the inverse of the tour length
+        if options.is_target_reached(1.0 / current_population.best().fitness()) {
+            break;
+        }
     }
 
     let best_candidate = current_population.best().clone();
 
-    best_candidate
+    Ok(best_candidate)
+}
+
+// what the islands tell to the thread, which publishes the progress of the solver
+enum IslandReport {
+    Improved(TspGenotype, usize), // the new best individual of the island and its epoch
+    Epoch(usize),
+}
+
+// the subpopulation of the island model, it sends its elites to the next island in the ring
+struct Island {
+    population: TspPopulation,
+    rng: StdRng,
+    migrants_out: Sender<Vec<TspGenotype>>,
+    migrants_in: Receiver<Vec<TspGenotype>>,
+}
+
+/// evolves `n_islands` populations on their own threads, every `migration_interval` epochs
+/// the elites of every island replace the weakest individuals of the next island;
+/// the islands wait for the migrants, so the seeded runs are reproducible
+fn solve_islands(
+    initial_route: &Route,
+    fitness_fn: FitnessFn,
+    options: &SolverOptions,
+    progress: &PublisherFn,
+    history: &mut History,
+    rng: &mut StdRng,
+) -> Result<TspGenotype, Error> {
+    let n_islands = options.n_islands;
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..n_islands).map(|_| mpsc::channel()).unzip();
+    let islands: Vec<Island> = receivers
+        .into_iter()
+        .enumerate()
+        .map(|(i, migrants_in)| {
+            let mut island_rng = StdRng::seed_from_u64(rng.gen());
+            let population = TspPopulation::from_route(
+                initial_route,
+                options.population_size,
+                &fitness_fn,
+                &mut island_rng,
+            )?;
+
+            Ok(Island {
+                population,
+                rng: island_rng,
+                migrants_out: senders[(i + 1) % n_islands].clone(),
+                migrants_in,
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+    drop(senders);
+
+    let stop = AtomicBool::new(false);
+    let (report_out, report_in) = mpsc::channel();
+    thread::scope(|scope| {
+        let handles: Vec<_> = islands
+            .into_iter()
+            .enumerate()
+            .map(|(i, island)| {
+                let (fitness_fn, report_out, stop) = (&fitness_fn, report_out.clone(), &stop);
+                scope.spawn(move || run_island(i, island, fitness_fn, options, report_out, stop))
+            })
+            .collect();
+        drop(report_out);
+
+        // the progress is published from this thread, e.g the restarts filter it per thread
+        let mut best_distance = f32::INFINITY;
+        for report in report_in {
+            match report {
+                IslandReport::Improved(candidate, epoch) => {
+                    // fitness is the inverse of the tour length
+                    let distance = 1.0 / candidate.fitness();
+                    if distance < best_distance {
+                        best_distance = distance;
+                        history.record(epoch, best_distance);
+                        progress(ProgressMessage::PathUpdate(
+                            Route::new(candidate.genotype()),
+                            distance,
+                        ));
+                    }
+                }
+                IslandReport::Epoch(epoch) => progress::report_epoch(progress, epoch),
+            }
+        }
+
+        let mut best: Option<TspGenotype> = None;
+        for handle in handles {
+            let candidate = handle
+                .join()
+                .map_err(|_| Error::Runtime("GA: island thread panicked".to_string()))??;
+            if best.as_ref().is_none_or(|b| candidate.fitness > b.fitness) {
+                best = Some(candidate);
+            }
+        }
+
+        best.ok_or_else(|| Error::Runtime("GA: no island to solve the problem".to_string()))
+    })
+}
+
+fn run_island(
+    id: usize,
+    mut island: Island,
+    fitness_fn: &FitnessFn,
+    options: &SolverOptions,
+    report: Sender<IslandReport>,
+    stop: &AtomicBool,
+) -> Result<TspGenotype, Error> {
+    let deadline = options.deadline();
+    let n_migrants = options.n_elite.clamp(1, options.population_size - 1);
+    let mut best = island.population.best().clone();
+    let mut epoch = 0;
+    let _ = report.send(IslandReport::Improved(best.clone(), epoch));
+
+    // every island stops on its own when it stagnates, the others may still improve
+    let mut n_stale = 0;
+    while epoch < options.epochs
+        && !deadline.is_over()
+        && !stop.load(AtomicOrdering::Relaxed)
+        && !options.is_stale(n_stale)
+    {
+        island.population =
+            next_generation(&mut island.population, fitness_fn, options, &mut island.rng)?;
+        epoch += 1;
+
+        if epoch.is_multiple_of(options.migration_interval) {
+            island.population.sort();
+            let migrants = island.population.individuals()[..n_migrants].to_vec();
+            // the next island may have finished already, then its migrants don't come either
+            let _ = island.migrants_out.send(migrants);
+            if let Ok(migrants) = island.migrants_in.recv() {
+                island.population.replace_weakest(migrants);
+            }
+        }
+
+        n_stale += 1;
+        if island.population.best().fitness() > best.fitness() {
+            best = island.population.best().clone();
+            n_stale = 0;
+            let _ = report.send(IslandReport::Improved(best.clone(), epoch));
+
+            if options.verbose {
+                println!(
+                    "GA: island.{} epoch.{:?} new best distance: {:?}",
+                    id,
+                    epoch,
+                    1.0 / best.fitness()
+                );
+            }
+        }
+        if id == 0 {
+            let _ = report.send(IslandReport::Epoch(epoch));
+        }
+
+        // fitness is the inverse of the tour length
+        if options.is_target_reached(1.0 / best.fitness()) {
+            stop.store(true, AtomicOrdering::Relaxed);
+        }
+    }
+
+    Ok(best)
 }
 
-fn build_evaluator(cities: &[KDPoint]) -> Rc<dyn Fn(&[usize]) -> f32> {
-    let dm = Rc::new(DistanceMatrix::from_cities(cities).unwrap());
+// the elites pass directly into the next generation, the rest of it are the fittest children
+fn next_generation(
+    current_population: &mut TspPopulation,
+    fitness_fn: &FitnessFn,
+    options: &SolverOptions,
+    rng: &mut StdRng,
+) -> Result<TspPopulation, Error> {
+    let population_size = current_population.len();
+    let mutation_prob = options.mutation_probability;
+    let elite_size = options.n_elite;
+    let n_survivors = population_size - elite_size;
+    let n_offspring = options.n_offspring.unwrap_or(n_survivors);
 
-    Rc::new(move |path: &[usize]| {
-        let tour_length = dm.tour_length(path);
+    let mut new_population = TspPopulation::with_capacity(population_size);
+
+    // pass n-fittest directly into new population;
+    current_population.sort();
+    for elite in current_population.individuals().iter().take(elite_size) {
+        new_population.add(elite.clone());
+    }
+
+    // the random generator breeds the children in order, so the seeded runs are reproducible
+    let mut children = Vec::with_capacity(n_offspring + 1);
+    while children.len() < n_offspring {
+        let parent1 = current_population.random_selection(rng);
+        let parent2 = current_population.random_selection(rng);
+
+        let (mut child1, mut child2) = ordered_crossover(parent1, parent2, rng)?;
+        if probability(mutation_prob, rng) {
+            child1.mutate(rng)?;
+        }
+        if probability(mutation_prob, rng) {
+            child2.mutate(rng)?;
+        }
+
+        children.push(child1);
+        children.push(child2);
+    }
+    children.truncate(n_offspring);
+
+    evaluate(&mut children, fitness_fn);
+    // only the fittest children survive, when there are more of them than free places
+    if children.len() > n_survivors {
+        sort_by_fitness(&mut children);
+        children.truncate(n_survivors);
+    }
+    for child in children {
+        new_population.add(child);
+    }
+
+    Ok(new_population)
+}
+
+fn build_evaluator(cities: &[KDPoint]) -> Result<FitnessFn, Error> {
+    let dm = distance_source(cities)?;
+
+    Ok(Arc::new(move |path: &[usize]| {
+        // genotypes are permutations of the city ids, so the lookup can not fail
+        let tour_length = dm.tour_length(path).unwrap_or(0.0);
 
         if tour_length == 0.0 {
             0.0
         } else {
             1.0 / tour_length
         }
-    })
+    }))
+}
+
+/// computes the fitness of the individuals on the threads of the rayon pool
+fn evaluate(individuals: &mut [TspGenotype], fitness_fn: &FitnessFn) {
+    individuals
+        .par_iter_mut()
+        .for_each(|individual| individual.set_fitness(fitness_fn(individual.genotype())));
 }
 
+// the children are not evaluated yet, their fitness is 0
 fn ordered_crossover(
     parent1: &TspGenotype,
     parent2: &TspGenotype,
-    fitness_fn: &FitnessFn,
-) -> (TspGenotype, TspGenotype) {
-    let (from, to) = random_position_pair(parent1.len());
+    rng: &mut StdRng,
+) -> Result<(TspGenotype, TspGenotype), Error> {
+    let (from, to) = random_position_pair(parent1.len(), rng)?;
     let (gene1, gene2) = ordered_crossover_genes(parent1.genotype(), parent2.genotype(), from, to);
 
-    let child1 = TspGenotype::new(fitness_fn(&gene1[..]), &gene1);
-    let child2 = TspGenotype::new(fitness_fn(&gene2[..]), &gene2);
-
-    (child1, child2)
+    Ok((TspGenotype::new(0.0, &gene1), TspGenotype::new(0.0, &gene2)))
 }
 
 fn ordered_crossover_genes(
@@ -163,9 +530,12 @@ fn ordered_crossover_genes(
 }
 
 // returns true with given probability
-fn probability(p: f32) -> bool {
-    let mut rng = rand::thread_rng();
+// the fittest individuals go first
+fn sort_by_fitness(individuals: &mut [TspGenotype]) {
+    individuals.sort_by(|x, y| y.fitness.partial_cmp(&x.fitness).unwrap_or(Ordering::Equal));
+}
 
+fn probability(p: f32, rng: &mut StdRng) -> bool {
     p > rng.gen()
 }
 
@@ -185,17 +555,26 @@ impl TspPopulation {
         }
     }
 
-    pub fn from_cities(cities: &[KDPoint], n: usize, fitness_fn: &FitnessFn) -> TspPopulation {
-        let mut population = TspPopulation::with_capacity(n);
-        let initial_route = Route::from_cities(cities);
+    /// the initial route and its random successors
+    pub fn from_route(
+        initial_route: &Route,
+        n: usize,
+        fitness_fn: &FitnessFn,
+        rng: &mut StdRng,
+    ) -> Result<TspPopulation, Error> {
+        let mut individuals = vec![TspGenotype::new(0.0, initial_route.route())];
+        for _ in 1..n {
+            let random_route = initial_route.random_successor(rng)?;
+            individuals.push(TspGenotype::new(0.0, random_route.route()));
+        }
 
-        for _ in 0..n {
-            let random_route = initial_route.random_successor();
-            let fitness = fitness_fn(random_route.route());
-            population.add(TspGenotype::new(fitness, random_route.route()));
+        evaluate(&mut individuals, fitness_fn);
+        let mut population = TspPopulation::with_capacity(n);
+        for individual in individuals {
+            population.add(individual);
         }
 
-        population
+        Ok(population)
     }
 
     pub fn add(&mut self, individual: TspGenotype) {
@@ -224,15 +603,23 @@ impl TspPopulation {
     }
 
     fn sort(&mut self) {
+        sort_by_fitness(&mut self.individuals);
+    }
+
+    // the migrants take the places of the weakest individuals
+    fn replace_weakest(&mut self, migrants: Vec<TspGenotype>) {
+        self.sort();
         self.individuals
-            .sort_by(|x, y| y.fitness.partial_cmp(&x.fitness).unwrap_or(Ordering::Equal));
+            .truncate(self.individuals.len().saturating_sub(migrants.len()));
+        for migrant in migrants {
+            self.add(migrant);
+        }
     }
 
     // TODO: test that entropy is good enough
     // roulette wheel selection
-    fn random_selection(&self) -> &TspGenotype {
+    fn random_selection(&self, rng: &mut StdRng) -> &TspGenotype {
         let total = self.total_fitness();
-        let mut rng = rand::thread_rng();
 
         let r = rng.gen_range(0.0, total);
         let mut up_to = 0.0;
@@ -281,20 +668,143 @@ impl TspGenotype {
     }
 
     // RSM from the reference paper
-    pub fn mutate(&mut self) {
-        let (mut from, mut to) = random_position_pair(self.genotype.len());
+    pub fn mutate(&mut self, rng: &mut StdRng) -> Result<(), Error> {
+        let (from, to) = random_position_pair(self.genotype.len(), rng)?;
 
-        while from < to {
-            self.genotype.swap(from, to);
-            from += 1;
-            to -= 1;
-        }
+        reverse_segment(&mut self.genotype, from, to);
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tsp::{generator, kdtree, total_distance};
+
+    #[test]
+    fn test_evaluate_computes_fitness_of_every_individual() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![3.0, 0.0], vec![3.0, 4.0]]);
+        let fitness_fn = build_evaluator(&cities).unwrap();
+        let mut individuals = vec![TspGenotype::new(0.0, &[0, 1, 2]); 64];
+
+        evaluate(&mut individuals, &fitness_fn);
+
+        for individual in individuals.iter() {
+            assert!((individual.fitness() - 1.0 / 12.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_seeded_runs_are_reproducible() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 3.0],
+            vec![2.0, 1.0],
+            vec![4.0, 4.0],
+            vec![5.0, 0.0],
+            vec![3.0, 2.0],
+        ]);
+        let mut options = SolverOptions::default();
+        options.epochs = 50;
+        options.seed = Some(7);
+
+        let run = || {
+            GeneticAlgorithmSolver
+                .solve(&cities, &options, progress::silent_publisher())
+                .unwrap()
+        };
+
+        assert_eq!(run().route(), run().route());
+    }
+
+    #[test]
+    fn test_validate_population() {
+        let mut options = SolverOptions::default();
+        assert!(validate_population(&options).is_ok());
+
+        options.population_size = 3;
+        let err = validate_population(&options).unwrap_err();
+        assert!(err.to_string().contains("n_elite 3 must be smaller"));
+
+        options.population_size = 10;
+        options.n_offspring = Some(6);
+        let err = validate_population(&options).unwrap_err();
+        assert!(err.to_string().contains("it needs at least 7"));
+
+        options.n_offspring = None;
+        options.n_islands = 0;
+        assert!(validate_population(&options).is_err());
+    }
+
+    #[test]
+    fn test_next_generation_keeps_population_size() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 3.0],
+            vec![2.0, 1.0],
+            vec![4.0, 4.0],
+            vec![5.0, 0.0],
+        ]);
+        let fitness_fn = build_evaluator(&cities).unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut options = SolverOptions::default();
+        let route = Route::from_cities(&cities);
+
+        let mut population = TspPopulation::from_route(&route, 20, &fitness_fn, &mut rng).unwrap();
+        for n_offspring in [None, Some(17), Some(40)] {
+            options.n_offspring = n_offspring;
+            let best = population.best().fitness();
+
+            population = next_generation(&mut population, &fitness_fn, &options, &mut rng).unwrap();
+            assert_eq!(20, population.len());
+            // the elites keep the best individual
+            assert!(population.best().fitness() >= best);
+        }
+    }
+
+    #[test]
+    fn test_seeded_island_runs_are_reproducible() {
+        let bbox = generator::BoundingBox::square(100.0);
+        let cities = generator::uniform(30, &bbox, &mut StdRng::seed_from_u64(5));
+        let start_distance = total_distance(&cities, Route::from_cities(&cities).route());
+
+        let mut options = SolverOptions::default();
+        options.epochs = 200;
+        options.population_size = 30;
+        options.n_islands = 4;
+        options.migration_interval = 10;
+        options.seed = Some(7);
+
+        let run = || {
+            GeneticAlgorithmSolver
+                .solve(&cities, &options, progress::silent_publisher())
+                .unwrap()
+        };
+
+        let tour = run();
+        assert_eq!(Ok(()), tour.validate());
+        assert!(tour.total < start_distance);
+        assert_eq!(tour.route(), run().route());
+    }
+
+    #[test]
+    fn test_replace_weakest_keeps_population_size() {
+        let mut population = TspPopulation::with_capacity(4);
+        for fitness in [0.4, 0.1, 0.3, 0.2] {
+            population.add(TspGenotype::new(fitness, &[0, 1, 2]));
+        }
+
+        population.replace_weakest(vec![TspGenotype::new(0.5, &[2, 1, 0])]);
+
+        assert_eq!(4, population.len());
+        let mut fitness: Vec<f32> = population
+            .individuals()
+            .iter()
+            .map(|x| x.fitness())
+            .collect();
+        fitness.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(vec![0.2, 0.3, 0.4, 0.5], fitness);
+    }
 
     #[test]
     fn test_ordered_crossover_genes_with_example_from_book() {
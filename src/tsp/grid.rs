@@ -0,0 +1,341 @@
+/// Uniform grid of the cities
+///
+/// The spatial index for the very large problems: the bounding box of the cities is split into
+/// square cells with ~2 cities each, so it's built with two passes over the cities in O(n),
+/// while the recursive KD-tree build becomes the bottleneck past ~1M cities.
+///
+/// The nearest search looks at the rings of the cells around the target, until the nearest
+/// city found is closer than the next ring; it takes O(1) expected time on the evenly spread
+/// cities. The visited cities are moved out of their cells, like the visited points of the
+/// KD-tree they are skipped by the searches.
+use std::fmt;
+use std::str::FromStr;
+
+use super::kdtree::KDPoint;
+
+// the expected number of cities in a cell
+const CITIES_PER_CELL: f64 = 2.0;
+
+/// which spatial index the construction heuristics search the nearest cities with
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SpatialIndex {
+    #[default]
+    KdTree,
+    Grid,
+}
+
+impl SpatialIndex {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["kdtree", "grid"]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpatialIndex::KdTree => "kdtree",
+            SpatialIndex::Grid => "grid",
+        }
+    }
+}
+
+impl FromStr for SpatialIndex {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "kdtree" => Ok(SpatialIndex::KdTree),
+            "grid" => Ok(SpatialIndex::Grid),
+            _ => Err("unknown spatial index"),
+        }
+    }
+}
+
+impl fmt::Display for SpatialIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// Grid keeps the cities by their positions in the `cities`
+#[derive(Debug, Clone)]
+pub struct Grid {
+    min_x: f64,
+    min_y: f64,
+    cell_size: f64,
+    n_columns: usize,
+    n_rows: usize,
+    points: Vec<(f64, f64)>,
+    cell_start: Vec<usize>, // the slots of the cell c are cell_start[c]..cell_start[c + 1]
+    n_in_cell: Vec<usize>,  // the unvisited cities are at the start of the slots of the cell
+    slots: Vec<usize>,      // the city positions ordered by their cells
+    slot_of: Vec<usize>,
+    cell_of: Vec<usize>,
+    n_unvisited: usize,
+}
+
+impl Grid {
+    pub fn new(cities: &[KDPoint]) -> Self {
+        let points: Vec<(f64, f64)> = cities
+            .iter()
+            .map(|c| (c.get(0).unwrap_or(0.0), c.get(1).unwrap_or(0.0)))
+            .collect();
+
+        let (min_x, max_x, min_y, max_y) = points.iter().fold(
+            (f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+            |(min_x, max_x, min_y, max_y), (x, y)| {
+                (min_x.min(*x), max_x.max(*x), min_y.min(*y), max_y.max(*y))
+            },
+        );
+        let (width, height) = ((max_x - min_x).max(0.0), (max_y - min_y).max(0.0));
+        let area = (width * height).max(width.max(height).powi(2) / points.len().max(1) as f64);
+        let cell_size = (area * CITIES_PER_CELL / points.len().max(1) as f64).sqrt();
+        let (n_columns, n_rows) = if cell_size > 0.0 {
+            (
+                (width / cell_size) as usize + 1,
+                (height / cell_size) as usize + 1,
+            )
+        } else {
+            (1, 1)
+        };
+
+        let mut grid = Grid {
+            min_x,
+            min_y,
+            cell_size,
+            n_columns,
+            n_rows,
+            points,
+            cell_start: vec![],
+            n_in_cell: vec![],
+            slots: vec![],
+            slot_of: vec![],
+            cell_of: vec![],
+            n_unvisited: 0,
+        };
+        grid.fill();
+
+        grid
+    }
+
+    // counting sort of the cities by their cells
+    fn fill(&mut self) {
+        let n_cells = self.n_columns * self.n_rows;
+        self.cell_of = self
+            .points
+            .iter()
+            .map(|(x, y)| {
+                let (column, row) = self.cell_coords(*x, *y);
+                row * self.n_columns + column
+            })
+            .collect();
+
+        let mut n_in_cell = vec![0; n_cells];
+        for cell in self.cell_of.iter() {
+            n_in_cell[*cell] += 1;
+        }
+
+        let mut cell_start = vec![0; n_cells + 1];
+        for cell in 0..n_cells {
+            cell_start[cell + 1] = cell_start[cell] + n_in_cell[cell];
+        }
+
+        let mut next_slot = cell_start.clone();
+        self.slots = vec![0; self.points.len()];
+        self.slot_of = vec![0; self.points.len()];
+        for (pos, cell) in self.cell_of.iter().enumerate() {
+            self.slots[next_slot[*cell]] = pos;
+            self.slot_of[pos] = next_slot[*cell];
+            next_slot[*cell] += 1;
+        }
+
+        self.cell_start = cell_start;
+        self.n_in_cell = n_in_cell;
+        self.n_unvisited = self.points.len();
+    }
+
+    fn cell_coords(&self, x: f64, y: f64) -> (usize, usize) {
+        if self.cell_size <= 0.0 {
+            return (0, 0);
+        }
+
+        let column = ((x - self.min_x) / self.cell_size).max(0.0) as usize;
+        let row = ((y - self.min_y) / self.cell_size).max(0.0) as usize;
+
+        (column.min(self.n_columns - 1), row.min(self.n_rows - 1))
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn n_unvisited(&self) -> usize {
+        self.n_unvisited
+    }
+
+    /// removes the city from the searches, returns false if it was already visited
+    pub fn mark_visited(&mut self, pos: usize) -> bool {
+        let cell = self.cell_of[pos];
+        let slot = self.slot_of[pos];
+        let last = self.cell_start[cell] + self.n_in_cell[cell];
+        if slot >= last {
+            return false;
+        }
+
+        // the city swaps its slot with the last unvisited city of the cell
+        let other = self.slots[last - 1];
+        self.slots.swap(slot, last - 1);
+        self.slot_of[other] = slot;
+        self.slot_of[pos] = last - 1;
+        self.n_in_cell[cell] -= 1;
+        self.n_unvisited -= 1;
+
+        true
+    }
+
+    pub fn reset_visited(&mut self) {
+        for cell in 0..self.n_in_cell.len() {
+            self.n_in_cell[cell] = self.cell_start[cell + 1] - self.cell_start[cell];
+        }
+        self.n_unvisited = self.points.len();
+    }
+
+    /// the position of the nearest unvisited city to the city at the position `pos`,
+    /// the city itself is not included
+    pub fn nearest(&self, pos: usize) -> Option<usize> {
+        let (x, y) = self.points[pos];
+        let (column, row) = self.cell_coords(x, y);
+        let max_ring = column
+            .max(self.n_columns - 1 - column)
+            .max(row)
+            .max(self.n_rows - 1 - row);
+
+        let mut best: Option<(f64, usize)> = None;
+        for ring in 0..=max_ring {
+            self.for_each_cell_in_ring(column, row, ring, |cell| {
+                let start = self.cell_start[cell];
+                for other in self.slots[start..start + self.n_in_cell[cell]].iter() {
+                    if *other == pos {
+                        continue;
+                    }
+
+                    let (ox, oy) = self.points[*other];
+                    let distance = (ox - x).powi(2) + (oy - y).powi(2);
+                    if best.is_none_or(|(best_distance, _)| distance < best_distance) {
+                        best = Some((distance, *other));
+                    }
+                }
+            });
+
+            // the cities of the next rings are at least `ring` cells away
+            let ring_distance = ring as f64 * self.cell_size;
+            if best.is_some_and(|(distance, _)| distance <= ring_distance * ring_distance) {
+                break;
+            }
+        }
+
+        best.map(|(_, other)| other)
+    }
+
+    fn for_each_cell_in_ring(
+        &self,
+        column: usize,
+        row: usize,
+        ring: usize,
+        mut callback: impl FnMut(usize),
+    ) {
+        let (column, row, ring) = (column as isize, row as isize, ring as isize);
+        let mut visit = |c: isize, r: isize| {
+            if c >= 0 && r >= 0 && (c as usize) < self.n_columns && (r as usize) < self.n_rows {
+                callback(r as usize * self.n_columns + c as usize);
+            }
+        };
+
+        if ring == 0 {
+            visit(column, row);
+            return;
+        }
+
+        for c in (column - ring)..=(column + ring) {
+            visit(c, row - ring);
+            visit(c, row + ring);
+        }
+        for r in (row - ring + 1)..=(row + ring - 1) {
+            visit(column - ring, r);
+            visit(column + ring, r);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::{generator, kdtree};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn brute_force_nearest(cities: &[KDPoint], pos: usize, is_visited: &[bool]) -> Option<f32> {
+        (0..cities.len())
+            .filter(|other| *other != pos && !is_visited[*other])
+            .map(|other| cities[pos].distance(&cities[other]))
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+    }
+
+    #[test]
+    fn test_nearest_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let cities = generator::uniform(300, &generator::BoundingBox::square(100.0), &mut rng);
+        let mut grid = Grid::new(&cities);
+        let mut is_visited = vec![false; cities.len()];
+
+        for _ in 0..3 {
+            for pos in 0..cities.len() {
+                let found = grid
+                    .nearest(pos)
+                    .map(|other| cities[pos].distance(&cities[other]));
+                assert_eq!(brute_force_nearest(&cities, pos, &is_visited), found);
+            }
+
+            // a third of the cities gets visited before the next round
+            for (pos, visited) in is_visited.iter_mut().enumerate() {
+                if !*visited && rng.gen_bool(0.33) {
+                    assert!(grid.mark_visited(pos));
+                    assert!(!grid.mark_visited(pos));
+                    *visited = true;
+                }
+            }
+        }
+        assert_eq!(
+            is_visited.iter().filter(|visited| !**visited).count(),
+            grid.n_unvisited()
+        );
+
+        grid.reset_visited();
+        assert_eq!(cities.len(), grid.n_unvisited());
+    }
+
+    #[test]
+    fn test_nearest_of_degenerate_cities() {
+        let on_line = kdtree::build_points(&[
+            vec![0.0, 5.0],
+            vec![3.0, 5.0],
+            vec![10.0, 5.0],
+            vec![4.0, 5.0],
+        ]);
+        let mut grid = Grid::new(&on_line);
+        assert_eq!(Some(3), grid.nearest(1));
+        grid.mark_visited(3);
+        assert_eq!(Some(0), grid.nearest(1));
+
+        let same_place = kdtree::build_points(&[vec![1.0, 1.0], vec![1.0, 1.0]]);
+        let mut grid = Grid::new(&same_place);
+        assert_eq!(Some(1), grid.nearest(0));
+        grid.mark_visited(1);
+        assert_eq!(None, grid.nearest(0));
+
+        let single = kdtree::build_points(&[vec![1.0, 1.0]]);
+        assert_eq!(None, Grid::new(&single).nearest(0));
+    }
+}
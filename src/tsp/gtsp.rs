@@ -0,0 +1,340 @@
+/// Generalized TSP
+///
+/// The cities of the generalized TSP are grouped into the clusters and the tour visits exactly
+/// one city of every cluster, e.g one of the entrances of every building. The TSPLIB files of the
+/// problems, in the format of GTSPLIB, list the clusters in the `GTSP_SET_SECTION`, every line
+/// has the id of the cluster, its city ids and `-1`:
+///
+/// ```text
+/// GTSP_SET_SECTION:
+/// 1 19 40 41 -1
+/// 2 3 5 7 -1
+/// ```
+///
+/// The tour is built by the nearest neighbor over the clusters: it moves to the nearest city of
+/// the cluster it has not visited yet. Then the local search improves it with 2-opt, swaps the
+/// city of the cluster with another city of the same cluster, and moves the cluster to another
+/// place of the tour with its best city there, until no move shortens the tour.
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::kdtree::KDPoint;
+use super::tsplib::TspLibData;
+use super::{validate_cities, Error, Solution, SolverOptions};
+
+// the smallest gain of the move, the rounding errors must not undo the earlier moves
+const MIN_GAIN: f32 = 1e-5;
+
+/// the cities grouped into the clusters, every city is in exactly one cluster
+#[derive(Debug, Clone)]
+pub struct GtspProblem {
+    cities: Vec<KDPoint>,
+    clusters: Vec<Vec<usize>>, // the positions of the cities
+    cluster_of: Vec<usize>,    // by the positions of the cities
+}
+
+impl GtspProblem {
+    /// checks that the clusters split the cities: every city is in exactly one of them
+    pub fn new(cities: &[KDPoint], clusters: &[Vec<usize>]) -> Result<Self, Error> {
+        validate_cities(cities, 1)?;
+        let invalid = |message: String| Err(Error::InvalidInput(format!("gtsp: {}", message)));
+
+        if clusters.len() < 2 {
+            return invalid(format!(
+                "it requires at least 2 clusters, got {}",
+                clusters.len()
+            ));
+        }
+
+        let pos_of: HashMap<usize, usize> =
+            cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+        let mut cluster_of = vec![usize::MAX; cities.len()];
+        let mut cluster_positions = Vec::with_capacity(clusters.len());
+        for (cluster, city_ids) in clusters.iter().enumerate() {
+            if city_ids.is_empty() {
+                return invalid(format!("the cluster {} has no cities", cluster + 1));
+            }
+
+            let mut positions = Vec::with_capacity(city_ids.len());
+            for city_id in city_ids.iter() {
+                let pos = match pos_of.get(city_id) {
+                    Some(pos) => *pos,
+                    None => {
+                        return invalid(format!(
+                            "the cluster {} has the unknown city {}",
+                            cluster + 1,
+                            city_id
+                        ))
+                    }
+                };
+                if cluster_of[pos] != usize::MAX {
+                    return invalid(format!("the city {} is in 2 clusters", city_id));
+                }
+
+                cluster_of[pos] = cluster;
+                positions.push(pos);
+            }
+            cluster_positions.push(positions);
+        }
+
+        if let Some(pos) = cluster_of.iter().position(|c| *c == usize::MAX) {
+            return invalid(format!("the city {} is in no cluster", cities[pos].id));
+        }
+
+        Ok(GtspProblem {
+            cities: cities.to_vec(),
+            clusters: cluster_positions,
+            cluster_of,
+        })
+    }
+
+    /// the problem of the TSPLIB file with the GTSP_SET_SECTION
+    pub fn from_tsplib(data: &TspLibData) -> Result<Self, Error> {
+        if data.clusters().is_empty() {
+            return Err(Error::InvalidInput(format!(
+                "gtsp: {} has no GTSP_SET_SECTION",
+                data.name
+            )));
+        }
+
+        GtspProblem::new(data.cities(), data.clusters())
+    }
+
+    pub fn n_clusters(&self) -> usize {
+        self.clusters.len()
+    }
+
+    fn distance(&self, a: usize, b: usize) -> f32 {
+        self.cities[a].distance(&self.cities[b])
+    }
+
+    fn tour_length(&self, tour: &[usize]) -> f32 {
+        let n = tour.len();
+        (0..n)
+            .map(|i| self.distance(tour[i], tour[(i + 1) % n]))
+            .sum()
+    }
+}
+
+/// builds the tour by the nearest neighbor over the clusters and improves it with the local
+/// search, it stops early after `max_seconds`; the route of the solution has a city of every
+/// cluster
+pub fn solve(problem: &GtspProblem, options: &SolverOptions) -> Result<Solution, Error> {
+    let deadline = options.deadline();
+
+    let mut tour = nearest_cluster_tour(problem);
+    if options.verbose {
+        println!(
+            "GTSP: the nearest neighbor tour is {:.2}",
+            problem.tour_length(&tour)
+        );
+    }
+
+    let mut n_passes = 0;
+    while !deadline.is_over() && improve_tour(problem, &mut tour) {
+        n_passes += 1;
+
+        if options.verbose {
+            println!(
+                "GTSP: pass {} shortened the tour to {:.2}",
+                n_passes,
+                problem.tour_length(&tour)
+            );
+        }
+    }
+
+    let route: Vec<usize> = tour.iter().map(|pos| problem.cities[*pos].id).collect();
+    Ok(Solution::new(&route, &problem.cities).with_target(options))
+}
+
+// the tour by the positions of the cities: it starts from the first city of the first cluster
+// and moves to the nearest city of the cluster, which is not visited yet
+fn nearest_cluster_tour(problem: &GtspProblem) -> Vec<usize> {
+    let mut is_visited = vec![false; problem.n_clusters()];
+    let mut tour = Vec::with_capacity(problem.n_clusters());
+
+    let mut current = problem.clusters[0][0];
+    loop {
+        tour.push(current);
+        is_visited[problem.cluster_of[current]] = true;
+
+        let next = (0..problem.cities.len())
+            .filter(|pos| !is_visited[problem.cluster_of[*pos]])
+            .min_by(|a, b| {
+                let (da, db) = (problem.distance(current, *a), problem.distance(current, *b));
+                da.partial_cmp(&db).unwrap_or(Ordering::Equal)
+            });
+
+        match next {
+            Some(next) => current = next,
+            None => break,
+        }
+    }
+
+    tour
+}
+
+// one pass of the moves, true if any of them shortened the tour
+fn improve_tour(problem: &GtspProblem, tour: &mut Vec<usize>) -> bool {
+    let mut improved = two_opt_tour(problem, tour);
+    improved |= swap_within_clusters(problem, tour);
+    improved |= move_clusters(problem, tour);
+
+    improved
+}
+
+// reverses the segments of the tour while it shortens it
+fn two_opt_tour(problem: &GtspProblem, tour: &mut [usize]) -> bool {
+    let n = tour.len();
+    let mut improved = false;
+    let mut found = n > 3;
+
+    while found {
+        found = false;
+        // the segment i + 1..=j is reversed, the edges (i, i + 1) and (j, j + 1) are replaced
+        for i in 0..(n - 2) {
+            for j in (i + 2)..n {
+                let (a, b, c, d) = (tour[i], tour[i + 1], tour[j], tour[(j + 1) % n]);
+                if d == a {
+                    continue;
+                }
+
+                let delta = problem.distance(a, c) + problem.distance(b, d)
+                    - problem.distance(a, b)
+                    - problem.distance(c, d);
+                if delta < -MIN_GAIN {
+                    tour[(i + 1)..=j].reverse();
+                    found = true;
+                    improved = true;
+                }
+            }
+        }
+    }
+
+    improved
+}
+
+// replaces the city of the tour with the city of the same cluster, which is the closest
+// to its neighbors
+fn swap_within_clusters(problem: &GtspProblem, tour: &mut [usize]) -> bool {
+    let n = tour.len();
+    let mut improved = false;
+
+    for i in 0..n {
+        let (p, s) = (tour[(i + n - 1) % n], tour[(i + 1) % n]);
+        let cost = |city: usize| problem.distance(p, city) + problem.distance(city, s);
+
+        let current_cost = cost(tour[i]);
+        let best = problem.clusters[problem.cluster_of[tour[i]]]
+            .iter()
+            .copied()
+            .map(|city| (cost(city), city))
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        if let Some((best_cost, city)) = best {
+            if best_cost - current_cost < -MIN_GAIN {
+                tour[i] = city;
+                improved = true;
+            }
+        }
+    }
+
+    improved
+}
+
+// removes the cluster from the tour and inserts it at the cheapest place with the best city
+// of the cluster there
+fn move_clusters(problem: &GtspProblem, tour: &mut Vec<usize>) -> bool {
+    let mut improved = false;
+    if tour.len() < 3 {
+        return improved;
+    }
+
+    let mut i = 0;
+    while i < tour.len() {
+        let n = tour.len();
+        let (p, city, s) = (tour[(i + n - 1) % n], tour[i], tour[(i + 1) % n]);
+        let saving = problem.distance(p, city) + problem.distance(city, s) - problem.distance(p, s);
+
+        let mut rest = tour.clone();
+        rest.remove(i);
+        let m = rest.len();
+        // the city goes between the positions k and k + 1 of the tour without it
+        let best = (0..m)
+            .flat_map(|k| {
+                problem.clusters[problem.cluster_of[city]]
+                    .iter()
+                    .map(move |other| (k, *other))
+            })
+            .map(|(k, other)| {
+                let (a, b) = (rest[k], rest[(k + 1) % m]);
+                let cost = problem.distance(a, other) + problem.distance(other, b)
+                    - problem.distance(a, b);
+                (cost, k, other)
+            })
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        match best {
+            Some((cost, k, other)) if cost - saving < -MIN_GAIN => {
+                rest.insert(k + 1, other);
+                *tour = rest;
+                improved = true;
+            }
+            _ => i += 1,
+        }
+    }
+
+    improved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    // the clusters of 2 cities, one of them is close to the others and the other one is far
+    fn problem() -> GtspProblem {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![10.0, 10.0],
+            vec![1.0, 0.0],
+            vec![-10.0, 10.0],
+            vec![1.0, 1.0],
+            vec![10.0, -10.0],
+            vec![0.0, 1.0],
+            vec![-10.0, -10.0],
+        ]);
+
+        GtspProblem::new(&cities, &[vec![0, 7], vec![1, 2], vec![3, 4], vec![5, 6]]).unwrap()
+    }
+
+    #[test]
+    fn test_solve_visits_one_city_of_every_cluster() {
+        let solution = solve(&problem(), &SolverOptions::default()).unwrap();
+
+        let mut route = solution.route().to_vec();
+        route.sort();
+        assert_eq!(vec![0, 2, 4, 6], route);
+        assert!((solution.total - 4.0).abs() < 0.001, "{}", solution.total);
+    }
+
+    #[test]
+    fn test_swap_within_clusters_picks_the_closest_city() {
+        let problem = problem();
+        let mut tour = vec![7, 2, 4, 6];
+
+        assert!(swap_within_clusters(&problem, &mut tour));
+        assert_eq!(vec![0, 2, 4, 6], tour);
+    }
+
+    #[test]
+    fn test_new_rejects_clusters_which_dont_split_the_cities() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 0.0], vec![2.0, 0.0]]);
+
+        assert!(GtspProblem::new(&cities, &[vec![0, 1], vec![2]]).is_ok());
+        assert!(GtspProblem::new(&cities, &[vec![0, 1, 2]]).is_err());
+        assert!(GtspProblem::new(&cities, &[vec![0, 1], vec![1, 2]]).is_err());
+        assert!(GtspProblem::new(&cities, &[vec![0], vec![1]]).is_err());
+        assert!(GtspProblem::new(&cities, &[vec![0, 1], vec![2, 7]]).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+/// Space-filling curve construction
+///
+/// It orders cities by their position on the Hilbert curve, that goes through every cell
+/// of the grid laid over the bounding box of the cities. Cities close on the curve are also
+/// close on the plane, so the order is a decent tour, which is usually 25-40% longer than
+/// the optimal one.
+///
+/// It needs only sorting, O(n log n), and no distance matrix, which makes it practical for
+/// problems with 100k+ cities; for 3D points only the first 2 coordinates are used.
+///
+/// source: Bartholdi & Platzman, "Heuristics based on spacefilling curves for combinatorial
+/// problems in Euclidean space", https://www2.isye.gatech.edu/~jjb/research/mow/mow.pdf
+use super::history::History;
+use super::kdtree::KDPoint;
+use super::progress::{self, ProgressMessage, PublisherFn};
+use super::registry::Category;
+use super::route::Route;
+use super::{
+    total_distance, validate_cities, Error, Solution, SolverInfo, SolverOptions, TspSolver,
+};
+
+// the grid has 2^ORDER cells on each side
+const ORDER: u32 = 16;
+
+pub struct HilbertSolver;
+
+impl TspSolver for HilbertSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "hilbert",
+            &[],
+            "visits cities in the order of the Hilbert space-filling curve",
+            false,
+        )
+        .with_category(Category::Constructive)
+        .with_complexity("O(n log n)")
+    }
+
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 1)?;
+
+        let mut history = History::new();
+        let path = curve_order(cities);
+
+        if options.verbose {
+            println!("Hilbert: ordered {} cities along the curve", path.len());
+        }
+
+        progress(ProgressMessage::PathUpdate(
+            Route::new(&path),
+            total_distance(cities, &path),
+        ));
+        progress(ProgressMessage::Done);
+
+        let tour = Solution::new(&path, cities).with_target(options);
+        history.record(0, tour.total);
+        Ok(tour.with_history(history))
+    }
+}
+
+/// solves the problem and sends the progress into the global progress channel
+pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Result<Solution, Error> {
+    HilbertSolver.solve(cities, options, progress::default_publisher())
+}
+
+/// returns city ids sorted by their index on the Hilbert curve
+pub fn curve_order(cities: &[KDPoint]) -> Vec<usize> {
+    let (min_x, min_y, span) = bounding_square(cities);
+    let max_cell = ((1u64 << ORDER) - 1) as f64;
+
+    let mut indexed: Vec<(u64, usize)> = cities
+        .iter()
+        .map(|c| {
+            let x = to_cell(c.get(0).unwrap_or(0.0), min_x, span, max_cell);
+            let y = to_cell(c.get(1).unwrap_or(0.0), min_y, span, max_cell);
+
+            (curve_index(ORDER, x, y), c.id)
+        })
+        .collect();
+
+    indexed.sort_unstable();
+
+    indexed.into_iter().map(|(_, id)| id).collect()
+}
+
+// cities are scaled by the same factor on both axis, so the shape stays same
+fn bounding_square(cities: &[KDPoint]) -> (f64, f64, f64) {
+    let mut min = [f64::MAX, f64::MAX];
+    let mut max = [f64::MIN, f64::MIN];
+
+    for city in cities.iter() {
+        for (dim, (lo, hi)) in min.iter_mut().zip(max.iter_mut()).enumerate() {
+            let val = city.get(dim).unwrap_or(0.0);
+            *lo = lo.min(val);
+            *hi = hi.max(val);
+        }
+    }
+
+    let span = (max[0] - min[0]).max(max[1] - min[1]);
+
+    (min[0], min[1], span)
+}
+
+fn to_cell(val: f64, min: f64, span: f64, max_cell: f64) -> u64 {
+    if span <= 0.0 {
+        return 0;
+    }
+
+    ((val - min) / span * max_cell).round() as u64
+}
+
+/// maps the cell (x, y) of the 2^order x 2^order grid to its distance along the curve
+fn curve_index(order: u32, x: u64, y: u64) -> u64 {
+    let mut x = x;
+    let mut y = y;
+    let mut d = 0;
+
+    let mut s = 1u64 << (order - 1);
+    while s > 0 {
+        let rx = u64::from(x & s > 0);
+        let ry = u64::from(y & s > 0);
+        d += s * s * ((3 * rx) ^ ry);
+
+        // rotate the quadrant, so the curve continues from the right corner
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        s /= 2;
+    }
+
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    #[test]
+    fn test_curve_index_with_first_order_curve() {
+        assert_eq!(0, curve_index(1, 0, 0));
+        assert_eq!(1, curve_index(1, 0, 1));
+        assert_eq!(2, curve_index(1, 1, 1));
+        assert_eq!(3, curve_index(1, 1, 0));
+    }
+
+    #[test]
+    fn test_curve_index_visits_every_cell_once() {
+        let order = 3;
+        let side = 1u64 << order;
+
+        let mut indices: Vec<u64> = (0..side)
+            .flat_map(|x| (0..side).map(move |y| curve_index(order, x, y)))
+            .collect();
+        indices.sort_unstable();
+
+        let expected: Vec<u64> = (0..side * side).collect();
+        assert_eq!(expected, indices);
+    }
+
+    #[test]
+    fn test_solve_with_square() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+
+        let tour = solve(&cities, &SolverOptions::default()).unwrap();
+        assert_eq!(&[0, 2, 1, 3], tour.route());
+        assert_eq!(4.0, tour.total);
+    }
+
+    #[test]
+    fn test_curve_order_with_same_coordinates() {
+        let cities = kdtree::build_points(&[vec![2.0, 2.0], vec![2.0, 2.0]]);
+
+        assert_eq!(vec![0, 1], curve_order(&cities));
+    }
+}
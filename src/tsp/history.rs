@@ -0,0 +1,170 @@
+/// Convergence history of the solver
+///
+/// The solvers record every new best tour with the epoch it was found on and the time since
+/// the start of the search, so the convergence of the solvers can be compared. The history is
+/// written as CSV:
+///
+/// ```text
+/// epoch,best_cost,elapsed_s
+/// 0,9876.5,0.000120
+/// 12,9611.25,0.001043
+/// ```
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use super::clock::Instant;
+use super::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryPoint {
+    pub epoch: usize,
+    pub best_cost: f32,
+    pub elapsed: Duration, // since the start of the search
+}
+
+/// the new best costs of the search, in the order they were found
+#[derive(Debug, Clone)]
+pub struct History {
+    started_at: Instant,
+    points: Vec<HistoryPoint>,
+}
+
+impl History {
+    /// starts the clock of the history
+    pub fn new() -> Self {
+        History {
+            started_at: Instant::now(),
+            points: vec![],
+        }
+    }
+
+    /// records the cost when it is better than the best one so far
+    pub fn record(&mut self, epoch: usize, cost: f32) {
+        if self.best_cost().is_some_and(|best| cost >= best) {
+            return;
+        }
+
+        self.points.push(HistoryPoint {
+            epoch,
+            best_cost: cost,
+            elapsed: self.started_at.elapsed(),
+        });
+    }
+
+    /// appends the history of the next stage, which started `offset` after this one; the epochs
+    /// continue after the last epoch of this history
+    pub fn extend(&mut self, other: &History, offset: Duration) {
+        let first_epoch = self.last_epoch();
+
+        for point in other.points() {
+            if self.best_cost().is_some_and(|best| point.best_cost >= best) {
+                continue;
+            }
+
+            self.points.push(HistoryPoint {
+                epoch: first_epoch + point.epoch,
+                best_cost: point.best_cost,
+                elapsed: offset + point.elapsed,
+            });
+        }
+    }
+
+    pub fn points(&self) -> &[HistoryPoint] {
+        &self.points
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn best_cost(&self) -> Option<f32> {
+        self.points.last().map(|point| point.best_cost)
+    }
+
+    fn last_epoch(&self) -> usize {
+        self.points.last().map(|point| point.epoch).unwrap_or(0)
+    }
+
+    /// writes every point as a CSV row
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "epoch,best_cost,elapsed_s")?;
+
+        for point in self.points.iter() {
+            writeln!(
+                writer,
+                "{},{},{:.6}",
+                point.epoch,
+                point.best_cost,
+                point.elapsed.as_secs_f64()
+            )?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), Error> {
+        let f = File::create(path).map_err(Error::io("history: failed to create file"))?;
+        let mut writer = BufWriter::new(f);
+
+        self.write_csv(&mut writer)
+            .map_err(Error::io("history: failed to write"))
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_records_only_the_improvements() {
+        let mut history = History::new();
+        history.record(0, 10.0);
+        history.record(3, 12.0);
+        history.record(5, 10.0);
+        history.record(7, 8.5);
+
+        let epochs: Vec<usize> = history.points().iter().map(|p| p.epoch).collect();
+        assert_eq!(vec![0, 7], epochs);
+        assert_eq!(Some(8.5), history.best_cost());
+    }
+
+    #[test]
+    fn test_extend_continues_the_epochs_and_the_time() {
+        let mut history = History::new();
+        history.record(0, 10.0);
+        history.record(4, 9.0);
+
+        let mut stage = History::new();
+        stage.record(0, 9.5);
+        stage.record(2, 7.0);
+        history.extend(&stage, Duration::from_secs(1));
+
+        let points = history.points();
+        assert_eq!(3, points.len());
+        assert_eq!(6, points[2].epoch);
+        assert_eq!(7.0, points[2].best_cost);
+        assert!(points[2].elapsed >= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut history = History::new();
+        history.record(2, 4.5);
+
+        let mut buf = vec![];
+        history.write_csv(&mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!("epoch,best_cost,elapsed_s", lines[0]);
+        assert!(lines[1].starts_with("2,4.5,"), "{}", lines[1]);
+    }
+}
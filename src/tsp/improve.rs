@@ -0,0 +1,117 @@
+/// Refinement of an existing tour
+///
+/// The local search and the metaheuristic solvers start from the initial route of the options,
+/// so they continue from a tour of an earlier run or of an external tool instead of starting
+/// from scratch.
+///
+/// The constructive and the exact solvers build the tour from scratch, they are refused.
+use super::kdtree::KDPoint;
+use super::progress::PublisherFn;
+use super::registry::Category;
+use super::{validate_route, Error, Solution, SolverInfo, SolverOptions, TspSolver};
+
+/// checks whether the solver continues from the initial route
+pub fn can_improve(info: &SolverInfo) -> bool {
+    !matches!(info.category, Category::Exact | Category::Constructive)
+}
+
+/// runs the solver from the tour; the metaheuristics may end up on a longer tour,
+/// then the starting tour is returned
+pub fn run(
+    solver: &dyn TspSolver,
+    cities: &[KDPoint],
+    tour: &[usize],
+    options: &SolverOptions,
+    progress: PublisherFn,
+) -> Result<Solution, Error> {
+    let info = solver.metadata();
+    if !can_improve(&info) {
+        return Err(Error::InvalidInput(format!(
+            "improve: {} builds the tour from scratch, use a local search or a metaheuristic",
+            info.name
+        )));
+    }
+
+    validate_route(cities, tour)?;
+
+    let mut options = options.clone();
+    options.initial_route = Some(tour.to_vec());
+
+    let start = Solution::new(tour, cities).with_target(&options);
+    let improved = solver.solve(cities, &options, progress)?;
+
+    if improved.total <= start.total {
+        Ok(improved)
+    } else {
+        Ok(start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::{kdtree, progress, Solvers};
+    use std::str::FromStr;
+
+    fn solver(name: &str) -> std::sync::Arc<dyn TspSolver> {
+        Solvers::from_str(name).unwrap().solver().unwrap()
+    }
+
+    fn square_cities() -> Vec<KDPoint> {
+        kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 0.5],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.0],
+        ])
+    }
+
+    #[test]
+    fn test_run_improves_the_tour() {
+        let cities = square_cities();
+        let tour = [0, 3, 1, 4, 2];
+        let start = Solution::new(&tour, &cities).total;
+
+        let res = run(
+            solver("2opt").as_ref(),
+            &cities,
+            &tour,
+            &SolverOptions::default(),
+            progress::silent_publisher(),
+        )
+        .unwrap();
+
+        assert!(res.total < start, "{} >= {}", res.total, start);
+    }
+
+    #[test]
+    fn test_run_refuses_invalid_tour() {
+        let err = run(
+            solver("2opt").as_ref(),
+            &square_cities(),
+            &[3, 1, 0, 4, 3],
+            &SolverOptions::default(),
+            progress::silent_publisher(),
+        )
+        .err()
+        .unwrap();
+
+        assert!(err.to_string().contains("visits the city 3 twice"));
+    }
+
+    #[test]
+    fn test_run_refuses_constructive_solver() {
+        let err = run(
+            solver("nn").as_ref(),
+            &square_cities(),
+            &[0, 1, 2, 3, 4],
+            &SolverOptions::default(),
+            progress::silent_publisher(),
+        )
+        .err()
+        .unwrap();
+
+        assert!(err.to_string().contains("nearest_neighbor builds the tour"));
+    }
+}
@@ -0,0 +1,278 @@
+/// JSON reading and writing, without the dependencies
+///
+/// The documents are read into the `toml::Value`, so the options of a JSON request go through
+/// the same checks as the configuration file. The integers are kept apart from the floats and
+/// the members set to `null` are left out, e.g `{"seed": null}` keeps the default seed.
+use std::iter::Peekable;
+use std::str::Chars;
+
+use toml::value::{Table, Value};
+
+use super::Error;
+
+/// parses the JSON document
+pub fn parse(text: &str) -> Result<Value, Error> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+        pos: 0,
+    };
+
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.chars.peek().is_some() {
+        return Err(parser.error("unexpected text after the document"));
+    }
+
+    value.ok_or_else(|| parser.error("the document is null"))
+}
+
+/// escapes the text for a JSON string, without the quotes
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    pos: usize, // characters read, for the error messages
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<char> {
+        self.pos += 1;
+        self.chars.next()
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidInput(format!("json: {} at char.{}", message, self.pos))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), Error> {
+        for c in expected.chars() {
+            if self.next() != Some(c) {
+                return Err(self.error(&format!("expected `{}`", expected)));
+            }
+        }
+
+        Ok(())
+    }
+
+    // None for null
+    fn value(&mut self) -> Result<Option<Value>, Error> {
+        self.skip_whitespace();
+
+        let value = match self.chars.peek() {
+            Some('{') => Value::Table(self.object()?),
+            Some('[') => Value::Array(self.array()?),
+            Some('"') => Value::String(self.string()?),
+            Some('t') => self.expect("true").map(|_| Value::Boolean(true))?,
+            Some('f') => self.expect("false").map(|_| Value::Boolean(false))?,
+            Some('n') => return self.expect("null").map(|_| None),
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number()?,
+            Some(_) => return Err(self.error("unexpected character")),
+            None => return Err(self.error("unexpected end of the document")),
+        };
+
+        Ok(Some(value))
+    }
+
+    fn object(&mut self) -> Result<Table, Error> {
+        let mut table = Table::new();
+        self.next();
+
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.next();
+            return Ok(table);
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.chars.peek() != Some(&'"') {
+                return Err(self.error("expected the key of the member"));
+            }
+            let key = self.string()?;
+
+            self.skip_whitespace();
+            self.expect(":")?;
+            if let Some(value) = self.value()? {
+                table.insert(key, value);
+            }
+
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(table),
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Vec<Value>, Error> {
+        let mut items = vec![];
+        self.next();
+
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&']') {
+            self.next();
+            return Ok(items);
+        }
+
+        loop {
+            match self.value()? {
+                Some(value) => items.push(value),
+                None => return Err(self.error("null is not supported in the arrays")),
+            }
+
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(items),
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let mut text = String::new();
+        self.next();
+
+        loop {
+            match self.next() {
+                Some('"') => return Ok(text),
+                Some('\\') => match self.next() {
+                    Some('"') => text.push('"'),
+                    Some('\\') => text.push('\\'),
+                    Some('/') => text.push('/'),
+                    Some('b') => text.push('\u{8}'),
+                    Some('f') => text.push('\u{c}'),
+                    Some('n') => text.push('\n'),
+                    Some('r') => text.push('\r'),
+                    Some('t') => text.push('\t'),
+                    Some('u') => text.push(self.unicode_escape()?),
+                    _ => return Err(self.error("unknown escape")),
+                },
+                Some(c) if c.is_control() => {
+                    return Err(self.error("control character in the string"))
+                }
+                Some(c) => text.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    // the characters outside of the basic plane come as the surrogate pairs
+    fn unicode_escape(&mut self) -> Result<char, Error> {
+        let high = self.hex_code()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("invalid unicode escape"));
+        }
+
+        self.expect("\\u")?;
+        let low = self.hex_code()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("invalid surrogate pair"));
+        }
+
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex_code(&mut self) -> Result<u32, Error> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.next().and_then(|c| c.to_digit(16));
+            code = code * 16 + digit.ok_or_else(|| self.error("invalid unicode escape"))?;
+        }
+
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Value, Error> {
+        let mut text = String::new();
+        while let Some(c) = self.chars.peek() {
+            if !(c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                break;
+            }
+
+            text.push(*c);
+            self.next();
+        }
+
+        let is_integer = !text.contains(|c| ".eE".contains(c));
+        if is_integer {
+            if let Ok(n) = text.parse::<i64>() {
+                return Ok(Value::Integer(n));
+            }
+        }
+
+        text.parse::<f64>()
+            .map(Value::Float)
+            .map_err(|_| self.error(&format!("invalid number `{}`", text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_document() {
+        let value = parse(
+            r#" {"name": "sq\"uare", "cities": [[0, 0.5], [-1e2, 3]],
+                 "options": {"verbose": true, "seed": null}, "tag": "é😀"} "#,
+        )
+        .unwrap();
+
+        assert_eq!(Some("sq\"uare"), value["name"].as_str());
+        assert_eq!(Some("é😀"), value["tag"].as_str());
+
+        let cities = value["cities"].as_array().unwrap();
+        assert_eq!(Some(0), cities[0][0].as_integer());
+        assert_eq!(Some(0.5), cities[0][1].as_float());
+        assert_eq!(Some(-100.0), cities[1][0].as_float());
+
+        let options = value["options"].as_table().unwrap();
+        assert_eq!(Some(true), options["verbose"].as_bool());
+        assert!(!options.contains_key("seed"));
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_documents() {
+        let err = parse("{\"epochs\": 10,}").err().unwrap();
+        assert_eq!(
+            "invalid input: json: expected the key of the member at char.14",
+            err.to_string()
+        );
+
+        assert!(parse("").is_err());
+        assert!(parse("null").is_err());
+        assert!(parse("[1, null]").is_err());
+        assert!(parse("[1 2]").is_err());
+        assert!(parse("\"open").is_err());
+        assert!(parse("{} {}").is_err());
+        assert!(parse("tru").is_err());
+        assert!(parse("1.2.3").is_err());
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("tri\\\"angle\\\\\\u000a", escape("tri\"angle\\\n"));
+    }
+}
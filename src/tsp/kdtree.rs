@@ -1,14 +1,15 @@
 use std::cell::RefCell;
 use std::cmp::Ordering;
+use std::sync::Arc;
 
-use super::NearestResult;
+use super::{validate_cities, Error, NearestResult, NearestResultItem};
 
-pub type PointMatrix = Vec<Vec<f32>>;
+pub type PointMatrix = Vec<Vec<f64>>;
 pub type KDSubTree = Option<Box<KDNode>>;
 
 /// builds a collection of KDPoints from PointMatrix,
 /// where id would be the row_id of PointMatri
-pub fn build_points(rows: &[Vec<f32>]) -> Vec<KDPoint> {
+pub fn build_points(rows: &[Vec<f64>]) -> Vec<KDPoint> {
     let mut points = vec![];
 
     for (i, coords) in rows.iter().enumerate() {
@@ -17,21 +18,9 @@ pub fn build_points(rows: &[Vec<f32>]) -> Vec<KDPoint> {
     points
 }
 
-pub fn from_cities(points: &[KDPoint]) -> KDTree {
-    let mut tree = KDTree::empty();
-
-    if points.is_empty() {
-        return tree;
-    };
-
-    let n_points = points.len();
-    let tree_points = points.clone().to_vec();
-    if let Some(root) = build_subtree(tree_points, 0) {
-        tree.size = n_points;
-        tree.root = Some(root);
-    }
-
-    tree
+/// builds balanced tree, it fails if the points have different dimensions
+pub fn from_cities(points: &[KDPoint]) -> Result<KDTree, Error> {
+    KDTree::from_points(points)
 }
 
 fn build_subtree(points: Vec<KDPoint>, depth: usize) -> KDSubTree {
@@ -56,6 +45,20 @@ fn build_subtree(points: Vec<KDPoint>, depth: usize) -> KDSubTree {
     Some(Box::new(root))
 }
 
+// moves the points out of the subtree, the visited ones are collected also separately
+fn take_points(subtree: KDSubTree, points: &mut Vec<KDPoint>, visited: &mut Vec<KDPoint>) {
+    if let Some(node) = subtree {
+        let node = *node;
+        if node.visited {
+            visited.push(node.point.clone());
+        }
+        points.push(node.point);
+
+        take_points(node.left, points, visited);
+        take_points(node.right, points, visited);
+    }
+}
+
 fn partition_points(
     points: Vec<KDPoint>,
     depth: usize,
@@ -84,6 +87,7 @@ fn partition_points(
 #[derive(Debug)]
 pub struct KDTree {
     size: usize,
+    n_visited: usize,
     dimensionality: usize,
     root: KDSubTree,
 }
@@ -96,6 +100,7 @@ impl KDTree {
             root: Some(Box::new(root)),
             dimensionality: pt_dimension,
             size: 1,
+            n_visited: 0,
         }
     }
 
@@ -104,37 +109,124 @@ impl KDTree {
             root: None,
             dimensionality: 0,
             size: 0,
+            n_visited: 0,
         }
     }
 
-    fn add(&mut self, new_point: KDPoint) {
-        self.size += 1;
+    /// builds balanced tree, it fails if the points have different dimensions
+    pub fn from_points(points: &[KDPoint]) -> Result<Self, Error> {
+        let mut tree = KDTree::empty();
+
+        if points.is_empty() {
+            return Ok(tree);
+        };
+
+        validate_cities(points, 1)?;
+
+        let n_points = points.len();
+        if let Some(root) = build_subtree(points.to_vec(), 0) {
+            tree.size = n_points;
+            tree.dimensionality = points[0].dim();
+            tree.root = Some(root);
+        }
+
+        Ok(tree)
+    }
+
+    /// builds the tree again from its points, so the added points get balanced too;
+    /// the visited points stay visited
+    pub fn rebuild(&mut self) {
+        let mut points = Vec::with_capacity(self.size);
+        let mut visited = vec![];
+        take_points(self.root.take(), &mut points, &mut visited);
+
+        self.root = build_subtree(points, 0);
+        self.n_visited = 0;
+        for city in visited.iter() {
+            self.mark_visited(city);
+        }
+    }
+
+    /// the height of the tree relative to the height of the balanced tree of the same size,
+    /// 1.0 is balanced; the tree built by adding sorted points gets the factor of n / log2(n)
+    pub fn balance_factor(&self) -> f32 {
+        let height = self.root.as_ref().map_or(0, |node| node.height());
+        let balanced_height = (usize::BITS - self.size.leading_zeros()) as usize;
+
+        if balanced_height == 0 {
+            1.0
+        } else {
+            height as f32 / balanced_height as f32
+        }
+    }
+
+    /// adds the point without rebalancing the tree, see `rebuild`
+    pub fn add(&mut self, new_point: KDPoint) -> Result<(), Error> {
         if self.dimensionality == 0 {
             self.dimensionality = new_point.dim();
         }
 
-        let parent = std::mem::replace(&mut self.root, None);
+        if new_point.dim() != self.dimensionality {
+            return Err(Error::InvalidInput(format!(
+                "point has {} coordinates, but the tree has {}",
+                new_point.dim(),
+                self.dimensionality
+            )));
+        }
+
+        let parent = self.root.take();
         self.root = self.add_rec(parent, new_point, 0);
+        self.size += 1;
+
+        Ok(())
     }
 
     fn add_rec(&mut self, parent: KDSubTree, new_point: KDPoint, depth: usize) -> KDSubTree {
-        if parent.is_none() {
-            return Some(Box::new(KDNode::leaf(new_point, depth + 1)));
+        let mut node = match parent {
+            None => return Some(Box::new(KDNode::leaf(new_point, depth + 1))),
+            Some(node) => node,
+        };
+
+        // the dimensions are checked by the caller, so it may only fail with NaN
+        if node.cmp_by_point(&new_point) == Some(Ordering::Greater) {
+            // if parent is greater than new point then the newpoint should go left
+            node.left = self.add_rec(node.left, new_point, depth + 1);
+        } else {
+            node.right = self.add_rec(node.right, new_point, depth + 1);
         }
+        node.size += 1;
+        node.n_unvisited += 1;
 
-        let mut node = parent.unwrap();
-        match node.cmp_by_point(&new_point) {
-            None => panic!("Point dimensionality is not matching with tree"), // fails with broken data
-            Some(Ordering::Greater) => {
-                // if parent is greater than new point then the newpoint should go left
-                node.left = self.add_rec(node.left, new_point, depth + 1);
-                Some(node)
-            }
-            _ => {
-                node.right = self.add_rec(node.right, new_point, depth + 1);
-                Some(node)
-            }
+        Some(node)
+    }
+
+    /// marks the city as visited, so the nearest searches don't return it anymore;
+    /// returns false if the tree has no such unvisited city
+    pub fn mark_visited(&mut self, city: &KDPoint) -> bool {
+        let is_marked = match self.root.as_mut() {
+            Some(node) if city.dim() == self.dimensionality => node.mark_visited(city),
+            _ => false,
+        };
+
+        if is_marked {
+            self.n_visited += 1;
         }
+
+        is_marked
+    }
+
+    /// makes all the cities unvisited again
+    pub fn reset_visited(&mut self) {
+        if let Some(node) = self.root.as_mut() {
+            node.reset_visited();
+        }
+
+        self.n_visited = 0;
+    }
+
+    /// the number of cities which are not marked as visited
+    pub fn n_unvisited(&self) -> usize {
+        self.size - self.n_visited
     }
 
     pub fn walk(&self, callback: impl Fn(&KDPoint) -> ()) {
@@ -149,13 +241,77 @@ impl KDTree {
         }
     }
 
-    pub fn nearest(&self, target: &KDPoint, n: usize) -> NearestResult {
+    pub fn nearest(&self, target: &KDPoint, n: usize) -> Result<NearestResult, Error> {
         let best_result = NearestResult::new(target.clone(), f32::INFINITY, n);
 
         match &self.root {
+            None => Ok(best_result),
+            Some(_) if target.dim() != self.dimensionality => Err(Error::InvalidInput(format!(
+                "target has {} coordinates, but the tree has {}",
+                target.dim(),
+                self.dimensionality
+            ))),
+            Some(n) => Ok(n.nearest(target, best_result)),
+        }
+    }
+
+    /// returns the `k` nearest points of the target, closest first;
+    /// the point with the same id as the target and the visited points are not included
+    pub fn nearest_k(&self, target: &KDPoint, k: usize) -> Result<Vec<NearestResultItem>, Error> {
+        let found = self.nearest(target, k)?;
+
+        Ok(found.nearest().into_iter().cloned().collect())
+    }
+
+    /// returns the `k` nearest points in the quadrant around the target, closest first: the bit 0
+    /// of the quadrant picks the points with the larger x, the bit 1 with the larger y, the points
+    /// on the axes belong to the larger side; the target and the visited points are not included
+    pub fn nearest_k_in_quadrant(
+        &self,
+        target: &KDPoint,
+        quadrant: usize,
+        k: usize,
+    ) -> Result<Vec<NearestResultItem>, Error> {
+        let best_result = NearestResult::new(target.clone(), f32::INFINITY, k);
+
+        let found = match &self.root {
             None => best_result,
-            Some(n) => n.nearest(target, best_result),
+            Some(_) if target.dim() != self.dimensionality => {
+                return Err(Error::InvalidInput(format!(
+                    "target has {} coordinates, but the tree has {}",
+                    target.dim(),
+                    self.dimensionality
+                )))
+            }
+            Some(n) => n.nearest_in_quadrant(target, quadrant, best_result),
+        };
+
+        Ok(found.nearest().into_iter().cloned().collect())
+    }
+
+    /// returns the points within the distance `radius` of the target, closest first;
+    /// like the nearest searches, it skips the target itself and the visited points
+    pub fn within_radius(&self, target: &KDPoint, radius: f32) -> Result<Vec<&KDPoint>, Error> {
+        let mut found = vec![];
+
+        match &self.root {
+            None => return Ok(found),
+            Some(_) if target.dim() != self.dimensionality => {
+                return Err(Error::InvalidInput(format!(
+                    "target has {} coordinates, but the tree has {}",
+                    target.dim(),
+                    self.dimensionality
+                )))
+            }
+            Some(n) => n.within_radius(target, radius, &mut found),
         }
+
+        found.sort_by(|a, b| {
+            let (dist_a, dist_b) = (a.distance(target), b.distance(target));
+            dist_a.partial_cmp(&dist_b).unwrap_or(Ordering::Equal)
+        });
+
+        Ok(found)
     }
 
     pub fn len(&self) -> usize {
@@ -176,7 +332,9 @@ impl KDTree {
 pub struct KDNode {
     point: KDPoint,
     depth: usize,
-    size: usize, // todo: remove seems redundant
+    size: usize,        // todo: remove seems redundant
+    n_unvisited: usize, // unvisited points of the subtree, the visited subtrees are skipped
+    visited: bool,
     left: KDSubTree,
     right: KDSubTree,
 }
@@ -200,6 +358,8 @@ impl KDNode {
             point,
             depth,
             size: 1 + left_size + right_size,
+            n_unvisited: 1 + left_size + right_size,
+            visited: false,
             left: left_node,
             right: right_node,
         }
@@ -215,6 +375,8 @@ impl KDNode {
             left,
             right,
             size: 1 + left_size + right_size,
+            n_unvisited: 1 + left_size + right_size,
+            visited: false,
         }
     }
 
@@ -223,52 +385,157 @@ impl KDNode {
             point,
             depth,
             size: 1,
+            n_unvisited: 1,
+            visited: false,
             left: None,
             right: None,
         }
     }
 
     pub fn nearest(&self, target_point: &KDPoint, best_result: NearestResult) -> NearestResult {
-        if self.is_empty() {
+        if self.is_empty() || self.n_unvisited == 0 {
             return best_result;
         }
 
-        let distance_from_target = self.point.distance(target_point);
+        let mut nearest_result = best_result;
+        if !self.visited {
+            let distance_from_target = self.point.distance(target_point);
+            nearest_result.add(self.point.clone(), distance_from_target);
+        }
+
+        // the tree checks the dimensions of the target before the search
+        let (closest_branch, futher_branch) = match self.cmp_by_point(&target_point) {
+            Some(Ordering::Greater) => (self.left(), self.right()),
+            _ => (self.right(), self.left()),
+        };
+
+        if let Some(branch) = closest_branch {
+            nearest_result = branch.nearest(target_point, nearest_result);
+        }
+
+        // the further branch may have closer points only if the split line is inside
+        // the search radius, which is the distance of the farthest result
+        let split_dist = self.point.split_distance(&target_point, self.level_coord());
+        if let Some(branch) = futher_branch {
+            if !nearest_result.is_full() || split_dist <= nearest_result.farthest_distance() {
+                nearest_result = branch.nearest(target_point, nearest_result);
+            }
+        }
+
+        nearest_result
+    }
+
+    fn nearest_in_quadrant(
+        &self,
+        target: &KDPoint,
+        quadrant: usize,
+        best_result: NearestResult,
+    ) -> NearestResult {
+        if self.is_empty() || self.n_unvisited == 0 {
+            return best_result;
+        }
 
-        let best_distance = best_result.distance;
         let mut nearest_result = best_result;
+        if !self.visited && is_in_quadrant(target, &self.point, quadrant) {
+            let distance_from_target = self.point.distance(target);
+            nearest_result.add(self.point.clone(), distance_from_target);
+        }
 
-        if distance_from_target <= best_distance {
-            let pt = self.point.clone();
-            nearest_result.add(pt, distance_from_target);
+        // the branch on the other side of the quadrant has none of its points
+        let coord = self.level_coord();
+        let (skip_left, skip_right) = match (self.point.get(coord), target.get(coord)) {
+            (Some(split), Some(origin)) if coord < 2 => {
+                let is_larger = (quadrant >> coord) & 1 == 1;
+                (
+                    is_larger && split + f64::EPSILON < origin,
+                    !is_larger && split - f64::EPSILON >= origin,
+                )
+            }
+            _ => (false, false),
         };
 
-        let (closest_branch, futher_branch) = match self.cmp_by_point(&target_point) {
-            None => panic!("Dimension conflict in nearest function"),
+        let (closest_branch, futher_branch) = match self.cmp_by_point(target) {
+            Some(Ordering::Greater) => ((self.left(), skip_left), (self.right(), skip_right)),
+            _ => ((self.right(), skip_right), (self.left(), skip_left)),
+        };
+
+        if let (Some(branch), false) = closest_branch {
+            nearest_result = branch.nearest_in_quadrant(target, quadrant, nearest_result);
+        }
+
+        let split_dist = self.point.split_distance(target, coord);
+        if let (Some(branch), false) = futher_branch {
+            if !nearest_result.is_full() || split_dist <= nearest_result.farthest_distance() {
+                nearest_result = branch.nearest_in_quadrant(target, quadrant, nearest_result);
+            }
+        }
+
+        nearest_result
+    }
+
+    fn within_radius<'a>(&'a self, target: &KDPoint, radius: f32, found: &mut Vec<&'a KDPoint>) {
+        if self.n_unvisited == 0 {
+            return;
+        }
+
+        if !self.visited && self.point.id != target.id && self.point.distance(target) <= radius {
+            found.push(&self.point);
+        }
+
+        // the branch beyond the split line is searched only if the circle crosses it
+        let (closest_branch, futher_branch) = match self.cmp_by_point(target) {
             Some(Ordering::Greater) => (self.left(), self.right()),
-            Some(_) => (self.right(), self.left()),
+            _ => (self.right(), self.left()),
         };
 
-        if closest_branch.is_some() {
-            let closest_result = closest_branch
-                .unwrap()
-                .nearest(target_point, nearest_result.clone());
+        if let Some(branch) = closest_branch {
+            branch.within_radius(target, radius, found);
+        }
 
-            let pt_distance = closest_result.closest_distance();
-            nearest_result.add(closest_result.point, pt_distance);
+        if let Some(branch) = futher_branch {
+            if self.point.split_distance(target, self.level_coord()) <= radius {
+                branch.within_radius(target, radius, found);
+            }
         }
+    }
 
-        // check distance from split line
-        let split_dist = self.point.split_distance(&target_point, self.level_coord());
-        if nearest_result.closest_distance() > split_dist && futher_branch.is_some() {
-            let further_result = futher_branch
-                .unwrap()
-                .nearest(target_point, nearest_result.clone());
-            let pt_distance = further_result.closest_distance();
-            nearest_result.add(further_result.point, pt_distance);
+    // the equal coordinates may end up on both sides of the split
+    fn mark_visited(&mut self, city: &KDPoint) -> bool {
+        if self.n_unvisited == 0 {
+            return false;
         }
 
-        nearest_result
+        let is_marked = if !self.visited && self.point.id == city.id {
+            self.visited = true;
+            true
+        } else {
+            match self.cmp_by_point(city) {
+                Some(Ordering::Greater) => self.left.as_mut().is_some_and(|n| n.mark_visited(city)),
+                Some(Ordering::Less) => self.right.as_mut().is_some_and(|n| n.mark_visited(city)),
+                _ => {
+                    self.right.as_mut().is_some_and(|n| n.mark_visited(city))
+                        || self.left.as_mut().is_some_and(|n| n.mark_visited(city))
+                }
+            }
+        };
+
+        if is_marked {
+            self.n_unvisited -= 1;
+        }
+
+        is_marked
+    }
+
+    fn reset_visited(&mut self) {
+        self.visited = false;
+        self.n_unvisited = self.size;
+
+        if let Some(node) = self.left.as_mut() {
+            node.reset_visited();
+        }
+        if let Some(node) = self.right.as_mut() {
+            node.reset_visited();
+        }
     }
 
     fn point(&self) -> &KDPoint {
@@ -329,26 +596,106 @@ impl KDNode {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        from = "super::serde_impls::PointData",
+        into = "super::serde_impls::PointData"
+    )
+)]
 pub struct KDPoint {
     pub id: usize,
     dimensionality: usize,
-    coords: Vec<f32>,
+    coords: Vec<f64>, // in double precision, the UTM coordinates have more digits than f32
+    weights: Option<WeightsRow>,
+}
+
+/// the explicit costs of the edges between the cities, e.g of the TSPLIB XML instances, which
+/// have no coordinates; the costs are the full matrix by the positions of the cities
+#[derive(Debug)]
+pub struct EdgeWeights {
+    n: usize,
+    costs: Vec<f64>,
+}
+
+impl EdgeWeights {
+    /// the rows of the square matrix of the costs
+    pub fn new(rows: &[Vec<f64>]) -> Result<Self, Error> {
+        let n = rows.len();
+        if let Some(row) = rows.iter().find(|row| row.len() != n) {
+            return Err(Error::InvalidInput(format!(
+                "the matrix of the edge weights has {} rows, but a row of {} costs",
+                n,
+                row.len()
+            )));
+        }
+
+        Ok(EdgeWeights {
+            n,
+            costs: rows.concat(),
+        })
+    }
+
+    pub fn cost(&self, from: usize, to: usize) -> f64 {
+        self.costs[from * self.n + to]
+    }
+
+    /// the cities 1..n with the distances of the weights; their coordinates are their rows of
+    /// the matrix, so the search trees can split them: the difference of the costs to a third
+    /// city is never bigger than their distance, if the costs are a metric
+    pub fn into_cities(self) -> Vec<KDPoint> {
+        let weights = Arc::new(self);
+
+        (0..weights.n)
+            .map(|pos| {
+                let row = &weights.costs[pos * weights.n..(pos + 1) * weights.n];
+                let mut city = KDPoint::new_with_id(pos + 1, row);
+                city.weights = Some(WeightsRow {
+                    weights: weights.clone(),
+                    pos,
+                });
+                city
+            })
+            .collect()
+    }
+}
+
+// the edge weights the city takes its distances from, and its position in them
+#[derive(Debug, Clone)]
+struct WeightsRow {
+    weights: Arc<EdgeWeights>,
+    pos: usize,
+}
+
+/// the quadrant of the point around the origin, see `KDTree::nearest_k_in_quadrant`
+pub fn quadrant_of(origin: &KDPoint, point: &KDPoint) -> usize {
+    let is_right = point.get(0) >= origin.get(0);
+    let is_above = point.get(1) >= origin.get(1);
+
+    is_right as usize + 2 * is_above as usize
+}
+
+fn is_in_quadrant(origin: &KDPoint, point: &KDPoint, quadrant: usize) -> bool {
+    quadrant_of(origin, point) == quadrant
 }
 
 impl KDPoint {
-    pub fn new(coords: &[f32]) -> Self {
+    pub fn new(coords: &[f64]) -> Self {
         KDPoint {
             id: 0,
             dimensionality: coords.len(),
             coords: coords.to_vec(),
+            weights: None,
         }
     }
 
-    pub fn new_with_id(id: usize, coords: &[f32]) -> Self {
+    pub fn new_with_id(id: usize, coords: &[f64]) -> Self {
         KDPoint {
             id,
             dimensionality: coords.len(),
             coords: coords.to_vec(),
+            weights: None,
         }
     }
 
@@ -356,11 +703,11 @@ impl KDPoint {
         self.dimensionality
     }
 
-    pub fn coords(&self) -> &[f32] {
+    pub fn coords(&self) -> &[f64] {
         &self.coords[..]
     }
 
-    pub fn get(&self, dimension: usize) -> Option<f32> {
+    pub fn get(&self, dimension: usize) -> Option<f64> {
         self.coords.get(dimension).map(|x| x.clone())
     }
 
@@ -374,21 +721,31 @@ impl KDPoint {
         diff < f32::EPSILON
     }
 
+    /// the distance is measured in double precision, only the result is rounded to f32
     pub fn distance(&self, other: &KDPoint) -> f32 {
-        let distance: f32 = self
-            .coords
+        self.distance_f64(other) as f32
+    }
+
+    /// the distance in double precision, for summing up the long tours; the cities of the same
+    /// explicit edge weights take it from them
+    pub fn distance_f64(&self, other: &KDPoint) -> f64 {
+        if let (Some(row), Some(other_row)) = (&self.weights, &other.weights) {
+            if Arc::ptr_eq(&row.weights, &other_row.weights) {
+                return row.weights.cost(row.pos, other_row.pos);
+            }
+        }
+
+        self.coords
             .iter()
             .zip(other.coords())
             .map(|(x, y)| (x - y).powi(2))
-            .sum::<f32>()
-            .sqrt();
-
-        distance
+            .sum::<f64>()
+            .sqrt()
     }
 
     /// returns distance from split level
     fn split_distance(&self, other: &KDPoint, coord: usize) -> f32 {
-        (self.coords[coord] - other.get(coord).unwrap()).abs()
+        (self.coords[coord] - other.get(coord).unwrap()).abs() as f32
     }
 
     pub fn cmp_by_coord(&self, other: &KDPoint, coord: usize) -> Option<Ordering> {
@@ -401,7 +758,7 @@ impl KDPoint {
 
         let res = if self_coord < other_coord {
             Ordering::Less
-        } else if (self_coord - other_coord).abs() < f32::EPSILON {
+        } else if (self_coord - other_coord).abs() < f64::EPSILON {
             Ordering::Equal
         } else {
             Ordering::Greater
@@ -410,7 +767,7 @@ impl KDPoint {
         Some(res)
     }
 
-    pub fn x(&self) -> f32 {
+    pub fn x(&self) -> f64 {
         if self.dimensionality < 1 {
             panic!("for accessing y, dimensionality must be > 0");
         }
@@ -418,7 +775,7 @@ impl KDPoint {
         self.coords[0].clone()
     }
 
-    pub fn y(&self) -> f32 {
+    pub fn y(&self) -> f64 {
         if self.dimensionality < 2 {
             panic!("for accessing y, dimensionality must be > 1");
         }
@@ -431,6 +788,10 @@ impl KDPoint {
 mod tests {
     use super::*;
     use crate::test::helpers::assert_approx;
+    use crate::tsp::generator;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
     use std::cell::RefCell;
 
     #[test]
@@ -512,13 +873,23 @@ mod tests {
         assert_approx(2.828427, pt.distance(&other))
     }
 
+    #[test]
+    fn kdpoint_distance_of_utm_coordinates() {
+        // f32 rounds the northing to the multiples of 0.5
+        let pt = KDPoint::new(&[500_000.25, 4_649_776.25]);
+        let other = KDPoint::new(&[500_000.25, 4_649_776.5]);
+
+        assert_approx(0.25, pt.distance(&other));
+        assert_eq!(Some(4_649_776.25), pt.get(1));
+    }
+
     #[test]
     fn kdtree_add_new_node_to_empty_tree() {
         let mut tree = KDTree::empty();
 
         assert_eq!(0, tree.len());
 
-        tree.add(KDPoint::new(&[0.0, 0.0]));
+        tree.add(KDPoint::new(&[0.0, 0.0])).unwrap();
 
         assert_eq!(1, tree.len());
         assert_eq!(2, tree.dimensionality);
@@ -533,7 +904,7 @@ mod tests {
         assert_eq!(1, tree.len());
         assert_eq!(2, tree.dimensionality);
 
-        tree.add(KDPoint::new(&[-1.0, 0.0]));
+        tree.add(KDPoint::new(&[-1.0, 0.0])).unwrap();
 
         assert_eq!(2, tree.len());
         assert_eq!(2, tree.dimensionality);
@@ -567,9 +938,9 @@ mod tests {
         let mut tree = KDTree::empty();
 
         //add some nodes
-        tree.add(KDPoint::new(&[0.0, 0.0]));
-        tree.add(KDPoint::new(&[-1.0, 0.0]));
-        tree.add(KDPoint::new(&[1.0, 0.0]));
+        tree.add(KDPoint::new(&[0.0, 0.0])).unwrap();
+        tree.add(KDPoint::new(&[-1.0, 0.0])).unwrap();
+        tree.add(KDPoint::new(&[1.0, 0.0])).unwrap();
 
         // double-check insertion
         assert_eq!(3, tree.len());
@@ -646,7 +1017,7 @@ mod tests {
             vec![1.0, -1.0],
             vec![1.0, 1.0],
         ]);
-        let tree = from_cities(&points);
+        let tree = from_cities(&points).unwrap();
 
         assert_eq!(7, tree.len());
 
@@ -672,21 +1043,21 @@ mod tests {
             vec![1.0, 0.0],
         ]);
 
-        let kd = from_cities(&cities);
+        let kd = from_cities(&cities).unwrap();
 
-        let res = kd.nearest(&cities[0], 2);
+        let res = kd.nearest(&cities[0], 2).unwrap();
         assert_eq!(cities[1].id, res.point.id);
 
-        let res2 = kd.nearest(&cities[1], 2);
+        let res2 = kd.nearest(&cities[1], 2).unwrap();
         assert_eq!(cities[2].id, res2.point.id);
 
-        let res3 = kd.nearest(&cities[2], 2);
+        let res3 = kd.nearest(&cities[2], 2).unwrap();
         assert_eq!(cities[1].id, res3.point.id);
 
-        let res4 = kd.nearest(&cities[3], 2);
+        let res4 = kd.nearest(&cities[3], 2).unwrap();
         assert_eq!(cities[2].id, res4.point.id);
 
-        let res5 = kd.nearest(&cities[4], 2);
+        let res5 = kd.nearest(&cities[4], 2).unwrap();
         assert_eq!(cities[3].id, res5.point.id);
     }
 
@@ -700,31 +1071,283 @@ mod tests {
         ]);
 
         let expected_coords = vec![-100.0, -100.0];
-        let tree = from_cities(&points);
+        let tree = from_cities(&points).unwrap();
         assert_eq!(4, tree.len());
 
         let pt1 = KDPoint::new(&[-110.0, -100.0]);
-        let res = tree.nearest(&pt1, 1);
+        let res = tree.nearest(&pt1, 1).unwrap();
 
         assert_approx(10.0, res.distance);
         assert_eq!(expected_coords, res.point.coords);
 
         let pt2 = KDPoint::new(&[-90.0, -100.0]);
-        let res = tree.nearest(&pt2, 1);
+        let res = tree.nearest(&pt2, 1).unwrap();
 
         assert_approx(10.0, res.distance);
         assert_eq!(expected_coords, res.point.coords);
 
         let pt3 = KDPoint::new(&[-100.0, -90.0]);
-        let res = tree.nearest(&pt3, 1);
+        let res = tree.nearest(&pt3, 1).unwrap();
 
         assert_approx(10.0, res.distance);
         assert_eq!(expected_coords, res.point.coords);
 
         let pt4 = KDPoint::new(&[-100.0, -110.0]);
-        let res = tree.nearest(&pt4, 1);
+        let res = tree.nearest(&pt4, 1).unwrap();
 
         assert_approx(10.0, res.distance);
         assert_eq!(expected_coords, res.point.coords);
     }
+
+    #[test]
+    fn from_cities_with_mixed_dimensions_fails() {
+        let points = build_points(&[vec![0.0, 0.0], vec![1.0, 1.0, 1.0]]);
+
+        assert!(from_cities(&points).is_err());
+    }
+
+    #[test]
+    fn kdtree_nearest_with_wrong_target_dimension_fails() {
+        let tree = from_cities(&build_points(&[vec![0.0, 0.0], vec![1.0, 1.0]])).unwrap();
+
+        assert!(tree.nearest(&KDPoint::new(&[0.0, 0.0, 0.0]), 1).is_err());
+    }
+
+    // the distances of the k nearest points by checking all the points
+    fn brute_force_nearest(points: &[KDPoint], target: &KDPoint, k: usize) -> Vec<f32> {
+        let mut distances: Vec<f32> = points
+            .iter()
+            .filter(|pt| pt.id != target.id)
+            .map(|pt| pt.distance(target))
+            .collect();
+        distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        distances.truncate(k);
+
+        distances
+    }
+
+    #[test]
+    fn kdtree_nearest_k_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for n in [1, 2, 7, 50, 200].iter() {
+            let points = generator::uniform(*n, &generator::BoundingBox::square(100.0), &mut rng);
+            let tree = from_cities(&points).unwrap();
+
+            for k in [1, 3, 10, *n + 1].iter() {
+                for target in points.iter() {
+                    let found = tree.nearest_k(target, *k).unwrap();
+                    let distances: Vec<f32> = found.iter().map(|item| item.distance).collect();
+
+                    // ties may be ordered differently, so only the distances are compared
+                    assert_eq!(brute_force_nearest(&points, target, *k), distances);
+                    assert!(found.iter().all(|item| item.point.id != target.id));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn kdtree_nearest_k_in_quadrant_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let points = generator::uniform(200, &generator::BoundingBox::square(100.0), &mut rng);
+        let mut tree = from_cities(&points).unwrap();
+        for point in points.iter().step_by(3) {
+            tree.mark_visited(point);
+        }
+
+        for target in points.iter() {
+            for quadrant in 0..4 {
+                let found = tree.nearest_k_in_quadrant(target, quadrant, 3).unwrap();
+                let distances: Vec<f32> = found.iter().map(|item| item.distance).collect();
+
+                let unvisited: Vec<KDPoint> = points
+                    .iter()
+                    .filter(|pt| pt.id % 3 != 0 && quadrant_of(target, pt) == quadrant)
+                    .cloned()
+                    .collect();
+                assert_eq!(brute_force_nearest(&unvisited, target, 3), distances);
+                assert!(found
+                    .iter()
+                    .all(|item| quadrant_of(target, &item.point) == quadrant));
+            }
+        }
+    }
+
+    #[test]
+    fn kdtree_nearest_k_with_distance_ties() {
+        // the neighbors on the grid without the jitter are at the same distances
+        let points = generator::grid(10, 10, 0.0, &mut StdRng::seed_from_u64(7));
+        let tree = from_cities(&points).unwrap();
+
+        for target in points.iter() {
+            let found = tree.nearest_k(target, 8).unwrap();
+            let distances: Vec<f32> = found.iter().map(|item| item.distance).collect();
+
+            assert_eq!(brute_force_nearest(&points, target, 8), distances);
+        }
+    }
+
+    #[test]
+    fn kdtree_nearest_skips_visited_cities() {
+        let cities = build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 0.5],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+        let mut tree = from_cities(&cities).unwrap();
+
+        assert!(tree.mark_visited(&cities[1]));
+        assert!(!tree.mark_visited(&cities[1]));
+        assert_eq!(4, tree.n_unvisited());
+        assert_eq!(cities[2].id, tree.nearest(&cities[0], 1).unwrap().point.id);
+
+        for city in cities.iter() {
+            tree.mark_visited(city);
+        }
+        assert_eq!(0, tree.n_unvisited());
+        assert!(tree.nearest_k(&cities[0], 3).unwrap().is_empty());
+
+        tree.reset_visited();
+        assert_eq!(5, tree.n_unvisited());
+        assert_eq!(cities[1].id, tree.nearest(&cities[0], 1).unwrap().point.id);
+    }
+
+    #[test]
+    fn kdtree_nearest_unvisited_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(3);
+        // the duplicate coordinates end up on both sides of the split
+        let mut points = generator::grid(8, 8, 0.0, &mut rng);
+        points.extend(
+            generator::grid(8, 8, 0.0, &mut rng)
+                .into_iter()
+                .map(|pt| KDPoint::new_with_id(pt.id + 64, pt.coords())),
+        );
+        let mut tree = from_cities(&points).unwrap();
+
+        let mut unvisited = points.clone();
+        unvisited.shuffle(&mut rng);
+        while let Some(city) = unvisited.pop() {
+            assert!(tree.mark_visited(&city));
+            assert_eq!(unvisited.len(), tree.n_unvisited());
+
+            let found = tree.nearest_k(&city, 5).unwrap();
+            let distances: Vec<f32> = found.iter().map(|item| item.distance).collect();
+            assert_eq!(brute_force_nearest(&unvisited, &city, 5), distances);
+        }
+    }
+
+    #[test]
+    fn kdtree_mark_visited_after_add() {
+        let mut tree = KDTree::empty();
+        tree.add(KDPoint::new_with_id(0, &[0.0, 0.0])).unwrap();
+        tree.add(KDPoint::new_with_id(1, &[1.0, 0.0])).unwrap();
+        tree.add(KDPoint::new_with_id(2, &[2.0, 0.0])).unwrap();
+
+        assert!(tree.mark_visited(&KDPoint::new_with_id(1, &[1.0, 0.0])));
+        assert!(!tree.mark_visited(&KDPoint::new_with_id(5, &[1.0, 0.0])));
+
+        let found = tree
+            .nearest_k(&KDPoint::new_with_id(0, &[0.0, 0.0]), 2)
+            .unwrap();
+        assert_eq!(
+            vec![2],
+            found.iter().map(|i| i.point.id).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn kdtree_within_radius_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let points = generator::uniform(300, &generator::BoundingBox::square(100.0), &mut rng);
+        let mut tree = from_cities(&points).unwrap();
+        for city in points.iter().step_by(5) {
+            tree.mark_visited(city);
+        }
+
+        for radius in [0.0, 3.0, 10.0, 200.0].iter() {
+            for target in points.iter().step_by(3) {
+                let found = tree.within_radius(target, *radius).unwrap();
+                let distances: Vec<f32> = found.iter().map(|pt| pt.distance(target)).collect();
+
+                let mut expected: Vec<f32> = points
+                    .iter()
+                    .filter(|pt| pt.id % 5 != 0 && pt.id != target.id)
+                    .map(|pt| pt.distance(target))
+                    .filter(|dist| dist <= radius)
+                    .collect();
+                expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+                assert_eq!(expected, distances);
+            }
+        }
+    }
+
+    #[test]
+    fn kdtree_within_radius_finds_duplicates() {
+        let points = build_points(&[
+            vec![1.0, 1.0],
+            vec![0.0, 0.0],
+            vec![1.0, 1.0],
+            vec![1.0, 1.0],
+        ]);
+        let tree = from_cities(&points).unwrap();
+
+        let found = tree.within_radius(&points[0], 0.0).unwrap();
+        let mut ids: Vec<usize> = found.iter().map(|pt| pt.id).collect();
+        ids.sort();
+
+        assert_eq!(vec![2, 3], ids);
+        assert!(tree.within_radius(&KDPoint::new(&[1.0]), 1.0).is_err());
+    }
+
+    #[test]
+    fn kdtree_rebuild_balances_added_points() {
+        let points: Vec<KDPoint> = (0..64)
+            .map(|i| KDPoint::new_with_id(i, &[i as f64, i as f64]))
+            .collect();
+        let mut tree = KDTree::empty();
+        for pt in points.iter() {
+            tree.add(pt.clone()).unwrap();
+        }
+        tree.mark_visited(&points[10]);
+
+        // the sorted points make a linked list
+        assert_eq!(64, tree.len());
+        assert!(tree.balance_factor() > 5.0, "{}", tree.balance_factor());
+
+        tree.rebuild();
+        assert_eq!(64, tree.len());
+        assert!((tree.balance_factor() - 1.0).abs() < f32::EPSILON);
+        assert_eq!(63, tree.n_unvisited());
+
+        let found = tree.nearest_k(&points[11], 2).unwrap();
+        let ids: Vec<usize> = found.iter().map(|item| item.point.id).collect();
+        // the visited city 10 is as close as 12
+        assert_eq!(12, ids[0]);
+        assert!(!ids.contains(&10));
+    }
+
+    #[test]
+    fn kdtree_from_points_is_balanced() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let points = generator::uniform(1000, &generator::BoundingBox::square(100.0), &mut rng);
+        let tree = KDTree::from_points(&points).unwrap();
+
+        assert_eq!(1000, tree.len());
+        assert!((tree.balance_factor() - 1.0).abs() < f32::EPSILON);
+        assert!((KDTree::empty().balance_factor() - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn kdtree_nearest_k_with_empty_tree() {
+        let tree = KDTree::empty();
+
+        assert!(tree
+            .nearest_k(&KDPoint::new(&[0.0, 0.0]), 3)
+            .unwrap()
+            .is_empty());
+    }
 }
@@ -0,0 +1,214 @@
+/// LKH interoperability
+///
+/// LKH is Keld Helsgaun's implementation of the Lin-Kernighan heuristic, which gives
+/// near optimal tours even for very large problems; it is a good yardstick for the
+/// heuristics of teeline.
+///
+/// Teeline writes a problem file and a parameter file, runs the LKH binary and then reads
+/// the tour it produced. Like Concorde, LKH expects that node ids are 1..n.
+///
+/// Example of the parameter file:
+///
+/// ```text
+/// PROBLEM_FILE = berlin52.tsp
+/// OUTPUT_TOUR_FILE = berlin52.tour
+/// RUNS = 1
+/// ```
+///
+/// source: http://akira.ruc.dk/~keld/research/LKH/
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::external::{self, absolute_path, file_stem, parent_dir};
+use super::kdtree::KDPoint;
+use super::tsplib::{self, TspLibData};
+use super::{Error, Solution};
+
+pub const DEFAULT_BINARY: &str = "LKH";
+
+#[derive(Debug, Clone)]
+pub struct LkhParameters {
+    pub runs: usize,               // how many times LKH restarts the search
+    pub max_trials: Option<usize>, // max number of trials per run, LKH's default is n
+    pub seed: Option<u64>,         // random seed, LKH's default is 1
+    pub time_limit: Option<f64>,   // time limit of the search in seconds
+}
+
+impl Default for LkhParameters {
+    fn default() -> Self {
+        LkhParameters {
+            runs: 1,
+            max_trials: None,
+            seed: None,
+            time_limit: None,
+        }
+    }
+}
+
+/// writes the problem into TSPLIB file that LKH accepts
+pub fn write_problem(path: &Path, data: &TspLibData) -> Result<(), Error> {
+    tsplib::write_to_file(path, &data.renumbered())
+}
+
+pub fn write_parameters(
+    par_path: &Path,
+    problem_path: &Path,
+    tour_path: &Path,
+    params: &LkhParameters,
+) -> Result<(), Error> {
+    let f = File::create(par_path).map_err(Error::io("lkh: failed to create file"))?;
+    let mut writer = BufWriter::new(f);
+
+    write_parameters_to(&mut writer, problem_path, tour_path, params)
+        .map_err(Error::io("lkh: failed to write parameters"))
+}
+
+/// reads the tour produced by LKH and maps node ids back to the city ids
+pub fn read_tour(tour_path: &Path, cities: &[KDPoint]) -> Result<Solution, Error> {
+    let node_ids = tsplib::read_tour_from_file(tour_path)?;
+
+    solution_from_nodes(&node_ids, cities)
+}
+
+/// executes LKH with the parameter file, LKH resolves relative paths from the working
+/// directory, so it runs in the directory of the parameter file
+pub fn run(binary: &Path, par_path: &Path) -> Result<(), Error> {
+    let args = vec![absolute_path(par_path)];
+
+    external::run("lkh", binary, &args, &parent_dir(par_path))
+}
+
+/// writes the problem and the parameter files into the `work_dir`, runs LKH with them
+/// and returns the tour it found
+pub fn solve(
+    binary: &Path,
+    data: &TspLibData,
+    work_dir: &Path,
+    params: &LkhParameters,
+) -> Result<Solution, Error> {
+    let stem = file_stem(data);
+    let problem_path = work_dir.join(format!("{}.tsp", stem));
+    let par_path = work_dir.join(format!("{}.par", stem));
+    let tour_path = work_dir.join(format!("{}.tour", stem));
+
+    write_problem(&problem_path, data)?;
+    write_parameters(&par_path, &problem_path, &tour_path, params)?;
+    run(binary, &par_path)?;
+
+    read_tour(&tour_path, data.cities())
+}
+
+fn write_parameters_to<W: Write>(
+    writer: &mut W,
+    problem_path: &Path,
+    tour_path: &Path,
+    params: &LkhParameters,
+) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "PROBLEM_FILE = {}",
+        absolute_path(problem_path).display()
+    )?;
+    writeln!(
+        writer,
+        "OUTPUT_TOUR_FILE = {}",
+        absolute_path(tour_path).display()
+    )?;
+    writeln!(writer, "RUNS = {}", params.runs)?;
+
+    if let Some(max_trials) = params.max_trials {
+        writeln!(writer, "MAX_TRIALS = {}", max_trials)?;
+    }
+
+    if let Some(seed) = params.seed {
+        writeln!(writer, "SEED = {}", seed)?;
+    }
+
+    if let Some(time_limit) = params.time_limit {
+        writeln!(writer, "TIME_LIMIT = {}", time_limit)?;
+    }
+
+    writer.flush()
+}
+
+fn solution_from_nodes(node_ids: &[usize], cities: &[KDPoint]) -> Result<Solution, Error> {
+    if node_ids.len() != cities.len() {
+        return Err(Error::external(
+            "lkh",
+            format!(
+                "tour has {} nodes, but problem has {} cities",
+                node_ids.len(),
+                cities.len()
+            ),
+        ));
+    }
+
+    let mut route = Vec::with_capacity(node_ids.len());
+    for &node_id in node_ids.iter() {
+        match node_id.checked_sub(1).and_then(|pos| cities.get(pos)) {
+            Some(city) => route.push(city.id),
+            None => {
+                return Err(Error::external(
+                    "lkh",
+                    format!("unknown node {} in tour", node_id),
+                ))
+            }
+        }
+    }
+
+    Ok(Solution::new(&route, cities))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    #[test]
+    fn test_write_parameters_to_with_optional_params() {
+        let params = LkhParameters {
+            runs: 3,
+            seed: Some(42),
+            ..LkhParameters::default()
+        };
+
+        let mut buffer: Vec<u8> = vec![];
+        write_parameters_to(
+            &mut buffer,
+            Path::new("/tmp/a.tsp"),
+            Path::new("/tmp/a.tour"),
+            &params,
+        )
+        .unwrap();
+
+        let content = String::from_utf8(buffer).unwrap();
+        assert!(content.contains("PROBLEM_FILE = /tmp/a.tsp\n"));
+        assert!(content.contains("OUTPUT_TOUR_FILE = /tmp/a.tour\n"));
+        assert!(content.contains("RUNS = 3\n"));
+        assert!(content.contains("SEED = 42\n"));
+        assert!(!content.contains("MAX_TRIALS"));
+    }
+
+    #[test]
+    fn test_solution_from_nodes_maps_node_ids_to_city_ids() {
+        let cities = vec![
+            KDPoint::new_with_id(10, &[0.0, 0.0]),
+            KDPoint::new_with_id(20, &[0.0, 1.0]),
+            KDPoint::new_with_id(30, &[1.0, 1.0]),
+        ];
+
+        let res = solution_from_nodes(&[1, 3, 2], &cities);
+        assert!(res.is_ok());
+        assert_eq!(&[10, 30, 20], res.unwrap().route());
+    }
+
+    #[test]
+    fn test_solution_from_nodes_with_invalid_nodes() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 1.0]]);
+
+        assert!(solution_from_nodes(&[0, 1], &cities).is_err());
+        assert!(solution_from_nodes(&[1, 3], &cities).is_err());
+        assert!(solution_from_nodes(&[1], &cities).is_err());
+    }
+}
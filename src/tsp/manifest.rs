@@ -0,0 +1,259 @@
+/// Run manifest
+///
+/// Next to the tour saved with `--output`, the solver writes `<output>.meta.json` with everything
+/// needed to reproduce the run: the version, the solver, all the resolved options with the seed,
+/// the checksum of the problem, the wall time and the final cost.
+///
+/// The options are written under the keys of the configuration file, so they can be copied into
+/// it or into the `options` of a `serve` request; the unset options are `null`.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::tsplib::TspLibData;
+use super::{json, Error, Solution, SolverOptions, VERSION};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub solver: String,
+    pub options: SolverOptions,
+    pub command: Vec<String>, // the command-line of the run
+    pub instance: String,
+    pub n_cities: usize,
+    pub checksum: String,
+    pub seconds: f64, // wall time of the solver
+    pub cost: f32,
+}
+
+impl Manifest {
+    pub fn new(
+        solver: &str,
+        options: &SolverOptions,
+        data: &TspLibData,
+        tour: &Solution,
+        seconds: f64,
+    ) -> Self {
+        Manifest {
+            solver: solver.to_string(),
+            options: options.clone(),
+            command: std::env::args().collect(),
+            instance: data.name.clone(),
+            n_cities: data.len(),
+            checksum: instance_checksum(data),
+            seconds,
+            cost: tour.total,
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let command: Vec<String> = self
+            .command
+            .iter()
+            .map(|arg| format!("\"{}\"", json::escape(arg)))
+            .collect();
+
+        writeln!(writer, "{{")?;
+        writeln!(writer, "  \"version\": \"{}\",", VERSION)?;
+        writeln!(writer, "  \"command\": [{}],", command.join(", "))?;
+        writeln!(writer, "  \"solver\": \"{}\",", json::escape(&self.solver))?;
+        writeln!(writer, "  \"options\": {},", options_json(&self.options))?;
+        writeln!(writer, "  \"seed\": {},", optional(self.options.seed))?;
+        writeln!(
+            writer,
+            "  \"instance\": {{\"name\": \"{}\", \"cities\": {}, \"checksum\": \"{}\"}},",
+            json::escape(&self.instance),
+            self.n_cities,
+            self.checksum
+        )?;
+        writeln!(writer, "  \"seconds\": {:.3},", self.seconds)?;
+        writeln!(writer, "  \"cost\": {}", self.cost)?;
+        writeln!(writer, "}}")?;
+
+        writer.flush()
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), Error> {
+        let f = File::create(path).map_err(Error::io("manifest: failed to create file"))?;
+        let mut writer = BufWriter::new(f);
+
+        self.write_to(&mut writer)
+            .map_err(Error::io("manifest: failed to write"))
+    }
+}
+
+/// `<output>.meta.json` next to the output file
+pub fn manifest_path(output_path: &Path) -> PathBuf {
+    let mut path = output_path.as_os_str().to_owned();
+    path.push(".meta.json");
+
+    PathBuf::from(path)
+}
+
+/// FNV-1a hash of the city ids and the coordinates, it doesn't change with the comments
+/// or the formatting of the file
+pub fn instance_checksum(data: &TspLibData) -> String {
+    let mut hash = FNV_OFFSET;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    };
+
+    for city in data.cities() {
+        feed(&(city.id as u64).to_le_bytes());
+        for coord in city.coords() {
+            feed(&coord.to_le_bytes());
+        }
+    }
+
+    format!("fnv1a:{:016x}", hash)
+}
+
+fn options_json(options: &SolverOptions) -> String {
+    let fields = [
+        ("epochs", options.epochs.to_string()),
+        ("platoo_epochs", options.platoo_epochs.to_string()),
+        (
+            "hill_strategy",
+            format!("\"{}\"", options.hill_strategy.as_str()),
+        ),
+        ("n_samples", optional(options.n_samples)),
+        ("verbose", options.verbose.to_string()),
+        ("show_progress", options.show_progress.to_string()),
+        ("n_nearest", options.n_nearest.to_string()),
+        ("candidates", format!("\"{}\"", options.candidates.as_str())),
+        ("start_city", optional(options.start_city)),
+        ("end_city", optional(options.end_city)),
+        ("all_starts", options.all_starts.to_string()),
+        ("open_tour", options.open_tour.to_string()),
+        (
+            "spatial_index",
+            format!("\"{}\"", options.spatial_index.as_str()),
+        ),
+        ("n_elite", options.n_elite.to_string()),
+        ("population_size", options.population_size.to_string()),
+        ("n_offspring", optional(options.n_offspring)),
+        ("n_islands", options.n_islands.to_string()),
+        ("migration_interval", options.migration_interval.to_string()),
+        (
+            "mutation_probability",
+            options.mutation_probability.to_string(),
+        ),
+        ("cooling_rate", options.cooling_rate.to_string()),
+        (
+            "cooling_schedule",
+            format!("\"{}\"", options.cooling_schedule.as_str()),
+        ),
+        (
+            "neighborhood",
+            format!("\"{}\"", options.neighborhood.as_str()),
+        ),
+        ("min_temperature", options.min_temperature.to_string()),
+        ("max_temperature", options.max_temperature.to_string()),
+        ("reheat", optional(options.reheat)),
+        ("restart_from_best", optional(options.restart_from_best)),
+        ("aspiration", options.aspiration.to_string()),
+        ("seed", optional(options.seed)),
+        ("target_cost", optional(options.target_cost)),
+        ("max_stale_epochs", optional(options.max_stale_epochs)),
+        ("max_nodes", optional(options.max_nodes)),
+        ("max_memory", options.max_memory_mb.to_string()),
+        ("max_seconds", optional(options.max_seconds)),
+    ];
+
+    let members: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("\"{}\": {}", key, value))
+        .collect();
+
+    format!("{{{}}}", members.join(", "))
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::{config, kdtree};
+
+    fn square_data() -> TspLibData {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+
+        TspLibData::new("square".to_string(), String::new(), cities)
+    }
+
+    fn written(manifest: &Manifest) -> String {
+        let mut buffer = vec![];
+        manifest.write_to(&mut buffer).unwrap();
+
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_manifest_path() {
+        assert_eq!(
+            PathBuf::from("out/berlin52.tour.meta.json"),
+            manifest_path(Path::new("out/berlin52.tour"))
+        );
+    }
+
+    #[test]
+    fn test_instance_checksum_depends_on_cities() {
+        let data = square_data();
+        let checksum = instance_checksum(&data);
+        assert!(checksum.starts_with("fnv1a:"));
+        assert_eq!(22, checksum.len());
+
+        let renamed = TspLibData::new("other".to_string(), String::new(), data.cities().to_vec());
+        assert_eq!(checksum, instance_checksum(&renamed));
+
+        let mut moved = data.cities().to_vec();
+        moved[2] = kdtree::KDPoint::new_with_id(2, &[1.0, 1.5]);
+        let moved = TspLibData::new("square".to_string(), String::new(), moved);
+        assert_ne!(checksum, instance_checksum(&moved));
+    }
+
+    #[test]
+    fn test_write_manifest() {
+        let data = square_data();
+        let mut options = SolverOptions::default();
+        options.seed = Some(42);
+        options.max_seconds = Some(2.5);
+
+        let tour = Solution::new(&[0, 1, 2, 3], data.cities());
+        let mut manifest = Manifest::new("simulated_annealing", &options, &data, &tour, 1.25);
+        manifest.command = vec!["bin".to_string(), "sa".to_string()];
+
+        let text = written(&manifest);
+        let document = json::parse(&text).unwrap();
+        assert_eq!(Some(VERSION), document["version"].as_str());
+        assert_eq!(Some("simulated_annealing"), document["solver"].as_str());
+        assert_eq!(Some(42), document["seed"].as_integer());
+        assert_eq!(Some("square"), document["instance"]["name"].as_str());
+        assert_eq!(Some(4), document["instance"]["cities"].as_integer());
+        assert_eq!(Some(1.25), document["seconds"].as_float());
+        assert_eq!(Some(4), document["cost"].as_integer());
+        assert!(text.contains("  \"command\": [\"bin\", \"sa\"],\n"));
+
+        // the options can be read back as the options of the configuration file
+        let mut read = SolverOptions::default();
+        for (key, value) in document["options"].as_table().unwrap() {
+            config::set_option(&mut read, key, value).unwrap();
+        }
+        assert_eq!(Some(42), read.seed);
+        assert_eq!(Some(2.5), read.max_seconds);
+        assert_eq!(options.cooling_rate, read.cooling_rate);
+        assert_eq!(None, read.target_cost);
+    }
+}
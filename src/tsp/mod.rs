@@ -1,33 +1,93 @@
+pub mod alns;
+pub mod analysis;
+pub mod batch;
 pub mod bellman_karp;
+pub mod benchmark;
 pub mod branch_bound;
+pub mod city_filter;
+pub mod clock;
+pub mod cluster;
+pub mod concorde;
+pub mod config;
+pub mod cvrp;
+pub mod delaunay;
 pub mod distance_matrix;
+pub mod error;
+pub mod external;
+pub mod fixed_edges;
+pub mod generator;
 pub mod genetic_algorithm;
+pub mod grid;
+pub mod gtsp;
+pub mod hilbert;
+pub mod history;
+pub mod improve;
+pub mod json;
 pub mod kdtree;
+pub mod lkh;
+pub mod manifest;
+pub mod mst;
 pub mod nearest_neighbor;
+pub mod neighbors;
+pub mod optima;
+pub mod output;
+pub mod pipeline;
+pub mod plot_script;
+pub mod plugin;
+pub mod precedence;
+pub mod prize_collecting;
 pub mod progress;
+pub mod registry;
+pub mod remote;
+pub mod restart;
 pub mod route;
+pub mod selftest;
+#[cfg(feature = "serde")]
+mod serde_impls;
+pub mod serve;
 pub mod simulated_annealing;
 pub mod stochastic_hill;
 pub mod tabu_search;
+pub mod tour;
+pub mod trace;
 pub mod tsplib;
+#[cfg(feature = "xml")]
+pub mod tsplib_xml;
 pub mod two_opt;
+pub mod units;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 use crate::tsp::kdtree::KDPoint;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 pub const VERSION: &'static str = "0.6.1";
 pub const AUTHOR: &'static str = "Timo Sulg <timo@sulg.dev>";
 
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use std::fmt;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clock::Instant;
+pub use error::{Error, ValidationError};
+use progress::PublisherFn;
+use route::Route;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Solvers {
+    Alns,
     BellmanKarp,
     BranchBound,
+    DoubleTree,
     NearestNeighbor,
     GeneticAlgorithm,
+    Hilbert,
     SimulatedAnnealing,
     StochasticHill,
     TabuSearch,
@@ -36,40 +96,71 @@ pub enum Solvers {
 }
 
 impl Solvers {
-    pub fn variants() -> Vec<&'static str> {
+    /// returns all the built-in solvers
+    pub fn all() -> Vec<Solvers> {
         vec![
-            "bellman_karp",
-            "bhk",
-            "branch_bound",
-            "nearest_neighbor",
-            "nn",
-            "genetic_algorithm",
-            "ga",
-            "simulated_annealing",
-            "sa",
-            "stochastic_hill",
-            "tabu_search",
-            "two_opt",
-            "2opt",
+            Solvers::Alns,
+            Solvers::BellmanKarp,
+            Solvers::BranchBound,
+            Solvers::DoubleTree,
+            Solvers::NearestNeighbor,
+            Solvers::GeneticAlgorithm,
+            Solvers::Hilbert,
+            Solvers::SimulatedAnnealing,
+            Solvers::StochasticHill,
+            Solvers::TabuSearch,
+            Solvers::TwoOpt,
         ]
     }
+
+    /// returns the names and the aliases of the built-in solvers
+    pub fn variants() -> Vec<String> {
+        let mut names = vec![];
+        for info in Solvers::all()
+            .iter()
+            .filter_map(|s| s.solver())
+            .map(|s| s.metadata())
+        {
+            names.push(info.name);
+            names.extend(info.aliases);
+        }
+
+        names
+    }
+
+    /// returns the implementation of the built-in solver
+    pub fn solver(&self) -> Option<Arc<dyn TspSolver>> {
+        let solver: Arc<dyn TspSolver> = match self {
+            Solvers::Alns => Arc::new(alns::AlnsSolver),
+            Solvers::BellmanKarp => Arc::new(bellman_karp::BellmanKarpSolver),
+            Solvers::BranchBound => Arc::new(branch_bound::BranchBoundSolver),
+            Solvers::DoubleTree => Arc::new(mst::DoubleTreeSolver),
+            Solvers::NearestNeighbor => Arc::new(nearest_neighbor::NearestNeighborSolver),
+            Solvers::GeneticAlgorithm => Arc::new(genetic_algorithm::GeneticAlgorithmSolver),
+            Solvers::Hilbert => Arc::new(hilbert::HilbertSolver),
+            Solvers::SimulatedAnnealing => Arc::new(simulated_annealing::SimulatedAnnealingSolver),
+            Solvers::StochasticHill => Arc::new(stochastic_hill::StochasticHillSolver),
+            Solvers::TabuSearch => Arc::new(tabu_search::TabuSearchSolver),
+            Solvers::TwoOpt => Arc::new(two_opt::TwoOptSolver),
+            Solvers::Unspecified => return None,
+        };
+
+        Some(solver)
+    }
 }
 
 impl FromStr for Solvers {
     type Err = &'static str;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "bhk" | "bellman_karp" => Ok(Solvers::BellmanKarp),
-            "branch_bound" => Ok(Solvers::BranchBound),
-            "nn" | "nearest_neighbor" => Ok(Solvers::NearestNeighbor),
-            "ga" | "genetic_algorithm" => Ok(Solvers::GeneticAlgorithm),
-            "sa" | "simulated_annealing" => Ok(Solvers::SimulatedAnnealing),
-            "stochastic_hill" => Ok(Solvers::StochasticHill),
-            "tabu_search" => Ok(Solvers::TabuSearch),
-            "2opt" | "two_opt" => Ok(Solvers::TwoOpt),
-            _ => Err("unknown solver"),
-        }
+        Solvers::all()
+            .into_iter()
+            .find(|solver_type| {
+                solver_type
+                    .solver()
+                    .is_some_and(|solver| solver.metadata().has_name(s))
+            })
+            .ok_or("unknown solver")
     }
 }
 
@@ -77,16 +168,46 @@ impl FromStr for Solvers {
 
 #[derive(Clone, Debug)]
 pub struct SolverOptions {
-    pub epochs: usize,        // how many iteration to run
-    pub platoo_epochs: usize, // how many iterations to do on the platoo
+    pub epochs: usize,                                // how many iteration to run
+    pub platoo_epochs: usize,                         // how many iterations to do on the platoo
+    pub hill_strategy: stochastic_hill::HillStrategy, // which sampled move the hill climbing takes
+    pub n_samples: Option<usize>, // moves sampled per epoch by the best strategy, n cities by default
     pub verbose: bool,
-    pub n_nearest: usize,
+    pub n_nearest: usize, // size of the neighbor lists of two_opt
+    pub candidates: neighbors::Candidates, // how the neighbor lists of two_opt are built
+    pub start_city: Option<usize>, // city id the tour starts from, the first by default
+    pub end_city: Option<usize>, // city id the path from the start city ends at, see `to_tour_options`
+    pub all_starts: bool, // nearest neighbor tries every start city and keeps the shortest tour
+    pub spatial_index: grid::SpatialIndex, // nearest neighbor searches the cities with it
     pub mutation_probability: f32,
     pub n_elite: usize,
+    pub population_size: usize,
+    pub n_offspring: Option<usize>, // children bred per generation, by default they refill the population
+    pub n_islands: usize, // populations of the genetic algorithm evolving on their own threads
+    pub migration_interval: usize, // epochs between the migrations of the elites to the next island
     pub cooling_rate: f32,
+    pub cooling_schedule: simulated_annealing::CoolingSchedule,
+    pub neighborhood: route::Neighborhood, // the moves of simulated annealing and stochastic hill
     pub max_temperature: f32,
     pub min_temperature: f32,
+    pub reheat: Option<f32>, // the fraction of max_temperature the annealing reheats to when stuck
+    pub restart_from_best: Option<usize>, // epochs without a new best tour before the annealing returns to it
+    pub aspiration: bool, // tabu search accepts the tabu move when it finds a new best tour
     pub show_progress: bool, // should we show and print progress
+    pub seed: Option<u64>, // makes runs of the stochastic solvers reproducible
+    pub max_seconds: Option<f32>, // wall-clock budget of the run
+    pub target_cost: Option<f32>, // the solver stops when it finds a tour this short
+    pub max_stale_epochs: Option<usize>, // the solver stops after so many epochs without a new best tour
+    pub max_nodes: Option<usize>,        // how many nodes branch and bound expands at most
+    pub max_memory_mb: usize, // the exact solvers refuse the problems which need more memory
+    pub cancel: CancelFlag,   // stops the solver from another thread, e.g on Ctrl-C
+    // city ids of the route the improving solvers start from, instead of the order of the cities
+    pub initial_route: Option<Vec<usize>>,
+    // pairs of city ids the tour must connect, see `fixed_edges`
+    pub fixed_edges: Vec<(usize, usize)>,
+    pub open_tour: bool, // the tour ends at its last city, the closing edge is not counted
+    // pairs of city ids, the first one is visited before the second one, see `precedence`
+    pub precedences: Vec<(usize, usize)>,
 }
 
 impl SolverOptions {
@@ -94,34 +215,552 @@ impl SolverOptions {
         SolverOptions {
             epochs: 10_000,
             platoo_epochs: 500,
+            hill_strategy: stochastic_hill::HillStrategy::First,
+            n_samples: None,
             verbose: false,
-            n_nearest: 3,
+            n_nearest: 8,
+            candidates: neighbors::Candidates::Knn,
+            start_city: None,
+            end_city: None,
+            all_starts: false,
+            spatial_index: grid::SpatialIndex::KdTree,
             mutation_probability: 0.001,
             n_elite: 3,
+            population_size: 100,
+            n_offspring: None,
+            n_islands: 1,
+            migration_interval: 50,
             cooling_rate: 0.0001,
+            cooling_schedule: simulated_annealing::CoolingSchedule::Geometric,
+            neighborhood: route::Neighborhood::Reverse,
             min_temperature: 0.001,
             max_temperature: 1_000.0,
+            reheat: None,
+            restart_from_best: None,
+            aspiration: true,
             show_progress: true,
+            seed: None,
+            max_seconds: None,
+            target_cost: None,
+            max_stale_epochs: None,
+            max_nodes: None,
+            max_memory_mb: 2_048,
+            cancel: CancelFlag::new(),
+            initial_route: None,
+            fixed_edges: vec![],
+            open_tour: false,
+            precedences: vec![],
+        }
+    }
+
+    /// checks the tour length against the target cost
+    pub fn is_target_reached(&self, cost: f32) -> bool {
+        matches!(self.target_cost, Some(target) if cost <= target)
+    }
+
+    /// checks the epochs since the last new best tour against max_stale_epochs
+    pub fn is_stale(&self, n_stale_epochs: usize) -> bool {
+        matches!(self.max_stale_epochs, Some(max) if n_stale_epochs >= max)
+    }
+
+    /// starts the clock of the time budget, solvers call it when they start
+    pub fn deadline(&self) -> Deadline {
+        Deadline::new(self.max_seconds).with_cancel(&self.cancel)
+    }
+
+    /// the route the improving solvers start from: the initial route, if it is given,
+    /// otherwise the order of the cities from the start city
+    pub fn start_route(&self, cities: &[KDPoint]) -> Result<Route, Error> {
+        match &self.initial_route {
+            Some(route) => {
+                validate_route(cities, route)?;
+                Ok(Route::new(route))
+            }
+            None => {
+                let mut route: Vec<usize> = cities.iter().map(|c| c.id).collect();
+                route.rotate_left(self.start_pos(cities)?);
+                // the path ends at the end city, the closing edge joins it to the start city
+                if let Some(end_city) = self.end_city {
+                    let pos = route.iter().position(|&id| id == end_city).ok_or_else(|| {
+                        Error::InvalidInput(format!("unknown end city {}", end_city))
+                    })?;
+                    let end_city = route.remove(pos);
+                    route.push(end_city);
+                }
+                Ok(Route::new(&route))
+            }
+        }
+    }
+
+    /// the position of the start city in the `cities`, the first city by default
+    pub fn start_pos(&self, cities: &[KDPoint]) -> Result<usize, Error> {
+        match self.start_city {
+            Some(city_id) => cities
+                .iter()
+                .position(|c| c.id == city_id)
+                .ok_or_else(|| Error::InvalidInput(format!("unknown start city {}", city_id))),
+            None => Ok(0),
+        }
+    }
+
+    /// returns the random generator for the solver, it is seeded from the entropy
+    /// if the options have no seed
+    pub fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    /// checks the values of the options the solver uses, the out of range values would
+    /// otherwise run forever or return a nonsense tour
+    pub fn validate(&self, solver: &SolverInfo) -> Result<(), Error> {
+        let uses = |option: &str| solver.uses_option(option);
+        let invalid = |message: String| -> Result<(), Error> {
+            Err(Error::InvalidInput(format!("{}: {}", solver.name, message)))
+        };
+
+        if uses("cooling_rate") && !(self.cooling_rate > 0.0 && self.cooling_rate < 1.0) {
+            return invalid(format!(
+                "cooling_rate must be in (0, 1), got {}",
+                self.cooling_rate
+            ));
+        }
+
+        if uses("cooling_schedule")
+            && self.cooling_schedule == simulated_annealing::CoolingSchedule::Timed
+            && self.max_seconds.is_none()
+        {
+            return invalid("the timed cooling_schedule needs max_seconds".to_string());
+        }
+
+        if uses("min_temperature")
+            && !(self.min_temperature > 0.0 && self.min_temperature.is_finite())
+        {
+            return invalid(format!(
+                "min_temperature must be positive, got {}",
+                self.min_temperature
+            ));
+        }
+
+        if uses("max_temperature")
+            && !(self.max_temperature > self.min_temperature && self.max_temperature.is_finite())
+        {
+            return invalid(format!(
+                "max_temperature must be > min_temperature {}, got {}",
+                self.min_temperature, self.max_temperature
+            ));
+        }
+
+        if uses("reheat")
+            && matches!(self.reheat, Some(fraction) if !(fraction > 0.0 && fraction <= 1.0))
+        {
+            return invalid("reheat must be in (0, 1]".to_string());
+        }
+
+        if uses("restart_from_best") && self.restart_from_best == Some(0) {
+            return invalid("restart_from_best must be at least 1".to_string());
+        }
+
+        if uses("max_stale_epochs") && self.max_stale_epochs == Some(0) {
+            return invalid("max_stale_epochs must be at least 1".to_string());
+        }
+
+        if uses("mutation_probability") && !(0.0..=1.0).contains(&self.mutation_probability) {
+            return invalid(format!(
+                "mutation_probability must be in [0, 1], got {}",
+                self.mutation_probability
+            ));
+        }
+
+        if uses("population_size") && self.population_size < 2 {
+            return invalid(format!(
+                "population_size must be at least 2, got {}",
+                self.population_size
+            ));
+        }
+
+        if uses("n_elite") && self.n_elite >= self.population_size {
+            return invalid(format!(
+                "n_elite must be < population size {}, got {}",
+                self.population_size, self.n_elite
+            ));
+        }
+
+        if uses("n_islands") && self.n_islands == 0 {
+            return invalid("n_islands must be at least 1".to_string());
+        }
+
+        if uses("migration_interval") && self.migration_interval == 0 {
+            return invalid("migration_interval must be at least 1".to_string());
+        }
+
+        if uses("n_samples") && self.n_samples == Some(0) {
+            return invalid("n_samples must be at least 1".to_string());
+        }
+
+        if uses("max_seconds")
+            && matches!(self.max_seconds, Some(secs) if !(secs >= 0.0 && secs.is_finite()))
+        {
+            return invalid("max_seconds must be a non-negative number".to_string());
+        }
+
+        self.check_constraints(solver)
+    }
+
+    /// fails when the solver can't keep the constraints of the tour, the fixed edges, the open
+    /// tour or the precedences, it would return the tour which doesn't meet them
+    pub fn check_constraints(&self, solver: &SolverInfo) -> Result<(), Error> {
+        if self.open_tour && !solver.uses_option("open_tour") {
+            return Err(Error::InvalidInput(format!(
+                "{}: it can't build the open tour, use nearest_neighbor, two_opt, \
+                 simulated_annealing, tabu_search or stochastic_hill",
+                solver.name
+            )));
+        }
+
+        if let Some(end_city) = self.end_city {
+            let invalid =
+                |message: &str| Err(Error::InvalidInput(format!("{}: {}", solver.name, message)));
+            if !solver.uses_option("end_city") {
+                return invalid(
+                    "it can't build the path to the end city, use nearest_neighbor or two_opt",
+                );
+            }
+            if self
+                .start_city
+                .is_none_or(|start_city| start_city == end_city)
+            {
+                return invalid("the end city needs another start city");
+            }
+            if !self.precedences.is_empty() {
+                return invalid("the path to the end city can't keep the precedences");
+            }
+        }
+
+        fixed_edges::check_solver(solver, self)?;
+        precedence::check_solver(solver, self)
+    }
+
+    /// the options the solver runs with: the path from the start city to the end city is solved
+    /// as the closed tour with the fixed edge between them, the tour is as much longer as the
+    /// edge for every path, so the shortest tour has the shortest path; `Solution::with_start_city`
+    /// cuts the edge off
+    pub fn to_tour_options(&self) -> SolverOptions {
+        let mut options = self.clone();
+        if let (Some(start_city), Some(end_city)) = (self.start_city, self.end_city) {
+            options.fixed_edges.push((end_city, start_city));
+            options.open_tour = false;
+        }
+
+        options
+    }
+
+    /// sets the options of the solver, e.g
+    /// `SolverOptions::default().with_algorithm(&AlgorithmOptions::TabuSearch(tabu))`
+    pub fn with_algorithm(mut self, algorithm: &AlgorithmOptions) -> Self {
+        match algorithm {
+            AlgorithmOptions::Alns(alns) => alns.apply(&mut self),
+            AlgorithmOptions::BranchBound(branch_bound) => branch_bound.apply(&mut self),
+            AlgorithmOptions::GeneticAlgorithm(ga) => ga.apply(&mut self),
+            AlgorithmOptions::NearestNeighbor(nn) => nn.apply(&mut self),
+            AlgorithmOptions::SimulatedAnnealing(sa) => sa.apply(&mut self),
+            AlgorithmOptions::StochasticHill(hill) => hill.apply(&mut self),
+            AlgorithmOptions::TabuSearch(tabu) => tabu.apply(&mut self),
+            AlgorithmOptions::TwoOpt(two_opt) => two_opt.apply(&mut self),
+        }
+
+        self
+    }
+}
+
+/// the options of one solver, the compiler checks that they belong to it; the solvers run with
+/// the flat `SolverOptions`, as the pipelines and the plugins pass the same options to every solver
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlgorithmOptions {
+    Alns(alns::AlnsOptions),
+    BranchBound(branch_bound::BranchBoundOptions),
+    GeneticAlgorithm(genetic_algorithm::GaOptions),
+    NearestNeighbor(nearest_neighbor::NearestNeighborOptions),
+    SimulatedAnnealing(simulated_annealing::SaOptions),
+    StochasticHill(stochastic_hill::HillOptions),
+    TabuSearch(tabu_search::TabuOptions),
+    TwoOpt(two_opt::TwoOptOptions),
+}
+
+impl AlgorithmOptions {
+    /// the solver the options belong to
+    pub fn solver(&self) -> Solvers {
+        match self {
+            AlgorithmOptions::Alns(_) => Solvers::Alns,
+            AlgorithmOptions::BranchBound(_) => Solvers::BranchBound,
+            AlgorithmOptions::GeneticAlgorithm(_) => Solvers::GeneticAlgorithm,
+            AlgorithmOptions::NearestNeighbor(_) => Solvers::NearestNeighbor,
+            AlgorithmOptions::SimulatedAnnealing(_) => Solvers::SimulatedAnnealing,
+            AlgorithmOptions::StochasticHill(_) => Solvers::StochasticHill,
+            AlgorithmOptions::TabuSearch(_) => Solvers::TabuSearch,
+            AlgorithmOptions::TwoOpt(_) => Solvers::TwoOpt,
         }
     }
+
+    /// the options of the solver as they are set in the `options`,
+    /// None for the solvers which use only the shared options
+    pub fn of(solver: &Solvers, options: &SolverOptions) -> Option<Self> {
+        let algorithm = match solver {
+            Solvers::Alns => AlgorithmOptions::Alns(options.into()),
+            Solvers::BranchBound => AlgorithmOptions::BranchBound(options.into()),
+            Solvers::GeneticAlgorithm => AlgorithmOptions::GeneticAlgorithm(options.into()),
+            Solvers::NearestNeighbor => AlgorithmOptions::NearestNeighbor(options.into()),
+            Solvers::SimulatedAnnealing => AlgorithmOptions::SimulatedAnnealing(options.into()),
+            Solvers::StochasticHill => AlgorithmOptions::StochasticHill(options.into()),
+            Solvers::TabuSearch => AlgorithmOptions::TabuSearch(options.into()),
+            Solvers::TwoOpt => AlgorithmOptions::TwoOpt(options.into()),
+            _ => return None,
+        };
+
+        Some(algorithm)
+    }
+}
+
+/// CancelFlag is shared between the solver and the code which may stop it early,
+/// all the clones of the flag see the cancellation
+#[derive(Clone, Debug, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    pub fn new() -> Self {
+        CancelFlag::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, AtomicOrdering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(AtomicOrdering::Relaxed)
+    }
+}
+
+/// Deadline tells when the solver has used up its time budget or it was cancelled,
+/// so it stops and returns the best tour found so far
+#[derive(Clone, Debug)]
+pub struct Deadline {
+    started_at: Instant,
+    budget: Option<Duration>,
+    cancel: CancelFlag,
+}
+
+impl Deadline {
+    pub fn new(max_seconds: Option<f32>) -> Self {
+        Deadline {
+            started_at: Instant::now(),
+            budget: max_seconds.map(|secs| Duration::from_secs_f32(secs.max(0.0))),
+            cancel: CancelFlag::new(),
+        }
+    }
+
+    pub fn with_cancel(mut self, cancel: &CancelFlag) -> Self {
+        self.cancel = cancel.clone();
+        self
+    }
+
+    pub fn is_over(&self) -> bool {
+        if self.cancel.is_cancelled() {
+            return true;
+        }
+
+        match self.budget {
+            Some(budget) => self.started_at.elapsed() >= budget,
+            None => false,
+        }
+    }
+}
+
+// -- TspSolver
+
+/// the options every solver honors, the runners apply them to the solution of the solver;
+/// the solvers list them only if they use them on the way, e.g the nearest neighbor starts
+/// its tour from the start city
+pub const COMMON_OPTIONS: &[&str] = &["start_city"];
+
+/// describes the solver for the command-line and for the runners
+#[derive(Clone, Debug)]
+pub struct SolverInfo {
+    pub name: String, // the name used for selecting the solver from the command-line
+    pub aliases: Vec<String>, // shorter names of the same solver
+    pub description: String,
+    pub is_stochastic: bool, // it may find a different solution on every run
+    pub category: registry::Category,
+    pub complexity: String, // rough time complexity, n is the number of cities
+    pub options: Vec<String>, // the command-line options the solver uses
+}
+
+impl SolverInfo {
+    pub fn new(name: &str, aliases: &[&str], description: &str, is_stochastic: bool) -> Self {
+        SolverInfo {
+            name: name.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            description: description.to_string(),
+            is_stochastic,
+            category: registry::Category::Unspecified,
+            complexity: String::new(),
+            options: vec![],
+        }
+    }
+
+    pub fn with_category(mut self, category: registry::Category) -> Self {
+        self.category = category;
+        self
+    }
+
+    pub fn with_complexity(mut self, complexity: &str) -> Self {
+        self.complexity = complexity.to_string();
+        self
+    }
+
+    pub fn with_options(mut self, options: &[&str]) -> Self {
+        self.options = options.iter().map(|o| o.to_string()).collect();
+        self
+    }
+
+    /// checks if the solver reads the option, by the name of its command-line flag
+    pub fn uses_option(&self, option: &str) -> bool {
+        self.options.iter().any(|o| o == option)
+    }
+
+    /// checks the name and the aliases
+    pub fn has_name(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|a| a == name)
+    }
+}
+
+/// TspSolver is the common interface of the built-in solvers and the plugins,
+/// check the `plugin` module how to make a new solver available for the CLI
+pub trait TspSolver: Send + Sync {
+    fn metadata(&self) -> SolverInfo;
+
+    /// solves the problem, the progress messages are sent into the `progress`
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error>;
 }
 
 // -- solution implementation
 pub type CityTable = HashMap<usize, KDPoint>;
 
 pub fn total_distance(cities: &[KDPoint], route: &[usize]) -> f32 {
-    let mut total = 0.0;
-    let last_idx = route.len() - 1;
+    tour_length(cities, route) as f32
+}
+
+/// the length of the closed tour, summed up in double precision: the f32 sum of the
+/// 100k+ edges drifts too much to compare the tours; the route through an unknown city has
+/// no length, it is infinite and `validate_route` tells which city it is
+pub fn tour_length(cities: &[KDPoint], route: &[usize]) -> f64 {
+    route_length(cities, route, true).unwrap_or(f64::INFINITY)
+}
+
+/// the length of the open tour, which ends at its last city without returning to the first
+pub fn path_length(cities: &[KDPoint], route: &[usize]) -> f64 {
+    route_length(cities, route, false).unwrap_or(f64::INFINITY)
+}
+
+fn route_length(
+    cities: &[KDPoint],
+    route: &[usize],
+    is_closed: bool,
+) -> Result<f64, ValidationError> {
+    let (first, last) = match (route.first(), route.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Ok(0.0),
+    };
 
     let cities_table = city_table_from_vec(cities);
-    for i in 0..last_idx {
-        let distance = cities_table[&route[i]].distance(&cities_table[&route[i + 1]]);
-        total += distance
+    let city = |city_id: usize| {
+        cities_table
+            .get(&city_id)
+            .ok_or(ValidationError::UnknownCity(city_id))
+    };
+
+    let mut total = 0.0;
+    for pair in route.windows(2) {
+        total += city(pair[0])?.distance_f64(city(pair[1])?);
+    }
+
+    if is_closed {
+        total += city(last)?.distance_f64(city(first)?);
+    }
+
+    Ok(total)
+}
+
+/// checks that there are enough cities for the solver, their ids are unique and all of
+/// them have the same number of finite coordinates, so the solvers can compare them
+pub fn validate_cities(cities: &[KDPoint], min_cities: usize) -> Result<(), Error> {
+    if cities.len() < min_cities {
+        return Err(Error::InvalidInput(format!(
+            "it requires at least {} cities, got {}",
+            min_cities,
+            cities.len()
+        )));
+    }
+
+    let dim = cities.first().map_or(0, |c| c.dim());
+    let mut ids = HashSet::with_capacity(cities.len());
+    for city in cities.iter() {
+        if !ids.insert(city.id) {
+            return Err(Error::InvalidInput(format!(
+                "city id {} is not unique",
+                city.id
+            )));
+        }
+
+        if city.dim() != dim || dim == 0 {
+            return Err(Error::InvalidInput(format!(
+                "city {} has {} coordinates, expected {}",
+                city.id,
+                city.dim(),
+                dim
+            )));
+        }
+
+        if city.coords().iter().any(|x| !x.is_finite()) {
+            return Err(Error::InvalidInput(format!(
+                "city {} has non-finite coordinates",
+                city.id
+            )));
+        }
     }
 
-    total += cities_table[&route[last_idx]].distance(&cities_table[&route[0]]);
+    Ok(())
+}
+
+/// checks that the route visits every city once
+pub fn validate_route(cities: &[KDPoint], route: &[usize]) -> Result<(), Error> {
+    check_route(cities, route).map_err(Error::from)
+}
+
+// the problems of the route are reported in its order, the missing cities in the order of cities
+fn check_route(cities: &[KDPoint], route: &[usize]) -> Result<(), ValidationError> {
+    let known_ids: HashSet<usize> = cities.iter().map(|city| city.id).collect();
+
+    let mut visited = HashSet::with_capacity(route.len());
+    for city_id in route.iter() {
+        if !known_ids.contains(city_id) {
+            return Err(ValidationError::UnknownCity(*city_id));
+        }
 
-    total
+        if !visited.insert(*city_id) {
+            return Err(ValidationError::DuplicateCity(*city_id));
+        }
+    }
+
+    match cities.iter().find(|city| !visited.contains(&city.id)) {
+        Some(city) => Err(ValidationError::MissingCity(city.id)),
+        None => Ok(()),
+    }
 }
 
 pub fn city_table_from_vec(cities: &[kdtree::KDPoint]) -> CityTable {
@@ -132,9 +771,13 @@ pub fn city_table_from_vec(cities: &[kdtree::KDPoint]) -> CityTable {
 
 pub struct Solution {
     pub total: f32,
+    pub length: f64, // the total in double precision, e.g for the gaps of the long tours
+    pub target_reached: bool, // the tour is at or below the target cost of the options
+    open_tour: bool, // the length has no closing edge
     route: Vec<usize>,
     cities: Vec<KDPoint>,
     cities_idx: HashMap<usize, usize>, // it maps city.id to internal vector_id
+    history: history::History,
 }
 
 impl Solution {
@@ -144,9 +787,13 @@ impl Solution {
 
         let mut solution = Solution {
             total: 0.0,
+            length: 0.0,
+            target_reached: false,
+            open_tour: false,
             route: route.to_vec(),
             cities: cities.to_vec(),
             cities_idx: idx,
+            history: history::History::new(),
         };
 
         solution.update_total();
@@ -175,8 +822,148 @@ impl Solution {
     }
 
     pub fn update_total(&mut self) {
-        self.total = total_distance(self.cities(), self.route());
+        self.length = if self.open_tour {
+            path_length(self.cities(), self.route())
+        } else {
+            tour_length(self.cities(), self.route())
+        };
+        self.total = self.length as f32;
+    }
+
+    /// measures the tour without its closing edge, if the tour is open
+    pub fn with_open_tour(mut self, open_tour: bool) -> Self {
+        self.open_tour = open_tour;
+        self.update_total();
+
+        self
     }
+
+    pub fn is_open_tour(&self) -> bool {
+        self.open_tour
+    }
+
+    /// rotates the tour to start at the start city of the options; the open tour and the tour
+    /// with the precedences start where the solver started them, the rotation would change them.
+    /// With the end city, the tour of `SolverOptions::to_tour_options` is cut into the path from
+    /// the start city to the end city
+    pub fn with_start_city(mut self, options: &SolverOptions) -> Result<Self, Error> {
+        let city_id = match options.start_city {
+            Some(city_id) => city_id,
+            None => return Ok(self),
+        };
+
+        let offset = self
+            .route
+            .iter()
+            .position(|id| *id == city_id)
+            .ok_or_else(|| Error::InvalidInput(format!("unknown start city {}", city_id)))?;
+        if let Some(end_city) = options.end_city {
+            self.route.rotate_left(offset);
+            // the end city is next to the start city on the tour, the path goes the other way
+            if self.route.get(1) == Some(&end_city) {
+                self.route[1..].reverse();
+            }
+            if self.route.last() != Some(&end_city) {
+                return Err(Error::Runtime(format!(
+                    "the tour doesn't connect the start city {} to the end city {}",
+                    city_id, end_city
+                )));
+            }
+
+            return Ok(self.with_open_tour(true).with_target(options));
+        }
+        if !self.open_tour && options.precedences.is_empty() {
+            self.route.rotate_left(offset);
+        }
+
+        Ok(self)
+    }
+
+    /// records if the tour reached the target cost of the options
+    pub fn with_target(mut self, options: &SolverOptions) -> Self {
+        self.target_reached = options.is_target_reached(self.total);
+
+        self
+    }
+
+    /// keeps the new best costs the solver found on the way to the tour
+    pub fn with_history(mut self, history: history::History) -> Self {
+        self.history = history;
+
+        self
+    }
+
+    pub fn history(&self) -> &history::History {
+        &self.history
+    }
+
+    /// checks that the route visits every city of the solution exactly once
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        check_route(self.cities(), self.route())
+    }
+
+    /// turns the route into the canonical form of the tour, see `Route::canonicalize`,
+    /// so the same tours have equal routes
+    pub fn canonicalize(&mut self) {
+        route::canonicalize(&mut self.route);
+    }
+
+    /// how much longer the tour is than the cost, in percents of the cost, e.g of the optimum;
+    /// None for the zero cost, there is no gap to it
+    pub fn gap_to(&self, cost: f64) -> Option<f32> {
+        if cost == 0.0 {
+            return None;
+        }
+
+        Some(((self.length - cost) / cost * 100.0) as f32)
+    }
+
+    /// compares the edges and the lengths of the tours, the direction of the edges doesn't matter;
+    /// the open tours have no closing edge
+    pub fn compare(&self, other: &Solution) -> TourDiff {
+        let edges = tour_edges(self.route(), self.open_tour);
+        let other_edges = tour_edges(other.route(), other.open_tour);
+
+        TourDiff {
+            shared: edges.intersection(&other_edges).cloned().collect(),
+            only_self: edges.difference(&other_edges).cloned().collect(),
+            only_other: other_edges.difference(&edges).cloned().collect(),
+            cost_delta: self.length - other.length,
+        }
+    }
+}
+
+/// the difference of two tours, the edges are given as (smaller city id, larger city id)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TourDiff {
+    pub shared: Vec<(usize, usize)>,     // the edges of both tours
+    pub only_self: Vec<(usize, usize)>,  // the edges the other tour doesn't have
+    pub only_other: Vec<(usize, usize)>, // the edges of the other tour only
+    pub cost_delta: f64,                 // the length of the tour minus the length of the other
+}
+
+impl TourDiff {
+    /// the tours visit the cities in the same cyclic order, in either direction
+    pub fn is_same_tour(&self) -> bool {
+        self.only_self.is_empty() && self.only_other.is_empty()
+    }
+}
+
+// the sorted undirected edges of the tour, the open tour has no edge from its last city back
+// to the first one
+fn tour_edges(route: &[usize], open_tour: bool) -> BTreeSet<(usize, usize)> {
+    let n_edges = if open_tour {
+        route.len().saturating_sub(1)
+    } else {
+        route.len()
+    };
+
+    (0..n_edges)
+        .map(|i| {
+            let (a, b) = (route[i], route[(i + 1) % route.len()]);
+            (a.min(b), a.max(b))
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone)]
@@ -207,13 +994,18 @@ impl NearestResult {
             return;
         }
 
+        // the kdtree search may reach the same point more than once
+        if self.results.iter().any(|r| r.point.id == pt.id) {
+            return;
+        }
+
         if new_distance < self.closest_distance() {
             self.distance = new_distance;
             self.point = pt.clone();
         }
 
         // we only keep the best results
-        if new_distance < self.farthest_distance() {
+        if !self.is_full() || new_distance < self.farthest_distance() {
             // if stack is full, then remove the weakest result
             if self.results.len() >= self.n {
                 self.results.pop();
@@ -233,6 +1025,11 @@ impl NearestResult {
         self.distance
     }
 
+    /// it has found `n` items, so only closer items can get in
+    pub fn is_full(&self) -> bool {
+        self.results.len() >= self.n
+    }
+
     pub fn farthest_distance(&self) -> f32 {
         self.results.last().map(|x| x.distance).unwrap_or(f32::MAX)
     }
@@ -281,4 +1078,318 @@ mod tests {
 
         assert_approx(4.0, total_distance(&cities, &route));
     }
+
+    #[test]
+    fn test_tour_length_of_long_tour_keeps_precision() {
+        // 100k short edges there and one long edge back
+        let rows: Vec<Vec<f64>> = (0..100_000).map(|i| vec![i as f64 * 0.1, 0.0]).collect();
+        let cities = kdtree::build_points(&rows);
+        let route: Vec<usize> = cities.iter().map(|c| c.id).collect();
+
+        let expected = 2.0 * cities[cities.len() - 1].coords()[0];
+        assert!((tour_length(&cities, &route) - expected).abs() < 1e-6);
+
+        let tour = Solution::new(&route, &cities);
+        assert_eq!(expected as f32, tour.total);
+    }
+
+    #[test]
+    fn test_solution_gap_to() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+        let tour = Solution::new(&[0, 1, 2, 3], &cities);
+
+        assert!((tour.gap_to(3.2).unwrap() - 25.0).abs() < 1e-4);
+        assert_eq!(Some(0.0), tour.gap_to(4.0));
+        assert_eq!(None, tour.gap_to(0.0));
+    }
+
+    #[test]
+    fn test_solution_compare() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+        let square = Solution::new(&[0, 1, 2, 3], &cities);
+        let crossing = Solution::new(&[0, 2, 1, 3], &cities);
+
+        let diff = crossing.compare(&square);
+        assert_eq!(vec![(0, 3), (1, 2)], diff.shared);
+        assert_eq!(vec![(0, 2), (1, 3)], diff.only_self);
+        assert_eq!(vec![(0, 1), (2, 3)], diff.only_other);
+        assert!((diff.cost_delta - (2.0 * 2f64.sqrt() - 2.0)).abs() < 1e-6);
+        assert!(!diff.is_same_tour());
+
+        // the rotated and reversed tour is the same tour
+        let reversed = Solution::new(&[2, 1, 0, 3], &cities);
+        let diff = reversed.compare(&square);
+        assert!(diff.is_same_tour());
+        assert_eq!(4, diff.shared.len());
+        assert_eq!(0.0, diff.cost_delta);
+
+        // the open tour has no closing edge, the open path in the other direction is the same
+        let open = Solution::new(&[0, 1, 2, 3], &cities).with_open_tour(true);
+        let diff = open.compare(&square);
+        assert_eq!(vec![(0, 1), (1, 2), (2, 3)], diff.shared);
+        assert!(diff.only_self.is_empty());
+        assert_eq!(vec![(0, 3)], diff.only_other);
+
+        let backwards = Solution::new(&[3, 2, 1, 0], &cities).with_open_tour(true);
+        assert!(backwards.compare(&open).is_same_tour());
+    }
+
+    #[test]
+    fn test_solution_canonicalize() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+        let mut tour = Solution::new(&[2, 3, 0, 1], &cities);
+        let mut reversed = Solution::new(&[3, 2, 1, 0], &cities);
+
+        tour.canonicalize();
+        reversed.canonicalize();
+        assert_eq!(&[0, 1, 2, 3], tour.route());
+        assert_eq!(tour.route(), reversed.route());
+        assert_eq!(4.0, tour.total);
+    }
+
+    #[test]
+    fn test_solution_with_start_city() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]]);
+        let mut options = SolverOptions::default();
+        options.start_city = Some(1);
+
+        let tour = Solution::new(&[2, 0, 1], &cities)
+            .with_start_city(&options)
+            .unwrap();
+        assert_eq!(&[1, 2, 0], tour.route());
+        assert_eq!(&[1, 2, 0], options.start_route(&cities).unwrap().route());
+
+        let open_tour = Solution::new(&[2, 0, 1], &cities)
+            .with_open_tour(true)
+            .with_start_city(&options)
+            .unwrap();
+        assert_eq!(&[2, 0, 1], open_tour.route());
+
+        options.start_city = Some(7);
+        assert!(tour.with_start_city(&options).is_err());
+    }
+
+    #[test]
+    fn test_solution_validate() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]]);
+
+        assert_eq!(Ok(()), Solution::new(&[2, 0, 1], &cities).validate());
+        assert_eq!(
+            Err(ValidationError::DuplicateCity(0)),
+            Solution::new(&[0, 1, 0], &cities).validate()
+        );
+        assert_eq!(
+            Err(ValidationError::MissingCity(2)),
+            Solution::new(&[1, 0], &cities).validate()
+        );
+
+        // the tour through the unknown city has no length
+        let solution = Solution::new(&[0, 5, 1, 2], &cities);
+        assert_eq!(Err(ValidationError::UnknownCity(5)), solution.validate());
+        assert!(solution.total.is_infinite());
+        assert!(path_length(&cities, &[0, 1, 7]).is_infinite());
+
+        let err = validate_route(&cities, &[0, 1, 1]).err().unwrap();
+        assert_eq!(
+            "invalid input: the route visits the city 1 twice",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_every_builtin_solver_returns_valid_tour() {
+        let rows: Vec<Vec<f64>> = (0..9)
+            .map(|i| vec![(i * 7 % 9) as f64, (i * i % 5) as f64])
+            .collect();
+        let cities = kdtree::build_points(&rows);
+        let mut options = SolverOptions::default();
+        options.epochs = 200;
+        options.seed = Some(7);
+
+        for solver in registry::builtin() {
+            let tour = solver
+                .solve(&cities, &options, progress::silent_publisher())
+                .unwrap();
+
+            assert_eq!(Ok(()), tour.validate(), "{}", solver.metadata().name);
+        }
+    }
+
+    #[test]
+    fn test_deadline_without_budget_is_never_over() {
+        assert!(!Deadline::new(None).is_over());
+        assert!(Deadline::new(Some(0.0)).is_over());
+        assert!(!Deadline::new(Some(60.0)).is_over());
+    }
+
+    #[test]
+    fn test_cancelled_deadline_is_over() {
+        let options = SolverOptions::default();
+        let deadline = options.deadline();
+        assert!(!deadline.is_over());
+
+        // the clone of the options shares the flag, like the restart runs do
+        options.clone().cancel.cancel();
+        assert!(deadline.is_over());
+    }
+
+    #[test]
+    fn test_every_solver_variant_has_implementation() {
+        for name in Solvers::variants().iter() {
+            let solver = Solvers::from_str(name).unwrap().solver().unwrap();
+
+            assert!(
+                solver.metadata().has_name(name),
+                "{} has wrong metadata",
+                name
+            );
+        }
+
+        assert!(Solvers::Unspecified.solver().is_none());
+    }
+
+    #[test]
+    fn test_validate_cities_rejects_invalid_input() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![0.0, 1.0]]);
+        assert!(validate_cities(&cities, 2).is_ok());
+        assert!(validate_cities(&cities, 3).is_err());
+
+        let duplicated = vec![cities[0].clone(), cities[0].clone()];
+        assert!(validate_cities(&duplicated, 2).is_err());
+
+        let mixed_dims = vec![cities[0].clone(), KDPoint::new_with_id(1, &[1.0, 1.0, 1.0])];
+        assert!(validate_cities(&mixed_dims, 2).is_err());
+
+        let not_finite = vec![cities[0].clone(), KDPoint::new_with_id(1, &[f64::NAN, 1.0])];
+        assert!(validate_cities(&not_finite, 2).is_err());
+    }
+
+    #[test]
+    fn test_start_route_follows_initial_route() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]]);
+        let mut options = SolverOptions::default();
+        assert_eq!(&[0, 1, 2], options.start_route(&cities).unwrap().route());
+
+        options.initial_route = Some(vec![2, 0, 1]);
+        assert_eq!(&[2, 0, 1], options.start_route(&cities).unwrap().route());
+
+        options.initial_route = Some(vec![2, 0, 0]);
+        assert!(options.start_route(&cities).is_err());
+        options.initial_route = Some(vec![2, 0]);
+        assert!(options.start_route(&cities).is_err());
+        options.initial_route = Some(vec![2, 0, 7]);
+        assert!(options.start_route(&cities).is_err());
+
+        options.initial_route = None;
+        options.start_city = Some(1);
+        options.end_city = Some(2);
+        assert_eq!(&[1, 0, 2], options.start_route(&cities).unwrap().route());
+    }
+
+    #[test]
+    fn test_validate_options_of_the_solver() {
+        for solver in Solvers::all().iter().filter_map(|s| s.solver()) {
+            assert!(SolverOptions::default()
+                .validate(&solver.metadata())
+                .is_ok());
+        }
+
+        let annealing = simulated_annealing::SimulatedAnnealingSolver.metadata();
+        let mut options = SolverOptions::default();
+        options.cooling_rate = 0.0;
+        let err = options.validate(&annealing).err().unwrap();
+        assert!(err.to_string().contains("cooling_rate must be in (0, 1)"));
+        options.cooling_rate = f32::NAN;
+        assert!(options.validate(&annealing).is_err());
+
+        let mut timed = SolverOptions::default();
+        timed.cooling_schedule = simulated_annealing::CoolingSchedule::Timed;
+        let err = timed.validate(&annealing).err().unwrap();
+        assert!(err.to_string().contains("needs max_seconds"));
+        timed.max_seconds = Some(10.0);
+        assert!(timed.validate(&annealing).is_ok());
+
+        // the genetic algorithm doesn't cool down
+        let genetic = genetic_algorithm::GeneticAlgorithmSolver.metadata();
+        assert!(options.validate(&genetic).is_ok());
+
+        options.n_elite = options.population_size;
+        let err = options.validate(&genetic).err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("n_elite must be < population size"));
+    }
+
+    #[test]
+    fn test_algorithm_options_set_the_options_of_the_solver() {
+        let tabu = tabu_search::TabuOptions { aspiration: false };
+        let options = SolverOptions::default().with_algorithm(&AlgorithmOptions::TabuSearch(tabu));
+        assert!(!options.aspiration);
+        assert_eq!(SolverOptions::default().cooling_rate, options.cooling_rate);
+
+        let algorithm = AlgorithmOptions::of(&Solvers::TabuSearch, &options).unwrap();
+        assert_eq!(Solvers::TabuSearch, algorithm.solver());
+        assert!(matches!(algorithm, AlgorithmOptions::TabuSearch(t) if !t.aspiration));
+
+        assert!(AlgorithmOptions::of(&Solvers::BellmanKarp, &options).is_none());
+    }
+
+    #[test]
+    fn test_solvers_record_the_history_of_the_best_costs() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+            vec![1.0, 0.0],
+            vec![0.5, 0.0],
+        ]);
+        let mut options = SolverOptions::default();
+        options.epochs = 200;
+        options.seed = Some(42);
+
+        for solver in Solvers::all().iter().filter_map(|s| s.solver()) {
+            let tour = solver
+                .solve(&cities, &options, progress::silent_publisher())
+                .unwrap();
+            let name = solver.metadata().name;
+
+            assert_eq!(Some(tour.total), tour.history().best_cost(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_solver_sends_progress_into_publisher() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]]);
+        let messages = Arc::new(std::sync::Mutex::new(vec![]));
+        let collected = Arc::clone(&messages);
+        let publisher: PublisherFn = Arc::new(move |msg| collected.lock().unwrap().push(msg));
+
+        let tour = Solvers::NearestNeighbor.solver().unwrap().solve(
+            &cities,
+            &SolverOptions::default(),
+            publisher,
+        );
+
+        assert_eq!(3, tour.unwrap().route().len());
+        let messages = messages.lock().unwrap();
+        assert!(matches!(
+            messages.last(),
+            Some(progress::ProgressMessage::Done)
+        ));
+    }
 }
@@ -0,0 +1,267 @@
+/// Minimum spanning tree, double-tree construction
+///
+/// It connects the cities with the minimum spanning tree, walks around the tree in the preorder
+/// and skips the cities it has already visited. The walk goes over every edge of the tree twice
+/// and the shortcuts are not longer by the triangle inequality, so the tour is at most twice as
+/// long as the tree, which is shorter than the optimal tour: the tour is at most 2x the optimum,
+/// usually about 30-40% longer.
+///
+/// The tree is built with the Prim algorithm: every city of the tree keeps its nearest city
+/// outside of the tree, found with the KD-tree, in a heap, so it takes about O(n log n). The tree
+/// is also the first step of Christofides.
+///
+/// source: Rosenkrantz, Stearns & Lewis, "An analysis of several heuristics for the traveling
+/// salesman problem", https://doi.org/10.1137/0206041
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::history::History;
+use super::kdtree::{KDPoint, KDTree};
+use super::progress::{self, ProgressMessage, PublisherFn};
+use super::registry::Category;
+use super::route::Route;
+use super::{
+    total_distance, validate_cities, Error, Solution, SolverInfo, SolverOptions, TspSolver,
+};
+
+pub struct DoubleTreeSolver;
+
+impl TspSolver for DoubleTreeSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "double_tree",
+            &["mst"],
+            "walks around the minimum spanning tree, at most 2x the optimal tour",
+            false,
+        )
+        .with_category(Category::Constructive)
+        .with_complexity("O(n log n)")
+        .with_options(&["start_city"])
+    }
+
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 1)?;
+
+        // the tree grows from the first city and the walk starts at its root
+        let mut ordered = cities.to_vec();
+        ordered.rotate_left(options.start_pos(cities)?);
+
+        let mut history = History::new();
+        let tree = minimum_spanning_tree(&ordered)?;
+        let path: Vec<usize> = preorder_walk(ordered.len(), &tree)
+            .into_iter()
+            .map(|pos| ordered[pos].id)
+            .collect();
+
+        if options.verbose {
+            let tree_length: f32 = tree
+                .iter()
+                .map(|(a, b)| cities[*a].distance(&cities[*b]))
+                .sum();
+            println!("Double tree: the spanning tree is {:.2} long", tree_length);
+        }
+
+        progress(ProgressMessage::PathUpdate(
+            Route::new(&path),
+            total_distance(cities, &path),
+        ));
+        progress(ProgressMessage::Done);
+
+        let tour = Solution::new(&path, cities).with_target(options);
+        history.record(0, tour.total);
+        Ok(tour.with_history(history))
+    }
+}
+
+/// solves the problem and sends the progress into the global progress channel
+pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Result<Solution, Error> {
+    DoubleTreeSolver.solve(cities, options, progress::default_publisher())
+}
+
+// the nearest city outside of the tree of the city in the tree
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    distance: f32,
+    from: usize,
+    to: usize,
+}
+
+// the heap pops the shortest edge first
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then(other.to.cmp(&self.to))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+/// the edges of the minimum spanning tree by the positions of the cities in the `cities`,
+/// in the order they are added to the tree growing from the first city
+pub fn minimum_spanning_tree(cities: &[KDPoint]) -> Result<Vec<(usize, usize)>, Error> {
+    let mut edges = Vec::with_capacity(cities.len().saturating_sub(1));
+    if cities.is_empty() {
+        return Ok(edges);
+    }
+
+    let mut search_tree = KDTree::from_points(cities)?;
+    let index_of: HashMap<usize, usize> =
+        cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+    let push_nearest = |search_tree: &KDTree,
+                        heap: &mut BinaryHeap<Candidate>,
+                        from: usize|
+     -> Result<(), Error> {
+        if let Some(item) = search_tree.nearest_k(&cities[from], 1)?.first() {
+            heap.push(Candidate {
+                distance: item.distance,
+                from,
+                to: index_of[&item.point.id],
+            });
+        }
+
+        Ok(())
+    };
+
+    let mut in_tree = vec![false; cities.len()];
+    let mut heap = BinaryHeap::new();
+    in_tree[0] = true;
+    search_tree.mark_visited(&cities[0]);
+    push_nearest(&search_tree, &mut heap, 0)?;
+
+    while let Some(Candidate { from, to, .. }) = heap.pop() {
+        if !in_tree[to] {
+            in_tree[to] = true;
+            search_tree.mark_visited(&cities[to]);
+            edges.push((from, to));
+            push_nearest(&search_tree, &mut heap, to)?;
+        }
+
+        // the nearest city of `from` is in the tree now or the edge was taken, look for the next one
+        push_nearest(&search_tree, &mut heap, from)?;
+    }
+
+    Ok(edges)
+}
+
+/// the positions of the cities in the preorder of the tree from the first city,
+/// the children are visited in the order of the edges
+pub fn preorder_walk(n_cities: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+    let mut children = vec![vec![]; n_cities];
+    for (parent, child) in edges.iter() {
+        children[*parent].push(*child);
+    }
+
+    let mut walk = Vec::with_capacity(n_cities);
+    let mut stack = vec![0];
+    while let Some(pos) = stack.pop() {
+        walk.push(pos);
+        stack.extend(children[pos].iter().rev());
+    }
+
+    walk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::{generator, kdtree};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // O(n^2) Prim over all the pairs
+    fn brute_force_tree_length(cities: &[KDPoint]) -> f32 {
+        let mut in_tree = vec![false; cities.len()];
+        let mut distance_to_tree = vec![f32::INFINITY; cities.len()];
+        distance_to_tree[0] = 0.0;
+        let mut length = 0.0;
+
+        for _ in 0..cities.len() {
+            let next = (0..cities.len())
+                .filter(|i| !in_tree[*i])
+                .min_by(|a, b| distance_to_tree[*a].total_cmp(&distance_to_tree[*b]))
+                .unwrap();
+            in_tree[next] = true;
+            length += distance_to_tree[next];
+
+            for other in 0..cities.len() {
+                let distance = cities[next].distance(&cities[other]);
+                if !in_tree[other] && distance < distance_to_tree[other] {
+                    distance_to_tree[other] = distance;
+                }
+            }
+        }
+
+        length
+    }
+
+    #[test]
+    fn test_minimum_spanning_tree_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let cities = generator::uniform(300, &generator::BoundingBox::square(100.0), &mut rng);
+
+        let tree = minimum_spanning_tree(&cities).unwrap();
+        assert_eq!(cities.len() - 1, tree.len());
+
+        let length: f32 = tree
+            .iter()
+            .map(|(a, b)| cities[*a].distance(&cities[*b]))
+            .sum();
+        assert!((brute_force_tree_length(&cities) - length).abs() < 0.01);
+
+        // the tour is at most twice as long as the tree
+        let tour = solve(&cities, &SolverOptions::default()).unwrap();
+        assert_eq!(Ok(()), tour.validate());
+        assert!(tour.total <= 2.0 * length);
+    }
+
+    #[test]
+    fn test_preorder_walk() {
+        // 0 has the children 1 and 3, 1 has the child 2
+        let walk = preorder_walk(5, &[(0, 1), (1, 2), (0, 3), (3, 4)]);
+        assert_eq!(vec![0, 1, 2, 3, 4], walk);
+    }
+
+    #[test]
+    fn test_solve_with_square_and_single_city() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+
+        let tour = solve(&cities, &SolverOptions::default()).unwrap();
+        assert_eq!(4.0, tour.total);
+
+        let mut options = SolverOptions::default();
+        options.start_city = Some(3);
+        let tour = solve(&cities, &options).unwrap();
+        assert_eq!(3, tour.route()[0]);
+        assert_eq!(4.0, tour.total);
+
+        let single = kdtree::build_points(&[vec![1.0, 1.0]]);
+        assert_eq!(
+            &[0],
+            solve(&single, &SolverOptions::default()).unwrap().route()
+        );
+    }
+}
@@ -1,52 +1,418 @@
+/// Nearest neighbor construction
+///
+/// It starts from a city and keeps moving to the nearest unvisited city until every city is
+/// visited; the KD-tree marks the visited cities, so every step is a single nearest search.
+/// The tour is usually about 25% longer than the optimal one.
+///
+/// The tour depends on the start city, `--start_city` picks it and `--all_starts` builds the
+/// tour from every city and keeps the shortest one, which takes n times longer.
+///
+/// The fixed edges of the problem are traveled as they come: the tour reaches the chain of the
+/// fixed edges only at its ends and follows it to the other end.
+///
+/// The precedences are kept by repairing the order of the tour, the city waits until the cities,
+/// which must be visited before it, are visited.
+///
+/// The open tour, `--open_tour`, ends at the last city visited, it doesn't return to the start.
+/// The path to `--end_city` is the tour with the fixed edge from the end city to the start city,
+/// the tour travels it first and the edge is cut off afterwards.
+///
+/// `--spatial_index grid` searches the nearest cities in the uniform grid instead of the KD-tree,
+/// which is faster to build for the millions of cities.
 use std::collections::HashMap;
 
-use super::kdtree::{self, KDPoint};
-use super::progress::{send_progress, ProgressMessage};
+use super::fixed_edges::FixedEdges;
+use super::grid::{Grid, SpatialIndex};
+use super::history::History;
+use super::kdtree::{KDPoint, KDTree};
+use super::precedence::Precedences;
+use super::progress::{self, ProgressMessage, PublisherFn};
+use super::registry::Category;
 use super::route::Route;
-use super::{Solution, SolverOptions};
+use super::{validate_cities, Error, Solution, SolverInfo, SolverOptions, TspSolver};
 
-pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Solution {
-    let search_tree = kdtree::from_cities(&cities);
-    let n_nearest = options.n_nearest;
+/// where the nearest neighbor tour starts and how it searches the cities
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearestNeighborOptions {
+    pub start_city: Option<usize>, // city id the tour starts from, the first by default
+    pub all_starts: bool,          // tries every start city and keeps the shortest tour
+    pub spatial_index: SpatialIndex, // searches the cities with it
+}
 
-    let cities_table: HashMap<usize, KDPoint> = cities.iter().map(|c| (c.id, c.clone())).collect();
-    let mut path: Vec<usize> = cities.iter().map(|c| c.id).collect();
+impl NearestNeighborOptions {
+    /// copies the options into the options the solver runs with
+    pub fn apply(&self, options: &mut SolverOptions) {
+        options.start_city = self.start_city;
+        options.all_starts = self.all_starts;
+        options.spatial_index = self.spatial_index;
+    }
+}
 
-    send_progress(ProgressMessage::PathUpdate(Route::new(&path), 0.0));
-    // run optimization round
-    for i in 0..(path.len() - 1) {
-        let id1 = path[i];
-        let city1 = cities_table[&id1].clone();
-        send_progress(ProgressMessage::CityChange(id1));
+impl Default for NearestNeighborOptions {
+    fn default() -> Self {
+        NearestNeighborOptions::from(&SolverOptions::default())
+    }
+}
 
-        let frontier = search_tree.nearest(&city1, n_nearest);
+impl From<&SolverOptions> for NearestNeighborOptions {
+    fn from(options: &SolverOptions) -> Self {
+        NearestNeighborOptions {
+            start_city: options.start_city,
+            all_starts: options.all_starts,
+            spatial_index: options.spatial_index,
+        }
+    }
+}
+
+pub struct NearestNeighborSolver;
+
+impl TspSolver for NearestNeighborSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "nearest_neighbor",
+            &["nn"],
+            "greedy tour construction from the nearest unvisited city",
+            false,
+        )
+        .with_category(Category::Constructive)
+        .with_complexity("O(n log n), O(n^2 log n) with all_starts")
+        .with_options(&[
+            "start_city",
+            "end_city",
+            "all_starts",
+            "spatial_index",
+            "max_seconds",
+            "target_cost",
+            "fixed_edges",
+            "open_tour",
+            "precedences",
+        ])
+    }
 
-        let id2 = path[i + 1];
-        let current_distance = city1.distance(&cities_table[&id2]);
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 1)?;
 
-        let search_result = frontier.nearest();
-        if search_result.is_empty() {
-            if options.verbose {
-                println!("No nearest for city: #{:?}", id1);
+        let deadline = options.deadline();
+        let mut search_index: Box<dyn VisitIndex> = match options.spatial_index {
+            SpatialIndex::KdTree => Box::new(KDTree::from_points(cities)?),
+            SpatialIndex::Grid => Box::new(Grid::new(cities)),
+        };
+        let city_pos: HashMap<usize, usize> =
+            cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+        let fixed = FixedEdges::from_options(cities, options)?;
+        let precedences = Precedences::from_options(cities, options)?;
+
+        let first_pos = options.start_pos(cities)?;
+
+        let mut start_positions = vec![first_pos];
+        if options.all_starts {
+            start_positions.extend((0..cities.len()).filter(|pos| *pos != first_pos));
+        }
+
+        let mut best: Option<Solution> = None;
+        let mut history = History::new();
+        // every start is an epoch of the search
+        for (epoch, start_pos) in start_positions.into_iter().enumerate() {
+            // the tour can't start inside the chain, it starts from its end and is rotated;
+            // the open tour can't be rotated, it keeps the start at the end of the chain
+            let start_id = cities[start_pos].id;
+            let first_id = if options.open_tour && !fixed.is_inner(start_id) {
+                start_id
+            } else {
+                fixed.chain_end(start_id)
+            };
+            let mut path = build_tour(
+                search_index.as_mut(),
+                cities,
+                &city_pos,
+                &fixed,
+                city_pos[&first_id],
+                &progress,
+            )?;
+            match path.iter().position(|id| *id == start_id) {
+                Some(offset) if !options.open_tour => path.rotate_left(offset),
+                _ => {}
+            }
+            if !precedences.is_empty() {
+                path = precedences.repair(&path);
+                if let Some((a, b)) = fixed.broken_edge(&path) {
+                    return Err(Error::InvalidInput(format!(
+                        "nearest_neighbor: the order of the precedences breaks the fixed edge {}-{}",
+                        a, b
+                    )));
+                }
             }
+            let tour = Solution::new(&path, cities).with_open_tour(options.open_tour);
 
-            continue;
+            if best.as_ref().is_none_or(|best| tour.total < best.total) {
+                if options.verbose {
+                    println!(
+                        "NN: tour from the city {} is {:.2}",
+                        cities[start_pos].id, tour.total
+                    );
+                }
+
+                progress(ProgressMessage::PathUpdate(Route::new(&path), tour.total));
+                history.record(epoch, tour.total);
+                best = Some(tour);
+            }
+
+            let best_total = best.as_ref().map_or(f32::MAX, |best| best.total);
+            if deadline.is_over() || options.is_target_reached(best_total) {
+                break;
+            }
         }
 
-        let closest_item = search_result.first().unwrap();
-        let next_distance = closest_item.distance;
+        progress(ProgressMessage::Done);
+        // the first start is always tried
+        Ok(best.unwrap().with_target(options).with_history(history))
+    }
+}
+
+/// solves the problem and sends the progress into the global progress channel
+pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Result<Solution, Error> {
+    NearestNeighborSolver.solve(cities, options, progress::default_publisher())
+}
+
+// the spatial index, which skips the visited cities in the nearest searches
+trait VisitIndex {
+    fn mark_visited(&mut self, cities: &[KDPoint], pos: usize);
+    // the city id of the nearest unvisited city
+    fn nearest_unvisited(&self, cities: &[KDPoint], pos: usize) -> Result<usize, Error>;
+    fn reset_visited(&mut self);
+}
+
+impl VisitIndex for KDTree {
+    fn mark_visited(&mut self, cities: &[KDPoint], pos: usize) {
+        KDTree::mark_visited(self, &cities[pos]);
+    }
+
+    fn nearest_unvisited(&self, cities: &[KDPoint], pos: usize) -> Result<usize, Error> {
+        Ok(self.nearest(&cities[pos], 1)?.point.id)
+    }
+
+    fn reset_visited(&mut self) {
+        KDTree::reset_visited(self)
+    }
+}
+
+impl VisitIndex for Grid {
+    fn mark_visited(&mut self, _cities: &[KDPoint], pos: usize) {
+        Grid::mark_visited(self, pos);
+    }
+
+    fn nearest_unvisited(&self, cities: &[KDPoint], pos: usize) -> Result<usize, Error> {
+        self.nearest(pos)
+            .map(|other| cities[other].id)
+            .ok_or_else(|| Error::InvalidInput("nearest_neighbor: no unvisited city".to_string()))
+    }
 
-        if next_distance < current_distance {
-            let nearest_city_id = closest_item.point.id;
-            if let Some(nearest_pos) = path.iter().position(|&x| x == nearest_city_id) {
-                path.swap(i + 1, nearest_pos);
+    fn reset_visited(&mut self) {
+        Grid::reset_visited(self)
+    }
+}
 
-                send_progress(ProgressMessage::PathUpdate(Route::new(&path), 0.0));
+// the city ids in the order of the visits, it leaves all the cities of the index unvisited;
+// the inner cities of the fixed chains are hidden from the index, they are reached along the chain
+fn build_tour(
+    search_index: &mut dyn VisitIndex,
+    cities: &[KDPoint],
+    city_pos: &HashMap<usize, usize>,
+    fixed: &FixedEdges,
+    start_pos: usize,
+    progress: &PublisherFn,
+) -> Result<Vec<usize>, Error> {
+    let mut path = Vec::with_capacity(cities.len());
+    let mut is_visited = vec![false; cities.len()];
+    if !fixed.is_empty() {
+        for (pos, city) in cities.iter().enumerate() {
+            if fixed.is_inner(city.id) {
+                search_index.mark_visited(cities, pos);
             }
         }
     }
 
-    send_progress(ProgressMessage::Done);
-    let tour = Solution::new(&path, cities);
-    tour
+    let mut current = start_pos;
+    loop {
+        search_index.mark_visited(cities, current);
+        is_visited[current] = true;
+        path.push(cities[current].id);
+        if path.len() == cities.len() {
+            break;
+        }
+
+        // the chain of the fixed edges is followed to its end
+        let unvisited_partner = fixed
+            .partners(cities[current].id)
+            .iter()
+            .find(|id| !is_visited[city_pos[*id]]);
+        let next_id = match unvisited_partner {
+            Some(partner_id) => *partner_id,
+            None => search_index.nearest_unvisited(cities, current)?,
+        };
+
+        current = city_pos[&next_id];
+        progress(ProgressMessage::CityChange(next_id));
+    }
+
+    search_index.reset_visited();
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::{generator, kdtree};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn cities() -> Vec<KDPoint> {
+        // the cities on the line, only the start in the middle makes a detour
+        kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![4.0, 0.0],
+            vec![1.0, 0.0],
+            vec![10.0, 0.0],
+            vec![2.6, 0.0],
+        ])
+    }
+
+    fn solve_with(options: &SolverOptions) -> Result<Solution, Error> {
+        NearestNeighborSolver.solve(&cities(), options, progress::silent_publisher())
+    }
+
+    #[test]
+    fn test_solve_visits_nearest_unvisited_city() {
+        let mut options = SolverOptions::default();
+        options.start_city = Some(4);
+
+        let tour = solve_with(&options).unwrap();
+        assert_eq!(&[4, 1, 2, 0, 3], tour.route());
+    }
+
+    #[test]
+    fn test_solve_open_tour_ends_at_last_city() {
+        let mut options = SolverOptions::default();
+        options.start_city = Some(4);
+        options.open_tour = true;
+        options.fixed_edges = vec![(4, 3)];
+
+        // the start at the end of the chain is kept, the open tour can't be rotated
+        let tour = solve_with(&options).unwrap();
+        assert_eq!(&[4, 3, 1, 2, 0], tour.route());
+        assert!((tour.total - 17.4).abs() < 0.001, "{}", tour.total);
+    }
+
+    #[test]
+    fn test_solve_repairs_order_of_precedences() {
+        let mut options = SolverOptions::default();
+        options.start_city = Some(4);
+        options.precedences = vec![(3, 1)];
+
+        let tour = solve_with(&options).unwrap();
+        assert_eq!(&[4, 2, 0, 3, 1], tour.route());
+
+        options.precedences = vec![(3, 1), (1, 3)];
+        assert!(solve_with(&options).is_err());
+    }
+
+    #[test]
+    fn test_solve_follows_fixed_edges() {
+        let mut options = SolverOptions::default();
+        options.start_city = Some(4);
+        options.fixed_edges = vec![(2, 3)];
+        let tour = solve_with(&options).unwrap();
+        assert_eq!(&[4, 1, 2, 3, 0], tour.route());
+
+        // the tour starts inside the chain 2-3-1
+        options.start_city = Some(3);
+        options.fixed_edges = vec![(2, 3), (3, 1)];
+        let tour = solve_with(&options).unwrap();
+        assert_eq!(3, tour.route()[0]);
+        let fixed = FixedEdges::from_options(&cities(), &options).unwrap();
+        assert_eq!(None, fixed.broken_edge(tour.route()));
+    }
+
+    #[test]
+    fn test_solve_with_all_starts_keeps_shortest_tour() {
+        let mut options = SolverOptions::default();
+        options.start_city = Some(4);
+        let from_middle = solve_with(&options).unwrap();
+
+        options.all_starts = true;
+        let best = solve_with(&options).unwrap();
+
+        assert!(best.total < from_middle.total);
+        assert!((best.total - 20.0).abs() < 0.001, "{}", best.total);
+    }
+
+    #[test]
+    fn test_solve_with_grid_matches_kdtree() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let cities = generator::uniform(500, &generator::BoundingBox::square(1000.0), &mut rng);
+        let mut options = SolverOptions::default();
+        options.start_city = Some(42);
+
+        let with_tree = NearestNeighborSolver
+            .solve(&cities, &options, progress::silent_publisher())
+            .unwrap();
+        options.spatial_index = SpatialIndex::Grid;
+        let with_grid = NearestNeighborSolver
+            .solve(&cities, &options, progress::silent_publisher())
+            .unwrap();
+
+        assert_eq!(with_tree.route(), with_grid.route());
+    }
+
+    #[test]
+    fn test_solve_path_from_start_to_end_city() {
+        let mut options = SolverOptions::default();
+        options.start_city = Some(2);
+        options.end_city = Some(3);
+        assert!(options
+            .check_constraints(&NearestNeighborSolver.metadata())
+            .is_ok());
+
+        let path = solve_with(&options.to_tour_options())
+            .unwrap()
+            .with_start_city(&options)
+            .unwrap();
+
+        assert!(path.is_open_tour());
+        assert_eq!(&[2, 0, 4, 1, 3], path.route());
+        assert!((path.total - 11.0).abs() < 0.001, "{}", path.total);
+
+        options.start_city = None;
+        assert!(options
+            .check_constraints(&NearestNeighborSolver.metadata())
+            .is_err());
+    }
+
+    #[test]
+    fn test_solve_refuses_unknown_start_city() {
+        let mut options = SolverOptions::default();
+        options.start_city = Some(7);
+
+        let err = solve_with(&options).err().unwrap();
+        assert!(err.to_string().contains("unknown start city 7"));
+    }
+
+    #[test]
+    fn test_solve_with_single_city() {
+        let cities = kdtree::build_points(&[vec![1.0, 1.0]]);
+        let tour = NearestNeighborSolver
+            .solve(
+                &cities,
+                &SolverOptions::default(),
+                progress::silent_publisher(),
+            )
+            .unwrap();
+
+        assert_eq!(&[0], tour.route());
+    }
 }
@@ -0,0 +1,268 @@
+/// Nearest neighbor lists
+///
+/// The candidate lists of the local searches: the `k` nearest cities of every city, found with
+/// the kd-tree in O(n k log n) time. The moves, which only connect a city to one of its nearest
+/// cities, find most of the improvements of the full O(n^2) neighborhood.
+///
+/// On the clustered problems the nearest cities are all in the same cluster, so the lists
+/// can be built from the edges of the Delaunay triangulation or from the nearest cities in
+/// each quadrant around the city instead, see `Candidates`. The quadrants, like in LKH, keep
+/// the lists of the stringy problems from having all the cities on one side of the city.
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::delaunay;
+use super::kdtree::{self, KDPoint};
+use super::Error;
+
+/// how the candidate lists of the local searches are built
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Candidates {
+    #[default]
+    Knn, // the k nearest cities
+    Delaunay, // the cities connected by the Delaunay triangulation, ~6 per city, k is not used
+    Quadrant, // the k / 4 nearest cities in each quadrant, at least 1, filled up with the nearest ones
+}
+
+impl Candidates {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["knn", "delaunay", "quadrant"]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Candidates::Knn => "knn",
+            Candidates::Delaunay => "delaunay",
+            Candidates::Quadrant => "quadrant",
+        }
+    }
+}
+
+impl FromStr for Candidates {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "knn" => Ok(Candidates::Knn),
+            "delaunay" => Ok(Candidates::Delaunay),
+            "quadrant" => Ok(Candidates::Quadrant),
+            _ => Err("unknown candidates"),
+        }
+    }
+}
+
+impl fmt::Display for Candidates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// the candidate lists of every city, closest first, by their positions in the `cities`
+pub fn build_candidates(
+    cities: &[KDPoint],
+    candidates: Candidates,
+    k: usize,
+) -> Result<Vec<Vec<usize>>, Error> {
+    match candidates {
+        Candidates::Knn => build_lists(cities, k),
+        Candidates::Delaunay => Ok(delaunay_lists(cities)),
+        Candidates::Quadrant => quadrant_lists(cities, k),
+    }
+}
+
+/// the `k` nearest cities of every city, closest first, by their positions in the `cities`;
+/// the list of the city doesn't include the city itself
+pub fn build_lists(cities: &[KDPoint], k: usize) -> Result<Vec<Vec<usize>>, Error> {
+    let search_tree = kdtree::from_cities(cities)?;
+    let index_of: HashMap<usize, usize> =
+        cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+
+    cities
+        .iter()
+        .map(|city| {
+            Ok(search_tree
+                .nearest_k(city, k)?
+                .iter()
+                .map(|item| index_of[&item.point.id])
+                .collect())
+        })
+        .collect()
+}
+
+fn delaunay_lists(cities: &[KDPoint]) -> Vec<Vec<usize>> {
+    let mut lists = vec![vec![]; cities.len()];
+    for (a, b) in delaunay::edges(cities) {
+        lists[a].push(b);
+        lists[b].push(a);
+    }
+
+    for (i, list) in lists.iter_mut().enumerate() {
+        sort_by_distance(cities, i, list);
+    }
+
+    lists
+}
+
+fn quadrant_lists(cities: &[KDPoint], k: usize) -> Result<Vec<Vec<usize>>, Error> {
+    let search_tree = kdtree::from_cities(cities)?;
+    let index_of: HashMap<usize, usize> =
+        cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+    let per_quadrant = (k / 4).max(1);
+
+    cities
+        .iter()
+        .enumerate()
+        .map(|(i, city)| {
+            let mut list = vec![];
+            for quadrant in 0..4 {
+                for item in search_tree.nearest_k_in_quadrant(city, quadrant, per_quadrant)? {
+                    list.push(index_of[&item.point.id]);
+                }
+            }
+
+            // the empty quadrants, e.g on the convex hull, are made up by the nearest cities
+            for item in search_tree.nearest_k(city, k)?.iter() {
+                let j = index_of[&item.point.id];
+                if list.len() < k && !list.contains(&j) {
+                    list.push(j);
+                }
+            }
+            sort_by_distance(cities, i, &mut list);
+
+            Ok(list)
+        })
+        .collect()
+}
+
+fn sort_by_distance(cities: &[KDPoint], i: usize, list: &mut [usize]) {
+    list.sort_by(|a, b| {
+        cities[i]
+            .distance(&cities[*a])
+            .partial_cmp(&cities[i].distance(&cities[*b]))
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::generator;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_build_lists_matches_all_pairs() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let cities = generator::uniform(60, &generator::BoundingBox::square(100.0), &mut rng);
+
+        let lists = build_lists(&cities, 5).unwrap();
+        assert_eq!(cities.len(), lists.len());
+
+        for (i, list) in lists.iter().enumerate() {
+            let mut expected: Vec<f32> = (0..cities.len())
+                .filter(|j| *j != i)
+                .map(|j| cities[i].distance(&cities[j]))
+                .collect();
+            expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            expected.truncate(5);
+
+            // ties may be ordered differently, so only the distances are compared
+            let distances: Vec<f32> = list
+                .iter()
+                .map(|j| cities[i].distance(&cities[*j]))
+                .collect();
+            assert_eq!(expected, distances);
+        }
+    }
+
+    #[test]
+    fn test_candidates_from_str() {
+        for name in Candidates::variants() {
+            assert_eq!(name, Candidates::from_str(name).unwrap().as_str());
+        }
+        assert!(Candidates::from_str("nearest").is_err());
+    }
+
+    #[test]
+    fn test_delaunay_candidates_connect_clusters() {
+        // two clusters of 5 cities, the nearest cities are all in the own cluster
+        let mut rows = vec![];
+        for offset in [0.0, 100.0].iter() {
+            for i in 0..5 {
+                rows.push(vec![offset + (i % 2) as f64, i as f64]);
+            }
+        }
+        let cities = kdtree::build_points(&rows);
+        let crosses = |lists: &[Vec<usize>]| {
+            lists
+                .iter()
+                .enumerate()
+                .any(|(i, list)| list.iter().any(|j| (i < 5) != (*j < 5)))
+        };
+
+        let knn = build_candidates(&cities, Candidates::Knn, 4).unwrap();
+        assert!(!crosses(&knn));
+
+        let delaunay = build_candidates(&cities, Candidates::Delaunay, 4).unwrap();
+        assert!(crosses(&delaunay));
+        for (i, list) in delaunay.iter().enumerate() {
+            assert!(!list.contains(&i));
+            assert!(list
+                .windows(2)
+                .all(|pair| cities[i].distance(&cities[pair[0]])
+                    <= cities[i].distance(&cities[pair[1]])));
+        }
+    }
+
+    #[test]
+    fn test_quadrant_candidates_cover_every_quadrant() {
+        // the city in the middle has 5 cities close on its left and 4 far away around it
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![-1.0, 0.1],
+            vec![-1.0, 0.2],
+            vec![-1.0, 0.3],
+            vec![-1.0, 0.4],
+            vec![-1.0, 0.5],
+            vec![10.0, 10.0],
+            vec![10.0, -10.0],
+            vec![-10.0, -10.0],
+            vec![-10.0, 10.0],
+        ]);
+
+        let knn = build_candidates(&cities, Candidates::Knn, 4).unwrap();
+        assert_eq!(vec![1, 2, 3, 4], knn[0]);
+
+        let quadrant = build_candidates(&cities, Candidates::Quadrant, 4).unwrap();
+        assert_eq!(4, quadrant[0].len());
+        assert_eq!(1, quadrant[0][0]);
+        assert!(quadrant[0].contains(&6));
+        assert!(quadrant[0].contains(&7));
+        assert!(quadrant[0].contains(&8));
+    }
+
+    #[test]
+    fn test_quadrant_candidates_find_far_quadrants() {
+        // 40 cities on the left of the first one, the single city on its right is far away
+        let mut rows = vec![vec![0.0, 0.0]];
+        rows.extend((1..=40).map(|i| vec![-(i as f64), 0.5]));
+        rows.push(vec![1000.0, 0.0]);
+        let cities = kdtree::build_points(&rows);
+
+        let quadrant = build_candidates(&cities, Candidates::Quadrant, 2).unwrap();
+        assert_eq!(vec![1, 41], quadrant[0]);
+
+        let knn = build_candidates(&cities, Candidates::Knn, 2).unwrap();
+        assert_eq!(vec![1, 2], knn[0]);
+    }
+
+    #[test]
+    fn test_build_lists_of_small_problem() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![0.0, 1.0], vec![3.0, 0.0]]);
+
+        let lists = build_lists(&cities, 5).unwrap();
+        assert_eq!(vec![vec![1, 2], vec![0, 2], vec![0, 1]], lists);
+    }
+}
@@ -0,0 +1,218 @@
+/// Known optima of the TSPLIB instances
+///
+/// The lengths of the optimal tours of the symmetric TSPLIB problems, as published with the
+/// library. When the NAME of the problem is in the table, the binary reports how far the tour is
+/// from the optimum, e.g `berlin52: cost 7905, optimum 7542, gap 4.81%`.
+///
+/// The optima are measured under the TSPLIB edge weight type of the problem, where the edges are
+/// rounded to integers, so the gap compares the tour length under the same type; the problems
+/// of the other edge weight types get no gap.
+use std::fmt;
+
+use super::tsplib::TspLibData;
+use super::validate;
+
+// sorted by the name, so it can be searched
+const KNOWN_OPTIMA: &[(&str, u64)] = &[
+    ("a280", 2579),
+    ("ali535", 202339),
+    ("att48", 10628),
+    ("att532", 27686),
+    ("bayg29", 1610),
+    ("bays29", 2020),
+    ("berlin52", 7542),
+    ("bier127", 118282),
+    ("brazil58", 25395),
+    ("brd14051", 469385),
+    ("brg180", 1950),
+    ("burma14", 3323),
+    ("ch130", 6110),
+    ("ch150", 6528),
+    ("d1291", 50801),
+    ("d15112", 1573084),
+    ("d1655", 62128),
+    ("d18512", 645238),
+    ("d198", 15780),
+    ("d2103", 80450),
+    ("d493", 35002),
+    ("d657", 48912),
+    ("dantzig42", 699),
+    ("dsj1000", 18659688),
+    ("eil101", 629),
+    ("eil51", 426),
+    ("eil76", 538),
+    ("fl1400", 20127),
+    ("fl1577", 22249),
+    ("fl3795", 28772),
+    ("fl417", 11861),
+    ("fnl4461", 182566),
+    ("fri26", 937),
+    ("gil262", 2378),
+    ("gr120", 6942),
+    ("gr137", 69853),
+    ("gr17", 2085),
+    ("gr202", 40160),
+    ("gr21", 2707),
+    ("gr229", 134602),
+    ("gr24", 1272),
+    ("gr431", 171414),
+    ("gr48", 5046),
+    ("gr666", 294358),
+    ("gr96", 55209),
+    ("hk48", 11461),
+    ("kroA100", 21282),
+    ("kroA150", 26524),
+    ("kroA200", 29368),
+    ("kroB100", 22141),
+    ("kroB150", 26130),
+    ("kroB200", 29437),
+    ("kroC100", 20749),
+    ("kroD100", 21294),
+    ("kroE100", 22068),
+    ("lin105", 14379),
+    ("lin318", 42029),
+    ("linhp318", 41345),
+    ("nrw1379", 56638),
+    ("p654", 34643),
+    ("pa561", 2763),
+    ("pcb1173", 56892),
+    ("pcb3038", 137694),
+    ("pcb442", 50778),
+    ("pla33810", 66048945),
+    ("pla7397", 23260728),
+    ("pla85900", 142382641),
+    ("pr1002", 259045),
+    ("pr107", 44303),
+    ("pr124", 59030),
+    ("pr136", 96772),
+    ("pr144", 58537),
+    ("pr152", 73682),
+    ("pr226", 80369),
+    ("pr2392", 378032),
+    ("pr264", 49135),
+    ("pr299", 48191),
+    ("pr439", 107217),
+    ("pr76", 108159),
+    ("rat195", 2323),
+    ("rat575", 6773),
+    ("rat783", 8806),
+    ("rat99", 1211),
+    ("rd100", 7910),
+    ("rd400", 15281),
+    ("rl11849", 923288),
+    ("rl1304", 252948),
+    ("rl1323", 270199),
+    ("rl1889", 316536),
+    ("rl5915", 565530),
+    ("rl5934", 556045),
+    ("si1032", 92650),
+    ("si175", 21407),
+    ("si535", 48450),
+    ("st70", 675),
+    ("swiss42", 1273),
+    ("ts225", 126643),
+    ("tsp225", 3916),
+    ("u1060", 224094),
+    ("u1432", 152970),
+    ("u159", 42080),
+    ("u1817", 57201),
+    ("u2152", 64253),
+    ("u2319", 234256),
+    ("u574", 36905),
+    ("u724", 41910),
+    ("ulysses16", 6859),
+    ("ulysses22", 7013),
+    ("usa13509", 19982859),
+    ("vm1084", 239297),
+    ("vm1748", 336556),
+];
+
+/// the length of the optimal tour of the TSPLIB instance
+pub fn known_optimum(name: &str) -> Option<u64> {
+    KNOWN_OPTIMA
+        .binary_search_by(|(known, _)| known.cmp(&name))
+        .ok()
+        .map(|i| KNOWN_OPTIMA[i].1)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    pub name: String,
+    pub cost: u64, // the length of the tour under the TSPLIB edge weight type
+    pub optimum: u64,
+}
+
+impl Gap {
+    /// how much longer the tour is than the optimal one, in percents
+    pub fn percent(&self) -> f64 {
+        (self.cost as f64 - self.optimum as f64) / self.optimum as f64 * 100.0
+    }
+}
+
+impl fmt::Display for Gap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: cost {}, optimum {}, gap {:.2}%",
+            self.name,
+            self.cost,
+            self.optimum,
+            self.percent()
+        )
+    }
+}
+
+/// the gap of the tour to the known optimum of the problem, None for the unknown problems
+pub fn gap(data: &TspLibData, route: &[usize]) -> Option<Gap> {
+    let optimum = known_optimum(data.name.trim())?;
+    let cost = validate::tsplib_length(data, route)?;
+
+    Some(Gap {
+        name: data.name.trim().to_string(),
+        cost,
+        optimum,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    #[test]
+    fn test_known_optima_are_sorted() {
+        assert!(KNOWN_OPTIMA.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_known_optimum() {
+        assert_eq!(Some(7542), known_optimum("berlin52"));
+        assert_eq!(Some(21282), known_optimum("kroA100"));
+        assert_eq!(Some(6859), known_optimum("ulysses16"));
+        assert_eq!(None, known_optimum("berlin53"));
+        assert_eq!(None, known_optimum("BERLIN52"));
+    }
+
+    #[test]
+    fn test_gap_of_known_instance() {
+        // the cities of burma14 are not needed, the square is 4 * 1000 long under EUC_2D
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 1000.0],
+            vec![1000.0, 1000.0],
+            vec![1000.0, 0.0],
+        ]);
+        let data = TspLibData::new("burma14".to_string(), String::new(), cities);
+
+        let gap = gap(&data, &[0, 1, 2, 3]).unwrap();
+        assert_eq!(4000, gap.cost);
+        assert!((gap.percent() - 20.373).abs() < 0.001);
+        assert_eq!(
+            "burma14: cost 4000, optimum 3323, gap 20.37%",
+            gap.to_string()
+        );
+
+        let unknown = TspLibData::new("square".to_string(), String::new(), data.cities().to_vec());
+        assert_eq!(None, super::gap(&unknown, &[0, 1, 2, 3]));
+    }
+}
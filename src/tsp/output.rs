@@ -0,0 +1,276 @@
+/// Output files of the solution
+///
+/// The format is inferred from the extension of the file:
+///
+/// * `.tour` - TSPLIB tour file, which the other TSPLIB tools can read
+/// * `.json` - `{"name": "berlin52", "total": 7544.37, "tour": [1, 49, ...]}`, the tours of
+///   `--units` have the converted length too: `"length": 7013.0, "units": "km"`
+/// * `.csv` - one city per row in the order of the tour: `position,city_id,x,y`
+/// * `.svg` - drawing of the tour, which the browsers can open
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::units::Length;
+use super::{json, tsplib, Error, Solution};
+
+// width and height of the SVG drawing, without the margins
+const SVG_SIZE: f64 = 800.0;
+const SVG_MARGIN: f64 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Tour,
+    Json,
+    Csv,
+    Svg,
+}
+
+impl OutputFormat {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["tour", "json", "csv", "svg"]
+    }
+
+    /// infers the format from the extension of the file
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "tour" => Ok(OutputFormat::Tour),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "svg" => Ok(OutputFormat::Svg),
+            _ => Err(Error::InvalidInput(format!(
+                "output: unknown format of {:?}, use one of the extensions: {}",
+                path,
+                OutputFormat::variants().join(", ")
+            ))),
+        }
+    }
+}
+
+/// writes the solution into the file, in the format of its extension
+pub fn write_to_file(
+    path: &Path,
+    name: &str,
+    tour: &Solution,
+    length: Option<&Length>,
+) -> Result<(), Error> {
+    let format = OutputFormat::from_path(path)?;
+    if format == OutputFormat::Tour {
+        return tsplib::write_tour_to_file(path, name, tour.route());
+    }
+
+    let f = File::create(path).map_err(Error::io("output: failed to create file"))?;
+    let mut writer = BufWriter::new(f);
+
+    write_to(&mut writer, format, name, tour, length).map_err(Error::io("output: failed to write"))
+}
+
+pub fn write_to<W: Write>(
+    writer: &mut W,
+    format: OutputFormat,
+    name: &str,
+    tour: &Solution,
+    length: Option<&Length>,
+) -> std::io::Result<()> {
+    match format {
+        OutputFormat::Tour => tsplib::write_tour_to(writer, name, tour.route()),
+        OutputFormat::Json => write_json(writer, name, tour, length),
+        OutputFormat::Csv => write_csv(writer, tour),
+        OutputFormat::Svg => write_svg(writer, name, tour),
+    }
+}
+
+fn write_json<W: Write>(
+    writer: &mut W,
+    name: &str,
+    tour: &Solution,
+    length: Option<&Length>,
+) -> std::io::Result<()> {
+    let ids: Vec<String> = tour.route().iter().map(|id| id.to_string()).collect();
+    let length_fields = length
+        .map(|length| {
+            format!(
+                ", \"length\": {}, \"units\": \"{}\"",
+                length.value, length.units
+            )
+        })
+        .unwrap_or_default();
+
+    writeln!(
+        writer,
+        "{{\"name\": \"{}\", \"total\": {}{}, \"tour\": [{}]}}",
+        json::escape(name),
+        tour.total,
+        length_fields,
+        ids.join(", ")
+    )?;
+
+    writer.flush()
+}
+
+fn write_csv<W: Write>(writer: &mut W, tour: &Solution) -> std::io::Result<()> {
+    writeln!(writer, "position,city_id,x,y")?;
+
+    for (pos, city_id) in tour.route().iter().enumerate() {
+        if let Some(city) = tour.get_by_city_id(*city_id) {
+            writeln!(writer, "{},{},{},{}", pos + 1, city_id, city.x(), city.y())?;
+        }
+    }
+
+    writer.flush()
+}
+
+fn write_svg<W: Write>(writer: &mut W, name: &str, tour: &Solution) -> std::io::Result<()> {
+    let coords: Vec<(f64, f64)> = tour
+        .route()
+        .iter()
+        .filter_map(|city_id| tour.get_by_city_id(*city_id))
+        .map(|city| (city.x(), city.y()))
+        .collect();
+
+    let min_x = coords.iter().map(|c| c.0).fold(f64::INFINITY, f64::min);
+    let min_y = coords.iter().map(|c| c.1).fold(f64::INFINITY, f64::min);
+    let max_x = coords.iter().map(|c| c.0).fold(f64::NEG_INFINITY, f64::max);
+    let max_y = coords.iter().map(|c| c.1).fold(f64::NEG_INFINITY, f64::max);
+    // keeps the aspect ratio, the single point or the line gets the full size
+    let extent = (max_x - min_x).max(max_y - min_y);
+    let scale = if extent > 0.0 { SVG_SIZE / extent } else { 1.0 };
+
+    // the y axis of SVG points down
+    let points: Vec<(f64, f64)> = coords
+        .iter()
+        .map(|(x, y)| {
+            (
+                SVG_MARGIN + (x - min_x) * scale,
+                SVG_MARGIN + (max_y - y) * scale,
+            )
+        })
+        .collect();
+
+    let width = SVG_MARGIN * 2.0 + (max_x - min_x).max(0.0) * scale;
+    let height = SVG_MARGIN * 2.0 + (max_y - min_y).max(0.0) * scale;
+    writeln!(
+        writer,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\">",
+        width, height
+    )?;
+    writeln!(
+        writer,
+        "  <title>{}: {:.2}</title>",
+        escape_xml(name),
+        tour.total
+    )?;
+
+    let polygon: Vec<String> = points
+        .iter()
+        .map(|(x, y)| format!("{:.2},{:.2}", x, y))
+        .collect();
+    writeln!(
+        writer,
+        "  <polygon points=\"{}\" fill=\"none\" stroke=\"red\" stroke-width=\"1.5\"/>",
+        polygon.join(" ")
+    )?;
+
+    for (x, y) in points.iter() {
+        writeln!(
+            writer,
+            "  <circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"3\" fill=\"black\"/>",
+            x, y
+        )?;
+    }
+
+    writeln!(writer, "</svg>")?;
+    writer.flush()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+    use crate::tsp::units::Units;
+
+    fn triangle_tour() -> Solution {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![3.0, 0.0], vec![3.0, 4.0]]);
+
+        Solution::new(&[2, 0, 1], &cities)
+    }
+
+    fn written(format: OutputFormat, name: &str) -> String {
+        let mut buffer: Vec<u8> = vec![];
+        write_to(&mut buffer, format, name, &triangle_tour(), None).unwrap();
+
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            OutputFormat::Tour,
+            OutputFormat::from_path(Path::new("out/berlin52.tour")).unwrap()
+        );
+        assert_eq!(
+            OutputFormat::Svg,
+            OutputFormat::from_path(Path::new("berlin52.SVG")).unwrap()
+        );
+        assert!(OutputFormat::from_path(Path::new("berlin52.txt")).is_err());
+        assert!(OutputFormat::from_path(Path::new("berlin52")).is_err());
+    }
+
+    #[test]
+    fn test_write_json() {
+        assert_eq!(
+            "{\"name\": \"tri\\\"angle\", \"total\": 12, \"tour\": [2, 0, 1]}\n",
+            written(OutputFormat::Json, "tri\"angle")
+        );
+
+        let length = Length {
+            value: 12.5,
+            units: Units::Miles,
+        };
+        let mut buffer: Vec<u8> = vec![];
+        write_to(
+            &mut buffer,
+            OutputFormat::Json,
+            "triangle",
+            &triangle_tour(),
+            Some(&length),
+        )
+        .unwrap();
+        assert_eq!(
+            "{\"name\": \"triangle\", \"total\": 12, \"length\": 12.5, \"units\": \"mi\", \"tour\": [2, 0, 1]}\n",
+            String::from_utf8(buffer).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_csv() {
+        assert_eq!(
+            "position,city_id,x,y\n1,2,3,4\n2,0,0,0\n3,1,3,0\n",
+            written(OutputFormat::Csv, "triangle")
+        );
+    }
+
+    #[test]
+    fn test_write_svg_flips_y_axis() {
+        let svg = written(OutputFormat::Svg, "triangle");
+
+        assert!(svg.starts_with(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"640\" height=\"840\">\n"
+        ));
+        assert!(svg.contains("<title>triangle: 12.00</title>"));
+        // the city (3, 4) is on the top right corner
+        assert!(svg.contains("<polygon points=\"620.00,20.00 20.00,820.00 620.00,820.00\""));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
@@ -0,0 +1,239 @@
+/// Solver pipelines
+///
+/// A pipeline chains the solvers, e.g `nn+2opt+sa`: the first stage builds the tour and
+/// every next stage continues from the route of the previous one, through the initial route
+/// of the options. So a constructive solver is only useful as the first stage.
+///
+/// The stages share the time budget of the options and the pipeline stops early when a stage
+/// reaches the target cost.
+use std::sync::Arc;
+
+use super::clock::Instant;
+use super::history::History;
+use super::improve::can_improve;
+use super::kdtree::KDPoint;
+use super::plugin::SharedSolver;
+use super::progress::{ProgressMessage, PublisherFn};
+use super::{registry, Error, Solution, SolverInfo, SolverOptions, TspSolver};
+
+pub const STAGE_SEPARATOR: char = '+';
+
+/// checks whether the solver name describes a pipeline
+pub fn is_pipeline(name: &str) -> bool {
+    name.contains(STAGE_SEPARATOR)
+}
+
+pub struct Pipeline {
+    spec: String,
+    stages: Vec<SharedSolver>,
+}
+
+impl Pipeline {
+    /// builds the pipeline of the solvers, the stages after the first one must continue from
+    /// the route
+    pub fn new(stages: Vec<SharedSolver>) -> Result<Self, Error> {
+        if stages.is_empty() {
+            return Err(Error::InvalidInput(
+                "pipeline: it has no stages".to_string(),
+            ));
+        }
+
+        for stage in stages.iter().skip(1) {
+            let info = stage.metadata();
+            if !can_improve(&info) {
+                return Err(Error::InvalidInput(format!(
+                    "pipeline: {} can't continue from a route, put it first",
+                    info.name
+                )));
+            }
+        }
+
+        let names: Vec<String> = stages.iter().map(|s| s.metadata().name).collect();
+        Ok(Pipeline {
+            spec: names.join(&STAGE_SEPARATOR.to_string()),
+            stages,
+        })
+    }
+
+    /// parses the pipeline from the solver names joined with `+`, e.g `nn+2opt`
+    pub fn parse(spec: &str) -> Result<Self, Error> {
+        let mut stages = vec![];
+
+        for name in spec.split(STAGE_SEPARATOR).map(|name| name.trim()) {
+            let solver = registry::find(name).ok_or_else(|| {
+                Error::InvalidInput(format!("pipeline: unknown solver `{}`", name))
+            })?;
+
+            stages.push(solver);
+        }
+
+        let mut pipeline = Pipeline::new(stages)?;
+        pipeline.spec = spec.to_string();
+
+        Ok(pipeline)
+    }
+
+    pub fn stages(&self) -> &[SharedSolver] {
+        &self.stages
+    }
+}
+
+impl TspSolver for Pipeline {
+    fn metadata(&self) -> SolverInfo {
+        let infos: Vec<SolverInfo> = self.stages.iter().map(|s| s.metadata()).collect();
+        let names: Vec<&str> = infos.iter().map(|info| info.name.as_str()).collect();
+        let descriptions: Vec<&str> = infos.iter().map(|i| i.description.as_str()).collect();
+        let complexities: Vec<&str> = infos.iter().map(|i| i.complexity.as_str()).collect();
+
+        let mut options: Vec<&str> = vec![];
+        for option in infos.iter().flat_map(|info| info.options.iter()) {
+            if !options.contains(&option.as_str()) {
+                options.push(option);
+            }
+        }
+        // the constraints of the tour are kept only if no stage breaks them
+        for constraint in ["end_city", "fixed_edges", "open_tour", "precedences"] {
+            if !infos.iter().all(|info| info.uses_option(constraint)) {
+                options.retain(|option| *option != constraint);
+            }
+        }
+
+        // the first stage decides whether the pipeline continues from the initial route
+        SolverInfo::new(
+            &names.join(&STAGE_SEPARATOR.to_string()),
+            &[&self.spec],
+            &descriptions.join(", then "),
+            infos.iter().any(|info| info.is_stochastic),
+        )
+        .with_category(infos[0].category)
+        .with_complexity(&complexities.join(" + "))
+        .with_options(&options)
+    }
+
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        let started_at = Instant::now();
+        // the pipeline is done after the last stage
+        let stage_progress: PublisherFn = {
+            let progress = Arc::clone(&progress);
+            Arc::new(move |msg| {
+                if !matches!(msg, ProgressMessage::Done) {
+                    progress(msg)
+                }
+            })
+        };
+
+        let mut stage_options = options.clone();
+        let mut best: Option<Solution> = None;
+        // the stages continue the history of the earlier ones
+        let mut history = History::new();
+        for stage in self.stages.iter() {
+            let stage_started = started_at.elapsed();
+            let tour = stage.solve(cities, &stage_options, Arc::clone(&stage_progress))?;
+            history.extend(tour.history(), stage_started);
+
+            // the metaheuristics may end up on a longer tour than they started from,
+            // then the next stage continues from the earlier one
+            if best.as_ref().is_none_or(|best| tour.total < best.total) {
+                best = Some(tour);
+            }
+
+            let best = best.as_ref().unwrap();
+            if options.is_target_reached(best.total) || options.deadline().is_over() {
+                break;
+            }
+
+            stage_options.initial_route = Some(best.route().to_vec());
+            stage_options.max_seconds = options
+                .max_seconds
+                .map(|secs| (secs - started_at.elapsed().as_secs_f32()).max(0.0));
+        }
+
+        progress(ProgressMessage::Done);
+        best.map(|tour| tour.with_target(options).with_history(history))
+            .ok_or_else(|| Error::Runtime("pipeline: no stage returned a tour".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::registry::Category;
+    use crate::tsp::{kdtree, progress};
+
+    fn cities() -> Vec<KDPoint> {
+        kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+            vec![1.0, 0.0],
+            vec![0.5, 0.0],
+            vec![0.5, 1.0],
+        ])
+    }
+
+    #[test]
+    fn test_parse_pipeline() {
+        let pipeline = Pipeline::parse("nn+2opt").unwrap();
+        let info = pipeline.metadata();
+
+        assert_eq!(2, pipeline.stages().len());
+        assert_eq!("nearest_neighbor+two_opt", info.name);
+        assert!(info.has_name("nn+2opt"));
+        assert_eq!(Category::Constructive, info.category);
+        assert!(!info.is_stochastic);
+        assert!(Pipeline::parse("nn+sa").unwrap().metadata().is_stochastic);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_pipelines() {
+        let err = Pipeline::parse("2opt+nn").err().unwrap();
+        assert!(err.to_string().contains("nearest_neighbor can't continue"));
+
+        let err = Pipeline::parse("nn+or_opt").err().unwrap();
+        assert!(err.to_string().contains("unknown solver `or_opt`"));
+    }
+
+    #[test]
+    fn test_solve_seeds_next_stage() {
+        let cities = cities();
+        let options = SolverOptions::default();
+        let nn = registry::find("nn")
+            .unwrap()
+            .solve(&cities, &options, progress::silent_publisher())
+            .unwrap();
+
+        let tour = Pipeline::parse("nn+2opt")
+            .unwrap()
+            .solve(&cities, &options, progress::silent_publisher())
+            .unwrap();
+
+        assert_eq!(cities.len(), tour.len());
+        assert!(tour.total <= nn.total);
+        assert!((tour.total - 4.0).abs() < 0.001, "{}", tour.total);
+    }
+
+    #[test]
+    fn test_solve_continues_the_history_of_the_stages() {
+        let cities = cities();
+        let tour = Pipeline::parse("nn+2opt")
+            .unwrap()
+            .solve(
+                &cities,
+                &SolverOptions::default(),
+                progress::silent_publisher(),
+            )
+            .unwrap();
+
+        let points = tour.history().points();
+        assert!(!points.is_empty());
+        assert!(points
+            .windows(2)
+            .all(|w| w[1].best_cost < w[0].best_cost && w[1].epoch >= w[0].epoch));
+        assert_eq!(Some(tour.total), tour.history().best_cost());
+    }
+}
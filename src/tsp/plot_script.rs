@@ -0,0 +1,257 @@
+/// Plot scripts for external plotting tools
+///
+/// It writes coordinates of the tour into a data file and a script, which plots the data
+/// with gnuplot or matplotlib, so the tour can be plotted offline and styled further.
+///
+/// The data file has one city per line in the order of the tour, the first city is repeated
+/// at the end to close the loop:
+///
+/// ```text
+/// # x y city_id
+/// 565 575 1
+/// 25 185 2
+/// 565 575 1
+/// ```
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::{external, Error, Solution};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlotFormat {
+    Gnuplot,
+    Matplotlib,
+}
+
+impl PlotFormat {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["gnuplot", "matplotlib"]
+    }
+
+    fn script_extension(&self) -> &'static str {
+        match self {
+            PlotFormat::Gnuplot => "gp",
+            PlotFormat::Matplotlib => "py",
+        }
+    }
+}
+
+impl FromStr for PlotFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gnuplot" => Ok(PlotFormat::Gnuplot),
+            "matplotlib" => Ok(PlotFormat::Matplotlib),
+            _ => Err("unknown plot format"),
+        }
+    }
+}
+
+/// writes `<name>_tour.dat` and the plotting script into the `dir`, the name keeps only the
+/// characters of `external::name_stem`; returns the path of the script
+pub fn export(
+    format: PlotFormat,
+    tour: &Solution,
+    name: &str,
+    dir: &Path,
+) -> Result<PathBuf, Error> {
+    let stem = external::name_stem(name);
+    let data_path = dir.join(format!("{}_tour.dat", stem));
+    let script_path = dir.join(format!("{}_tour.{}", stem, format.script_extension()));
+
+    write_tour_coords(&data_path, tour)?;
+
+    let f = File::create(&script_path).map_err(Error::io("plot_script: failed to create file"))?;
+    let mut writer = BufWriter::new(f);
+    // both paths are joined from a file name, so they always have one
+    let data_file = data_path.file_name().unwrap_or_default().to_string_lossy();
+    let script_file = script_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let title = format!("{}: {:.2}", name, tour.total);
+
+    let res = match format {
+        PlotFormat::Gnuplot => write_gnuplot(&mut writer, &script_file, &data_file, &title),
+        PlotFormat::Matplotlib => write_matplotlib(&mut writer, &data_file, &title),
+    };
+    res.map_err(Error::io("plot_script: failed to write script"))?;
+
+    Ok(script_path)
+}
+
+/// writes the coordinates of the cities in the order of the tour
+pub fn write_tour_coords(path: &Path, tour: &Solution) -> Result<(), Error> {
+    let f = File::create(path).map_err(Error::io("plot_script: failed to create file"))?;
+    let mut writer = BufWriter::new(f);
+
+    write_tour_coords_to(&mut writer, tour)
+        .map_err(Error::io("plot_script: failed to write coordinates"))
+}
+
+fn write_tour_coords_to<W: Write>(writer: &mut W, tour: &Solution) -> std::io::Result<()> {
+    writeln!(writer, "# x y city_id")?;
+
+    let closed_route = tour.route().iter().chain(tour.route().first());
+    for city_id in closed_route {
+        if let Some(city) = tour.get_by_city_id(*city_id) {
+            let x = city.get(0).unwrap_or(0.0);
+            let y = city.get(1).unwrap_or(0.0);
+
+            writeln!(writer, "{} {} {}", x, y, city_id)?;
+        }
+    }
+
+    writer.flush()
+}
+
+// gnuplot looks for the data file from the working directory
+fn write_gnuplot<W: Write>(
+    writer: &mut W,
+    script_file: &str,
+    data_file: &str,
+    title: &str,
+) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "# run it in the same directory: gnuplot -p {}",
+        script_file
+    )?;
+    writeln!(writer, "set title {}", quoted(title))?;
+    writeln!(writer, "set key off")?;
+    writeln!(writer, "set size ratio -1")?;
+    writeln!(
+        writer,
+        "plot \"{}\" using 1:2 with linespoints pointtype 7 pointsize 0.5 linecolor rgb \"red\"",
+        data_file
+    )?;
+
+    writer.flush()
+}
+
+fn write_matplotlib<W: Write>(writer: &mut W, data_file: &str, title: &str) -> std::io::Result<()> {
+    writeln!(writer, "#!/usr/bin/env python3")?;
+    writeln!(writer, "import os")?;
+    writeln!(writer, "import matplotlib.pyplot as plt")?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "data_path = os.path.join(os.path.dirname(os.path.abspath(__file__)), \"{}\")",
+        data_file
+    )?;
+    writeln!(writer, "xs, ys = [], []")?;
+    writeln!(writer, "with open(data_path) as f:")?;
+    writeln!(writer, "    for line in f:")?;
+    writeln!(writer, "        if line.startswith(\"#\"):")?;
+    writeln!(writer, "            continue")?;
+    writeln!(writer, "        x, y, _city_id = line.split()")?;
+    writeln!(writer, "        xs.append(float(x))")?;
+    writeln!(writer, "        ys.append(float(y))")?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "plt.plot(xs, ys, \"o-\", color=\"red\", markersize=3)"
+    )?;
+    writeln!(writer, "plt.title({})", quoted(title))?;
+    writeln!(writer, "plt.axis(\"equal\")")?;
+    writeln!(writer, "plt.show()")?;
+
+    writer.flush()
+}
+
+// the double quoted string of gnuplot and python, the backslash escapes the quote and itself
+// in both of them; the control characters would end the line of the script, they are spaces
+fn quoted(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            c if c.is_control() => literal.push(' '),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+
+    literal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    #[test]
+    fn test_write_tour_coords_to_closes_the_loop() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 2.0], vec![3.0, 1.5]]);
+        let tour = Solution::new(&[2, 0, 1], &cities);
+
+        let mut buffer: Vec<u8> = vec![];
+        write_tour_coords_to(&mut buffer, &tour).unwrap();
+
+        let content = String::from_utf8(buffer).unwrap();
+        assert_eq!("# x y city_id\n3 1.5 2\n0 0 0\n1 2 1\n3 1.5 2\n", content);
+    }
+
+    #[test]
+    fn test_write_gnuplot_plots_data_file() {
+        let mut buffer: Vec<u8> = vec![];
+        write_gnuplot(
+            &mut buffer,
+            "case1_tour.gp",
+            "case1_tour.dat",
+            "case1: 4.00",
+        )
+        .unwrap();
+
+        let content = String::from_utf8(buffer).unwrap();
+        assert!(content.contains("gnuplot -p case1_tour.gp\n"));
+        assert!(content.contains("set title \"case1: 4.00\"\n"));
+        assert!(content.contains("plot \"case1_tour.dat\" using 1:2"));
+    }
+
+    #[test]
+    fn test_quoted_escapes_title() {
+        assert_eq!("\"case1: 4.00\"", quoted("case1: 4.00"));
+        assert_eq!(
+            "\"a\\\"); os.system(\\\"rm\\\\x \"",
+            quoted("a\"); os.system(\"rm\\x\n")
+        );
+    }
+
+    #[test]
+    fn test_export_keeps_files_in_dir() {
+        let dir = std::env::temp_dir().join(format!("teeline_plot_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 2.0], vec![3.0, 1.5]]);
+        let tour = Solution::new(&[0, 1, 2], &cities);
+
+        let res = export(PlotFormat::Matplotlib, &tour, "../x/\"y", &dir);
+        let script = res
+            .as_ref()
+            .ok()
+            .and_then(|path| std::fs::read_to_string(path).ok());
+        let files: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(Some(dir.join("xy_tour.py")), res.ok());
+        assert_eq!(2, files.len(), "{:?}", files);
+        assert!(script.unwrap().contains("plt.title(\"../x/\\\"y: "));
+    }
+
+    #[test]
+    fn test_plot_format_from_str() {
+        for name in PlotFormat::variants().iter() {
+            assert!(PlotFormat::from_str(name).is_ok());
+        }
+
+        assert!(PlotFormat::from_str("svg").is_err());
+    }
+}
@@ -0,0 +1,190 @@
+/// Plugins let research users to run their own solvers inside teeline's CLI
+/// and progress infrastructure without forking the crate.
+///
+/// There are 2 ways to make a solver available:
+///
+/// * the applications embedding teeline call `plugin::register` for their solvers;
+/// * the solvers compiled into a `cdylib` are loaded at runtime with `--plugin <path>`,
+///   it requires the `plugins` feature;
+///
+/// A dynamic plugin exports a registration function with the `declare_plugin!` macro:
+///
+/// ```ignore
+/// use teeline::tsp::plugin::PluginRegistrar;
+///
+/// fn register(registrar: &mut PluginRegistrar) {
+///     registrar.register(Box::new(MySolver));
+/// }
+///
+/// teeline::declare_plugin!(register);
+/// ```
+///
+/// NB! Rust has no stable ABI, so the plugin must be compiled with the same compiler
+/// and the same version of teeline as the binary that loads it.
+use lazy_static::lazy_static;
+use std::sync::{Arc, Mutex};
+
+use super::TspSolver;
+
+pub type SharedSolver = Arc<dyn TspSolver>;
+
+/// name of the function a dynamic plugin must export
+pub const REGISTER_SYMBOL: &[u8] = b"teeline_register_plugins";
+/// name of the static holding the teeline version the plugin was compiled against
+pub const VERSION_SYMBOL: &[u8] = b"TEELINE_PLUGIN_VERSION";
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Vec<SharedSolver>> = Mutex::new(vec![]);
+}
+
+/// collects solvers from the plugin's registration function
+#[derive(Default)]
+pub struct PluginRegistrar {
+    solvers: Vec<Box<dyn TspSolver>>,
+}
+
+impl PluginRegistrar {
+    pub fn new() -> Self {
+        PluginRegistrar { solvers: vec![] }
+    }
+
+    pub fn register(&mut self, solver: Box<dyn TspSolver>) {
+        self.solvers.push(solver);
+    }
+}
+
+/// makes the solver available by its name, a solver with the same name is replaced
+pub fn register(solver: Box<dyn TspSolver>) {
+    let mut registry = REGISTRY.lock().unwrap();
+
+    let name = solver.metadata().name;
+    registry.retain(|s| s.metadata().name != name);
+    registry.push(Arc::from(solver));
+}
+
+/// finds the solver by its name or by its alias
+pub fn find(name: &str) -> Option<SharedSolver> {
+    let registry = REGISTRY.lock().unwrap();
+
+    registry
+        .iter()
+        .find(|s| s.metadata().has_name(name))
+        .cloned()
+}
+
+/// returns all the registered solvers
+pub fn solvers() -> Vec<SharedSolver> {
+    REGISTRY.lock().unwrap().clone()
+}
+
+/// returns names of all the registered solvers
+pub fn names() -> Vec<String> {
+    let registry = REGISTRY.lock().unwrap();
+
+    registry.iter().map(|s| s.metadata().name).collect()
+}
+
+/// loads the dynamic library and registers all its solvers,
+/// returns the number of registered solvers
+#[cfg(feature = "plugins")]
+pub fn load_library(path: &std::path::Path) -> Result<usize, super::Error> {
+    use super::Error;
+    use libloading::{Library, Symbol};
+
+    lazy_static! {
+        // libraries are never unloaded, as the registered solvers point into their code
+        static ref LIBRARIES: Mutex<Vec<Library>> = Mutex::new(vec![]);
+    }
+
+    let library = unsafe { Library::new(path) }
+        .map_err(|e| Error::Plugin(format!("failed to load {:?}: {}", path, e)))?;
+
+    let n_solvers = unsafe {
+        let version: Symbol<*const &str> = library
+            .get(VERSION_SYMBOL)
+            .map_err(|_| Error::Plugin(format!("{:?} is not a teeline plugin", path)))?;
+
+        if **version != super::VERSION {
+            return Err(Error::Plugin(format!(
+                "{:?} is built for teeline {}, but this is {}",
+                path,
+                **version,
+                super::VERSION
+            )));
+        }
+
+        let register_fn: Symbol<fn(&mut PluginRegistrar)> = library
+            .get(REGISTER_SYMBOL)
+            .map_err(|_| Error::Plugin(format!("{:?} has no registration function", path)))?;
+
+        let mut registrar = PluginRegistrar::new();
+        register_fn(&mut registrar);
+
+        let n_solvers = registrar.solvers.len();
+        for solver in registrar.solvers.into_iter() {
+            register(solver);
+        }
+
+        n_solvers
+    };
+
+    LIBRARIES.lock().unwrap().push(library);
+
+    Ok(n_solvers)
+}
+
+/// exports the registration function and the version marker of a dynamic plugin
+#[macro_export]
+macro_rules! declare_plugin {
+    ($register_fn:path) => {
+        #[no_mangle]
+        pub static TEELINE_PLUGIN_VERSION: &str = $crate::tsp::VERSION;
+
+        #[no_mangle]
+        pub fn teeline_register_plugins(registrar: &mut $crate::tsp::plugin::PluginRegistrar) {
+            $register_fn(registrar);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree::KDPoint;
+    use crate::tsp::progress::PublisherFn;
+    use crate::tsp::route::Route;
+    use crate::tsp::{Error, Solution, SolverInfo, SolverOptions};
+
+    struct InputOrderSolver;
+
+    impl TspSolver for InputOrderSolver {
+        fn metadata(&self) -> SolverInfo {
+            SolverInfo::new("input_order_test", &["iot"], "keeps the input order", false)
+        }
+
+        fn solve(
+            &self,
+            cities: &[KDPoint],
+            _options: &SolverOptions,
+            _progress: PublisherFn,
+        ) -> Result<Solution, Error> {
+            Ok(Solution::new(Route::from_cities(cities).route(), cities))
+        }
+    }
+
+    #[test]
+    fn test_register_and_find_solver() {
+        let mut registrar = PluginRegistrar::new();
+        registrar.register(Box::new(InputOrderSolver));
+        assert_eq!(1, registrar.solvers.len());
+
+        register(Box::new(InputOrderSolver));
+        register(Box::new(InputOrderSolver));
+
+        let n_registered = names().iter().filter(|&n| n == "input_order_test").count();
+        assert_eq!(1, n_registered);
+        assert!(find("input_order_test").is_some());
+        assert!(find("iot").is_some());
+        assert!(find("missing_solver").is_none());
+    }
+}
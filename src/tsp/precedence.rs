@@ -0,0 +1,233 @@
+/// Precedence constraints
+///
+/// The pairs of the cities, where the first city must be visited before the second one, e.g the
+/// pickup before the delivery. The tour is read from its first city, so the route starts where
+/// the salesperson starts. They come from the `PRECEDENCE_SECTION` of the TSPLIB file or from the
+/// file of `--precedences`, with a pair of the city ids per line:
+///
+/// ```text
+/// PRECEDENCE_SECTION
+/// 1 7
+/// 7 3
+/// -1
+/// ```
+///
+/// The nearest neighbor construction repairs the order of its tour: the city waits until all the
+/// cities before it are visited. 2-opt keeps the first city of the route and rejects the moves,
+/// which would reverse the order of the pair. The other solvers refuse the options with them.
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::kdtree::KDPoint;
+use super::{Error, SolverInfo, SolverOptions};
+
+/// the cities which must be visited after the city, by the city ids
+#[derive(Debug, Clone, Default)]
+pub struct Precedences {
+    successors: HashMap<usize, Vec<usize>>,
+    n_predecessors: HashMap<usize, usize>,
+}
+
+impl Precedences {
+    /// checks that the pairs have the known cities and that some tour keeps all of them,
+    /// the pairs make no cycle
+    pub fn new(cities: &[KDPoint], pairs: &[(usize, usize)]) -> Result<Self, Error> {
+        let invalid =
+            |message: String| Err(Error::InvalidInput(format!("precedences: {}", message)));
+
+        let known_ids: HashSet<usize> = cities.iter().map(|c| c.id).collect();
+        let mut precedences = Precedences::default();
+        for (a, b) in pairs.iter().copied() {
+            if let Some(city_id) = [a, b].iter().find(|id| !known_ids.contains(id)) {
+                return invalid(format!(
+                    "the pair {} {} has the unknown city {}",
+                    a, b, city_id
+                ));
+            }
+            if a == b {
+                return invalid(format!("the city {} can't be visited before itself", a));
+            }
+            if precedences.successors(a).contains(&b) {
+                continue;
+            }
+
+            precedences.successors.entry(a).or_default().push(b);
+            *precedences.n_predecessors.entry(b).or_default() += 1;
+        }
+
+        // the cities of the cycle never get all their predecessors visited
+        let order = precedences.repair(&cities.iter().map(|c| c.id).collect::<Vec<usize>>());
+        if order.len() < cities.len() {
+            let ordered: HashSet<usize> = order.into_iter().collect();
+            let city_id = cities.iter().find(|c| !ordered.contains(&c.id)).unwrap().id;
+            return invalid(format!("the city {} is on the cycle of the pairs", city_id));
+        }
+
+        Ok(precedences)
+    }
+
+    /// the precedences of the options
+    pub fn from_options(cities: &[KDPoint], options: &SolverOptions) -> Result<Self, Error> {
+        Precedences::new(cities, &options.precedences)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.successors.is_empty()
+    }
+
+    /// the cities which must be visited after the city
+    pub fn successors(&self, city_id: usize) -> &[usize] {
+        self.successors.get(&city_id).map_or(&[], |s| s.as_slice())
+    }
+
+    /// the first pair the route visits in the wrong order
+    pub fn violation(&self, route: &[usize]) -> Option<(usize, usize)> {
+        let pos_of: HashMap<usize, usize> =
+            route.iter().enumerate().map(|(i, c)| (*c, i)).collect();
+
+        route.iter().find_map(|a| {
+            self.successors(*a)
+                .iter()
+                .find(|b| pos_of.get(b).is_some_and(|pos_b| *pos_b < pos_of[a]))
+                .map(|b| (*a, *b))
+        })
+    }
+
+    /// the route in the same order as much as possible, but every city comes after the cities,
+    /// which must be visited before it; the cities on the cycle of the pairs are left out
+    pub fn repair(&self, route: &[usize]) -> Vec<usize> {
+        let pos_of: HashMap<usize, usize> =
+            route.iter().enumerate().map(|(i, c)| (*c, i)).collect();
+        let mut n_waiting: HashMap<usize, usize> = self.n_predecessors.clone();
+
+        // the cities whose predecessors are visited, the earliest of the route first
+        let mut ready: BinaryHeap<Reverse<usize>> = route
+            .iter()
+            .enumerate()
+            .filter(|(_, city_id)| !n_waiting.contains_key(city_id))
+            .map(|(pos, _)| Reverse(pos))
+            .collect();
+
+        let mut repaired = Vec::with_capacity(route.len());
+        while let Some(Reverse(pos)) = ready.pop() {
+            let city_id = route[pos];
+            repaired.push(city_id);
+
+            for successor in self.successors(city_id).iter() {
+                let n = n_waiting.entry(*successor).or_default();
+                *n = n.saturating_sub(1);
+                if *n == 0 {
+                    if let Some(successor_pos) = pos_of.get(successor) {
+                        ready.push(Reverse(*successor_pos));
+                    }
+                }
+            }
+        }
+
+        repaired
+    }
+
+    /// true if the reversal of the positions from..=to breaks a pair: both of its cities are
+    /// in the reversed segment, the cities outside of it keep their order to the segment
+    pub fn breaks_reversal(
+        &self,
+        route: &[usize],
+        pos_of: &HashMap<usize, usize>,
+        from: usize,
+        to: usize,
+    ) -> bool {
+        route[from..=to].iter().any(|a| {
+            self.successors(*a)
+                .iter()
+                .any(|b| pos_of.get(b).is_some_and(|pos| (from..=to).contains(pos)))
+        })
+    }
+}
+
+/// reads the pairs of the city ids from the file, a pair per line; the empty lines and the lines
+/// starting with `#` are skipped
+pub fn read_from_file(path: &Path) -> Result<Vec<(usize, usize)>, Error> {
+    let content =
+        fs::read_to_string(path).map_err(Error::io("precedences: failed to read file"))?;
+
+    let mut pairs = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let ids: Vec<usize> = line
+            .split_whitespace()
+            .map(usize::from_str)
+            .collect::<Result<_, _>>()
+            .map_err(|_| Error::parse("precedences", i + 1, "invalid city id"))?;
+        match ids.as_slice() {
+            [a, b] => pairs.push((*a, *b)),
+            _ => {
+                return Err(Error::parse(
+                    "precedences",
+                    i + 1,
+                    "the pair must have 2 city ids",
+                ))
+            }
+        }
+    }
+
+    Ok(pairs)
+}
+
+/// fails when the options have the precedences and the solver can't keep them
+pub fn check_solver(solver: &SolverInfo, options: &SolverOptions) -> Result<(), Error> {
+    if options.precedences.is_empty() || solver.uses_option("precedences") {
+        return Ok(());
+    }
+
+    Err(Error::InvalidInput(format!(
+        "{}: it can't keep the order of the precedences, use nearest_neighbor or two_opt",
+        solver.name
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    fn cities() -> Vec<KDPoint> {
+        kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![2.0, 0.0],
+            vec![3.0, 0.0],
+        ])
+    }
+
+    #[test]
+    fn test_repair_keeps_the_order_of_the_route() {
+        let precedences = Precedences::new(&cities(), &[(3, 1), (2, 3)]).unwrap();
+
+        assert_eq!(Some((3, 1)), precedences.violation(&[0, 1, 2, 3]));
+        let repaired = precedences.repair(&[0, 1, 2, 3]);
+        assert_eq!(vec![0, 2, 3, 1], repaired);
+        assert_eq!(None, precedences.violation(&repaired));
+
+        let pos_of: HashMap<usize, usize> =
+            repaired.iter().enumerate().map(|(i, c)| (*c, i)).collect();
+        assert!(precedences.breaks_reversal(&repaired, &pos_of, 1, 2));
+        assert!(!precedences.breaks_reversal(&repaired, &pos_of, 0, 1));
+    }
+
+    #[test]
+    fn test_new_rejects_pairs_no_tour_keeps() {
+        let cities = cities();
+
+        assert!(Precedences::new(&cities, &[(0, 7)]).is_err());
+        assert!(Precedences::new(&cities, &[(2, 2)]).is_err());
+        assert!(Precedences::new(&cities, &[(0, 1), (1, 2), (2, 0)]).is_err());
+        assert!(Precedences::new(&cities, &[(0, 1), (0, 2), (1, 2)]).is_ok());
+    }
+}
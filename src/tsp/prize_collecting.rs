@@ -0,0 +1,483 @@
+/// Prize-collecting tours
+///
+/// The cities of the selective TSP have prizes and the tour doesn't have to visit all of them:
+/// it starts from the depot and collects the prizes of the cities it visits. The TSPLIB files of
+/// the problems list the prizes in the `PRIZE_SECTION`, the cities without the prize have none,
+/// the depot is in the `DEPOT_SECTION` or it is the first city:
+///
+/// ```text
+/// teeline pctsp -i ./data/pctsp/islands.tsp
+/// teeline pctsp -i ./data/pctsp/islands.tsp --quota 400
+/// ```
+///
+/// By default the tour collects the most prizes for its length, the prizes minus the length.
+/// With the quota the tour collects at least the quota and it is as short as possible.
+///
+/// The tour is built by inserting the cities at their cheapest place, the city which gains the
+/// most first; then the local search shortens the tour with 2-opt, drops the cities which cost
+/// more than they bring, adds the ones which bring more than they cost and replaces the visited
+/// cities with the unvisited ones, until no move improves the tour.
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::kdtree::KDPoint;
+use super::tsplib::TspLibData;
+use super::{validate_cities, Deadline, Error, SolverOptions};
+
+// the smallest gain of the move, the rounding errors must not undo the earlier moves
+const MIN_GAIN: f32 = 1e-5;
+
+/// what the tour is optimized for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Objective {
+    Profit,       // the most prizes minus the length of the tour
+    Quota(usize), // the shortest tour, which collects at least the quota of the prizes
+}
+
+/// the cities with their prizes, the depot is one of the cities
+#[derive(Debug, Clone)]
+pub struct PrizeProblem {
+    cities: Vec<KDPoint>,
+    prizes: Vec<usize>, // by the positions of the cities
+    depot: usize,       // the position of the depot
+}
+
+impl PrizeProblem {
+    /// the cities without the prize have none
+    pub fn new(
+        cities: &[KDPoint],
+        prizes: &[(usize, usize)],
+        depot_id: usize,
+    ) -> Result<Self, Error> {
+        validate_cities(cities, 2)?;
+        let invalid = |message: String| Err(Error::InvalidInput(format!("pctsp: {}", message)));
+
+        let pos_of: HashMap<usize, usize> =
+            cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+        let depot = match pos_of.get(&depot_id) {
+            Some(pos) => *pos,
+            None => return invalid(format!("the depot {} is not a city", depot_id)),
+        };
+
+        let mut city_prizes = vec![0; cities.len()];
+        for (city_id, prize) in prizes.iter() {
+            match pos_of.get(city_id) {
+                Some(pos) => city_prizes[*pos] = *prize,
+                None => return invalid(format!("the prize of the unknown city {}", city_id)),
+            }
+        }
+
+        Ok(PrizeProblem {
+            cities: cities.to_vec(),
+            prizes: city_prizes,
+            depot,
+        })
+    }
+
+    /// the problem of the TSPLIB file, the first depot or, without the DEPOT_SECTION,
+    /// the first city is the depot
+    pub fn from_tsplib(data: &TspLibData) -> Result<Self, Error> {
+        if data.prizes().is_empty() {
+            return Err(Error::InvalidInput(format!(
+                "pctsp: {} has no PRIZE_SECTION",
+                data.name
+            )));
+        }
+        if data.depots().len() > 1 {
+            return Err(Error::InvalidInput(
+                "pctsp: only the problems with a single depot are supported".to_string(),
+            ));
+        }
+
+        let depot_id = match data.depots().first() {
+            Some(depot_id) => *depot_id,
+            None => data.cities().first().map_or(0, |city| city.id),
+        };
+
+        PrizeProblem::new(data.cities(), data.prizes(), depot_id)
+    }
+
+    pub fn depot_id(&self) -> usize {
+        self.cities[self.depot].id
+    }
+
+    /// the prizes of all the cities, the most the tour can collect
+    pub fn total_prize(&self) -> usize {
+        self.prizes.iter().sum()
+    }
+
+    fn distance(&self, a: usize, b: usize) -> f32 {
+        self.cities[a].distance(&self.cities[b])
+    }
+
+    // the length of the tour from the depot through the cities back to the depot
+    fn route_length(&self, route: &[usize]) -> f32 {
+        let mut length = 0.0;
+        let mut previous = self.depot;
+        for city in route.iter().chain(std::iter::once(&self.depot)) {
+            length += self.distance(previous, *city);
+            previous = *city;
+        }
+
+        length
+    }
+
+    // the prizes of the cities of the route and of the depot
+    fn collected(&self, route: &[usize]) -> usize {
+        self.prizes[self.depot] + route.iter().map(|city| self.prizes[*city]).sum::<usize>()
+    }
+}
+
+/// the tour by the city ids, it starts from the depot
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrizeSolution {
+    pub route: Vec<usize>,
+    pub prize: usize, // the prizes of the visited cities
+    pub length: f32,  // the length of the closed tour
+}
+
+impl PrizeSolution {
+    /// the collected prizes minus the length of the tour
+    pub fn profit(&self) -> f32 {
+        self.prize as f32 - self.length
+    }
+}
+
+impl fmt::Display for PrizeSolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ids: Vec<String> = self.route.iter().map(|id| id.to_string()).collect();
+        writeln!(f, "Route: {}", ids.join(" "))?;
+        writeln!(f, "Prize {}", self.prize)?;
+        writeln!(f, "Length {}", self.length)?;
+        writeln!(f, "Profit {}", self.profit())
+    }
+}
+
+/// builds the tour by the cheapest insertions and improves it with the local search,
+/// it stops early after `max_seconds`
+pub fn solve(
+    problem: &PrizeProblem,
+    objective: Objective,
+    options: &SolverOptions,
+) -> Result<PrizeSolution, Error> {
+    if let Objective::Quota(quota) = objective {
+        if quota > problem.total_prize() {
+            return Err(Error::InvalidInput(format!(
+                "pctsp: the quota {} is more than all the prizes {}",
+                quota,
+                problem.total_prize()
+            )));
+        }
+    }
+
+    let deadline = options.deadline();
+    let mut route = insertion_route(problem, objective, &deadline);
+    if options.verbose {
+        println!(
+            "PCTSP: the insertions visited {} cities, prize {} length {:.2}",
+            route.len() + 1,
+            problem.collected(&route),
+            problem.route_length(&route)
+        );
+    }
+
+    let mut n_passes = 0;
+    while !deadline.is_over() && improve_route(problem, objective, &mut route) {
+        n_passes += 1;
+
+        if options.verbose {
+            println!(
+                "PCTSP: pass {} visits {} cities, prize {} length {:.2}",
+                n_passes,
+                route.len() + 1,
+                problem.collected(&route),
+                problem.route_length(&route)
+            );
+        }
+    }
+
+    Ok(PrizeSolution {
+        route: std::iter::once(&problem.depot)
+            .chain(route.iter())
+            .map(|pos| problem.cities[*pos].id)
+            .collect(),
+        prize: problem.collected(&route),
+        length: problem.route_length(&route),
+    })
+}
+
+// the city before the position `i` of the route, the depot before the first one
+fn before(problem: &PrizeProblem, route: &[usize], i: usize) -> usize {
+    if i == 0 {
+        problem.depot
+    } else {
+        route[i - 1]
+    }
+}
+
+// the city after the position `i` of the route, the depot after the last one
+fn after(problem: &PrizeProblem, route: &[usize], i: usize) -> usize {
+    route.get(i + 1).copied().unwrap_or(problem.depot)
+}
+
+// the cheapest place of the city: it goes before the position, the last one is after the last city
+fn cheapest_insertion(problem: &PrizeProblem, route: &[usize], city: usize) -> (f32, usize) {
+    (0..=route.len())
+        .map(|k| {
+            let (a, b) = (
+                before(problem, route, k),
+                route.get(k).copied().unwrap_or(problem.depot),
+            );
+            let cost =
+                problem.distance(a, city) + problem.distance(city, b) - problem.distance(a, b);
+            (cost, k)
+        })
+        .min_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(Ordering::Equal))
+        .unwrap_or((0.0, 0))
+}
+
+// how much the city at the cost is worth: its prize minus the cost for the profit, the prize
+// per the cost for the quota
+fn insertion_value(problem: &PrizeProblem, objective: Objective, city: usize, cost: f32) -> f32 {
+    let prize = problem.prizes[city] as f32;
+    match objective {
+        Objective::Profit => prize - cost,
+        Objective::Quota(_) => prize / cost.max(MIN_GAIN),
+    }
+}
+
+// the cities by their positions, without the depot; the most valuable city is inserted at its
+// cheapest place while it gains for the profit, or until the quota is collected
+fn insertion_route(
+    problem: &PrizeProblem,
+    objective: Objective,
+    deadline: &Deadline,
+) -> Vec<usize> {
+    let mut route: Vec<usize> = vec![];
+    let mut is_visited = vec![false; problem.cities.len()];
+    is_visited[problem.depot] = true;
+
+    loop {
+        if let Objective::Quota(quota) = objective {
+            if problem.collected(&route) >= quota {
+                break;
+            }
+        }
+        if deadline.is_over() && objective == Objective::Profit {
+            break;
+        }
+
+        let best = (0..problem.cities.len())
+            .filter(|city| !is_visited[*city] && problem.prizes[*city] > 0)
+            .map(|city| {
+                let (cost, k) = cheapest_insertion(problem, &route, city);
+                (insertion_value(problem, objective, city, cost), city, k)
+            })
+            .max_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(Ordering::Equal));
+
+        match best {
+            Some((value, _, _)) if objective == Objective::Profit && value <= MIN_GAIN => break,
+            Some((_, city, k)) => {
+                route.insert(k, city);
+                is_visited[city] = true;
+            }
+            None => break,
+        }
+    }
+
+    route
+}
+
+// one pass of the moves, true if any of them improved the tour
+fn improve_route(problem: &PrizeProblem, objective: Objective, route: &mut Vec<usize>) -> bool {
+    let mut improved = two_opt_route(problem, route);
+    improved |= drop_cities(problem, objective, route);
+    if objective == Objective::Profit {
+        improved |= add_cities(problem, route);
+    }
+    improved |= replace_cities(problem, objective, route);
+
+    improved
+}
+
+// reverses the segments of the route while it shortens it
+fn two_opt_route(problem: &PrizeProblem, route: &mut [usize]) -> bool {
+    let mut improved = false;
+    let mut found = true;
+
+    while found {
+        found = false;
+        // the segment i..=j is reversed, the edges before i and after j are replaced
+        for i in 0..route.len() {
+            for j in (i + 1)..route.len() {
+                let (p, s) = (before(problem, route, i), after(problem, route, j));
+                let delta = problem.distance(p, route[j]) + problem.distance(route[i], s)
+                    - problem.distance(p, route[i])
+                    - problem.distance(route[j], s);
+
+                if delta < -MIN_GAIN {
+                    route[i..=j].reverse();
+                    found = true;
+                    improved = true;
+                }
+            }
+        }
+    }
+
+    improved
+}
+
+// the length the tour saves without the city at the position `i`
+fn removal_saving(problem: &PrizeProblem, route: &[usize], i: usize) -> f32 {
+    let (p, s) = (before(problem, route, i), after(problem, route, i));
+    problem.distance(p, route[i]) + problem.distance(route[i], s) - problem.distance(p, s)
+}
+
+// drops the cities, which cost more than their prize, or which the quota doesn't need
+fn drop_cities(problem: &PrizeProblem, objective: Objective, route: &mut Vec<usize>) -> bool {
+    let mut improved = false;
+    let mut i = 0;
+
+    while i < route.len() {
+        let city = route[i];
+        let saving = removal_saving(problem, route, i);
+        let gains = match objective {
+            Objective::Profit => saving - problem.prizes[city] as f32 > MIN_GAIN,
+            Objective::Quota(quota) => {
+                saving > MIN_GAIN && problem.collected(route) - problem.prizes[city] >= quota
+            }
+        };
+
+        if gains {
+            route.remove(i);
+            improved = true;
+        } else {
+            i += 1;
+        }
+    }
+
+    improved
+}
+
+// adds the unvisited cities, whose prize is bigger than the cost of their cheapest place
+fn add_cities(problem: &PrizeProblem, route: &mut Vec<usize>) -> bool {
+    let mut improved = false;
+
+    for city in 0..problem.cities.len() {
+        if city == problem.depot || problem.prizes[city] == 0 || route.contains(&city) {
+            continue;
+        }
+
+        let (cost, k) = cheapest_insertion(problem, route, city);
+        if problem.prizes[city] as f32 - cost > MIN_GAIN {
+            route.insert(k, city);
+            improved = true;
+        }
+    }
+
+    improved
+}
+
+// replaces the visited city with the unvisited one at the same place, when the tour gains
+// and still collects the quota
+fn replace_cities(problem: &PrizeProblem, objective: Objective, route: &mut [usize]) -> bool {
+    let mut improved = false;
+    let mut is_visited = vec![false; problem.cities.len()];
+    is_visited[problem.depot] = true;
+    for city in route.iter() {
+        is_visited[*city] = true;
+    }
+
+    for i in 0..route.len() {
+        let city = route[i];
+        let (p, s) = (before(problem, route, i), after(problem, route, i));
+        let removed = problem.distance(p, city) + problem.distance(city, s);
+        let collected = problem.collected(route);
+
+        for other in 0..problem.cities.len() {
+            if is_visited[other] {
+                continue;
+            }
+
+            let delta = problem.distance(p, other) + problem.distance(other, s) - removed;
+            let prize_delta = problem.prizes[other] as f32 - problem.prizes[city] as f32;
+            let gains = match objective {
+                Objective::Profit => prize_delta - delta > MIN_GAIN,
+                Objective::Quota(quota) => {
+                    delta < -MIN_GAIN
+                        && collected - problem.prizes[city] + problem.prizes[other] >= quota
+                }
+            };
+
+            if gains {
+                route[i] = other;
+                is_visited[city] = false;
+                is_visited[other] = true;
+                improved = true;
+                break;
+            }
+        }
+    }
+
+    improved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    // the depot in the middle, 3 cities around it and the far one with the small prize
+    fn problem() -> PrizeProblem {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![0.0, 1.0],
+            vec![-1.0, 0.0],
+            vec![20.0, 0.0],
+        ]);
+
+        PrizeProblem::new(&cities, &[(1, 10), (2, 10), (3, 10), (4, 5)], 0).unwrap()
+    }
+
+    #[test]
+    fn test_solve_skips_city_which_costs_more_than_its_prize() {
+        let solution = solve(&problem(), Objective::Profit, &SolverOptions::default()).unwrap();
+
+        assert_eq!(0, solution.route[0]);
+        assert_eq!(4, solution.route.len());
+        assert!(!solution.route.contains(&4));
+        assert_eq!(30, solution.prize);
+        assert!((solution.profit() - (30.0 - 2.0 - 2.0 * 2f32.sqrt())).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_solve_collects_the_quota_with_shortest_tour() {
+        let options = SolverOptions::default();
+
+        let solution = solve(&problem(), Objective::Quota(20), &options).unwrap();
+        assert_eq!(3, solution.route.len());
+        assert!(solution.prize >= 20);
+        assert!((solution.length - (2.0 + 2f32.sqrt())).abs() < 0.001);
+
+        let solution = solve(&problem(), Objective::Quota(35), &options).unwrap();
+        assert!(solution.route.contains(&4));
+
+        assert!(solve(&problem(), Objective::Quota(36), &options).is_err());
+    }
+
+    #[test]
+    fn test_new_rejects_unknown_cities() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 0.0]]);
+
+        assert!(PrizeProblem::new(&cities, &[(1, 5)], 7).is_err());
+        assert!(PrizeProblem::new(&cities, &[(9, 5)], 0).is_err());
+        assert_eq!(
+            5,
+            PrizeProblem::new(&cities, &[(1, 5)], 0)
+                .unwrap()
+                .total_prize()
+        );
+    }
+}
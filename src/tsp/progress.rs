@@ -1,48 +1,48 @@
 use lazy_static::lazy_static;
-use std::sync::mpsc;
 use std::sync::Mutex;
 
-use piston::window::WindowSettings;
-//use piston::event_loop::{EventLoop, EventSettings, Events};
-use piston_window::*;
-
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::str::FromStr;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::Arc;
-use std::time::Duration;
 
 use super::route::Route;
-use super::KDPoint;
+
+// the progress window is optional, so teeline could be built on machines without display
+#[cfg(feature = "gui")]
+mod gui;
+#[cfg(feature = "gui")]
+pub use gui::ProgressPlot;
+mod animation;
+pub use animation::GifRecorder;
+pub mod events;
+pub use events::EventRecorder;
+mod jsonl;
+pub use jsonl::JsonlSink;
+mod term;
+pub use term::TermSink;
 
 pub type PublishChannel = Sender<ProgressMessage>;
 pub type ReceiverChannel = Receiver<ProgressMessage>;
-pub type PublisherFn = Arc<dyn Fn(ProgressMessage) -> ()>;
-
-type RGBA = [f32; 4];
-type RectCoords = [f64; 4];
-type Point2D = (f64, f64);
+// solvers report their progress through it
+pub type PublisherFn = Arc<dyn Fn(ProgressMessage) + Send + Sync>;
+// it may drop or rewrite the messages sent from the current thread
+pub type ProgressFilter = Arc<dyn Fn(ProgressMessage) -> Option<ProgressMessage> + Send + Sync>;
 
-const WHITE: RGBA = [1.0; 4];
-const RED: RGBA = [1.0, 0.0, 0.0, 0.9];
-const GREEN: RGBA = [0.0, 1.0, 0.0, 0.9];
-const BLUE: RGBA = [0.0, 0.0, 1.0, 0.9];
-const GREY: RGBA = [0.7, 0.7, 0.7, 0.9];
+pub const EPOCH_REPORT_INTERVAL: usize = 100;
 
-const ACTIVE_COLOR: RGBA = RED;
-const INACTIVE_COLOR: RGBA = GREY;
-const VISITED_COLOR: RGBA = GREEN;
-
-const FONT_SIZE: u32 = 16;
-
-const RENDER_FRQ: u64 = 5;
+thread_local! {
+    static THREAD_FILTER: RefCell<Option<ProgressFilter>> = RefCell::new(None);
+}
 
 lazy_static! {
     static ref PUBLISH_CHANNEL: Mutex<Option<PublishChannel>> = Mutex::new(None);
     static ref RECEIVER_CHANNEL: Mutex<Option<ReceiverChannel>> = Mutex::new(None);
 }
 
+#[cfg(feature = "gui")]
 fn init_channels() {
-    let (out_ch, in_ch) = mpsc::channel();
+    let (out_ch, in_ch) = std::sync::mpsc::channel();
     let mut mtx = PUBLISH_CHANNEL.lock().unwrap();
     *mtx = Some(out_ch);
 
@@ -58,421 +58,199 @@ pub fn get_publisher() -> Option<PublishChannel> {
 }
 
 pub fn send_progress(msg: ProgressMessage) {
-    let publish_ch = PUBLISH_CHANNEL.lock().unwrap();
-    if publish_ch.is_some() {
-        publish_ch
-            .clone()
-            .unwrap()
-            .send(msg)
-            .expect("Failed to publish message");
-    }
-}
-
-fn retrieve_message() -> Option<ProgressMessage> {
-    let ch = RECEIVER_CHANNEL.lock().unwrap();
+    let publish_ch = match get_publisher() {
+        Some(ch) => ch,
+        None => return,
+    };
 
-    ch.as_ref()
-        .map(|x| x.recv_timeout(Duration::from_millis(RENDER_FRQ)).ok())
-        .unwrap_or(None)
-}
+    let filtered_msg = THREAD_FILTER.with(|filter| match filter.borrow().as_ref() {
+        Some(filter_fn) => filter_fn(msg),
+        None => Some(msg),
+    });
 
-#[derive(Debug, Clone)]
-pub enum ProgressMessage {
-    CityChange(usize),
-    PathUpdate(Route, f32),
-    EpochUpdate(usize),
-    Done,
-    Restart,
+    if let Some(msg) = filtered_msg {
+        publish_ch.send(msg).expect("Failed to publish message");
+    }
 }
 
-trait Renderable {
-    fn render(&self, ctx: &Context, renderer: &mut G2d, glyphs: &mut Glyphs);
+/// ProgressSink receives the progress of the solvers; applications embedding teeline
+/// implement it to show the progress in their own way. Calls come from the solver thread,
+/// so the implementations should return quickly.
+pub trait ProgressSink: Send + Sync {
+    /// it is called every `EPOCH_REPORT_INTERVAL` epochs of the iterative solvers
+    fn on_epoch(&self, _epoch: usize) {}
 
-    fn belongs_to_city(&self, other_city_id: usize) -> bool;
+    /// the current temperature of the annealing, it comes before the epoch update
+    fn on_temperature(&self, _temperature: f32) {}
 
-    fn set_color(&mut self, new_color: RGBA);
-    fn is_edge(&self) -> bool;
-}
-
-#[derive(Debug, Clone)]
-struct Node {
-    city_id: Option<usize>,
-    x: f64,
-    y: f64,
-    height: f64,
-    width: f64,
-    color: RGBA,
-}
-
-impl Node {
-    fn new(city_id: Option<usize>, x: f64, y: f64, height: f64, width: f64, color: RGBA) -> Self {
-        Node {
-            city_id,
-            x,
-            y,
-            height,
-            width,
-            color,
-        }
-    }
+    /// the solver found a new route, the route may be also worse one, e.g for the annealing
+    fn on_improvement(&self, _route: &Route, _distance: f32) {}
 
-    fn to_rect(&self) -> RectCoords {
-        let half_h = self.height / 2.0;
-        let half_w = self.width / 2.0;
+    /// the solver is looking at the city
+    fn on_city_change(&self, _city_id: usize) {}
 
-        [self.x - half_w, self.y - half_h, self.width, self.height]
-    }
+    fn on_done(&self) {}
 }
 
-impl Renderable for Node {
-    fn render(&self, ctx: &Context, renderer: &mut G2d, glyphs: &mut Glyphs) {
-        rectangle::Rectangle::new(self.color).draw(
-            self.to_rect(),
-            &ctx.draw_state,
-            ctx.transform,
-            renderer,
-        );
-
-        TextBox::new(
-            format!("id.{:?}", self.city_id.unwrap_or(0)),
-            self.x,
-            self.y + FONT_SIZE as f64,
-            self.color,
-            FONT_SIZE,
-        )
-        .render(ctx, renderer, glyphs);
-    }
+/// forwards the progress into the global channel, which the `ProgressPlot` renders
+pub struct PlotSink;
 
-    fn belongs_to_city(&self, other_city_id: usize) -> bool {
-        self.city_id
-            .map(|c_id| c_id == other_city_id)
-            .unwrap_or(false)
+impl ProgressSink for PlotSink {
+    fn on_improvement(&self, route: &Route, distance: f32) {
+        send_progress(ProgressMessage::PathUpdate(route.clone(), distance));
     }
 
-    fn set_color(&mut self, new_color: RGBA) {
-        self.color = new_color;
+    fn on_city_change(&self, city_id: usize) {
+        send_progress(ProgressMessage::CityChange(city_id));
     }
 
-    fn is_edge(&self) -> bool {
-        false
+    fn on_done(&self) {
+        send_progress(ProgressMessage::Done);
     }
 }
 
-#[derive(Debug, Clone)]
-struct Edge {
-    from: Point2D,
-    to: Point2D,
-    width: f64,
-    color: RGBA,
+/// returns the publisher, which passes the messages of the solver to the sink
+pub fn publisher_for(sink: Arc<dyn ProgressSink>) -> PublisherFn {
+    Arc::new(move |msg| match msg {
+        ProgressMessage::EpochUpdate(epoch) => sink.on_epoch(epoch),
+        ProgressMessage::TemperatureUpdate(temperature) => sink.on_temperature(temperature),
+        ProgressMessage::PathUpdate(route, distance) => sink.on_improvement(&route, distance),
+        ProgressMessage::CityChange(city_id) => sink.on_city_change(city_id),
+        ProgressMessage::Done => sink.on_done(),
+        ProgressMessage::Restart => (),
+    })
 }
 
-impl Edge {
-    fn new(from: Point2D, to: Point2D, color: RGBA, width: f64) -> Self {
-        Edge {
-            from,
-            to,
-            width,
-            color,
-        }
-    }
-
-    fn to_line(&self) -> RectCoords {
-        [self.from.0, self.from.1, self.to.0, self.to.1]
-    }
+/// returns the publisher, which sends messages into the global progress channel
+#[cfg(feature = "gui")]
+pub fn default_publisher() -> PublisherFn {
+    publisher_for(Arc::new(PlotSink))
 }
 
-impl Renderable for Edge {
-    fn render(&self, ctx: &Context, renderer: &mut G2d, _glyphs: &mut Glyphs) {
-        line::Line::new(self.color, self.width).draw(
-            self.to_line(),
-            &ctx.draw_state,
-            ctx.transform,
-            renderer,
-        );
-    }
-
-    fn belongs_to_city(&self, _other_city_id: usize) -> bool {
-        false
-    }
-
-    fn set_color(&mut self, new_color: RGBA) {
-        self.color = new_color;
-    }
-
-    fn is_edge(&self) -> bool {
-        true
-    }
+/// returns the publisher, which drops all the messages as there is no window to show them
+#[cfg(not(feature = "gui"))]
+pub fn default_publisher() -> PublisherFn {
+    silent_publisher()
 }
 
-#[derive(Debug, Clone)]
-struct TextBox {
-    text: String,
-    x: f64,
-    y: f64,
-    font_size: u32,
-    color: RGBA,
+/// sends the epoch update after every `EPOCH_REPORT_INTERVAL` epochs,
+/// so the sinks are not called on every cheap move
+pub fn report_epoch(progress: &PublisherFn, epoch: usize) {
+    if epoch.is_multiple_of(EPOCH_REPORT_INTERVAL) {
+        progress(ProgressMessage::EpochUpdate(epoch));
+    }
 }
 
-impl TextBox {
-    pub fn new<S: Into<String>>(text: S, x: f64, y: f64, color: RGBA, font_size: u32) -> Self {
-        TextBox {
-            text: text.into(),
-            x,
-            y,
-            color,
-            font_size,
+/// returns the publisher, which passes every message to all the publishers
+pub fn broadcast(publishers: Vec<PublisherFn>) -> PublisherFn {
+    Arc::new(move |msg| {
+        for publish in publishers.iter() {
+            publish(msg.clone());
         }
-    }
+    })
 }
 
-impl Renderable for TextBox {
-    fn render(&self, ctx: &Context, renderer: &mut G2d, glyphs: &mut Glyphs) {
-        text::Text::new_color(self.color, self.font_size)
-            .draw(
-                self.text.as_ref(),
-                glyphs,
-                &ctx.draw_state,
-                ctx.transform.trans(self.x, self.y),
-                renderer,
-            )
-            .unwrap();
-    }
-
-    fn belongs_to_city(&self, _other_city_id: usize) -> bool {
-        false
-    }
-
-    fn set_color(&mut self, new_color: RGBA) {
-        self.color = new_color;
-    }
-
-    fn is_edge(&self) -> bool {
-        false
-    }
-}
-pub struct ProgressPlot {
-    city_table: HashMap<usize, KDPoint>,
-    shapes: Vec<Box<dyn Renderable>>,
-    viewport_dimensions: ViewportDimensions,
-    cities_bounding_box: RectCoords,
+/// returns the publisher, which drops all the messages
+pub fn silent_publisher() -> PublisherFn {
+    Arc::new(|_msg| {})
 }
 
-impl ProgressPlot {
-    pub fn new(cities: &[KDPoint], width: f64, height: f64, margin: f64) -> Self {
-        let mut plot = ProgressPlot {
-            city_table: HashMap::new(),
-            shapes: Vec::new(),
-            viewport_dimensions: ViewportDimensions::new(width, height, margin),
-            cities_bounding_box: cities_bounding_box(&cities),
-        };
-
-        plot.add_cities(cities);
-        plot.add_nodes();
-        plot
-    }
-
-    pub fn run(&mut self) {
-        init_channels();
-
-        let settings = WindowSettings::new("Teeline - TSP solver", self.window_size())
-            .exit_on_esc(true)
-            .resizable(false);
-
-        let mut window: PistonWindow = settings
-            .build()
-            .expect("ProgressPlot: Failed to create window");
-
-        let mut glyphs = window.load_font("./assets/FiraSans-Light.ttf").unwrap();
-
-        while let Some(e) = window.next() {
-            // render updates
-            window.draw_2d(&e, |ctx, renderer, device| {
-                clear(WHITE, renderer);
-
-                // render shapes
-                for shape in &self.shapes {
-                    shape.render(&ctx, renderer, &mut glyphs);
-                }
+/// sets the filter for messages sent from the current thread,
+/// so solvers running side by side dont flood the progress display
+pub fn set_thread_filter(filter: Option<ProgressFilter>) {
+    THREAD_FILTER.with(|current| *current.borrow_mut() = filter);
+}
 
-                // update glyphs before rendering
-                glyphs.factory.encoder.flush(device);
-            });
+/// how the binary shows the progress of the solver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressMode {
+    Window, // piston window, requires the gui feature
+    Term,   // status line in the terminal
+    Jsonl,  // one JSON object per event on the STDERR, for the scripts
+    Off,
+}
 
-            // update state
-            if let Some(msg) = retrieve_message() {
-                self.update(&msg);
-            }
-        }
+impl ProgressMode {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["window", "term", "jsonl", "off"]
     }
+}
 
-    fn update(&mut self, msg: &ProgressMessage) {
-        match msg {
-            ProgressMessage::Done => self.add_textbox(TextBox::new("Done", 100.0, 100.0, RED, 24)),
-            ProgressMessage::PathUpdate(route, _distance) => {
-                self.clean_path();
-                self.add_path(route);
-            }
-            ProgressMessage::CityChange(city_id) => self.highlight_city(city_id.clone()),
-            _ => println!("ProgressUpdate: {:?}", msg),
+impl Default for ProgressMode {
+    fn default() -> Self {
+        if cfg!(feature = "gui") {
+            ProgressMode::Window
+        } else {
+            ProgressMode::Off
         }
     }
+}
 
-    fn add_textbox(&mut self, textbox: TextBox) {
-        self.shapes.push(Box::new(textbox));
-    }
+impl FromStr for ProgressMode {
+    type Err = &'static str;
 
-    fn add_cities(&mut self, cities: &[KDPoint]) {
-        for city in cities.iter() {
-            self.city_table.insert(city.id, city.clone());
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "window" => Ok(ProgressMode::Window),
+            "term" => Ok(ProgressMode::Term),
+            "jsonl" => Ok(ProgressMode::Jsonl),
+            "off" => Ok(ProgressMode::Off),
+            _ => Err("unknown progress mode"),
         }
     }
+}
 
-    fn clean_path(&mut self) {
-        self.shapes.retain(|x| !x.is_edge());
-    }
-
-    fn add_path(&mut self, route: &Route) {
-        let path = route.route().to_vec();
-        let mut from_city_id = path[0];
-
-        for to_city_id in path.iter().skip(1) {
-            let from_city = self.city_table.get(&from_city_id);
-            let to_city = self.city_table.get(&to_city_id);
-
-            // only add the path if both city IDs exist
-            if from_city.is_some() && to_city.is_some() {
-                let new_edge = self.build_edge(&from_city.unwrap(), &to_city.unwrap(), GREY);
-                self.shapes.push(Box::new(new_edge));
-
-                from_city_id = to_city_id.clone();
-            }
-        }
-
-        // connect the last city and the first
-        let from_city = self.city_table.get(&from_city_id).unwrap();
-        let to_city = self.city_table.get(&path[0]).unwrap();
+#[derive(Debug, Clone)]
+pub enum ProgressMessage {
+    CityChange(usize),
+    PathUpdate(Route, f32),
+    EpochUpdate(usize),
+    TemperatureUpdate(f32),
+    Done,
+    Restart,
+}
 
-        let new_edge = self.build_edge(&from_city, &to_city, GREY);
-        self.shapes.push(Box::new(new_edge));
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn build_edge(&self, from_city: &KDPoint, to_city: &KDPoint, color: RGBA) -> Edge {
-        let from_point = scaled_point(
-            from_city,
-            &self.cities_bounding_box,
-            &self.viewport_dimensions,
-        );
-        let to_point = scaled_point(
-            to_city,
-            &self.cities_bounding_box,
-            &self.viewport_dimensions,
-        );
-
-        Edge::new(from_point, to_point, color, 2.0)
+    #[derive(Default)]
+    struct RecordingSink {
+        events: Mutex<Vec<String>>,
     }
 
-    fn add_nodes(&mut self) {
-        for city in self.city_table.values() {
-            let city_pt = scaled_point(city, &self.cities_bounding_box, &self.viewport_dimensions);
-            let shape = Node::new(
-                Some(city.id),
-                city_pt.0,
-                city_pt.1,
-                10.0,
-                10.0,
-                INACTIVE_COLOR,
-            );
-
-            self.shapes.push(Box::new(shape));
+    impl ProgressSink for RecordingSink {
+        fn on_epoch(&self, epoch: usize) {
+            self.events.lock().unwrap().push(format!("epoch {}", epoch));
         }
-    }
 
-    fn highlight_city(&mut self, city_id: usize) {
-        for shape in self.shapes.iter_mut() {
-            if shape.belongs_to_city(city_id) {
-                shape.set_color(ACTIVE_COLOR);
-            }
+        fn on_improvement(&self, route: &Route, distance: f32) {
+            let msg = format!("route {:?} {}", route.route(), distance);
+            self.events.lock().unwrap().push(msg);
         }
-    }
 
-    fn window_size(&self) -> [f64; 2] {
-        [
-            self.viewport_dimensions.width,
-            self.viewport_dimensions.height,
-        ]
-    }
-}
-
-// -- helper functions
-
-#[derive(Debug, Clone)]
-struct ViewportDimensions {
-    height: f64,
-    width: f64,
-    margin: f64,
-}
-
-impl ViewportDimensions {
-    fn new(width: f64, height: f64, margin: f64) -> Self {
-        ViewportDimensions {
-            height,
-            width,
-            margin,
+        fn on_done(&self) {
+            self.events.lock().unwrap().push("done".to_string());
         }
     }
-}
 
-fn scaled_point(point: &KDPoint, window: &RectCoords, viewport: &ViewportDimensions) -> Point2D {
-    point_to_viewport(point.x() as f64, point.y() as f64, window, viewport)
-}
+    #[test]
+    fn test_publisher_for_calls_the_sink() {
+        let sink = Arc::new(RecordingSink::default());
+        let publisher = publisher_for(sink.clone());
 
-fn cities_bounding_box(cities: &[KDPoint]) -> RectCoords {
-    let mut x_min = f32::MAX;
-    let mut x_max = f32::MIN;
-    let mut y_min = f32::MAX;
-    let mut y_max = f32::MIN;
-
-    for city in cities.iter() {
-        if let Some(x) = city.get(0) {
-            if x < x_min {
-                x_min = x;
-            }
-            if x > x_max {
-                x_max = x;
-            }
+        for epoch in 1..=EPOCH_REPORT_INTERVAL {
+            report_epoch(&publisher, epoch);
         }
+        publisher(ProgressMessage::PathUpdate(Route::new(&[1, 0]), 2.0));
+        publisher(ProgressMessage::CityChange(1));
+        publisher(ProgressMessage::Done);
 
-        if let Some(y) = city.get(1) {
-            if y < y_min {
-                y_min = y
-            }
-            if y > y_max {
-                y_max = y
-            }
-        }
+        let expected = vec![
+            format!("epoch {}", EPOCH_REPORT_INTERVAL),
+            "route [1, 0] 2".to_string(),
+            "done".to_string(),
+        ];
+        assert_eq!(expected, *sink.events.lock().unwrap());
     }
-
-    [x_min as f64, y_min as f64, x_max as f64, y_max as f64]
-}
-
-// converts EUC2D space into GUI coords [0..self.height, 0..self.width]
-fn point_to_viewport(
-    x: f64,
-    y: f64,
-    window: &RectCoords,
-    viewport: &ViewportDimensions,
-) -> Point2D {
-    let x_min = window[0];
-    let y_min = window[1];
-    let x_max = window[2];
-    let y_max = window[3];
-
-    // TODO: research how to scale viewport itself, so we can get rid of margin here
-    let x_v = (viewport.width - viewport.margin * 2.0) as f64 * (x - x_min) / (x_max - x_min);
-    let y_v = (viewport.height - viewport.margin * 2.0) as f64 * (y - y_min) / (y_max - y_min);
-
-    (
-        x_v + (viewport.margin as f64),
-        y_v + (viewport.margin as f64),
-    )
 }
@@ -0,0 +1,321 @@
+/// Animated GIF of the solver progress
+///
+/// The recorder keeps a fixed number of routes sent by the solver and rasterizes them
+/// off-screen once the solver is done, so it works also without the display.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::ProgressSink;
+use crate::tsp::route::Route;
+use crate::tsp::{Error, KDPoint};
+
+// the animation is sampled down to this many frames, whatever the number of updates
+pub const MAX_FRAMES: usize = 200;
+
+const FRAME_SIZE: u16 = 512;
+const MARGIN: f32 = 16.0;
+// frame delays are in 1/100 of second
+const FRAME_DELAY: u16 = 5;
+const LAST_FRAME_DELAY: u16 = 300;
+
+// indices of the palette
+const BACKGROUND: u8 = 0;
+const CITY: u8 = 1;
+const EDGE: u8 = 2;
+const PALETTE: [u8; 9] = [
+    255, 255, 255, // background
+    50, 50, 50, // city
+    220, 40, 40, // edge
+];
+
+#[derive(Debug)]
+struct Frames {
+    routes: Vec<Vec<usize>>,
+    last_route: Option<Vec<usize>>,
+    n_updates: usize,
+    // every nth update is kept, it doubles when the frames are sampled down
+    stride: usize,
+}
+
+impl Frames {
+    fn new() -> Self {
+        Frames {
+            routes: vec![],
+            last_route: None,
+            n_updates: 0,
+            stride: 1,
+        }
+    }
+
+    fn push(&mut self, route: &[usize]) {
+        if self.n_updates.is_multiple_of(self.stride) {
+            self.routes.push(route.to_vec());
+
+            if self.routes.len() >= MAX_FRAMES {
+                // keep every second frame, so the animation covers the whole run
+                self.routes = self.routes.iter().step_by(2).cloned().collect();
+                self.stride *= 2;
+            }
+        }
+
+        self.n_updates += 1;
+        self.last_route = Some(route.to_vec());
+    }
+
+    // the final route ends the animation, even if it fell between the sampled ones
+    fn to_vec(&self) -> Vec<Vec<usize>> {
+        let mut routes = self.routes.clone();
+
+        if let Some(last_route) = &self.last_route {
+            if routes.last() != Some(last_route) {
+                routes.push(last_route.clone());
+            }
+        }
+
+        routes
+    }
+}
+
+/// GifRecorder collects the routes of the solver and writes them as an animated GIF
+pub struct GifRecorder {
+    // pixel positions of the cities
+    positions: HashMap<usize, (i32, i32)>,
+    frames: Mutex<Frames>,
+}
+
+impl GifRecorder {
+    pub fn new(cities: &[KDPoint]) -> Self {
+        GifRecorder {
+            positions: pixel_positions(cities, FRAME_SIZE as f32),
+            frames: Mutex::new(Frames::new()),
+        }
+    }
+
+    pub fn n_frames(&self) -> usize {
+        self.frames.lock().unwrap().to_vec().len()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let file = File::create(path).map_err(Error::io("gif: failed to create file"))?;
+
+        self.write_to(BufWriter::new(file))
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let routes = self.frames.lock().unwrap().to_vec();
+        if routes.is_empty() {
+            return Err(Error::Runtime(
+                "the solver sent no routes to record".to_string(),
+            ));
+        }
+
+        let mut encoder =
+            gif::Encoder::new(writer, FRAME_SIZE, FRAME_SIZE, &PALETTE).map_err(gif_error)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(gif_error)?;
+
+        let n_routes = routes.len();
+        for (i, route) in routes.iter().enumerate() {
+            let pixels = self.rasterize(route);
+            let mut frame = gif::Frame::from_indexed_pixels(FRAME_SIZE, FRAME_SIZE, &pixels, None);
+            frame.delay = if i + 1 == n_routes {
+                LAST_FRAME_DELAY
+            } else {
+                FRAME_DELAY
+            };
+
+            encoder.write_frame(&frame).map_err(gif_error)?;
+        }
+
+        Ok(())
+    }
+
+    fn rasterize(&self, route: &[usize]) -> Vec<u8> {
+        let mut canvas = Canvas::new(FRAME_SIZE as usize);
+
+        let points: Vec<(i32, i32)> = route
+            .iter()
+            .filter_map(|city_id| self.positions.get(city_id).copied())
+            .collect();
+        for (i, from) in points.iter().enumerate() {
+            let to = points[(i + 1) % points.len()];
+            canvas.line(*from, to, EDGE);
+        }
+
+        for pos in self.positions.values() {
+            canvas.square(*pos, 2, CITY);
+        }
+
+        canvas.pixels
+    }
+}
+
+impl ProgressSink for GifRecorder {
+    fn on_improvement(&self, route: &Route, _distance: f32) {
+        self.frames.lock().unwrap().push(route.route());
+    }
+}
+
+fn gif_error(err: gif::EncodingError) -> Error {
+    Error::Runtime(format!("failed to encode the GIF: {}", err))
+}
+
+// fits the cities into the frame and keeps the aspect ratio; y axis points up
+fn pixel_positions(cities: &[KDPoint], size: f32) -> HashMap<usize, (i32, i32)> {
+    let min_x = cities.iter().map(|c| c.x()).fold(f64::INFINITY, f64::min);
+    let max_x = cities
+        .iter()
+        .map(|c| c.x())
+        .fold(f64::NEG_INFINITY, f64::max);
+    let min_y = cities.iter().map(|c| c.y()).fold(f64::INFINITY, f64::min);
+    let max_y = cities
+        .iter()
+        .map(|c| c.y())
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    let span = (max_x - min_x).max(max_y - min_y);
+    let scale = if span > 0.0 {
+        (size - 2.0 * MARGIN) as f64 / span
+    } else {
+        0.0
+    };
+
+    cities
+        .iter()
+        .map(|c| {
+            let x = MARGIN as f64 + (c.x() - min_x) * scale;
+            let y = (size - MARGIN) as f64 - (c.y() - min_y) * scale;
+            (c.id, (x.round() as i32, y.round() as i32))
+        })
+        .collect()
+}
+
+struct Canvas {
+    size: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(size: usize) -> Self {
+        Canvas {
+            size,
+            pixels: vec![BACKGROUND; size * size],
+        }
+    }
+
+    fn set(&mut self, x: i32, y: i32, color: u8) {
+        if x >= 0 && y >= 0 && (x as usize) < self.size && (y as usize) < self.size {
+            self.pixels[y as usize * self.size + x as usize] = color;
+        }
+    }
+
+    fn square(&mut self, center: (i32, i32), radius: i32, color: u8) {
+        for y in (center.1 - radius)..=(center.1 + radius) {
+            for x in (center.0 - radius)..=(center.0 + radius) {
+                self.set(x, y, color);
+            }
+        }
+    }
+
+    // Bresenham's line algorithm
+    fn line(&mut self, from: (i32, i32), to: (i32, i32), color: u8) {
+        let (mut x, mut y) = from;
+        let dx = (to.0 - x).abs();
+        let dy = -(to.1 - y).abs();
+        let step_x = if x < to.0 { 1 } else { -1 };
+        let step_y = if y < to.1 { 1 } else { -1 };
+        let mut err = dx + dy;
+
+        loop {
+            self.set(x, y, color);
+            if (x, y) == to {
+                break;
+            }
+
+            let err2 = 2 * err;
+            if err2 >= dy {
+                err += dy;
+                x += step_x;
+            }
+            if err2 <= dx {
+                err += dx;
+                y += step_y;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    fn square_cities() -> Vec<KDPoint> {
+        kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.0],
+        ])
+    }
+
+    #[test]
+    fn test_frames_are_sampled_down_to_the_budget() {
+        let recorder = GifRecorder::new(&square_cities());
+
+        for i in 0..(10 * MAX_FRAMES) {
+            let route = if i % 2 == 0 {
+                [0, 1, 2, 3]
+            } else {
+                [0, 2, 1, 3]
+            };
+            recorder.on_improvement(&Route::new(&route), 4.0);
+        }
+
+        assert!(recorder.n_frames() <= MAX_FRAMES);
+        assert!(recorder.n_frames() >= MAX_FRAMES / 4);
+
+        let frames = recorder.frames.lock().unwrap().to_vec();
+        assert_eq!(&vec![0, 2, 1, 3], frames.last().unwrap());
+    }
+
+    #[test]
+    fn test_rasterize_draws_the_closed_route() {
+        let recorder = GifRecorder::new(&square_cities());
+        let pixels = recorder.rasterize(&[0, 1, 2, 3]);
+        let size = FRAME_SIZE as usize;
+        let low = MARGIN as usize;
+        let high = size - MARGIN as usize;
+
+        // the middle of every side of the square
+        assert_eq!(EDGE, pixels[high * size + size / 2]);
+        assert_eq!(EDGE, pixels[low * size + size / 2]);
+        assert_eq!(EDGE, pixels[(size / 2) * size + low]);
+        assert_eq!(EDGE, pixels[(size / 2) * size + high]);
+        assert_eq!(CITY, pixels[low * size + low]);
+        assert_eq!(BACKGROUND, pixels[(size / 2) * size + size / 2]);
+    }
+
+    #[test]
+    fn test_write_to_encodes_gif() {
+        let recorder = GifRecorder::new(&square_cities());
+        recorder.on_improvement(&Route::new(&[0, 2, 1, 3]), 4.8);
+        recorder.on_improvement(&Route::new(&[0, 1, 2, 3]), 4.0);
+
+        let mut buf = vec![];
+        recorder.write_to(&mut buf).unwrap();
+
+        assert_eq!(b"GIF89a", &buf[0..6]);
+    }
+
+    #[test]
+    fn test_write_to_fails_without_routes() {
+        let recorder = GifRecorder::new(&square_cities());
+
+        assert!(recorder.write_to(vec![]).is_err());
+    }
+}
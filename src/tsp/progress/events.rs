@@ -0,0 +1,299 @@
+/// Recording of the progress messages
+///
+/// The recorder writes the cities and every progress message of the solver into a file,
+/// one record per line as tab separated values (tabs are shown as spaces):
+///
+/// ```text
+/// # elapsed_us event values
+/// 0       city    1    565    575
+/// 0       city    2    25     185
+/// 120     path    7542.5    1 2 3
+/// 540     epoch   100
+/// 541     temp    0.25
+/// 900     done
+/// ```
+///
+/// The cities come first, so the recording can be replayed without the problem file.
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{ProgressMessage, ProgressSink, PublisherFn};
+use crate::tsp::route::Route;
+use crate::tsp::{CancelFlag, Error, KDPoint};
+
+const HEADER: &str = "# elapsed_us event values";
+
+#[derive(Debug, Clone)]
+pub struct TimedMessage {
+    pub elapsed_us: u128,
+    pub message: ProgressMessage,
+}
+
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub cities: Vec<KDPoint>,
+    pub messages: Vec<TimedMessage>,
+}
+
+/// EventRecorder writes every progress message with its timestamp into the file
+pub struct EventRecorder {
+    writer: Mutex<BufWriter<File>>,
+    started_at: Instant,
+}
+
+impl EventRecorder {
+    pub fn create(path: &Path, cities: &[KDPoint]) -> Result<Self, Error> {
+        let f = File::create(path).map_err(Error::io("events: failed to create file"))?;
+        let mut writer = BufWriter::new(f);
+
+        writeln!(writer, "{}", HEADER).map_err(Error::io("events: failed to write"))?;
+        for city in cities {
+            writeln!(writer, "0\tcity\t{}\t{}\t{}", city.id, city.x(), city.y())
+                .map_err(Error::io("events: failed to write"))?;
+        }
+
+        Ok(EventRecorder {
+            writer: Mutex::new(writer),
+            started_at: Instant::now(),
+        })
+    }
+
+    pub fn record(&self, msg: &ProgressMessage) {
+        let line = format!(
+            "{}\t{}",
+            self.started_at.elapsed().as_micros(),
+            encode_message(msg)
+        );
+
+        // a broken recording should not stop the search
+        let mut writer = self.writer.lock().unwrap();
+        writeln!(writer, "{}", line).unwrap_or_default();
+    }
+
+    pub fn finish(&self) -> Result<(), Error> {
+        self.writer
+            .lock()
+            .unwrap()
+            .flush()
+            .map_err(Error::io("events: failed to write"))
+    }
+}
+
+impl ProgressSink for EventRecorder {
+    fn on_epoch(&self, epoch: usize) {
+        self.record(&ProgressMessage::EpochUpdate(epoch));
+    }
+
+    fn on_temperature(&self, temperature: f32) {
+        self.record(&ProgressMessage::TemperatureUpdate(temperature));
+    }
+
+    fn on_improvement(&self, route: &Route, distance: f32) {
+        self.record(&ProgressMessage::PathUpdate(route.clone(), distance));
+    }
+
+    fn on_city_change(&self, city_id: usize) {
+        self.record(&ProgressMessage::CityChange(city_id));
+    }
+
+    fn on_done(&self) {
+        self.record(&ProgressMessage::Done);
+    }
+}
+
+fn encode_message(msg: &ProgressMessage) -> String {
+    match msg {
+        ProgressMessage::CityChange(city_id) => format!("visit\t{}", city_id),
+        ProgressMessage::EpochUpdate(epoch) => format!("epoch\t{}", epoch),
+        ProgressMessage::TemperatureUpdate(temperature) => format!("temp\t{}", temperature),
+        ProgressMessage::Done => "done".to_string(),
+        ProgressMessage::Restart => "restart".to_string(),
+        ProgressMessage::PathUpdate(route, distance) => {
+            let mut line = format!("path\t{}\t", distance);
+            for (i, city_id) in route.route().iter().enumerate() {
+                if i > 0 {
+                    line.push(' ');
+                }
+                write!(line, "{}", city_id).unwrap_or_default();
+            }
+            line
+        }
+    }
+}
+
+pub fn read_from_file(path: &Path) -> Result<Recording, Error> {
+    let f = File::open(path).map_err(Error::io("events: failed to open file"))?;
+
+    read_recording(BufReader::new(f))
+}
+
+fn read_recording<R: BufRead>(reader: R) -> Result<Recording, Error> {
+    let mut recording = Recording {
+        cities: vec![],
+        messages: vec![],
+    };
+
+    for (line_no, line_res) in reader.lines().enumerate() {
+        let line =
+            line_res.map_err(|_| Error::parse("events", line_no + 1, "failed to read line"))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() > 1 && fields[1] == "city" {
+            let city = parse_city(&fields[2..])
+                .ok_or_else(|| Error::parse("events", line_no + 1, "invalid city"))?;
+            recording.cities.push(city);
+            continue;
+        }
+
+        let msg = parse_message(&fields)
+            .ok_or_else(|| Error::parse("events", line_no + 1, "invalid event"))?;
+        recording.messages.push(msg);
+    }
+
+    if recording.cities.is_empty() {
+        return Err(Error::InvalidInput(
+            "events: the recording has no cities".to_string(),
+        ));
+    }
+
+    Ok(recording)
+}
+
+fn parse_city(fields: &[&str]) -> Option<KDPoint> {
+    if fields.len() != 3 {
+        return None;
+    }
+
+    let id = fields[0].parse().ok()?;
+    let x = fields[1].parse().ok()?;
+    let y = fields[2].parse().ok()?;
+
+    Some(KDPoint::new_with_id(id, &[x, y]))
+}
+
+fn parse_message(fields: &[&str]) -> Option<TimedMessage> {
+    let elapsed_us = fields.first()?.parse().ok()?;
+
+    let message = match (fields.get(1).copied()?, &fields[2..]) {
+        ("visit", [city_id]) => ProgressMessage::CityChange(city_id.parse().ok()?),
+        ("epoch", [epoch]) => ProgressMessage::EpochUpdate(epoch.parse().ok()?),
+        ("temp", [temperature]) => ProgressMessage::TemperatureUpdate(temperature.parse().ok()?),
+        ("done", []) => ProgressMessage::Done,
+        ("restart", []) => ProgressMessage::Restart,
+        ("path", [distance, ids]) => {
+            let path = ids
+                .split_whitespace()
+                .map(|id| id.parse().ok())
+                .collect::<Option<Vec<usize>>>()?;
+
+            ProgressMessage::PathUpdate(Route::new(&path), distance.parse().ok()?)
+        }
+        _ => return None,
+    };
+
+    Some(TimedMessage {
+        elapsed_us,
+        message,
+    })
+}
+
+/// sends the recorded messages to the publisher, keeping the recorded pauses between them;
+/// the speed 2.0 replays it twice as fast
+pub fn replay(messages: &[TimedMessage], publisher: &PublisherFn, speed: f32, cancel: &CancelFlag) {
+    let started_at = Instant::now();
+
+    for timed in messages {
+        if cancel.is_cancelled() {
+            break;
+        }
+
+        let due = Duration::from_secs_f64(timed.elapsed_us as f64 / 1_000_000.0 / speed as f64);
+        if let Some(pause) = due.checked_sub(started_at.elapsed()) {
+            thread::sleep(pause);
+        }
+
+        publisher(timed.message.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_and_parse_message() {
+        let messages = vec![
+            ProgressMessage::PathUpdate(Route::new(&[3, 1, 2]), 12.5),
+            ProgressMessage::CityChange(2),
+            ProgressMessage::EpochUpdate(100),
+            ProgressMessage::TemperatureUpdate(0.25),
+            ProgressMessage::Restart,
+            ProgressMessage::Done,
+        ];
+
+        for msg in messages {
+            let line = format!("42\t{}", encode_message(&msg));
+            let fields: Vec<&str> = line.split('\t').collect();
+            let parsed = parse_message(&fields).unwrap();
+
+            assert_eq!(42, parsed.elapsed_us);
+            assert_eq!(encode_message(&msg), encode_message(&parsed.message));
+        }
+    }
+
+    #[test]
+    fn test_read_recording() {
+        let text = "# elapsed_us event values\n\
+                    0\tcity\t1\t0\t0\n\
+                    0\tcity\t2\t3\t4\n\
+                    10\tpath\t10\t1 2\n\
+                    20\tdone\n";
+
+        let recording = read_recording(text.as_bytes()).unwrap();
+
+        assert_eq!(2, recording.cities.len());
+        assert_eq!(2, recording.cities[1].id);
+        assert_eq!(4.0, recording.cities[1].y());
+        assert_eq!(2, recording.messages.len());
+        assert_eq!(20, recording.messages[1].elapsed_us);
+    }
+
+    #[test]
+    fn test_read_recording_reports_bad_line() {
+        let text = "0\tcity\t1\t0\t0\n10\tpath\tten\t1\n";
+
+        let err = read_recording(text.as_bytes()).unwrap_err();
+
+        assert_eq!("events: invalid event on line.2", err.to_string());
+    }
+
+    #[test]
+    fn test_replay_sends_messages_in_order() {
+        let messages: Vec<TimedMessage> = (1..=3)
+            .map(|epoch| TimedMessage {
+                elapsed_us: epoch as u128 * 1000,
+                message: ProgressMessage::EpochUpdate(epoch),
+            })
+            .collect();
+        let received = std::sync::Arc::new(Mutex::new(vec![]));
+        let sink = received.clone();
+        let publisher: PublisherFn = std::sync::Arc::new(move |msg| {
+            if let ProgressMessage::EpochUpdate(epoch) = msg {
+                sink.lock().unwrap().push(epoch);
+            }
+        });
+
+        replay(&messages, &publisher, 10.0, &CancelFlag::new());
+
+        assert_eq!(vec![1, 2, 3], *received.lock().unwrap());
+    }
+}
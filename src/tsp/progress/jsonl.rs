@@ -0,0 +1,160 @@
+/// JSON-lines progress stream
+///
+/// Every event is a single JSON object on its own line of the STDERR, so the dashboards and the
+/// scripts can follow the run with `tail -f` or read it line by line:
+///
+/// ```text
+/// {"event": "improvement", "solver": "simulated_annealing", "epoch": 1200, "cost": 8012.5, "elapsed_ms": 15}
+/// {"event": "epoch", "solver": "simulated_annealing", "epoch": 1300, "cost": 8012.5, "elapsed_ms": 16}
+/// {"event": "done", "solver": "simulated_annealing", "epoch": 10000, "cost": 7703.2, "elapsed_ms": 98}
+/// ```
+///
+/// The cost is the best one so far, it is `null` until the first route.
+use std::io::{Stderr, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::ProgressSink;
+use crate::tsp::clock::Instant;
+use crate::tsp::json;
+use crate::tsp::route::Route;
+
+// the epoch events are written at most this often, the improvements always
+const EPOCH_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+struct JsonlState {
+    epoch: usize,
+    best_distance: Option<f32>,
+    started_at: Instant,
+    epoch_written_at: Option<Instant>,
+}
+
+/// JsonlSink writes the improvements, the epochs and the end of the run as JSON lines
+pub struct JsonlSink<W: Write + Send = Stderr> {
+    solver: String,
+    state: Mutex<JsonlState>,
+    writer: Mutex<W>,
+}
+
+impl JsonlSink {
+    /// writes the events of the solver into the STDERR
+    pub fn new(solver: &str) -> Self {
+        JsonlSink::with_writer(solver, std::io::stderr())
+    }
+}
+
+impl<W: Write + Send> JsonlSink<W> {
+    pub fn with_writer(solver: &str, writer: W) -> Self {
+        JsonlSink {
+            solver: solver.to_string(),
+            state: Mutex::new(JsonlState {
+                epoch: 0,
+                best_distance: None,
+                started_at: Instant::now(),
+                epoch_written_at: None,
+            }),
+            writer: Mutex::new(writer),
+        }
+    }
+
+    fn write_event(&self, event: &str, state: &JsonlState) {
+        let cost = state
+            .best_distance
+            .map_or("null".to_string(), |distance| distance.to_string());
+        let line = format!(
+            "{{\"event\": \"{}\", \"solver\": \"{}\", \"epoch\": {}, \"cost\": {}, \"elapsed_ms\": {}}}",
+            event,
+            json::escape(&self.solver),
+            state.epoch,
+            cost,
+            state.started_at.elapsed().as_millis()
+        );
+
+        if let Ok(mut writer) = self.writer.lock() {
+            // a closed pipe should not stop the search
+            writeln!(writer, "{}", line).unwrap_or_default();
+            writer.flush().unwrap_or_default();
+        }
+    }
+}
+
+impl<W: Write + Send> ProgressSink for JsonlSink<W> {
+    fn on_epoch(&self, epoch: usize) {
+        if let Ok(mut state) = self.state.lock() {
+            state.epoch = epoch;
+
+            let is_due = state
+                .epoch_written_at
+                .is_none_or(|written_at| written_at.elapsed() >= EPOCH_INTERVAL);
+            if is_due {
+                state.epoch_written_at = Some(Instant::now());
+                self.write_event("epoch", &state);
+            }
+        }
+    }
+
+    fn on_improvement(&self, _route: &Route, distance: f32) {
+        if let Ok(mut state) = self.state.lock() {
+            // annealing reports the accepted worse routes too
+            if state.best_distance.is_none_or(|best| distance < best) {
+                state.best_distance = Some(distance);
+                self.write_event("improvement", &state);
+            }
+        }
+    }
+
+    fn on_done(&self) {
+        if let Ok(state) = self.state.lock() {
+            self.write_event("done", &state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written_lines(sink: &JsonlSink<Vec<u8>>) -> Vec<String> {
+        let written = sink.writer.lock().unwrap();
+        String::from_utf8(written.clone())
+            .unwrap()
+            .lines()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_sink_writes_one_object_per_line() {
+        let sink = JsonlSink::with_writer("sa \"fast\"", vec![]);
+        let route = Route::new(&[0, 1]);
+
+        sink.on_epoch(100);
+        sink.on_improvement(&route, 10.5);
+        sink.on_improvement(&route, 12.0);
+        sink.on_epoch(200);
+        sink.on_improvement(&route, 8.0);
+        sink.on_done();
+
+        let lines = written_lines(&sink);
+        let events: Vec<String> = lines
+            .iter()
+            .map(|line| {
+                json::parse(line).unwrap()["event"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        // the worse route and the epoch right after the first one are left out
+        assert_eq!(vec!["epoch", "improvement", "improvement", "done"], events);
+
+        assert!(lines[0].starts_with(
+            "{\"event\": \"epoch\", \"solver\": \"sa \\\"fast\\\"\", \"epoch\": 100, \"cost\": null, \"elapsed_ms\": "
+        ));
+
+        let done = json::parse(&lines[3]).unwrap();
+        assert_eq!(Some(200), done["epoch"].as_integer());
+        assert_eq!(Some(8), done["cost"].as_integer());
+    }
+}
@@ -0,0 +1,170 @@
+/// Terminal progress display
+///
+/// It keeps a single status line on the STDERR, so the progress could be followed over SSH;
+/// the solution is still printed into STDOUT.
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::ProgressSink;
+use crate::tsp::route::Route;
+
+// how often the status line is redrawn
+const RENDER_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+struct TermState {
+    epoch: usize,
+    best_distance: Option<f32>,
+    temperature: Option<f32>,
+    n_improvements: usize,
+    started_at: Instant,
+    rendered_at: Option<Instant>,
+}
+
+impl TermState {
+    fn new() -> Self {
+        TermState {
+            epoch: 0,
+            best_distance: None,
+            temperature: None,
+            n_improvements: 0,
+            started_at: Instant::now(),
+            rendered_at: None,
+        }
+    }
+
+    fn status_line(&self) -> String {
+        let mut line = format!("epoch {:>10}", self.epoch);
+
+        match self.best_distance {
+            Some(distance) => line.push_str(&format!("  best {:>12.2}", distance)),
+            None => line.push_str(&format!("  best {:>12}", "-")),
+        }
+
+        if let Some(temperature) = self.temperature {
+            line.push_str(&format!("  temp {:>10.4}", temperature));
+        }
+
+        line.push_str(&format!("  {:>8.1} impr/s", self.improvement_rate()));
+        line
+    }
+
+    fn improvement_rate(&self) -> f32 {
+        let secs = self.started_at.elapsed().as_secs_f32();
+        if secs > 0.0 {
+            self.n_improvements as f32 / secs
+        } else {
+            0.0
+        }
+    }
+
+    fn should_render(&self) -> bool {
+        match self.rendered_at {
+            Some(rendered_at) => rendered_at.elapsed() >= RENDER_INTERVAL,
+            None => true,
+        }
+    }
+}
+
+/// TermSink shows the epoch, the best distance, the temperature of the annealing
+/// and the rate of improvements on a single line of the terminal
+pub struct TermSink {
+    state: Mutex<TermState>,
+}
+
+impl TermSink {
+    pub fn new() -> Self {
+        TermSink {
+            state: Mutex::new(TermState::new()),
+        }
+    }
+
+    fn render(&self, state: &mut TermState, force: bool) {
+        if !force && !state.should_render() {
+            return;
+        }
+
+        state.rendered_at = Some(Instant::now());
+        let mut stderr = std::io::stderr();
+        // a broken terminal should not stop the search
+        write!(stderr, "\r{}", state.status_line()).unwrap_or_default();
+        stderr.flush().unwrap_or_default();
+    }
+}
+
+impl Default for TermSink {
+    fn default() -> Self {
+        TermSink::new()
+    }
+}
+
+impl ProgressSink for TermSink {
+    fn on_epoch(&self, epoch: usize) {
+        if let Ok(mut state) = self.state.lock() {
+            state.epoch = epoch;
+            self.render(&mut state, false);
+        }
+    }
+
+    fn on_temperature(&self, temperature: f32) {
+        if let Ok(mut state) = self.state.lock() {
+            state.temperature = Some(temperature);
+        }
+    }
+
+    fn on_improvement(&self, _route: &Route, distance: f32) {
+        if let Ok(mut state) = self.state.lock() {
+            // annealing reports the accepted worse routes too
+            if state.best_distance.is_none_or(|best| distance < best) {
+                state.best_distance = Some(distance);
+                state.n_improvements += 1;
+                self.render(&mut state, false);
+            }
+        }
+    }
+
+    fn on_done(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            self.render(&mut state, true);
+            eprintln!();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_line_shows_known_values() {
+        let mut state = TermState::new();
+        assert!(state
+            .status_line()
+            .starts_with("epoch          0  best            -"));
+        assert!(!state.status_line().contains("temp"));
+
+        state.epoch = 300;
+        state.best_distance = Some(1234.5);
+        state.temperature = Some(0.25);
+        let line = state.status_line();
+        assert!(line.contains("epoch        300"));
+        assert!(line.contains("best      1234.50"));
+        assert!(line.contains("temp     0.2500"));
+        assert!(line.ends_with("impr/s"));
+    }
+
+    #[test]
+    fn test_sink_counts_only_improvements() {
+        let sink = TermSink::new();
+        let route = Route::new(&[0, 1]);
+
+        sink.on_improvement(&route, 10.0);
+        sink.on_improvement(&route, 12.0);
+        sink.on_improvement(&route, 8.0);
+
+        let state = sink.state.lock().unwrap();
+        assert_eq!(Some(8.0), state.best_distance);
+        assert_eq!(2, state.n_improvements);
+    }
+}
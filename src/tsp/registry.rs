@@ -0,0 +1,145 @@
+/// Registry of the solvers
+///
+/// It lists the built-in solvers and the registered plugins with their metadata,
+/// so the command-line and the applications embedding teeline don't keep their own lists.
+/// Every solver describes itself with `TspSolver::metadata`:
+///
+/// ```ignore
+/// SolverInfo::new("two_opt", &["2opt"], "applies improving 2-opt moves", false)
+///     .with_category(Category::LocalSearch)
+///     .with_complexity("O(n^2) per pass")
+///     .with_options(&["max_seconds", "target_cost"])
+/// ```
+use std::fmt;
+
+use std::sync::Arc;
+
+use super::pipeline::{self, Pipeline};
+use super::plugin::{self, SharedSolver};
+use super::{Error, SolverInfo, Solvers};
+
+/// what kind of algorithm the solver is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Exact,         // always finds the optimal tour
+    Constructive,  // builds a single tour
+    LocalSearch,   // improves the tour until there is no better neighbor
+    Metaheuristic, // guided search, which may also accept worse tours
+    Unspecified,
+}
+
+impl Category {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Exact => "exact",
+            Category::Constructive => "constructive",
+            Category::LocalSearch => "local_search",
+            Category::Metaheuristic => "metaheuristic",
+            Category::Unspecified => "unspecified",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// returns the built-in solvers in the order of `Solvers`
+pub fn builtin() -> Vec<SharedSolver> {
+    Solvers::all().iter().filter_map(|s| s.solver()).collect()
+}
+
+/// returns the built-in solvers and the plugins
+pub fn solvers() -> Vec<SharedSolver> {
+    let mut solvers = builtin();
+    solvers.extend(plugin::solvers());
+
+    solvers
+}
+
+/// finds the solver by its name or alias, a plugin with the same name wins over the built-in
+pub fn find(name: &str) -> Option<SharedSolver> {
+    plugin::find(name).or_else(|| {
+        builtin()
+            .into_iter()
+            .find(|solver| solver.metadata().has_name(name))
+    })
+}
+
+/// finds the solver like `find`, the names joined with `+` give the pipeline of the solvers
+pub fn resolve(name: &str) -> Result<SharedSolver, Error> {
+    if pipeline::is_pipeline(name) {
+        return Ok(Arc::new(Pipeline::parse(name)?));
+    }
+
+    find(name).ok_or_else(|| {
+        Error::InvalidInput(format!(
+            "unknown solver `{}`, available solvers: {}",
+            name,
+            names().join(", ")
+        ))
+    })
+}
+
+/// returns the metadata of all the solvers
+pub fn catalog() -> Vec<SolverInfo> {
+    solvers().iter().map(|solver| solver.metadata()).collect()
+}
+
+/// returns the names and the aliases of all the solvers
+pub fn names() -> Vec<String> {
+    let mut names = vec![];
+    for info in catalog() {
+        names.push(info.name);
+        names.extend(info.aliases);
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_solvers_are_described() {
+        let infos: Vec<SolverInfo> = builtin().iter().map(|s| s.metadata()).collect();
+
+        assert_eq!(Solvers::all().len(), infos.len());
+        for info in infos.iter() {
+            assert_ne!(Category::Unspecified, info.category, "{}", info.name);
+            assert!(!info.complexity.is_empty(), "{}", info.name);
+        }
+    }
+
+    #[test]
+    fn test_find_by_name_and_alias() {
+        assert_eq!("simulated_annealing", find("sa").unwrap().metadata().name);
+        assert_eq!("two_opt", find("two_opt").unwrap().metadata().name);
+        assert!(find("unknown").is_none());
+    }
+
+    #[test]
+    fn test_resolve_solvers_and_pipelines() {
+        assert_eq!("two_opt", resolve("2opt").unwrap().metadata().name);
+        assert_eq!(
+            "nearest_neighbor+two_opt",
+            resolve("nn+2opt").unwrap().metadata().name
+        );
+
+        let err = resolve("unknown").err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("unknown solver `unknown`, available solvers: "));
+    }
+
+    #[test]
+    fn test_names_include_aliases() {
+        let names = names();
+
+        assert!(names.contains(&"bellman_karp".to_string()));
+        assert!(names.contains(&"bhk".to_string()));
+    }
+}
@@ -0,0 +1,136 @@
+/// Remote instances
+///
+/// The input given as `http://` or `https://` URL is downloaded once into the cache directory,
+/// `$XDG_CACHE_HOME/teeline` or `~/.cache/teeline`, and read from there on the next runs:
+///
+/// ```text
+/// teeline 2opt -i http://comopt.ifi.uni-heidelberg.de/software/TSPLIB95/tsp/pcb442.tsp.gz
+/// ```
+///
+/// The download needs the `remote` feature, the cached files are found without it.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::Error;
+
+/// true when the input is the URL of the file, not the path
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// the directory the downloaded files are kept in
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+    Some(cache_home.join("teeline"))
+}
+
+/// the path of the cached file, the host and the path of the URL, e.g
+/// `http://example.com/tsp/pcb442.tsp` is kept at `<cache_dir>/example.com/tsp/pcb442.tsp`
+pub fn cache_path(cache_dir: &Path, url: &str) -> Result<PathBuf, Error> {
+    let invalid = || Error::InvalidInput(format!("remote: {} is not the URL of a file", url));
+
+    let (_, rest) = url.split_once("://").ok_or_else(invalid)?;
+    let rest = rest.split(['?', '#']).next().unwrap_or("");
+    let segments: Vec<&str> = rest.split('/').filter(|s| !s.is_empty()).collect();
+    // the host and the file name at least
+    if segments.len() < 2 || segments.iter().any(|s| *s == "." || *s == "..") {
+        return Err(invalid());
+    }
+
+    let host = segments[0].replace(':', "_");
+    let path = segments[1..]
+        .iter()
+        .fold(cache_dir.join(host), |path, segment| path.join(segment));
+
+    Ok(path)
+}
+
+/// returns the path of the cached copy of the file, downloads it when it is not cached yet
+pub fn fetch(url: &str) -> Result<PathBuf, Error> {
+    let cache_dir = cache_dir().ok_or_else(|| {
+        Error::InvalidInput("remote: found no cache directory, set HOME or XDG_CACHE_HOME".into())
+    })?;
+
+    fetch_into(&cache_dir, url)
+}
+
+/// same as `fetch`, but keeps the files in the given directory
+pub fn fetch_into(cache_dir: &Path, url: &str) -> Result<PathBuf, Error> {
+    let path = cache_path(cache_dir, url)?;
+    if path.exists() {
+        return Ok(path);
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(Error::io("remote: failed to create cache directory"))?;
+    }
+
+    // the interrupted download must not be found as the cached file
+    let partial_path = path.with_extension("part");
+    download(url, &partial_path)?;
+    fs::rename(&partial_path, &path).map_err(Error::io("remote: failed to cache the file"))?;
+
+    Ok(path)
+}
+
+#[cfg(feature = "remote")]
+fn download(url: &str, path: &Path) -> Result<(), Error> {
+    let response = ureq::get(url)
+        .call()
+        .map_err(|err| Error::Runtime(format!("remote: failed to download {}: {}", url, err)))?;
+
+    let mut f = fs::File::create(path).map_err(Error::io("remote: failed to create file"))?;
+    let res = std::io::copy(&mut response.into_reader(), &mut f);
+    if let Err(err) = res {
+        let _ = fs::remove_file(path);
+        return Err(Error::io("remote: failed to download")(err));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "remote"))]
+fn download(url: &str, _path: &Path) -> Result<(), Error> {
+    Err(Error::InvalidInput(format!(
+        "remote: teeline is built without the `remote` feature, download {} by hand",
+        url
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_path_keeps_the_host_and_the_path() {
+        let dir = Path::new("/cache");
+
+        let path = cache_path(dir, "https://example.com:8080/tsp/pcb442.tsp?raw=1").unwrap();
+        assert_eq!(Path::new("/cache/example.com_8080/tsp/pcb442.tsp"), path);
+
+        assert!(cache_path(dir, "https://example.com/").is_err());
+        assert!(cache_path(dir, "https://example.com/../etc/passwd").is_err());
+        assert!(is_url("http://example.com/a.tsp"));
+        assert!(!is_url("data/a.tsp"));
+    }
+
+    #[test]
+    fn test_fetch_finds_the_cached_file() {
+        let dir = env::temp_dir().join(format!("teeline_cache_{}", std::process::id()));
+        let url = "http://example.com/tsp/square.tsp";
+        let cached = cache_path(&dir, url).unwrap();
+        fs::create_dir_all(cached.parent().unwrap()).unwrap();
+        fs::write(&cached, "NAME: square\n").unwrap();
+
+        let res = fetch_into(&dir, url);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(cached, res.unwrap());
+    }
+}
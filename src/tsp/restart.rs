@@ -0,0 +1,516 @@
+/// Random-restart runner
+///
+/// It re-runs the stochastic solver several times and keeps the best solution,
+/// as every run starts from a different random state it may end up in a different local optimum.
+///
+/// The runs are independent, so `run_parallel` executes them on a pool of threads.
+/// The result keeps the cost and the time of every run, `--repeats` prints their spread.
+use rayon::prelude::*;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::clock::Instant;
+use super::kdtree::KDPoint;
+use super::progress::{self, send_progress, ProgressFilter, ProgressMessage};
+use super::route::Route;
+use super::{city_table_from_vec, CityTable, Error, Solution, SolverOptions};
+
+pub struct RestartResult {
+    pub best: Solution,
+    pub costs: Vec<f32>,   // total distance of each run, in the order of runs
+    pub seconds: Vec<f64>, // wall time of each run, in the same order
+}
+
+impl RestartResult {
+    pub fn best_run(&self) -> usize {
+        let mut best = 0;
+        for (i, &cost) in self.costs.iter().enumerate() {
+            if cost < self.costs[best] {
+                best = i;
+            }
+        }
+
+        best
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.costs.iter().sum::<f32>() / self.costs.len() as f32
+    }
+
+    pub fn std_dev(&self) -> f32 {
+        let mean = self.mean();
+        let variance = self
+            .costs
+            .iter()
+            .map(|cost| (cost - mean).powi(2))
+            .sum::<f32>()
+            / self.costs.len() as f32;
+
+        variance.sqrt()
+    }
+
+    pub fn worst(&self) -> f32 {
+        self.costs.iter().cloned().fold(f32::MIN, f32::max)
+    }
+}
+
+/// runs the solver `n_restarts` times and returns the best solution with costs of all runs
+pub fn run<F>(
+    solve_fn: F,
+    cities: &[KDPoint],
+    options: &SolverOptions,
+    n_restarts: usize,
+) -> Result<RestartResult, Error>
+where
+    F: Fn(&[KDPoint], &SolverOptions) -> Result<Solution, Error>,
+{
+    let n_restarts = n_restarts.max(1);
+    let (mut best, secs) = timed_run(&solve_fn, cities, &run_options(options, 0))?;
+    let mut costs = vec![best.total];
+    let mut seconds = vec![secs];
+
+    if options.verbose {
+        println!("Restart: run.1 total {:?}", best.total);
+    }
+
+    for run_no in 2..=n_restarts {
+        // the target reached, so there is nothing to improve; or the user stopped the search
+        if best.target_reached || options.cancel.is_cancelled() {
+            break;
+        }
+
+        let (candidate, secs) = timed_run(&solve_fn, cities, &run_options(options, run_no - 1))?;
+        costs.push(candidate.total);
+        seconds.push(secs);
+
+        if options.verbose {
+            println!("Restart: run.{} total {:?}", run_no, candidate.total);
+        }
+
+        if candidate.total < best.total {
+            best = candidate;
+        }
+    }
+
+    let res = RestartResult {
+        best,
+        costs,
+        seconds,
+    };
+    if options.verbose {
+        print_summary(&res);
+    }
+
+    Ok(res)
+}
+
+/// runs `n_starts` independent solvers on `n_threads` threads and returns the best solution;
+/// the progress display gets only the improvements of the best distance over all the runs
+pub fn run_parallel<F>(
+    solve_fn: F,
+    cities: &[KDPoint],
+    options: &SolverOptions,
+    n_starts: usize,
+    n_threads: usize,
+) -> Result<RestartResult, Error>
+where
+    F: Fn(&[KDPoint], &SolverOptions) -> Result<Solution, Error> + Sync,
+{
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(n_threads.max(1))
+        .build()
+        .map_err(|e| Error::Runtime(format!("restart: failed to start threads: {}", e)))?;
+
+    let fan_in = Arc::new(ProgressFanIn::new(cities));
+    let target_reached = AtomicBool::new(false);
+    let runs: Result<Vec<(Solution, f64)>, Error> = pool.install(|| {
+        (0..n_starts.max(1))
+            .into_par_iter()
+            .filter_map(|start| {
+                // the starts, which have not begun yet, are skipped after any run reached the target
+                // or the search was cancelled; the first one always runs, so there is a tour to
+                // return even when the search was cancelled before it began
+                if start > 0
+                    && (target_reached.load(Ordering::Relaxed) || options.cancel.is_cancelled())
+                {
+                    return None;
+                }
+
+                progress::set_thread_filter(Some(fan_in.filter(start)));
+                let res = timed_run(&solve_fn, cities, &run_options(options, start));
+                progress::set_thread_filter(None);
+
+                let (tour, secs) = match res {
+                    Ok(run) => run,
+                    Err(err) => return Some(Err(err)),
+                };
+
+                if options.verbose {
+                    println!("Restart: run.{} total {:?}", start + 1, tour.total);
+                }
+
+                if tour.target_reached {
+                    target_reached.store(true, Ordering::Relaxed);
+                }
+
+                Some(Ok((tour, secs)))
+            })
+            .collect()
+    });
+    let runs = runs?;
+
+    let costs: Vec<f32> = runs.iter().map(|(s, _)| s.total).collect();
+    let seconds: Vec<f64> = runs.iter().map(|(_, secs)| *secs).collect();
+    let best = runs
+        .into_iter()
+        .map(|(s, _)| s)
+        .fold(None, |best: Option<Solution>, candidate| match best {
+            Some(b) if b.total <= candidate.total => Some(b),
+            _ => Some(candidate),
+        })
+        .ok_or_else(|| Error::Runtime("restart: no run finished".to_string()))?;
+
+    send_progress(ProgressMessage::PathUpdate(
+        Route::new(best.route()),
+        best.total,
+    ));
+    send_progress(ProgressMessage::Done);
+
+    let res = RestartResult {
+        best,
+        costs,
+        seconds,
+    };
+    if options.verbose {
+        print_summary(&res);
+    }
+
+    Ok(res)
+}
+
+fn timed_run<F>(
+    solve_fn: &F,
+    cities: &[KDPoint],
+    options: &SolverOptions,
+) -> Result<(Solution, f64), Error>
+where
+    F: Fn(&[KDPoint], &SolverOptions) -> Result<Solution, Error>,
+{
+    let started = Instant::now();
+    let tour = solve_fn(cities, options)?;
+
+    Ok((tour, started.elapsed().as_secs_f64()))
+}
+
+// every run gets its own seed, otherwise the seeded runs would find the same solution
+pub(crate) fn run_options(options: &SolverOptions, run: usize) -> SolverOptions {
+    let mut run_options = options.clone();
+    run_options.seed = options.seed.map(|seed| seed.wrapping_add(run as u64));
+
+    run_options
+}
+
+fn print_summary(res: &RestartResult) {
+    println!(
+        "Restart: best run.{} total {:?}, mean {:?}, std_dev {:?}, worst {:?}",
+        res.best_run() + 1,
+        res.best.total,
+        res.mean(),
+        res.std_dev(),
+        res.worst()
+    );
+}
+
+// mean, standard deviation, min and max of the values
+fn spread(values: &[f64]) -> (f64, f64, f64, f64) {
+    let n = values.len().max(1) as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    let min = values.iter().cloned().fold(f64::MAX, f64::min);
+    let max = values.iter().cloned().fold(f64::MIN, f64::max);
+
+    (mean, variance.sqrt(), min, max)
+}
+
+impl fmt::Display for RestartResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let best_run = self.best_run();
+        writeln!(
+            f,
+            "{} runs, the best is run.{}: {:.2} in {:.3}s",
+            self.costs.len(),
+            best_run + 1,
+            self.costs[best_run],
+            self.seconds[best_run]
+        )?;
+
+        let costs: Vec<f64> = self.costs.iter().map(|cost| *cost as f64).collect();
+        let (mean, std_dev, min, max) = spread(&costs);
+        writeln!(
+            f,
+            "cost: mean {:.2}, std_dev {:.2}, min {:.2}, max {:.2}",
+            mean, std_dev, min, max
+        )?;
+
+        let (mean, std_dev, min, max) = spread(&self.seconds);
+        writeln!(
+            f,
+            "time: mean {:.3}s, std_dev {:.3}s, min {:.3}s, max {:.3}s",
+            mean, std_dev, min, max
+        )
+    }
+}
+
+// merges progress messages of the parallel runs
+struct ProgressFanIn {
+    cities_table: CityTable,
+    best_distance: Mutex<f32>,
+}
+
+impl ProgressFanIn {
+    fn new(cities: &[KDPoint]) -> Self {
+        ProgressFanIn {
+            cities_table: city_table_from_vec(cities),
+            best_distance: Mutex::new(f32::MAX),
+        }
+    }
+
+    fn filter(self: &Arc<Self>, start: usize) -> ProgressFilter {
+        let fan_in = Arc::clone(self);
+
+        Arc::new(move |msg| fan_in.forward(start, msg))
+    }
+
+    // solvers dont agree what the distance of PathUpdate means, so it is measured here
+    fn forward(&self, start: usize, msg: ProgressMessage) -> Option<ProgressMessage> {
+        match msg {
+            ProgressMessage::PathUpdate(route, _) => {
+                let distance = self.route_length(route.route());
+                let mut best_distance = self.best_distance.lock().unwrap();
+                if distance < *best_distance {
+                    *best_distance = distance;
+                    Some(ProgressMessage::PathUpdate(route, distance))
+                } else {
+                    None
+                }
+            }
+            // the runner sends Done after all the runs are finished
+            ProgressMessage::Done | ProgressMessage::Restart => None,
+            other if start == 0 => Some(other),
+            _ => None,
+        }
+    }
+
+    fn route_length(&self, route: &[usize]) -> f32 {
+        if route.is_empty() {
+            return f32::MAX;
+        }
+
+        let closing = (route[route.len() - 1], route[0]);
+        route
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .chain(std::iter::once(closing))
+            .map(|(from, to)| self.cities_table[&from].distance(&self.cities_table[&to]))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+    use std::cell::Cell;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_run_keeps_best_solution() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+
+        // every second run returns the crossing tour
+        let n_calls = Cell::new(0);
+        let solve_fn = |cities: &[KDPoint], _options: &SolverOptions| {
+            n_calls.set(n_calls.get() + 1);
+            let route = if n_calls.get() % 2 == 1 {
+                vec![0, 1, 2, 3]
+            } else {
+                vec![0, 2, 1, 3]
+            };
+
+            Ok(Solution::new(&route, cities))
+        };
+
+        let res = run(solve_fn, &cities, &SolverOptions::default(), 3).unwrap();
+        assert_eq!(3, n_calls.get());
+        assert_eq!(3, res.costs.len());
+        assert_eq!(3, res.seconds.len());
+        assert_eq!(&[0, 2, 1, 3], res.best.route());
+        assert_eq!(1, res.best_run());
+        assert_eq!(4.0, res.best.total);
+        assert!((res.worst() - 4.828_427).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_run_parallel_keeps_best_solution() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+
+        // only a single run finds the shortest tour
+        let n_calls = Mutex::new(0);
+        let solve_fn = |cities: &[KDPoint], _options: &SolverOptions| {
+            let mut n = n_calls.lock().unwrap();
+            *n += 1;
+            let route = if *n == 3 {
+                vec![0, 2, 1, 3]
+            } else {
+                vec![0, 1, 2, 3]
+            };
+
+            Ok(Solution::new(&route, cities))
+        };
+
+        let res = run_parallel(solve_fn, &cities, &SolverOptions::default(), 5, 2).unwrap();
+        assert_eq!(5, *n_calls.lock().unwrap());
+        assert_eq!(5, res.costs.len());
+        assert_eq!(5, res.seconds.len());
+        assert_eq!(&[0, 2, 1, 3], res.best.route());
+        assert_eq!(4.0, res.best.total);
+    }
+
+    #[test]
+    fn test_progress_fan_in_forwards_only_improvements() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+        let fan_in = ProgressFanIn::new(&cities);
+        let path_update = |route: &[usize]| ProgressMessage::PathUpdate(Route::new(route), 0.0);
+
+        assert!(fan_in.forward(1, path_update(&[0, 1, 2, 3])).is_some());
+        assert!(fan_in.forward(0, path_update(&[0, 1, 2, 3])).is_none());
+
+        match fan_in.forward(0, path_update(&[0, 2, 1, 3])) {
+            Some(ProgressMessage::PathUpdate(_, distance)) => assert_eq!(4.0, distance),
+            _ => panic!("improvement was not forwarded"),
+        }
+
+        assert!(fan_in.forward(0, ProgressMessage::CityChange(1)).is_some());
+        assert!(fan_in.forward(1, ProgressMessage::CityChange(1)).is_none());
+        assert!(fan_in.forward(0, ProgressMessage::Done).is_none());
+    }
+
+    #[test]
+    fn test_run_gives_every_run_own_seed() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 1.0]]);
+        let seeds = Mutex::new(vec![]);
+        let solve_fn = |cities: &[KDPoint], options: &SolverOptions| {
+            seeds.lock().unwrap().push(options.seed);
+            Ok(Solution::new(&[0, 1], cities))
+        };
+
+        let mut options = SolverOptions::default();
+        options.seed = Some(10);
+        run(solve_fn, &cities, &options, 3).unwrap();
+        assert_eq!(vec![Some(10), Some(11), Some(12)], *seeds.lock().unwrap());
+
+        seeds.lock().unwrap().clear();
+        run_parallel(solve_fn, &cities, &options, 2, 2).unwrap();
+        let mut parallel_seeds = seeds.lock().unwrap().clone();
+        parallel_seeds.sort();
+        assert_eq!(vec![Some(10), Some(11)], parallel_seeds);
+    }
+
+    #[test]
+    fn test_summary_shows_spread_of_cost_and_time() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![3.0, 4.0]]);
+        let res = RestartResult {
+            best: Solution::new(&[0, 1], &cities),
+            costs: vec![12.0, 10.0, 14.0],
+            seconds: vec![0.5, 0.25, 0.75],
+        };
+
+        assert_eq!(
+            "3 runs, the best is run.2: 10.00 in 0.250s\n\
+             cost: mean 12.00, std_dev 1.63, min 10.00, max 14.00\n\
+             time: mean 0.500s, std_dev 0.204s, min 0.250s, max 0.750s\n",
+            res.to_string()
+        );
+    }
+
+    #[test]
+    fn test_run_stops_when_target_is_reached() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+            vec![1.0, 0.0],
+        ]);
+        let n_calls = Cell::new(0);
+        let solve_fn = |cities: &[KDPoint], options: &SolverOptions| {
+            n_calls.set(n_calls.get() + 1);
+            Ok(Solution::new(&[0, 2, 1, 3], cities).with_target(options))
+        };
+
+        let mut options = SolverOptions::default();
+        options.target_cost = Some(4.0);
+
+        let res = run(solve_fn, &cities, &options, 5).unwrap();
+        assert_eq!(1, n_calls.get());
+        assert!(res.best.target_reached);
+    }
+
+    #[test]
+    fn test_run_stops_after_cancel() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 1.0]]);
+        let n_calls = Cell::new(0);
+        let solve_fn = |cities: &[KDPoint], options: &SolverOptions| {
+            n_calls.set(n_calls.get() + 1);
+            options.cancel.cancel();
+            Ok(Solution::new(&[0, 1], cities))
+        };
+
+        let res = run(solve_fn, &cities, &SolverOptions::default(), 5).unwrap();
+        assert_eq!(1, n_calls.get());
+        assert_eq!(1, res.costs.len());
+    }
+
+    #[test]
+    fn test_run_parallel_after_cancel_runs_first_start() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 1.0]]);
+        let n_calls = AtomicUsize::new(0);
+        let solve_fn = |cities: &[KDPoint], _options: &SolverOptions| {
+            n_calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Solution::new(&[0, 1], cities))
+        };
+
+        let options = SolverOptions::default();
+        options.cancel.cancel();
+        let res = run_parallel(solve_fn, &cities, &options, 4, 2).unwrap();
+        assert_eq!(1, n_calls.load(Ordering::SeqCst));
+        assert_eq!(1, res.costs.len());
+        assert_eq!(&[0, 1], res.best.route());
+    }
+
+    #[test]
+    fn test_run_with_zero_restarts_runs_once() {
+        let cities = kdtree::build_points(&[vec![0.0, 0.0], vec![1.0, 1.0]]);
+        let solve_fn =
+            |cities: &[KDPoint], _options: &SolverOptions| Ok(Solution::new(&[0, 1], cities));
+
+        let res = run(solve_fn, &cities, &SolverOptions::default(), 0).unwrap();
+        assert_eq!(1, res.costs.len());
+        assert_eq!(0.0, res.std_dev());
+    }
+}
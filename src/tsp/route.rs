@@ -1,11 +1,89 @@
 /// Route is ordered list of city ids that our traveling salesperson
 /// is going to visit
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 use rand::Rng;
+use std::fmt;
+use std::str::FromStr;
 
+use super::distance_matrix::DistanceSource;
 use super::kdtree::KDPoint;
+use super::trace::MoveKind;
+use super::Error;
+
+/// which moves the perturbation-based solvers try on the route
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Neighborhood {
+    #[default]
+    Reverse, // reverses the cities between 2 positions, the 2-opt move
+    Swap,   // swaps the cities at 2 positions
+    Insert, // moves a city to another position
+    Mixed,  // picks one of the moves above at random
+}
+
+impl Neighborhood {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["reverse", "swap", "insert", "mixed"]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Neighborhood::Reverse => "reverse",
+            Neighborhood::Swap => "swap",
+            Neighborhood::Insert => "insert",
+            Neighborhood::Mixed => "mixed",
+        }
+    }
+}
+
+impl FromStr for Neighborhood {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reverse" => Ok(Neighborhood::Reverse),
+            "swap" => Ok(Neighborhood::Swap),
+            "insert" => Ok(Neighborhood::Insert),
+            "mixed" => Ok(Neighborhood::Mixed),
+            _ => Err("unknown neighborhood"),
+        }
+    }
+}
+
+impl fmt::Display for Neighborhood {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// a change of the route by the positions of the cities
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Move {
+    Reverse(usize, usize), // reverses the cities between the positions
+    Swap(usize, usize),    // swaps the cities at the positions
+    Insert(usize, usize),  // moves the city from the first position to the second one
+}
+
+impl Move {
+    pub fn positions(&self) -> (usize, usize) {
+        match self {
+            Move::Reverse(from, to) | Move::Swap(from, to) | Move::Insert(from, to) => (*from, *to),
+        }
+    }
+
+    /// how the move is written into the trace
+    pub fn kind(&self) -> MoveKind {
+        match self {
+            Move::Reverse(..) => MoveKind::TwoOpt,
+            Move::Swap(..) => MoveKind::Swap,
+            Move::Insert(..) => MoveKind::Relocate,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Route {
     route: Vec<usize>,
 }
@@ -35,25 +113,326 @@ impl Route {
         self.route.as_slice()
     }
 
-    pub fn shuffle(&mut self) {
-        let mut rng = rand::thread_rng();
-
-        self.route.shuffle(&mut rng);
+    pub fn shuffle(&mut self, rng: &mut StdRng) {
+        self.route.shuffle(rng);
     }
 
     // it swaps 2 cities using 2-opt
-    pub fn random_successor(&self) -> Route {
-        let mut candidate = self.route.clone().to_vec();
+    pub fn random_successor(&self, rng: &mut StdRng) -> Result<Route, Error> {
+        self.random_neighbor(Neighborhood::Reverse, rng)
+    }
+
+    /// returns new route changed by a random move of the neighborhood
+    pub fn random_neighbor(
+        &self,
+        neighborhood: Neighborhood,
+        rng: &mut StdRng,
+    ) -> Result<Route, Error> {
+        let mut candidate = self.clone();
+        candidate.apply(self.random_move(neighborhood, rng)?)?;
+
+        Ok(candidate)
+    }
+
+    /// picks a random move of the neighborhood, the positions are different
+    pub fn random_move(&self, neighborhood: Neighborhood, rng: &mut StdRng) -> Result<Move, Error> {
+        let neighborhood = match neighborhood {
+            Neighborhood::Mixed => match rng.gen_range(0, 3) {
+                0 => Neighborhood::Reverse,
+                1 => Neighborhood::Swap,
+                _ => Neighborhood::Insert,
+            },
+            other => other,
+        };
+
+        let (from_pos, to_pos) = random_position_pair(self.len(), rng)?;
+        let mv = match neighborhood {
+            Neighborhood::Swap => Move::Swap(from_pos, to_pos),
+            // the city moves forward or backward
+            Neighborhood::Insert if rng.gen::<bool>() => Move::Insert(to_pos, from_pos),
+            Neighborhood::Insert => Move::Insert(from_pos, to_pos),
+            _ => Move::Reverse(from_pos, to_pos),
+        };
+
+        Ok(mv)
+    }
+
+    /// changes the route in place
+    pub fn apply(&mut self, mv: Move) -> Result<(), Error> {
+        let (from_pos, to_pos) = mv.positions();
+        self.check_positions(from_pos, to_pos)?;
+
+        match mv {
+            Move::Reverse(from_pos, to_pos) => self.reverse(from_pos, to_pos)?,
+            Move::Swap(from_pos, to_pos) => self.route.swap(from_pos, to_pos),
+            Move::Insert(from_pos, to_pos) => {
+                let city = self.route.remove(from_pos);
+                self.route.insert(to_pos, city);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// the change of the tour length if the move was applied
+    pub fn move_delta(&self, dm: &dyn DistanceSource, mv: Move) -> Result<f32, Error> {
+        match mv {
+            Move::Reverse(from_pos, to_pos) => self.reversal_delta(dm, from_pos, to_pos),
+            Move::Swap(from_pos, to_pos) => self.swap_delta(dm, from_pos, to_pos),
+            Move::Insert(from_pos, to_pos) => self.insertion_delta(dm, from_pos, to_pos),
+        }
+    }
+
+    /// the change of the length of the open tour if the move was applied; the open tour has
+    /// no edge from its last city back to the first one, so the ends of the route have only
+    /// one neighbor
+    pub fn open_move_delta(&self, dm: &dyn DistanceSource, mv: Move) -> Result<f32, Error> {
+        let (from_pos, to_pos) = mv.positions();
+        self.check_positions(from_pos, to_pos)?;
+        if from_pos == to_pos {
+            return Ok(0.0);
+        }
+
+        // the missing neighbor of the end of the route adds nothing to the length
+        let dist = |a: Option<usize>, b: Option<usize>| match (a, b) {
+            (Some(a), Some(b)) => dm.distance_between(a, b),
+            _ => Ok(0.0),
+        };
+        let city = |pos: usize| self.route.get(pos).copied();
+        let before = |pos: usize| pos.checked_sub(1).and_then(city);
+        let after = |pos: usize| city(pos + 1);
+
+        match mv {
+            // the segment wraps around the end of the route, it changes the ends of the tour
+            Move::Reverse(..) if from_pos > to_pos => {
+                let mut moved = self.clone();
+                moved.apply(mv)?;
+
+                Ok(dm.path_length(moved.route())? - dm.path_length(self.route())?)
+            }
+            Move::Reverse(..) => {
+                let (a, b) = (before(from_pos), city(from_pos));
+                let (c, d) = (city(to_pos), after(to_pos));
+
+                Ok(dist(a, c)? + dist(b, d)? - dist(a, b)? - dist(c, d)?)
+            }
+            Move::Swap(..) => {
+                let (i, j) = (from_pos.min(to_pos), from_pos.max(to_pos));
+                let (a, b) = (city(i), city(j));
+                if i + 1 == j {
+                    return Ok(dist(before(i), b)? + dist(a, after(j))?
+                        - dist(before(i), a)?
+                        - dist(b, after(j))?);
+                }
+
+                Ok(dist(before(i), b)?
+                    + dist(b, after(i))?
+                    + dist(before(j), a)?
+                    + dist(a, after(j))?
+                    - dist(before(i), a)?
+                    - dist(a, after(i))?
+                    - dist(before(j), b)?
+                    - dist(b, after(j))?)
+            }
+            Move::Insert(..) => {
+                let moved = city(from_pos);
+                // the neighbors at the new place, by the positions of the route without the city
+                let without = |pos: usize| city(if pos < from_pos { pos } else { pos + 1 });
+                let new_before = to_pos.checked_sub(1).and_then(without);
+                let new_after = if to_pos + 1 < self.len() {
+                    without(to_pos)
+                } else {
+                    None
+                };
+
+                Ok(dist(before(from_pos), after(from_pos))?
+                    - dist(before(from_pos), moved)?
+                    - dist(moved, after(from_pos))?
+                    + dist(new_before, moved)?
+                    + dist(moved, new_after)?
+                    - dist(new_before, new_after)?)
+            }
+        }
+    }
+
+    /// the change of the length the solvers minimize, the open tour has no closing edge
+    pub fn tour_move_delta(
+        &self,
+        dm: &dyn DistanceSource,
+        mv: Move,
+        open_tour: bool,
+    ) -> Result<f32, Error> {
+        if open_tour {
+            self.open_move_delta(dm, mv)
+        } else {
+            self.move_delta(dm, mv)
+        }
+    }
+
+    // returns new route where the cities between positions are reversed with 2-opt
+    pub fn successor(&self, from_pos: usize, to_pos: usize) -> Result<Route, Error> {
+        let mut candidate = self.clone();
+        candidate.reverse(from_pos, to_pos)?;
+
+        Ok(candidate)
+    }
 
-        let (from_pos, to_pos) = random_position_pair(self.len());
-        swap_cities(&mut candidate, from_pos, to_pos);
+    // reverses the cities between positions in place, same call again undoes it
+    pub fn reverse(&mut self, from_pos: usize, to_pos: usize) -> Result<(), Error> {
+        swap_cities(&mut self.route, from_pos, to_pos)
+    }
+
+    /// the change of the tour length if the cities between positions were reversed;
+    /// the reversal replaces the edges a-b and c-d around the segment with a-c and b-d,
+    /// so it takes 4 distances instead of summing up the whole tour
+    pub fn reversal_delta(
+        &self,
+        dm: &dyn DistanceSource,
+        from_pos: usize,
+        to_pos: usize,
+    ) -> Result<f32, Error> {
+        let n = self.len();
+        if from_pos >= n || to_pos >= n {
+            return Err(Error::InvalidInput(format!(
+                "route: positions {}..{} are outside of the route of {} cities",
+                from_pos, to_pos, n
+            )));
+        }
+
+        // the reversal of all but one city only flips the direction of the tour
+        let segment_len = (to_pos + n - from_pos) % n + 1;
+        if segment_len < 2 || segment_len + 1 >= n {
+            return Ok(0.0);
+        }
+
+        let a = self.route[(from_pos + n - 1) % n];
+        let b = self.route[from_pos];
+        let c = self.route[to_pos];
+        let d = self.route[(to_pos + 1) % n];
+
+        Ok(dm.distance_between(a, c)? + dm.distance_between(b, d)?
+            - dm.distance_between(a, b)?
+            - dm.distance_between(c, d)?)
+    }
+
+    fn check_positions(&self, from_pos: usize, to_pos: usize) -> Result<(), Error> {
+        if from_pos >= self.len() || to_pos >= self.len() {
+            return Err(Error::InvalidInput(format!(
+                "route: positions {}..{} are outside of the route of {} cities",
+                from_pos,
+                to_pos,
+                self.len()
+            )));
+        }
 
-        Route { route: candidate }
+        Ok(())
+    }
+
+    /// the change of the tour length if the cities at the positions were swapped;
+    /// the neighboring cities share the edge between them, which stays in the tour
+    pub fn swap_delta(
+        &self,
+        dm: &dyn DistanceSource,
+        from_pos: usize,
+        to_pos: usize,
+    ) -> Result<f32, Error> {
+        self.check_positions(from_pos, to_pos)?;
+
+        let n = self.len();
+        // every swap of the triangle gives the same tour
+        if from_pos == to_pos || n < 4 {
+            return Ok(0.0);
+        }
+
+        // the first one is followed by the second one when they are neighbors
+        let (i, j) = if (to_pos + 1) % n == from_pos {
+            (to_pos, from_pos)
+        } else {
+            (from_pos, to_pos)
+        };
+        let a = self.route[i];
+        let b = self.route[j];
+        let before_a = self.route[(i + n - 1) % n];
+        let after_b = self.route[(j + 1) % n];
+
+        if (i + 1) % n == j {
+            return Ok(
+                dm.distance_between(before_a, b)? + dm.distance_between(a, after_b)?
+                    - dm.distance_between(before_a, a)?
+                    - dm.distance_between(b, after_b)?,
+            );
+        }
+
+        let after_a = self.route[(i + 1) % n];
+        let before_b = self.route[(j + n - 1) % n];
+
+        Ok(dm.distance_between(before_a, b)?
+            + dm.distance_between(b, after_a)?
+            + dm.distance_between(before_b, a)?
+            + dm.distance_between(a, after_b)?
+            - dm.distance_between(before_a, a)?
+            - dm.distance_between(a, after_a)?
+            - dm.distance_between(before_b, b)?
+            - dm.distance_between(b, after_b)?)
+    }
+
+    /// the change of the tour length if the city at `from_pos` was moved to `to_pos`;
+    /// the city leaves the edges to its neighbors and splits the edge at its new place
+    pub fn insertion_delta(
+        &self,
+        dm: &dyn DistanceSource,
+        from_pos: usize,
+        to_pos: usize,
+    ) -> Result<f32, Error> {
+        self.check_positions(from_pos, to_pos)?;
+
+        let n = self.len();
+        if from_pos == to_pos || n < 4 {
+            return Ok(0.0);
+        }
+
+        let city = self.route[from_pos];
+        let before = self.route[(from_pos + n - 1) % n];
+        let after = self.route[(from_pos + 1) % n];
+
+        // the neighbors at the new place, by the positions of the route without the city
+        let without = |pos: usize| self.route[if pos < from_pos { pos } else { pos + 1 }];
+        let new_before = without((to_pos + n - 2) % (n - 1));
+        let new_after = without(to_pos % (n - 1));
+
+        Ok(dm.distance_between(before, after)?
+            - dm.distance_between(before, city)?
+            - dm.distance_between(city, after)?
+            + dm.distance_between(new_before, city)?
+            + dm.distance_between(city, new_after)?
+            - dm.distance_between(new_before, new_after)?)
     }
 
     pub fn sort(&mut self) {
         self.route.sort()
     }
+
+    /// rotates the route to start from the smallest city id and turns it into the direction,
+    /// which is lexicographically smaller; the same tours get the same route
+    pub fn canonicalize(&mut self) {
+        canonicalize(&mut self.route);
+    }
+}
+
+/// see `Route::canonicalize`
+pub fn canonicalize(route: &mut [usize]) {
+    let start = match route.iter().enumerate().min_by_key(|(_, id)| **id) {
+        Some((start, _)) => start,
+        None => return,
+    };
+    route.rotate_left(start);
+
+    // the other direction keeps the first city and reverses the rest
+    let mut reversed = route.to_vec();
+    reversed[1..].reverse();
+    if reversed.as_slice() < &*route {
+        route.copy_from_slice(&reversed);
+    }
 }
 
 impl PartialEq for Route {
@@ -63,8 +442,8 @@ impl PartialEq for Route {
 }
 
 // maybe into utils?
-pub fn random_position_pair(n_items: usize) -> (usize, usize) {
-    let mut pair = random_pair(n_items);
+pub fn random_position_pair(n_items: usize, rng: &mut StdRng) -> Result<(usize, usize), Error> {
+    let mut pair = random_pair(n_items, rng)?;
     let max_iter = 10;
 
     for _ in 0..max_iter {
@@ -73,47 +452,70 @@ pub fn random_position_pair(n_items: usize) -> (usize, usize) {
             break;
         }
 
-        pair = random_pair(n_items);
+        pair = random_pair(n_items, rng)?;
     }
 
-    pair
+    Ok(pair)
 }
 
 // from Skiena ch.7.5.1 - random sampling
-fn random_pair(n_items: usize) -> (usize, usize) {
+fn random_pair(n_items: usize, rng: &mut StdRng) -> Result<(usize, usize), Error> {
     if n_items < 2 {
-        panic!("n_items must be bigger than 2");
+        return Err(Error::InvalidInput(format!(
+            "route: can not pick 2 positions out of {}",
+            n_items
+        )));
     }
 
-    let mut rng = rand::thread_rng();
     let pos1 = rng.gen_range(0, n_items);
     let pos2 = rng.gen_range(0, n_items);
 
     if pos1 < pos2 {
-        (pos1, pos2)
+        Ok((pos1, pos2))
     } else {
-        (pos2, pos1)
+        Ok((pos2, pos1))
     }
 }
 
-fn swap_cities(route: &mut Vec<usize>, from: usize, to: usize) {
-    if to >= route.len() {
-        panic!("to can not be same or bigger than route size");
+fn swap_cities(route: &mut [usize], from: usize, to: usize) -> Result<(), Error> {
+    if from >= route.len() || to >= route.len() {
+        return Err(Error::InvalidInput(format!(
+            "route: positions {}..{} are outside of the route of {} cities",
+            from,
+            to,
+            route.len()
+        )));
     }
 
     // 2-OPT keeps changes in more stable
-    let reversed_seq: Vec<usize> = route[from..=to].iter().map(|x| x.clone()).rev().collect();
+    reverse_segment(route, from, to);
+    Ok(())
+}
+
+/// reverses the segment from..=to of the cyclic route in place,
+/// if `from` is after `to` the segment wraps around the end of the route
+pub fn reverse_segment(route: &mut [usize], from: usize, to: usize) {
+    if from <= to {
+        route[from..=to].reverse();
+        return;
+    }
 
-    // swap values from routes with reversed_seq
-    for (i, swapped_val) in reversed_seq.iter().enumerate() {
-        route[from + i] = swapped_val.clone();
+    let n = route.len();
+    let segment_len = n - from + to + 1;
+    let (mut i, mut j) = (from, to);
+    for _ in 0..(segment_len / 2) {
+        route.swap(i, j);
+        i = (i + 1) % n;
+        j = (j + n - 1) % n;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tsp::distance_matrix::DistanceMatrix;
     use crate::tsp::kdtree;
+    use rand::SeedableRng;
 
     #[test]
     fn test_route_from_cities_with_1_elem() {
@@ -145,8 +547,250 @@ mod tests {
         ]);
 
         let route1 = Route::from_cities(&cities);
-        let route2 = route1.random_successor();
+        let route2 = route1
+            .random_successor(&mut StdRng::seed_from_u64(42))
+            .unwrap();
 
         assert!(route1.len() == route2.len())
     }
+
+    #[test]
+    fn test_random_successor_with_same_seed_is_same() {
+        let route = Route::new(&(0..20).collect::<Vec<usize>>());
+
+        let successor1 = route
+            .random_successor(&mut StdRng::seed_from_u64(7))
+            .unwrap();
+        let successor2 = route
+            .random_successor(&mut StdRng::seed_from_u64(7))
+            .unwrap();
+
+        assert_eq!(successor1, successor2);
+    }
+
+    #[test]
+    fn test_reverse_segment_in_the_middle() {
+        let mut route = vec![0, 1, 2, 3, 4, 5];
+        reverse_segment(&mut route, 1, 4);
+
+        assert_eq!(vec![0, 4, 3, 2, 1, 5], route);
+    }
+
+    #[test]
+    fn test_reverse_segment_wraps_around_the_end() {
+        let mut route = vec![0, 1, 2, 3, 4, 5];
+        reverse_segment(&mut route, 4, 1);
+
+        // segment 4, 5, 0, 1 becomes 1, 0, 5, 4
+        assert_eq!(vec![5, 4, 2, 3, 1, 0], route);
+
+        let mut route = vec![0, 1, 2, 3, 4];
+        reverse_segment(&mut route, 3, 0);
+        assert_eq!(vec![3, 1, 2, 0, 4], route);
+    }
+
+    #[test]
+    fn test_route_reverse_twice_restores_the_route() {
+        let mut route = Route::new(&[0, 1, 2, 3, 4]);
+        route.reverse(1, 3).unwrap();
+        assert_eq!(&[0, 3, 2, 1, 4], route.route());
+
+        route.reverse(1, 3).unwrap();
+        assert_eq!(&[0, 1, 2, 3, 4], route.route());
+    }
+
+    #[test]
+    fn test_route_moves_outside_of_route_are_errors() {
+        let mut route = Route::new(&[0, 1, 2]);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        assert!(route.reverse(1, 3).is_err());
+        assert!(route.apply(Move::Swap(0, 5)).is_err());
+        assert!(route.apply(Move::Insert(4, 0)).is_err());
+        assert_eq!(&[0, 1, 2], route.route());
+
+        assert!(random_position_pair(1, &mut rng).is_err());
+        assert!(Route::new(&[0])
+            .random_move(Neighborhood::Mixed, &mut rng)
+            .is_err());
+    }
+
+    #[test]
+    fn test_route_successor_reverses_cities_between_positions() {
+        let route = Route::new(&[0, 1, 2, 3, 4]);
+
+        assert_eq!(&[0, 3, 2, 1, 4], route.successor(1, 3).unwrap().route());
+        assert_eq!(&[0, 1, 2, 3, 4], route.route());
+    }
+
+    #[test]
+    fn test_canonicalize_gives_same_route_for_same_tour() {
+        let expected = vec![1, 2, 4, 5, 3];
+
+        for route in [[4, 2, 1, 3, 5], [5, 3, 1, 2, 4], [1, 2, 4, 5, 3]].iter() {
+            let mut route = Route::new(route);
+            route.canonicalize();
+            assert_eq!(expected, route.route());
+        }
+
+        let mut empty: Vec<usize> = vec![];
+        canonicalize(&mut empty);
+        assert!(empty.is_empty());
+
+        let mut single = vec![7];
+        canonicalize(&mut single);
+        assert_eq!(vec![7], single);
+    }
+
+    #[test]
+    fn test_reversal_delta_matches_tour_length() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 0.5],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.5],
+            vec![1.0, 0.0],
+        ]);
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+        let route = Route::new(&[0, 3, 1, 2, 4, 5]);
+        let length = dm.tour_length(route.route()).unwrap();
+
+        for (from_pos, to_pos) in [(1, 2), (1, 3), (0, 2), (4, 1), (0, 5), (2, 2)].iter() {
+            let delta = route.reversal_delta(&dm, *from_pos, *to_pos).unwrap();
+            let after = dm
+                .tour_length(route.successor(*from_pos, *to_pos).unwrap().route())
+                .unwrap();
+
+            assert!(
+                (length + delta - after).abs() < 0.001,
+                "{}..{}: {}",
+                from_pos,
+                to_pos,
+                delta
+            );
+        }
+
+        assert!(route.reversal_delta(&dm, 1, 6).is_err());
+    }
+
+    #[test]
+    fn test_move_delta_matches_tour_length() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 0.5],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.5],
+            vec![1.0, 0.0],
+            vec![0.5, 2.0],
+        ]);
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+        let route = Route::new(&[0, 3, 1, 6, 2, 4, 5]);
+        let length = dm.tour_length(route.route()).unwrap();
+
+        for from_pos in 0..route.len() {
+            for to_pos in 0..route.len() {
+                for mv in [
+                    Move::Reverse(from_pos, to_pos),
+                    Move::Swap(from_pos, to_pos),
+                    Move::Insert(from_pos, to_pos),
+                ]
+                .iter()
+                {
+                    let delta = route.move_delta(&dm, *mv).unwrap();
+                    let mut after = route.clone();
+                    after.apply(*mv).unwrap();
+
+                    let after_length = dm.tour_length(after.route()).unwrap();
+                    assert!((length + delta - after_length).abs() < 0.001, "{:?}", mv);
+                }
+            }
+        }
+
+        assert!(route.move_delta(&dm, Move::Swap(1, 7)).is_err());
+        assert!(route.move_delta(&dm, Move::Insert(7, 1)).is_err());
+    }
+
+    #[test]
+    fn test_open_move_delta_matches_path_length() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 0.5],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.5],
+            vec![1.0, 0.0],
+            vec![0.5, 2.0],
+        ]);
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+        let route = Route::new(&[0, 3, 1, 6, 2, 4, 5]);
+        let length = dm.path_length(route.route()).unwrap();
+
+        for from_pos in 0..route.len() {
+            for to_pos in 0..route.len() {
+                for mv in [
+                    Move::Reverse(from_pos, to_pos),
+                    Move::Swap(from_pos, to_pos),
+                    Move::Insert(from_pos, to_pos),
+                ]
+                .iter()
+                {
+                    let delta = route.open_move_delta(&dm, *mv).unwrap();
+                    let mut after = route.clone();
+                    after.apply(*mv).unwrap();
+
+                    let after_length = dm.path_length(after.route()).unwrap();
+                    assert!((length + delta - after_length).abs() < 0.001, "{:?}", mv);
+                }
+            }
+        }
+
+        assert!(route.open_move_delta(&dm, Move::Reverse(1, 7)).is_err());
+    }
+
+    #[test]
+    fn test_random_move_of_neighborhood() {
+        let route = Route::new(&[0, 1, 2, 3, 4, 5, 6, 7]);
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for _ in 0..20 {
+            assert!(matches!(
+                route.random_move(Neighborhood::Swap, &mut rng).unwrap(),
+                Move::Swap(..)
+            ));
+            assert!(matches!(
+                route.random_move(Neighborhood::Insert, &mut rng).unwrap(),
+                Move::Insert(..)
+            ));
+        }
+
+        // the mixed neighborhood uses all of the moves
+        let kinds: Vec<MoveKind> = (0..100)
+            .map(|_| {
+                route
+                    .random_move(Neighborhood::Mixed, &mut rng)
+                    .unwrap()
+                    .kind()
+            })
+            .collect();
+        for kind in [MoveKind::TwoOpt, MoveKind::Swap, MoveKind::Relocate].iter() {
+            assert!(kinds.contains(kind));
+        }
+
+        let neighbor = route
+            .random_neighbor(Neighborhood::Insert, &mut rng)
+            .unwrap();
+        let mut cities = neighbor.route().to_vec();
+        cities.sort();
+        assert_eq!(route.route(), cities.as_slice());
+    }
+
+    #[test]
+    fn test_neighborhood_from_str() {
+        for name in Neighborhood::variants() {
+            assert_eq!(name, Neighborhood::from_str(name).unwrap().as_str());
+        }
+        assert!(Neighborhood::from_str("2opt").is_err());
+    }
 }
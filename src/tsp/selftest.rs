@@ -0,0 +1,254 @@
+/// Self-test of the build
+///
+/// It cross-checks implementations, which must agree with each other, on random instances:
+///
+/// * k nearest neighbors found by the `KDTree` and by the `DistanceMatrix`;
+/// * tour length from `total_distance`, from `DistanceMatrix` and from `LazyDistances`;
+/// * TSPLIB problem and tour files, which are written and read back;
+///
+/// so users can quickly check that the build gives sane results on their platform.
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+
+use super::distance_matrix::{DistanceMatrix, DistanceSource, LazyDistances};
+use super::generator::{self, BoundingBox};
+use super::kdtree::{self, KDPoint};
+use super::total_distance;
+use super::tsplib::{self, TspLibData};
+use super::NearestResult;
+
+const MIN_CITIES: usize = 5;
+const MAX_CITIES: usize = 60;
+const MAX_COORD: f64 = 1_000.0;
+const N_NEAREST: usize = 5;
+const TOLERANCE: f32 = 1e-4; // relative difference of distances we accept
+
+#[derive(Debug, Clone)]
+pub struct CheckReport {
+    pub name: &'static str,
+    pub n_cases: usize,
+    pub discrepancies: Vec<String>,
+}
+
+impl CheckReport {
+    fn new(name: &'static str) -> Self {
+        CheckReport {
+            name,
+            n_cases: 0,
+            discrepancies: vec![],
+        }
+    }
+
+    fn add_case(&mut self, res: Result<(), String>) {
+        self.n_cases += 1;
+
+        if let Err(msg) = res {
+            self.discrepancies.push(msg);
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelftestReport {
+    pub seed: u64,
+    pub checks: Vec<CheckReport>,
+}
+
+impl SelftestReport {
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(|c| c.is_ok())
+    }
+}
+
+impl fmt::Display for SelftestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "selftest with seed {}", self.seed)?;
+
+        for check in self.checks.iter() {
+            let status = if check.is_ok() { "ok" } else { "FAILED" };
+            writeln!(
+                f,
+                "{:<24} {:>6} cases {:>6} discrepancies  {}",
+                check.name,
+                check.n_cases,
+                check.discrepancies.len(),
+                status
+            )?;
+
+            for msg in check.discrepancies.iter() {
+                writeln!(f, "\t{}", msg)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// runs all the checks on `n_instances` random instances
+pub fn run(n_instances: usize, seed: u64) -> SelftestReport {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut knn = CheckReport::new("kdtree_vs_matrix_knn");
+    let mut tour_length = CheckReport::new("tour_length");
+    let mut problem_roundtrip = CheckReport::new("tsplib_roundtrip");
+    let mut tour_roundtrip = CheckReport::new("tour_roundtrip");
+
+    for instance in 0..n_instances {
+        let cities = random_cities(&mut rng);
+        let mut route: Vec<usize> = cities.iter().map(|c| c.id).collect();
+        route.shuffle(&mut rng);
+
+        knn.add_case(check_knn(&cities).map_err(|e| format!("instance.{}: {}", instance, e)));
+        tour_length.add_case(
+            check_tour_length(&cities, &route).map_err(|e| format!("instance.{}: {}", instance, e)),
+        );
+        problem_roundtrip.add_case(
+            check_problem_roundtrip(&cities).map_err(|e| format!("instance.{}: {}", instance, e)),
+        );
+        tour_roundtrip.add_case(
+            check_tour_roundtrip(&route).map_err(|e| format!("instance.{}: {}", instance, e)),
+        );
+    }
+
+    SelftestReport {
+        seed,
+        checks: vec![knn, tour_length, problem_roundtrip, tour_roundtrip],
+    }
+}
+
+fn random_cities(rng: &mut StdRng) -> Vec<KDPoint> {
+    let n_cities = rng.gen_range(MIN_CITIES, MAX_CITIES + 1);
+
+    generator::uniform(n_cities, &BoundingBox::square(MAX_COORD), rng)
+}
+
+fn check_knn(cities: &[KDPoint]) -> Result<(), String> {
+    let kd = kdtree::from_cities(cities).map_err(|e| e.to_string())?;
+    let dm = DistanceMatrix::from_cities(cities).map_err(|e| e.to_string())?;
+    let n = N_NEAREST.min(cities.len() - 1);
+
+    for city in cities.iter() {
+        let kd_found = kd.nearest(city, n).map_err(|e| e.to_string())?;
+        let dm_found = dm.nearest(city, n).map_err(|e| e.to_string())?;
+        let kd_distances = nearest_distances(&kd_found);
+        let dm_distances = nearest_distances(&dm_found);
+
+        let agree = kd_distances.len() == dm_distances.len()
+            && kd_distances
+                .iter()
+                .zip(dm_distances.iter())
+                .all(|(d1, d2)| approx(*d1, *d2));
+
+        if !agree {
+            return Err(format!(
+                "city.{} kdtree found {:?}, matrix found {:?}",
+                city.id, kd_distances, dm_distances
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// ties may be ordered differently, so only the distances are compared
+fn nearest_distances(res: &NearestResult) -> Vec<f32> {
+    res.nearest().iter().map(|item| item.distance).collect()
+}
+
+fn check_tour_length(cities: &[KDPoint], route: &[usize]) -> Result<(), String> {
+    let dm = DistanceMatrix::from_cities(cities).map_err(|e| e.to_string())?;
+    let length1 = total_distance(cities, route);
+    let length2 = dm.tour_length(route).map_err(|e| e.to_string())?;
+    let lazy = LazyDistances::from_cities(cities).map_err(|e| e.to_string())?;
+    let length3 = lazy.tour_length(route).map_err(|e| e.to_string())?;
+
+    if approx(length1, length2) && approx(length1, length3) {
+        Ok(())
+    } else {
+        Err(format!(
+            "total_distance {:?}, tour_length {:?}, lazy tour_length {:?}",
+            length1, length2, length3
+        ))
+    }
+}
+
+fn check_problem_roundtrip(cities: &[KDPoint]) -> Result<(), String> {
+    let data = TspLibData::new("selftest".to_string(), String::new(), cities.to_vec());
+
+    let mut buffer: Vec<u8> = vec![];
+    tsplib::write_to(&mut buffer, &data).map_err(|e| format!("failed to write: {}", e))?;
+    let parsed = tsplib::read_from(buffer.as_slice()).map_err(|e| e.to_string())?;
+
+    if parsed.len() != cities.len() {
+        return Err(format!(
+            "wrote {} cities, read {}",
+            cities.len(),
+            parsed.len()
+        ));
+    }
+
+    for (city, parsed_city) in cities.iter().zip(parsed.cities().iter()) {
+        if city.id != parsed_city.id || city.coords() != parsed_city.coords() {
+            return Err(format!("wrote {:?}, read {:?}", city, parsed_city));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_tour_roundtrip(route: &[usize]) -> Result<(), String> {
+    let mut buffer: Vec<u8> = vec![];
+    tsplib::write_tour_to(&mut buffer, "selftest", route)
+        .map_err(|e| format!("failed to write: {}", e))?;
+    let parsed = tsplib::read_tour_from(buffer.as_slice()).map_err(|e| e.to_string())?;
+
+    if parsed == route {
+        Ok(())
+    } else {
+        Err(format!("wrote {:?}, read {:?}", route, parsed))
+    }
+}
+
+fn approx(x1: f32, x2: f32) -> bool {
+    (x1 - x2).abs() <= TOLERANCE * x1.abs().max(x2.abs()).max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_finds_no_discrepancies() {
+        let report = run(20, 42);
+
+        assert_eq!(4, report.checks.len());
+        assert!(report.is_ok(), "{}", report);
+        assert!(report.checks.iter().all(|c| c.n_cases == 20));
+    }
+
+    #[test]
+    fn test_check_tour_roundtrip_keeps_order() {
+        assert!(check_tour_roundtrip(&[3, 1, 0, 2]).is_ok());
+    }
+
+    #[test]
+    fn test_report_shows_failed_check() {
+        let mut check = CheckReport::new("broken");
+        check.add_case(Ok(()));
+        check.add_case(Err("lengths differ".to_string()));
+
+        let report = SelftestReport {
+            seed: 1,
+            checks: vec![check],
+        };
+
+        assert!(!report.is_ok());
+        assert!(report.to_string().contains("FAILED"));
+        assert!(report.to_string().contains("\tlengths differ\n"));
+    }
+}
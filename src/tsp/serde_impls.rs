@@ -0,0 +1,147 @@
+/// Serialization of the core types, behind the `serde` feature
+///
+/// `Route`, `KDPoint` and `TspLibData` derive the traits; the point is kept as its id and
+/// coordinates. The solution keeps its route, cities and the totals, the lookup of the cities
+/// is built again and the route is checked against the cities when it is read back.
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use super::kdtree::KDPoint;
+use super::{validate_route, Solution};
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PointData {
+    id: usize,
+    coords: Vec<f64>,
+}
+
+impl From<PointData> for KDPoint {
+    fn from(data: PointData) -> Self {
+        KDPoint::new_with_id(data.id, &data.coords)
+    }
+}
+
+impl From<KDPoint> for PointData {
+    fn from(point: KDPoint) -> Self {
+        PointData {
+            id: point.id,
+            coords: point.coords().to_vec(),
+        }
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[derive(Serialize)]
+struct SolutionRef<'a> {
+    total: f32,
+    length: f64,
+    target_reached: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    open_tour: bool,
+    route: &'a [usize],
+    cities: &'a [KDPoint],
+}
+
+#[derive(Deserialize)]
+struct SolutionData {
+    target_reached: bool,
+    #[serde(default)]
+    open_tour: bool,
+    route: Vec<usize>,
+    cities: Vec<KDPoint>,
+}
+
+impl Serialize for Solution {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SolutionRef {
+            total: self.total,
+            length: self.length,
+            target_reached: self.target_reached,
+            open_tour: self.is_open_tour(),
+            route: self.route(),
+            cities: self.cities(),
+        }
+        .serialize(serializer)
+    }
+}
+
+// the totals are computed again from the route, so they can't disagree with it
+impl<'de> Deserialize<'de> for Solution {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = SolutionData::deserialize(deserializer)?;
+        validate_route(&data.cities, &data.route).map_err(de::Error::custom)?;
+
+        let mut solution = Solution::new(&data.route, &data.cities).with_open_tour(data.open_tour);
+        solution.target_reached = data.target_reached;
+
+        Ok(solution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+    use crate::tsp::route::Route;
+    use crate::tsp::tsplib::TspLibData;
+
+    fn square_cities() -> Vec<KDPoint> {
+        kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.0],
+        ])
+    }
+
+    #[test]
+    fn test_solution_round_trip() {
+        let solution = Solution::new(&[2, 1, 0, 3], &square_cities());
+
+        let text = toml::to_string(&solution).unwrap();
+        assert!(text.starts_with("total = 4.0\nlength = 4.0\ntarget_reached = false\n"));
+
+        let read: Solution = toml::from_str(&text).unwrap();
+        assert_eq!(solution.route(), read.route());
+        assert_eq!(solution.total, read.total);
+        assert_eq!(Some(1.0), read.get_by_city_id(2).map(|c| c.x()));
+    }
+
+    #[test]
+    fn test_solution_with_invalid_route_fails() {
+        let text = toml::to_string(&Solution::new(&[2, 1, 0, 3], &square_cities()))
+            .unwrap()
+            .replace("route = [2, 1, 0, 3]", "route = [2, 1, 0, 7]");
+
+        let err = toml::from_str::<Solution>(&text).err().unwrap();
+        assert!(err.to_string().contains("unknown city"), "{}", err);
+    }
+
+    #[test]
+    fn test_route_and_problem_round_trip() {
+        #[derive(Serialize, Deserialize)]
+        struct Snapshot {
+            route: Route,
+            problem: TspLibData,
+        }
+
+        let snapshot = Snapshot {
+            route: Route::new(&[3, 0, 1, 2]),
+            problem: TspLibData::new("square".to_string(), String::new(), square_cities()),
+        };
+
+        let text = toml::to_string(&snapshot).unwrap();
+        assert!(text.starts_with("route = [3, 0, 1, 2]\n"));
+        assert!(text.contains("[[problem.cities]]\nid = 1\ncoords = [0.0, 1.0]\n"));
+
+        let read: Snapshot = toml::from_str(&text).unwrap();
+        assert_eq!(snapshot.route, read.route);
+        assert_eq!("square", read.problem.name);
+        assert_eq!(4, read.problem.cities().len());
+        assert_eq!(2, read.problem.cities()[1].dim());
+    }
+}
@@ -0,0 +1,553 @@
+/// HTTP server of the solvers
+///
+/// `bin serve --port 8080` makes teeline a small optimization service:
+///
+/// * `GET /solvers` - the solvers with their category, complexity and options
+/// * `POST /solve` - solves the problem of the JSON body and answers with the tour
+///
+/// ```text
+/// {"solver": "nn+2opt",
+///  "instance": {"name": "square", "cities": [[0, 0], [0, 1], [1, 1], [1, 0]]},
+///  "options": {"max_seconds": 5, "seed": 42}}
+///
+/// {"name": "square", "solver": "nearest_neighbor+two_opt", "total": 4, "tour": [1, 3, 2, 4], ...}
+/// ```
+///
+/// The cities of the list get the ids 1..n in their order, the instance can also be the text of
+/// a TSPLIB file. The options take the keys of the configuration file. Every request is solved
+/// on its own thread and its `max_seconds` is capped by the time budget of the server, so a
+/// single request can't keep the thread forever. At most `MAX_CONNECTIONS` requests are handled
+/// at once, the others are answered with 503 until one of them is done.
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use toml::value::{Table, Value};
+
+use super::kdtree::KDPoint;
+use super::tsplib::{self, TspLibData};
+use super::{config, json, progress, registry, Error, Solution, SolverOptions};
+
+// the slow clients can't keep the connection open longer, the solving time doesn't count
+const READ_TIMEOUT_SECONDS: u64 = 30;
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+// the request line and the headers together
+const MAX_HEADER_BYTES: u64 = 64 * 1024;
+const MAX_CONNECTIONS: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String, // JSON document
+}
+
+impl Response {
+    fn ok(body: String) -> Self {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Response {
+            status,
+            body: format!("{{\"error\": \"{}\"}}", json::escape(message)),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            431 => "Request Header Fields Too Large",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// answers the requests on the address until the process is stopped,
+/// `budget` is the most seconds a request may run the solver
+pub fn serve(addr: &str, budget: f32) -> Result<(), Error> {
+    let listener = TcpListener::bind(addr).map_err(Error::io("serve: failed to listen"))?;
+    let slots = ConnectionSlots::new(MAX_CONNECTIONS);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => match slots.acquire() {
+                Some(slot) => {
+                    thread::spawn(move || {
+                        handle_connection(stream, budget);
+                        drop(slot);
+                    });
+                }
+                None => {
+                    let busy = Response::error(503, "too many requests, try again later");
+                    let _ = write_response(&stream, &busy);
+                }
+            },
+            Err(err) => eprintln!("serve: failed to accept the connection: {}", err),
+        }
+    }
+
+    Ok(())
+}
+
+// counts the connections, which are handled at the moment
+struct ConnectionSlots {
+    n_used: Arc<AtomicUsize>,
+    max: usize,
+}
+
+// the slot of the connection is freed when it's dropped
+struct Slot(Arc<AtomicUsize>);
+
+impl ConnectionSlots {
+    fn new(max: usize) -> Self {
+        ConnectionSlots {
+            n_used: Arc::new(AtomicUsize::new(0)),
+            max,
+        }
+    }
+
+    // None when all the slots are used
+    fn acquire(&self) -> Option<Slot> {
+        self.n_used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                Some(n + 1).filter(|n| *n <= self.max)
+            })
+            .ok()
+            .map(|_| Slot(self.n_used.clone()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn handle_connection(stream: TcpStream, budget: f32) {
+    let timeout = Some(Duration::from_secs(READ_TIMEOUT_SECONDS));
+    if stream.set_read_timeout(timeout).is_err() {
+        return;
+    }
+
+    let response = match read_request(BufReader::new(&stream)) {
+        Ok(request) => handle(&request, budget),
+        Err(response) => response,
+    };
+
+    // the client may be gone already, there is nobody to tell about it
+    let _ = write_response(&stream, &response);
+}
+
+/// reads the request line, the headers and the body of the `Content-Length`; the request line
+/// and the headers may take `MAX_HEADER_BYTES` together
+pub fn read_request<R: BufRead>(mut reader: R) -> Result<Request, Response> {
+    let bad_request = |message: &str| Response::error(400, message);
+    let too_large = || Response::error(431, "the headers are too large");
+
+    let mut head = (&mut reader).take(MAX_HEADER_BYTES);
+    let mut line = String::new();
+    head.read_line(&mut line)
+        .map_err(|_| bad_request("failed to read the request"))?;
+    if head.limit() == 0 && !line.ends_with('\n') {
+        return Err(too_large());
+    }
+
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(bad_request("malformed request line")),
+    };
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        head.read_line(&mut line)
+            .map_err(|_| bad_request("failed to read the headers"))?;
+        if head.limit() == 0 && !line.ends_with('\n') {
+            return Err(too_large());
+        }
+
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| bad_request("invalid Content-Length"))?;
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_BYTES {
+        return Err(Response::error(413, "the body is too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|_| bad_request("the body is shorter than its Content-Length"))?;
+    let body = String::from_utf8(body).map_err(|_| bad_request("the body is not UTF-8"))?;
+
+    Ok(Request { method, path, body })
+}
+
+fn write_response<W: Write>(mut writer: W, response: &Response) -> std::io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.reason(),
+        response.body.len(),
+        response.body
+    )?;
+
+    writer.flush()
+}
+
+/// routes the request, the query of the path is ignored
+pub fn handle(request: &Request, budget: f32) -> Response {
+    let path = request.path.split('?').next().unwrap_or_default();
+
+    match (request.method.as_str(), path) {
+        ("GET", "/solvers") => Response::ok(solvers_json()),
+        ("POST", "/solve") => match solve_json(&request.body, budget) {
+            Ok(body) => Response::ok(body),
+            Err(err) => Response::error(error_status(&err), &err.to_string()),
+        },
+        (_, "/solvers") | (_, "/solve") => Response::error(405, "method not allowed"),
+        _ => Response::error(404, "not found"),
+    }
+}
+
+// the mistakes of the request are told apart from the failures of the solver
+fn error_status(err: &Error) -> u16 {
+    match err {
+        Error::InvalidInput(_) | Error::Parse { .. } | Error::UnknownCity(_) => 400,
+        _ => 500,
+    }
+}
+
+fn solvers_json() -> String {
+    let solvers: Vec<String> = registry::catalog()
+        .iter()
+        .map(|info| {
+            format!(
+                "{{\"name\": \"{}\", \"aliases\": {}, \"description\": \"{}\", \"category\": \"{}\", \"complexity\": \"{}\", \"is_stochastic\": {}, \"options\": {}}}",
+                json::escape(&info.name),
+                strings_json(&info.aliases),
+                json::escape(&info.description),
+                info.category,
+                json::escape(&info.complexity),
+                info.is_stochastic,
+                strings_json(&info.options)
+            )
+        })
+        .collect();
+
+    format!("[{}]", solvers.join(", "))
+}
+
+fn strings_json(items: &[String]) -> String {
+    let quoted: Vec<String> = items
+        .iter()
+        .map(|item| format!("\"{}\"", json::escape(item)))
+        .collect();
+
+    format!("[{}]", quoted.join(", "))
+}
+
+fn solve_json(body: &str, budget: f32) -> Result<String, Error> {
+    let document = json::parse(body)?;
+    let request = document
+        .as_table()
+        .ok_or_else(|| invalid_request("the body must be an object"))?;
+
+    let solver_name = match request.get("solver").map(Value::as_str) {
+        Some(Some(name)) => name.to_lowercase(),
+        Some(None) => return Err(invalid_request("`solver` must be a string")),
+        None => return Err(invalid_request("`solver` is missing")),
+    };
+    let instance = match request.get("instance") {
+        Some(instance) => read_instance(instance)?,
+        None => return Err(invalid_request("`instance` is missing")),
+    };
+    let options = match request.get("options") {
+        Some(Value::Table(table)) => request_options(table, budget)?,
+        Some(_) => return Err(invalid_request("`options` must be an object")),
+        None => request_options(&Table::new(), budget)?,
+    };
+
+    let solver = registry::resolve(&solver_name)?;
+    options.validate(&solver.metadata())?;
+    let started = Instant::now();
+    let tour = solver
+        .solve(
+            instance.cities(),
+            &options.to_tour_options(),
+            progress::silent_publisher(),
+        )?
+        .with_start_city(&options)?;
+
+    Ok(tour_json(
+        &instance.name,
+        &solver.metadata().name,
+        &tour,
+        started.elapsed(),
+    ))
+}
+
+/// the answer of the solved request, `tsp::wasm` gives the same to JavaScript
+pub(crate) fn tour_json(name: &str, solver: &str, tour: &Solution, elapsed: Duration) -> String {
+    let ids: Vec<String> = tour.route().iter().map(|id| id.to_string()).collect();
+
+    format!(
+        "{{\"name\": \"{}\", \"solver\": \"{}\", \"total\": {}, \"tour\": [{}], \"target_reached\": {}, \"elapsed_ms\": {}}}",
+        json::escape(name),
+        json::escape(solver),
+        tour.total,
+        ids.join(", "),
+        tour.target_reached,
+        elapsed.as_millis()
+    )
+}
+
+/// the solver options of the request, its `max_seconds` can't exceed the budget
+pub fn request_options(table: &Table, budget: f32) -> Result<SolverOptions, Error> {
+    let mut options = SolverOptions::default();
+    options.show_progress = false;
+
+    for (key, value) in table.iter() {
+        config::set_option(&mut options, key, value)?;
+    }
+    options.max_seconds = Some(options.max_seconds.map_or(budget, |s| s.min(budget)));
+
+    Ok(options)
+}
+
+/// the instance of the request, a list of the coordinates or the text of a TSPLIB file
+pub(crate) fn read_instance(instance: &Value) -> Result<TspLibData, Error> {
+    let table = match instance {
+        Value::String(text) => return tsplib::read_from(Cursor::new(text.as_bytes())),
+        Value::Table(table) => table,
+        _ => {
+            return Err(invalid_request(
+                "`instance` must be an object or TSPLIB text",
+            ))
+        }
+    };
+
+    let name = match table.get("name") {
+        Some(name) => name
+            .as_str()
+            .ok_or_else(|| invalid_request("`instance.name` must be a string"))?,
+        None => "instance",
+    };
+    let rows = table
+        .get("cities")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid_request("`instance.cities` must be a list of coordinates"))?;
+
+    let mut cities = Vec::with_capacity(rows.len());
+    for (i, row) in rows.iter().enumerate() {
+        let coords: Option<Vec<f64>> = row.as_array().and_then(|coords| {
+            coords
+                .iter()
+                .map(|c| c.as_float().or_else(|| c.as_integer().map(|n| n as f64)))
+                .collect()
+        });
+
+        match coords {
+            Some(coords) if !coords.is_empty() => cities.push(KDPoint::new_with_id(i + 1, &coords)),
+            _ => {
+                return Err(invalid_request(&format!(
+                    "the city {} must be a list of numbers",
+                    i + 1
+                )))
+            }
+        }
+    }
+
+    Ok(TspLibData::new(name.to_string(), String::new(), cities))
+}
+
+fn invalid_request(message: &str) -> Error {
+    Error::InvalidInput(format!("serve: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post_solve(body: &str) -> Response {
+        let request = Request {
+            method: "POST".to_string(),
+            path: "/solve".to_string(),
+            body: body.to_string(),
+        };
+
+        handle(&request, 5.0)
+    }
+
+    #[test]
+    fn test_read_request() {
+        let text = "POST /solve HTTP/1.1\r\nHost: localhost\r\ncontent-length: 2\r\n\r\n{}";
+
+        let request = read_request(Cursor::new(text)).unwrap();
+        assert_eq!("POST", request.method);
+        assert_eq!("/solve", request.path);
+        assert_eq!("{}", request.body);
+
+        let short = "POST /solve HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}";
+        assert_eq!(400, read_request(Cursor::new(short)).err().unwrap().status);
+    }
+
+    #[test]
+    fn test_read_request_limits_headers() {
+        // the endless header line is cut at the limit
+        let endless = "GET /solvers HTTP/1.1\r\nX-Data: "
+            .bytes()
+            .chain(std::iter::repeat(b'x'))
+            .take(MAX_HEADER_BYTES as usize + 10);
+        let text: Vec<u8> = endless.collect();
+        assert_eq!(431, read_request(Cursor::new(text)).err().unwrap().status);
+
+        // the headers, which end at the limit without the empty line, are cut too
+        let mut text = "GET /solvers HTTP/1.1\r\n".to_string();
+        while text.len() < MAX_HEADER_BYTES as usize {
+            text.push_str("X-Data: 1\r\n");
+        }
+        text.truncate(MAX_HEADER_BYTES as usize - 2);
+        text.push_str("\r\n\r\n");
+        assert_eq!(431, read_request(Cursor::new(text)).err().unwrap().status);
+
+        // the body doesn't count into the headers
+        let body = "x".repeat(MAX_HEADER_BYTES as usize);
+        let text = format!(
+            "POST /solve HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        assert_eq!(body, read_request(Cursor::new(text)).unwrap().body);
+    }
+
+    #[test]
+    fn test_connection_slots_are_limited() {
+        let slots = ConnectionSlots::new(2);
+
+        let first = slots.acquire();
+        let second = slots.acquire();
+        assert!(first.is_some() && second.is_some());
+        assert!(slots.acquire().is_none());
+
+        drop(first);
+        assert!(slots.acquire().is_some());
+    }
+
+    #[test]
+    fn test_handle_lists_solvers() {
+        let request = Request {
+            method: "GET".to_string(),
+            path: "/solvers?format=json".to_string(),
+            body: String::new(),
+        };
+
+        let response = handle(&request, 5.0);
+        assert_eq!(200, response.status);
+        assert!(response.body.starts_with('['));
+        assert!(response
+            .body
+            .contains("{\"name\": \"nearest_neighbor\", \"aliases\": [\"nn\"]"));
+        assert!(json::parse(&response.body).is_ok());
+    }
+
+    #[test]
+    fn test_handle_solves_list_of_cities() {
+        let response = post_solve(
+            r#"{"solver": "nn+2opt", "options": {"seed": 42},
+                "instance": {"name": "square", "cities": [[0, 0], [1, 1], [0, 1], [1, 0]]}}"#,
+        );
+        assert_eq!(200, response.status, "{}", response.body);
+
+        let answer = json::parse(&response.body).unwrap();
+        assert_eq!(Some("square"), answer["name"].as_str());
+        assert_eq!(Some(4), answer["total"].as_integer());
+        assert_eq!(4, answer["tour"].as_array().unwrap().len());
+    }
+
+    #[test]
+    fn test_handle_solves_tsplib_text() {
+        let instance = "NAME: line\\nTYPE: TSP\\nDIMENSION: 3\\nEDGE_WEIGHT_TYPE: EUC_2D\\nNODE_COORD_SECTION\\n1 0 0\\n2 2 0\\n3 1 0\\nEOF\\n";
+        let response = post_solve(&format!(
+            "{{\"solver\": \"nn\", \"instance\": \"{}\"}}",
+            instance
+        ));
+        assert_eq!(200, response.status, "{}", response.body);
+
+        let answer = json::parse(&response.body).unwrap();
+        assert_eq!(Some("line"), answer["name"].as_str());
+        assert_eq!(Some(4), answer["total"].as_integer());
+    }
+
+    #[test]
+    fn test_handle_rejects_invalid_requests() {
+        let response = post_solve(r#"{"solver": "nn", "instance": {"cities": [[0, 0], []]}}"#);
+        assert_eq!(400, response.status);
+        assert_eq!(
+            "{\"error\": \"invalid input: serve: the city 2 must be a list of numbers\"}",
+            response.body
+        );
+
+        let response = post_solve(
+            r#"{"solver": "nn", "instance": {"cities": [[0, 0]]}, "options": {"epohcs": 1}}"#,
+        );
+        assert_eq!(400, response.status);
+        assert!(response.body.contains("unknown key `epohcs`"));
+
+        assert_eq!(
+            400,
+            post_solve(r#"{"solver": "nope", "instance": "x"}"#).status
+        );
+        assert_eq!(400, post_solve("{\"solver\": ").status);
+
+        let request = Request {
+            method: "GET".to_string(),
+            path: "/solve".to_string(),
+            body: String::new(),
+        };
+        assert_eq!(405, handle(&request, 5.0).status);
+    }
+
+    #[test]
+    fn test_request_options_are_capped_by_budget() {
+        let table = json::parse(r#"{"max_seconds": 120, "epochs": 50}"#).unwrap();
+        let options = request_options(table.as_table().unwrap(), 10.0).unwrap();
+        assert_eq!(Some(10.0), options.max_seconds);
+        assert_eq!(50, options.epochs);
+
+        let options = request_options(&Table::new(), 10.0).unwrap();
+        assert_eq!(Some(10.0), options.max_seconds);
+
+        let table = json::parse(r#"{"max-seconds": 2}"#).unwrap();
+        let options = request_options(table.as_table().unwrap(), 10.0).unwrap();
+        assert_eq!(Some(2.0), options.max_seconds);
+    }
+}
@@ -1,56 +1,380 @@
+use rand::rngs::StdRng;
 use rand::Rng;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
 
+use super::clock::Instant;
+use super::distance_matrix::distance_source;
+use super::history::History;
 use super::kdtree::KDPoint;
-use super::progress::{send_progress, ProgressMessage};
-use super::route::Route;
-use super::{total_distance, Solution, SolverOptions};
-
-pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Solution {
-    let cooling_rate = options.cooling_rate;
-    let mut epoch = 0;
-
-    let mut best_route = Route::from_cities(cities);
-    let mut best_distance = total_distance(cities, best_route.route());
-
-    send_progress(ProgressMessage::PathUpdate(
-        best_route.clone(),
-        best_distance,
-    ));
-
-    let mut temperature = options.max_temperature;
-    while epoch < options.epochs || temperature > options.min_temperature {
-        let candidate = best_route.random_successor();
-        let candidate_distance = total_distance(cities, candidate.route());
-
-        if is_acceptable(temperature, best_distance, candidate_distance) {
-            best_route = candidate;
-            best_distance = candidate_distance;
-
-            send_progress(ProgressMessage::PathUpdate(
-                best_route.clone(),
-                best_distance,
-            ));
-            if options.verbose {
-                println!(
-                    "SA: epoch.{:?} new best distance: {:?}",
-                    epoch, best_distance
+use super::progress::{self, ProgressMessage, PublisherFn};
+use super::registry::Category;
+use super::route::Neighborhood;
+use super::trace::{self, MoveKind};
+use super::{validate_cities, Error, Solution, SolverInfo, SolverOptions, TspSolver};
+
+// the adaptive schedule measures the acceptance ratio over this many iterations
+const ADAPTIVE_WINDOW: usize = 100;
+// the acceptance ratio, at which the adaptive schedule cools at the cooling rate
+const TARGET_ACCEPTANCE: f32 = 0.3;
+
+/// how the temperature decreases over the iterations
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CoolingSchedule {
+    #[default]
+    Geometric, // loses the cooling rate share of the temperature on every iteration
+    Linear,      // loses the cooling rate share of the starting temperature on every iteration
+    Logarithmic, // t0 / (1 + ln(1 + rate * k)), cools slower the longer it runs
+    Adaptive,    // geometric, faster while most of the moves are accepted, slower when few are
+    Timed,       // geometric from max to min temperature over the time budget, whatever the epochs
+}
+
+impl CoolingSchedule {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["geometric", "linear", "logarithmic", "adaptive", "timed"]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoolingSchedule::Geometric => "geometric",
+            CoolingSchedule::Linear => "linear",
+            CoolingSchedule::Logarithmic => "logarithmic",
+            CoolingSchedule::Adaptive => "adaptive",
+            CoolingSchedule::Timed => "timed",
+        }
+    }
+}
+
+impl FromStr for CoolingSchedule {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "geometric" => Ok(CoolingSchedule::Geometric),
+            "linear" => Ok(CoolingSchedule::Linear),
+            "logarithmic" => Ok(CoolingSchedule::Logarithmic),
+            "adaptive" => Ok(CoolingSchedule::Adaptive),
+            "timed" => Ok(CoolingSchedule::Timed),
+            _ => Err("unknown cooling schedule"),
+        }
+    }
+}
+
+impl fmt::Display for CoolingSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// Cooling keeps the state of the schedule, which depends on more than the last temperature
+#[derive(Debug, Clone)]
+pub struct Cooling {
+    schedule: CoolingSchedule,
+    rate: f32,
+    start_temperature: f32,
+    step: usize,              // iterations since the start or the last reheat
+    n_accepted: usize,        // accepted moves in the current window of the adaptive schedule
+    speed: f32,               // how much faster than the rate the adaptive schedule cools
+    min_temperature: f32,     // the timed schedule reaches it at the end of the budget
+    started_at: Instant,      // the start or the last reheat
+    ends_at: Option<Instant>, // the end of the time budget
+}
+
+impl Cooling {
+    pub fn new(schedule: CoolingSchedule, rate: f32, start_temperature: f32) -> Self {
+        Cooling {
+            schedule,
+            rate,
+            start_temperature,
+            step: 0,
+            n_accepted: 0,
+            speed: 1.0,
+            min_temperature: 0.0,
+            started_at: Instant::now(),
+            ends_at: None,
+        }
+    }
+
+    /// sets the time budget the timed schedule cools down to the minimum temperature in
+    pub fn with_budget(mut self, min_temperature: f32, max_seconds: Option<f32>) -> Self {
+        self.min_temperature = min_temperature;
+        self.ends_at = max_seconds.map(|secs| self.started_at + Duration::from_secs_f32(secs));
+
+        self
+    }
+
+    /// starts the schedule again from the temperature, the timed one cools down from it
+    /// over the rest of the budget
+    pub fn reheat(&mut self, temperature: f32) {
+        *self = Cooling {
+            min_temperature: self.min_temperature,
+            ends_at: self.ends_at,
+            ..Cooling::new(self.schedule, self.rate, temperature)
+        };
+    }
+
+    /// after the epochs the annealing keeps running until the minimum temperature,
+    /// the logarithmic schedule would practically never reach it
+    pub fn cools_after_epochs(&self) -> bool {
+        self.schedule != CoolingSchedule::Logarithmic
+    }
+
+    /// returns the temperature of the next iteration
+    pub fn next(&mut self, temperature: f32, accepted: bool) -> f32 {
+        self.step += 1;
+
+        match self.schedule {
+            CoolingSchedule::Geometric => cooling(temperature, self.rate),
+            CoolingSchedule::Linear => (temperature - self.rate * self.start_temperature).max(0.0),
+            CoolingSchedule::Logarithmic => {
+                self.start_temperature / (1.0 + (1.0 + self.rate * self.step as f32).ln())
+            }
+            CoolingSchedule::Adaptive => {
+                if accepted {
+                    self.n_accepted += 1;
+                }
+
+                if self.step.is_multiple_of(ADAPTIVE_WINDOW) {
+                    let ratio = self.n_accepted as f32 / ADAPTIVE_WINDOW as f32;
+                    self.speed = (ratio / TARGET_ACCEPTANCE).clamp(0.1, 10.0);
+                    self.n_accepted = 0;
+                }
+
+                cooling(temperature, self.rate * self.speed)
+            }
+            CoolingSchedule::Timed => match self.ends_at {
+                Some(ends_at) => {
+                    let budget = ends_at.saturating_duration_since(self.started_at);
+                    let share = (self.started_at.elapsed().as_secs_f32()
+                        / budget.as_secs_f32().max(f32::EPSILON))
+                    .min(1.0);
+
+                    self.start_temperature
+                        * (self.min_temperature / self.start_temperature).powf(share)
+                }
+                // the options are validated, so it cools at the rate only without the budget
+                None => cooling(temperature, self.rate),
+            },
+        }
+    }
+}
+
+/// the temperature and the moves of the annealing
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaOptions {
+    pub cooling_rate: f32, // the share of the temperature lost on every epoch, in (0, 1)
+    pub cooling_schedule: CoolingSchedule, // how the temperature decreases
+    pub neighborhood: Neighborhood, // the moves tried on the tour
+    pub max_temperature: f32, // the initial temperature
+    pub min_temperature: f32, // the search stops when it has cooled down to it
+    pub reheat: Option<f32>, // the fraction of max_temperature it reheats to when stuck
+    pub restart_from_best: Option<usize>, // epochs without a new best tour before it returns to it
+    pub platoo_epochs: usize, // epochs without an accepted move before it reheats
+}
+
+impl SaOptions {
+    /// copies the options into the options the solver runs with
+    pub fn apply(&self, options: &mut SolverOptions) {
+        options.cooling_rate = self.cooling_rate;
+        options.cooling_schedule = self.cooling_schedule;
+        options.neighborhood = self.neighborhood;
+        options.max_temperature = self.max_temperature;
+        options.min_temperature = self.min_temperature;
+        options.reheat = self.reheat;
+        options.restart_from_best = self.restart_from_best;
+        options.platoo_epochs = self.platoo_epochs;
+    }
+}
+
+impl Default for SaOptions {
+    fn default() -> Self {
+        SaOptions::from(&SolverOptions::default())
+    }
+}
+
+impl From<&SolverOptions> for SaOptions {
+    fn from(options: &SolverOptions) -> Self {
+        SaOptions {
+            cooling_rate: options.cooling_rate,
+            cooling_schedule: options.cooling_schedule,
+            neighborhood: options.neighborhood,
+            max_temperature: options.max_temperature,
+            min_temperature: options.min_temperature,
+            reheat: options.reheat,
+            restart_from_best: options.restart_from_best,
+            platoo_epochs: options.platoo_epochs,
+        }
+    }
+}
+
+pub struct SimulatedAnnealingSolver;
+
+impl TspSolver for SimulatedAnnealingSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "simulated_annealing",
+            &["sa"],
+            "random moves accepted by the cooling temperature",
+            true,
+        )
+        .with_category(Category::Metaheuristic)
+        .with_complexity("O(epochs * n)")
+        .with_options(&[
+            "epochs",
+            "max_seconds",
+            "target_cost",
+            "cooling_rate",
+            "cooling_schedule",
+            "neighborhood",
+            "min_temperature",
+            "max_temperature",
+            "reheat",
+            "restart_from_best",
+            "platoo_epochs",
+            "max_stale_epochs",
+            "open_tour",
+            "seed",
+        ])
+    }
+
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 3)?;
+
+        let mut cooling = Cooling::new(
+            options.cooling_schedule,
+            options.cooling_rate,
+            options.max_temperature,
+        )
+        .with_budget(options.min_temperature, options.max_seconds);
+        let mut rng = options.rng();
+        let deadline = options.deadline();
+        let mut epoch = 0;
+
+        // the distances are looked up instead of rebuilding the city table on every move
+        let dm = distance_source(cities)?;
+        let mut route = options.start_route(cities)?;
+        let mut distance = dm.route_length(route.route(), options.open_tour)?;
+        // the reheating lets the search walk away from the best tour, so it is kept aside
+        let mut best_route = route.clone();
+        let mut best_distance = distance;
+        let mut history = History::new();
+        history.record(epoch, best_distance);
+
+        progress(ProgressMessage::PathUpdate(route.clone(), distance));
+
+        let mut temperature = options.max_temperature;
+        let mut n_rejected = 0;
+        let mut n_since_best = 0;
+        // unlike n_since_best, the restarts from the best tour don't reset it
+        let mut n_stale = 0;
+        while (epoch < options.epochs
+            || (cooling.cools_after_epochs() && temperature > options.min_temperature))
+            && !deadline.is_over()
+            && !options.is_target_reached(best_distance)
+            && !options.is_stale(n_stale)
+        {
+            n_stale += 1;
+            // the route is changed only when the move is accepted
+            let mv = route.random_move(options.neighborhood, &mut rng)?;
+            let candidate_distance =
+                distance + route.tour_move_delta(dm.as_ref(), mv, options.open_tour)?;
+
+            if is_acceptable(temperature, distance, candidate_distance, &mut rng) {
+                let (from_pos, to_pos) = mv.positions();
+                trace::record(
+                    mv.kind(),
+                    epoch,
+                    from_pos,
+                    to_pos,
+                    candidate_distance - distance,
                 );
+
+                route.apply(mv)?;
+                distance = candidate_distance;
+                n_rejected = 0;
+                if distance < best_distance {
+                    // the deltas accumulate rounding errors, the best one gets the exact length
+                    distance = dm.route_length(route.route(), options.open_tour)?;
+                }
+                if distance < best_distance {
+                    best_route = route.clone();
+                    best_distance = distance;
+                    n_since_best = 0;
+                    n_stale = 0;
+                    history.record(epoch, best_distance);
+                }
+
+                progress(ProgressMessage::PathUpdate(route.clone(), distance));
+                if options.verbose {
+                    println!("SA: epoch.{:?} new best distance: {:?}", epoch, distance);
+                }
+            } else {
+                n_rejected += 1;
+            }
+
+            // the walk went too far from the best tour, it continues from it at the same temperature
+            n_since_best += 1;
+            if options
+                .restart_from_best
+                .is_some_and(|n_epochs| n_since_best > n_epochs)
+            {
+                trace::record(MoveKind::Restart, epoch, 0, 0, best_distance - distance);
+
+                route = best_route.clone();
+                distance = best_distance;
+                n_since_best = 0;
+
+                progress(ProgressMessage::PathUpdate(route.clone(), distance));
+                if options.verbose {
+                    println!("SA: epoch.{:?} restarted from the best tour", epoch);
+                }
+            }
+
+            temperature = cooling.next(temperature, n_rejected == 0);
+            // it reheats only within the epochs, otherwise it would never cool down
+            if let Some(fraction) = options.reheat {
+                if n_rejected >= options.platoo_epochs && epoch < options.epochs {
+                    temperature = temperature.max(fraction * options.max_temperature);
+                    cooling.reheat(temperature);
+                    n_rejected = 0;
+
+                    if options.verbose {
+                        println!("SA: epoch.{:?} reheated to {:?}", epoch, temperature);
+                    }
+                }
+            }
+
+            epoch += 1;
+            if epoch.is_multiple_of(progress::EPOCH_REPORT_INTERVAL) {
+                progress(ProgressMessage::TemperatureUpdate(temperature));
             }
+            progress::report_epoch(&progress, epoch);
         }
 
-        temperature = cooling(temperature, cooling_rate);
-        epoch += 1;
+        progress(ProgressMessage::Done);
+        Ok(Solution::new(best_route.route(), cities)
+            .with_open_tour(options.open_tour)
+            .with_target(options)
+            .with_history(history))
     }
+}
 
-    send_progress(ProgressMessage::Done);
-    Solution::new(best_route.route(), cities)
+/// solves the problem and sends the progress into the global progress channel
+pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Result<Solution, Error> {
+    SimulatedAnnealingSolver.solve(cities, options, progress::default_publisher())
 }
 
 fn cooling(temperature: f32, cooling_rate: f32) -> f32 {
     temperature - cooling_rate * temperature
 }
 
-fn is_acceptable(temperature: f32, old_distance: f32, new_distance: f32) -> bool {
+fn is_acceptable(temperature: f32, old_distance: f32, new_distance: f32, rng: &mut StdRng) -> bool {
     if new_distance < old_distance {
         return true;
     }
@@ -60,8 +384,6 @@ fn is_acceptable(temperature: f32, old_distance: f32, new_distance: f32) -> bool
         return false;
     }
 
-    let mut rng = rand::thread_rng();
-
     let p: f32 = rng.gen();
     let criteria = metropolis(temperature, old_distance, new_distance);
 
@@ -73,4 +395,225 @@ fn metropolis(t: f32, e1: f32, e2: f32) -> f32 {
     (-(e2 - e1) / t).exp()
 }
 
-// TODO: add missing tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::route::{Neighborhood, Route};
+    use crate::tsp::total_distance;
+    use crate::tsp::{generator, kdtree};
+    use rand::SeedableRng;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_solve_with_same_seed_finds_same_route() {
+        let bbox = generator::BoundingBox::square(12.0);
+        let cities = generator::uniform(12, &bbox, &mut StdRng::seed_from_u64(3));
+
+        let mut options = SolverOptions::default();
+        options.epochs = 500;
+        options.cooling_rate = 0.01;
+        options.seed = Some(42);
+
+        let publisher = progress::silent_publisher();
+        let tour1 = SimulatedAnnealingSolver
+            .solve(&cities, &options, publisher.clone())
+            .unwrap();
+        let tour2 = SimulatedAnnealingSolver
+            .solve(&cities, &options, publisher)
+            .unwrap();
+
+        assert_eq!(tour1.route(), tour2.route());
+    }
+
+    #[test]
+    fn test_solve_with_every_neighborhood() {
+        let bbox = generator::BoundingBox::square(100.0);
+        let cities = generator::uniform(30, &bbox, &mut StdRng::seed_from_u64(5));
+        let start_distance = total_distance(&cities, Route::from_cities(&cities).route());
+
+        let mut options = SolverOptions::default();
+        options.epochs = 20_000;
+        options.cooling_rate = 0.001;
+        options.max_temperature = 100.0;
+        options.seed = Some(42);
+
+        for neighborhood in Neighborhood::variants() {
+            options.neighborhood = Neighborhood::from_str(neighborhood).unwrap();
+
+            let tour = solve(&cities, &options).unwrap();
+            assert_eq!(Ok(()), tour.validate());
+            assert!(tour.total < start_distance, "{}", neighborhood);
+        }
+    }
+
+    fn temperature_after(cooling: &mut Cooling, n_steps: usize, accepted: bool) -> f32 {
+        let mut temperature = cooling.start_temperature;
+        for _ in 0..n_steps {
+            temperature = cooling.next(temperature, accepted);
+        }
+
+        temperature
+    }
+
+    #[test]
+    fn test_cooling_schedules() {
+        let mut geometric = Cooling::new(CoolingSchedule::Geometric, 0.01, 100.0);
+        assert!((temperature_after(&mut geometric, 100, true) - 36.6).abs() < 0.1);
+
+        let mut linear = Cooling::new(CoolingSchedule::Linear, 0.01, 100.0);
+        assert!((temperature_after(&mut linear, 50, true) - 50.0).abs() < 0.01);
+        let mut linear = Cooling::new(CoolingSchedule::Linear, 0.01, 100.0);
+        assert_eq!(0.0, temperature_after(&mut linear, 110, true));
+
+        // 100 / (1 + ln(1 + 0.01 * 100))
+        let mut logarithmic = Cooling::new(CoolingSchedule::Logarithmic, 0.01, 100.0);
+        assert!((temperature_after(&mut logarithmic, 100, true) - 59.07).abs() < 0.01);
+        assert!(!logarithmic.cools_after_epochs());
+
+        logarithmic.reheat(50.0);
+        assert!((temperature_after(&mut logarithmic, 100, true) - 29.53).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_adaptive_cooling_follows_acceptance() {
+        let mut accepting = Cooling::new(CoolingSchedule::Adaptive, 0.001, 100.0);
+        let mut rejecting = Cooling::new(CoolingSchedule::Adaptive, 0.001, 100.0);
+        let mut geometric = Cooling::new(CoolingSchedule::Geometric, 0.001, 100.0);
+
+        let n_steps = 10 * ADAPTIVE_WINDOW;
+        let hot = temperature_after(&mut accepting, n_steps, true);
+        let frozen = temperature_after(&mut rejecting, n_steps, false);
+        let plain = temperature_after(&mut geometric, n_steps, true);
+
+        assert!(hot < plain, "{} >= {}", hot, plain);
+        assert!(frozen > plain, "{} <= {}", frozen, plain);
+    }
+
+    #[test]
+    fn test_timed_cooling_follows_the_budget() {
+        let mut timed =
+            Cooling::new(CoolingSchedule::Timed, 0.5, 100.0).with_budget(1.0, Some(0.05));
+        let warm = timed.next(100.0, true);
+        assert!(warm > 50.0, "{}", warm);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!((timed.next(warm, true) - 1.0).abs() < 0.001);
+
+        // the epochs don't end the timed annealing, it runs until the budget is over
+        let bbox = generator::BoundingBox::square(10.0);
+        let cities = generator::uniform(8, &bbox, &mut StdRng::seed_from_u64(3));
+        let mut options = SolverOptions::default();
+        options.cooling_schedule = CoolingSchedule::Timed;
+        options.epochs = 1;
+        options.max_seconds = Some(0.1);
+        options.seed = Some(42);
+
+        let started_at = Instant::now();
+        SimulatedAnnealingSolver
+            .solve(&cities, &options, progress::silent_publisher())
+            .unwrap();
+        assert!(started_at.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[test]
+    fn test_cooling_schedule_from_str() {
+        for name in CoolingSchedule::variants() {
+            assert_eq!(name, CoolingSchedule::from_str(name).unwrap().as_str());
+        }
+
+        assert!(CoolingSchedule::from_str("exponential").is_err());
+    }
+
+    #[test]
+    fn test_reheat_raises_temperature_when_stuck() {
+        let bbox = generator::BoundingBox::square(12.0);
+        let cities = generator::uniform(12, &bbox, &mut StdRng::seed_from_u64(3));
+        let start_distance = total_distance(&cities, Route::from_cities(&cities).route());
+
+        let mut options = SolverOptions::default();
+        options.epochs = 5_000;
+        options.cooling_rate = 0.01;
+        options.platoo_epochs = 100;
+        options.reheat = Some(0.5);
+        options.seed = Some(42);
+
+        let temperatures = Arc::new(Mutex::new(vec![]));
+        let collected = Arc::clone(&temperatures);
+        let publisher: PublisherFn = Arc::new(move |msg| {
+            if let ProgressMessage::TemperatureUpdate(t) = msg {
+                collected.lock().unwrap().push(t);
+            }
+        });
+        let tour = SimulatedAnnealingSolver
+            .solve(&cities, &options, publisher)
+            .unwrap();
+
+        let temperatures = temperatures.lock().unwrap();
+        assert!(
+            temperatures.windows(2).any(|pair| pair[1] > pair[0]),
+            "{:?}",
+            temperatures
+        );
+        assert!(tour.total <= start_distance);
+    }
+
+    #[test]
+    fn test_restart_from_best_returns_to_best_tour() {
+        let bbox = generator::BoundingBox::square(100.0);
+        let cities = generator::uniform(30, &bbox, &mut StdRng::seed_from_u64(3));
+
+        // the temperature stays so high, that the walk takes almost every move
+        let mut options = SolverOptions::default();
+        options.epochs = 2_000;
+        options.restart_from_best = Some(20);
+        options.seed = Some(42);
+
+        let distances = Arc::new(Mutex::new(vec![]));
+        let collected = Arc::clone(&distances);
+        let publisher: PublisherFn = Arc::new(move |msg| {
+            if let ProgressMessage::PathUpdate(_, distance) = msg {
+                collected.lock().unwrap().push(distance);
+            }
+        });
+        let tour = SimulatedAnnealingSolver
+            .solve(&cities, &options, publisher)
+            .unwrap();
+
+        let distances = distances.lock().unwrap();
+        let mut best = distances[0];
+        let mut n_restarts = 0;
+        for pair in distances.windows(2) {
+            best = best.min(pair[0]);
+            if pair[0] > best && pair[1] == best {
+                n_restarts += 1;
+            }
+        }
+
+        assert!(n_restarts > 0);
+        assert!((best.min(*distances.last().unwrap()) - tour.total).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_solve_open_tour() {
+        // the cities on the line, the open tour goes from one end to the other
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![3.0, 0.0],
+            vec![1.0, 0.0],
+            vec![4.0, 0.0],
+            vec![2.0, 0.0],
+        ]);
+        let mut options = SolverOptions::default();
+        options.epochs = 1_000;
+        options.seed = Some(42);
+        options.open_tour = true;
+        assert!(options
+            .check_constraints(&SimulatedAnnealingSolver.metadata())
+            .is_ok());
+
+        let tour = solve(&cities, &options).unwrap();
+
+        assert!(tour.is_open_tour());
+        assert!((tour.total - 4.0).abs() < 0.001, "{}", tour.total);
+    }
+}
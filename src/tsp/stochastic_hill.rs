@@ -1,66 +1,333 @@
+use rand::rngs::StdRng;
+use std::fmt;
+use std::str::FromStr;
+
+use super::distance_matrix::{distance_source, DistanceSource};
+use super::history::History;
 use super::kdtree::KDPoint;
-use super::progress::{send_progress, ProgressMessage};
-use super::route::Route;
-use super::{total_distance, Solution, SolverOptions};
-
-pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Solution {
-    let mut current_route = Route::from_cities(cities);
-    let mut best_route = current_route.clone();
-
-    //mix up the cities to avoid getting stuck due bad initial state
-    current_route.shuffle();
-    send_progress(ProgressMessage::PathUpdate(current_route.clone(), 0.0));
-
-    let mut epoch = 0;
-    let mut n_stale = 0;
-    let mut best_distance = total_distance(cities, &best_route.route());
-    loop {
-        let candidate = current_route.random_successor();
-        let candidate_distance = total_distance(&cities, candidate.route());
-
-        if candidate_distance < best_distance {
-            best_route = candidate;
-            best_distance = candidate_distance;
-
-            n_stale = 0;
-
-            send_progress(ProgressMessage::PathUpdate(
-                best_route.clone(),
-                best_distance,
-            ));
-
-            if options.verbose {
-                println!("Epoch: {:?}, new best distance: {:}", epoch, best_distance);
-            }
-        } else {
-            n_stale += 1; // to measure how long we have been walking around on the platoo
+use super::progress::{self, ProgressMessage, PublisherFn};
+use super::registry::Category;
+use super::route::{Move, Neighborhood, Route};
+use super::trace::{self, MoveKind};
+use super::{validate_cities, Error, Solution, SolverInfo, SolverOptions, TspSolver};
+
+// the moves shorter than this are rounding errors, they would let the climb go in circles
+const MIN_GAIN: f32 = 1e-5;
+
+/// which of the sampled moves the hill climbing takes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HillStrategy {
+    #[default]
+    First, // samples one move per epoch and takes it when it shortens the tour
+    Best, // samples `n_samples` moves per epoch and takes the shortest one
+}
+
+impl HillStrategy {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["first", "best"]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HillStrategy::First => "first",
+            HillStrategy::Best => "best",
+        }
+    }
+}
+
+impl FromStr for HillStrategy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(HillStrategy::First),
+            "best" => Ok(HillStrategy::Best),
+            _ => Err("unknown hill strategy"),
+        }
+    }
+}
+
+impl fmt::Display for HillStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// how the hill climbing samples its moves and when it restarts
+#[derive(Debug, Clone, PartialEq)]
+pub struct HillOptions {
+    pub platoo_epochs: usize, // epochs without a better tour before the restart
+    pub neighborhood: Neighborhood, // the moves tried on the tour
+    pub hill_strategy: HillStrategy, // which sampled move the climb takes
+    pub n_samples: Option<usize>, // moves sampled per epoch by the best strategy, n cities by default
+}
+
+impl HillOptions {
+    /// copies the options into the options the solver runs with
+    pub fn apply(&self, options: &mut SolverOptions) {
+        options.platoo_epochs = self.platoo_epochs;
+        options.neighborhood = self.neighborhood;
+        options.hill_strategy = self.hill_strategy;
+        options.n_samples = self.n_samples;
+    }
+}
+
+impl Default for HillOptions {
+    fn default() -> Self {
+        HillOptions::from(&SolverOptions::default())
+    }
+}
+
+impl From<&SolverOptions> for HillOptions {
+    fn from(options: &SolverOptions) -> Self {
+        HillOptions {
+            platoo_epochs: options.platoo_epochs,
+            neighborhood: options.neighborhood,
+            hill_strategy: options.hill_strategy,
+            n_samples: options.n_samples,
+        }
+    }
+}
+
+pub struct StochasticHillSolver;
+
+impl TspSolver for StochasticHillSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "stochastic_hill",
+            &[],
+            "random moves with restarts on the plateau",
+            true,
+        )
+        .with_category(Category::Metaheuristic)
+        .with_complexity("O(epochs * n)")
+        .with_options(&[
+            "epochs",
+            "platoo_epochs",
+            "neighborhood",
+            "hill_strategy",
+            "n_samples",
+            "max_seconds",
+            "target_cost",
+            "open_tour",
+            "seed",
+        ])
+    }
+
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 3)?;
+
+        let mut rng = options.rng();
+        let deadline = options.deadline();
+        // the distances are looked up instead of rebuilding the city table on every move
+        let dm = distance_source(cities)?;
+        let mut current_route = options.start_route(cities)?;
+        let mut best_route = current_route.clone();
+
+        //mix up the cities to avoid getting stuck due bad initial state,
+        //the given initial route is kept as it is
+        if options.initial_route.is_none() {
+            current_route.shuffle(&mut rng);
         }
+        let mut current_distance = dm.route_length(current_route.route(), options.open_tour)?;
+        progress(ProgressMessage::PathUpdate(
+            current_route.clone(),
+            current_distance,
+        ));
+
+        let n_samples = match options.hill_strategy {
+            HillStrategy::First => 1,
+            HillStrategy::Best => options.n_samples.unwrap_or(cities.len()).max(1),
+        };
+
+        let mut epoch = 0;
+        let mut n_stale = 0;
+        let mut best_distance = dm.route_length(best_route.route(), options.open_tour)?;
+        let mut history = History::new();
+        history.record(epoch, best_distance);
+        loop {
+            // it climbs only with the moves which shorten the current tour
+            let (mv, delta) = best_sample(
+                dm.as_ref(),
+                &current_route,
+                options.neighborhood,
+                n_samples,
+                options.open_tour,
+                &mut rng,
+            )?;
 
-        epoch += 1;
+            if delta < -MIN_GAIN {
+                let (from_pos, to_pos) = mv.positions();
+                trace::record(mv.kind(), epoch, from_pos, to_pos, delta);
 
-        // restart search if been wandering too long on the platoo
-        if n_stale > options.platoo_epochs && options.platoo_epochs > 0 {
-            if options.verbose {
-                println!(
-                    "Epoch: {:?}, got stuck after {:?} steps, going to restart search",
-                    epoch, options.platoo_epochs
-                );
+                current_route.apply(mv)?;
+                current_distance += delta;
+                n_stale = 0;
+
+                if current_distance < best_distance {
+                    // the exact length, so the deltas don't accumulate rounding errors
+                    current_distance = dm.route_length(current_route.route(), options.open_tour)?;
+                }
+                if current_distance < best_distance {
+                    best_route = current_route.clone();
+                    best_distance = current_distance;
+                    history.record(epoch, best_distance);
+
+                    progress(ProgressMessage::PathUpdate(
+                        best_route.clone(),
+                        best_distance,
+                    ));
+
+                    if options.verbose {
+                        println!("Epoch: {:?}, new best distance: {:}", epoch, best_distance);
+                    }
+                }
+            } else {
+                n_stale += 1; // to measure how long we have been walking around on the platoo
             }
 
-            current_route.shuffle();
-            n_stale = 0;
+            epoch += 1;
+
+            progress::report_epoch(&progress, epoch);
 
-            send_progress(ProgressMessage::PathUpdate(current_route.clone(), 0.0));
+            // restart search if been wandering too long on the platoo
+            if n_stale > options.platoo_epochs && options.platoo_epochs > 0 {
+                if options.verbose {
+                    println!(
+                        "Epoch: {:?}, got stuck after {:?} steps, going to restart search",
+                        epoch, options.platoo_epochs
+                    );
+                }
+
+                current_route.shuffle(&mut rng);
+                n_stale = 0;
+
+                current_distance = dm.route_length(current_route.route(), options.open_tour)?;
+                if trace::is_enabled() {
+                    trace::record(
+                        MoveKind::Restart,
+                        epoch,
+                        0,
+                        0,
+                        current_distance - best_distance,
+                    );
+                }
+
+                progress(ProgressMessage::PathUpdate(
+                    current_route.clone(),
+                    current_distance,
+                ));
+            }
+
+            // check if we should finish the search
+            if (options.epochs > 0 && epoch > options.epochs)
+                || deadline.is_over()
+                || options.is_target_reached(best_distance)
+            {
+                break;
+            }
         }
 
-        // check if we should finish the search
-        if options.epochs > 0 && epoch > options.epochs {
-            break;
+        progress(ProgressMessage::Done);
+        Ok(Solution::new(best_route.route(), cities)
+            .with_open_tour(options.open_tour)
+            .with_target(options)
+            .with_history(history))
+    }
+}
+
+/// solves the problem and sends the progress into the global progress channel
+pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Result<Solution, Error> {
+    StochasticHillSolver.solve(cities, options, progress::default_publisher())
+}
+
+// samples n random moves of the neighborhood and returns the one with the smallest delta
+fn best_sample(
+    dm: &dyn DistanceSource,
+    route: &Route,
+    neighborhood: Neighborhood,
+    n_samples: usize,
+    open_tour: bool,
+    rng: &mut StdRng,
+) -> Result<(Move, f32), Error> {
+    let mv = route.random_move(neighborhood, rng)?;
+    let mut best = (mv, route.tour_move_delta(dm, mv, open_tour)?);
+
+    for _ in 1..n_samples {
+        let mv = route.random_move(neighborhood, rng)?;
+        let delta = route.tour_move_delta(dm, mv, open_tour)?;
+        if delta < best.1 {
+            best = (mv, delta);
         }
     }
 
-    send_progress(ProgressMessage::Done);
-    Solution::new(best_route.route(), cities)
+    Ok(best)
 }
 
-// TODO: add missing tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::{generator, kdtree, total_distance};
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_solve_with_every_strategy() {
+        let bbox = generator::BoundingBox::square(100.0);
+        let cities = generator::uniform(40, &bbox, &mut StdRng::seed_from_u64(7));
+
+        let mut options = SolverOptions::default();
+        options.epochs = 300;
+        options.platoo_epochs = 0;
+        options.initial_route = Some(Route::from_cities(&cities).route().to_vec());
+        options.seed = Some(42);
+        let start_distance = total_distance(&cities, Route::from_cities(&cities).route());
+
+        let first = solve(&cities, &options).unwrap();
+        assert_eq!(Ok(()), first.validate());
+        assert!(first.total < start_distance);
+
+        // the best of the samples climbs faster over the same number of epochs
+        options.hill_strategy = HillStrategy::Best;
+        let best = solve(&cities, &options).unwrap();
+        assert_eq!(Ok(()), best.validate());
+        assert!(best.total < first.total);
+    }
+
+    #[test]
+    fn test_hill_strategy_from_str() {
+        for name in HillStrategy::variants() {
+            assert_eq!(name, HillStrategy::from_str(name).unwrap().as_str());
+        }
+        assert!(HillStrategy::from_str("steepest").is_err());
+    }
+
+    #[test]
+    fn test_solve_open_tour() {
+        // the cities on the line, the open tour goes from one end to the other
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![3.0, 0.0],
+            vec![1.0, 0.0],
+            vec![4.0, 0.0],
+            vec![2.0, 0.0],
+        ]);
+        let mut options = SolverOptions::default();
+        options.epochs = 1_000;
+        options.platoo_epochs = 50;
+        options.seed = Some(42);
+        options.open_tour = true;
+        assert!(options
+            .check_constraints(&StochasticHillSolver.metadata())
+            .is_ok());
+
+        let tour = solve(&cities, &options).unwrap();
+
+        assert!(tour.is_open_tour());
+        assert!((tour.total - 4.0).abs() < 0.001, "{}", tour.total);
+    }
+}
@@ -1,106 +1,458 @@
-use std::collections::VecDeque;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::HashMap;
 
+use super::distance_matrix::{distance_source, DistanceSource};
+use super::history::History;
 use super::kdtree::KDPoint;
-use super::progress::{send_progress, ProgressMessage};
-use super::route::Route;
-use super::{total_distance, Solution, SolverOptions};
-
-pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Solution {
-    let tabu_capacity = cities.len();
-
-    let mut tabu_list = TabuList::new(tabu_capacity);
-
-    let mut best_route = Route::from_cities(cities);
-    tabu_list.add(best_route.clone());
-
-    send_progress(ProgressMessage::PathUpdate(best_route.clone(), 0.0));
-
-    let mut u = best_route.clone();
-    let mut best_distance = distance(cities, &u);
-    let mut done = false;
-    let mut epoch = 0;
-    while !done {
-        let (local_best, local_distance) = select(cities, &u, &tabu_list);
-        if local_distance < best_distance {
-            best_route = local_best.clone();
-            best_distance = local_distance;
-
-            send_progress(ProgressMessage::PathUpdate(
-                best_route.clone(),
-                best_distance,
-            ));
-
-            if options.verbose {
-                println!(
-                    "Tabusearch: epoch.{:?} new best {:?}",
-                    epoch, local_distance
-                );
-            }
+use super::progress::{self, ProgressMessage, PublisherFn};
+use super::registry::Category;
+use super::route::{random_position_pair, Move, Route};
+use super::trace::{self, MoveKind};
+use super::{validate_cities, Error, Solution, SolverInfo, SolverOptions, TspSolver};
+
+// the removed edges stay tabu at least for so many epochs
+const MIN_TENURE: usize = 5;
+
+/// the options tabu search adds to the shared ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabuOptions {
+    pub aspiration: bool, // accepts the tabu move when it finds a new best tour
+}
+
+impl TabuOptions {
+    /// copies the options into the options the solver runs with
+    pub fn apply(&self, options: &mut SolverOptions) {
+        options.aspiration = self.aspiration;
+    }
+}
+
+impl Default for TabuOptions {
+    fn default() -> Self {
+        TabuOptions::from(&SolverOptions::default())
+    }
+}
+
+impl From<&SolverOptions> for TabuOptions {
+    fn from(options: &SolverOptions) -> Self {
+        TabuOptions {
+            aspiration: options.aspiration,
         }
+    }
+}
 
-        // refine tabu list
-        tabu_list.add(u.clone());
-        u = local_best; // continue search from local best
+pub struct TabuSearchSolver;
 
-        epoch += 1;
-        done = update_terminate(epoch, options.epochs);
+impl TspSolver for TabuSearchSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "tabu_search",
+            &[],
+            "best 2-opt move which is not on the tabu list",
+            true,
+        )
+        .with_category(Category::Metaheuristic)
+        .with_complexity("O(epochs * n)")
+        .with_options(&[
+            "epochs",
+            "aspiration",
+            "max_seconds",
+            "target_cost",
+            "max_stale_epochs",
+            "open_tour",
+            "seed",
+        ])
     }
 
-    send_progress(ProgressMessage::Done);
-    Solution::new(best_route.route(), cities)
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 3)?;
+
+        let mut rng = options.rng();
+        let deadline = options.deadline();
+        let dm = distance_source(cities)?;
+
+        let mut tabu_list = TabuList::new(cities.len());
+
+        let mut best_route = options.start_route(cities)?;
+        let mut u = best_route.clone();
+        let mut u_distance = distance(dm.as_ref(), &u, options.open_tour)?;
+        progress(ProgressMessage::PathUpdate(best_route.clone(), u_distance));
+        let mut best_distance = u_distance;
+        let mut done = false;
+        let mut epoch = 0;
+        let mut n_stale = 0;
+        let mut history = History::new();
+        history.record(epoch, best_distance);
+        while !done {
+            n_stale += 1;
+            // the tabu move must shorten the tour below the best one
+            let aspiration = Some(best_distance - u_distance).filter(|_| options.aspiration);
+            if let Some(mv) = select(
+                dm.as_ref(),
+                &u,
+                &tabu_list,
+                epoch,
+                aspiration,
+                options.open_tour,
+                &mut rng,
+            )? {
+                trace::record(MoveKind::TwoOpt, epoch, mv.from, mv.to, mv.delta);
+
+                // the move removes the edges a-b and c-d, adding them back is tabu for a while
+                tabu_list.add(mv.removed[0], epoch, &mut rng);
+                tabu_list.add(mv.removed[1], epoch, &mut rng);
+                u.reverse(mv.from, mv.to)?; // continue search from local best
+                u_distance += mv.delta;
+            }
+
+            if u_distance < best_distance {
+                // the deltas accumulate rounding errors, the best one gets the exact length
+                u_distance = distance(dm.as_ref(), &u, options.open_tour)?;
+            }
+
+            if u_distance < best_distance {
+                best_route = u.clone();
+                best_distance = u_distance;
+                n_stale = 0;
+                history.record(epoch, best_distance);
+
+                progress(ProgressMessage::PathUpdate(
+                    best_route.clone(),
+                    best_distance,
+                ));
+
+                if options.verbose {
+                    println!("Tabusearch: epoch.{:?} new best {:?}", epoch, u_distance);
+                }
+            }
+
+            epoch += 1;
+            tabu_list.expire(epoch);
+
+            progress::report_epoch(&progress, epoch);
+            done = update_terminate(epoch, options.epochs)
+                || deadline.is_over()
+                || options.is_target_reached(best_distance)
+                || options.is_stale(n_stale);
+        }
+
+        progress(ProgressMessage::Done);
+        Ok(Solution::new(best_route.route(), cities)
+            .with_open_tour(options.open_tour)
+            .with_target(options)
+            .with_history(history))
+    }
 }
 
-fn select(cities: &[KDPoint], route: &Route, tabu_list: &TabuList) -> (Route, f32) {
-    let local_best = distance(cities, route);
+/// solves the problem and sends the progress into the global progress channel
+pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Result<Solution, Error> {
+    TabuSearchSolver.solve(cities, options, progress::default_publisher())
+}
 
-    let mut candidate = route.random_successor();
-    let mut candidate_distance = distance(cities, &candidate);
+// 2-opt move which reverses the cities between positions
+#[derive(Debug, Clone, PartialEq)]
+struct TabuMove {
+    from: usize,
+    to: usize,
+    delta: f32,
+    removed: [Edge; 2],
+    added: [Edge; 2],
+}
+
+// samples n random 2-opt moves and returns the best one which doesn't add a tabu edge,
+// the tabu move is allowed only when its delta is below the aspiration;
+// returns None if every sampled move was tabu
+fn select(
+    dm: &dyn DistanceSource,
+    route: &Route,
+    tabu_list: &TabuList,
+    epoch: usize,
+    aspiration: Option<f32>,
+    open_tour: bool,
+    rng: &mut StdRng,
+) -> Result<Option<TabuMove>, Error> {
+    let mut selected: Option<TabuMove> = None;
 
-    // try to local best
     for _ in 0..route.len() {
-        if candidate_distance < local_best && !tabu_list.contains(&candidate) {
-            break;
+        let (from, to) = random_position_pair(route.len(), rng)?;
+        let candidate = match two_opt_move(dm, route, from, to, open_tour)? {
+            Some(candidate) => candidate,
+            None => continue,
+        };
+
+        if selected
+            .as_ref()
+            .is_some_and(|m| m.delta <= candidate.delta)
+        {
+            continue;
         }
 
-        candidate = route.random_successor();
-        candidate_distance = distance(cities, &candidate);
+        if is_allowed(&candidate, tabu_list, epoch, aspiration) {
+            selected = Some(candidate);
+        }
+    }
+
+    Ok(selected)
+}
+
+// the move is allowed if it doesn't add a tabu edge or if its delta is below the aspiration,
+// None disables the aspiration
+fn is_allowed(mv: &TabuMove, tabu_list: &TabuList, epoch: usize, aspiration: Option<f32>) -> bool {
+    let is_tabu = mv.added.iter().any(|e| tabu_list.is_tabu(*e, epoch));
+
+    !is_tabu || aspiration.is_some_and(|max_delta| mv.delta < max_delta)
+}
+
+// the cities a-b and c-d around the reversed segment; the ends of the open tour count as
+// the edge too, so the tabu list keeps them from becoming the ends again
+fn end_cities(route: &[usize], from: usize, to: usize) -> (usize, usize, usize, usize) {
+    let n = route.len();
+
+    (
+        route[(from + n - 1) % n],
+        route[from],
+        route[to],
+        route[(to + 1) % n],
+    )
+}
+
+// the move replaces the edges a-b and c-d with a-c and b-d;
+// None for the segments which don't change the tour, the open tour changes unless
+// the whole tour is reversed
+fn two_opt_move(
+    dm: &dyn DistanceSource,
+    route: &Route,
+    from: usize,
+    to: usize,
+    open_tour: bool,
+) -> Result<Option<TabuMove>, Error> {
+    let n_kept = if open_tour { 1 } else { 2 };
+    if from >= to || to - from + n_kept >= route.len() {
+        return Ok(None);
     }
 
-    (candidate, candidate_distance)
+    let (a, b, c, d) = end_cities(route.route(), from, to);
+
+    Ok(Some(TabuMove {
+        from,
+        to,
+        delta: route.tour_move_delta(dm, Move::Reverse(from, to), open_tour)?,
+        removed: [edge(a, b), edge(c, d)],
+        added: [edge(a, c), edge(b, d)],
+    }))
 }
 
-fn distance(cities: &[KDPoint], route: &Route) -> f32 {
-    total_distance(cities, route.route())
+fn distance(dm: &dyn DistanceSource, route: &Route, open_tour: bool) -> Result<f32, Error> {
+    dm.route_length(route.route(), open_tour)
 }
 
 fn update_terminate(epoch: usize, max_epochs: usize) -> bool {
     max_epochs > 0 && epoch > max_epochs
 }
 
+// undirected edge between the cities, the smaller id goes first
+type Edge = (usize, usize);
+
+fn edge(a: usize, b: usize) -> Edge {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+// tabu edges with the epoch until which they can't be added back into the tour,
+// every entry gets its own tenure so the search doesn't cycle with a fixed period
 struct TabuList {
-    pub capacity: usize, // number of max items we are going to block
-    items: VecDeque<Route>,
+    min_tenure: usize,
+    expires_at: HashMap<Edge, usize>,
 }
 
 impl TabuList {
-    pub fn new(capacity: usize) -> Self {
+    pub fn new(n_cities: usize) -> Self {
         TabuList {
-            capacity,
-            items: VecDeque::with_capacity(capacity),
+            min_tenure: (n_cities / 10).max(MIN_TENURE),
+            expires_at: HashMap::new(),
         }
     }
 
-    pub fn add(&mut self, route: Route) {
-        // if queue is full drop the oldest item
-        if self.items.len() >= self.capacity {
-            self.items.pop_back();
+    // the tenure is drawn from min_tenure..=2*min_tenure epochs
+    pub fn add(&mut self, edge: Edge, epoch: usize, rng: &mut StdRng) {
+        let tenure = rng.gen_range(self.min_tenure, 2 * self.min_tenure + 1);
+
+        self.expires_at.insert(edge, epoch + tenure);
+    }
+
+    pub fn is_tabu(&self, edge: Edge, epoch: usize) -> bool {
+        self.expires_at
+            .get(&edge)
+            .is_some_and(|expires_at| epoch < *expires_at)
+    }
+
+    // drops the expired entries, so the list keeps only the recent moves
+    pub fn expire(&mut self, epoch: usize) {
+        if epoch.is_multiple_of(self.min_tenure) {
+            self.expires_at.retain(|_, expires_at| epoch < *expires_at);
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::distance_matrix::DistanceMatrix;
+    use crate::tsp::kdtree;
+    use rand::SeedableRng;
+    use std::sync::{Arc, Mutex};
 
-        // add new item at the beginning
-        self.items.push_front(route);
+    fn square_cities() -> Vec<KDPoint> {
+        kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 0.5],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 0.5],
+            vec![1.0, 0.0],
+        ])
     }
 
-    pub fn contains(&self, route: &Route) -> bool {
-        self.items.contains(route)
+    #[test]
+    fn test_move_delta_matches_tour_length() {
+        let cities = square_cities();
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+        let route = Route::new(&[0, 3, 1, 2, 4, 5]);
+
+        let mv = two_opt_move(&dm, &route, 1, 2, false).unwrap().unwrap();
+        let after = route.successor(mv.from, mv.to).unwrap();
+
+        let expected =
+            distance(&dm, &after, false).unwrap() - distance(&dm, &route, false).unwrap();
+        assert!((mv.delta - expected).abs() < 0.001, "{}", mv.delta);
+        assert_eq!([(0, 3), (1, 2)], mv.removed);
+
+        // the reversal of all but one city only flips the direction of the tour
+        assert_eq!(None, two_opt_move(&dm, &route, 0, 4, false).unwrap());
+
+        // but it moves the end of the open tour
+        let mv = two_opt_move(&dm, &route, 0, 4, true).unwrap().unwrap();
+        let after = route.successor(mv.from, mv.to).unwrap();
+        let expected = distance(&dm, &after, true).unwrap() - distance(&dm, &route, true).unwrap();
+        assert!((mv.delta - expected).abs() < 0.001, "{}", mv.delta);
+    }
+
+    #[test]
+    fn test_unknown_city_is_error() {
+        let cities = square_cities();
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+        let route = Route::new(&[0, 3, 1, 9, 4, 5]);
+
+        assert!(distance(&dm, &route, false).is_err());
+        assert!(two_opt_move(&dm, &route, 1, 3, false).is_err());
+    }
+
+    #[test]
+    fn test_tabu_entries_expire() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut tabu_list = TabuList::new(6);
+        tabu_list.add(edge(3, 1), 10, &mut rng);
+
+        assert!(tabu_list.is_tabu((1, 3), 10));
+        assert!(tabu_list.is_tabu((1, 3), 10 + MIN_TENURE - 1));
+        assert!(!tabu_list.is_tabu((1, 3), 10 + 2 * MIN_TENURE));
+        assert!(!tabu_list.is_tabu((1, 2), 10));
+
+        tabu_list.expire(10 + 2 * MIN_TENURE);
+        assert!(tabu_list.expires_at.is_empty());
+    }
+
+    #[test]
+    fn test_aspiration_allows_tabu_move() {
+        let cities = square_cities();
+        let dm = DistanceMatrix::from_cities(&cities).unwrap();
+        let route = Route::new(&[0, 3, 2, 1, 4, 5]);
+
+        // reversing 3-2-1 adds the edges 0-1 and 3-4 and finds the optimum
+        let mv = two_opt_move(&dm, &route, 1, 3, false).unwrap().unwrap();
+        assert_eq!([(0, 1), (3, 4)], mv.added);
+        assert!(mv.delta < 0.0);
+
+        let mut tabu_list = TabuList::new(6);
+        assert!(is_allowed(&mv, &tabu_list, 1, None));
+
+        tabu_list.add(edge(1, 0), 0, &mut StdRng::seed_from_u64(7));
+        assert!(!is_allowed(&mv, &tabu_list, 1, None));
+        // the move must be shorter than the best tour, not only as short as it
+        assert!(!is_allowed(&mv, &tabu_list, 1, Some(mv.delta)));
+        assert!(is_allowed(&mv, &tabu_list, 1, Some(0.0)));
+    }
+
+    #[test]
+    fn test_solve_finds_optimum_of_small_problem() {
+        let cities = square_cities();
+        let mut options = SolverOptions::default();
+        options.epochs = 200;
+        options.seed = Some(42);
+        options.initial_route = Some(vec![0, 3, 1, 4, 2, 5]);
+
+        let tour = TabuSearchSolver
+            .solve(&cities, &options, progress::silent_publisher())
+            .unwrap();
+
+        assert!((tour.total - 4.0).abs() < 0.001, "{}", tour.total);
+    }
+
+    #[test]
+    fn test_solve_open_tour() {
+        // the cities on the line, the open tour goes from one end to the other
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![3.0, 0.0],
+            vec![1.0, 0.0],
+            vec![4.0, 0.0],
+            vec![2.0, 0.0],
+        ]);
+        let mut options = SolverOptions::default();
+        options.epochs = 200;
+        options.seed = Some(42);
+        options.open_tour = true;
+        assert!(options
+            .check_constraints(&TabuSearchSolver.metadata())
+            .is_ok());
+
+        let tour = TabuSearchSolver
+            .solve(&cities, &options, progress::silent_publisher())
+            .unwrap();
+
+        assert!(tour.is_open_tour());
+        assert!((tour.total - 4.0).abs() < 0.001, "{}", tour.total);
+    }
+
+    #[test]
+    fn test_solve_stops_when_stale() {
+        let cities = square_cities();
+        let epochs = Arc::new(Mutex::new(vec![]));
+        let collected = Arc::clone(&epochs);
+        let publisher: PublisherFn = Arc::new(move |msg| {
+            if let ProgressMessage::EpochUpdate(epoch) = msg {
+                collected.lock().unwrap().push(epoch);
+            }
+        });
+        let mut options = SolverOptions::default();
+        options.epochs = 100_000;
+        options.max_stale_epochs = Some(50);
+        options.seed = Some(42);
+
+        let tour = TabuSearchSolver
+            .solve(&cities, &options, publisher)
+            .unwrap();
+
+        assert!((tour.total - 4.0).abs() < 0.001, "{}", tour.total);
+        // the optimum of the square is found in a few epochs, then it stops after 50 more
+        assert!(epochs.lock().unwrap().is_empty());
     }
 }
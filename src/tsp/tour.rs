@@ -0,0 +1,535 @@
+/// Tour is a cyclic order of nodes, which supports the queries and the moves that
+/// local search heuristics need: successor, predecessor, betweenness and 2-opt reversal.
+///
+/// Nodes are positions of cities in the input slice (0..n), not the city ids.
+///
+/// There are 2 implementations:
+///
+/// * `ArrayTour` keeps the order in a vector and reverses by swapping the elements,
+///   it is O(n) per reversal but has very low constant factors;
+/// * `TwoLevelTour` splits the order into ~sqrt(n) segments, which all have a reversal flag,
+///   so that a reversal only splits a few segments and flips the flags of the others;
+///
+/// Use `from_order`, which picks the better representation for the size of the problem.
+///
+/// source:
+/// Fredman, Johnson, McGeoch, Ostheimer, "Data structures for traveling salesmen", 1995
+use std::cmp::max;
+
+/// from this size on the two-level list beats the plain vector, see benches/tour_reversal.rs
+pub const TWO_LEVEL_THRESHOLD: usize = 1_000;
+
+const MIN_SEGMENT_SIZE: usize = 8;
+
+pub trait Tour {
+    /// number of nodes on the tour
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// returns the node following the node on the tour
+    fn next(&self, node: usize) -> usize;
+
+    /// returns the node preceding the node on the tour
+    fn prev(&self, node: usize) -> usize;
+
+    /// true if the node `b` is on the path from `a` to `c` following the tour direction
+    fn between(&self, a: usize, b: usize, c: usize) -> bool;
+
+    /// reverses the path from node `from` to node `to` (both inclusive);
+    /// implementations may reverse the complementary path instead, which results
+    /// in the same cycle, but traversed in the opposite direction
+    fn reverse(&mut self, from: usize, to: usize);
+
+    /// returns the nodes in the tour order, starting from the node 0
+    fn to_vec(&self) -> Vec<usize>;
+
+    /// returns the position of the node in `to_vec`
+    fn position(&self, node: usize) -> usize;
+}
+
+/// builds the tour representation that suits best for the number of nodes
+pub fn from_order(order: &[usize]) -> Box<dyn Tour> {
+    if order.len() < TWO_LEVEL_THRESHOLD {
+        Box::new(ArrayTour::new(order))
+    } else {
+        Box::new(TwoLevelTour::new(order))
+    }
+}
+
+fn between_positions(pa: usize, pb: usize, pc: usize) -> bool {
+    if pa <= pc {
+        pa <= pb && pb <= pc
+    } else {
+        pb >= pa || pb <= pc
+    }
+}
+
+fn rotate_to_first(mut nodes: Vec<usize>) -> Vec<usize> {
+    if let Some(pos) = nodes.iter().position(|&x| x == 0) {
+        nodes.rotate_left(pos);
+    }
+
+    nodes
+}
+
+// -- ArrayTour
+
+#[derive(Debug, Clone)]
+pub struct ArrayTour {
+    order: Vec<usize>,
+    pos: Vec<usize>, // node -> index in the order
+}
+
+impl ArrayTour {
+    /// order must be a permutation of the nodes 0..n
+    pub fn new(order: &[usize]) -> Self {
+        let mut pos = vec![0; order.len()];
+        for (i, &node) in order.iter().enumerate() {
+            pos[node] = i;
+        }
+
+        ArrayTour {
+            order: order.to_vec(),
+            pos,
+        }
+    }
+}
+
+impl Tour for ArrayTour {
+    fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    fn next(&self, node: usize) -> usize {
+        self.order[(self.pos[node] + 1) % self.order.len()]
+    }
+
+    fn prev(&self, node: usize) -> usize {
+        let n = self.order.len();
+        self.order[(self.pos[node] + n - 1) % n]
+    }
+
+    fn between(&self, a: usize, b: usize, c: usize) -> bool {
+        between_positions(self.pos[a], self.pos[b], self.pos[c])
+    }
+
+    fn reverse(&mut self, from: usize, to: usize) {
+        let n = self.order.len();
+        let mut i = self.pos[from];
+        let mut j = self.pos[to];
+
+        // reversing the shorter side gives the same cycle with fewer swaps
+        let inner_len = (j + n - i) % n + 1;
+        let swap_len = if inner_len * 2 > n {
+            let complement = n - inner_len;
+            i = (j + 1) % n;
+            j = (i + complement + n - 1) % n;
+            complement
+        } else {
+            inner_len
+        };
+
+        for _ in 0..(swap_len / 2) {
+            self.order.swap(i, j);
+            self.pos[self.order[i]] = i;
+            self.pos[self.order[j]] = j;
+
+            i = (i + 1) % n;
+            j = (j + n - 1) % n;
+        }
+    }
+
+    fn to_vec(&self) -> Vec<usize> {
+        rotate_to_first(self.order.clone())
+    }
+
+    fn position(&self, node: usize) -> usize {
+        let n = self.order.len();
+        (self.pos[node] + n - self.pos[0]) % n
+    }
+}
+
+// -- TwoLevelTour
+
+#[derive(Debug, Clone)]
+struct Segment {
+    nodes: Vec<usize>,
+    reversed: bool,
+    rank: usize, // position of the segment in the tour
+}
+
+impl Segment {
+    fn node_at(&self, inner_pos: usize) -> usize {
+        if self.reversed {
+            self.nodes[self.nodes.len() - 1 - inner_pos]
+        } else {
+            self.nodes[inner_pos]
+        }
+    }
+
+    fn first(&self) -> usize {
+        self.node_at(0)
+    }
+
+    fn last(&self) -> usize {
+        self.node_at(self.nodes.len() - 1)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TwoLevelTour {
+    segments: Vec<Segment>,
+    order: Vec<usize>,  // segment ids in the tour order
+    seg_of: Vec<usize>, // node -> segment id
+    offset: Vec<usize>, // node -> index in the segment.nodes
+    group_size: usize,  // the segment size after rebalancing
+}
+
+impl TwoLevelTour {
+    /// order must be a permutation of the nodes 0..n
+    pub fn new(order: &[usize]) -> Self {
+        let n = order.len();
+        let group_size = max(MIN_SEGMENT_SIZE, (n as f64).sqrt() as usize);
+
+        let mut tour = TwoLevelTour {
+            segments: vec![],
+            order: vec![],
+            seg_of: vec![0; n],
+            offset: vec![0; n],
+            group_size,
+        };
+
+        tour.rebuild(order);
+        tour
+    }
+
+    /// number of segments the tour is currently split into
+    pub fn n_segments(&self) -> usize {
+        self.order.len()
+    }
+
+    fn rebuild(&mut self, order: &[usize]) {
+        self.segments.clear();
+        self.order.clear();
+
+        for (seg_id, chunk) in order.chunks(self.group_size).enumerate() {
+            for (i, &node) in chunk.iter().enumerate() {
+                self.seg_of[node] = seg_id;
+                self.offset[node] = i;
+            }
+
+            self.segments.push(Segment {
+                nodes: chunk.to_vec(),
+                reversed: false,
+                rank: seg_id,
+            });
+            self.order.push(seg_id);
+        }
+    }
+
+    fn sequence(&self) -> Vec<usize> {
+        let mut nodes = Vec::with_capacity(self.seg_of.len());
+        for &seg_id in self.order.iter() {
+            let seg = &self.segments[seg_id];
+            for i in 0..seg.nodes.len() {
+                nodes.push(seg.node_at(i));
+            }
+        }
+
+        nodes
+    }
+
+    fn inner_pos(&self, node: usize) -> usize {
+        let seg = &self.segments[self.seg_of[node]];
+        if seg.reversed {
+            seg.nodes.len() - 1 - self.offset[node]
+        } else {
+            self.offset[node]
+        }
+    }
+
+    // the index of the node in the `sequence`, O(number of segments)
+    fn sequence_index(&self, node: usize) -> usize {
+        let rank = self.segments[self.seg_of[node]].rank;
+        let before: usize = self.order[..rank]
+            .iter()
+            .map(|&seg_id| self.segments[seg_id].nodes.len())
+            .sum();
+
+        before + self.inner_pos(node)
+    }
+
+    fn update_ranks(&mut self) {
+        for (rank, &seg_id) in self.order.iter().enumerate() {
+            self.segments[seg_id].rank = rank;
+        }
+    }
+
+    /// splits the segment of the node so that the node becomes the first of its segment
+    fn split_before(&mut self, node: usize) {
+        let seg_id = self.seg_of[node];
+        let p = self.inner_pos(node);
+        if p == 0 {
+            return;
+        }
+
+        let new_id = self.segments.len();
+        let seg = &mut self.segments[seg_id];
+        let len = seg.nodes.len();
+        let reversed = seg.reversed;
+
+        let tail: Vec<usize> = if reversed {
+            // the tail of tour order is at the beginning of the physical vector
+            let tail: Vec<usize> = seg.nodes.drain(0..(len - p)).collect();
+            for (i, &n) in seg.nodes.iter().enumerate() {
+                self.offset[n] = i;
+            }
+            tail
+        } else {
+            seg.nodes.split_off(p)
+        };
+
+        for (i, &n) in tail.iter().enumerate() {
+            self.seg_of[n] = new_id;
+            self.offset[n] = i;
+        }
+
+        let rank = seg.rank;
+        self.segments.push(Segment {
+            nodes: tail,
+            reversed,
+            rank: rank + 1,
+        });
+        self.order.insert(rank + 1, new_id);
+        self.update_ranks();
+    }
+
+    fn rebalance(&mut self) {
+        let n = self.seg_of.len();
+        if self.order.len() > 2 * (n / self.group_size + 1) {
+            let nodes = self.sequence();
+            self.rebuild(&nodes);
+        }
+    }
+}
+
+impl Tour for TwoLevelTour {
+    fn len(&self) -> usize {
+        self.seg_of.len()
+    }
+
+    fn next(&self, node: usize) -> usize {
+        let seg = &self.segments[self.seg_of[node]];
+        let p = self.inner_pos(node);
+
+        if p + 1 < seg.nodes.len() {
+            seg.node_at(p + 1)
+        } else {
+            let next_rank = (seg.rank + 1) % self.order.len();
+            self.segments[self.order[next_rank]].first()
+        }
+    }
+
+    fn prev(&self, node: usize) -> usize {
+        let seg = &self.segments[self.seg_of[node]];
+        let p = self.inner_pos(node);
+
+        if p > 0 {
+            seg.node_at(p - 1)
+        } else {
+            let n_segments = self.order.len();
+            let prev_rank = (seg.rank + n_segments - 1) % n_segments;
+            self.segments[self.order[prev_rank]].last()
+        }
+    }
+
+    fn between(&self, a: usize, b: usize, c: usize) -> bool {
+        let (sa, sb, sc) = (self.seg_of[a], self.seg_of[b], self.seg_of[c]);
+        let (ra, rb, rc) = (
+            self.segments[sa].rank,
+            self.segments[sb].rank,
+            self.segments[sc].rank,
+        );
+
+        // encode (segment rank, position in segment) into one comparable number
+        let n = self.len();
+        let key = |rank: usize, node: usize| rank * n + self.inner_pos(node);
+
+        between_positions(key(ra, a), key(rb, b), key(rc, c))
+    }
+
+    fn reverse(&mut self, from: usize, to: usize) {
+        if from == to {
+            return;
+        }
+
+        let after_to = self.next(to);
+        if after_to == from {
+            // the path covers the whole tour, so we just change the direction
+            self.order.reverse();
+            for seg in self.segments.iter_mut() {
+                seg.reversed = !seg.reversed;
+            }
+            self.update_ranks();
+            return;
+        }
+
+        self.split_before(from);
+        self.split_before(after_to);
+
+        // make the segment of `from` the first one, then the path is a prefix of segments
+        let first_rank = self.segments[self.seg_of[from]].rank;
+        self.order.rotate_left(first_rank);
+        self.update_ranks();
+
+        let last_rank = self.segments[self.seg_of[to]].rank;
+        self.order[0..=last_rank].reverse();
+        for i in 0..=last_rank {
+            let seg_id = self.order[i];
+            self.segments[seg_id].reversed = !self.segments[seg_id].reversed;
+        }
+        self.update_ranks();
+
+        self.rebalance();
+    }
+
+    fn to_vec(&self) -> Vec<usize> {
+        rotate_to_first(self.sequence())
+    }
+
+    fn position(&self, node: usize) -> usize {
+        let n = self.len();
+        (self.sequence_index(node) + n - self.sequence_index(0)) % n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // reference implementation, which rotates the order, so the path starts from 0
+    fn reverse_naive(order: &mut [usize], from: usize, to: usize) {
+        let start = order.iter().position(|&x| x == from).unwrap();
+        order.rotate_left(start);
+        let end = order.iter().position(|&x| x == to).unwrap();
+        order[0..=end].reverse();
+    }
+
+    // tours are same if they describe the same cycle in either direction
+    fn same_cycle(expected: &[usize], actual: &[usize]) -> bool {
+        let expected = rotate_to_first(expected.to_vec());
+        let mut mirrored = expected.clone();
+        mirrored[1..].reverse();
+
+        actual == &expected[..] || actual == &mirrored[..]
+    }
+
+    fn check_random_reversals(tour: &mut dyn Tour, n: usize, n_moves: usize) {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for _ in 0..n_moves {
+            let from = rng.gen_range(0, n);
+            let to = rng.gen_range(0, n);
+
+            // the direction of the tour may flip, so the expected path follows the current one
+            let mut expected = tour.to_vec();
+            reverse_naive(&mut expected, from, to);
+            tour.reverse(from, to);
+
+            let actual = tour.to_vec();
+            assert!(
+                same_cycle(&expected, &actual),
+                "{:?} {:?}",
+                expected,
+                actual
+            );
+
+            for node in 0..n {
+                assert_eq!(node, tour.prev(tour.next(node)));
+                assert_eq!(node, actual[tour.position(node)]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_array_tour_next_and_prev() {
+        let tour = ArrayTour::new(&[2, 0, 1, 3]);
+
+        assert_eq!(1, tour.next(0));
+        assert_eq!(2, tour.next(3));
+        assert_eq!(3, tour.prev(2));
+        assert_eq!(vec![0, 1, 3, 2], tour.to_vec());
+    }
+
+    #[test]
+    fn test_array_tour_between_with_wrap() {
+        let tour = ArrayTour::new(&[0, 1, 2, 3, 4]);
+
+        assert!(tour.between(1, 2, 3));
+        assert!(tour.between(3, 4, 1));
+        assert!(tour.between(3, 0, 1));
+        assert!(!tour.between(3, 2, 1));
+    }
+
+    #[test]
+    fn test_array_tour_random_reversals() {
+        let mut tour = ArrayTour::new(&(0..50).collect::<Vec<usize>>());
+
+        check_random_reversals(&mut tour, 50, 200);
+    }
+
+    #[test]
+    fn test_two_level_tour_keeps_order() {
+        let order: Vec<usize> = (0..20).rev().collect();
+        let tour = TwoLevelTour::new(&order);
+
+        assert_eq!(3, tour.n_segments());
+        assert_eq!(19, tour.next(0));
+        assert_eq!(1, tour.prev(0));
+        assert!(tour.between(10, 9, 0));
+    }
+
+    #[test]
+    fn test_two_level_tour_random_reversals() {
+        let mut tour = TwoLevelTour::new(&(0..100).collect::<Vec<usize>>());
+
+        check_random_reversals(&mut tour, 100, 300);
+    }
+
+    #[test]
+    fn test_two_level_tour_between_matches_array_tour() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let n = 40;
+        let mut array = ArrayTour::new(&(0..n).collect::<Vec<usize>>());
+
+        for _ in 0..50 {
+            let (from, to) = (rng.gen_range(0, n), rng.gen_range(0, n));
+            // use the same order for both, as implementations may choose different side
+            array.reverse(from, to);
+            let two_level = TwoLevelTour::new(&array.to_vec());
+
+            let (a, b, c) = (
+                rng.gen_range(0, n),
+                rng.gen_range(0, n),
+                rng.gen_range(0, n),
+            );
+            assert_eq!(array.between(a, b, c), two_level.between(a, b, c));
+        }
+    }
+
+    #[test]
+    fn test_from_order_picks_representation_by_size() {
+        let small = from_order(&[0, 1, 2]);
+        assert_eq!(3, small.len());
+
+        let big: Vec<usize> = (0..TWO_LEVEL_THRESHOLD).collect();
+        let tour = from_order(&big);
+        assert_eq!(TWO_LEVEL_THRESHOLD, tour.len());
+        assert_eq!(1, tour.next(0));
+    }
+}
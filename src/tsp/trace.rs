@@ -0,0 +1,348 @@
+/// Trace of accepted moves
+///
+/// When tracing is enabled, solvers record every move they accept into the trace file,
+/// one move per line as tab separated values (tabs are shown as spaces):
+///
+/// ```text
+/// # epoch elapsed_us move from to delta
+/// 12    1043    2opt    3    17    -12.5
+/// 13    1090    2opt    4    9     3.25
+/// ```
+///
+/// `from` and `to` are positions in the route, `delta` is the change of the tour length,
+/// so negative values are improvements. The trace can be summarized with `trace-stats`.
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use super::Error;
+
+const HEADER: &str = "# epoch elapsed_us move from to delta";
+
+lazy_static! {
+    static ref TRACER: Mutex<Option<Tracer>> = Mutex::new(None);
+}
+
+// allows solvers to skip the lock when tracing is disabled
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+struct Tracer {
+    writer: BufWriter<File>,
+    started_at: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MoveKind {
+    TwoOpt,
+    Swap,
+    Relocate,
+    Restart,
+}
+
+impl MoveKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MoveKind::TwoOpt => "2opt",
+            MoveKind::Swap => "swap",
+            MoveKind::Relocate => "relocate",
+            MoveKind::Restart => "restart",
+        }
+    }
+}
+
+impl FromStr for MoveKind {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "2opt" => Ok(MoveKind::TwoOpt),
+            "swap" => Ok(MoveKind::Swap),
+            "relocate" => Ok(MoveKind::Relocate),
+            "restart" => Ok(MoveKind::Restart),
+            _ => Err("Unknown move type"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEvent {
+    pub epoch: usize,
+    pub elapsed_us: u128,
+    pub kind: MoveKind,
+    pub from: usize,
+    pub to: usize,
+    pub delta: f32,
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.epoch,
+            self.elapsed_us,
+            self.kind.as_str(),
+            self.from,
+            self.to,
+            self.delta
+        )
+    }
+}
+
+/// starts writing the trace of accepted moves into the file
+pub fn init(path: &Path) -> Result<(), Error> {
+    let f = File::create(path).map_err(Error::io("trace: failed to create file"))?;
+    let mut writer = BufWriter::new(f);
+    writeln!(writer, "{}", HEADER).map_err(Error::io("trace: failed to write"))?;
+
+    let mut tracer = TRACER.lock().unwrap();
+    *tracer = Some(Tracer {
+        writer,
+        started_at: Instant::now(),
+    });
+    ENABLED.store(true, Ordering::SeqCst);
+
+    Ok(())
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// records the accepted move, it does nothing if tracing is not enabled
+pub fn record(kind: MoveKind, epoch: usize, from: usize, to: usize, delta: f32) {
+    if !is_enabled() {
+        return;
+    }
+
+    let mut tracer = TRACER.lock().unwrap();
+    if let Some(t) = tracer.as_mut() {
+        let event = TraceEvent {
+            epoch,
+            elapsed_us: t.started_at.elapsed().as_micros(),
+            kind,
+            from,
+            to,
+            delta,
+        };
+
+        // a broken trace should not stop the search
+        writeln!(t.writer, "{}", event).unwrap_or_default();
+    }
+}
+
+/// flushes the trace file and disables tracing
+pub fn finish() {
+    ENABLED.store(false, Ordering::SeqCst);
+
+    let mut tracer = TRACER.lock().unwrap();
+    if let Some(mut t) = tracer.take() {
+        t.writer.flush().unwrap_or_default();
+    }
+}
+
+pub fn read_from_file(path: &Path) -> Result<Vec<TraceEvent>, Error> {
+    let f = File::open(path).map_err(Error::io("trace: failed to open file"))?;
+
+    read_events(BufReader::new(f))
+}
+
+fn read_events<R: BufRead>(reader: R) -> Result<Vec<TraceEvent>, Error> {
+    let mut events = vec![];
+
+    for (line_no, line_res) in reader.lines().enumerate() {
+        let line =
+            line_res.map_err(|_| Error::parse("trace", line_no + 1, "failed to read line"))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let event =
+            parse_event(line).ok_or_else(|| Error::parse("trace", line_no + 1, "invalid event"))?;
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+fn parse_event(line: &str) -> Option<TraceEvent> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != 6 {
+        return None;
+    }
+
+    Some(TraceEvent {
+        epoch: fields[0].parse().ok()?,
+        elapsed_us: fields[1].parse().ok()?,
+        kind: MoveKind::from_str(fields[2]).ok()?,
+        from: fields[3].parse().ok()?,
+        to: fields[4].parse().ok()?,
+        delta: fields[5].parse().ok()?,
+    })
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MoveStats {
+    pub count: usize,
+    pub improving: usize,
+    pub worsening: usize,
+    pub total_delta: f32,
+    pub best_delta: f32,
+}
+
+impl MoveStats {
+    pub fn mean_delta(&self) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+
+        self.total_delta / self.count as f32
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraceStats {
+    pub n_events: usize,
+    pub last_epoch: usize,
+    pub elapsed_us: u128,
+    pub longest_stall: usize, // max number of epochs without an improving move
+    pub by_kind: BTreeMap<MoveKind, MoveStats>,
+}
+
+/// summarizes how effective each move type was over the run
+pub fn summarize(events: &[TraceEvent]) -> TraceStats {
+    let mut stats = TraceStats::default();
+    let mut last_improvement = 0;
+
+    for event in events.iter() {
+        let kind_stats = stats.by_kind.entry(event.kind).or_default();
+        kind_stats.best_delta = if kind_stats.count == 0 {
+            event.delta
+        } else {
+            kind_stats.best_delta.min(event.delta)
+        };
+        kind_stats.count += 1;
+        kind_stats.total_delta += event.delta;
+
+        if event.delta < 0.0 {
+            kind_stats.improving += 1;
+
+            let stall = event.epoch.saturating_sub(last_improvement);
+            stats.longest_stall = stats.longest_stall.max(stall);
+            last_improvement = event.epoch;
+        } else if event.delta > 0.0 {
+            kind_stats.worsening += 1;
+        }
+
+        stats.last_epoch = stats.last_epoch.max(event.epoch);
+        stats.elapsed_us = stats.elapsed_us.max(event.elapsed_us);
+    }
+
+    stats.n_events = events.len();
+    let final_stall = stats.last_epoch.saturating_sub(last_improvement);
+    stats.longest_stall = stats.longest_stall.max(final_stall);
+
+    stats
+}
+
+impl fmt::Display for TraceStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "moves: {}, epochs: {}, elapsed: {:.3}s, longest stall: {} epochs",
+            self.n_events,
+            self.last_epoch,
+            self.elapsed_us as f64 / 1_000_000.0,
+            self.longest_stall
+        )?;
+        writeln!(
+            f,
+            "{:<10}{:>10}{:>10}{:>10}{:>14}{:>14}{:>14}",
+            "move", "count", "improving", "worsening", "total_delta", "mean_delta", "best_delta"
+        )?;
+
+        for (kind, s) in self.by_kind.iter() {
+            writeln!(
+                f,
+                "{:<10}{:>10}{:>10}{:>10}{:>14.3}{:>14.3}{:>14.3}",
+                kind.as_str(),
+                s.count,
+                s.improving,
+                s.worsening,
+                s.total_delta,
+                s.mean_delta(),
+                s.best_delta
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_display_and_parse_roundtrip() {
+        let event = TraceEvent {
+            epoch: 12,
+            elapsed_us: 1043,
+            kind: MoveKind::TwoOpt,
+            from: 3,
+            to: 17,
+            delta: -12.5,
+        };
+
+        let line = event.to_string();
+        assert_eq!("12\t1043\t2opt\t3\t17\t-12.5", line);
+        assert_eq!(Some(event), parse_event(&line));
+    }
+
+    #[test]
+    fn test_read_events_skips_header_and_reports_invalid_lines() {
+        let content = format!("{}\n1\t10\t2opt\t0\t2\t-1.5\n\n", HEADER);
+        let events = read_events(content.as_bytes()).unwrap();
+        assert_eq!(1, events.len());
+
+        let res = read_events("1\t10\t3opt\t0\t2\t-1.5\n".as_bytes());
+        assert_eq!(
+            "trace: invalid event on line.1",
+            res.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_summarize_counts_moves_by_kind() {
+        let content = "1\t10\t2opt\t0\t2\t-2.0\n\
+                       3\t20\t2opt\t1\t4\t1.0\n\
+                       9\t30\trestart\t0\t0\t5.0\n\
+                       10\t40\t2opt\t2\t5\t-3.0\n\
+                       14\t50\t2opt\t2\t5\t0.5\n";
+        let events = read_events(content.as_bytes()).unwrap();
+        let stats = summarize(&events);
+
+        assert_eq!(5, stats.n_events);
+        assert_eq!(14, stats.last_epoch);
+        assert_eq!(50, stats.elapsed_us);
+        assert_eq!(9, stats.longest_stall);
+
+        let two_opt = &stats.by_kind[&MoveKind::TwoOpt];
+        assert_eq!(4, two_opt.count);
+        assert_eq!(2, two_opt.improving);
+        assert_eq!(2, two_opt.worsening);
+        assert_eq!(-3.0, two_opt.best_delta);
+        assert_eq!(-0.875, two_opt.mean_delta());
+
+        assert_eq!(1, stats.by_kind[&MoveKind::Restart].count);
+        assert_eq!(5.0, stats.by_kind[&MoveKind::Restart].best_delta);
+    }
+}
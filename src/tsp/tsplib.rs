@@ -1,28 +1,87 @@
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::Path;
 use std::str::FromStr;
 
+use flate2::read::MultiGzDecoder;
 use lazy_static::lazy_static;
 use regex::Regex;
 
 use super::kdtree::KDPoint;
+use super::Error;
 
 const COORD_SECTION_KEY: &'static str = "NODE_COORD_SECTION";
 const DISPLAY_DATA_SECTION_KEY: &'static str = "DISPLAY_DATA_SECTION";
 const EOF_KEY: &'static str = "EOF";
+const FIXED_EDGES_SECTION_KEY: &str = "FIXED_EDGES_SECTION";
+const DEMAND_SECTION_KEY: &str = "DEMAND_SECTION";
+const DEPOT_SECTION_KEY: &str = "DEPOT_SECTION";
+const PRIZE_SECTION_KEY: &str = "PRIZE_SECTION";
+const GTSP_SET_SECTION_KEY: &str = "GTSP_SET_SECTION";
+const PRECEDENCE_SECTION_KEY: &str = "PRECEDENCE_SECTION";
+const SECTION_END_MARKER: &str = "-1";
+const TOUR_SECTION_KEY: &str = "TOUR_SECTION";
+const TOUR_END_MARKER: &str = SECTION_END_MARKER;
+const DEFAULT_EDGE_WEIGHT_TYPE: &str = "EUC_2D";
+// the first bytes of the gzipped files
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+// distance types, where distances are kilometers on the earth surface
+const GEOGRAPHIC_WEIGHT_TYPES: [&str; 2] = ["GEO", "GEOM"];
 
 lazy_static! {
-    static ref SECTION_START_MATCHER: Regex = Regex::new(r"^(?P<key>\w+)$").unwrap();
+    // the ids of the DEPOT_SECTION are not the section keys
+    static ref SECTION_START_MATCHER: Regex = Regex::new(r"^(?P<key>[A-Z_]\w*)$").unwrap();
     // it matches key value pairs separated by colon(:)
     static ref KEY_VALUE_MATCHER: Regex = Regex::new(r"^(?P<key>\w+)\s*:\s*(?P<val>.+)$").unwrap();
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TspLibData {
     pub name: String,
     pub comment: String,
+    pub edge_weight_type: String,
+    // the pairs of city ids the tour must connect, from the FIXED_EDGES_SECTION
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    fixed_edges: Vec<(usize, usize)>,
+    // the pairs of city ids, the first one is visited before the second, from the PRECEDENCE_SECTION
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    precedences: Vec<(usize, usize)>,
+    // the vehicle capacity, the demands of the cities and the depots of the CVRP problems
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    capacity: Option<usize>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    demands: Vec<(usize, usize)>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    depots: Vec<usize>,
+    // the prizes of the cities of the prize-collecting problems, from the PRIZE_SECTION
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    prizes: Vec<(usize, usize)>,
+    // the clusters of the city ids of the generalized TSP, from the GTSP_SET_SECTION
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    clusters: Vec<Vec<usize>>,
     cities: Vec<KDPoint>,
 }
 
@@ -31,10 +90,63 @@ impl TspLibData {
         TspLibData {
             name,
             comment,
+            edge_weight_type: DEFAULT_EDGE_WEIGHT_TYPE.to_string(),
             cities,
+            fixed_edges: vec![],
+            precedences: vec![],
+            capacity: None,
+            demands: vec![],
+            depots: vec![],
+            prizes: vec![],
+            clusters: vec![],
         }
     }
 
+    pub fn with_fixed_edges(mut self, fixed_edges: Vec<(usize, usize)>) -> Self {
+        self.fixed_edges = fixed_edges;
+        self
+    }
+
+    pub fn with_precedences(mut self, precedences: Vec<(usize, usize)>) -> Self {
+        self.precedences = precedences;
+        self
+    }
+
+    /// makes it the CVRP problem: the demands are the pairs of the city id and its demand
+    pub fn with_demands(
+        mut self,
+        capacity: usize,
+        demands: Vec<(usize, usize)>,
+        depots: Vec<usize>,
+    ) -> Self {
+        self.capacity = Some(capacity);
+        self.demands = demands;
+        self.depots = depots;
+        self
+    }
+
+    /// the depots of the problem without the vehicles, e.g of the prize-collecting problem
+    pub fn with_depots(mut self, depots: Vec<usize>) -> Self {
+        self.depots = depots;
+        self
+    }
+
+    /// the prizes are the pairs of the city id and its prize
+    pub fn with_prizes(mut self, prizes: Vec<(usize, usize)>) -> Self {
+        self.prizes = prizes;
+        self
+    }
+
+    /// makes it the generalized TSP, the tour visits one city of every cluster
+    pub fn with_clusters(mut self, clusters: Vec<Vec<usize>>) -> Self {
+        self.clusters = clusters;
+        self
+    }
+
+    pub fn is_geographic(&self) -> bool {
+        GEOGRAPHIC_WEIGHT_TYPES.contains(&self.edge_weight_type.as_str())
+    }
+
     pub fn cities(&self) -> &[KDPoint] {
         self.cities.as_ref()
     }
@@ -42,39 +154,389 @@ impl TspLibData {
     pub fn len(&self) -> usize {
         self.cities.len()
     }
-}
 
-pub fn read_from_file(path: &Path) -> Result<TspLibData, String> {
-    let f_res = File::open(path);
-    if f_res.is_err() {
-        return Err("tsplib: failed to read file".to_owned());
+    /// the edges the tour must keep, as the pairs of city ids
+    pub fn fixed_edges(&self) -> &[(usize, usize)] {
+        &self.fixed_edges
     }
 
-    let reader = BufReader::new(f_res.unwrap());
+    /// the pairs of city ids, where the first city is visited before the second one
+    pub fn precedences(&self) -> &[(usize, usize)] {
+        &self.precedences
+    }
+
+    /// the capacity of the vehicles, only the CVRP problems have it
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// the demands of the cities from the DEMAND_SECTION, as the pairs of the city id and its demand
+    pub fn demands(&self) -> &[(usize, usize)] {
+        &self.demands
+    }
+
+    /// the city ids of the depots from the DEPOT_SECTION
+    pub fn depots(&self) -> &[usize] {
+        &self.depots
+    }
+
+    /// the prizes of the cities from the PRIZE_SECTION, as the pairs of the city id and its prize
+    pub fn prizes(&self) -> &[(usize, usize)] {
+        &self.prizes
+    }
+
+    /// the clusters of the city ids from the GTSP_SET_SECTION, in the order of the file
+    pub fn clusters(&self) -> &[Vec<usize>] {
+        &self.clusters
+    }
+
+    /// returns a copy where city ids are 1..n in the order of cities,
+    /// which is what most of the external solvers expect
+    pub fn renumbered(&self) -> TspLibData {
+        let cities: Vec<KDPoint> = self
+            .cities
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                // the clone keeps the explicit edge weights of the city
+                let mut city = c.clone();
+                city.id = i + 1;
+                city
+            })
+            .collect();
+
+        let new_ids: HashMap<usize, usize> = self
+            .cities
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.id, i + 1))
+            .collect();
+
+        self.with_city_ids(cities, &new_ids)
+    }
+
+    /// returns a copy with the cities the `keeps` accepts, the pairs, the prizes, the demands and
+    /// the clusters of the other cities are dropped; the subset is another problem, so its name
+    /// doesn't match the known optimum of the whole problem
+    pub fn subset<F: Fn(usize) -> bool>(&self, keeps: F) -> TspLibData {
+        let cities: Vec<KDPoint> = self
+            .cities
+            .iter()
+            .filter(|c| keeps(c.id))
+            .cloned()
+            .collect();
+        let same_ids: HashMap<usize, usize> = cities.iter().map(|c| (c.id, c.id)).collect();
+
+        let mut data = self.with_city_ids(cities, &same_ids);
+        data.name = format!("{}-subset", self.name.trim());
+        data.comment = format!(
+            "{} of the {} cities of {}",
+            data.len(),
+            self.len(),
+            self.name.trim()
+        );
+        data
+    }
+
+    // the copy with the cities, the sections refer to them by the new ids of the old city ids;
+    // the entries of the cities without the new id are dropped
+    fn with_city_ids(&self, cities: Vec<KDPoint>, new_ids: &HashMap<usize, usize>) -> TspLibData {
+        let renumbered_pairs = |pairs: &[(usize, usize)]| -> Vec<(usize, usize)> {
+            pairs
+                .iter()
+                .filter_map(|(a, b)| Some((*new_ids.get(a)?, *new_ids.get(b)?)))
+                .collect()
+        };
+
+        let prizes = self
+            .prizes
+            .iter()
+            .filter_map(|(id, prize)| Some((*new_ids.get(id)?, *prize)))
+            .collect();
+        let depots: Vec<usize> = self
+            .depots
+            .iter()
+            .filter_map(|id| new_ids.get(id))
+            .copied()
+            .collect();
+
+        let clusters = self
+            .clusters
+            .iter()
+            .map(|cluster| -> Vec<usize> {
+                cluster
+                    .iter()
+                    .filter_map(|id| new_ids.get(id))
+                    .copied()
+                    .collect()
+            })
+            .filter(|cluster| !cluster.is_empty())
+            .collect();
+
+        let mut data = TspLibData::new(self.name.clone(), self.comment.clone(), cities)
+            .with_fixed_edges(renumbered_pairs(&self.fixed_edges))
+            .with_precedences(renumbered_pairs(&self.precedences))
+            .with_prizes(prizes)
+            .with_clusters(clusters);
+        data.edge_weight_type = self.edge_weight_type.clone();
+        if let Some(capacity) = self.capacity {
+            let demands = self
+                .demands
+                .iter()
+                .filter_map(|(id, demand)| Some((*new_ids.get(id)?, *demand)))
+                .collect();
+
+            data = data.with_demands(capacity, demands, depots);
+        } else {
+            data = data.with_depots(depots);
+        }
+
+        data
+    }
+}
+
+pub fn read_from_file(path: &Path) -> Result<TspLibData, Error> {
+    let reader = open_file(path).map_err(Error::io("tsplib: failed to read file"))?;
 
     process_lines(reader)
 }
 
-pub fn read_from_stdin() -> Result<TspLibData, String> {
+/// opens the file for reading, the gzipped files are recognized by their first bytes
+/// and decompressed on the fly, e.g `pla85900.tsp.gz`
+pub fn open_file(path: &Path) -> std::io::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+pub fn read_from_stdin() -> Result<TspLibData, Error> {
     let reader = std::io::stdin();
 
     process_lines(reader.lock())
 }
 
-fn process_lines<R: BufRead>(reader: R) -> Result<TspLibData, String> {
+/// reads the problem from any buffered reader, e.g from the in-memory buffer
+pub fn read_from<R: BufRead>(reader: R) -> Result<TspLibData, Error> {
+    process_lines(reader)
+}
+
+pub fn write_to_file(path: &Path, data: &TspLibData) -> Result<(), Error> {
+    let f = File::create(path).map_err(Error::io("tsplib: failed to create file"))?;
+    let mut writer = BufWriter::new(f);
+
+    write_to(&mut writer, data).map_err(Error::io("tsplib: failed to write file"))
+}
+
+/// writes the problem in TSPLIB format, so it could be read back or used by other solvers
+pub fn write_to<W: Write>(writer: &mut W, data: &TspLibData) -> std::io::Result<()> {
+    let dimensionality = data.cities().first().map_or(2, |c| c.dim());
+    let weight_type = if dimensionality == 3 {
+        "EUC_3D"
+    } else {
+        "EUC_2D"
+    };
+
+    writeln!(writer, "NAME: {}", data.name)?;
+    if !data.comment.is_empty() {
+        writeln!(writer, "COMMENT: {}", data.comment)?;
+    }
+    match data.capacity() {
+        Some(capacity) => {
+            writeln!(writer, "TYPE: CVRP")?;
+            writeln!(writer, "CAPACITY: {}", capacity)?;
+        }
+        None if !data.clusters().is_empty() => writeln!(writer, "TYPE: GTSP")?,
+        None => writeln!(writer, "TYPE: TSP")?,
+    }
+    writeln!(writer, "DIMENSION: {}", data.len())?;
+    if !data.clusters().is_empty() {
+        writeln!(writer, "GTSP_SETS: {}", data.clusters().len())?;
+    }
+    writeln!(writer, "EDGE_WEIGHT_TYPE: {}", weight_type)?;
+    writeln!(writer, "{}", COORD_SECTION_KEY)?;
+
+    for city in data.cities().iter() {
+        write!(writer, "{}", city.id)?;
+        for coord in city.coords().iter() {
+            write!(writer, " {}", coord)?;
+        }
+        writeln!(writer)?;
+    }
+
+    if !data.fixed_edges().is_empty() {
+        writeln!(writer, "{}", FIXED_EDGES_SECTION_KEY)?;
+        for (a, b) in data.fixed_edges().iter() {
+            writeln!(writer, "{} {}", a, b)?;
+        }
+        writeln!(writer, "{}", SECTION_END_MARKER)?;
+    }
+
+    if !data.precedences().is_empty() {
+        writeln!(writer, "{}", PRECEDENCE_SECTION_KEY)?;
+        for (a, b) in data.precedences().iter() {
+            writeln!(writer, "{} {}", a, b)?;
+        }
+        writeln!(writer, "{}", SECTION_END_MARKER)?;
+    }
+
+    if !data.demands().is_empty() {
+        writeln!(writer, "{}", DEMAND_SECTION_KEY)?;
+        for (city_id, demand) in data.demands().iter() {
+            writeln!(writer, "{} {}", city_id, demand)?;
+        }
+    }
+
+    if !data.prizes().is_empty() {
+        writeln!(writer, "{}", PRIZE_SECTION_KEY)?;
+        for (city_id, prize) in data.prizes().iter() {
+            writeln!(writer, "{} {}", city_id, prize)?;
+        }
+    }
+
+    if !data.clusters().is_empty() {
+        writeln!(writer, "{}", GTSP_SET_SECTION_KEY)?;
+        for (i, cluster) in data.clusters().iter().enumerate() {
+            write!(writer, "{}", i + 1)?;
+            for city_id in cluster.iter() {
+                write!(writer, " {}", city_id)?;
+            }
+            writeln!(writer, " {}", SECTION_END_MARKER)?;
+        }
+    }
+
+    if !data.depots().is_empty() {
+        writeln!(writer, "{}", DEPOT_SECTION_KEY)?;
+        for city_id in data.depots().iter() {
+            writeln!(writer, "{}", city_id)?;
+        }
+        writeln!(writer, "{}", SECTION_END_MARKER)?;
+    }
+
+    writeln!(writer, "{}", EOF_KEY)?;
+    writer.flush()
+}
+
+/// reads city ids from the TOUR_SECTION of TSPLIB tour file
+pub fn read_tour_from_file(path: &Path) -> Result<Vec<usize>, Error> {
+    let reader = open_file(path).map_err(Error::io("tsplib: failed to read tour file"))?;
+
+    process_tour_lines(reader)
+}
+
+/// reads city ids of the tour from any buffered reader
+pub fn read_tour_from<R: BufRead>(reader: R) -> Result<Vec<usize>, Error> {
+    process_tour_lines(reader)
+}
+
+/// city id of the tour file with the number of the line it is on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TourEntry {
+    pub line: usize,
+    pub city_id: usize,
+}
+
+/// reads the city ids of the tour with their line numbers, e.g to report where the tour is broken
+pub fn read_tour_entries_from_file(path: &Path) -> Result<Vec<TourEntry>, Error> {
+    let reader = open_file(path).map_err(Error::io("tsplib: failed to read tour file"))?;
+
+    process_tour_entries(reader)
+}
+
+pub fn write_tour_to_file(path: &Path, name: &str, route: &[usize]) -> Result<(), Error> {
+    let f = File::create(path).map_err(Error::io("tsplib: failed to create file"))?;
+    let mut writer = BufWriter::new(f);
+
+    write_tour_to(&mut writer, name, route).map_err(Error::io("tsplib: failed to write tour"))
+}
+
+/// writes the route in TSPLIB tour format
+pub fn write_tour_to<W: Write>(writer: &mut W, name: &str, route: &[usize]) -> std::io::Result<()> {
+    writeln!(writer, "NAME: {}", name)?;
+    writeln!(writer, "TYPE: TOUR")?;
+    writeln!(writer, "DIMENSION: {}", route.len())?;
+    writeln!(writer, "{}", TOUR_SECTION_KEY)?;
+
+    for city_id in route.iter() {
+        writeln!(writer, "{}", city_id)?;
+    }
+
+    writeln!(writer, "{}", TOUR_END_MARKER)?;
+    writeln!(writer, "{}", EOF_KEY)?;
+    writer.flush()
+}
+
+fn process_tour_lines<R: BufRead>(reader: R) -> Result<Vec<usize>, Error> {
+    let entries = process_tour_entries(reader)?;
+
+    Ok(entries.iter().map(|entry| entry.city_id).collect())
+}
+
+fn process_tour_entries<R: BufRead>(reader: R) -> Result<Vec<TourEntry>, Error> {
+    let mut route: Vec<TourEntry> = vec![];
+    let mut in_section = false;
+
+    for (i, line_res) in reader.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line_res.map_err(|_| Error::parse("tsplib", line_no, "failed to read line"))?;
+        let line = line.trim().to_uppercase();
+
+        if !in_section {
+            in_section = line == TOUR_SECTION_KEY;
+            continue;
+        }
+
+        for token in line.split_whitespace() {
+            if token == TOUR_END_MARKER || token == EOF_KEY {
+                return Ok(route);
+            }
+
+            match usize::from_str(token) {
+                Ok(city_id) => route.push(TourEntry {
+                    line: line_no,
+                    city_id,
+                }),
+                Err(_) => return Err(Error::parse("tsplib", line_no, "invalid city id")),
+            }
+        }
+    }
+
+    if route.is_empty() {
+        return Err(Error::InvalidInput(
+            "tsplib: found no TOUR_SECTION".to_string(),
+        ));
+    }
+
+    Ok(route)
+}
+
+fn process_lines<R: BufRead>(reader: R) -> Result<TspLibData, Error> {
     let mut metadata: HashMap<String, String> = HashMap::new();
     let mut cities: Vec<KDPoint> = vec![];
+    let mut fixed_edges: Vec<(usize, usize)> = vec![];
+    let mut precedences: Vec<(usize, usize)> = vec![];
+    let mut demands: Vec<(usize, usize)> = vec![];
+    let mut depots: Vec<usize> = vec![];
+    let mut prizes: Vec<(usize, usize)> = vec![];
+    let mut clusters: Vec<Vec<usize>> = vec![];
+    // the coordinate sections with the line they start on and the number of their cities
+    let mut sections: Vec<(String, usize, usize)> = vec![];
 
     let mut state = TspReaderStates::START;
-    let mut line_no = 1;
-    for line_res in reader.lines() {
-        if line_res.is_err() {
-            return Err(format!("Failed to read line.{:?}", line_no));
+    for (i, line_res) in reader.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line_res.map_err(|_| Error::parse("tsplib", line_no, "failed to read line"))?;
+        let mut line = line.trim().to_uppercase();
+        // the GTSPLIB files end the section keys with the colon, e.g `GTSP_SET_SECTION:`
+        if let Some(key) = line.strip_suffix(':').map(str::trim_end) {
+            if SECTION_START_MATCHER.is_match(key) {
+                line = key.to_string();
+            }
         }
 
-        let line = line_res.unwrap().trim().to_uppercase();
-        line_no += 1;
-
         if state == TspReaderStates::END {
             break;
         }
@@ -82,24 +544,67 @@ fn process_lines<R: BufRead>(reader: R) -> Result<TspLibData, String> {
         // -- UPDATE STATE
         if is_state_marker(&line) {
             state = next_state(&state, &line);
+
+            // stop right after EOF marker, so the terminal input doesnt wait for more lines
+            if state == TspReaderStates::END {
+                break;
+            }
+
+            if let TspReaderStates::INSECTION(section_id) = &state {
+                if is_coord_section(section_id) {
+                    sections.push((section_id.clone(), line_no, 0));
+                }
+            }
+
             continue;
         }
 
         // -- EXTRACT VALUE
         match &state {
             TspReaderStates::START => match KEY_VALUE_MATCHER.captures(&line) {
-                None => return Err(format!("Failed to extract meta data on line.{:?}", line_no)),
+                None => {
+                    return Err(Error::parse(
+                        "tsplib",
+                        line_no,
+                        "failed to extract meta data",
+                    ))
+                }
                 Some(res) => {
                     metadata.insert(res["key"].to_string(), res["val"].to_string());
                 }
             },
             // we parse coords only from those 2 sections
-            TspReaderStates::INSECTION(section_id)
-                if (section_id == COORD_SECTION_KEY || section_id == DISPLAY_DATA_SECTION_KEY) =>
-            {
-                match coords_from_text(line_no, &line) {
-                    Err(msg) => return Err(msg),
-                    Ok(pt) => cities.push(pt),
+            TspReaderStates::INSECTION(section_id) if is_coord_section(section_id) => {
+                cities.push(coords_from_text(line_no, &line)?);
+                if let Some(section) = sections.last_mut() {
+                    section.2 += 1;
+                }
+            }
+            TspReaderStates::INSECTION(section_id) if section_id == FIXED_EDGES_SECTION_KEY => {
+                if line != SECTION_END_MARKER {
+                    fixed_edges.push(pair_from_text(line_no, &line, "fixed edge")?);
+                }
+            }
+            TspReaderStates::INSECTION(section_id) if section_id == PRECEDENCE_SECTION_KEY => {
+                if line != SECTION_END_MARKER {
+                    precedences.push(pair_from_text(line_no, &line, "precedence")?);
+                }
+            }
+            TspReaderStates::INSECTION(section_id) if section_id == DEMAND_SECTION_KEY => {
+                demands.push(pair_from_text(line_no, &line, "demand")?);
+            }
+            TspReaderStates::INSECTION(section_id) if section_id == PRIZE_SECTION_KEY => {
+                prizes.push(pair_from_text(line_no, &line, "prize")?);
+            }
+            TspReaderStates::INSECTION(section_id) if section_id == GTSP_SET_SECTION_KEY => {
+                clusters.push(cluster_from_text(line_no, &line)?);
+            }
+            TspReaderStates::INSECTION(section_id) if section_id == DEPOT_SECTION_KEY => {
+                if line != SECTION_END_MARKER {
+                    let depot = usize::from_str(&line).map_err(|_| {
+                        Error::parse("tsplib", line_no, "invalid city id of the depot")
+                    })?;
+                    depots.push(depot);
                 }
             }
             TspReaderStates::END => {
@@ -110,11 +615,17 @@ fn process_lines<R: BufRead>(reader: R) -> Result<TspLibData, String> {
     }
 
     if cities.is_empty() {
-        return Err("Found no valid city coordinates".to_string());
+        return Err(Error::InvalidInput(
+            "tsplib: found no valid city coordinates".to_string(),
+        ));
+    }
+
+    if let Some(dimension) = metadata.get("DIMENSION") {
+        check_dimension(dimension, &sections)?;
     }
 
     let unspecified_val = "unspecified".to_string();
-    let dt = TspLibData::new(
+    let mut dt = TspLibData::new(
         metadata
             .get("NAME")
             .unwrap_or(&unspecified_val)
@@ -126,11 +637,60 @@ fn process_lines<R: BufRead>(reader: R) -> Result<TspLibData, String> {
             .to_owned()
             .to_lowercase(),
         cities,
-    );
+    )
+    .with_fixed_edges(fixed_edges)
+    .with_precedences(precedences)
+    .with_prizes(prizes)
+    .with_clusters(clusters);
+
+    if let Some(capacity) = metadata.get("CAPACITY") {
+        let capacity = capacity.trim().parse().map_err(|_| {
+            Error::InvalidInput(format!(
+                "tsplib: CAPACITY must be a number, got {}",
+                capacity.trim()
+            ))
+        })?;
+        dt = dt.with_demands(capacity, demands, depots);
+    } else {
+        dt = dt.with_depots(depots);
+    }
+
+    if let Some(weight_type) = metadata.get("EDGE_WEIGHT_TYPE") {
+        dt.edge_weight_type = weight_type.trim().to_string();
+    }
 
     Ok(dt)
 }
 
+// the truncated file would otherwise be read as the smaller problem
+fn check_dimension(dimension: &str, sections: &[(String, usize, usize)]) -> Result<(), Error> {
+    let dimension: usize = dimension.trim().parse().map_err(|_| {
+        Error::InvalidInput(format!(
+            "tsplib: DIMENSION must be the number of cities, got {}",
+            dimension.trim()
+        ))
+    })?;
+
+    for (section_id, line_no, n_cities) in sections.iter() {
+        if *n_cities != dimension {
+            return Err(Error::parse(
+                "tsplib",
+                *line_no,
+                &format!(
+                    "DIMENSION is {}, but {} lists {} cities",
+                    dimension, section_id, n_cities
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn is_coord_section(section_id: &str) -> bool {
+    section_id == COORD_SECTION_KEY || section_id == DISPLAY_DATA_SECTION_KEY
+}
+
 fn is_state_marker(line: &String) -> bool {
     SECTION_START_MATCHER.captures(line).is_some()
 }
@@ -171,30 +731,73 @@ enum TspReaderStates {
     END,
 }
 
-fn coords_from_text<S: AsRef<str>>(line_no: usize, txt: S) -> Result<KDPoint, String> {
+fn coords_from_text<S: AsRef<str>>(line_no: usize, txt: S) -> Result<KDPoint, Error> {
     if starts_with_number(txt.as_ref()) {
-        let text = String::from_str(txt.as_ref()).unwrap();
-        let mut tokens = text.split_whitespace();
-        let id_str = tokens.next().unwrap().clone();
-        let id: usize = usize::from_str(id_str).unwrap();
+        let mut tokens = txt.as_ref().split_whitespace();
+        let id = tokens
+            .next()
+            .and_then(|id_str| usize::from_str(id_str).ok())
+            .ok_or_else(|| Error::parse("tsplib", line_no, "invalid city id"))?;
 
         // it is important we take id first out, then we dont need skip(1) here
-        let coords_res: Result<Vec<f32>, _> = tokens.map(|x| f32::from_str(x)).collect();
-        if coords_res.is_err() {
-            return Err(format!("Error on line.{:?} - invalid number", line_no));
-        }
-
-        let pt = KDPoint::new_with_id(id, &coords_res.unwrap());
+        let coords: Vec<f64> = tokens
+            .map(f64::from_str)
+            .collect::<Result<_, _>>()
+            .map_err(|_| Error::parse("tsplib", line_no, "invalid number"))?;
 
-        Ok(pt)
+        Ok(KDPoint::new_with_id(id, &coords))
     } else {
-        Err(format!(
-            "Failed to extract coordinates on line.{:?}",
-            line_no
+        Err(Error::parse(
+            "tsplib",
+            line_no,
+            "failed to extract coordinates",
         ))
     }
 }
 
+// the line of 2 numbers, the pair of the cities or the city id with its demand or prize
+fn pair_from_text(line_no: usize, txt: &str, what: &str) -> Result<(usize, usize), Error> {
+    let numbers: Vec<usize> = txt
+        .split_whitespace()
+        .map(usize::from_str)
+        .collect::<Result<_, _>>()
+        .map_err(|_| {
+            Error::parse(
+                "tsplib",
+                line_no,
+                &format!("invalid number of the {}", what),
+            )
+        })?;
+
+    match numbers.as_slice() {
+        [a, b] => Ok((*a, *b)),
+        _ => Err(Error::parse(
+            "tsplib",
+            line_no,
+            &format!("the {} must have 2 numbers", what),
+        )),
+    }
+}
+
+// the line of the GTSP_SET_SECTION: the id of the cluster, its city ids and the end marker
+fn cluster_from_text(line_no: usize, txt: &str) -> Result<Vec<usize>, Error> {
+    let mut tokens: Vec<&str> = txt.split_whitespace().collect();
+    if tokens.last() == Some(&SECTION_END_MARKER) {
+        tokens.pop();
+    }
+
+    let ids: Vec<usize> = tokens
+        .iter()
+        .map(|token| usize::from_str(token))
+        .collect::<Result<_, _>>()
+        .map_err(|_| Error::parse("tsplib", line_no, "invalid city id of the cluster"))?;
+    if ids.len() < 2 {
+        return Err(Error::parse("tsplib", line_no, "the cluster has no cities"));
+    }
+
+    Ok(ids[1..].to_vec())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -240,6 +843,16 @@ mod tests {
         assert_eq!(Some(3.0), pt.get(2));
     }
 
+    #[test]
+    fn test_coords_from_text_with_invalid_id() {
+        let res = coords_from_text(3, "1.5 10 20");
+
+        assert_eq!(
+            "tsplib: invalid city id on line.3",
+            res.unwrap_err().to_string()
+        );
+    }
+
     #[test]
     fn test_is_state_marker_with_empty_string() {
         let line = "".to_string();
@@ -308,6 +921,244 @@ mod tests {
         assert_eq!(Some(3.0), pt.get(1));
     }
 
+    #[test]
+    fn test_process_lines_reads_edge_weight_type() {
+        let cursor = "NAME: geo1\nEDGE_WEIGHT_TYPE: GEO\nNODE_COORD_SECTION\n1 2.0 3.0\nEOF\n";
+
+        let dt = process_lines(BufReader::new(cursor.as_bytes())).unwrap();
+        assert_eq!("GEO", dt.edge_weight_type);
+        assert!(dt.is_geographic());
+
+        let cursor = "NAME: euc1\nNODE_COORD_SECTION\n1 2.0 3.0\nEOF\n";
+        let dt = process_lines(BufReader::new(cursor.as_bytes())).unwrap();
+        assert_eq!(DEFAULT_EDGE_WEIGHT_TYPE, dt.edge_weight_type);
+        assert!(!dt.is_geographic());
+    }
+
+    #[test]
+    fn test_process_lines_checks_dimension() {
+        let cursor = "NAME: cut\nDIMENSION: 3\nNODE_COORD_SECTION\n1 2.0 3.0\n2 4.0 5.0\nEOF\n";
+        let err = process_lines(BufReader::new(cursor.as_bytes()))
+            .err()
+            .unwrap();
+        assert_eq!(
+            "tsplib: DIMENSION is 3, but NODE_COORD_SECTION lists 2 cities on line.3",
+            err.to_string()
+        );
+
+        let cursor = "NAME: ok\nDIMENSION : 2\nNODE_COORD_SECTION\n1 2.0 3.0\n2 4.0 5.0\nEOF\n";
+        assert_eq!(
+            2,
+            process_lines(BufReader::new(cursor.as_bytes()))
+                .unwrap()
+                .len()
+        );
+
+        let cursor = "NAME: bad\nDIMENSION: many\nNODE_COORD_SECTION\n1 2.0 3.0\nEOF\n";
+        assert!(process_lines(BufReader::new(cursor.as_bytes())).is_err());
+    }
+
+    #[test]
+    fn test_process_lines_reads_fixed_edges() {
+        let cursor = "NAME: fixed\nNODE_COORD_SECTION\n1 0 0\n2 1 0\n3 0 1\nFIXED_EDGES_SECTION\n1 3\n-1\nPRECEDENCE_SECTION\n3 2\n-1\nEOF\n";
+        let dt = process_lines(BufReader::new(cursor.as_bytes())).unwrap();
+        assert_eq!(&[(1, 3)], dt.fixed_edges());
+        assert_eq!(&[(3, 2)], dt.precedences());
+
+        let mut buffer: Vec<u8> = vec![];
+        write_to(&mut buffer, &dt).unwrap();
+        let dt = process_lines(BufReader::new(buffer.as_slice())).unwrap();
+        assert_eq!(&[(1, 3)], dt.fixed_edges());
+        assert_eq!(&[(3, 2)], dt.precedences());
+
+        let cursor =
+            "NAME: fixed\nNODE_COORD_SECTION\n1 0 0\nFIXED_EDGES_SECTION\n1 2 3\n-1\nEOF\n";
+        let err = process_lines(BufReader::new(cursor.as_bytes()))
+            .err()
+            .unwrap();
+        assert!(err.to_string().ends_with("on line.5"), "{}", err);
+    }
+
+    #[test]
+    fn test_process_lines_reads_demands() {
+        let cursor = "NAME: vrp\nTYPE: CVRP\nCAPACITY: 10\nNODE_COORD_SECTION\n1 0 0\n2 1 0\n3 0 1\nDEMAND_SECTION\n1 0\n2 4\n3 7\nDEPOT_SECTION\n1\n-1\nEOF\n";
+        let dt = process_lines(BufReader::new(cursor.as_bytes())).unwrap();
+        assert_eq!(Some(10), dt.capacity());
+        assert_eq!(&[(1, 0), (2, 4), (3, 7)], dt.demands());
+        assert_eq!(&[1], dt.depots());
+
+        let mut buffer: Vec<u8> = vec![];
+        write_to(&mut buffer, &dt).unwrap();
+        let dt = process_lines(BufReader::new(buffer.as_slice())).unwrap();
+        assert_eq!(Some(10), dt.capacity());
+        assert_eq!(3, dt.demands().len());
+        assert_eq!(&[1], dt.depots());
+    }
+
+    #[test]
+    fn test_process_lines_reads_prizes() {
+        let cursor = "NAME: pctsp\nTYPE: TSP\nNODE_COORD_SECTION\n1 0 0\n2 1 0\n3 0 1\nPRIZE_SECTION\n2 5\n3 8\nDEPOT_SECTION\n1\n-1\nEOF\n";
+        let dt = process_lines(BufReader::new(cursor.as_bytes())).unwrap();
+        assert_eq!(&[(2, 5), (3, 8)], dt.prizes());
+        assert_eq!(&[1], dt.depots());
+        assert_eq!(None, dt.capacity());
+
+        let mut buffer: Vec<u8> = vec![];
+        write_to(&mut buffer, &dt.renumbered()).unwrap();
+        let dt = process_lines(BufReader::new(buffer.as_slice())).unwrap();
+        assert_eq!(&[(2, 5), (3, 8)], dt.prizes());
+        assert_eq!(&[1], dt.depots());
+    }
+
+    #[test]
+    fn test_process_lines_reads_clusters() {
+        let cursor = "NAME : 3gtsp\nTYPE : GTSP\nDIMENSION : 4\nGTSP_SETS : 2\nNODE_COORD_SECTION\n1 0 0\n2 1 0\n3 0 1\n4 1 1\nGTSP_SET_SECTION:\n1 1 3 -1\n2 2 4 -1\nEOF\n";
+        let dt = process_lines(BufReader::new(cursor.as_bytes())).unwrap();
+        assert_eq!(&[vec![1, 3], vec![2, 4]], dt.clusters());
+
+        let mut buffer: Vec<u8> = vec![];
+        write_to(&mut buffer, &dt.renumbered()).unwrap();
+        let dt = process_lines(BufReader::new(buffer.as_slice())).unwrap();
+        assert_eq!(&[vec![1, 3], vec![2, 4]], dt.clusters());
+
+        let err = process_lines(BufReader::new(
+            "NODE_COORD_SECTION\n1 0 0\nGTSP_SET_SECTION\n1 -1\nEOF\n".as_bytes(),
+        ))
+        .err()
+        .unwrap();
+        assert!(err.to_string().ends_with("on line.4"), "{}", err);
+    }
+
+    #[test]
+    fn test_process_lines_stops_reading_after_eof() {
+        // invalid utf8 after EOF would fail, if the reader asks for the next line
+        let cursor: &[u8] = b"NAME: case1\nNODE_COORD_SECTION\n1 2.0 3.0\nEOF\n\xff\xfe\n";
+
+        let res = process_lines(BufReader::new(cursor));
+        assert!(res.is_ok());
+        assert_eq!(1, res.unwrap().len());
+    }
+
+    #[test]
+    fn test_write_to_can_be_read_back() {
+        let cities = vec![
+            KDPoint::new_with_id(1, &[2.0, 3.5]),
+            KDPoint::new_with_id(2, &[-1.0, 0.0]),
+        ];
+        let data = TspLibData::new("case2".to_string(), "roundtrip".to_string(), cities);
+
+        let mut buffer: Vec<u8> = vec![];
+        write_to(&mut buffer, &data).unwrap();
+
+        let res = process_lines(BufReader::new(buffer.as_slice()));
+        assert!(res.is_ok());
+
+        let dt = res.unwrap();
+        assert_eq!("case2".to_string(), dt.name);
+        assert_eq!(2, dt.len());
+        assert_eq!(2, dt.cities()[1].id);
+        assert_eq!(Some(3.5), dt.cities()[0].get(1));
+    }
+
+    #[test]
+    fn test_read_from_file_decompresses_gzipped_file() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let path = std::env::temp_dir().join(format!("teeline_{}.tsp.gz", std::process::id()));
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        encoder
+            .write_all(b"NAME: gz1\nNODE_COORD_SECTION\n1 2.0 3.0\n2 4.0 5.0\nEOF\n")
+            .unwrap();
+        encoder.finish().unwrap();
+
+        let res = read_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let dt = res.unwrap();
+        assert_eq!("gz1", dt.name);
+        assert_eq!(2, dt.len());
+    }
+
+    #[test]
+    fn test_renumbered_keeps_order_of_cities() {
+        let cities = vec![
+            KDPoint::new_with_id(7, &[2.0, 3.5]),
+            KDPoint::new_with_id(3, &[-1.0, 0.0]),
+        ];
+        let data = TspLibData::new("case3".to_string(), "".to_string(), cities);
+
+        let renumbered = data.renumbered();
+        assert_eq!(1, renumbered.cities()[0].id);
+        assert_eq!(2, renumbered.cities()[1].id);
+        assert_eq!(Some(-1.0), renumbered.cities()[1].get(0));
+    }
+
+    #[test]
+    fn test_subset_drops_sections_of_other_cities() {
+        let cities = crate::tsp::kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![2.0, 0.0],
+            vec![3.0, 0.0],
+        ]);
+        let data = TspLibData::new("case4".to_string(), "".to_string(), cities)
+            .with_fixed_edges(vec![(0, 1), (2, 3)])
+            .with_clusters(vec![vec![0, 1], vec![2]]);
+
+        let subset = data.subset(|id| id != 2);
+        assert_eq!("case4-subset", subset.name);
+        let ids: Vec<usize> = subset.cities().iter().map(|c| c.id).collect();
+        assert_eq!(vec![0, 1, 3], ids);
+        assert_eq!(&[(0, 1)], subset.fixed_edges());
+        assert_eq!(&[vec![0, 1]], subset.clusters());
+    }
+
+    #[test]
+    fn test_tour_roundtrip() {
+        let mut buffer: Vec<u8> = vec![];
+        write_tour_to(&mut buffer, "tour1", &[3, 1, 2]).unwrap();
+
+        let res = process_tour_lines(BufReader::new(buffer.as_slice()));
+        assert_eq!(vec![3, 1, 2], res.unwrap());
+    }
+
+    #[test]
+    fn test_process_tour_lines_without_end_marker() {
+        let cursor = "NAME: x\nTOUR_SECTION\n1 2\n3\n".as_bytes();
+
+        assert_eq!(vec![1, 2, 3], process_tour_lines(cursor).unwrap());
+    }
+
+    #[test]
+    fn test_process_tour_entries_keeps_line_numbers() {
+        let cursor = "NAME: x\nTOUR_SECTION\n1 2\n3\n-1\n".as_bytes();
+
+        let entries = process_tour_entries(cursor).unwrap();
+
+        assert_eq!(
+            TourEntry {
+                line: 3,
+                city_id: 2
+            },
+            entries[1]
+        );
+        assert_eq!(
+            TourEntry {
+                line: 4,
+                city_id: 3
+            },
+            entries[2]
+        );
+    }
+
+    #[test]
+    fn test_process_tour_lines_without_tour_section() {
+        let cursor = "NAME: x\n1\n2\n".as_bytes();
+
+        assert!(process_tour_lines(cursor).is_err());
+    }
+
     #[test]
     fn test_process_lines_with_empty_string() {
         let cursor = "".as_bytes();
@@ -0,0 +1,224 @@
+/// TSPLIB XML format
+///
+/// The XML encoding of the TSPLIB instances lists the cost of every edge of the complete graph,
+/// the vertices are numbered from 0 in the order they are listed:
+///
+/// ```xml
+/// <travellingSalesmanProblemInstance>
+///   <name>burma14</name>
+///   <source>TSPLIB</source>
+///   <description>14-Staedte in Burma (Zaw Win)</description>
+///   <graph>
+///     <vertex>
+///       <edge cost="1.530000000000000e+02">1</edge>
+///       <edge cost="5.100000000000000e+02">2</edge>
+///     </vertex>
+///   </graph>
+/// </travellingSalesmanProblemInstance>
+/// ```
+///
+/// The instance has no coordinates, so the cities `1..n`, the ids the text format gives them,
+/// take their distances from the `EdgeWeights` of the costs; the solvers solve them like the
+/// instances of the coordinates. The edge weight type of the instance is `EXPLICIT`.
+use std::io::Read;
+use std::path::Path;
+
+use roxmltree::{Document, Node};
+
+use super::kdtree::EdgeWeights;
+use super::tsplib::TspLibData;
+use super::Error;
+
+const FORMAT: &str = "tsplib_xml";
+// the costs are printed with 15 digits, the rounding of them is not the asymmetry
+const MAX_ASYMMETRY: f64 = 1e-6;
+
+const EDGE_WEIGHT_TYPE: &str = "EXPLICIT";
+
+pub fn read_from_file(path: &Path) -> Result<TspLibData, Error> {
+    let mut text = String::new();
+    super::tsplib::open_file(path)
+        .and_then(|mut reader| reader.read_to_string(&mut text))
+        .map_err(Error::io("tsplib_xml: failed to read file"))?;
+
+    read_from_str(&text)
+}
+
+/// reads the instance from the XML document, its description is the comment of the problem
+pub fn read_from_str(text: &str) -> Result<TspLibData, Error> {
+    let doc = Document::parse(text)
+        .map_err(|err| Error::parse(FORMAT, err.pos().row as usize, &err.to_string()))?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "travellingSalesmanProblemInstance" {
+        return Err(Error::parse(
+            FORMAT,
+            line_of(&doc, root),
+            "expected <travellingSalesmanProblemInstance>",
+        ));
+    }
+
+    let graph = child(root, "graph")
+        .ok_or_else(|| Error::parse(FORMAT, line_of(&doc, root), "found no <graph>"))?;
+    let costs = read_costs(&doc, graph)?;
+    let cities = edge_weights(&costs)?.into_cities();
+
+    let mut data = TspLibData::new(
+        child_text(root, "name").to_lowercase(),
+        child_text(root, "description"),
+        cities,
+    );
+    data.edge_weight_type = EDGE_WEIGHT_TYPE.to_string();
+
+    Ok(data)
+}
+
+// the full matrix of the costs, by the positions of the vertices
+fn read_costs(doc: &Document, graph: Node) -> Result<Vec<Vec<Option<f64>>>, Error> {
+    let vertices: Vec<Node> = graph
+        .children()
+        .filter(|node| node.has_tag_name("vertex"))
+        .collect();
+    let n = vertices.len();
+    if n < 2 {
+        return Err(Error::parse(
+            FORMAT,
+            line_of(doc, graph),
+            "the graph needs at least 2 vertices",
+        ));
+    }
+
+    let mut costs = vec![vec![None; n]; n];
+    for (from, vertex) in vertices.iter().enumerate() {
+        costs[from][from] = Some(0.0);
+
+        for edge in vertex.children().filter(|node| node.has_tag_name("edge")) {
+            let line = line_of(doc, edge);
+            let to = edge
+                .text()
+                .and_then(|text| text.trim().parse::<usize>().ok())
+                .filter(|to| *to < n)
+                .ok_or_else(|| Error::parse(FORMAT, line, "the edge must name a vertex 0..n-1"))?;
+            let cost = edge
+                .attribute("cost")
+                .and_then(|cost| cost.trim().parse::<f64>().ok())
+                .filter(|cost| cost.is_finite() && *cost >= 0.0)
+                .ok_or_else(|| {
+                    Error::parse(FORMAT, line, "the edge must have a non-negative cost")
+                })?;
+
+            costs[from][to] = Some(cost);
+        }
+    }
+
+    Ok(costs)
+}
+
+// the costs of the symmetric instance, the edge listed only once has the same cost both ways
+fn edge_weights(costs: &[Vec<Option<f64>>]) -> Result<EdgeWeights, Error> {
+    let n = costs.len();
+    let mut rows = vec![vec![0.0; n]; n];
+
+    for (i, row) in costs.iter().enumerate() {
+        for (j, cost) in row.iter().enumerate().take(i) {
+            let cost = match (*cost, costs[j][i]) {
+                (Some(a), Some(b)) if (a - b).abs() <= MAX_ASYMMETRY * a.max(b).max(1.0) => a,
+                (Some(_), Some(_)) => {
+                    return Err(Error::InvalidInput(format!(
+                        "tsplib_xml: the costs of the edge {}-{} differ, only the symmetric instances are supported",
+                        j, i
+                    )))
+                }
+                (Some(cost), None) | (None, Some(cost)) => cost,
+                (None, None) => {
+                    return Err(Error::InvalidInput(format!(
+                        "tsplib_xml: the graph has no edge {}-{}",
+                        j, i
+                    )))
+                }
+            };
+
+            rows[i][j] = cost;
+            rows[j][i] = cost;
+        }
+    }
+
+    EdgeWeights::new(&rows)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn child_text(node: Node, name: &str) -> String {
+    child(node, name)
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn line_of(doc: &Document, node: Node) -> usize {
+    doc.text_pos_at(node.range().start).row as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<travellingSalesmanProblemInstance>
+  <name>Triangle</name>
+  <source>teeline</source>
+  <description>3 cities</description>
+  <doublePrecision>15</doublePrecision>
+  <ignoredDigits>0</ignoredDigits>
+  <graph>
+    <vertex>
+      <edge cost="3.000000000000000e+00">1</edge>
+      <edge cost="4.000000000000000e+00">2</edge>
+    </vertex>
+    <vertex>
+      <edge cost="3.000000000000000e+00">0</edge>
+      <edge cost="5.000000000000000e+00">2</edge>
+    </vertex>
+    <vertex>
+      <edge cost="4.000000000000000e+00">0</edge>
+      <edge cost="5.000000000000000e+00">1</edge>
+    </vertex>
+  </graph>
+</travellingSalesmanProblemInstance>
+"#;
+
+    #[test]
+    fn test_read_from_str() {
+        let instance = read_from_str(TRIANGLE).unwrap();
+
+        assert_eq!("triangle", instance.name);
+        assert_eq!("3 cities", instance.comment);
+        assert_eq!("EXPLICIT", instance.edge_weight_type);
+
+        let cities = instance.cities();
+        assert_eq!(
+            vec![1, 2, 3],
+            cities.iter().map(|c| c.id).collect::<Vec<_>>()
+        );
+        assert_eq!(3.0, cities[0].distance(&cities[1]));
+        assert_eq!(4.0, cities[2].distance(&cities[0]));
+        assert_eq!(5.0, cities[1].distance(&cities[2]));
+    }
+
+    #[test]
+    fn test_read_rejects_broken_instances() {
+        let asymmetric = TRIANGLE.replacen("5.000000000000000e+00", "6.0", 1);
+        let err = read_from_str(&asymmetric).err().unwrap();
+        assert!(err.to_string().contains("edge 1-2 differ"), "{}", err);
+
+        let unknown_vertex = TRIANGLE.replacen(">2</edge>", ">7</edge>", 1);
+        let err = read_from_str(&unknown_vertex).err().unwrap();
+        assert!(err.to_string().contains("on line.11"), "{}", err);
+
+        let err = read_from_str("<tour></tour>").err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("travellingSalesmanProblemInstance"));
+    }
+}
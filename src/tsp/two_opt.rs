@@ -1,65 +1,618 @@
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use super::fixed_edges::FixedEdges;
+use super::history::History;
 use super::kdtree::KDPoint;
-use super::progress::{send_progress, ProgressMessage};
-use super::route::Route;
-use super::{city_table_from_vec, Solution, SolverOptions};
-
-pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Solution {
-    let n_indices = cities.len() - 1;
-    let cities_table = city_table_from_vec(cities);
-    let mut path: Vec<usize> = cities.iter().map(|c| c.id).collect();
-
-    send_progress(ProgressMessage::PathUpdate(Route::new(&path), 0.0));
-
-    let mut improved = true;
-    while improved {
-        improved = false;
-        for i in 0..(n_indices - 2) {
-            send_progress(ProgressMessage::CityChange(path[i]));
-
-            for j in (i + 2)..n_indices {
-                let current_distance = cities_table[&path[i]].distance(&cities_table[&path[i + 1]])
-                    + cities_table[&path[j]].distance(&cities_table[&path[j + 1]]);
-
-                let new_distance = cities_table[&path[i]].distance(&cities_table[&path[j]])
-                    + cities_table[&path[i + 1]].distance(&cities_table[&path[j + 1]]);
-
-                if new_distance < current_distance {
-                    swap_2opt(&mut path, i + 1, j);
-                    improved = true;
-
-                    send_progress(ProgressMessage::PathUpdate(Route::new(&path), new_distance));
-
-                    if options.verbose {
-                        println!(
-                            "2OPT: cities(i: {:?}, j: {:?}) new best {:?}",
-                            i, j, new_distance
-                        );
-                    }
+use super::neighbors;
+use super::precedence::Precedences;
+use super::progress::{self, ProgressMessage, PublisherFn};
+use super::registry::Category;
+use super::route::{reverse_segment, Route};
+use super::tour::{self, Tour};
+use super::trace::{self, MoveKind};
+use super::{
+    path_length, total_distance, validate_cities, Deadline, Error, Solution, SolverInfo,
+    SolverOptions, TspSolver,
+};
+
+// the smallest gain of the move, the rounding errors must not undo the earlier moves
+const MIN_GAIN: f32 = 1e-5;
+
+/// the neighbor lists 2-opt looks for the moves in
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoOptOptions {
+    pub n_nearest: usize, // size of the neighbor lists, 0 turns them off
+    pub candidates: neighbors::Candidates, // how the neighbor lists are built
+}
+
+impl TwoOptOptions {
+    /// copies the options into the options the solver runs with
+    pub fn apply(&self, options: &mut SolverOptions) {
+        options.n_nearest = self.n_nearest;
+        options.candidates = self.candidates;
+    }
+}
+
+impl Default for TwoOptOptions {
+    fn default() -> Self {
+        TwoOptOptions::from(&SolverOptions::default())
+    }
+}
+
+impl From<&SolverOptions> for TwoOptOptions {
+    fn from(options: &SolverOptions) -> Self {
+        TwoOptOptions {
+            n_nearest: options.n_nearest,
+            candidates: options.candidates,
+        }
+    }
+}
+
+pub struct TwoOptSolver;
+
+impl TspSolver for TwoOptSolver {
+    fn metadata(&self) -> SolverInfo {
+        SolverInfo::new(
+            "two_opt",
+            &["2opt"],
+            "applies improving 2-opt and city relocation moves until none is left",
+            false,
+        )
+        .with_category(Category::LocalSearch)
+        .with_complexity("O(n k) per pass with n_nearest k, O(n^2) without")
+        .with_options(&[
+            "n_nearest",
+            "candidates",
+            "max_seconds",
+            "target_cost",
+            "max_stale_epochs",
+            "end_city",
+            "fixed_edges",
+            "open_tour",
+            "precedences",
+        ])
+    }
+
+    fn solve(
+        &self,
+        cities: &[KDPoint],
+        options: &SolverOptions,
+        progress: PublisherFn,
+    ) -> Result<Solution, Error> {
+        validate_cities(cities, 3)?;
+
+        let mut path: Vec<usize> = options.start_route(cities)?.route().to_vec();
+        // the moves never remove the fixed edges, so the start route must have them already
+        let fixed = FixedEdges::from_options(cities, options)?;
+        if let Some((a, b)) = fixed.broken_edge(&path) {
+            return Err(Error::InvalidInput(format!(
+                "two_opt: the start route breaks the fixed edge {}-{}, build it with nearest_neighbor",
+                a, b
+            )));
+        }
+        // the moves keep the order of the precedences, so the start route is put in order first
+        let precedences = Precedences::from_options(cities, options)?;
+        if precedences.violation(&path).is_some() {
+            path = precedences.repair(&path);
+            if let Some((a, b)) = fixed.broken_edge(&path) {
+                return Err(Error::InvalidInput(format!(
+                    "two_opt: the order of the precedences breaks the fixed edge {}-{}",
+                    a, b
+                )));
+            }
+        }
+
+        let mut history = History::new();
+        history.record(0, route_length(cities, &path, options));
+        progress(ProgressMessage::PathUpdate(
+            Route::new(&path),
+            route_length(cities, &path, options),
+        ));
+
+        // the neighbor lists don't look for the moves, which reverse the ends of the open tour,
+        // and their reversals may move the first city, which the precedences are counted from
+        if uses_neighbor_lists(cities.len(), options.n_nearest)
+            && !options.open_tour
+            && precedences.is_empty()
+        {
+            search_neighbor_lists(cities, &mut path, options, fixed, &progress, &mut history)?;
+        } else {
+            let constraints = Constraints {
+                fixed: &fixed,
+                precedences: &precedences,
+            };
+            search_all_pairs(
+                cities,
+                &mut path,
+                options,
+                &constraints,
+                &progress,
+                &mut history,
+            );
+        }
+
+        progress(ProgressMessage::Done);
+        Ok(Solution::new(&path, cities)
+            .with_open_tour(options.open_tour)
+            .with_target(options)
+            .with_history(history))
+    }
+}
+
+// the length of the tour the search minimizes, without the closing edge if the tour is open
+fn route_length(cities: &[KDPoint], path: &[usize], options: &SolverOptions) -> f32 {
+    if options.open_tour {
+        path_length(cities, path) as f32
+    } else {
+        total_distance(cities, path)
+    }
+}
+
+/// the neighbor lists pay off only if they are shorter than the list of all the other cities;
+/// `n_nearest` 0 turns them off
+fn uses_neighbor_lists(n_cities: usize, n_nearest: usize) -> bool {
+    n_nearest > 0 && n_nearest < n_cities - 1
+}
+
+// what the moves of the search over all the pairs must keep
+struct Constraints<'a> {
+    fixed: &'a FixedEdges,
+    precedences: &'a Precedences,
+}
+
+impl Constraints<'_> {
+    // true if the reversal of the positions from..=to of the path keeps the order of the
+    // precedences; the positions of the path by the city ids are needed only with them
+    fn keeps_order(
+        &self,
+        path: &[usize],
+        pos_of: &HashMap<usize, usize>,
+        from: usize,
+        to: usize,
+    ) -> bool {
+        self.precedences.is_empty() || !self.precedences.breaks_reversal(path, pos_of, from, to)
+    }
+}
+
+// checks every pair of the edges on every pass, O(n^2); the improving moves of the edges are
+// looked for in parallel, then the best moves, which don't overlap, are applied together
+fn search_all_pairs(
+    cities: &[KDPoint],
+    path: &mut [usize],
+    options: &SolverOptions,
+    constraints: &Constraints,
+    progress: &PublisherFn,
+    history: &mut History,
+) {
+    let deadline = options.deadline();
+    let index_of: HashMap<usize, usize> =
+        cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+    let mut tour: Vec<usize> = path.iter().map(|id| index_of[id]).collect();
+    let mut tour_length = route_length(cities, path, options);
+
+    let mut epoch = 0;
+    // the path is a valid tour after every pass, so it can stop anywhere
+    while !deadline.is_over() && !options.is_target_reached(tour_length) {
+        epoch += 1;
+        progress::report_epoch(progress, epoch);
+
+        let mut moves = improving_moves(cities, &tour, constraints.fixed, &deadline);
+        if options.open_tour {
+            let keeps_first = options.start_city.is_some();
+            moves.extend(end_reversals(cities, &tour, constraints.fixed, keeps_first));
+        }
+        if !constraints.precedences.is_empty() {
+            let pos_of: HashMap<usize, usize> =
+                path.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+            moves.retain(|mv| constraints.keeps_order(path, &pos_of, mv.from, mv.to));
+        }
+        if moves.is_empty() {
+            break;
+        }
+
+        for mv in disjoint_moves(moves) {
+            swap_2opt(&mut tour, mv.from, mv.to);
+            tour_length += mv.delta;
+
+            trace::record(MoveKind::TwoOpt, epoch, mv.from, mv.to, mv.delta);
+            if options.verbose {
+                println!(
+                    "2OPT: positions(from: {:?}, to: {:?}) new best {:?}",
+                    mv.from, mv.to, tour_length
+                );
+            }
+        }
+
+        for (i, city) in tour.iter().enumerate() {
+            path[i] = cities[*city].id;
+        }
+        history.record(epoch, tour_length);
+        progress(ProgressMessage::PathUpdate(Route::new(path), tour_length));
+    }
+}
+
+// the move reverses the positions from..=to of the tour
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TwoOptMove {
+    from: usize,
+    to: usize,
+    delta: f32,
+}
+
+// the first improving move of every edge (i, i + 1), it has the shortest segment to reverse,
+// so it overlaps the fewest other moves; the tour holds the positions of the cities
+fn improving_moves(
+    cities: &[KDPoint],
+    tour: &[usize],
+    fixed: &FixedEdges,
+    deadline: &Deadline,
+) -> Vec<TwoOptMove> {
+    let n_indices = tour.len() - 1;
+    let distance = |a: usize, b: usize| cities[tour[a]].distance(&cities[tour[b]]);
+    let is_fixed = |a: usize| fixed.is_fixed(cities[tour[a]].id, cities[tour[a + 1]].id);
+
+    (0..(n_indices - 2))
+        .into_par_iter()
+        .filter_map(|i| {
+            if deadline.is_over() || is_fixed(i) {
+                return None;
+            }
+
+            ((i + 2)..n_indices).find_map(|j| {
+                if is_fixed(j) {
+                    return None;
                 }
+
+                let delta = distance(i, j) + distance(i + 1, j + 1)
+                    - distance(i, i + 1)
+                    - distance(j, j + 1);
+
+                (delta < -MIN_GAIN).then_some(TwoOptMove {
+                    from: i + 1,
+                    to: j,
+                    delta,
+                })
+            })
+        })
+        .collect()
+}
+
+// the open tour has no closing edge, the reversal of its first or last cities replaces only
+// one edge: reversing the positions 0..=i replaces the edge (i, i + 1) with (0, i + 1), and
+// reversing the positions i + 1..=n - 1 replaces it with (i, n - 1); the better one of every edge.
+// The path from the start city keeps its first city, so only its last cities are reversed
+fn end_reversals(
+    cities: &[KDPoint],
+    tour: &[usize],
+    fixed: &FixedEdges,
+    keeps_first: bool,
+) -> Vec<TwoOptMove> {
+    let last = tour.len() - 1;
+    let distance = |a: usize, b: usize| cities[tour[a]].distance(&cities[tour[b]]);
+
+    (0..last)
+        .filter(|i| !fixed.is_fixed(cities[tour[*i]].id, cities[tour[i + 1]].id))
+        .filter_map(|i| {
+            let removed = distance(i, i + 1);
+            let prefix = (i > 0 && !keeps_first).then(|| TwoOptMove {
+                from: 0,
+                to: i,
+                delta: distance(0, i + 1) - removed,
+            });
+            let suffix = (i + 1 < last).then(|| TwoOptMove {
+                from: i + 1,
+                to: last,
+                delta: distance(i, last) - removed,
+            });
+
+            prefix
+                .into_iter()
+                .chain(suffix)
+                .filter(|mv| mv.delta < -MIN_GAIN)
+                .min_by(|a, b| a.delta.partial_cmp(&b.delta).unwrap_or(Ordering::Equal))
+        })
+        .collect()
+}
+
+// picks the best moves, which don't touch the same positions: the move changes the edges
+// (from - 1, from) and (to, to + 1), and only the cities between them are moved
+fn disjoint_moves(mut moves: Vec<TwoOptMove>) -> Vec<TwoOptMove> {
+    moves.sort_by(|a, b| a.delta.partial_cmp(&b.delta).unwrap_or(Ordering::Equal));
+
+    // the spans of the picked moves, by the first position
+    let mut spans: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut picked = vec![];
+    for mv in moves {
+        // the reversal of the first cities of the open tour has no edge before it
+        let (start, end) = (mv.from.saturating_sub(1), mv.to + 1);
+        let overlaps_previous = spans
+            .range(..=start)
+            .next_back()
+            .is_some_and(|(_, prev_end)| *prev_end > start);
+        let overlaps_next = spans
+            .range(start..)
+            .next()
+            .is_some_and(|(next_start, _)| *next_start < end);
+
+        if !overlaps_previous && !overlaps_next {
+            spans.insert(start, end);
+            picked.push(mv);
+        }
+    }
+
+    picked
+}
+
+// replaces the edge of every city with an edge to one of its candidate cities, O(n k) per pass;
+// the new edge must be shorter than the old one, otherwise the move can't gain. If no 2-opt
+// move improves the tour, the city is moved between one of its nearest cities and their neighbor.
+// The don't-look bits skip the cities, which had no improving move, until a move changes
+// one of their edges: only the cities in the queue are looked at.
+fn search_neighbor_lists(
+    cities: &[KDPoint],
+    path: &mut [usize],
+    options: &SolverOptions,
+    fixed: FixedEdges,
+    progress: &PublisherFn,
+    history: &mut History,
+) -> Result<(), Error> {
+    let deadline = options.deadline();
+    let mut search = NeighborSearch::new(cities, path, options, fixed)?;
+    let mut tour_length = total_distance(cities, path);
+
+    let mut queue: VecDeque<usize> = search.tour.to_vec().into_iter().collect();
+    let mut is_queued = vec![true; cities.len()];
+
+    let mut epoch = 0;
+    // the cities looked at since the last improving move; the passes over all the pairs stop
+    // anyway as soon as one of them doesn't improve the tour
+    let mut n_stale = 0;
+    // every epoch looks at the cities, which were in the queue when it started;
+    // the tour is published after the epoch, copying it after every move would take O(n)
+    'search: while !queue.is_empty() {
+        epoch += 1;
+        progress::report_epoch(progress, epoch);
+        for _ in 0..queue.len() {
+            if deadline.is_over()
+                || options.is_target_reached(tour_length)
+                || options.is_stale(n_stale)
+            {
+                break 'search;
+            }
+
+            let a = queue.pop_front().unwrap();
+            is_queued[a] = false;
+            progress(ProgressMessage::CityChange(cities[a].id));
+
+            let applied = match search.improve_city(a) {
+                Some(applied) => applied,
+                None => {
+                    n_stale += 1;
+                    continue;
+                }
+            };
+
+            n_stale = 0;
+            tour_length += applied.delta;
+            for city in applied.endpoints.iter().copied() {
+                if !is_queued[city] {
+                    is_queued[city] = true;
+                    queue.push_back(city);
+                }
+            }
+
+            trace::record(applied.kind, epoch, applied.from, applied.to, applied.delta);
+
+            if options.verbose {
+                println!(
+                    "2OPT: {} cities(a: {:?}, c: {:?}) new best {:?}",
+                    applied.kind.as_str(),
+                    cities[a].id,
+                    cities[applied.endpoints[2]].id,
+                    tour_length
+                );
             }
         }
+
+        search.copy_tour_into(path);
+        history.record(epoch, tour_length);
+        progress(ProgressMessage::PathUpdate(Route::new(path), tour_length));
     }
 
-    send_progress(ProgressMessage::Done);
-    Solution::new(&path, cities)
+    // the search may stop in the middle of the epoch
+    history.record(epoch, tour_length);
+    search.copy_tour_into(path);
+    Ok(())
 }
 
-fn swap_2opt(path: &mut Vec<usize>, from: usize, to: usize) {
-    if from >= to {
-        return; // ignore if from to are equal or wrong order
+// the move done by the neighbor search
+struct AppliedMove {
+    kind: MoveKind,
+    from: usize, // the positions of the reversed segment, or the old and new position of the city
+    to: usize,
+    delta: f32,
+    endpoints: Vec<usize>, // the cities of the replaced edges, the moved city and its new neighbor first
+}
+
+// the tour by the positions of the cities in the `cities`, `tour::from_order` picks the two-level
+// list for the big tours, so the reversals don't copy the half of the tour
+struct NeighborSearch<'a> {
+    cities: &'a [KDPoint],
+    neighbors: Vec<Vec<usize>>,
+    tour: Box<dyn Tour>,
+    fixed: FixedEdges, // the edges the moves must not remove
+}
+
+impl<'a> NeighborSearch<'a> {
+    fn new(
+        cities: &'a [KDPoint],
+        path: &[usize],
+        options: &SolverOptions,
+        fixed: FixedEdges,
+    ) -> Result<Self, Error> {
+        let index_of: HashMap<usize, usize> =
+            cities.iter().enumerate().map(|(i, c)| (c.id, i)).collect();
+        let order: Vec<usize> = path.iter().map(|id| index_of[id]).collect();
+
+        Ok(NeighborSearch {
+            cities,
+            neighbors: neighbors::build_candidates(cities, options.candidates, options.n_nearest)?,
+            tour: tour::from_order(&order),
+            fixed,
+        })
+    }
+
+    fn distance(&self, a: usize, b: usize) -> f32 {
+        self.cities[a].distance(&self.cities[b])
+    }
+
+    fn is_fixed(&self, a: usize, b: usize) -> bool {
+        self.fixed.is_fixed(self.cities[a].id, self.cities[b].id)
+    }
+
+    fn neighbor(&self, city: usize, forward: bool) -> usize {
+        if forward {
+            self.tour.next(city)
+        } else {
+            self.tour.prev(city)
+        }
+    }
+
+    // applies the first improving move, which replaces the edge to the successor
+    // or to the predecessor of the city
+    fn improve_city(&mut self, a: usize) -> Option<AppliedMove> {
+        for forward in [true, false] {
+            let b = self.neighbor(a, forward);
+            if self.is_fixed(a, b) {
+                continue;
+            }
+
+            let removed = self.distance(a, b);
+
+            for c in self.neighbors[a].iter().copied() {
+                let added = self.distance(a, c);
+                if added >= removed {
+                    break;
+                }
+
+                let d = self.neighbor(c, forward);
+                if c == b || d == a || self.is_fixed(c, d) {
+                    continue;
+                }
+
+                let delta = added + self.distance(b, d) - removed - self.distance(c, d);
+                if delta > -MIN_GAIN {
+                    continue;
+                }
+
+                let (first, last) = if forward { (b, c) } else { (c, b) };
+                let (from, to) = (self.tour.position(first), self.tour.position(last));
+                self.tour.reverse(first, last);
+
+                return Some(AppliedMove {
+                    kind: MoveKind::TwoOpt,
+                    from,
+                    to,
+                    delta,
+                    endpoints: vec![a, b, c, d],
+                });
+            }
+        }
+
+        self.relocate_city(a)
+    }
+
+    // moves the city between one of its nearest cities and their successor or predecessor,
+    // the first move which is shorter than the tour wins; the new edges may be longer than the
+    // removed ones, so all the k neighbors are checked
+    fn relocate_city(&mut self, a: usize) -> Option<AppliedMove> {
+        let p = self.tour.prev(a);
+        let s = self.tour.next(a);
+        if self.is_fixed(p, a) || self.is_fixed(a, s) {
+            return None;
+        }
+
+        // what the tour saves when the city is left out
+        let removed = self.distance(p, a) + self.distance(a, s) - self.distance(p, s);
+
+        for c in self.neighbors[a].iter().copied() {
+            let added = self.distance(a, c);
+
+            for forward in [true, false] {
+                let d = self.neighbor(c, forward);
+                if d == a || self.is_fixed(c, d) {
+                    continue;
+                }
+
+                let delta = added + self.distance(a, d) - self.distance(c, d) - removed;
+                if delta > -MIN_GAIN {
+                    continue;
+                }
+
+                let from = self.tour.position(a);
+                let after = if forward { c } else { d };
+                relocate(self.tour.as_mut(), a, after);
+
+                return Some(AppliedMove {
+                    kind: MoveKind::Relocate,
+                    from,
+                    to: self.tour.position(a),
+                    delta,
+                    endpoints: vec![a, p, c, d, s],
+                });
+            }
+        }
+
+        None
     }
 
-    let reversed_seq: Vec<usize> = path[from..=to].iter().map(|x| x.clone()).rev().collect();
+    fn copy_tour_into(&self, path: &mut [usize]) {
+        for (i, city) in self.tour.to_vec().into_iter().enumerate() {
+            path[i] = self.cities[city].id;
+        }
+    }
+}
+
+// moves the city right after the city `after` with two reversals: the first one puts the city
+// before the successor of `after`, the second one turns back the cities it jumped over
+fn relocate(tour: &mut dyn Tour, city: usize, after: usize) {
+    let (p, s) = (tour.prev(city), tour.next(city));
+    if after == p || after == city {
+        return;
+    }
+
+    // p s .. after next .. => p after .. s city next ..
+    tour.reverse(city, after);
+    // the tour may have reversed the rest instead, then it goes the other way
+    if tour.next(p) == after {
+        tour.reverse(after, s);
+    } else {
+        tour.reverse(s, after);
+    }
+}
+
+/// solves the problem and sends the progress into the global progress channel
+pub fn solve(cities: &[KDPoint], options: &SolverOptions) -> Result<Solution, Error> {
+    TwoOptSolver.solve(cities, options, progress::default_publisher())
+}
 
-    for (i, swapped_val) in reversed_seq.iter().enumerate() {
-        path[from + i] = swapped_val.clone();
+fn swap_2opt(path: &mut [usize], from: usize, to: usize) {
+    if from >= to {
+        return; // ignore if from to are equal or wrong order
     }
+
+    reverse_segment(path, from, to);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::tsp::kdtree;
+    use crate::tsp::tour::{ArrayTour, TwoLevelTour};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
 
     #[test]
     fn test_swap_2opt_2middle_elems_in_even_size_list() {
@@ -90,8 +643,253 @@ mod tests {
         ]);
 
         let default_opts = SolverOptions::default();
-        let tour = solve(&cities, &default_opts);
+        let tour = solve(&cities, &default_opts).unwrap();
         assert_eq!(4.0, tour.total);
         assert_eq!(&[0, 1, 2, 3, 4], tour.route());
     }
+
+    #[test]
+    fn test_disjoint_moves_picks_best_moves() {
+        let mv = |from, to, delta| TwoOptMove { from, to, delta };
+        let moves = vec![
+            mv(1, 3, -1.0),
+            // the position 4 is in both, but neither of them moves the city on it
+            mv(5, 8, -2.0),
+            // both of them overlap the better moves
+            mv(4, 6, -0.5),
+            mv(2, 3, -0.1),
+        ];
+
+        assert_eq!(vec![mv(5, 8, -2.0), mv(1, 3, -1.0)], disjoint_moves(moves));
+    }
+
+    #[test]
+    fn test_solve_all_pairs_in_parallel() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![2.0, 0.0],
+            vec![3.0, 0.0],
+            vec![3.0, 1.0],
+            vec![2.0, 1.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+        ]);
+
+        let mut options = SolverOptions::default();
+        options.n_nearest = 0;
+        options.initial_route = Some(vec![0, 2, 1, 3, 5, 4, 6, 7]);
+        let tour = solve(&cities, &options).unwrap();
+
+        assert!((tour.total - 8.0).abs() < 0.001, "{}", tour.total);
+    }
+
+    #[test]
+    fn test_solve_keeps_fixed_edges() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![2.0, 0.0],
+            vec![3.0, 0.0],
+            vec![3.0, 1.0],
+            vec![2.0, 1.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+        ]);
+        let mut options = SolverOptions::default();
+        options.fixed_edges = vec![(0, 5)];
+
+        let err = solve(&cities, &options).err().unwrap();
+        assert!(
+            err.to_string().contains("breaks the fixed edge 0-5"),
+            "{}",
+            err
+        );
+
+        options.initial_route = Some(vec![0, 5, 1, 2, 3, 4, 6, 7]);
+        for n_nearest in [0, 3] {
+            options.n_nearest = n_nearest;
+            let tour = solve(&cities, &options).unwrap();
+
+            let fixed = FixedEdges::from_options(&cities, &options).unwrap();
+            assert_eq!(None, fixed.broken_edge(tour.route()));
+            assert!(tour.total > 8.0);
+        }
+    }
+
+    #[test]
+    fn test_solve_keeps_order_of_precedences() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![2.0, 0.0],
+            vec![3.0, 0.0],
+            vec![3.0, 1.0],
+            vec![2.0, 1.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+        ]);
+        let mut options = SolverOptions::default();
+        options.n_nearest = 3;
+        // the start route breaks both pairs, it is repaired to 0 7 4 3 2 6 1 5 of the length 12.06
+        options.initial_route = Some(vec![0, 7, 6, 5, 4, 3, 2, 1]);
+        options.precedences = vec![(2, 6), (1, 5)];
+
+        let tour = solve(&cities, &options).unwrap();
+
+        let precedences = Precedences::from_options(&cities, &options).unwrap();
+        assert_eq!(None, precedences.violation(tour.route()));
+        assert_eq!(0, tour.route()[0]);
+        assert!(tour.total < 12.0, "{}", tour.total);
+    }
+
+    #[test]
+    fn test_solve_open_tour() {
+        // the cities on the line, the open tour goes from one end to the other
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![3.0, 0.0],
+            vec![1.0, 0.0],
+            vec![4.0, 0.0],
+            vec![2.0, 0.0],
+        ]);
+        let mut options = SolverOptions::default();
+        options.open_tour = true;
+
+        let tour = solve(&cities, &options).unwrap();
+
+        assert!(tour.is_open_tour());
+        assert!((tour.total - 4.0).abs() < 0.001, "{}", tour.total);
+        assert!([0, 3].contains(&tour.route()[0]), "{:?}", tour.route());
+    }
+
+    #[test]
+    fn test_solve_path_from_start_to_end_city() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![3.0, 0.0],
+            vec![1.0, 0.0],
+            vec![4.0, 0.0],
+            vec![2.0, 0.0],
+        ]);
+        let mut options = SolverOptions::default();
+        options.start_city = Some(2);
+        options.end_city = Some(3);
+        assert!(options.check_constraints(&TwoOptSolver.metadata()).is_ok());
+
+        let path = solve(&cities, &options.to_tour_options())
+            .unwrap()
+            .with_start_city(&options)
+            .unwrap();
+
+        assert!(path.is_open_tour());
+        assert_eq!(&[2, 0, 4, 1, 3], path.route());
+        assert!((path.total - 5.0).abs() < 0.001, "{}", path.total);
+    }
+
+    #[test]
+    fn test_dont_look_bits_keep_quality() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let rows: Vec<Vec<f64>> = (0..200)
+            .map(|_| vec![rng.gen_range(0.0, 100.0), rng.gen_range(0.0, 100.0)])
+            .collect();
+        let cities = kdtree::build_points(&rows);
+        let mut options = SolverOptions::default();
+
+        let tour = solve(&cities, &options).unwrap();
+        options.n_nearest = 0;
+        let all_pairs = solve(&cities, &options).unwrap();
+
+        assert_eq!(cities.len(), tour.len());
+        assert!(
+            tour.total < all_pairs.total * 1.05,
+            "{} vs {}",
+            tour.total,
+            all_pairs.total
+        );
+    }
+
+    #[test]
+    fn test_solve_with_neighbor_lists() {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![1.0, 0.0],
+            vec![2.0, 0.0],
+            vec![3.0, 0.0],
+            vec![3.0, 1.0],
+            vec![2.0, 1.0],
+            vec![1.0, 1.0],
+            vec![0.0, 1.0],
+        ]);
+
+        let mut options = SolverOptions::default();
+        options.n_nearest = 3;
+        // the tour crosses itself twice, once on the closing edge
+        options.initial_route = Some(vec![1, 2, 3, 5, 4, 6, 0, 7]);
+        let tour = solve(&cities, &options).unwrap();
+
+        assert!(uses_neighbor_lists(cities.len(), options.n_nearest));
+        assert!((tour.total - 8.0).abs() < 0.001, "{}", tour.total);
+    }
+
+    #[test]
+    fn test_relocate_moves_city_after_other() {
+        // the tour may go either way after the reversals
+        fn assert_cycle(expected: &[usize], tour: &dyn Tour) {
+            let mut mirrored = expected.to_vec();
+            mirrored[1..].reverse();
+            let actual = tour.to_vec();
+            assert!(actual == expected || actual == mirrored, "{:?}", actual);
+        }
+
+        let order = [0, 1, 2, 3, 4, 5];
+        let mut tours: Vec<Box<dyn Tour>> = vec![
+            Box::new(ArrayTour::new(&order)),
+            Box::new(TwoLevelTour::new(&order)),
+        ];
+
+        for tour in tours.iter_mut() {
+            relocate(tour.as_mut(), 1, 3);
+            assert_cycle(&[0, 2, 3, 1, 4, 5], tour.as_ref());
+
+            // the city 0 goes between 4 and 5, the first of them in the direction of the tour
+            let after = if tour.next(4) == 5 { 4 } else { 5 };
+            relocate(tour.as_mut(), 0, after);
+            assert_cycle(&[0, 5, 2, 3, 1, 4], tour.as_ref());
+
+            // the city is there already
+            let after = tour.prev(0);
+            relocate(tour.as_mut(), 0, after);
+            assert_cycle(&[0, 5, 2, 3, 1, 4], tour.as_ref());
+        }
+    }
+
+    #[test]
+    fn test_relocation_improves_2opt_optimum() {
+        let cities = kdtree::build_points(&[
+            vec![9.0, 2.0],
+            vec![4.0, 5.0],
+            vec![0.0, 4.0],
+            vec![7.0, 7.0],
+            vec![2.0, 2.0],
+            vec![0.0, 5.0],
+            vec![6.0, 8.0],
+        ]);
+        let mut options = SolverOptions::default();
+        // no 2-opt move improves this tour, but moving a city does
+        options.initial_route = Some(vec![5, 6, 3, 0, 1, 4, 2]);
+        let start = total_distance(&cities, &[5, 6, 3, 0, 1, 4, 2]);
+
+        options.n_nearest = 0;
+        let all_pairs = solve(&cities, &options).unwrap();
+        assert!(
+            (all_pairs.total - start).abs() < 0.001,
+            "{}",
+            all_pairs.total
+        );
+
+        options.n_nearest = 5;
+        let tour = solve(&cities, &options).unwrap();
+        assert!(tour.total < start - 1.0, "{} vs {}", tour.total, start);
+    }
 }
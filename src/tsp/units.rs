@@ -0,0 +1,173 @@
+/// Units of the reported tour length
+///
+/// The TSPLIB `GEO` distance type measures distances in kilometers on the earth surface,
+/// the units are applied only at output time, so solvers keep working with raw distances.
+/// The solvers compare the coordinates as they are, so the length in the units is measured
+/// again with the TSPLIB formula of the `GEO` distances.
+use std::fmt;
+use std::str::FromStr;
+
+use super::tsplib::TspLibData;
+use super::validate::tsplib_distance;
+use super::Solution;
+
+const KM_IN_MILE: f64 = 1.609_344;
+const KM_WEIGHT_TYPE: &str = "GEO";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Units {
+    Kilometers,
+    Miles,
+    Meters,
+}
+
+impl Units {
+    pub fn variants() -> Vec<&'static str> {
+        vec!["km", "mi", "m"]
+    }
+
+    /// converts the distance in kilometers into the units
+    pub fn from_km(&self, km: f64) -> f64 {
+        match self {
+            Units::Kilometers => km,
+            Units::Miles => km / KM_IN_MILE,
+            Units::Meters => km * 1_000.0,
+        }
+    }
+
+    /// the length of the tour in the units, None if the distances of the problem are not
+    /// kilometers or the tour has a city the problem doesn't have
+    pub fn length(&self, data: &TspLibData, tour: &Solution) -> Option<Length> {
+        let km = length_km(data, tour)?;
+
+        Some(Length {
+            value: self.from_km(km),
+            units: *self,
+        })
+    }
+
+    /// true if the distances of the problem are in kilometers
+    pub fn applies_to(data: &TspLibData) -> bool {
+        data.edge_weight_type == KM_WEIGHT_TYPE
+    }
+}
+
+/// the tour length in the units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Length {
+    pub value: f64,
+    pub units: Units,
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.5} {}", self.value, self.units)
+    }
+}
+
+// the TSPLIB distances of the tour edges, the open tour has no edge back to the first city
+fn length_km(data: &TspLibData, tour: &Solution) -> Option<f64> {
+    if !Units::applies_to(data) {
+        return None;
+    }
+
+    let route = tour.route();
+    let n_edges = if tour.is_open_tour() {
+        route.len().saturating_sub(1)
+    } else {
+        route.len()
+    };
+
+    let mut km = 0;
+    for i in 0..n_edges {
+        let a = tour.get_by_city_id(route[i])?;
+        let b = tour.get_by_city_id(route[(i + 1) % route.len()])?;
+        km += tsplib_distance(&data.edge_weight_type, a, b)?;
+    }
+
+    Some(km as f64)
+}
+
+impl FromStr for Units {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "km" => Ok(Units::Kilometers),
+            "mi" => Ok(Units::Miles),
+            "m" => Ok(Units::Meters),
+            _ => Err("unknown units"),
+        }
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Units::Kilometers => "km",
+            Units::Miles => "mi",
+            Units::Meters => "m",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::tsplib;
+    use std::io::Cursor;
+
+    // the first cities of ulysses16, the GEO distances between them are 509, 126 and 501 km
+    const TRIANGLE: &str = "NAME: ulysses3
+TYPE: TSP
+DIMENSION: 3
+EDGE_WEIGHT_TYPE: GEO
+NODE_COORD_SECTION
+1 38.24 20.42
+2 39.57 26.15
+3 40.56 25.32
+EOF
+";
+
+    #[test]
+    fn test_from_km_converts_distance() {
+        assert_eq!(12.5, Units::Kilometers.from_km(12.5));
+        assert_eq!(12_500.0, Units::Meters.from_km(12.5));
+        assert!((Units::Miles.from_km(16.093_44) - 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_length_is_measured_by_geo_distances() {
+        let data = tsplib::read_from(Cursor::new(TRIANGLE)).unwrap();
+        let tour = Solution::new(&[1, 2, 3], data.cities());
+        let closed = Units::Kilometers.length(&data, &tour).unwrap();
+        let open = Units::Meters
+            .length(
+                &data,
+                &Solution::new(&[1, 2, 3], data.cities()).with_open_tour(true),
+            )
+            .unwrap();
+
+        // the solvers measure the degrees, not the kilometers
+        assert!(tour.total < 20.0);
+        assert_eq!(509.0 + 126.0 + 501.0, closed.value);
+        assert_eq!("1136.00000 km", closed.to_string());
+        assert_eq!((509.0 + 126.0) * 1_000.0, open.value);
+
+        let mut euclidean = data.clone();
+        euclidean.edge_weight_type = "EUC_2D".to_string();
+        assert_eq!(None, Units::Miles.length(&euclidean, &tour));
+    }
+
+    #[test]
+    fn test_units_from_str_and_display() {
+        for symbol in Units::variants().iter() {
+            let units = Units::from_str(symbol).unwrap();
+            assert_eq!(symbol.to_string(), units.to_string());
+        }
+
+        assert!(Units::from_str("ft").is_err());
+    }
+}
@@ -0,0 +1,309 @@
+/// Validation of tour files
+///
+/// It checks that the tour visits every city of the problem exactly once and recomputes its
+/// length, e.g for the tours of other solvers or the student submissions. The problems reported
+/// point to the lines of the tour file.
+///
+/// Besides the euclidean length, which the solvers optimize, it reports the length under the
+/// TSPLIB edge weight type of the problem, where every edge is rounded to an integer; the known
+/// optima of the TSPLIB problems are given in it.
+use std::collections::HashMap;
+use std::fmt;
+
+use super::kdtree::KDPoint;
+use super::tour_length;
+use super::tsplib::{TourEntry, TspLibData};
+
+// the constants of the TSPLIB GEO distance, the published distances use the rounded PI
+#[allow(clippy::approx_constant)]
+const GEO_PI: f64 = 3.141592;
+const EARTH_RADIUS: f64 = 6378.388;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    UnknownCity {
+        line: usize,
+        city_id: usize,
+    },
+    DuplicateCity {
+        line: usize,
+        city_id: usize,
+        first_line: usize,
+    },
+    MissingCity(usize),
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::UnknownCity { line, city_id } => {
+                write!(f, "line.{}: unknown city {}", line, city_id)
+            }
+            Issue::DuplicateCity {
+                line,
+                city_id,
+                first_line,
+            } => write!(
+                f,
+                "line.{}: city {} is visited again, first on line.{}",
+                line, city_id, first_line
+            ),
+            Issue::MissingCity(city_id) => write!(f, "city {} is not visited", city_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationReport {
+    pub n_cities: usize,
+    pub n_visits: usize,
+    pub issues: Vec<Issue>,
+    pub length: Option<f64>, // euclidean length of the valid tour
+    pub edge_weight_type: String,
+    pub tsplib_length: Option<u64>, // None for the invalid tour or unsupported edge weight type
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// checks the tour entries against the cities of the problem
+pub fn validate(data: &TspLibData, tour: &[TourEntry]) -> ValidationReport {
+    let cities = data.cities();
+    let known_ids: HashMap<usize, &KDPoint> = cities.iter().map(|c| (c.id, c)).collect();
+
+    let mut issues = vec![];
+    let mut visited_on: HashMap<usize, usize> = HashMap::new();
+    for entry in tour.iter() {
+        if !known_ids.contains_key(&entry.city_id) {
+            issues.push(Issue::UnknownCity {
+                line: entry.line,
+                city_id: entry.city_id,
+            });
+        } else if let Some(first_line) = visited_on.get(&entry.city_id) {
+            issues.push(Issue::DuplicateCity {
+                line: entry.line,
+                city_id: entry.city_id,
+                first_line: *first_line,
+            });
+        } else {
+            visited_on.insert(entry.city_id, entry.line);
+        }
+    }
+
+    for city in cities.iter() {
+        if !visited_on.contains_key(&city.id) {
+            issues.push(Issue::MissingCity(city.id));
+        }
+    }
+
+    let mut report = ValidationReport {
+        n_cities: cities.len(),
+        n_visits: tour.len(),
+        issues,
+        length: None,
+        edge_weight_type: data.edge_weight_type.clone(),
+        tsplib_length: None,
+    };
+
+    if report.is_valid() {
+        let route: Vec<usize> = tour.iter().map(|entry| entry.city_id).collect();
+
+        report.length = Some(tour_length(cities, &route));
+        report.tsplib_length = tsplib_length(data, &route);
+    }
+
+    report
+}
+
+/// the length of the tour under the TSPLIB edge weight type of the problem,
+/// returns None for the unknown cities or the unsupported edge weight type
+pub fn tsplib_length(data: &TspLibData, route: &[usize]) -> Option<u64> {
+    let known_ids: HashMap<usize, &KDPoint> = data.cities().iter().map(|c| (c.id, c)).collect();
+    let stops: Option<Vec<&KDPoint>> = route.iter().map(|id| known_ids.get(id).copied()).collect();
+    let stops = stops?;
+
+    (0..stops.len())
+        .map(|i| {
+            tsplib_distance(
+                &data.edge_weight_type,
+                stops[i],
+                stops[(i + 1) % stops.len()],
+            )
+        })
+        .sum()
+}
+
+/// the distance of the cities under the TSPLIB edge weight type,
+/// returns None for the types which are not computed from the coordinates
+pub fn tsplib_distance(edge_weight_type: &str, a: &KDPoint, b: &KDPoint) -> Option<u64> {
+    let dx = a.x() - b.x();
+    let dy = a.y() - b.y();
+
+    let dist = match edge_weight_type {
+        "EUC_2D" => nint((dx * dx + dy * dy).sqrt()),
+        "CEIL_2D" => (dx * dx + dy * dy).sqrt().ceil(),
+        "MAN_2D" => nint(dx.abs() + dy.abs()),
+        "MAX_2D" => nint(dx.abs()).max(nint(dy.abs())),
+        "ATT" => {
+            let r = ((dx * dx + dy * dy) / 10.0).sqrt();
+            let t = nint(r);
+            if t < r {
+                t + 1.0
+            } else {
+                t
+            }
+        }
+        "GEO" => geo_distance(a, b),
+        _ => return None,
+    };
+
+    Some(dist as u64)
+}
+
+fn nint(x: f64) -> f64 {
+    (x + 0.5).floor()
+}
+
+// the coordinates are latitude and longitude in the DDD.MM format
+fn geo_distance(a: &KDPoint, b: &KDPoint) -> f64 {
+    let radians = |coord: f64| {
+        let degrees = coord.trunc();
+        let minutes = coord - degrees;
+
+        GEO_PI * (degrees + 5.0 * minutes / 3.0) / 180.0
+    };
+
+    let (lat_a, lon_a) = (radians(a.x()), radians(a.y()));
+    let (lat_b, lon_b) = (radians(b.x()), radians(b.y()));
+    let q1 = (lon_a - lon_b).cos();
+    let q2 = (lat_a - lat_b).cos();
+    let q3 = (lat_a + lat_b).cos();
+
+    (EARTH_RADIUS * (0.5 * ((1.0 + q1) * q2 - (1.0 - q1) * q3)).acos() + 1.0).trunc()
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.is_valid() {
+            writeln!(
+                f,
+                "invalid tour: {} visits for {} cities, {} problems",
+                self.n_visits,
+                self.n_cities,
+                self.issues.len()
+            )?;
+            for issue in self.issues.iter() {
+                writeln!(f, "  {}", issue)?;
+            }
+
+            return Ok(());
+        }
+
+        writeln!(f, "valid tour of {} cities", self.n_cities)?;
+        if let Some(length) = self.length {
+            writeln!(f, "length: {:.2}", length)?;
+        }
+        if let Some(tsplib_length) = self.tsplib_length {
+            writeln!(
+                f,
+                "TSPLIB length ({}): {}",
+                self.edge_weight_type, tsplib_length
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tsp::kdtree;
+
+    fn square_data() -> TspLibData {
+        let cities = kdtree::build_points(&[
+            vec![0.0, 0.0],
+            vec![0.0, 1.4],
+            vec![1.4, 1.4],
+            vec![1.4, 0.0],
+        ]);
+
+        TspLibData::new("square".to_string(), String::new(), cities)
+    }
+
+    fn entries(ids: &[usize]) -> Vec<TourEntry> {
+        ids.iter()
+            .enumerate()
+            .map(|(i, city_id)| TourEntry {
+                line: i + 5,
+                city_id: *city_id,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_valid_tour() {
+        let report = validate(&square_data(), &entries(&[0, 1, 2, 3]));
+
+        assert!(report.is_valid());
+        assert!((report.length.unwrap() - 5.6).abs() < 0.001);
+        // every edge of 1.4 is rounded to 1
+        assert_eq!(Some(4), report.tsplib_length);
+        assert_eq!(
+            "valid tour of 4 cities\nlength: 5.60\nTSPLIB length (EUC_2D): 4\n",
+            report.to_string()
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_lines_of_problems() {
+        let report = validate(&square_data(), &entries(&[0, 1, 7, 1]));
+
+        assert!(!report.is_valid());
+        assert_eq!(None, report.length);
+        assert_eq!(
+            vec![
+                Issue::UnknownCity {
+                    line: 7,
+                    city_id: 7
+                },
+                Issue::DuplicateCity {
+                    line: 8,
+                    city_id: 1,
+                    first_line: 6
+                },
+                Issue::MissingCity(2),
+                Issue::MissingCity(3),
+            ],
+            report.issues
+        );
+        assert!(report
+            .to_string()
+            .contains("line.8: city 1 is visited again, first on line.6"));
+    }
+
+    #[test]
+    fn test_tsplib_distance() {
+        let a = KDPoint::new(&[0.0, 0.0]);
+        let b = KDPoint::new(&[3.0, 4.2]);
+
+        assert_eq!(Some(5), tsplib_distance("EUC_2D", &a, &b));
+        assert_eq!(Some(6), tsplib_distance("CEIL_2D", &a, &b));
+        assert_eq!(Some(7), tsplib_distance("MAN_2D", &a, &b));
+        assert_eq!(Some(4), tsplib_distance("MAX_2D", &a, &b));
+        assert_eq!(Some(2), tsplib_distance("ATT", &a, &b));
+        assert_eq!(None, tsplib_distance("EXPLICIT", &a, &b));
+    }
+
+    #[test]
+    fn test_geo_distance() {
+        // the first two cities of TSPLIB ulysses16, 509 km apart
+        let a = KDPoint::new(&[38.24, 20.42]);
+        let b = KDPoint::new(&[39.57, 26.15]);
+
+        assert_eq!(Some(509), tsplib_distance("GEO", &a, &b));
+    }
+}
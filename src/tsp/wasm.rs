@@ -0,0 +1,195 @@
+/// WebAssembly bindings of the solvers
+///
+/// The `wasm` feature builds the library for the browser, `wasm-bindgen` generates its
+/// JavaScript module:
+///
+/// ```text
+/// cargo build --lib --release --target wasm32-unknown-unknown --no-default-features --features wasm
+/// wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/teeline.wasm
+/// ```
+///
+/// `solve_json` takes the instance and the options as JSON and returns the tour as JSON, the
+/// instance and the tour are the same as the ones of `POST /solve` of `tsp::serve`:
+///
+/// ```text
+/// import init, { solve_json } from "./pkg/teeline.js";
+///
+/// await init();
+/// const tour = JSON.parse(solve_json(
+///     '{"name": "square", "cities": [[0, 0], [0, 1], [1, 1], [1, 0]]}',
+///     '{"solver": "nn+2opt", "max_seconds": 1, "seed": 42}',
+///     (event) => console.log(JSON.parse(event))
+/// ));
+/// ```
+///
+/// The options take the keys of the configuration file and the `solver`, `nn+2opt` by default.
+/// The progress callback is optional, it gets the events of `--progress jsonl`. The browser has
+/// no threads, so the solver runs on the calling thread until it's done; a Web Worker keeps the
+/// page responsive meanwhile.
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use toml::value::Value;
+use wasm_bindgen::prelude::*;
+
+use super::clock::Instant;
+use super::progress::{self, JsonlSink, PublisherFn};
+use super::{config, json, registry, serve, Error, SolverOptions};
+
+const DEFAULT_SOLVER: &str = "nn+2opt";
+
+thread_local! {
+    // the JavaScript function can't be shared between the threads, the solver runs on this one
+    static PROGRESS_CALLBACK: RefCell<Option<js_sys::Function>> = RefCell::new(None);
+}
+
+/// solves the instance and returns the tour as JSON, the errors are thrown as the exceptions
+/// with the message of the error
+#[wasm_bindgen]
+pub fn solve_json(
+    instance_json: &str,
+    options_json: &str,
+    progress: Option<js_sys::Function>,
+) -> Result<String, JsValue> {
+    let has_callback = progress.is_some();
+    PROGRESS_CALLBACK.with(|callback| *callback.borrow_mut() = progress);
+
+    let res = solve_request(instance_json, options_json, |solver| {
+        if has_callback {
+            progress::publisher_for(Arc::new(JsonlSink::with_writer(
+                solver,
+                CallbackWriter::default(),
+            )))
+        } else {
+            progress::silent_publisher()
+        }
+    });
+
+    PROGRESS_CALLBACK.with(|callback| *callback.borrow_mut() = None);
+    res.map_err(|err| JsValue::from_str(&err.to_string()))
+}
+
+/// solves the instance of the JSON document with the solver and the options of the other one,
+/// `publisher_for` makes the progress publisher for the name of the solver
+pub fn solve_request<F>(
+    instance_json: &str,
+    options_json: &str,
+    publisher_for: F,
+) -> Result<String, Error>
+where
+    F: FnOnce(&str) -> PublisherFn,
+{
+    let instance = serve::read_instance(&json::parse(instance_json)?)?;
+    let (solver_name, options) = read_options(options_json)?;
+    let solver = registry::resolve(&solver_name)?.metadata().name;
+
+    let started = Instant::now();
+    let tour =
+        crate::solve_with_progress(&instance, &solver_name, &options, publisher_for(&solver))?;
+
+    Ok(serve::tour_json(
+        &instance.name,
+        &solver,
+        &tour,
+        started.elapsed(),
+    ))
+}
+
+// the solver and the options of the configuration keys
+fn read_options(options_json: &str) -> Result<(String, SolverOptions), Error> {
+    let document = json::parse(options_json)?;
+    let table = document
+        .as_table()
+        .ok_or_else(|| invalid_options("the options must be an object"))?;
+
+    let mut solver = DEFAULT_SOLVER.to_string();
+    let mut options = SolverOptions::default();
+    options.show_progress = false;
+    for (key, value) in table.iter() {
+        match (key.as_str(), value) {
+            ("solver", Value::String(name)) => solver = name.to_lowercase(),
+            ("solver", _) => return Err(invalid_options("`solver` must be a string")),
+            _ => config::set_option(&mut options, key, value)?,
+        }
+    }
+
+    Ok((solver, options))
+}
+
+fn invalid_options(message: &str) -> Error {
+    Error::InvalidInput(format!("wasm: {}", message))
+}
+
+// passes the JSON lines of the sink to the progress callback one by one
+#[derive(Default)]
+struct CallbackWriter {
+    line: Vec<u8>,
+}
+
+impl Write for CallbackWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf.iter() {
+            if *byte != b'\n' {
+                self.line.push(*byte);
+                continue;
+            }
+
+            let line = JsValue::from_str(&String::from_utf8_lossy(&self.line));
+            self.line.clear();
+            PROGRESS_CALLBACK.with(|callback| {
+                if let Some(callback) = callback.borrow().as_ref() {
+                    // the exceptions of the callback don't stop the solver
+                    let _ = callback.call1(&JsValue::NULL, &line);
+                }
+            });
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    const SQUARE: &str = r#"{"name": "square", "cities": [[0, 0], [0, 1], [1, 1], [1, 0]]}"#;
+
+    #[test]
+    fn test_solve_request_returns_tour() {
+        let solvers = Arc::new(Mutex::new(vec![]));
+        let seen = solvers.clone();
+
+        let tour = solve_request(SQUARE, r#"{"solver": "2opt", "seed": 42}"#, |solver| {
+            seen.lock().unwrap().push(solver.to_string());
+            progress::silent_publisher()
+        })
+        .unwrap();
+
+        assert!(tour.starts_with(r#"{"name": "square", "solver": "two_opt", "total": 4,"#));
+        assert_eq!(vec!["two_opt".to_string()], *solvers.lock().unwrap());
+    }
+
+    #[test]
+    fn test_solve_request_uses_default_solver() {
+        let tour = solve_request(SQUARE, "{}", |_| progress::silent_publisher()).unwrap();
+
+        assert!(tour.contains(r#""solver": "nearest_neighbor+two_opt""#));
+    }
+
+    #[test]
+    fn test_solve_request_rejects_invalid_options() {
+        let silent = |_: &str| progress::silent_publisher();
+
+        assert!(solve_request(SQUARE, "[]", silent).is_err());
+        assert!(solve_request(SQUARE, r#"{"solver": 2}"#, silent).is_err());
+        assert!(solve_request(SQUARE, r#"{"solver": "nope"}"#, silent).is_err());
+        assert!(solve_request(SQUARE, r#"{"no_such_option": 1}"#, silent).is_err());
+        assert!(solve_request("{}", "{}", silent).is_err());
+    }
+}
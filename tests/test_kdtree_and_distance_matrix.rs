@@ -1,7 +1,7 @@
 extern crate teeline;
 
 use teeline::tsp::kdtree::KDPoint;
-use teeline::tsp::{distance_matrix, kdtree};
+use teeline::tsp::{distance_matrix, kdtree, selftest};
 
 #[test]
 fn test_kdtree_vs_distance_matrix() {
@@ -13,12 +13,19 @@ fn test_kdtree_vs_distance_matrix() {
         vec![1.0, 0.0],
     ]);
 
-    let kd = kdtree::from_cities(&cities);
-    let dm = distance_matrix::from_cities(&cities);
+    let kd = kdtree::from_cities(&cities).unwrap();
+    let dm = distance_matrix::from_cities(&cities).unwrap();
 
     let pt1 = KDPoint::new(&[0.0, 0.0]);
-    let kd_res1 = kd.nearest(&pt1, 1);
-    let dm_res1 = dm.nearest(&pt1, 1);
+    let kd_res1 = kd.nearest(&pt1, 1).unwrap();
+    let dm_res1 = dm.nearest(&pt1, 1).unwrap();
 
     assert_eq!(kd_res1.closest_distance(), dm_res1.closest_distance());
 }
+
+#[test]
+fn test_selftest_on_random_instances() {
+    let report = selftest::run(50, 2020);
+
+    assert!(report.is_ok(), "{}", report);
+}
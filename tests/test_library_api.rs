@@ -0,0 +1,211 @@
+extern crate teeline;
+
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+
+use teeline::tsp::progress::{self, ProgressSink};
+use teeline::tsp::route::Route;
+use teeline::tsp::simulated_annealing::SaOptions;
+use teeline::tsp::tsplib;
+use teeline::{AlgorithmOptions, SolverOptions};
+
+const SQUARE: &str = "NAME : square
+TYPE : TSP
+DIMENSION : 5
+EDGE_WEIGHT_TYPE : EUC_2D
+NODE_COORD_SECTION
+1 0 0
+2 0 1
+3 1 0
+4 1 1
+5 0 0.5
+EOF
+";
+
+// the start city 6 is in the middle, the open tour is shorter from one of the corners
+const SCATTERED: &str = "NAME : scattered
+TYPE : TSP
+DIMENSION : 10
+EDGE_WEIGHT_TYPE : EUC_2D
+NODE_COORD_SECTION
+1 0 0
+2 9 1
+3 2 7
+4 8 8
+5 1 3
+6 5 4
+7 7 2
+8 3 9
+9 6 6
+10 0 9
+EOF
+";
+
+#[derive(Default)]
+struct Improvements(Mutex<Vec<f32>>);
+
+impl ProgressSink for Improvements {
+    fn on_improvement(&self, _route: &Route, distance: f32) {
+        self.0.lock().unwrap().push(distance);
+    }
+}
+
+#[test]
+fn test_solve_with_pipeline() {
+    let problem = tsplib::read_from(Cursor::new(SQUARE)).unwrap();
+
+    let tour = teeline::solve(&problem, "nn+2opt", &SolverOptions::default()).unwrap();
+
+    assert_eq!(5, tour.len());
+    assert!((tour.total - 4.0).abs() < 0.001, "{}", tour.total);
+}
+
+#[test]
+fn test_solve_with_progress() {
+    let problem = tsplib::read_from(Cursor::new(SQUARE)).unwrap();
+    let sink = Arc::new(Improvements::default());
+
+    let tour = teeline::solve_with_progress(
+        &problem,
+        "2opt",
+        &SolverOptions::default(),
+        progress::publisher_for(sink.clone()),
+    )
+    .unwrap();
+
+    let improvements = sink.0.lock().unwrap();
+    assert!(!improvements.is_empty());
+    assert_eq!(tour.total, *improvements.last().unwrap());
+}
+
+#[test]
+fn test_solve_with_unknown_solver_fails() {
+    let problem = tsplib::read_from(Cursor::new(SQUARE)).unwrap();
+
+    let err = teeline::solve(&problem, "or_opt", &SolverOptions::default())
+        .err()
+        .unwrap();
+
+    assert!(err.to_string().contains("unknown solver `or_opt`"));
+}
+
+#[test]
+fn test_solve_with_typed_options() {
+    let problem = tsplib::read_from(Cursor::new(SQUARE)).unwrap();
+    let annealing = AlgorithmOptions::SimulatedAnnealing(SaOptions {
+        cooling_rate: 0.01,
+        ..SaOptions::default()
+    });
+
+    let tour = teeline::solve_with(&problem, &annealing, &SolverOptions::default()).unwrap();
+    assert_eq!(5, tour.len());
+
+    let invalid = AlgorithmOptions::SimulatedAnnealing(SaOptions {
+        cooling_rate: 0.0,
+        ..SaOptions::default()
+    });
+    let err = teeline::solve_with(&problem, &invalid, &SolverOptions::default())
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("cooling_rate"));
+}
+
+#[test]
+fn test_solve_with_progress_rejects_invalid_options() {
+    let problem = tsplib::read_from(Cursor::new(SQUARE)).unwrap();
+    let mut options = SolverOptions::default();
+    options.cooling_rate = 0.0;
+
+    let invalid = AlgorithmOptions::SimulatedAnnealing(SaOptions::from(&options));
+    assert!(teeline::solve_with(&problem, &invalid, &options).is_err());
+
+    // the pipeline checks the options of every stage
+    for solver in ["sa", "nn+sa"] {
+        let err =
+            teeline::solve_with_progress(&problem, solver, &options, progress::silent_publisher())
+                .err()
+                .unwrap();
+        assert!(err.to_string().contains("cooling_rate"), "{}", err);
+    }
+}
+
+#[test]
+fn test_solve_keeps_fixed_edges_of_instance() {
+    let text = SQUARE.replace("EOF\n", "FIXED_EDGES_SECTION\n1 4\n-1\nEOF\n");
+    let problem = tsplib::read_from(Cursor::new(text)).unwrap();
+
+    let tour = teeline::solve(&problem, "nn+2opt", &SolverOptions::default()).unwrap();
+    let route = tour.route();
+    let at = route.iter().position(|id| *id == 1).unwrap();
+    let neighbors = [route[(at + 1) % 5], route[(at + 4) % 5]];
+    assert!(neighbors.contains(&4), "{:?}", route);
+
+    let err = teeline::solve(&problem, "sa", &SolverOptions::default())
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("can't keep the fixed edges"));
+}
+
+#[test]
+fn test_solve_open_tour_from_start_city() {
+    let problem = tsplib::read_from(Cursor::new(SCATTERED)).unwrap();
+    let mut options = SolverOptions::default();
+    options.open_tour = true;
+    options.start_city = Some(6);
+
+    let mut solvers: Vec<String> = teeline::tsp::registry::catalog()
+        .into_iter()
+        .filter(|info| info.uses_option("start_city") && info.uses_option("open_tour"))
+        .map(|info| info.name)
+        .collect();
+    solvers.push("nn+2opt".to_string());
+
+    for solver in solvers.iter() {
+        let tour = teeline::solve(&problem, solver, &options).unwrap();
+
+        assert!(tour.is_open_tour(), "{}", solver);
+        assert_eq!(6, tour.route()[0], "{}: {:?}", solver, tour.route());
+    }
+}
+
+// the edges of the tour 1 3 5 2 4 cost 1 and the other ones 2, the order of the ids costs 10
+#[cfg(feature = "xml")]
+fn pentagram_xml() -> String {
+    let cheap = [(0, 2), (2, 4), (4, 1), (1, 3), (3, 0)];
+    let vertices: Vec<String> = (0..5)
+        .map(|from| {
+            let edges: Vec<String> = (0..5)
+                .filter(|to| *to != from)
+                .map(|to| {
+                    let is_cheap = cheap.contains(&(from, to)) || cheap.contains(&(to, from));
+                    let cost = if is_cheap { 1.0 } else { 2.0 };
+                    format!("<edge cost=\"{:e}\">{}</edge>", cost, to)
+                })
+                .collect();
+            format!("<vertex>{}</vertex>", edges.join(""))
+        })
+        .collect();
+
+    format!(
+        "<travellingSalesmanProblemInstance><name>pentagram</name><graph>{}</graph></travellingSalesmanProblemInstance>",
+        vertices.join("")
+    )
+}
+
+#[cfg(feature = "xml")]
+#[test]
+fn test_solve_xml_instance() {
+    let problem = teeline::tsp::tsplib_xml::read_from_str(&pentagram_xml()).unwrap();
+
+    for solver in ["nn+2opt", "bellman_karp"].iter() {
+        let tour = teeline::solve(&problem, solver, &SolverOptions::default()).unwrap();
+
+        assert_eq!(5, tour.len(), "{}", solver);
+        assert!(
+            (tour.total - 5.0).abs() < 0.001,
+            "{}: {}",
+            solver,
+            tour.total
+        );
+    }
+}